@@ -37,14 +37,14 @@ struct LifecycleTestConfiguration {
     sector_class: sector_builder_ffi_FFISectorClass,
     third_piece_bytes: usize,
     fourth_piece_bytes: usize,
-    max_num_staged_sectors: u8,
+    max_num_staged_sectors: u64,
     max_secs_to_seal_sector: u64,
 }
 
 #[derive(Debug, Clone, Copy)]
 struct KillRestartTestConfiguration {
     sector_class: sector_builder_ffi_FFISectorClass,
-    max_num_staged_sectors: u8,
+    max_num_staged_sectors: u64,
     max_secs_to_seal_sector: u64,
 }
 
@@ -72,6 +72,8 @@ unsafe fn kill_restart_recovery(sector_size: u64) -> Result<(), failure::Error>
         sector_class: sector_builder_ffi_FFISectorClass {
             sector_size,
             porep_proof_partitions: 2,
+            post_proof_partitions: 0,
+            post_challenge_count: 0,
         },
         max_num_staged_sectors: 2,
         max_secs_to_seal_sector: 60 * 60, // TODO: something more rigorous
@@ -88,7 +90,9 @@ unsafe fn kill_restart_recovery(sector_size: u64) -> Result<(), failure::Error>
     let staging_dir_a_c = staging_dir_a.path().clone();
     let sealed_dir_a_c = sealed_dir_a.path().clone();
 
-    let prover_id = [1u8; 31];
+    // a 31-byte prover id, extended with a zero byte
+    let mut prover_id = [1u8; 32];
+    prover_id[31] = 0;
 
     // use an OS pipe so that we can communicate between processes
     let (mut done_tx, mut done_rx) = pipe_channel::channel();
@@ -118,7 +122,7 @@ unsafe fn kill_restart_recovery(sector_size: u64) -> Result<(), failure::Error>
 
             let mut ctx: Deallocator = Default::default();
 
-            let ptr = init_sector_builder(
+            let handle = init_sector_builder(
                 &mut ctx,
                 &metadata_dir_a_c,
                 &staging_dir_a_c,
@@ -135,13 +139,13 @@ unsafe fn kill_restart_recovery(sector_size: u64) -> Result<(), failure::Error>
                 let MakePiece { file, bytes, key } = make_piece(max_user_bytes as usize);
                 assert_eq!(
                     501,
-                    add_piece(&mut ctx, ptr, &key, file.as_file(), bytes.len(), 5000000)
+                    add_piece(&mut ctx, handle, &key, file.as_file(), bytes.len(), 5000000)
                 );
             }
 
             // block until sector sealing begins
             poll_for_sector_sealing_status(
-                ptr,
+                handle,
                 501,
                 sector_builder_ffi_FFISealStatus_Sealing,
                 cfg.max_secs_to_seal_sector * 2,
@@ -161,7 +165,7 @@ unsafe fn kill_restart_recovery(sector_size: u64) -> Result<(), failure::Error>
     }
 
     // initialize a sector builder - sealing will resume immediately
-    let ptr = init_sector_builder(
+    let handle = init_sector_builder(
         &mut Default::default(),
         &metadata_dir_a,
         &staging_dir_a,
@@ -174,7 +178,7 @@ unsafe fn kill_restart_recovery(sector_size: u64) -> Result<(), failure::Error>
 
     // block until the sector has sealed
     poll_for_sector_sealing_status(
-        ptr,
+        handle,
         501,
         sector_builder_ffi_FFISealStatus_Sealed,
         cfg.max_secs_to_seal_sector * 2,
@@ -201,6 +205,8 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
         sector_class: sector_builder_ffi_FFISectorClass {
             sector_size,
             porep_proof_partitions: 2,
+            post_proof_partitions: 0,
+            post_challenge_count: 0,
         },
         first_piece_bytes: ((400.0 / 1024.0) * (sector_size as f64)) as usize,
         second_piece_bytes: ((200.0 / 1024.0) * (sector_size as f64)) as usize,
@@ -219,11 +225,13 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
     let sealed_dir_a = tempfile::tempdir()?;
     let sealed_dir_b = tempfile::tempdir()?;
 
-    let prover_id = [1u8; 31];
+    // a 31-byte prover id, extended with a zero byte
+    let mut prover_id = [1u8; 32];
+    prover_id[31] = 0;
 
     let mut ctx: Deallocator = Default::default();
 
-    let a_ptr = init_sector_builder(
+    let a_handle = init_sector_builder(
         &mut ctx,
         &metadata_dir_a,
         &staging_dir_a,
@@ -238,8 +246,8 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
 
     // verify that we have neither sealed nor staged sectors yet
     {
-        let sealed_sectors = get_sealed_sectors(&mut ctx, a_ptr, false);
-        let staged_sectors = get_staged_sectors(&mut ctx, a_ptr);
+        let sealed_sectors = get_sealed_sectors(&mut ctx, a_handle, false);
+        let staged_sectors = get_staged_sectors(&mut ctx, a_handle);
         assert_eq!(0, sealed_sectors.len());
         assert_eq!(0, staged_sectors.len());
     }
@@ -249,7 +257,7 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
         let MakePiece { file, bytes, key } = make_piece(cfg.first_piece_bytes);
         assert_eq!(
             124,
-            add_piece(&mut ctx, a_ptr, &key, file.as_file(), bytes.len(), 5000000)
+            add_piece(&mut ctx, a_handle, &key, file.as_file(), bytes.len(), 5000000)
         );
     }
 
@@ -258,7 +266,7 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
         let MakePiece { file, bytes, key } = make_piece(cfg.second_piece_bytes);
         assert_eq!(
             124,
-            add_piece(&mut ctx, a_ptr, &key, file.as_file(), bytes.len(), 5000000)
+            add_piece(&mut ctx, a_handle, &key, file.as_file(), bytes.len(), 5000000)
         );
     }
 
@@ -267,14 +275,14 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
         let MakePiece { file, bytes, key } = make_piece(cfg.third_piece_bytes);
         assert_eq!(
             125,
-            add_piece(&mut ctx, a_ptr, &key, file.as_file(), bytes.len(), 5000000)
+            add_piece(&mut ctx, a_handle, &key, file.as_file(), bytes.len(), 5000000)
         );
     }
 
     // get staged sector metadata and verify that we've now got two staged
     // sectors
     {
-        let staged_sectors = get_staged_sectors(&mut ctx, a_ptr);
+        let staged_sectors = get_staged_sectors(&mut ctx, a_handle);
         assert_eq!(2, staged_sectors.len());
     }
 
@@ -288,7 +296,7 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
         124,
         add_piece(
             &mut ctx,
-            a_ptr,
+            a_handle,
             &fourth_piece_key,
             fourth_piece_file.as_file(),
             fourth_piece_bytes.len(),
@@ -302,32 +310,32 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
         let MakePiece { file, bytes, key } = make_piece(max_user_bytes as usize);
         assert_eq!(
             126,
-            add_piece(&mut ctx, a_ptr, &key, file.as_file(), bytes.len(), 5000000)
+            add_piece(&mut ctx, a_handle, &key, file.as_file(), bytes.len(), 5000000)
         );
     }
 
     // block until both sectors have been sealed - note that we won't know which
     // of the two sectors will seal first
     poll_for_sector_sealing_status(
-        a_ptr,
+        a_handle,
         124,
         sector_builder_ffi_FFISealStatus_Sealed,
         cfg.max_secs_to_seal_sector * 2,
     );
 
     poll_for_sector_sealing_status(
-        a_ptr,
+        a_handle,
         126,
         sector_builder_ffi_FFISealStatus_Sealed,
         cfg.max_secs_to_seal_sector * 2,
     );
 
     // drop the first sector builder, relinquishing any locks on persistence
-    destroy_sector_builder(a_ptr);
+    destroy_sector_builder(a_handle);
 
     // migrate staged sectors, sealed sectors, and sector builder metadata to
     // new directory (overwrites destination directory)
-    let b_ptr = {
+    let b_handle = {
         let renames = vec![
             (metadata_dir_a.as_ref(), metadata_dir_b.as_ref()),
             (staging_dir_a.as_ref(), staging_dir_b.as_ref()),
@@ -352,12 +360,12 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
             cfg.max_num_staged_sectors,
         )
     };
-    defer!(sector_builder_ffi_destroy_sector_builder(b_ptr));
+    defer!(destroy_sector_builder(b_handle));
 
     // after sealing, read the bytes (triggering unseal) and compare with what
     // we've added to the sector
     {
-        let unsealed_bytes = read_piece_from_sealed_sector(&mut ctx, b_ptr, &fourth_piece_key);
+        let unsealed_bytes = read_piece_from_sealed_sector(&mut ctx, b_handle, &fourth_piece_key);
         assert_eq!(
             format!("{:x?}", fourth_piece_bytes),
             format!("{:x?}", unsealed_bytes)
@@ -366,7 +374,7 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
 
     // get sealed sector and verify the proof
     {
-        let sealed_sector = get_sealed_sector(&mut ctx, b_ptr, 124);
+        let sealed_sector = get_sealed_sector(&mut ctx, b_handle, 124);
 
         assert!(
             verify_seal(
@@ -392,7 +400,7 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
             .expect("failed to seek to the start");
 
         assert_eq!(
-            format!("{:x?}", get_sealed_piece(&mut ctx, b_ptr, 124, &fourth_piece_key).comm_p),
+            format!("{:x?}", get_sealed_piece(&mut ctx, b_handle, 124, &fourth_piece_key).comm_p),
             format!(
                 "{:x?}",
                 generate_piece_commitment(&mut ctx, fourth_piece_file.as_file_mut(), fourth_piece_bytes.len())
@@ -403,15 +411,15 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
 
     // get sealed sectors w/health checks
     {
-        assert_eq!(2, get_sealed_sectors(&mut ctx, b_ptr, true).len());
+        assert_eq!(2, get_sealed_sectors(&mut ctx, b_handle, true).len());
 
         assert_eq!(
-            get_sealed_sector(&mut ctx, b_ptr, 126).health,
+            get_sealed_sector(&mut ctx, b_handle, 126).health,
             sector_builder_ffi_FFISealedSectorHealth_Ok
         );
 
         let sealed_sector_path = sealed_dir_b.path().join(c_str_to_pbuf(
-            get_sealed_sector(&mut ctx, b_ptr, 126).sector_access,
+            get_sealed_sector(&mut ctx, b_handle, 126).sector_access,
         ));
 
         let content = std::fs::read(&sealed_sector_path).expect("failed to read sector data");
@@ -425,14 +433,14 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
 
         // invalid checksum
         assert_eq!(
-            get_sealed_sector(&mut ctx, b_ptr, 126).health,
+            get_sealed_sector(&mut ctx, b_handle, 126).health,
             sector_builder_ffi_FFISealedSectorHealth_ErrorInvalidChecksum
         );
     }
 
     // verify piece inclusion proofs
     {
-        let sealed_sector = get_sealed_sector(&mut ctx, b_ptr, 124);
+        let sealed_sector = get_sealed_sector(&mut ctx, b_handle, 124);
 
         for piece in slice::from_raw_parts(sealed_sector.pieces_ptr, sealed_sector.pieces_len) {
             assert!(
@@ -454,8 +462,8 @@ unsafe fn sector_builder_lifecycle(sector_size: u64) -> Result<(), failure::Erro
     // generate and then verify a proof-of-spacetime for the sealed sector
     {
         let cseed = [1u8; 32];
-        let p_set = ProvingSet::new(get_sector_info(&mut ctx, b_ptr));
-        let proof = generate_post(b_ptr, cseed, &p_set);
+        let p_set = ProvingSet::new(get_sector_info(&mut ctx, b_handle));
+        let proof = generate_post(b_handle, cseed, &p_set);
 
         assert!(
             verify_post(cfg.sector_class.sector_size, cseed, &p_set, &proof),
@@ -1,5 +1,4 @@
 use std::path::Path;
-use std::ptr;
 use std::slice;
 
 use ffi_toolkit::{c_str_to_rust_str, free_c_str, rust_str_to_c_str};
@@ -10,7 +9,7 @@ use crate::provingset::*;
 include!(concat!(env!("OUT_DIR"), "/libsector_builder_ffi.rs"));
 
 pub(crate) unsafe fn generate_post(
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     challenge_seed: [u8; 32],
     proving_set: &ProvingSet,
 ) -> Vec<u8> {
@@ -18,12 +17,20 @@ pub(crate) unsafe fn generate_post(
     let faulty_sector_ids = proving_set.faulty_sector_ids();
 
     let resp = sector_builder_ffi_generate_post(
-        ptr,
+        handle,
         flattened_comm_rs.as_ptr(),
         flattened_comm_rs.len(),
         &mut challenge_seed.clone(),
         faulty_sector_ids.as_ptr(),
         faulty_sector_ids.len(),
+        0,
+        false,
+        std::ptr::null(),
+        0,
+        sector_builder_ffi_FFIPoStConfig {
+            sector_size: 0,
+            challenge_count: 0,
+        },
     );
     defer!(sector_builder_ffi_destroy_generate_post_response(resp));
 
@@ -67,10 +74,10 @@ pub(crate) unsafe fn verify_post(
 
 pub(crate) unsafe fn get_sealed_sectors(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     with_health: bool,
 ) -> Vec<sector_builder_ffi_FFISealedSectorMetadata> {
-    let resp = sector_builder_ffi_get_sealed_sectors(ptr, with_health);
+    let resp = sector_builder_ffi_get_sealed_sectors(handle, with_health);
     defer!(ctx.destructors.push(Box::new(move || {
         sector_builder_ffi_destroy_get_sealed_sectors_response(resp);
     })));
@@ -84,9 +91,12 @@ pub(crate) unsafe fn get_sealed_sectors(
 
 pub(crate) unsafe fn get_staged_sectors(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
 ) -> Vec<sector_builder_ffi_FFIStagedSectorMetadata> {
-    let resp = sector_builder_ffi_get_staged_sectors(ptr);
+    let resp = sector_builder_ffi_get_staged_sectors(
+        handle,
+        sector_builder_ffi_FFIStagedSectorFilter_All,
+    );
     defer!(ctx.destructors.push(Box::new(move || {
         sector_builder_ffi_destroy_get_staged_sectors_response(resp);
     })));
@@ -101,7 +111,7 @@ pub(crate) unsafe fn get_staged_sectors(
 #[cfg(not(target_os = "windows"))]
 pub(crate) unsafe fn add_piece(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     piece_key: &str,
     piece_file: &std::fs::File,
     piece_len: usize,
@@ -114,7 +124,7 @@ pub(crate) unsafe fn add_piece(
     let c_piece_fd = piece_file.as_raw_fd() as libc::c_int;
 
     let resp = sector_builder_ffi_add_piece(
-        ptr,
+        handle,
         c_piece_key,
         c_piece_fd,
         piece_len as u64,
@@ -133,10 +143,10 @@ pub(crate) unsafe fn add_piece(
 
 pub(crate) unsafe fn get_seal_status(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     sector_id: u64,
 ) -> sector_builder_ffi_FFISealStatus {
-    let resp = sector_builder_ffi_get_seal_status(ptr, sector_id);
+    let resp = sector_builder_ffi_get_seal_status(handle, sector_id, false);
     defer!(ctx.destructors.push(Box::new(move || {
         sector_builder_ffi_destroy_get_seal_status_response(resp);
     })));
@@ -150,13 +160,13 @@ pub(crate) unsafe fn get_seal_status(
 
 pub(crate) unsafe fn read_piece_from_sealed_sector(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     piece_key: &str,
 ) -> Vec<u8> {
     let c_piece_key = rust_str_to_c_str(piece_key);
     defer!(free_c_str(c_piece_key));
 
-    let resp = sector_builder_ffi_read_piece_from_sealed_sector(ptr, c_piece_key);
+    let resp = sector_builder_ffi_read_piece_from_sealed_sector(handle, c_piece_key);
     defer!(ctx.destructors.push(Box::new(move || {
         sector_builder_ffi_destroy_read_piece_from_sealed_sector_response(resp);
     })));
@@ -193,7 +203,7 @@ pub(crate) unsafe fn verify_seal(
     comm_r: [u8; 32],
     comm_d: [u8; 32],
     comm_r_star: [u8; 32],
-    prover_id: [u8; 31],
+    prover_id: [u8; 32],
 ) -> bool {
     let resp = sector_builder_ffi_verify_seal(
         sector_size,
@@ -220,10 +230,19 @@ pub(crate) unsafe fn get_max_user_bytes_per_staged_sector(sector_size: u64) -> u
     sector_builder_ffi_get_max_user_bytes_per_staged_sector(sector_size)
 }
 
-pub(crate) unsafe fn destroy_sector_builder(mut p: *mut sector_builder_ffi_SectorBuilder) {
-    sector_builder_ffi_destroy_sector_builder(p);
-    p = ptr::null_mut();
-    assert!(p.is_null());
+pub(crate) unsafe fn destroy_sector_builder(handle: u64) {
+    let resp = sector_builder_ffi_destroy_sector_builder(handle);
+    defer!(sector_builder_ffi_destroy_destroy_sector_builder_response(resp));
+
+    if (*resp).status_code != 0 {
+        panic!("{}", c_str_to_rust_str((*resp).error_msg));
+    }
+
+    // the handle no longer refers to the destroyed builder
+    let resp = sector_builder_ffi_destroy_sector_builder(handle);
+    defer!(sector_builder_ffi_destroy_destroy_sector_builder_response(resp));
+
+    assert_ne!(0, (*resp).status_code);
 }
 
 pub(crate) unsafe fn verify_piece_inclusion_proof(
@@ -255,11 +274,11 @@ pub(crate) unsafe fn init_sector_builder<T: AsRef<Path>>(
     metadata_dir: T,
     staging_dir: T,
     sealed_dir: T,
-    prover_id: [u8; 31],
+    prover_id: [u8; 32],
     last_committed_sector_id: u64,
     sector_class: sector_builder_ffi_FFISectorClass,
-    max_num_staged_sectors: u8,
-) -> *mut sector_builder_ffi_SectorBuilder {
+    max_num_staged_sectors: u64,
+) -> u64 {
     let c_metadata_dir = rust_str_to_c_str(metadata_dir.as_ref().to_str().unwrap());
     let c_sealed_dir = rust_str_to_c_str(sealed_dir.as_ref().to_str().unwrap());
     let c_staging_dir = rust_str_to_c_str(staging_dir.as_ref().to_str().unwrap());
@@ -278,6 +297,17 @@ pub(crate) unsafe fn init_sector_builder<T: AsRef<Path>>(
         c_sealed_dir,
         c_staging_dir,
         max_num_staged_sectors,
+        std::ptr::null(),
+        0,
+        std::ptr::null(),
+        std::ptr::null(),
+        sector_builder_ffi_FFISchedulerConfig {
+            channel_capacity: 0,
+            reject_when_busy: false,
+        },
+        std::ptr::null(),
+        0,
+        sector_builder_ffi_FFIStaleSealPolicy_Resume,
     );
     defer!(ctx.destructors.push(Box::new(move || {
         sector_builder_ffi_destroy_init_sector_builder_response(resp);
@@ -1,4 +1,3 @@
-use std::sync::atomic::AtomicPtr;
 use std::sync::mpsc;
 use std::time::Duration;
 use std::{slice, thread};
@@ -11,10 +10,10 @@ use crate::provingset::*;
 
 pub(crate) unsafe fn get_sealed_sector(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     sector_id: u64,
 ) -> sector_builder_ffi_FFISealedSectorMetadata {
-    let sealed_sector = get_sealed_sectors(ctx, ptr, true)
+    let sealed_sector = get_sealed_sectors(ctx, handle, true)
         .into_iter()
         .find(|ss| ss.sector_id == sector_id)
         .expect("no sealed sector with id 124");
@@ -24,11 +23,11 @@ pub(crate) unsafe fn get_sealed_sector(
 
 pub(crate) unsafe fn get_sealed_piece(
     ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     sector_id: u64,
     piece_key: &str,
 ) -> sector_builder_ffi_FFIPieceMetadata {
-    let sealed_sector = get_sealed_sectors(ctx, ptr, true)
+    let sealed_sector = get_sealed_sectors(ctx, handle, true)
         .into_iter()
         .find(|ss| ss.sector_id == sector_id)
         .expect(&format!("no sealed sector with id={}", sector_id));
@@ -48,9 +47,9 @@ pub(crate) unsafe fn get_sealed_piece(
 
 pub(crate) unsafe fn get_sector_info(
     mut ctx: &mut Deallocator,
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
 ) -> Vec<PoStSectorInfo> {
-    get_sealed_sectors(&mut ctx, ptr, true)
+    get_sealed_sectors(&mut ctx, handle, true)
         .into_iter()
         .map(|ss| PoStSectorInfo {
             sector_id: ss.sector_id,
@@ -61,7 +60,7 @@ pub(crate) unsafe fn get_sector_info(
 }
 
 pub(crate) unsafe fn poll_for_sector_sealing_status(
-    ptr: *mut sector_builder_ffi_SectorBuilder,
+    handle: u64,
     sector_id: u64,
     target_status: sector_builder_ffi_FFISealStatus,
     max_wait_secs: u64,
@@ -69,18 +68,14 @@ pub(crate) unsafe fn poll_for_sector_sealing_status(
     let (result_tx, result_rx) = mpsc::channel();
     let (kill_tx, kill_rx) = mpsc::channel();
 
-    let atomic_ptr = AtomicPtr::new(ptr);
-
     let _join_handle = thread::spawn(move || {
-        let sector_builder = atomic_ptr.into_inner();
-
         loop {
             match kill_rx.try_recv() {
                 Ok(_) => return,
                 _ => (),
             };
 
-            if get_seal_status(&mut Default::default(), sector_builder, sector_id) == target_status
+            if get_seal_status(&mut Default::default(), handle, sector_id) == target_status
             {
                 let _ = result_tx.send(sector_id).unwrap();
             }
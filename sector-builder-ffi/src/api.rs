@@ -1,18 +1,25 @@
 use std::mem;
+use std::path::PathBuf;
 use std::ptr;
 use std::slice::from_raw_parts;
-use std::collections::HashMap;
+use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 use ffi_toolkit::rust_str_to_c_str;
-use ffi_toolkit::{c_str_to_rust_str, raw_ptr};
+use filecoin_proofs::pieces::get_piece_start_byte;
+use filecoin_proofs::PoStConfig;
+use ffi_toolkit::{c_str_to_pbuf, c_str_to_rust_str, raw_ptr};
 use libc;
 use once_cell::sync::OnceCell;
-use sector_builder::{GetSealedSectorResult, PieceMetadata, SealStatus, SecondsSinceEpoch, StagedSectorMetadata, UnpaddedBytesAmount, SealedSectorMetadata};
+use sector_builder::{err_invalid_sector_class, GetSealedSectorResult, PieceMetadata, SealStatus, SecondsSinceEpoch, StagedSectorMetadata, PaddedBytesAmount, UnpaddedBytesAmount, SealedSectorMetadata, SealedSectorLocation, SealTicket, SealFailure, SealScheduleOutcome, SealScheduleResult, TaskTimeouts, HealthCheckMode, AutoSealPolicy, PoStDeadline, PoStOptions, GeneratedPoSt, SealIsolation, WorkerAffinity, PieceDealInfo, SealTiming, SealDurationStats, SectorAccess, parse_cpu_list, prover_id_from_bytes, prover_id_to_bytes, HandoffBundle, LastShutdown, OverloadPolicy, SchedulerConfig, SectorAccessProto, SectorBuilderOptions, StagedDataKey, StagedSectorDir, StagedSpillOver, StagedWriteConfig, UnsealedRetention, DEFAULT_STAGED_WRITE_BUFFER_SIZE, CommandReplicator, DirReplicator, ReplicationStatus, SealedSectorReplicator};
 use storage_proofs::sector::SectorId;
 
+use crate::handles::HandleTable;
+use crate::operations::{OperationOutput, OperationTable, Poll};
 use crate::responses::{
-    self, err_code_and_msg, FCPResponseStatus, FFIPieceMetadata, FFISealStatus,
-    FFISealedSectorHealth,
+    self, err_code_and_msg, FCPResponseStatus, FFILastShutdown, FFIOperationStatus, FFIPieceLabel, FFIPieceMetadata, FFIReplicationStatus, FFISealFailure,
+    FFISealStatus, FFISealedSectorHealth,
 };
 use storage_proofs::rational_post::Challenge;
 
@@ -20,6 +27,73 @@ use storage_proofs::rational_post::Challenge;
 pub struct FFISectorClass {
     sector_size: u64,
     porep_proof_partitions: u8,
+
+    // PoSt parameterization - 0 selects the value implied by the sector size
+    post_proof_partitions: u8,
+    post_challenge_count: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FFISealTicket {
+    pub block_height: u64,
+    pub ticket_bytes: [u8; 32],
+}
+
+/// PoSt parameterization with which a single proof-of-spacetime is
+/// generated instead of the one implied by the builder's sector class. A
+/// zeroed value selects the builder's own.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FFIPoStConfig {
+    // 0 selects the builder's sector size
+    pub sector_size: u64,
+
+    // 0 selects the value implied by the sector size
+    pub challenge_count: u64,
+}
+
+/// How calls are queued for a SectorBuilder's scheduler. A zeroed value
+/// makes every call wait until the scheduler is free.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FFISchedulerConfig {
+    // the number of calls which may wait for the scheduler
+    pub channel_capacity: libc::size_t,
+
+    // if set, calls which don't fit into the channel fail with
+    // FCPSchedulerBusy instead of blocking
+    pub reject_when_busy: bool,
+}
+
+impl From<FFISchedulerConfig> for SchedulerConfig {
+    fn from(config: FFISchedulerConfig) -> Self {
+        SchedulerConfig {
+            channel_capacity: config.channel_capacity,
+            overload_policy: if config.reject_when_busy {
+                OverloadPolicy::Reject
+            } else {
+                OverloadPolicy::Block
+            },
+        }
+    }
+}
+
+/// A piece to be written into a staged sector by add_pieces_second, read
+/// from the provided file descriptor.
+#[repr(C)]
+pub struct FFIPieceToAdd {
+    pub piece_key: *const libc::c_char,
+    pub piece_fd_raw: libc::c_int,
+    pub piece_bytes_amount: u64,
+}
+
+/// Path from which the replica of a sector is read instead of its sealed
+/// sector-file.
+#[repr(C)]
+pub struct FFIReplicaPath {
+    pub sector_id: u64,
+    pub path: *const libc::c_char,
 }
 
 pub type SectorBuilder = sector_builder::SectorBuilder<FileDescriptorRef>;
@@ -47,24 +121,146 @@ impl std::io::Read for FileDescriptorRef {
 #[no_mangle]
 #[cfg(not(target_os = "windows"))]
 pub unsafe extern "C" fn sector_builder_ffi_add_piece(
-    ptr: *mut SectorBuilder,
+    handle: u64,
+    piece_key: *const libc::c_char,
+    piece_fd_raw: libc::c_int,
+    piece_bytes_amount: u64,
+    store_until_utc_secs: u64,
+) -> *mut responses::AddPieceResponse {
+    init_log();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+    let piece_fd = FileDescriptorRef::new(piece_fd_raw);
+
+    let mut response: responses::AddPieceResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.add_piece(
+        String::from(piece_key),
+        piece_fd,
+        piece_bytes_amount,
+        SecondsSinceEpoch(store_until_utc_secs),
+    ) {
+        Ok(sector_id) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_id = u64::from(sector_id);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Writes user piece-bytes to a staged sector like sector_builder_ffi_add_piece,
+/// unless a piece has already been added with the provided idempotency key, in
+/// which case the id of that piece's sector is returned and nothing is
+/// written. A null idempotency key behaves like sector_builder_ffi_add_piece.
+/// The caller is responsible for closing the file descriptor.
+#[no_mangle]
+#[cfg(not(target_os = "windows"))]
+pub unsafe extern "C" fn sector_builder_ffi_add_piece_with_idempotency_key(
+    handle: u64,
+    piece_key: *const libc::c_char,
+    piece_fd_raw: libc::c_int,
+    piece_bytes_amount: u64,
+    store_until_utc_secs: u64,
+    idempotency_key: *const libc::c_char,
+) -> *mut responses::AddPieceResponse {
+    init_log();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+    let piece_fd = FileDescriptorRef::new(piece_fd_raw);
+
+    let idempotency_key = if idempotency_key.is_null() {
+        None
+    } else {
+        Some(String::from(c_str_to_rust_str(idempotency_key)))
+    };
+
+    let mut response: responses::AddPieceResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.add_piece_with_idempotency_key(
+        String::from(piece_key),
+        piece_fd,
+        piece_bytes_amount,
+        SecondsSinceEpoch(store_until_utc_secs),
+        idempotency_key,
+    ) {
+        Ok(sector_id) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_id = u64::from(sector_id);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Writes user piece-bytes to a staged sector like
+/// sector_builder_ffi_add_piece_with_idempotency_key, recording the id of the
+/// piece's deal (if has_deal_id is true) and the provided labels with the
+/// piece's metadata. Labels are copied, and their number and length limited.
+/// If origin isn't null, it identifies the client or source of the piece and
+/// is recorded in the journal (see sector_builder_ffi_get_pieces_by_origin).
+/// The caller is responsible for closing the file descriptor.
+#[no_mangle]
+#[cfg(not(target_os = "windows"))]
+pub unsafe extern "C" fn sector_builder_ffi_add_piece_with_deal_info(
+    handle: u64,
     piece_key: *const libc::c_char,
     piece_fd_raw: libc::c_int,
     piece_bytes_amount: u64,
     store_until_utc_secs: u64,
+    idempotency_key: *const libc::c_char,
+    has_deal_id: bool,
+    deal_id: u64,
+    labels_ptr: *const FFIPieceLabel,
+    labels_len: libc::size_t,
+    origin: *const libc::c_char,
 ) -> *mut responses::AddPieceResponse {
     init_log();
 
     let piece_key = c_str_to_rust_str(piece_key);
     let piece_fd = FileDescriptorRef::new(piece_fd_raw);
 
+    let idempotency_key = if idempotency_key.is_null() {
+        None
+    } else {
+        Some(String::from(c_str_to_rust_str(idempotency_key)))
+    };
+
+    let deal_info = PieceDealInfo {
+        deal_id: if has_deal_id { Some(deal_id) } else { None },
+        labels: from_ffi_piece_labels(labels_ptr, labels_len),
+        origin: if origin.is_null() {
+            None
+        } else {
+            Some(String::from(c_str_to_rust_str(origin)))
+        },
+    };
+
     let mut response: responses::AddPieceResponse = Default::default();
 
-    match (*ptr).add_piece(
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.add_piece_with_deal_info(
         String::from(piece_key),
         piece_fd,
         piece_bytes_amount,
         SecondsSinceEpoch(store_until_utc_secs),
+        idempotency_key,
+        deal_info,
     ) {
         Ok(sector_id) => {
             response.status_code = FCPResponseStatus::FCPNoError;
@@ -92,6 +288,76 @@ pub unsafe extern "C" fn sector_builder_ffi_get_max_user_bytes_per_staged_sector
     filecoin_proofs_ffi::api::get_max_user_bytes_per_staged_sector(sector_size)
 }
 
+/// Returns the number of bytes which the given number of user bytes occupy
+/// once bit-padding has been added.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_padded_bytes_amount(unpadded_bytes: u64) -> u64 {
+    init_log();
+
+    u64::from(PaddedBytesAmount::from(UnpaddedBytesAmount(unpadded_bytes)))
+}
+
+/// Returns the number of user bytes (before bit-padding has been added) which
+/// occupy the given number of bit-padded bytes.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_unpadded_bytes_amount(padded_bytes: u64) -> u64 {
+    init_log();
+
+    u64::from(UnpaddedBytesAmount::from(PaddedBytesAmount(padded_bytes)))
+}
+
+/// Returns the offset (in user bytes, before bit-padding has been added) at
+/// which a piece of the given number of user bytes starts when it is written
+/// to a sector after pieces of the given numbers of user bytes, taking into
+/// account the alignment of pieces within the sector. This is the offset at
+/// which the piece is read back from the unsealed sector.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_piece_start_byte(
+    preceding_piece_lens_ptr: *const u64,
+    preceding_piece_lens_len: libc::size_t,
+    piece_len: u64,
+) -> u64 {
+    init_log();
+
+    let preceding_piece_lens: Vec<UnpaddedBytesAmount> =
+        from_raw_parts(preceding_piece_lens_ptr, preceding_piece_lens_len)
+            .iter()
+            .map(|len| UnpaddedBytesAmount(*len))
+            .collect();
+
+    u64::from(get_piece_start_byte(&preceding_piece_lens, UnpaddedBytesAmount(piece_len)))
+}
+
+/// Returns the sector sizes for which all parameters exist in the parameter
+/// cache, given the number of PoRep proof partitions.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_supported_sector_sizes(
+    porep_proof_partitions: u8,
+) -> *mut responses::GetSupportedSectorSizesResponse {
+    init_log();
+
+    let mut response: responses::GetSupportedSectorSizesResponse = Default::default();
+
+    let sector_sizes = sector_builder::supported_sector_sizes(
+        filecoin_proofs::PoRepProofPartitions(porep_proof_partitions),
+    )
+    .into_iter()
+    .map(|filecoin_proofs::SectorSize(size)| size)
+    .collect::<Vec<u64>>();
+
+    response.status_code = FCPResponseStatus::FCPNoError;
+    response.sector_sizes_len = sector_sizes.len();
+    response.sector_sizes_ptr = sector_sizes.as_ptr();
+
+    mem::forget(sector_sizes);
+
+    raw_ptr(response)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn sector_builder_ffi_verify_piece_inclusion_proof(
     comm_d: &[u8; 32],
@@ -126,20 +392,115 @@ pub unsafe extern "C" fn sector_builder_ffi_generate_piece_commitment(
     filecoin_proofs_ffi::api::generate_piece_commitment(piece_fd_raw, unpadded_piece_size)
 }
 
+/// Returns the merkle roots for many pieces, computed in parallel using at
+/// most max_num_threads threads. The commitments are flattened into a single
+/// array, 32 bytes per piece, in the order of the provided pieces.
+/// The caller is responsible for closing the file descriptors.
+#[no_mangle]
+#[cfg(not(target_os = "windows"))]
+pub unsafe extern "C" fn sector_builder_ffi_generate_piece_commitments(
+    piece_fds_ptr: *const libc::c_int,
+    unpadded_piece_sizes_ptr: *const u64,
+    pieces_len: libc::size_t,
+    max_num_threads: libc::size_t,
+) -> *mut responses::GeneratePieceCommitmentsResponse {
+    init_log();
+
+    let mut response: responses::GeneratePieceCommitmentsResponse = Default::default();
+
+    let pieces = from_raw_parts(piece_fds_ptr, pieces_len)
+        .iter()
+        .zip(from_raw_parts(unpadded_piece_sizes_ptr, pieces_len))
+        .map(|(fd, size)| (FileDescriptorRef::new(*fd), UnpaddedBytesAmount(*size)))
+        .collect();
+
+    match sector_builder::generate_piece_commitments(pieces, max_num_threads) {
+        Ok(comm_ps) => {
+            let flattened: Vec<u8> = comm_ps.iter().flat_map(|comm_p| comm_p.iter().cloned()).collect();
+
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.flattened_comm_ps_len = flattened.len();
+            response.flattened_comm_ps_ptr = flattened.as_ptr();
+
+            mem::forget(flattened);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Returns the merkle root for the piece in the file at the provided path
+/// after piece padding and alignment. Unlike
+/// sector_builder_ffi_generate_piece_commitment, it's available on hosts
+/// which can't pass file descriptors, e.g. Windows.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_generate_piece_commitment_from_path(
+    piece_path: *const libc::c_char,
+    unpadded_piece_size: u64,
+) -> *mut responses::GeneratePieceCommitmentResponse {
+    init_log();
+
+    let result = std::fs::File::open(c_str_to_pbuf(piece_path))
+        .map_err(failure::Error::from)
+        .and_then(|file| {
+            filecoin_proofs::generate_piece_commitment(file, UnpaddedBytesAmount(unpadded_piece_size))
+        });
+
+    into_generate_piece_commitment_response(result)
+}
+
+/// Returns the merkle root for the piece in the provided buffer after piece
+/// padding and alignment. The piece is as long as the buffer.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_generate_piece_commitment_from_bytes(
+    piece_bytes_ptr: *const u8,
+    piece_bytes_len: libc::size_t,
+) -> *mut responses::GeneratePieceCommitmentResponse {
+    init_log();
+
+    let piece_bytes = from_raw_parts(piece_bytes_ptr, piece_bytes_len);
+
+    let result = filecoin_proofs::generate_piece_commitment(
+        piece_bytes,
+        UnpaddedBytesAmount(piece_bytes_len as u64),
+    );
+
+    into_generate_piece_commitment_response(result)
+}
+
 /// Returns sector sealing status for the provided sector id if it exists. If
-/// we don't know about the provided sector id, produce an error.
+/// we don't know about the provided sector id, produce an error. If
+/// check_health is set, the health of a sealed sector is checked (hashing
+/// its whole replica, but no other) and included in the response.
 #[no_mangle]
 pub unsafe extern "C" fn sector_builder_ffi_get_seal_status(
-    ptr: *mut SectorBuilder,
+    handle: u64,
     sector_id: u64,
+    check_health: bool,
 ) -> *mut responses::GetSealStatusResponse {
     init_log();
 
     let mut response: responses::GetSealStatusResponse = Default::default();
 
-    match (*ptr).get_seal_status(SectorId::from(sector_id)) {
-        Ok(seal_status) => {
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    let mode = if check_health {
+        Some(HealthCheckMode::Full)
+    } else {
+        None
+    };
+
+    match builder.get_seal_status_with_health_check(SectorId::from(sector_id), mode) {
+        Ok((seal_status, health)) => {
             response.status_code = FCPResponseStatus::FCPNoError;
+            response.health = health.map(Into::into).unwrap_or(FFISealedSectorHealth::Unknown);
 
             match seal_status {
                 SealStatus::Sealed(meta) => {
@@ -159,7 +520,7 @@ pub unsafe extern "C" fn sector_builder_ffi_get_seal_status(
                     response.proof_len = meta.proof.len();
                     response.proof_ptr = meta.proof.as_ptr();
                     response.seal_status_code = FFISealStatus::Sealed;
-                    response.sector_access = rust_str_to_c_str(meta.sector_access);
+                    response.sector_access = rust_str_to_c_str(String::from(meta.sector_access));
                     response.sector_id = u64::from(meta.sector_id);
 
                     mem::forget(meta.proof);
@@ -171,9 +532,10 @@ pub unsafe extern "C" fn sector_builder_ffi_get_seal_status(
                 SealStatus::Pending => {
                     response.seal_status_code = FFISealStatus::Pending;
                 }
-                SealStatus::Failed(err) => {
+                SealStatus::Failed(failure) => {
                     response.seal_status_code = FFISealStatus::Failed;
-                    response.seal_error_msg = rust_str_to_c_str(err);
+                    response.seal_failure_code = into_ffi_seal_failure(&failure);
+                    response.seal_error_msg = rust_str_to_c_str(format!("{}", failure));
                 }
             }
         }
@@ -187,60 +549,75 @@ pub unsafe extern "C" fn sector_builder_ffi_get_seal_status(
     raw_ptr(response)
 }
 
+/// Returns the sealing status of each sector with the provided ids, in the
+/// order of the ids, in one call. A sector whose status can't be determined
+/// (e.g. because no sector with its id exists) has an error in its entry of
+/// the response rather than failing the call.
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_get_sealed_sectors(
-    ptr: *mut SectorBuilder,
-    check_health: bool,
-) -> *mut responses::GetSealedSectorsResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_seal_statuses(
+    handle: u64,
+    sector_ids_ptr: *const u64,
+    sector_ids_len: libc::size_t,
+) -> *mut responses::GetSealStatusesResponse {
     init_log();
-    let mut response: responses::GetSealedSectorsResponse = Default::default();
 
-    match (*ptr).get_sealed_sectors(check_health) {
-        Ok(sealed_sectors) => {
-            response.status_code = FCPResponseStatus::FCPNoError;
+    let mut response: responses::GetSealStatusesResponse = Default::default();
 
-            let sectors = sealed_sectors
-                .iter()
-                .map(|wrapped_meta| {
-                    let (ffi_health, meta) = match wrapped_meta {
-                        GetSealedSectorResult::WithHealth(h, m) => ((*h).into(), m),
-                        GetSealedSectorResult::WithoutHealth(m) => {
-                            (FFISealedSectorHealth::Unknown, m)
-                        }
-                    };
+    let sector_ids: Vec<SectorId> = if sector_ids_ptr.is_null() {
+        Vec::new()
+    } else {
+        from_raw_parts(sector_ids_ptr, sector_ids_len)
+            .iter()
+            .map(|x| SectorId::from(*x))
+            .collect()
+    };
 
-                    let pieces = meta
-                        .pieces
-                        .iter()
-                        .map(into_ffi_piece_metadata)
-                        .collect::<Vec<FFIPieceMetadata>>();
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-                    let snark_proof = meta.proof.clone();
-
-                    let sector = responses::FFISealedSectorMetadata {
-                        comm_d: meta.comm_d,
-                        comm_r: meta.comm_r,
-                        comm_r_star: meta.comm_r_star,
-                        pieces_len: pieces.len(),
-                        pieces_ptr: pieces.as_ptr(),
-                        proofs_len: snark_proof.len(),
-                        proofs_ptr: snark_proof.as_ptr(),
-                        sector_access: rust_str_to_c_str(meta.sector_access.clone()),
-                        sector_id: u64::from(meta.sector_id),
-                        health: ffi_health,
-                    };
+    let statuses = sector_ids
+        .iter()
+        .zip(builder.get_seal_statuses(&sector_ids))
+        .map(|(sector_id, result)| into_ffi_sector_seal_status(*sector_id, result))
+        .collect::<Vec<responses::FFISectorSealStatus>>();
 
-                    mem::forget(snark_proof);
-                    mem::forget(pieces);
+    response.status_code = FCPResponseStatus::FCPNoError;
+    response.statuses_len = statuses.len();
+    response.statuses_ptr = statuses.as_ptr();
 
-                    sector
-                })
-                .collect::<Vec<responses::FFISealedSectorMetadata>>();
+    mem::forget(statuses);
 
-            response.sectors_len = sectors.len();
-            response.sectors_ptr = sectors.as_ptr();
+    raw_ptr(response)
+}
 
-            mem::forget(sectors);
+/// Checks the health of the sealed sector with the provided id, without
+/// hashing any other sector's replica. If num_samples is 0, the whole replica
+/// is hashed; otherwise, only num_samples of its extents, picked from the
+/// seed, are hashed.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_check_sector_health(
+    handle: u64,
+    sector_id: u64,
+    num_samples: u64,
+    seed: u64,
+) -> *mut responses::CheckSectorHealthResponse {
+    init_log();
+
+    let mut response: responses::CheckSectorHealthResponse = Default::default();
+
+    let mode = if num_samples == 0 {
+        HealthCheckMode::Full
+    } else {
+        HealthCheckMode::Quick { seed, num_samples: num_samples as usize }
+    };
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.check_sector_health(SectorId::from(sector_id), mode) {
+        Ok(health) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.health = health.into();
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -252,61 +629,26 @@ pub unsafe extern "C" fn sector_builder_ffi_get_sealed_sectors(
     raw_ptr(response)
 }
 
+/// Computes and persists the checksum and length of the sealed sector with
+/// the provided id if its metadata lacks them, e.g. because it was imported
+/// from metadata which predates checksums. Sectors which have them are left
+/// alone. Reads the whole replica.
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_get_staged_sectors(
-    ptr: *mut SectorBuilder,
-) -> *mut responses::GetStagedSectorsResponse {
+pub unsafe extern "C" fn sector_builder_ffi_hydrate_sector_integrity(
+    handle: u64,
+    sector_id: u64,
+) -> *mut responses::HydrateSectorIntegrityResponse {
     init_log();
-    let mut response: responses::GetStagedSectorsResponse = Default::default();
-
-    match (*ptr).get_staged_sectors() {
-        Ok(staged_sectors) => {
-            response.status_code = FCPResponseStatus::FCPNoError;
-
-            let sectors = staged_sectors
-                .iter()
-                .map(|meta| {
-                    let pieces = meta
-                        .pieces
-                        .iter()
-                        .map(into_ffi_piece_metadata)
-                        .collect::<Vec<FFIPieceMetadata>>();
-
-                    let mut sector = responses::FFIStagedSectorMetadata {
-                        sector_access: rust_str_to_c_str(meta.sector_access.clone()),
-                        sector_id: u64::from(meta.sector_id),
-                        pieces_len: pieces.len(),
-                        pieces_ptr: pieces.as_ptr(),
-                        seal_status_code: FFISealStatus::Pending,
-                        seal_error_msg: ptr::null(),
-                    };
-
-                    match meta.seal_status {
-                        SealStatus::Failed(ref s) => {
-                            sector.seal_status_code = FFISealStatus::Failed;
-                            sector.seal_error_msg = rust_str_to_c_str(s.clone());
-                        }
-                        SealStatus::Sealing => {
-                            sector.seal_status_code = FFISealStatus::Sealing;
-                        }
-                        SealStatus::Pending => {
-                            sector.seal_status_code = FFISealStatus::Pending;
-                        }
-                        SealStatus::Sealed(_) => {
-                            sector.seal_status_code = FFISealStatus::Sealed;
-                        }
-                    };
-
-                    mem::forget(pieces);
 
-                    sector
-                })
-                .collect::<Vec<responses::FFIStagedSectorMetadata>>();
+    let mut response: responses::HydrateSectorIntegrityResponse = Default::default();
 
-            response.sectors_len = sectors.len();
-            response.sectors_ptr = sectors.as_ptr();
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-            mem::forget(sectors);
+    match builder.hydrate_sector_integrity(SectorId::from(sector_id)) {
+        Ok(hydrated) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.hydrated = hydrated;
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -318,40 +660,37 @@ pub unsafe extern "C" fn sector_builder_ffi_get_staged_sectors(
     raw_ptr(response)
 }
 
-/// Generates a proof-of-spacetime for the given replica commitments.
+/// Returns the padded start byte and length of each piece in the sector with
+/// the provided id, in the order in which the pieces were added.
 ///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_generate_post(
-    ptr: *mut SectorBuilder,
-    flattened_comm_rs_ptr: *const u8,
-    flattened_comm_rs_len: libc::size_t,
-    challenge_seed: &[u8; 32],
-    faults_ptr: *const u64,
-    faults_len: libc::size_t,
-) -> *mut responses::GeneratePoStResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_piece_layout(
+    handle: u64,
+    sector_id: u64,
+) -> *mut responses::GetPieceLayoutResponse {
     init_log();
 
-    info!("generate_post: {}", "start");
-
-    let comm_rs = into_commitments(flattened_comm_rs_ptr, flattened_comm_rs_len);
-    let faults = from_raw_parts(faults_ptr, faults_len)
-        .iter()
-        .map(|x| SectorId::from(*x))
-        .collect();
-
-    let result = (*ptr).generate_post(&comm_rs, challenge_seed, faults);
+    let mut response: responses::GetPieceLayoutResponse = Default::default();
 
-    let mut response = responses::GeneratePoStResponse::default();
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-    match result {
-        Ok(proof) => {
+    match builder.get_piece_layout(SectorId::from(sector_id)) {
+        Ok(layout) => {
             response.status_code = FCPResponseStatus::FCPNoError;
 
-            response.proof_len = proof.len();
-            response.proof_ptr = proof.as_ptr();
+            let pieces = layout
+                .into_iter()
+                .map(|piece| responses::FFIPieceLayout {
+                    piece_key: rust_str_to_c_str(piece.piece_key),
+                    padded_start_byte: piece.padded_start_byte,
+                    padded_len: u64::from(piece.padded_len),
+                })
+                .collect::<Vec<responses::FFIPieceLayout>>();
 
-            // we'll free this stuff when we free the GeneratePoSTResponse
-            mem::forget(proof);
+            response.pieces_len = pieces.len();
+            response.pieces_ptr = pieces.as_ptr();
+
+            mem::forget(pieces);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -360,41 +699,41 @@ pub unsafe extern "C" fn sector_builder_ffi_generate_post(
         }
     }
 
-    info!("generate_post: {}", "finish");
-
     raw_ptr(response)
 }
 
-/// Initializes and returns a SectorBuilder.
-///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_init_sector_builder(
-    sector_class: FFISectorClass,
-    last_used_sector_id: u64,
-    metadata_dir: *const libc::c_char,
-    prover_id: &[u8; 31],
-    sealed_sector_dir: *const libc::c_char,
-    staged_sector_dir: *const libc::c_char,
-    max_num_staged_sectors: u8,
-) -> *mut responses::InitSectorBuilderResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_sealed_sectors(
+    handle: u64,
+    check_health: bool,
+) -> *mut responses::GetSealedSectorsResponse {
     init_log();
+    let mut response: responses::GetSealedSectorsResponse = Default::default();
 
-    let result = SectorBuilder::init_from_metadata(
-        from_ffi_sector_class(sector_class),
-        SectorId::from(last_used_sector_id),
-        c_str_to_rust_str(metadata_dir).to_string(),
-        *prover_id,
-        c_str_to_rust_str(sealed_sector_dir).to_string(),
-        c_str_to_rust_str(staged_sector_dir).to_string(),
-        max_num_staged_sectors,
-    );
-
-    let mut response = responses::InitSectorBuilderResponse::default();
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-    match result {
-        Ok(sb) => {
+    match builder.get_sealed_sectors(check_health) {
+        Ok(sealed_sectors) => {
             response.status_code = FCPResponseStatus::FCPNoError;
-            response.sector_builder = raw_ptr(sb);
+
+            let sectors = sealed_sectors
+                .iter()
+                .map(|wrapped_meta| {
+                    let (ffi_health, meta) = match wrapped_meta {
+                        GetSealedSectorResult::WithHealth(h, m) => ((*h).into(), m),
+                        GetSealedSectorResult::WithoutHealth(m) => {
+                            (FFISealedSectorHealth::Unknown, m)
+                        }
+                    };
+
+                    into_ffi_sealed_sector_metadata(meta, ffi_health)
+                })
+                .collect::<Vec<responses::FFISealedSectorMetadata>>();
+
+            response.sectors_len = sectors.len();
+            response.sectors_ptr = sectors.as_ptr();
+
+            mem::forget(sectors);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -406,25 +745,46 @@ pub unsafe extern "C" fn sector_builder_ffi_init_sector_builder(
     raw_ptr(response)
 }
 
-/// Unseals and returns the bytes associated with the provided piece key.
+/// Calculates statistics of how long sealing sectors took, over all sealed
+/// sectors and per machine on which they were sealed. If since_utc_secs isn't
+/// 0, only sectors which finished sealing at or after that time (in seconds
+/// since the epoch) are included.
 ///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_sealed_sector(
-    ptr: *mut SectorBuilder,
-    piece_key: *const libc::c_char,
-) -> *mut responses::ReadPieceFromSealedSectorResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_seal_statistics(
+    handle: u64,
+    since_utc_secs: u64,
+) -> *mut responses::GetSealStatisticsResponse {
     init_log();
 
-    let mut response: responses::ReadPieceFromSealedSectorResponse = Default::default();
+    let mut response: responses::GetSealStatisticsResponse = Default::default();
 
-    let piece_key = c_str_to_rust_str(piece_key);
+    let since = if since_utc_secs == 0 {
+        None
+    } else {
+        Some(SecondsSinceEpoch(since_utc_secs))
+    };
 
-    match (*ptr).read_piece_from_sealed_sector(String::from(piece_key)) {
-        Ok(piece_bytes) => {
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.get_seal_statistics(since) {
+        Ok(stats) => {
             response.status_code = FCPResponseStatus::FCPNoError;
-            response.data_ptr = piece_bytes.as_ptr();
-            response.data_len = piece_bytes.len();
-            mem::forget(piece_bytes);
+            response.overall = into_ffi_seal_duration_stats(&stats.overall);
+
+            let per_host = stats
+                .per_host
+                .iter()
+                .map(|(hostname, stats)| responses::FFIHostSealStatistics {
+                    hostname: rust_str_to_c_str(hostname.clone()),
+                    stats: into_ffi_seal_duration_stats(stats),
+                })
+                .collect::<Vec<responses::FFIHostSealStatistics>>();
+
+            response.per_host_len = per_host.len();
+            response.per_host_ptr = per_host.as_ptr();
+
+            mem::forget(per_host);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -436,19 +796,34 @@ pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_sealed_sector(
     raw_ptr(response)
 }
 
-/// For demo purposes. Seals all staged sectors.
+/// Returns the sealed sectors whose every piece was to be stored until a time
+/// before the provided time, in seconds since the epoch. Their replicas
+/// aren't health-checked.
 ///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_seal_all_staged_sectors(
-    ptr: *mut SectorBuilder,
-) -> *mut responses::SealAllStagedSectorsResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_expired_sectors(
+    handle: u64,
+    now_utc_secs: u64,
+) -> *mut responses::GetExpiredSectorsResponse {
     init_log();
 
-    let mut response: responses::SealAllStagedSectorsResponse = Default::default();
+    let mut response: responses::GetExpiredSectorsResponse = Default::default();
 
-    match (*ptr).seal_all_staged_sectors() {
-        Ok(_) => {
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.get_expired_sectors(SecondsSinceEpoch(now_utc_secs)) {
+        Ok(expired_sectors) => {
             response.status_code = FCPResponseStatus::FCPNoError;
+
+            let sectors = expired_sectors
+                .iter()
+                .map(|meta| into_ffi_sealed_sector_metadata(meta, FFISealedSectorHealth::Unknown))
+                .collect::<Vec<responses::FFISealedSectorMetadata>>();
+
+            response.sectors_len = sectors.len();
+            response.sectors_ptr = sectors.as_ptr();
+
+            mem::forget(sectors);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -460,88 +835,62 @@ pub unsafe extern "C" fn sector_builder_ffi_seal_all_staged_sectors(
     raw_ptr(response)
 }
 
-/// Verifies the output of seal.
+/// Returns the metadata of the staged sectors with the seal status which the
+/// provided filter selects.
 ///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_verify_seal(
-    sector_size: u64,
-    comm_r: &[u8; 32],
-    comm_d: &[u8; 32],
-    comm_r_star: &[u8; 32],
-    prover_id: &[u8; 31],
-    sector_id: u64,
-    proof_ptr: *const u8,
-    proof_len: libc::size_t,
-) -> *mut filecoin_proofs_ffi::responses::VerifySealResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_staged_sectors(
+    handle: u64,
+    filter: responses::FFIStagedSectorFilter,
+) -> *mut responses::GetStagedSectorsResponse {
     init_log();
+    let mut response: responses::GetStagedSectorsResponse = Default::default();
 
-    filecoin_proofs_ffi::api::verify_seal(
-        sector_size,
-        comm_r,
-        comm_d,
-        comm_r_star,
-        prover_id,
-        sector_id,
-        proof_ptr,
-        proof_len,
-    )
-}
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-/// Verifies that a proof-of-spacetime is valid.
-///
-#[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_verify_post(
-    sector_size: u64,
-    challenge_seed: &[u8; 32],
-    sector_ids_ptr: *const u64,
-    sector_ids_len: libc::size_t,
-    faulty_sector_ids_ptr: *const u64,
-    faulty_sector_ids_len: libc::size_t,
-    flattened_comm_rs_ptr: *const u8,
-    flattened_comm_rs_len: libc::size_t,
-    proof_ptr: *const u8,
-    proof_len: libc::size_t,
-) -> *mut filecoin_proofs_ffi::responses::VerifyPoStResponse {
-    init_log();
+    match builder.get_staged_sectors(filter.into()) {
+        Ok(staged_sectors) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
 
-    filecoin_proofs_ffi::api::verify_post(
-        sector_size,
-        challenge_seed,
-        sector_ids_ptr,
-        sector_ids_len,
-        faulty_sector_ids_ptr,
-        faulty_sector_ids_len,
-        flattened_comm_rs_ptr,
-        flattened_comm_rs_len,
-        proof_ptr,
-        proof_len,
-    )
-}
+            let sectors = staged_sectors
+                .iter()
+                .map(into_ffi_staged_sector_metadata)
+                .collect::<Vec<responses::FFIStagedSectorMetadata>>();
 
-pub type SimpleSectorBuilder = sector_builder::SimpleSectorBuilder;
+            response.sectors_len = sectors.len();
+            response.sectors_ptr = sectors.as_ptr();
+
+            mem::forget(sectors);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
 
+/// Returns the metadata of the staged sector with the provided id, without
+/// marshalling every staged sector.
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_init_simple_sector_builder(
-    sector_class: FFISectorClass,
-    sealed_sector_dir: *const libc::c_char,
-    staged_sector_dir: *const libc::c_char,
-    max_num_staged_sectors: u8,
-) -> *mut responses::InitSimpleSectorBuilderResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_staged_sector(
+    handle: u64,
+    sector_id: u64,
+) -> *mut responses::GetStagedSectorResponse {
     init_log();
 
-    let result = SimpleSectorBuilder::new(
-        from_ffi_sector_class(sector_class),
-        c_str_to_rust_str(sealed_sector_dir).to_string(),
-        c_str_to_rust_str(staged_sector_dir).to_string(),
-        max_num_staged_sectors,
-    );
+    let mut response: responses::GetStagedSectorResponse = Default::default();
 
-    let mut response = responses::InitSimpleSectorBuilderResponse::default();
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-    match result {
-        Ok(sb) => {
+    match builder.get_staged_sector(SectorId::from(sector_id)) {
+        Ok(meta) => {
             response.status_code = FCPResponseStatus::FCPNoError;
-            response.sector_builder = raw_ptr(sb);
+            response.sector_ptr = raw_ptr(into_ffi_staged_sector_metadata(&meta));
+            response.sector_len = 1;
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -553,46 +902,25 @@ pub unsafe extern "C" fn sector_builder_ffi_init_simple_sector_builder(
     raw_ptr(response)
 }
 
+/// Reports the number of bytes on disk and the number of keys of the store
+/// holding the sector builder's metadata, and when it last flushed its writes
+/// (0 if it hasn't).
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_init_simple_sector_builder_response(
-    ptr: *mut responses::InitSimpleSectorBuilderResponse,
-) {
-    let _ = Box::from_raw(ptr);
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_simple_sector_builder(ptr: *mut SimpleSectorBuilder) {
-    let _ = Box::from_raw(ptr);
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_add_piece_first(
-    ptr: *mut SimpleSectorBuilder,
-    miner: *const libc::c_char,
-    sectors_ptr: *const responses::FFIPendingStagedSectorMetadata,
-    sectors_len: libc::size_t,
-    piece_bytes_amount: u64,
-    new_sector_id: u64,
-) -> *mut responses::AddPieceResponse {
+pub unsafe extern "C" fn sector_builder_ffi_get_kv_store_stats(
+    handle: u64,
+) -> *mut responses::GetKvStoreStatsResponse {
     init_log();
+    let mut response: responses::GetKvStoreStatsResponse = Default::default();
 
-    let mut response: responses::AddPieceResponse = Default::default();
-
-    let sectors: Vec<&responses::FFIPendingStagedSectorMetadata> = from_raw_parts(sectors_ptr, sectors_len).iter().collect();
-    let mut staged_sectors: HashMap<SectorId, StagedSectorMetadata> = HashMap::new();
-    for s in sectors {
-        staged_sectors.insert(s.sector_id.into(), into_staged_sector_metadata(s));
-    }
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-    match (*ptr).add_piece_first(
-        c_str_to_rust_str(miner).into(),
-        staged_sectors,
-        piece_bytes_amount,
-        new_sector_id.into(),
-    ) {
-        Ok(sector_id) => {
+    match builder.kv_store_stats() {
+        Ok(stats) => {
             response.status_code = FCPResponseStatus::FCPNoError;
-            response.sector_id = u64::from(sector_id);
+            response.size_on_disk = stats.size_on_disk;
+            response.num_keys = stats.num_keys;
+            response.last_flush = stats.last_flush.map_or(0, |last_flush| last_flush.0);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -604,58 +932,23 @@ pub unsafe extern "C" fn sector_builder_ffi_add_piece_first(
     raw_ptr(response)
 }
 
-////////////////////////////////////////////////////////////////////////////////
-// DESTRUCTORS
-//////////////
-
-#[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_add_piece_response(
-    ptr: *mut responses::AddPieceResponse,
-) {
-    let _ = Box::from_raw(ptr);
-}
-
+/// Sets the number of the most recent journal entries which are kept once
+/// the snapshot reflecting them has been flushed. Older entries are pruned.
+///
 #[no_mangle]
-#[cfg(not(target_os = "windows"))]
-pub unsafe extern "C" fn sector_builder_ffi_add_piece_second(
-    ptr: *mut SimpleSectorBuilder,
-    miner: *const libc::c_char,
-    sector_ptr: *const responses::FFIPendingStagedSectorMetadata,
-    piece_key: *const libc::c_char,
-    piece_fd_raw: libc::c_int,
-    piece_bytes_amount: u64,
-) -> *mut responses::AddPieceSecondResponse {
+pub unsafe extern "C" fn sector_builder_ffi_set_journal_keep_last_n(
+    handle: u64,
+    keep_last_n: u64,
+) -> *mut responses::SetJournalKeepLastNResponse {
     init_log();
 
-    let sector = into_staged_sector_metadata(sector_ptr);
-
-    let mut response: responses::AddPieceSecondResponse = Default::default();
-
-    match (*ptr).add_piece_second(
-        c_str_to_rust_str(miner).into(),
-        sector,
-        c_str_to_rust_str(piece_key).into(),
-        FileDescriptorRef::new(piece_fd_raw),
-        piece_bytes_amount,
-    ) {
-        Ok(meta) => {
-            let pieces = meta
-                .pieces
-                .iter()
-                .map(into_ffi_piece_metadata)
-                .collect::<Vec<FFIPieceMetadata>>();
+    let mut response: responses::SetJournalKeepLastNResponse = Default::default();
 
-            let sector = responses::FFIPendingStagedSectorMetadata {
-                sector_access: rust_str_to_c_str(meta.sector_access.clone()),
-                sector_id: u64::from(meta.sector_id),
-                pieces_len: pieces.len(),
-                pieces_ptr: pieces.as_ptr(),
-            };
-            mem::forget(pieces);
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
+    match builder.set_journal_keep_last_n(keep_last_n) {
+        Ok(_) => {
             response.status_code = FCPResponseStatus::FCPNoError;
-            response.sector_ptr = raw_ptr(sector);
-            response.sector_len = 1;
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -667,38 +960,25 @@ pub unsafe extern "C" fn sector_builder_ffi_add_piece_second(
     raw_ptr(response)
 }
 
+/// Flushes pending metadata changes and prunes the journal down to its most
+/// recent entries right away, reporting the metadata store's statistics
+/// afterwards (see sector_builder_ffi_get_kv_store_stats).
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_add_piece_second_response(
-    ptr: *mut responses::AddPieceSecondResponse,
-) {
-    let _ = Box::from_raw(ptr);
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_specified_sealed_sector(
-    ptr: *mut SimpleSectorBuilder,
-    miner: *const libc::c_char,
-    sector_ptr: *const responses::FFISealedSectorMetadata,
-    piece_key: *const libc::c_char,
-    prover_id: &[u8; 31],
-) -> *mut responses::ReadPieceFromSealedSectorResponse {
+pub unsafe extern "C" fn sector_builder_ffi_compact_metadata(
+    handle: u64,
+) -> *mut responses::CompactMetadataResponse {
     init_log();
+    let mut response: responses::CompactMetadataResponse = Default::default();
 
-    let mut response: responses::ReadPieceFromSealedSectorResponse = Default::default();
-
-    let sector = into_sealed_sector_metadata(sector_ptr);
+    let builder = resolve_handle!(sector_builders(), handle, response);
 
-    match (*ptr).read_piece_from_sealed_sector(
-        c_str_to_rust_str(miner).into(),
-        &sector,
-        c_str_to_rust_str(piece_key).into(),
-        *prover_id,
-    ) {
-        Ok(piece_bytes) => {
+    match builder.compact_metadata() {
+        Ok(stats) => {
             response.status_code = FCPResponseStatus::FCPNoError;
-            response.data_ptr = piece_bytes.as_ptr();
-            response.data_len = piece_bytes.len();
-            mem::forget(piece_bytes);
+            response.size_on_disk = stats.size_on_disk;
+            response.num_keys = stats.num_keys;
+            response.last_flush = stats.last_flush.map_or(0, |last_flush| last_flush.0);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -710,53 +990,1618 @@ pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_specified_sealed_sec
     raw_ptr(response)
 }
 
+/// Lists the pieces which were added with the provided origin, with the
+/// sector to which and the time at which each was added, in the order in
+/// which they were added.
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_read_piece_from_specified_sealed_sector_response(
-    ptr: *mut responses::ReadPieceFromSealedSectorResponse,
+pub unsafe extern "C" fn sector_builder_ffi_get_pieces_by_origin(
+    handle: u64,
+    origin: *const libc::c_char,
+) -> *mut responses::GetPiecesByOriginResponse {
+    init_log();
+
+    let origin = c_str_to_rust_str(origin);
+
+    let mut response: responses::GetPiecesByOriginResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.get_pieces_by_origin(String::from(origin)) {
+        Ok(pieces) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+
+            let pieces = pieces
+                .into_iter()
+                .map(|piece| responses::FFIPieceProvenance {
+                    piece_key: rust_str_to_c_str(piece.piece_key),
+                    sector_id: u64::from(piece.sector_id),
+                    added_at: piece.added_at.0,
+                })
+                .collect::<Vec<responses::FFIPieceProvenance>>();
+
+            response.pieces_len = pieces.len();
+            response.pieces_ptr = pieces.as_ptr();
+
+            mem::forget(pieces);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Reports the storage consumed by the sector builder's sectors and the free
+/// space in each directory holding sector-files.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_storage_report(
+    handle: u64,
+) -> *mut responses::GetStorageReportResponse {
+    init_log();
+    let mut response: responses::GetStorageReportResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.get_storage_report() {
+        Ok(report) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.staged_bytes = report.staged_bytes;
+            response.sealed_bytes = report.sealed_bytes;
+            response.num_pending_sectors = report.num_pending_sectors;
+            response.num_sealing_sectors = report.num_sealing_sectors;
+            response.num_failed_sectors = report.num_failed_sectors;
+            response.num_sealed_sectors = report.num_sealed_sectors;
+
+            let directories = report
+                .directories
+                .iter()
+                .map(|usage| responses::FFIDirectoryUsage {
+                    path: rust_str_to_c_str(usage.path.to_string_lossy().into_owned()),
+                    available_bytes: usage.available_bytes,
+                    total_bytes: usage.total_bytes,
+                })
+                .collect::<Vec<responses::FFIDirectoryUsage>>();
+
+            response.directories_len = directories.len();
+            response.directories_ptr = directories.as_ptr();
+
+            mem::forget(directories);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Generates a proof-of-spacetime for the given replica commitments. If
+/// deadline (in seconds since the epoch) isn't 0, proving is abandoned once
+/// the deadline has passed, or before it starts if it's estimated not to
+/// complete in time. If mark_slowest_faulty is set, the slowest sectors are
+/// proven as faulty instead, which the faults in the response then include.
+/// The replicas of sectors with a replica path are read from that path, e.g.
+/// from a temporarily mounted archive volume. Sectors whose replica is
+/// missing or truncated are proven as faulty as well, so the faults in the
+/// response are the ones to declare on chain. A non-zero PoSt configuration
+/// overrides the one implied by the builder's sector class.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_generate_post(
+    handle: u64,
+    flattened_comm_rs_ptr: *const u8,
+    flattened_comm_rs_len: libc::size_t,
+    challenge_seed: &[u8; 32],
+    faults_ptr: *const u64,
+    faults_len: libc::size_t,
+    deadline: u64,
+    mark_slowest_faulty: bool,
+    replica_paths_ptr: *const FFIReplicaPath,
+    replica_paths_len: libc::size_t,
+    post_config: FFIPoStConfig,
+) -> *mut responses::GeneratePoStResponse {
+    init_log();
+
+    info!("generate_post: {}", "start");
+
+    let mut response = responses::GeneratePoStResponse::default();
+
+    let comm_rs = into_commitments(flattened_comm_rs_ptr, flattened_comm_rs_len);
+    let faults = from_raw_parts(faults_ptr, faults_len)
+        .iter()
+        .map(|x| SectorId::from(*x))
+        .collect();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    let deadline = into_post_deadline(deadline, mark_slowest_faulty);
+    let replica_paths = into_replica_paths(replica_paths_ptr, replica_paths_len);
+
+    let result = from_ffi_post_config(post_config).and_then(|post_config| {
+        builder.generate_post(
+            &comm_rs,
+            challenge_seed,
+            faults,
+            PoStOptions {
+                deadline,
+                replica_paths,
+                post_config,
+                cancel: None,
+            },
+        )
+    });
+
+    match result {
+        Ok(GeneratedPoSt { proof, faults }) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+
+            response.proof_len = proof.len();
+            response.proof_ptr = proof.as_ptr();
+
+            let faults: Vec<u64> = faults.into_iter().map(u64::from).collect();
+
+            response.faults_len = faults.len();
+            response.faults_ptr = faults.as_ptr();
+
+            // we'll free this stuff when we free the GeneratePoSTResponse
+            mem::forget(proof);
+            mem::forget(faults);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    info!("generate_post: {}", "finish");
+
+    raw_ptr(response)
+}
+
+/// Starts generating a proof-of-spacetime (see generate_post) without
+/// waiting for it, returning the id of the operation right away. The proof
+/// is retrieved by polling the operation.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_generate_post_async(
+    handle: u64,
+    flattened_comm_rs_ptr: *const u8,
+    flattened_comm_rs_len: libc::size_t,
+    challenge_seed: &[u8; 32],
+    faults_ptr: *const u64,
+    faults_len: libc::size_t,
+    deadline: u64,
+    mark_slowest_faulty: bool,
+    replica_paths_ptr: *const FFIReplicaPath,
+    replica_paths_len: libc::size_t,
+    post_config: FFIPoStConfig,
+) -> *mut responses::GeneratePoStAsyncResponse {
+    init_log();
+
+    let mut response = responses::GeneratePoStAsyncResponse::default();
+
+    let comm_rs = into_commitments(flattened_comm_rs_ptr, flattened_comm_rs_len);
+    let faults = from_raw_parts(faults_ptr, faults_len)
+        .iter()
+        .map(|x| SectorId::from(*x))
+        .collect();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    let deadline = into_post_deadline(deadline, mark_slowest_faulty);
+    let replica_paths = into_replica_paths(replica_paths_ptr, replica_paths_len);
+    let challenge_seed = *challenge_seed;
+    let post_config = from_ffi_post_config(post_config);
+
+    response.op_id = operations().start(
+        |cancel| {
+            post_config.and_then(|post_config| {
+                builder.start_generate_post(
+                    &comm_rs,
+                    &challenge_seed,
+                    faults,
+                    PoStOptions {
+                        deadline,
+                        replica_paths,
+                        post_config,
+                        cancel: Some(cancel),
+                    },
+                )
+            })
+        },
+        OperationOutput::PoSt,
+    );
+
+    raw_ptr(response)
+}
+
+/// Cancels an operation, e.g. a proof-of-spacetime which is no longer needed.
+/// The operation is abandoned (or isn't started, if it hasn't been yet) and
+/// fails, which it's polled for like for any other result.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_cancel(op_id: u64) -> *mut responses::CancelResponse {
+    init_log();
+
+    let mut response = responses::CancelResponse::default();
+
+    match operations().cancel(op_id) {
+        Ok(()) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err.into());
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Returns the state of an operation: pending, done (along with its output)
+/// or failed (along with the error). Once an operation is done or has
+/// failed, it's forgotten and can't be polled again. An operation which
+/// hasn't been polled for an hour is cancelled and forgotten as well.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_poll(op_id: u64) -> *mut responses::PollResponse {
+    init_log();
+
+    let mut response = responses::PollResponse::default();
+
+    match operations().poll(op_id) {
+        Ok(Poll::Pending) => {
+            response.op_status = FFIOperationStatus::Pending;
+        }
+        Ok(Poll::Done(OperationOutput::PoSt(GeneratedPoSt { proof, faults }))) => {
+            response.op_status = FFIOperationStatus::Done;
+
+            response.proof_len = proof.len();
+            response.proof_ptr = proof.as_ptr();
+
+            let faults: Vec<u64> = faults.into_iter().map(u64::from).collect();
+
+            response.faults_len = faults.len();
+            response.faults_ptr = faults.as_ptr();
+
+            // we'll free this stuff when we free the PollResponse
+            mem::forget(proof);
+            mem::forget(faults);
+        }
+        Ok(Poll::Done(OperationOutput::Piece(piece_bytes))) => {
+            response.op_status = FFIOperationStatus::Done;
+
+            response.data_len = piece_bytes.len();
+            response.data_ptr = piece_bytes.as_ptr();
+
+            mem::forget(piece_bytes);
+        }
+        Ok(Poll::Failed(err)) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.op_status = FFIOperationStatus::Failed;
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err.into());
+            response.op_status = FFIOperationStatus::Failed;
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Derives the challenges of a proof-of-spacetime for the given replica
+/// commitments without generating the proof. A non-zero PoSt configuration
+/// overrides the one implied by the builder's sector class.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_generate_post_challenges(
+    handle: u64,
+    flattened_comm_rs_ptr: *const u8,
+    flattened_comm_rs_len: libc::size_t,
+    challenge_seed: &[u8; 32],
+    faults_ptr: *const u64,
+    faults_len: libc::size_t,
+    post_config: FFIPoStConfig,
+) -> *mut responses::GeneratePoStFirstResponse {
+    init_log();
+
+    let mut response = responses::GeneratePoStFirstResponse::default();
+
+    let comm_rs = into_commitments(flattened_comm_rs_ptr, flattened_comm_rs_len);
+    let faults = from_raw_parts(faults_ptr, faults_len)
+        .iter()
+        .map(|x| SectorId::from(*x))
+        .collect();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match from_ffi_post_config(post_config).and_then(|post_config| {
+        builder.generate_post_first(&comm_rs, challenge_seed, faults, post_config)
+    }) {
+        Ok(challenges) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+
+            let ffi_challenges: Vec<responses::FFIChallenge> = challenges
+                .iter()
+                .map(|c| responses::FFIChallenge {
+                    sector: c.sector.into(),
+                    leaf: c.leaf,
+                })
+                .collect();
+
+            response.challenges_ptr = ffi_challenges.as_ptr();
+            response.challenges_len = ffi_challenges.len();
+
+            mem::forget(ffi_challenges);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_post_challenges_response(
+    ptr: *mut responses::GeneratePoStFirstResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+/// Initializes and returns a SectorBuilder.
+///
+/// Each worker may be pinned to a set of CPUs, given (indexed by worker) as a
+/// list in the kernel's format, e.g. "0-15,32-47" for the CPUs of a NUMA
+/// node. An empty list, or a null pointer for all of them, leaves workers
+/// unpinned.
+///
+/// If a staged data key is provided, staged sector-files are encrypted with
+/// it at rest. A null pointer leaves them unencrypted.
+///
+/// If a miner address is provided, new sector-files are named lotus-style,
+/// i.e. s-<miner>-<sector id>. A null pointer keeps the default names.
+///
+/// Calls are queued for the scheduler according to the scheduler config.
+///
+/// If a secondary staged sector directory is provided, new staged sectors are
+/// created in it while fewer than staged_spill_over_min_free_bytes bytes are
+/// available in the staged sector directory. A null pointer keeps every
+/// staged sector in the staged sector directory.
+///
+/// Sectors which were being sealed when the process went away are handled
+/// according to the stale seal policy: their seals are resumed, or they are
+/// marked as failed or reverted to pending.
+///
+/// Like every prover id taken by the FFI, the prover id is 32 bytes long and
+/// its last byte must be zero. Legacy 31-byte prover ids are extended with a
+/// zero byte.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_init_sector_builder(
+    sector_class: FFISectorClass,
+    last_used_sector_id: u64,
+    metadata_dir: *const libc::c_char,
+    prover_id: &[u8; 32],
+    sealed_sector_dir: *const libc::c_char,
+    staged_sector_dir: *const libc::c_char,
+    max_num_staged_sectors: u64,
+    worker_cpu_lists_ptr: *const *const libc::c_char,
+    worker_cpu_lists_len: libc::size_t,
+    staged_data_key: *const [u8; 32],
+    sector_access_miner: *const libc::c_char,
+    scheduler_config: FFISchedulerConfig,
+    secondary_staged_sector_dir: *const libc::c_char,
+    staged_spill_over_min_free_bytes: u64,
+    stale_seal_policy: responses::FFIStaleSealPolicy,
+) -> *mut responses::InitSectorBuilderResponse {
+    init_log();
+
+    let result = from_ffi_sector_class(sector_class).and_then(|sector_class| {
+        let worker_affinity = into_worker_affinity(worker_cpu_lists_ptr, worker_cpu_lists_len)?;
+        let prover_id = prover_id_from_bytes(prover_id)?;
+
+        SectorBuilder::init_from_metadata(
+            sector_class,
+            SectorId::from(last_used_sector_id),
+            c_str_to_rust_str(metadata_dir).to_string(),
+            prover_id,
+            c_str_to_rust_str(sealed_sector_dir).to_string(),
+            c_str_to_rust_str(staged_sector_dir).to_string(),
+            SectorBuilderOptions {
+                max_num_staged_sectors,
+                worker_affinity,
+                staged_data_key: staged_data_key.as_ref().map(|key| StagedDataKey::new(*key)),
+                sector_access_proto: if sector_access_miner.is_null() {
+                    SectorAccessProto::default()
+                } else {
+                    SectorAccessProto::Lotus(c_str_to_rust_str(sector_access_miner).to_string())
+                },
+                scheduler_config: scheduler_config.into(),
+                staged_spill_over: if secondary_staged_sector_dir.is_null() {
+                    None
+                } else {
+                    Some(StagedSpillOver {
+                        dir: PathBuf::from(c_str_to_rust_str(secondary_staged_sector_dir).to_string()),
+                        min_free_bytes: staged_spill_over_min_free_bytes,
+                    })
+                },
+                stale_seal_policy: stale_seal_policy.into(),
+            },
+        )
+    });
+
+    let mut response = responses::InitSectorBuilderResponse::default();
+
+    match result {
+        Ok(sb) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_builder = sector_builders().insert(sb);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Returns the sector class, prover id, directories and limits the
+/// SectorBuilder is running with, with which a caller can verify that it's
+/// attached to the instance it expects. Limits are reported as they are now,
+/// which may differ from those the SectorBuilder was initialized with.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_config(
+    handle: u64,
+) -> *mut responses::GetConfigResponse {
+    init_log();
+
+    let mut response = responses::GetConfigResponse::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.get_config() {
+        Ok(config) => {
+            let filecoin_proofs::SectorClass(
+                filecoin_proofs::SectorSize(sector_size),
+                filecoin_proofs::PoRepProofPartitions(partitions),
+            ) = config.sector_class;
+
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_size = sector_size;
+            response.porep_proof_partitions = partitions;
+            response.prover_id = prover_id_to_bytes(&config.prover_id);
+            response.metadata_dir =
+                rust_str_to_c_str(config.metadata_dir.to_string_lossy().into_owned());
+            response.sealed_sector_dir =
+                rust_str_to_c_str(config.sealed_sector_dir.to_string_lossy().into_owned());
+            response.staged_sector_dir =
+                rust_str_to_c_str(config.staged_sector_dir.to_string_lossy().into_owned());
+            response.secondary_staged_sector_dir = config
+                .secondary_staged_sector_dir
+                .map_or(ptr::null(), |dir| rust_str_to_c_str(dir.to_string_lossy().into_owned()));
+            response.max_num_staged_sectors = config.max_num_staged_sectors;
+            response.max_staged_bytes = config.quota.max_staged_bytes.unwrap_or(0);
+            response.max_sealed_bytes = config.quota.max_sealed_bytes.unwrap_or(0);
+            response.max_staged_sectors = config.quota.max_staged_sectors.unwrap_or(0);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Returns how the previous instance of the SectorBuilder stopped: a crash is
+/// told apart from a clean shutdown, after which the staged sectors needn't
+/// be scanned. Also returns the sectors whose sealing was interrupted (and
+/// has been restarted) and the staged sectors which were truncated to the
+/// pieces recorded in their metadata after a crash.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_startup_report(
+    handle: u64,
+) -> *mut responses::GetStartupReportResponse {
+    init_log();
+
+    let mut response = responses::GetStartupReportResponse::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    let report = builder.startup_report();
+
+    response.last_shutdown = match report.last_shutdown {
+        LastShutdown::Fresh => FFILastShutdown::Fresh,
+        LastShutdown::Clean => FFILastShutdown::Clean,
+        LastShutdown::Crash => FFILastShutdown::Crash,
+    };
+
+    let interrupted_seals: Vec<u64> = report.interrupted_seals.into_iter().map(u64::from).collect();
+    let truncated_sectors: Vec<u64> = report.truncated_sectors.into_iter().map(u64::from).collect();
+
+    response.interrupted_seals_len = interrupted_seals.len();
+    response.interrupted_seals_ptr = interrupted_seals.as_ptr();
+
+    response.truncated_sectors_len = truncated_sectors.len();
+    response.truncated_sectors_ptr = truncated_sectors.as_ptr();
+
+    // we'll free this stuff when we free the GetStartupReportResponse
+    mem::forget(interrupted_seals);
+    mem::forget(truncated_sectors);
+
+    raw_ptr(response)
+}
+
+/// Unseals and returns the bytes associated with the provided piece key.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_sealed_sector(
+    handle: u64,
+    piece_key: *const libc::c_char,
+) -> *mut responses::ReadPieceFromSealedSectorResponse {
+    init_log();
+
+    let mut response: responses::ReadPieceFromSealedSectorResponse = Default::default();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.read_piece_from_sealed_sector(String::from(piece_key)) {
+        Ok(piece_bytes) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.data_ptr = piece_bytes.as_ptr();
+            response.data_len = piece_bytes.len();
+            mem::forget(piece_bytes);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Starts unsealing the bytes associated with the provided piece key (see
+/// read_piece_from_sealed_sector) without waiting for them, returning the id
+/// of the operation right away. The bytes are retrieved by polling the
+/// operation, which can be cancelled to abandon the unseal.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_sealed_sector_async(
+    handle: u64,
+    piece_key: *const libc::c_char,
+) -> *mut responses::ReadPieceAsyncResponse {
+    init_log();
+
+    let mut response = responses::ReadPieceAsyncResponse::default();
+
+    let piece_key = String::from(c_str_to_rust_str(piece_key));
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    response.op_id = operations().start(
+        |cancel| builder.start_read_piece_from_sealed_sector(piece_key, Some(cancel)),
+        |piece_bytes| OperationOutput::Piece(piece_bytes.to_vec()),
+    );
+
+    raw_ptr(response)
+}
+
+/// Unseals and returns the bytes associated with the provided piece key, along
+/// with the piece's comm_p, its inclusion proof and the comm_d of its sector.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_read_piece_with_proof(
+    handle: u64,
+    piece_key: *const libc::c_char,
+) -> *mut responses::ReadPieceWithProofResponse {
+    init_log();
+
+    let mut response: responses::ReadPieceWithProofResponse = Default::default();
+
+    let piece_key = c_str_to_rust_str(piece_key);
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.read_piece_with_proof(String::from(piece_key)) {
+        Ok(piece) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.data_ptr = piece.bytes.as_ptr();
+            response.data_len = piece.bytes.len();
+            response.sector_id = u64::from(piece.sector_id);
+            response.comm_d = piece.comm_d;
+            response.comm_p = piece.comm_p;
+            response.piece_inclusion_proof_ptr = piece.piece_inclusion_proof.as_ptr();
+            response.piece_inclusion_proof_len = piece.piece_inclusion_proof.len();
+            mem::forget(piece.bytes);
+            mem::forget(piece.piece_inclusion_proof);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// For demo purposes. Seals all staged sectors with the provided ticket.
+/// Returns the ids of the sectors which were scheduled for sealing, and for
+/// every staged sector which hasn't been sealed whether it was scheduled,
+/// skipped or couldn't be scheduled.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_seal_all_staged_sectors(
+    handle: u64,
+    seal_ticket: FFISealTicket,
+) -> *mut responses::SealAllStagedSectorsResponse {
+    init_log();
+
+    let mut response: responses::SealAllStagedSectorsResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.seal_all_staged_sectors(from_ffi_seal_ticket(seal_ticket)) {
+        Ok(results) => {
+            let sector_ids: Vec<u64> = results
+                .iter()
+                .filter(|result| result.outcome == SealScheduleOutcome::Scheduled)
+                .map(|result| u64::from(result.sector_id))
+                .collect();
+
+            let results: Vec<responses::FFISealScheduleResult> =
+                results.iter().map(into_ffi_seal_schedule_result).collect();
+
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_ids_ptr = sector_ids.as_ptr();
+            response.sector_ids_len = sector_ids.len();
+            response.results_ptr = results.as_ptr();
+            response.results_len = results.len();
+            mem::forget(sector_ids);
+            mem::forget(results);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Sets the maximum number of staged sectors which accept pieces. Lowering
+/// the number schedules the surplus sectors for sealing.
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_max_num_staged_sectors(
+    handle: u64,
+    max_num_staged_sectors: u64,
+) -> *mut responses::SetMaxNumStagedSectorsResponse {
+    init_log();
+
+    let mut response: responses::SetMaxNumStagedSectorsResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_max_num_staged_sectors(max_num_staged_sectors) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Returns the sectors which have been scheduled for sealing and are waiting
+/// for a worker, in the order in which they will be handed to the workers.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_get_seal_queue(
+    handle: u64,
+) -> *mut responses::GetSealQueueResponse {
+    init_log();
+
+    let mut response: responses::GetSealQueueResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.get_seal_queue() {
+        Ok(entries) => {
+            let entries = entries
+                .into_iter()
+                .map(|entry| responses::FFISealQueueEntry {
+                    sector_id: u64::from(entry.sector_id),
+                    enqueued_at: entry.enqueued_at.0,
+                    priority: entry.priority,
+                })
+                .collect::<Vec<responses::FFISealQueueEntry>>();
+
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.entries_len = entries.len();
+            response.entries_ptr = entries.as_ptr();
+
+            mem::forget(entries);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Changes the priority of a sector waiting for a worker. Sectors with a
+/// higher priority are handed to a worker first; sectors are scheduled with
+/// priority 0.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_seal_priority(
+    handle: u64,
+    sector_id: u64,
+    priority: i32,
+) -> *mut responses::SetSealPriorityResponse {
+    init_log();
+
+    let mut response: responses::SetSealPriorityResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_seal_priority(SectorId::from(sector_id), priority) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Moves a sector waiting for a worker to the head of the seal queue.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_promote_seal(
+    handle: u64,
+    sector_id: u64,
+) -> *mut responses::PromoteSealResponse {
+    init_log();
+
+    let mut response: responses::PromoteSealResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.promote_seal(SectorId::from(sector_id)) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Cancels sealing the sector with the provided id. A sector waiting for a
+/// worker is removed from the seal queue; a sector which is being sealed is
+/// stopped between the phases of its seal. Either way, its seal fails.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_cancel_seal(
+    handle: u64,
+    sector_id: u64,
+) -> *mut responses::CancelSealResponse {
+    init_log();
+
+    let mut response: responses::CancelSealResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.cancel_seal(SectorId::from(sector_id)) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Seals sectors in child processes running the provided worker binary, which
+/// are restarted up to max_restarts times if they crash, instead of in the
+/// builder's process. A null worker_bin seals sectors in process again.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_seal_isolation(
+    handle: u64,
+    worker_bin: *const libc::c_char,
+    max_restarts: u32,
+) -> *mut responses::SetSealIsolationResponse {
+    init_log();
+
+    let mut response: responses::SetSealIsolationResponse = Default::default();
+
+    let seal_isolation = if worker_bin.is_null() {
+        SealIsolation::InProcess
+    } else {
+        SealIsolation::ChildProcess {
+            worker_bin: c_str_to_pbuf(worker_bin),
+            max_restarts,
+        }
+    };
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_seal_isolation(seal_isolation) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Copies the replica of each sector into the provided directory, e.g. on a
+/// mounted backup volume, once it has been sealed. A null directory stops
+/// copying replicas. Like every replicator, it must be set again after the
+/// builder has been initialized, which restarts interrupted copies.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_replication_dir(
+    handle: u64,
+    replication_dir: *const libc::c_char,
+) -> *mut responses::SetReplicatorResponse {
+    init_log();
+
+    let replicator: Option<Arc<dyn SealedSectorReplicator>> = if replication_dir.is_null() {
+        None
+    } else {
+        Some(Arc::new(DirReplicator {
+            dir: c_str_to_pbuf(replication_dir),
+        }))
+    };
+
+    set_replicator(handle, replicator)
+}
+
+/// Copies the replica of each sector, once it has been sealed, by running the
+/// provided program (e.g. a script wrapping rsync or an object store's CLI)
+/// with the provided arguments followed by the sector id and the path of the
+/// replica. Whatever the program prints is recorded as the location of the
+/// copy. A null program stops copying replicas.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_replication_command(
+    handle: u64,
+    program: *const libc::c_char,
+    args_ptr: *const *const libc::c_char,
+    args_len: libc::size_t,
+) -> *mut responses::SetReplicatorResponse {
+    init_log();
+
+    let replicator: Option<Arc<dyn SealedSectorReplicator>> = if program.is_null() {
+        None
+    } else {
+        let args = if args_ptr.is_null() {
+            vec![]
+        } else {
+            from_raw_parts(args_ptr, args_len)
+                .iter()
+                .map(|arg| c_str_to_rust_str(*arg).to_string())
+                .collect()
+        };
+
+        Some(Arc::new(CommandReplicator {
+            program: c_str_to_pbuf(program),
+            args,
+        }))
+    };
+
+    set_replicator(handle, replicator)
+}
+
+unsafe fn set_replicator(
+    handle: u64,
+    replicator: Option<Arc<dyn SealedSectorReplicator>>,
+) -> *mut responses::SetReplicatorResponse {
+    let mut response: responses::SetReplicatorResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_replicator(replicator) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Copies the replica of the sealed sector with the provided id with the
+/// replicator in the background, e.g. to retry a copy which failed.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_replicate_sealed_sector(
+    handle: u64,
+    sector_id: u64,
+) -> *mut responses::ReplicateSealedSectorResponse {
+    init_log();
+
+    let mut response: responses::ReplicateSealedSectorResponse = Default::default();
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.replicate_sealed_sector(SectorId::from(sector_id)) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Limits the memory, in bytes, which the sectors being sealed are estimated to
+/// require. Sectors which would exceed it wait until others have been sealed.
+/// A watermark of 0 removes the limit.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_seal_memory_watermark(
+    handle: u64,
+    memory_watermark: u64,
+) -> *mut responses::SetSealMemoryWatermarkResponse {
+    init_log();
+
+    let mut response: responses::SetSealMemoryWatermarkResponse = Default::default();
+
+    let memory_watermark = if memory_watermark == 0 {
+        None
+    } else {
+        Some(memory_watermark)
+    };
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_seal_memory_watermark(memory_watermark) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Configures how pieces are written to staged sector-files: the number of
+/// bytes read from a piece and written at once, and the strategy with which
+/// they're written. A buffer size of 0 selects the default buffer size.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_staged_write_config(
+    handle: u64,
+    buffer_size: u64,
+    strategy: responses::FFIStagedWriteStrategy,
+) -> *mut responses::SetStagedWriteConfigResponse {
+    init_log();
+
+    let mut response: responses::SetStagedWriteConfigResponse = Default::default();
+
+    let staged_write_config = StagedWriteConfig {
+        buffer_size: if buffer_size == 0 {
+            DEFAULT_STAGED_WRITE_BUFFER_SIZE
+        } else {
+            buffer_size as usize
+        },
+        strategy: strategy.into(),
+    };
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_staged_write_config(staged_write_config) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Limits the rate, in bytes per second, at which pieces are read while
+/// they're added. A limit of 0 removes the limit.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_ingest_rate_limit(
+    handle: u64,
+    bytes_per_sec: u64,
+) -> *mut responses::SetIngestRateLimitResponse {
+    init_log();
+
+    let mut response: responses::SetIngestRateLimitResponse = Default::default();
+
+    let bytes_per_sec = if bytes_per_sec == 0 {
+        None
+    } else {
+        Some(bytes_per_sec)
+    };
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_ingest_rate_limit(bytes_per_sec) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Limits how long seal, unseal and PoSt tasks may run, in seconds. A timeout
+/// of 0 leaves tasks of that kind unlimited.
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_task_timeouts(
+    handle: u64,
+    seal_timeout_secs: u64,
+    unseal_timeout_secs: u64,
+    post_timeout_secs: u64,
+) -> *mut responses::SetTaskTimeoutsResponse {
+    init_log();
+
+    let mut response: responses::SetTaskTimeoutsResponse = Default::default();
+
+    let into_timeout = |secs: u64| if secs == 0 { None } else { Some(Duration::from_secs(secs)) };
+
+    let task_timeouts = TaskTimeouts {
+        seal: into_timeout(seal_timeout_secs),
+        unseal: into_timeout(unseal_timeout_secs),
+        post: into_timeout(post_timeout_secs),
+    };
+
+    let builder = resolve_handle!(sector_builders(), handle, response);
+
+    match builder.set_task_timeouts(task_timeouts) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Verifies the output of seal. The 32-byte prover id is validated as in
+/// sector_builder_ffi_init_sector_builder.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_verify_seal(
+    sector_size: u64,
+    comm_r: &[u8; 32],
+    comm_d: &[u8; 32],
+    comm_r_star: &[u8; 32],
+    prover_id: &[u8; 32],
+    sector_id: u64,
+    proof_ptr: *const u8,
+    proof_len: libc::size_t,
+) -> *mut responses::VerifySealResponse {
+    init_log();
+
+    let mut response: responses::VerifySealResponse = Default::default();
+
+    // the linked proofs verify seals of every sector size with two PoRep
+    // partitions
+    let porep_config = filecoin_proofs::PoRepConfig(
+        filecoin_proofs::SectorSize(sector_size),
+        filecoin_proofs::PoRepProofPartitions(2),
+    );
+
+    let result = prover_id_from_bytes(prover_id).and_then(|prover_id| {
+        filecoin_proofs::verify_seal(
+            porep_config,
+            *comm_r,
+            *comm_d,
+            *comm_r_star,
+            &prover_id,
+            SectorId::from(sector_id),
+            from_raw_parts(proof_ptr, proof_len),
+        )
+    });
+
+    match result {
+        Ok(is_valid) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.is_valid = is_valid;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Verifies that a proof-of-spacetime is valid.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_verify_post(
+    sector_size: u64,
+    challenge_seed: &[u8; 32],
+    sector_ids_ptr: *const u64,
+    sector_ids_len: libc::size_t,
+    faulty_sector_ids_ptr: *const u64,
+    faulty_sector_ids_len: libc::size_t,
+    flattened_comm_rs_ptr: *const u8,
+    flattened_comm_rs_len: libc::size_t,
+    proof_ptr: *const u8,
+    proof_len: libc::size_t,
+) -> *mut filecoin_proofs_ffi::responses::VerifyPoStResponse {
+    init_log();
+
+    filecoin_proofs_ffi::api::verify_post(
+        sector_size,
+        challenge_seed,
+        sector_ids_ptr,
+        sector_ids_len,
+        faulty_sector_ids_ptr,
+        faulty_sector_ids_len,
+        flattened_comm_rs_ptr,
+        flattened_comm_rs_len,
+        proof_ptr,
+        proof_len,
+    )
+}
+
+pub type SimpleSectorBuilder = sector_builder::SimpleSectorBuilder;
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_init_simple_sector_builder(
+    sector_class: FFISectorClass,
+    sealed_sector_dir: *const libc::c_char,
+    staged_sector_dir: *const libc::c_char,
+    max_num_staged_sectors: u64,
+) -> *mut responses::InitSimpleSectorBuilderResponse {
+    init_log();
+
+    let result = from_ffi_sector_class(sector_class).and_then(|sector_class| {
+        SimpleSectorBuilder::new(
+            sector_class,
+            c_str_to_rust_str(sealed_sector_dir).to_string(),
+            c_str_to_rust_str(staged_sector_dir).to_string(),
+            max_num_staged_sectors,
+        )
+    });
+
+    let mut response = responses::InitSimpleSectorBuilderResponse::default();
+
+    match result {
+        Ok(sb) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_builder = simple_sector_builders().insert(sb);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_init_simple_sector_builder_response(
+    ptr: *mut responses::InitSimpleSectorBuilderResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
+/// Destroys the SimpleSectorBuilder with the provided handle. Destroying it
+/// again produces an error.
+///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_seal_staged_sector(
-    ptr: *mut SimpleSectorBuilder,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_simple_sector_builder(
+    handle: u64,
+) -> *mut responses::DestroySectorBuilderResponse {
+    init_log();
+
+    let mut response: responses::DestroySectorBuilderResponse = Default::default();
+
+    if let Err(err) = simple_sector_builders().remove(handle) {
+        let (code, ptr) = err_code_and_msg(&err.into());
+        response.status_code = code;
+        response.error_msg = ptr;
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_add_piece_first(
+    handle: u64,
+    miner: *const libc::c_char,
+    sectors_ptr: *const responses::FFIPendingStagedSectorMetadata,
+    sectors_len: libc::size_t,
+    piece_bytes_amount: u64,
+    new_sector_id: u64,
+) -> *mut responses::AddPieceResponse {
+    init_log();
+
+    let mut response: responses::AddPieceResponse = Default::default();
+
+    let staged_sectors = into_staged_sectors(sectors_ptr, sectors_len);
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    let result = staged_sectors.and_then(|staged_sectors| {
+        builder.add_piece_first(
+            c_str_to_rust_str(miner).into(),
+            staged_sectors,
+            piece_bytes_amount,
+            new_sector_id.into(),
+        )
+    });
+
+    match result {
+        Ok(sector_id) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_id = u64::from(sector_id);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// DESTRUCTORS
+//////////////
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_add_piece_response(
+    ptr: *mut responses::AddPieceResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+#[cfg(not(target_os = "windows"))]
+pub unsafe extern "C" fn sector_builder_ffi_add_piece_second(
+    handle: u64,
     miner: *const libc::c_char,
     sector_ptr: *const responses::FFIPendingStagedSectorMetadata,
-    prover_id: &[u8; 31],
-) -> *mut responses::SealStagedSectorResponse {
+    piece_key: *const libc::c_char,
+    piece_fd_raw: libc::c_int,
+    piece_bytes_amount: u64,
+) -> *mut responses::AddPieceSecondResponse {
     init_log();
 
-    let mut response: responses::SealStagedSectorResponse = Default::default();
+    let sector = into_staged_sector_metadata(sector_ptr);
 
-    match (*ptr).seal_staged_sector(
-        c_str_to_rust_str(miner).into(),
-        &mut into_staged_sector_metadata(sector_ptr),
-        *prover_id,
-    ) {
+    let mut response: responses::AddPieceSecondResponse = Default::default();
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    let result = sector.and_then(|sector| {
+        builder.add_piece_second(
+            c_str_to_rust_str(miner).into(),
+            sector,
+            c_str_to_rust_str(piece_key).into(),
+            FileDescriptorRef::new(piece_fd_raw),
+            piece_bytes_amount,
+        )
+    });
+
+    match result {
         Ok(meta) => {
-            let pieces = meta
-                .pieces
-                .iter()
-                .map(into_ffi_piece_metadata)
-                .collect::<Vec<FFIPieceMetadata>>();
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_ptr = raw_ptr(into_ffi_pending_staged_sector_metadata(&meta));
+            response.sector_len = 1;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_add_piece_second_response(
+    ptr: *mut responses::AddPieceSecondResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+/// Writes several pieces into the provided staged sector in one call,
+/// returning the sector's metadata once, with all of the pieces added. The
+/// pieces must all fit into the sector. If any piece can't be written, none
+/// of them are added.
+///
+#[no_mangle]
+#[cfg(not(target_os = "windows"))]
+pub unsafe extern "C" fn sector_builder_ffi_add_pieces_second(
+    handle: u64,
+    miner: *const libc::c_char,
+    sector_ptr: *const responses::FFIPendingStagedSectorMetadata,
+    pieces_ptr: *const FFIPieceToAdd,
+    pieces_len: libc::size_t,
+) -> *mut responses::AddPieceSecondResponse {
+    init_log();
+
+    let sector = into_staged_sector_metadata(sector_ptr);
+
+    let pieces: Vec<_> = from_raw_parts(pieces_ptr, pieces_len)
+        .iter()
+        .map(|p| {
+            (
+                c_str_to_rust_str(p.piece_key).to_string(),
+                FileDescriptorRef::new(p.piece_fd_raw),
+                p.piece_bytes_amount,
+            )
+        })
+        .collect();
+
+    let mut response: responses::AddPieceSecondResponse = Default::default();
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    let result = sector.and_then(|sector| {
+        builder.add_pieces_second(c_str_to_rust_str(miner).into(), sector, pieces)
+    });
+
+    match result {
+        Ok(meta) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_ptr = raw_ptr(into_ffi_pending_staged_sector_metadata(&meta));
+            response.sector_len = 1;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_add_pieces_second_response(
+    ptr: *mut responses::AddPieceSecondResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_read_piece_from_specified_sealed_sector(
+    handle: u64,
+    miner: *const libc::c_char,
+    sector_ptr: *const responses::FFISealedSectorMetadata,
+    piece_key: *const libc::c_char,
+    prover_id: &[u8; 32],
+) -> *mut responses::ReadPieceFromSealedSectorResponse {
+    init_log();
+
+    let mut response: responses::ReadPieceFromSealedSectorResponse = Default::default();
+
+    let sector = into_sealed_sector_metadata(sector_ptr);
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    let result = sector.and_then(|sector| {
+        let prover_id = prover_id_from_bytes(prover_id)?;
+
+        builder.read_piece_from_sealed_sector(
+            c_str_to_rust_str(miner).into(),
+            &sector,
+            c_str_to_rust_str(piece_key).into(),
+            prover_id,
+        )
+    });
+
+    match result {
+        Ok(piece_bytes) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.data_ptr = piece_bytes.as_ptr();
+            response.data_len = piece_bytes.len();
+            mem::forget(piece_bytes);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_read_piece_from_specified_sealed_sector_response(
+    ptr: *mut responses::ReadPieceFromSealedSectorResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+/// Sets how long, in seconds, the files into which pieces are unsealed are
+/// kept after they've been provisioned. A retention of 0 deletes each file
+/// as soon as its piece has been read.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_set_unsealed_retention(
+    handle: u64,
+    keep_for_secs: u64,
+) -> *mut responses::SetUnsealedRetentionResponse {
+    init_log();
+
+    let mut response: responses::SetUnsealedRetentionResponse = Default::default();
+
+    let retention = if keep_for_secs == 0 {
+        UnsealedRetention::DeleteAfterRead
+    } else {
+        UnsealedRetention::KeepFor(Duration::from_secs(keep_for_secs))
+    };
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    builder.set_unsealed_retention(retention);
+
+    response.status_code = FCPResponseStatus::FCPNoError;
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_unsealed_retention_response(
+    ptr: *mut responses::SetUnsealedRetentionResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+/// Deletes the file into which the provided miner's sector was unsealed, if
+/// there is one, e.g. once the host has read a piece it unsealed itself.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_purge_unsealed(
+    handle: u64,
+    miner: *const libc::c_char,
+    sector_id: u64,
+) -> *mut responses::PurgeUnsealedResponse {
+    init_log();
+
+    let mut response: responses::PurgeUnsealedResponse = Default::default();
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    match builder.purge_unsealed(&c_str_to_rust_str(miner), SectorId::from(sector_id)) {
+        Ok(_) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_purge_unsealed_response(
+    ptr: *mut responses::PurgeUnsealedResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_seal_staged_sector(
+    handle: u64,
+    miner: *const libc::c_char,
+    sector_ptr: *const responses::FFIPendingStagedSectorMetadata,
+    seal_ticket: FFISealTicket,
+    prover_id: &[u8; 32],
+) -> *mut responses::SealStagedSectorResponse {
+    init_log();
 
-            let snark_proof = meta.proof.clone();
-
-            let sector = responses::FFISealedSectorMetadata {
-                comm_d: meta.comm_d,
-                comm_r: meta.comm_r,
-                comm_r_star: meta.comm_r_star,
-                pieces_len: pieces.len(),
-                pieces_ptr: pieces.as_ptr(),
-                proofs_len: snark_proof.len(),
-                proofs_ptr: snark_proof.as_ptr(),
-                sector_access: rust_str_to_c_str(meta.sector_access.clone()),
-                sector_id: u64::from(meta.sector_id),
-                health: FFISealedSectorHealth::Unknown, // not used
-            };
+    let mut response: responses::SealStagedSectorResponse = Default::default();
 
-            mem::forget(snark_proof);
-            mem::forget(pieces);
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    let result = into_staged_sector_metadata(sector_ptr).and_then(|mut sector| {
+        let prover_id = prover_id_from_bytes(prover_id)?;
+
+        builder.seal_staged_sector(
+            c_str_to_rust_str(miner).into(),
+            &mut sector,
+            from_ffi_seal_ticket(seal_ticket),
+            prover_id,
+        )
+    });
+
+    match result {
+        Ok(meta) => {
+            // health is not used
+            let sector = into_ffi_sealed_sector_metadata(&meta, FFISealedSectorHealth::Unknown);
 
             response.status_code = FCPResponseStatus::FCPNoError;
             response.sector_ptr = raw_ptr(sector);
@@ -781,36 +2626,39 @@ pub unsafe extern "C" fn sector_builder_ffi_destroy_seal_staged_sector_response(
 
 #[no_mangle]
 pub unsafe extern "C" fn sector_builder_ffi_generate_post_first(
-    ptr: *mut SimpleSectorBuilder,
+    handle: u64,
     challenge_seed: &[u8; 32],
     faults_ptr: *const u64,
     faults_len: libc::size_t,
     sectors_ptr: *const responses::FFISealedSectorMetadata,
     sectors_len: libc::size_t,
+    post_config: FFIPoStConfig,
 ) -> *mut responses::GeneratePoStFirstResponse {
     init_log();
 
     info!("generate_post_first: {}", "start");
 
+    let mut response = responses::GeneratePoStFirstResponse::default();
+
     let faults = from_raw_parts(faults_ptr, faults_len)
         .iter()
         .map(|x| SectorId::from(*x))
         .collect();
 
-    let sectors: Vec<&responses::FFISealedSectorMetadata> = from_raw_parts(sectors_ptr, sectors_len).iter().collect();
-    let mut sealed_sectors: HashMap<SectorId, SealedSectorMetadata> = HashMap::new();
-    for s in sectors {
-        let meta = into_sealed_sector_metadata(s);
-        sealed_sectors.insert(meta.sector_id, meta);
-    }
+    let sealed_sectors = into_sealed_sectors(sectors_ptr, sectors_len);
 
-    let result = (*ptr).generate_post_first(
-        challenge_seed,
-        faults,
-        &sealed_sectors,
-    );
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
 
-    let mut response = responses::GeneratePoStFirstResponse::default();
+    let result = sealed_sectors.and_then(|sealed_sectors| {
+        let post_config = from_ffi_post_config(post_config)?;
+
+        builder.generate_post_first(
+            challenge_seed,
+            faults,
+            &sealed_sectors,
+            post_config,
+        )
+    });
 
     match result {
         Ok(challenges) => {
@@ -848,7 +2696,7 @@ pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_post_first_response
 
 #[no_mangle]
 pub unsafe extern "C" fn sector_builder_ffi_generate_post_second(
-    ptr: *mut SimpleSectorBuilder,
+    handle: u64,
     miner: *const libc::c_char,
     challenges_ptr: *const responses::FFIChallenge,
     challenges_len: libc::size_t,
@@ -856,36 +2704,47 @@ pub unsafe extern "C" fn sector_builder_ffi_generate_post_second(
     faults_len: libc::size_t,
     sectors_ptr: *const responses::FFISealedSectorMetadata,
     sectors_len: libc::size_t,
+    deadline: u64,
+    replica_paths_ptr: *const FFIReplicaPath,
+    replica_paths_len: libc::size_t,
+    post_config: FFIPoStConfig,
 ) -> *mut responses::GeneratePoStResponse {
     init_log();
 
     info!("generate_post_second: {}", "start");
 
-    let faults = from_raw_parts(faults_ptr, faults_len)
+    let mut response = responses::GeneratePoStResponse::default();
+
+    let faults: Vec<SectorId> = from_raw_parts(faults_ptr, faults_len)
         .iter()
         .map(|x| SectorId::from(*x))
         .collect();
 
     let challenges = from_raw_parts(challenges_ptr, challenges_len);
 
-    let sectors: Vec<&responses::FFISealedSectorMetadata> = from_raw_parts(sectors_ptr, sectors_len).iter().collect();
-    let mut sealed_sectors: HashMap<SectorId, SealedSectorMetadata> = HashMap::new();
-    for s in sectors {
-        let meta = into_sealed_sector_metadata(s);
-        sealed_sectors.insert(meta.sector_id, meta);
-    }
+    let sealed_sectors = into_sealed_sectors(sectors_ptr, sectors_len);
 
-    let result = (*ptr).generate_post_second(
-        c_str_to_rust_str(miner).into(),
-        &challenges.iter().map(|c| Challenge {
-            sector: c.sector.into(),
-            leaf: c.leaf,
-        }).collect(),
-        faults,
-        &sealed_sectors,
-    );
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
 
-    let mut response = responses::GeneratePoStResponse::default();
+    let result = sealed_sectors.and_then(|sealed_sectors| {
+        let post_config = from_ffi_post_config(post_config)?;
+
+        builder.generate_post_second(
+            c_str_to_rust_str(miner).into(),
+            &challenges.iter().map(|c| Challenge {
+                sector: c.sector.into(),
+                leaf: c.leaf,
+            }).collect(),
+            faults.clone(),
+            &sealed_sectors,
+            PoStOptions {
+                deadline: into_post_deadline(deadline, false),
+                replica_paths: into_replica_paths(replica_paths_ptr, replica_paths_len),
+                post_config,
+                cancel: None,
+            },
+        )
+    });
 
     match result {
         Ok(proof) => {
@@ -894,8 +2753,14 @@ pub unsafe extern "C" fn sector_builder_ffi_generate_post_second(
             response.proof_len = proof.len();
             response.proof_ptr = proof.as_ptr();
 
+            let faults: Vec<u64> = faults.into_iter().map(u64::from).collect();
+
+            response.faults_len = faults.len();
+            response.faults_ptr = faults.as_ptr();
+
             // we'll free this stuff when we free the GeneratePoSTResponse
             mem::forget(proof);
+            mem::forget(faults);
         }
         Err(err) => {
             let (code, ptr) = err_code_and_msg(&err);
@@ -918,127 +2783,488 @@ pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_post_second_respons
 
 #[no_mangle]
 pub unsafe extern "C" fn sector_builder_ffi_get_sectors_ready_for_sealing(
-    ptr: *mut SimpleSectorBuilder,
+    handle: u64,
     sectors_ptr: *const responses::FFIPendingStagedSectorMetadata,
     sectors_len: libc::size_t,
+    fill_ratio: f64,
+    max_age_secs: u64,
+    deadline_margin_secs: u64,
     seal_all_staged_sectors: bool,
 ) -> *mut responses::GetSectorsReadyForSealingResponse {
     init_log();
 
     let mut response: responses::GetSectorsReadyForSealingResponse = Default::default();
 
-    let sectors: Vec<&responses::FFIPendingStagedSectorMetadata> = from_raw_parts(sectors_ptr, sectors_len).iter().collect();
-    let mut staged_sectors: HashMap<SectorId, StagedSectorMetadata> = HashMap::new();
-    for s in sectors {
-        staged_sectors.insert(s.sector_id.into(), into_staged_sector_metadata(s));
+    let staged_sectors = into_staged_sectors(sectors_ptr, sectors_len);
+
+    let builder = resolve_handle!(simple_sector_builders(), handle, response);
+
+    // a fill ratio, maximum age or deadline margin of 0 doesn't limit how
+    // long sectors are staged
+    let auto_seal_policy = AutoSealPolicy {
+        fill_ratio: if fill_ratio > 0.0 { Some(fill_ratio) } else { None },
+        max_age: if max_age_secs > 0 { Some(Duration::from_secs(max_age_secs)) } else { None },
+        deadline_margin: if deadline_margin_secs > 0 { Some(Duration::from_secs(deadline_margin_secs)) } else { None },
+    };
+
+    match staged_sectors {
+        Ok(staged_sectors) => {
+            let sector_ids: Vec<u64> = builder.get_sectors_ready_for_sealing(
+                staged_sectors,
+                &auto_seal_policy,
+                seal_all_staged_sectors,
+            ).iter().map(|s| u64::from(*s)).collect();
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_ids_ptr = sector_ids.as_ptr();
+            response.sector_ids_len = sector_ids.len();
+            mem::forget(sector_ids);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
     }
 
-    let sector_ids: Vec<u64> = (*ptr).get_sectors_ready_for_sealing(
-        staged_sectors,
-        seal_all_staged_sectors,
-    ).iter().map(|s| u64::from(*s)).collect();
-    response.status_code = FCPResponseStatus::FCPNoError;
-    response.sector_ids_ptr = sector_ids.as_ptr();
-    response.sector_ids_len = sector_ids.len();
-    mem::forget(sector_ids);
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_sectors_ready_for_sealing_response(
+    ptr: *mut responses::GetSectorsReadyForSealingResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn into_staged_sector_metadata(
+    sector_ptr: *const responses::FFIPendingStagedSectorMetadata,
+) -> Result<StagedSectorMetadata, failure::Error> {
+    let pieces: Vec<&FFIPieceMetadata> = from_raw_parts((*sector_ptr).pieces_ptr, (*sector_ptr).pieces_len).iter().collect();
+    let sector_access = SectorAccess::new(c_str_to_rust_str((*sector_ptr).sector_access))?;
+
+    Ok(StagedSectorMetadata {
+        sector_id: (*sector_ptr).sector_id.into(),
+        sector_access,
+        pieces: pieces.iter().map(|p| PieceMetadata {
+            piece_key: String::from(c_str_to_rust_str(p.piece_key)),
+            num_bytes: UnpaddedBytesAmount(p.num_bytes),
+            comm_p: Some(p.comm_p),
+            piece_inclusion_proof: Some(from_raw_parts(p.piece_inclusion_proof_ptr, p.piece_inclusion_proof_len).to_vec()),
+            idempotency_key: None,
+            store_until: match p.store_until {
+                0 => None,
+                store_until => Some(SecondsSinceEpoch(store_until)),
+            },
+            deal_id: if p.has_deal_id { Some(p.deal_id) } else { None },
+            labels: from_ffi_piece_labels(p.labels_ptr, p.labels_len),
+        }).collect(),
+        seal_status: SealStatus::Pending,
+        seal_ticket: None,
+        staged_data: Default::default(),
+        staged_dir: if (*sector_ptr).in_secondary_staged_dir {
+            StagedSectorDir::Secondary
+        } else {
+            StagedSectorDir::Primary
+        },
+        created_at: match (*sector_ptr).created_at {
+            0 => None,
+            created_at => Some(SecondsSinceEpoch(created_at)),
+        },
+        repairing: false,
+    })
+}
+
+/// Reconstructs sealed sector metadata from its FFI representation, which
+/// carries all of the metadata, so that the round-trip through the FFI is
+/// lossless. Fails if the sector access doesn't name a sector-file in the
+/// directory holding it, so that hosts can't direct the builder to arbitrary
+/// paths.
+#[no_mangle]
+pub unsafe extern "C" fn into_sealed_sector_metadata(
+    sector_ptr: *const responses::FFISealedSectorMetadata,
+) -> Result<SealedSectorMetadata, failure::Error> {
+    let pieces: Vec<&FFIPieceMetadata> = from_raw_parts((*sector_ptr).pieces_ptr, (*sector_ptr).pieces_len).iter().collect();
+    let sector_access = SectorAccess::new(c_str_to_rust_str((*sector_ptr).sector_access))?;
+
+    Ok(SealedSectorMetadata {
+        sector_id: (*sector_ptr).sector_id.into(),
+        sector_access,
+        pieces: pieces.iter().map(|p| PieceMetadata {
+            piece_key: String::from(c_str_to_rust_str(p.piece_key)),
+            num_bytes: UnpaddedBytesAmount(p.num_bytes),
+            comm_p: Some(p.comm_p),
+            piece_inclusion_proof: Some(from_raw_parts(p.piece_inclusion_proof_ptr, p.piece_inclusion_proof_len).to_vec()),
+            idempotency_key: None,
+            store_until: match p.store_until {
+                0 => None,
+                store_until => Some(SecondsSinceEpoch(store_until)),
+            },
+            deal_id: if p.has_deal_id { Some(p.deal_id) } else { None },
+            labels: from_ffi_piece_labels(p.labels_ptr, p.labels_len),
+        }).collect(),
+        comm_r_star: (*sector_ptr).comm_r_star,
+        comm_r: (*sector_ptr).comm_r,
+        comm_d: (*sector_ptr).comm_d,
+        proof: from_raw_parts((*sector_ptr).proofs_ptr, (*sector_ptr).proofs_len).to_vec(),
+        blake2b_checksum: from_raw_parts((*sector_ptr).checksum_ptr, (*sector_ptr).checksum_len).to_vec(),
+        len: (*sector_ptr).len,
+        location: if !(*sector_ptr).sealed_sector_store.is_null() {
+            SealedSectorLocation::Store(c_str_to_rust_str((*sector_ptr).sealed_sector_store).to_string())
+        } else if !(*sector_ptr).sealed_sector_dir.is_null() {
+            SealedSectorLocation::Dir(c_str_to_pbuf((*sector_ptr).sealed_sector_dir))
+        } else {
+            SealedSectorLocation::Default
+        },
+        seal_ticket: if (*sector_ptr).has_seal_ticket {
+            Some(from_ffi_seal_ticket((*sector_ptr).seal_ticket))
+        } else {
+            None
+        },
+        checksum_algo: (*sector_ptr).checksum_algo.into(),
+        extent_checksums: from_raw_parts((*sector_ptr).extent_checksums_ptr, (*sector_ptr).extent_checksums_len).to_vec(),
+        seal_timing: if (*sector_ptr).has_seal_timing {
+            Some(SealTiming {
+                started_at: SecondsSinceEpoch((*sector_ptr).seal_started_at),
+                finished_at: SecondsSinceEpoch((*sector_ptr).seal_finished_at),
+                duration: Duration::from_millis((*sector_ptr).seal_duration_ms),
+                checksum_duration: Duration::from_millis((*sector_ptr).seal_checksum_duration_ms),
+                hostname: if (*sector_ptr).seal_hostname.is_null() {
+                    String::new()
+                } else {
+                    c_str_to_rust_str((*sector_ptr).seal_hostname).to_string()
+                },
+            })
+        } else {
+            None
+        },
+        seal_verified_at: match (*sector_ptr).seal_verified_at {
+            0 => None,
+            seal_verified_at => Some(SecondsSinceEpoch(seal_verified_at)),
+        },
+        replication: {
+            let detail = || {
+                if (*sector_ptr).replication_detail.is_null() {
+                    String::new()
+                } else {
+                    c_str_to_rust_str((*sector_ptr).replication_detail).to_string()
+                }
+            };
+
+            match (*sector_ptr).replication_status {
+                FFIReplicationStatus::None => None,
+                FFIReplicationStatus::Pending => Some(ReplicationStatus::Pending),
+                FFIReplicationStatus::Copying => Some(ReplicationStatus::Copying {
+                    bytes_copied: (*sector_ptr).replication_bytes_copied,
+                }),
+                FFIReplicationStatus::Replicated => Some(ReplicationStatus::Replicated {
+                    location: detail(),
+                    replicated_at: SecondsSinceEpoch((*sector_ptr).replicated_at),
+                }),
+                FFIReplicationStatus::Failed => Some(ReplicationStatus::Failed(detail())),
+            }
+        },
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_post_response(
+    ptr: *mut responses::GeneratePoStResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_seal_status_response(
+    ptr: *mut responses::GetSealStatusResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_check_sector_health_response(
+    ptr: *mut responses::CheckSectorHealthResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_hydrate_sector_integrity_response(
+    ptr: *mut responses::HydrateSectorIntegrityResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_piece_layout_response(
+    ptr: *mut responses::GetPieceLayoutResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_seal_queue_response(
+    ptr: *mut responses::GetSealQueueResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_seal_priority_response(
+    ptr: *mut responses::SetSealPriorityResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_promote_seal_response(
+    ptr: *mut responses::PromoteSealResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_cancel_seal_response(
+    ptr: *mut responses::CancelSealResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_sealed_sectors_response(
+    ptr: *mut responses::GetSealedSectorsResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_seal_statistics_response(
+    ptr: *mut responses::GetSealStatisticsResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_expired_sectors_response(
+    ptr: *mut responses::GetExpiredSectorsResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_staged_sectors_response(
+    ptr: *mut responses::GetStagedSectorsResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_staged_sector_response(
+    ptr: *mut responses::GetStagedSectorResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+#[cfg(not(target_os = "windows"))]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_piece_commitments_response(
+    ptr: *mut responses::GeneratePieceCommitmentsResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_piece_commitment_from_path_response(
+    ptr: *mut responses::GeneratePieceCommitmentResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_piece_commitment_from_bytes_response(
+    ptr: *mut responses::GeneratePieceCommitmentResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_supported_sector_sizes_response(
+    ptr: *mut responses::GetSupportedSectorSizesResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_kv_store_stats_response(
+    ptr: *mut responses::GetKvStoreStatsResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_journal_keep_last_n_response(
+    ptr: *mut responses::SetJournalKeepLastNResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_compact_metadata_response(
+    ptr: *mut responses::CompactMetadataResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_seal_statuses_response(
+    ptr: *mut responses::GetSealStatusesResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_pieces_by_origin_response(
+    ptr: *mut responses::GetPiecesByOriginResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_storage_report_response(
+    ptr: *mut responses::GetStorageReportResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_init_sector_builder_response(
+    ptr: *mut responses::InitSectorBuilderResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_post_async_response(
+    ptr: *mut responses::GeneratePoStAsyncResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_read_piece_async_response(
+    ptr: *mut responses::ReadPieceAsyncResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_poll_response(
+    ptr: *mut responses::PollResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_cancel_response(
+    ptr: *mut responses::CancelResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_freeze_sector_builder_response(
+    ptr: *mut responses::FreezeSectorBuilderResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_config_response(
+    ptr: *mut responses::GetConfigResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_get_startup_report_response(
+    ptr: *mut responses::GetStartupReportResponse,
+) {
+    let _ = Box::from_raw(ptr);
+}
 
-    raw_ptr(response)
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_read_piece_from_sealed_sector_response(
+    ptr: *mut responses::ReadPieceFromSealedSectorResponse,
+) {
+    let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_get_sectors_ready_for_sealing_response(
-    ptr: *mut responses::GetSectorsReadyForSealingResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_read_piece_with_proof_response(
+    ptr: *mut responses::ReadPieceWithProofResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn into_staged_sector_metadata(
-    sector_ptr: *const responses::FFIPendingStagedSectorMetadata,
-) -> StagedSectorMetadata {
-    let pieces: Vec<&FFIPieceMetadata> = from_raw_parts((*sector_ptr).pieces_ptr, (*sector_ptr).pieces_len).iter().collect();
-    StagedSectorMetadata {
-        sector_id: (*sector_ptr).sector_id.into(),
-        sector_access: c_str_to_rust_str((*sector_ptr).sector_access).into(),
-        pieces: pieces.iter().map(|p| PieceMetadata {
-            piece_key: String::from(c_str_to_rust_str(p.piece_key)),
-            num_bytes: UnpaddedBytesAmount(p.num_bytes),
-            comm_p: Some(p.comm_p),
-            piece_inclusion_proof: Some(from_raw_parts(p.piece_inclusion_proof_ptr, p.piece_inclusion_proof_len).to_vec()),
-        }).collect(),
-        seal_status: SealStatus::Pending,
-    }
+pub unsafe extern "C" fn sector_builder_ffi_destroy_seal_all_staged_sectors_response(
+    ptr: *mut responses::SealAllStagedSectorsResponse,
+) {
+    let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn into_sealed_sector_metadata(
-    sector_ptr: *const responses::FFISealedSectorMetadata,
-) -> SealedSectorMetadata {
-    let pieces: Vec<&FFIPieceMetadata> = from_raw_parts((*sector_ptr).pieces_ptr, (*sector_ptr).pieces_len).iter().collect();
-    SealedSectorMetadata {
-        sector_id: (*sector_ptr).sector_id.into(),
-        sector_access: c_str_to_rust_str((*sector_ptr).sector_access).into(),
-        pieces: pieces.iter().map(|p| PieceMetadata {
-            piece_key: String::from(c_str_to_rust_str(p.piece_key)),
-            num_bytes: UnpaddedBytesAmount(p.num_bytes),
-            comm_p: Some(p.comm_p),
-            piece_inclusion_proof: Some(from_raw_parts(p.piece_inclusion_proof_ptr, p.piece_inclusion_proof_len).to_vec()),
-        }).collect(),
-        // The following fields are unused.
-        comm_r_star: (*sector_ptr).comm_r_star,
-        comm_r: (*sector_ptr).comm_r,
-        comm_d: (*sector_ptr).comm_d,
-        proof: from_raw_parts((*sector_ptr).proofs_ptr, (*sector_ptr).proofs_len).to_vec(),
-        blake2b_checksum: Default::default(), // unset
-        len: 0, // unset
-    }
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_max_num_staged_sectors_response(
+    ptr: *mut responses::SetMaxNumStagedSectorsResponse,
+) {
+    let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_post_response(
-    ptr: *mut responses::GeneratePoStResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_task_timeouts_response(
+    ptr: *mut responses::SetTaskTimeoutsResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_get_seal_status_response(
-    ptr: *mut responses::GetSealStatusResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_seal_memory_watermark_response(
+    ptr: *mut responses::SetSealMemoryWatermarkResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_get_sealed_sectors_response(
-    ptr: *mut responses::GetSealedSectorsResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_seal_isolation_response(
+    ptr: *mut responses::SetSealIsolationResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_get_staged_sectors_response(
-    ptr: *mut responses::GetStagedSectorsResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_replicator_response(
+    ptr: *mut responses::SetReplicatorResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_init_sector_builder_response(
-    ptr: *mut responses::InitSectorBuilderResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_replicate_sealed_sector_response(
+    ptr: *mut responses::ReplicateSealedSectorResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_read_piece_from_sealed_sector_response(
-    ptr: *mut responses::ReadPieceFromSealedSectorResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_staged_write_config_response(
+    ptr: *mut responses::SetStagedWriteConfigResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_seal_all_staged_sectors_response(
-    ptr: *mut responses::SealAllStagedSectorsResponse,
+pub unsafe extern "C" fn sector_builder_ffi_destroy_set_ingest_rate_limit_response(
+    ptr: *mut responses::SetIngestRateLimitResponse,
 ) {
     let _ = Box::from_raw(ptr);
 }
@@ -1047,9 +3273,9 @@ pub unsafe extern "C" fn sector_builder_ffi_destroy_seal_all_staged_sectors_resp
 ///
 #[no_mangle]
 pub unsafe extern "C" fn sector_builder_ffi_destroy_verify_seal_response(
-    ptr: *mut filecoin_proofs_ffi::responses::VerifySealResponse,
+    ptr: *mut responses::VerifySealResponse,
 ) {
-    filecoin_proofs_ffi::api::destroy_verify_seal_response(ptr)
+    let _ = Box::from_raw(ptr);
 }
 
 /// Deallocates a VerifyPoStResponse.
@@ -1080,10 +3306,109 @@ pub unsafe extern "C" fn sector_builder_ffi_destroy_generate_piece_commitment_re
     filecoin_proofs_ffi::api::destroy_generate_piece_commitment_response(ptr)
 }
 
-/// Destroys a SectorBuilder.
+/// Freezes the SectorBuilder with the provided handle, shutting it down and
+/// producing a serialized handoff bundle from which it's thawed in another
+/// process (see sector_builder_ffi_thaw_sector_builder). The handle is
+/// destroyed. Fails, leaving the SectorBuilder running, while other calls
+/// are using it. Blocks until the seals being run by the workers, replica
+/// transfers and replication copies have finished; seals waiting for a
+/// worker are handed off instead.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_freeze_sector_builder(
+    handle: u64,
+) -> *mut responses::FreezeSectorBuilderResponse {
+    init_log();
+
+    let mut response: responses::FreezeSectorBuilderResponse = Default::default();
+
+    let result = sector_builders()
+        .take(handle)
+        .and_then(|builder| builder.freeze())
+        .and_then(|bundle| bundle.to_bytes());
+
+    match result {
+        Ok(bundle) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.bundle_len = bundle.len();
+            response.bundle_ptr = bundle.as_ptr();
+
+            mem::forget(bundle);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Initializes a SectorBuilder from a serialized handoff bundle produced by
+/// sector_builder_ffi_freeze_sector_builder, with the config and limits of
+/// the frozen SectorBuilder, and returns its handle. The seals it interrupted
+/// are restarted. The staged data key must be provided again if staged
+/// sector-files are encrypted; a null pointer leaves them unencrypted.
+/// Runtime settings (e.g. task timeouts, seal isolation, sealed stores, the
+/// replicator or the auto-seal policy) aren't handed off and must be set
+/// again on the returned handle.
+///
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_thaw_sector_builder(
+    bundle_ptr: *const u8,
+    bundle_len: libc::size_t,
+    staged_data_key: *const [u8; 32],
+) -> *mut responses::InitSectorBuilderResponse {
+    init_log();
+
+    let staged_data_key = staged_data_key.as_ref().map(|key| StagedDataKey::new(*key));
+
+    let result = HandoffBundle::from_bytes(from_raw_parts(bundle_ptr, bundle_len))
+        .and_then(|bundle| SectorBuilder::thaw(bundle, staged_data_key));
+
+    let mut response = responses::InitSectorBuilderResponse::default();
+
+    match result {
+        Ok(sb) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.sector_builder = sector_builders().insert(sb);
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
+/// Destroys the SectorBuilder with the provided handle, which is shut down
+/// once any calls still using it have returned. Destroying it again produces
+/// an error.
 ///
 #[no_mangle]
-pub unsafe extern "C" fn sector_builder_ffi_destroy_sector_builder(ptr: *mut SectorBuilder) {
+pub unsafe extern "C" fn sector_builder_ffi_destroy_sector_builder(
+    handle: u64,
+) -> *mut responses::DestroySectorBuilderResponse {
+    init_log();
+
+    let mut response: responses::DestroySectorBuilderResponse = Default::default();
+
+    if let Err(err) = sector_builders().remove(handle) {
+        let (code, ptr) = err_code_and_msg(&err.into());
+        response.status_code = code;
+        response.error_msg = ptr;
+    }
+
+    raw_ptr(response)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sector_builder_ffi_destroy_destroy_sector_builder_response(
+    ptr: *mut responses::DestroySectorBuilderResponse,
+) {
     let _ = Box::from_raw(ptr);
 }
 
@@ -1091,6 +3416,44 @@ pub unsafe extern "C" fn sector_builder_ffi_destroy_sector_builder(ptr: *mut Sec
 // HELPER FUNCTIONS
 ///////////////////
 
+fn into_ffi_seal_schedule_result(result: &SealScheduleResult) -> responses::FFISealScheduleResult {
+    let (outcome, message) = match &result.outcome {
+        SealScheduleOutcome::Scheduled => (responses::FFISealScheduleOutcome::Scheduled, ptr::null()),
+        SealScheduleOutcome::Skipped(reason) => {
+            (responses::FFISealScheduleOutcome::Skipped, rust_str_to_c_str(reason.clone()))
+        }
+        SealScheduleOutcome::Error(err) => {
+            (responses::FFISealScheduleOutcome::Error, rust_str_to_c_str(err.clone()))
+        }
+    };
+
+    responses::FFISealScheduleResult {
+        sector_id: u64::from(result.sector_id),
+        outcome,
+        message,
+    }
+}
+
+fn into_generate_piece_commitment_response(
+    result: Result<[u8; 32], failure::Error>,
+) -> *mut responses::GeneratePieceCommitmentResponse {
+    let mut response: responses::GeneratePieceCommitmentResponse = Default::default();
+
+    match result {
+        Ok(comm_p) => {
+            response.status_code = FCPResponseStatus::FCPNoError;
+            response.comm_p = comm_p;
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            response.status_code = code;
+            response.error_msg = ptr;
+        }
+    }
+
+    raw_ptr(response)
+}
+
 unsafe fn into_commitments(
     flattened_comms_ptr: *const u8,
     flattened_comms_len: libc::size_t,
@@ -1107,16 +3470,257 @@ unsafe fn into_commitments(
         })
 }
 
-pub fn from_ffi_sector_class(fsc: FFISectorClass) -> filecoin_proofs::SectorClass {
+fn into_post_deadline(deadline: u64, mark_slowest_faulty: bool) -> Option<PoStDeadline> {
+    // hosts without a deadline pass 0
+    if deadline == 0 {
+        return None;
+    }
+
+    Some(PoStDeadline {
+        deadline: SecondsSinceEpoch(deadline),
+        mark_slowest_faulty,
+    })
+}
+
+unsafe fn into_staged_sectors(
+    sectors_ptr: *const responses::FFIPendingStagedSectorMetadata,
+    sectors_len: libc::size_t,
+) -> Result<HashMap<SectorId, StagedSectorMetadata>, failure::Error> {
+    from_raw_parts(sectors_ptr, sectors_len)
+        .iter()
+        .map(|s| into_staged_sector_metadata(s).map(|meta| (meta.sector_id, meta)))
+        .collect()
+}
+
+unsafe fn into_sealed_sectors(
+    sectors_ptr: *const responses::FFISealedSectorMetadata,
+    sectors_len: libc::size_t,
+) -> Result<HashMap<SectorId, SealedSectorMetadata>, failure::Error> {
+    from_raw_parts(sectors_ptr, sectors_len)
+        .iter()
+        .map(|s| into_sealed_sector_metadata(s).map(|meta| (meta.sector_id, meta)))
+        .collect()
+}
+
+unsafe fn into_replica_paths(
+    replica_paths_ptr: *const FFIReplicaPath,
+    replica_paths_len: libc::size_t,
+) -> HashMap<SectorId, PathBuf> {
+    // hosts without overrides may pass a null pointer
+    if replica_paths_ptr.is_null() {
+        return HashMap::new();
+    }
+
+    from_raw_parts(replica_paths_ptr, replica_paths_len)
+        .iter()
+        .map(|replica_path| (SectorId::from(replica_path.sector_id), c_str_to_pbuf(replica_path.path)))
+        .collect()
+}
+
+unsafe fn into_worker_affinity(
+    worker_cpu_lists_ptr: *const *const libc::c_char,
+    worker_cpu_lists_len: libc::size_t,
+) -> Result<WorkerAffinity, failure::Error> {
+    // hosts which don't pin workers may pass a null pointer
+    if worker_cpu_lists_ptr.is_null() {
+        return Ok(WorkerAffinity::default());
+    }
+
+    from_raw_parts(worker_cpu_lists_ptr, worker_cpu_lists_len)
+        .iter()
+        .map(|cpu_list| parse_cpu_list(&c_str_to_rust_str(*cpu_list)))
+        .collect::<Result<_, _>>()
+        .map(WorkerAffinity)
+}
+
+pub fn from_ffi_sector_class(fsc: FFISectorClass) -> Result<filecoin_proofs::SectorClass, failure::Error> {
     match fsc {
         FFISectorClass {
             sector_size,
             porep_proof_partitions,
-        } => filecoin_proofs::SectorClass(
-            filecoin_proofs::SectorSize(sector_size),
-            filecoin_proofs::PoRepProofPartitions(porep_proof_partitions),
-        ),
+            post_proof_partitions,
+            post_challenge_count,
+        } => {
+            // The PoSt configuration of the linked proofs is derived from the
+            // sector size alone, so an explicit PoSt parameterization can't
+            // be honored yet. Reject it rather than silently ignoring it.
+            if post_proof_partitions != 0 || post_challenge_count != 0 {
+                return Err(err_invalid_sector_class(format!(
+                    "unsupported PoSt parameterization (partitions: {}, challenge count: {})",
+                    post_proof_partitions, post_challenge_count
+                ))
+                .into());
+            }
+
+            Ok(filecoin_proofs::SectorClass(
+                filecoin_proofs::SectorSize(sector_size),
+                filecoin_proofs::PoRepProofPartitions(porep_proof_partitions),
+            ))
+        }
+    }
+}
+
+pub fn from_ffi_post_config(fpc: FFIPoStConfig) -> Result<Option<PoStConfig>, failure::Error> {
+    match fpc {
+        FFIPoStConfig {
+            sector_size: 0,
+            challenge_count: 0,
+        } => Ok(None),
+        FFIPoStConfig {
+            challenge_count,
+            ..
+        } if challenge_count != 0 => {
+            // Like the PoSt parameterization of a sector class, the challenge
+            // count of the linked proofs can't be chosen yet.
+            Err(err_invalid_sector_class(format!(
+                "unsupported PoSt challenge count: {}",
+                challenge_count
+            ))
+            .into())
+        }
+        FFIPoStConfig { sector_size, .. } => {
+            Ok(Some(PoStConfig(filecoin_proofs::SectorSize(sector_size))))
+        }
+    }
+}
+
+pub fn from_ffi_seal_ticket(fst: FFISealTicket) -> SealTicket {
+    match fst {
+        FFISealTicket {
+            block_height,
+            ticket_bytes,
+        } => SealTicket {
+            block_height,
+            ticket_bytes,
+        },
+    }
+}
+
+fn into_ffi_pending_staged_sector_metadata(
+    meta: &StagedSectorMetadata,
+) -> responses::FFIPendingStagedSectorMetadata {
+    let pieces = meta
+        .pieces
+        .iter()
+        .map(into_ffi_piece_metadata)
+        .collect::<Vec<FFIPieceMetadata>>();
+
+    let sector = responses::FFIPendingStagedSectorMetadata {
+        sector_access: rust_str_to_c_str(meta.sector_access.to_string()),
+        sector_id: u64::from(meta.sector_id),
+        pieces_len: pieces.len(),
+        pieces_ptr: pieces.as_ptr(),
+        created_at: meta.created_at.as_ref().map_or(0, |created_at| created_at.0),
+        in_secondary_staged_dir: meta.staged_dir == StagedSectorDir::Secondary,
+    };
+
+    mem::forget(pieces);
+
+    sector
+}
+
+fn into_ffi_staged_sector_metadata(meta: &StagedSectorMetadata) -> responses::FFIStagedSectorMetadata {
+    let pieces = meta
+        .pieces
+        .iter()
+        .map(into_ffi_piece_metadata)
+        .collect::<Vec<FFIPieceMetadata>>();
+
+    let mut sector = responses::FFIStagedSectorMetadata {
+        sector_access: rust_str_to_c_str(meta.sector_access.to_string()),
+        sector_id: u64::from(meta.sector_id),
+        pieces_len: pieces.len(),
+        pieces_ptr: pieces.as_ptr(),
+        seal_status_code: FFISealStatus::Pending,
+        seal_error_msg: ptr::null(),
+        seal_failure_code: FFISealFailure::None,
+    };
+
+    match meta.seal_status {
+        SealStatus::Failed(ref failure) => {
+            sector.seal_status_code = FFISealStatus::Failed;
+            sector.seal_failure_code = into_ffi_seal_failure(failure);
+            sector.seal_error_msg = rust_str_to_c_str(format!("{}", failure));
+        }
+        SealStatus::Sealing => {
+            sector.seal_status_code = FFISealStatus::Sealing;
+        }
+        SealStatus::Pending => {
+            sector.seal_status_code = FFISealStatus::Pending;
+        }
+        SealStatus::Sealed(_) => {
+            sector.seal_status_code = FFISealStatus::Sealed;
+        }
+    };
+
+    mem::forget(pieces);
+
+    sector
+}
+
+fn into_ffi_sector_seal_status(
+    sector_id: SectorId,
+    result: Result<SealStatus, failure::Error>,
+) -> responses::FFISectorSealStatus {
+    let mut status = responses::FFISectorSealStatus {
+        sector_id: u64::from(sector_id),
+        status_code: FCPResponseStatus::FCPNoError,
+        error_msg: ptr::null(),
+        seal_status_code: FFISealStatus::Pending,
+        seal_error_msg: ptr::null(),
+        seal_failure_code: FFISealFailure::None,
+        comm_d: Default::default(),
+        comm_r: Default::default(),
+        comm_r_star: Default::default(),
+        sector_access: ptr::null(),
+        proof_len: 0,
+        proof_ptr: ptr::null(),
+        pieces_len: 0,
+        pieces_ptr: ptr::null(),
+    };
+
+    match result {
+        Ok(SealStatus::Sealed(meta)) => {
+            let meta = *meta;
+
+            let pieces = meta
+                .pieces
+                .iter()
+                .map(into_ffi_piece_metadata)
+                .collect::<Vec<FFIPieceMetadata>>();
+
+            status.seal_status_code = FFISealStatus::Sealed;
+            status.comm_d = meta.comm_d;
+            status.comm_r = meta.comm_r;
+            status.comm_r_star = meta.comm_r_star;
+            status.sector_access = rust_str_to_c_str(String::from(meta.sector_access));
+            status.proof_len = meta.proof.len();
+            status.proof_ptr = meta.proof.as_ptr();
+            status.pieces_len = pieces.len();
+            status.pieces_ptr = pieces.as_ptr();
+
+            mem::forget(meta.proof);
+            mem::forget(pieces);
+        }
+        Ok(SealStatus::Sealing) => {
+            status.seal_status_code = FFISealStatus::Sealing;
+        }
+        Ok(SealStatus::Pending) => {
+            status.seal_status_code = FFISealStatus::Pending;
+        }
+        Ok(SealStatus::Failed(failure)) => {
+            status.seal_status_code = FFISealStatus::Failed;
+            status.seal_failure_code = into_ffi_seal_failure(&failure);
+            status.seal_error_msg = rust_str_to_c_str(format!("{}", failure));
+        }
+        Err(err) => {
+            let (code, ptr) = err_code_and_msg(&err);
+            status.status_code = code;
+            status.error_msg = ptr;
+        }
     }
+
+    status
 }
 
 fn into_ffi_piece_metadata(piece_metadata: &PieceMetadata) -> FFIPieceMetadata {
@@ -1134,18 +3738,209 @@ fn into_ffi_piece_metadata(piece_metadata: &PieceMetadata) -> FFIPieceMetadata {
         None => (0, ptr::null()),
     };
 
-    FFIPieceMetadata {
+    let labels = piece_metadata
+        .labels
+        .iter()
+        .map(|(key, value)| {
+            let value = value.clone();
+
+            let label = FFIPieceLabel {
+                key: rust_str_to_c_str(key.clone()),
+                value_len: value.len(),
+                value_ptr: value.as_ptr(),
+            };
+
+            mem::forget(value);
+
+            label
+        })
+        .collect::<Vec<FFIPieceLabel>>();
+
+    let piece = FFIPieceMetadata {
         piece_key: rust_str_to_c_str(piece_metadata.piece_key.to_string()),
         num_bytes: piece_metadata.num_bytes.into(),
         comm_p: piece_metadata.comm_p.unwrap_or([0; 32]),
         piece_inclusion_proof_len: len,
         piece_inclusion_proof_ptr: ptr,
+        store_until: piece_metadata.store_until.as_ref().map_or(0, |store_until| store_until.0),
+        has_deal_id: piece_metadata.deal_id.is_some(),
+        deal_id: piece_metadata.deal_id.unwrap_or(0),
+        labels_len: labels.len(),
+        labels_ptr: labels.as_ptr(),
+    };
+
+    mem::forget(labels);
+
+    piece
+}
+
+// Copies the provided labels, of which the caller retains ownership.
+unsafe fn from_ffi_piece_labels(
+    labels_ptr: *const FFIPieceLabel,
+    labels_len: libc::size_t,
+) -> BTreeMap<String, Vec<u8>> {
+    if labels_ptr.is_null() {
+        return BTreeMap::new();
+    }
+
+    from_raw_parts(labels_ptr, labels_len)
+        .iter()
+        .map(|label| {
+            let value = if label.value_ptr.is_null() {
+                Vec::new()
+            } else {
+                from_raw_parts(label.value_ptr, label.value_len).to_vec()
+            };
+
+            (String::from(c_str_to_rust_str(label.key)), value)
+        })
+        .collect()
+}
+
+fn into_ffi_seal_duration_stats(stats: &SealDurationStats) -> responses::FFISealDurationStats {
+    responses::FFISealDurationStats {
+        num_sectors: stats.num_sectors,
+        min_ms: stats.min.as_millis() as u64,
+        max_ms: stats.max.as_millis() as u64,
+        mean_ms: stats.mean.as_millis() as u64,
+        median_ms: stats.median.as_millis() as u64,
+    }
+}
+
+fn into_ffi_seal_ticket(seal_ticket: &SealTicket) -> FFISealTicket {
+    FFISealTicket {
+        block_height: seal_ticket.block_height,
+        ticket_bytes: seal_ticket.ticket_bytes,
+    }
+}
+
+fn into_ffi_sealed_sector_metadata(
+    meta: &SealedSectorMetadata,
+    health: FFISealedSectorHealth,
+) -> responses::FFISealedSectorMetadata {
+    let pieces = meta
+        .pieces
+        .iter()
+        .map(into_ffi_piece_metadata)
+        .collect::<Vec<FFIPieceMetadata>>();
+
+    let snark_proof = meta.proof.clone();
+    let checksum = meta.blake2b_checksum.clone();
+    let extent_checksums = meta.extent_checksums.clone();
+
+    let sector = responses::FFISealedSectorMetadata {
+        comm_d: meta.comm_d,
+        comm_r: meta.comm_r,
+        comm_r_star: meta.comm_r_star,
+        pieces_len: pieces.len(),
+        pieces_ptr: pieces.as_ptr(),
+        proofs_len: snark_proof.len(),
+        proofs_ptr: snark_proof.as_ptr(),
+        sector_access: rust_str_to_c_str(meta.sector_access.to_string()),
+        sector_id: u64::from(meta.sector_id),
+        health,
+        sealed_sector_store: into_ffi_sealed_sector_store(&meta.location),
+        sealed_sector_dir: into_ffi_sealed_sector_dir(&meta.location),
+        checksum_len: checksum.len(),
+        checksum_ptr: checksum.as_ptr(),
+        checksum_algo: meta.checksum_algo.into(),
+        extent_checksums_len: extent_checksums.len(),
+        extent_checksums_ptr: extent_checksums.as_ptr(),
+        len: meta.len,
+        has_seal_ticket: meta.seal_ticket.is_some(),
+        seal_ticket: meta.seal_ticket.as_ref().map(into_ffi_seal_ticket).unwrap_or_default(),
+        has_seal_timing: meta.seal_timing.is_some(),
+        seal_started_at: meta.seal_timing.as_ref().map_or(0, |t| t.started_at.0),
+        seal_finished_at: meta.seal_timing.as_ref().map_or(0, |t| t.finished_at.0),
+        seal_duration_ms: meta.seal_timing.as_ref().map_or(0, |t| t.duration.as_millis() as u64),
+        seal_checksum_duration_ms: meta.seal_timing.as_ref().map_or(0, |t| t.checksum_duration.as_millis() as u64),
+        seal_hostname: match &meta.seal_timing {
+            Some(t) => rust_str_to_c_str(t.hostname.clone()),
+            None => ptr::null(),
+        },
+        seal_verified_at: meta.seal_verified_at.map_or(0, |t| t.0),
+        replication_status: match &meta.replication {
+            None => FFIReplicationStatus::None,
+            Some(ReplicationStatus::Pending) => FFIReplicationStatus::Pending,
+            Some(ReplicationStatus::Copying { .. }) => FFIReplicationStatus::Copying,
+            Some(ReplicationStatus::Replicated { .. }) => FFIReplicationStatus::Replicated,
+            Some(ReplicationStatus::Failed(_)) => FFIReplicationStatus::Failed,
+        },
+        replicated_at: match &meta.replication {
+            Some(ReplicationStatus::Replicated { replicated_at, .. }) => replicated_at.0,
+            _ => 0,
+        },
+        replication_detail: match &meta.replication {
+            Some(ReplicationStatus::Replicated { location, .. }) => rust_str_to_c_str(location.clone()),
+            Some(ReplicationStatus::Failed(err)) => rust_str_to_c_str(err.clone()),
+            _ => ptr::null(),
+        },
+        replication_bytes_copied: match &meta.replication {
+            Some(ReplicationStatus::Copying { bytes_copied }) => *bytes_copied,
+            _ => 0,
+        },
+    };
+
+    mem::forget(snark_proof);
+    mem::forget(pieces);
+    mem::forget(checksum);
+    mem::forget(extent_checksums);
+
+    sector
+}
+
+fn into_ffi_sealed_sector_store(location: &SealedSectorLocation) -> *const libc::c_char {
+    match location {
+        SealedSectorLocation::Store(store_id) => rust_str_to_c_str(store_id.clone()),
+        _ => ptr::null(),
+    }
+}
+
+fn into_ffi_sealed_sector_dir(location: &SealedSectorLocation) -> *const libc::c_char {
+    match location {
+        SealedSectorLocation::Dir(dir) => rust_str_to_c_str(dir.to_string_lossy().into_owned()),
+        _ => ptr::null(),
+    }
+}
+
+fn into_ffi_seal_failure(failure: &SealFailure) -> FFISealFailure {
+    match failure {
+        SealFailure::ParamsMissing(_) => FFISealFailure::ParamsMissing,
+        SealFailure::Io { .. } => FFISealFailure::Io,
+        SealFailure::ProofGeneration(_) => FFISealFailure::ProofGeneration,
+        SealFailure::Cancelled => FFISealFailure::Cancelled,
+        SealFailure::OutOfMemory => FFISealFailure::OutOfMemory,
+        SealFailure::ReplicaMismatch(_) => FFISealFailure::ReplicaMismatch,
+        SealFailure::TimedOut => FFISealFailure::TimedOut,
+        SealFailure::StagedDataCorrupted(_) => FFISealFailure::StagedDataCorrupted,
+        SealFailure::Interrupted => FFISealFailure::Interrupted,
+        SealFailure::WorkerCrashed(_) => FFISealFailure::WorkerCrashed,
+        SealFailure::Other(_) => FFISealFailure::Other,
     }
 }
 
 /// Protects the init off the logger.
 static LOG_INIT: OnceCell<bool> = OnceCell::new();
 
+/// The builders the host holds handles to.
+static SECTOR_BUILDERS: OnceCell<HandleTable<SectorBuilder>> = OnceCell::new();
+static SIMPLE_SECTOR_BUILDERS: OnceCell<HandleTable<SimpleSectorBuilder>> = OnceCell::new();
+
+/// The operations the host has started and not yet seen complete.
+static OPERATIONS: OnceCell<OperationTable> = OnceCell::new();
+
+fn sector_builders() -> &'static HandleTable<SectorBuilder> {
+    SECTOR_BUILDERS.get_or_init(Default::default)
+}
+
+fn simple_sector_builders() -> &'static HandleTable<SimpleSectorBuilder> {
+    SIMPLE_SECTOR_BUILDERS.get_or_init(Default::default)
+}
+
+fn operations() -> &'static OperationTable {
+    OPERATIONS.get_or_init(Default::default)
+}
+
 /// Ensures the logger is initialized.
 fn init_log() {
     LOG_INIT.get_or_init(|| {
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use failure::Fail;
+
+const FATAL_NOLOCK: &str = "error acquiring handle table lock";
+
+/// Opaque integer through which the host refers to a builder. No builder is
+/// ever given the handle 0.
+pub type Handle = u64;
+
+#[derive(Debug, Fail)]
+#[fail(display = "no builder with handle {} exists (was it destroyed?)", _0)]
+pub struct UnknownHandle(pub Handle);
+
+#[derive(Debug, Fail)]
+#[fail(display = "builder with handle {} is in use by another call", _0)]
+pub struct HandleInUse(pub Handle);
+
+/// Hands out handles for values and resolves them again, so that a handle
+/// which refers to a value that has been removed (or never existed) produces
+/// an error instead of a dangling pointer. Handles aren't reused.
+///
+/// The host calls into the FFI from whichever of its threads it likes, and
+/// may do so concurrently using the same handle, so the table only holds
+/// values which can be shared between threads.
+pub struct HandleTable<T: Send + Sync> {
+    next: AtomicU64,
+    entries: Mutex<HashMap<Handle, Arc<T>>>,
+}
+
+impl<T: Send + Sync> Default for HandleTable<T> {
+    fn default() -> HandleTable<T> {
+        HandleTable {
+            next: AtomicU64::new(1),
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<T: Send + Sync> HandleTable<T> {
+    pub fn insert(&self, value: T) -> Handle {
+        let handle = self.next.fetch_add(1, Ordering::SeqCst);
+
+        self.entries
+            .lock()
+            .expect(FATAL_NOLOCK)
+            .insert(handle, Arc::new(value));
+
+        handle
+    }
+
+    // Resolves the handle. The value stays alive for as long as the returned
+    // reference does, even if it's removed from the table in the meantime.
+    pub fn get(&self, handle: Handle) -> Result<Arc<T>, UnknownHandle> {
+        self.entries
+            .lock()
+            .expect(FATAL_NOLOCK)
+            .get(&handle)
+            .cloned()
+            .ok_or(UnknownHandle(handle))
+    }
+
+    // Removes the value from the table. It is dropped once the calls which
+    // resolved its handle before it was removed have returned.
+    pub fn remove(&self, handle: Handle) -> Result<(), UnknownHandle> {
+        let removed = self
+            .entries
+            .lock()
+            .expect(FATAL_NOLOCK)
+            .remove(&handle)
+            .ok_or(UnknownHandle(handle))?;
+
+        // drop the value outside of the lock, as dropping a builder waits for
+        // its threads to shut down
+        drop(removed);
+
+        Ok(())
+    }
+
+    // Removes the value from the table and hands it over. Fails, leaving the
+    // value in the table, while calls which resolved its handle are running.
+    pub fn take(&self, handle: Handle) -> Result<T, failure::Error> {
+        let mut entries = self.entries.lock().expect(FATAL_NOLOCK);
+
+        let value = entries.remove(&handle).ok_or(UnknownHandle(handle))?;
+
+        match Arc::try_unwrap(value) {
+            Ok(value) => Ok(value),
+            Err(value) => {
+                entries.insert(handle, value);
+                Err(HandleInUse(handle).into())
+            }
+        }
+    }
+}
+
+// Resolves the handle in the provided table, returning the response with an
+// error from the enclosing function if the handle refers to no builder.
+macro_rules! resolve_handle {
+    ($table:expr, $handle:expr, $response:ident) => {
+        match $table.get($handle) {
+            Ok(builder) => builder,
+            Err(err) => {
+                let (code, ptr) = err_code_and_msg(&err.into());
+                $response.status_code = code;
+                $response.error_msg = ptr;
+                return raw_ptr($response);
+            }
+        }
+    };
+}
@@ -3,6 +3,9 @@
 #[macro_use]
 extern crate log;
 
+#[macro_use]
+mod handles;
+mod operations;
 mod responses;
 
 pub mod api;
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+use failure::{err_msg, Error, Fail};
+use sector_builder::{CancellationToken, GeneratedPoSt};
+
+const FATAL_NOLOCK: &str = "error acquiring operation table lock";
+
+/// Operations which the host hasn't polled for this long are cancelled and
+/// forgotten, so that a host which loses track of an operation doesn't leak
+/// it (and its result) for the life of the process.
+pub const UNPOLLED_OPERATION_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Identifies a long-running operation which the host started without
+/// waiting for it to complete. No operation is ever given the id 0.
+pub type OperationId = u64;
+
+#[derive(Debug, Fail)]
+#[fail(
+    display = "no operation with id {} exists (has its result been polled already?)",
+    _0
+)]
+pub struct UnknownOperation(pub OperationId);
+
+/// What an operation produces once it's done.
+pub enum OperationOutput {
+    PoSt(GeneratedPoSt),
+    Piece(Vec<u8>),
+}
+
+pub enum Poll {
+    Pending,
+    Done(OperationOutput),
+    Failed(Error),
+}
+
+/// Holds on to the results of operations until the host polls for them. A
+/// call which blocks for the duration of a PoSt ties up one of the host's
+/// threads (and, for cgo, one of its limited OS threads) for just as long; an
+/// operation returns right away. Operations are run by the SectorBuilder
+/// they were started on, so they don't need threads of their own.
+///
+/// Each operation is handed a cancellation token, through which the host can
+/// abandon it. Operations which are cancelled fail.
+#[derive(Default)]
+pub struct OperationTable {
+    next: AtomicU64,
+    entries: Mutex<HashMap<OperationId, Operation>>,
+}
+
+type TryRecv =
+    Box<dyn FnMut() -> Result<Result<OperationOutput, Error>, mpsc::TryRecvError> + Send>;
+
+struct Operation {
+    try_recv: TryRecv,
+    cancel: CancellationToken,
+    last_polled: Instant,
+}
+
+impl OperationTable {
+    // Starts an operation, which sends its result to the receiver returned by
+    // the provided function. An operation which fails to start fails like
+    // one which fails later on.
+    pub fn start<T, F>(&self, start: F, into_output: fn(T) -> OperationOutput) -> OperationId
+    where
+        T: Send + 'static,
+        F: FnOnce(CancellationToken) -> Result<mpsc::Receiver<Result<T, Error>>, Error>,
+    {
+        let id = self.next.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let cancel = CancellationToken::default();
+
+        let result_rx = start(cancel.clone()).unwrap_or_else(|err| {
+            let (tx, rx) = mpsc::sync_channel(1);
+            let _ = tx.send(Err(err));
+            rx
+        });
+
+        let mut entries = self.entries.lock().expect(FATAL_NOLOCK);
+
+        reap(&mut entries);
+
+        entries.insert(
+            id,
+            Operation {
+                try_recv: Box::new(move || result_rx.try_recv().map(|r| r.map(into_output))),
+                cancel,
+                last_polled: Instant::now(),
+            },
+        );
+
+        id
+    }
+
+    // Returns the state of the operation. Once it's done (or has failed), its
+    // result is handed over and the operation is forgotten.
+    pub fn poll(&self, id: OperationId) -> Result<Poll, UnknownOperation> {
+        let mut entries = self.entries.lock().expect(FATAL_NOLOCK);
+
+        reap(&mut entries);
+
+        let operation = entries.get_mut(&id).ok_or(UnknownOperation(id))?;
+        operation.last_polled = Instant::now();
+
+        let poll = match (operation.try_recv)() {
+            Err(mpsc::TryRecvError::Empty) => return Ok(Poll::Pending),
+            Ok(Ok(output)) => Poll::Done(output),
+            Ok(Err(err)) => Poll::Failed(err),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Poll::Failed(err_msg(format!("operation {} ended without a result", id)))
+            }
+        };
+
+        entries.remove(&id);
+
+        Ok(poll)
+    }
+
+    // Cancels the operation, which fails once it has been abandoned. The
+    // failure is polled like any other result.
+    pub fn cancel(&self, id: OperationId) -> Result<(), UnknownOperation> {
+        self.entries
+            .lock()
+            .expect(FATAL_NOLOCK)
+            .get(&id)
+            .ok_or(UnknownOperation(id))?
+            .cancel
+            .cancel();
+
+        Ok(())
+    }
+}
+
+// Cancels and forgets the operations which haven't been polled for longer
+// than UNPOLLED_OPERATION_TTL. Whatever they still produce is dropped.
+fn reap(entries: &mut HashMap<OperationId, Operation>) {
+    entries.retain(|id, operation| {
+        if operation.last_polled.elapsed() < UNPOLLED_OPERATION_TTL {
+            return true;
+        }
+
+        warn!(
+            "operation {} hasn't been polled for {:?}, forgetting it",
+            id, UNPOLLED_OPERATION_TTL
+        );
+
+        operation.cancel.cancel();
+
+        false
+    });
+}
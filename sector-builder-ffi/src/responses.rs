@@ -6,9 +6,14 @@ use drop_struct_macro_derive::DropStructMacro;
 use failure::Error;
 use ffi_toolkit::free_c_str;
 use libc;
-use sector_builder::{SealedSectorHealth, SectorBuilderErr, SectorManagerErr};
+use sector_builder::{
+    ChecksumAlgo, SealedSectorHealth, SectorBuilderErr, SectorManagerErr, StagedSectorFilter,
+    StagedWriteStrategy, StaleSealPolicy,
+};
 
-use crate::api::{SectorBuilder, SimpleSectorBuilder};
+use crate::api::FFISealTicket;
+use crate::handles::{HandleInUse, UnknownHandle};
+use crate::operations::UnknownOperation;
 
 #[repr(C)]
 #[derive(PartialEq, Debug)]
@@ -31,6 +36,97 @@ impl From<SealedSectorHealth> for FFISealedSectorHealth {
     }
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FFIChecksumAlgo {
+    Blake2b = 0,
+    Blake3 = 1,
+}
+
+impl From<ChecksumAlgo> for FFIChecksumAlgo {
+    fn from(algo: ChecksumAlgo) -> Self {
+        match algo {
+            ChecksumAlgo::Blake2b => FFIChecksumAlgo::Blake2b,
+            ChecksumAlgo::Blake3 => FFIChecksumAlgo::Blake3,
+        }
+    }
+}
+
+impl From<FFIChecksumAlgo> for ChecksumAlgo {
+    fn from(algo: FFIChecksumAlgo) -> Self {
+        match algo {
+            FFIChecksumAlgo::Blake2b => ChecksumAlgo::Blake2b,
+            FFIChecksumAlgo::Blake3 => ChecksumAlgo::Blake3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FFIStagedWriteStrategy {
+    Seek = 0,
+    Positional = 1,
+    Direct = 2,
+}
+
+impl From<FFIStagedWriteStrategy> for StagedWriteStrategy {
+    fn from(strategy: FFIStagedWriteStrategy) -> Self {
+        match strategy {
+            FFIStagedWriteStrategy::Seek => StagedWriteStrategy::Seek,
+            FFIStagedWriteStrategy::Positional => StagedWriteStrategy::Positional,
+            FFIStagedWriteStrategy::Direct => StagedWriteStrategy::Direct,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FFIStagedSectorFilter {
+    All = 0,
+    Pending = 1,
+    Failed = 2,
+    Sealing = 3,
+}
+
+impl From<FFIStagedSectorFilter> for StagedSectorFilter {
+    fn from(filter: FFIStagedSectorFilter) -> Self {
+        match filter {
+            FFIStagedSectorFilter::All => StagedSectorFilter::All,
+            FFIStagedSectorFilter::Pending => StagedSectorFilter::Pending,
+            FFIStagedSectorFilter::Failed => StagedSectorFilter::Failed,
+            FFIStagedSectorFilter::Sealing => StagedSectorFilter::Sealing,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FFIStaleSealPolicy {
+    Resume = 0,
+    MarkFailed = 1,
+    RevertToPending = 2,
+}
+
+impl From<FFIStaleSealPolicy> for StaleSealPolicy {
+    fn from(policy: FFIStaleSealPolicy) -> Self {
+        match policy {
+            FFIStaleSealPolicy::Resume => StaleSealPolicy::Resume,
+            FFIStaleSealPolicy::MarkFailed => StaleSealPolicy::MarkFailed,
+            FFIStaleSealPolicy::RevertToPending => StaleSealPolicy::RevertToPending,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFIReplicationStatus {
+    None = 0,
+    Pending = 1,
+    Replicated = 2,
+    Failed = 3,
+    Copying = 4,
+}
+
 #[repr(C)]
 #[derive(PartialEq, Debug)]
 pub enum FCPResponseStatus {
@@ -39,6 +135,10 @@ pub enum FCPResponseStatus {
     FCPUnclassifiedError = 1,
     FCPCallerError = 2,
     FCPReceiverError = 3,
+    // Another sector builder holds a lock on one of the directories.
+    FCPDirectoryInUse = 4,
+    // The scheduler is overloaded and rejected the call, which may be retried.
+    FCPSchedulerBusy = 5,
 }
 
 #[repr(C)]
@@ -50,6 +150,47 @@ pub enum FFISealStatus {
     Sealing = 3,
 }
 
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFISealScheduleOutcome {
+    Scheduled = 0,
+    Skipped = 1,
+    Error = 2,
+}
+
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFIOperationStatus {
+    Pending = 0,
+    Done = 1,
+    Failed = 2,
+}
+
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFILastShutdown {
+    Fresh = 0,
+    Clean = 1,
+    Crash = 2,
+}
+
+#[repr(C)]
+#[derive(PartialEq, Debug)]
+pub enum FFISealFailure {
+    None = 0,
+    ParamsMissing = 1,
+    Io = 2,
+    ProofGeneration = 3,
+    Cancelled = 4,
+    OutOfMemory = 5,
+    ReplicaMismatch = 6,
+    Other = 7,
+    TimedOut = 8,
+    StagedDataCorrupted = 9,
+    Interrupted = 10,
+    WorkerCrashed = 11,
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// GeneratePoSTResult
 //////////////////////
@@ -60,6 +201,10 @@ pub struct GeneratePoStResponse {
     pub error_msg: *const libc::c_char,
     pub proof_len: libc::size_t,
     pub proof_ptr: *const u8,
+    // sectors proven as faulty, including those marked faulty to meet the
+    // deadline and those whose replica is unavailable
+    pub faults_len: libc::size_t,
+    pub faults_ptr: *const u64,
 }
 
 impl Default for GeneratePoStResponse {
@@ -69,6 +214,106 @@ impl Default for GeneratePoStResponse {
             error_msg: ptr::null(),
             proof_len: 0,
             proof_ptr: ptr::null(),
+            faults_len: 0,
+            faults_ptr: ptr::null(),
+            data_len: 0,
+            data_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GeneratePoStAsyncResponse
+/////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GeneratePoStAsyncResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    // id through which the operation is polled
+    pub op_id: u64,
+}
+
+impl Default for GeneratePoStAsyncResponse {
+    fn default() -> GeneratePoStAsyncResponse {
+        GeneratePoStAsyncResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            op_id: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ReadPieceAsyncResponse
+//////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct ReadPieceAsyncResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    // id through which the operation is polled
+    pub op_id: u64,
+}
+
+impl Default for ReadPieceAsyncResponse {
+    fn default() -> ReadPieceAsyncResponse {
+        ReadPieceAsyncResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            op_id: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// CancelResponse
+//////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct CancelResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for CancelResponse {
+    fn default() -> CancelResponse {
+        CancelResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// PollResponse
+////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct PollResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub op_status: FFIOperationStatus,
+    // output of a generate_post operation which is done
+    pub proof_len: libc::size_t,
+    pub proof_ptr: *const u8,
+    pub faults_len: libc::size_t,
+    pub faults_ptr: *const u64,
+    // output of a read_piece_from_sealed_sector operation which is done
+    pub data_len: libc::size_t,
+    pub data_ptr: *const u8,
+}
+
+impl Default for PollResponse {
+    fn default() -> PollResponse {
+        PollResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            op_status: FFIOperationStatus::Pending,
+            proof_len: 0,
+            proof_ptr: ptr::null(),
+            faults_len: 0,
+            faults_ptr: ptr::null(),
         }
     }
 }
@@ -88,9 +333,44 @@ pub fn err_code_and_msg(err: &Error) -> (FCPResponseStatus, *const libc::c_char)
         Some(SectorBuilderErr::IncompleteWriteError { .. }) => return (FCPReceiverError, ptr),
         Some(SectorBuilderErr::Unrecoverable(_, _)) => return (FCPReceiverError, ptr),
         Some(SectorBuilderErr::PieceNotFound(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::SectorAlreadyExists(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::InvalidReplica(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::UnknownSealedStore(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::QuotaExceeded { .. }) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::SectorIdConflict { .. }) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::UnknownMiner(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::InvalidMiner(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::InvalidSectorClass(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::IdempotencyKeyReused(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::AlreadySealing(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::IllegalSealTransition { .. }) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::TaskTimedOut { .. }) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::TaskCancelled(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::WorkerPanicked { .. }) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::PoStDeadlineMissed(_)) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::DirectoryInUse(_)) => return (FCPDirectoryInUse, ptr),
+        Some(SectorBuilderErr::StagedDataCorrupted { .. }) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::InvalidPieceLabels(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::InvalidProverId(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::SchedulerBusy) => return (FCPSchedulerBusy, ptr),
+        Some(SectorBuilderErr::HandoffMismatch(_)) => return (FCPCallerError, ptr),
+        Some(SectorBuilderErr::ParamsMissing(_)) => return (FCPReceiverError, ptr),
+        Some(SectorBuilderErr::UnsealedBytesTruncated { .. }) => return (FCPReceiverError, ptr),
         None => (),
     }
 
+    if err.downcast_ref::<UnknownHandle>().is_some() {
+        return (FCPCallerError, ptr);
+    }
+
+    if err.downcast_ref::<HandleInUse>().is_some() {
+        return (FCPCallerError, ptr);
+    }
+
+    if err.downcast_ref::<UnknownOperation>().is_some() {
+        return (FCPCallerError, ptr);
+    }
+
     match err.downcast_ref() {
         Some(SectorManagerErr::UnclassifiedError(_)) => return (FCPUnclassifiedError, ptr),
         Some(SectorManagerErr::CallerError(_)) => return (FCPCallerError, ptr),
@@ -109,7 +389,8 @@ pub fn err_code_and_msg(err: &Error) -> (FCPResponseStatus, *const libc::c_char)
 pub struct InitSectorBuilderResponse {
     pub status_code: FCPResponseStatus,
     pub error_msg: *const libc::c_char,
-    pub sector_builder: *mut SectorBuilder,
+    // handle through which the builder is referred to in other calls
+    pub sector_builder: u64,
 }
 
 impl Default for InitSectorBuilderResponse {
@@ -117,7 +398,51 @@ impl Default for InitSectorBuilderResponse {
         InitSectorBuilderResponse {
             status_code: FCPResponseStatus::FCPNoError,
             error_msg: ptr::null(),
-            sector_builder: ptr::null_mut(),
+            sector_builder: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// DestroySectorBuilderResponse
+////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct DestroySectorBuilderResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for DestroySectorBuilderResponse {
+    fn default() -> DestroySectorBuilderResponse {
+        DestroySectorBuilderResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// FreezeSectorBuilderResponse
+///////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FreezeSectorBuilderResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    // the serialized handoff bundle, from which the builder is thawed
+    pub bundle_len: libc::size_t,
+    pub bundle_ptr: *const u8,
+}
+
+impl Default for FreezeSectorBuilderResponse {
+    fn default() -> FreezeSectorBuilderResponse {
+        FreezeSectorBuilderResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            bundle_len: 0,
+            bundle_ptr: ptr::null(),
         }
     }
 }
@@ -127,7 +452,8 @@ impl Default for InitSectorBuilderResponse {
 pub struct InitSimpleSectorBuilderResponse {
     pub status_code: FCPResponseStatus,
     pub error_msg: *const libc::c_char,
-    pub sector_builder: *mut SimpleSectorBuilder,
+    // handle through which the builder is referred to in other calls
+    pub sector_builder: u64,
 }
 
 impl Default for InitSimpleSectorBuilderResponse {
@@ -135,7 +461,7 @@ impl Default for InitSimpleSectorBuilderResponse {
         InitSimpleSectorBuilderResponse {
             status_code: FCPResponseStatus::FCPNoError,
             error_msg: ptr::null(),
-            sector_builder: ptr::null_mut(),
+            sector_builder: 0,
         }
     }
 }
@@ -206,6 +532,10 @@ pub struct FFIPendingStagedSectorMetadata {
     pub sector_id: u64,
     pub pieces_len: libc::size_t,
     pub pieces_ptr: *const FFIPieceMetadata,
+    // seconds since the epoch at which the sector was provisioned, 0 if unknown
+    pub created_at: u64,
+    // true if the sector spilled over into the secondary staged sector directory
+    pub in_secondary_staged_dir: bool,
 }
 
 #[repr(C)]
@@ -301,18 +631,18 @@ impl Default for ReadPieceFromSealedSectorResponse {
 }
 
 ///////////////////////////////////////////////////////////////////////////////
-/// SealAllStagedSectorsResponse
+/// SetUnsealedRetentionResponse
 ////////////////////////////////
 #[repr(C)]
 #[derive(DropStructMacro)]
-pub struct SealAllStagedSectorsResponse {
+pub struct SetUnsealedRetentionResponse {
     pub status_code: FCPResponseStatus,
     pub error_msg: *const libc::c_char,
 }
 
-impl Default for SealAllStagedSectorsResponse {
-    fn default() -> SealAllStagedSectorsResponse {
-        SealAllStagedSectorsResponse {
+impl Default for SetUnsealedRetentionResponse {
+    fn default() -> SetUnsealedRetentionResponse {
+        SetUnsealedRetentionResponse {
             status_code: FCPResponseStatus::FCPNoError,
             error_msg: ptr::null(),
         }
@@ -320,111 +650,653 @@ impl Default for SealAllStagedSectorsResponse {
 }
 
 ///////////////////////////////////////////////////////////////////////////////
-/// GetSealStatusResponse
+/// PurgeUnsealedResponse
 /////////////////////////
 #[repr(C)]
 #[derive(DropStructMacro)]
-pub struct GetSealStatusResponse {
+pub struct PurgeUnsealedResponse {
     pub status_code: FCPResponseStatus,
     pub error_msg: *const libc::c_char,
+}
 
-    pub seal_status_code: FFISealStatus,
-
-    // sealing failed - here's the error
-    pub seal_error_msg: *const libc::c_char,
-
-    // sealed sector metadata
-    pub comm_d: [u8; 32],
-    pub comm_r: [u8; 32],
-    pub comm_r_star: [u8; 32],
-    pub sector_access: *const libc::c_char,
-    pub sector_id: u64,
-    pub proof_len: libc::size_t,
-    pub proof_ptr: *const u8,
-    pub pieces_len: libc::size_t,
-    pub pieces_ptr: *const FFIPieceMetadata,
+impl Default for PurgeUnsealedResponse {
+    fn default() -> PurgeUnsealedResponse {
+        PurgeUnsealedResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
 }
 
+///////////////////////////////////////////////////////////////////////////////
+/// ReadPieceWithProofResponse
+//////////////////////////////
 #[repr(C)]
 #[derive(DropStructMacro)]
-pub struct FFIPieceMetadata {
-    pub piece_key: *const libc::c_char,
-    pub num_bytes: u64,
+pub struct ReadPieceWithProofResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub data_len: libc::size_t,
+    pub data_ptr: *const u8,
+    pub sector_id: u64,
+    pub comm_d: [u8; 32],
     pub comm_p: [u8; 32],
-    pub piece_inclusion_proof_ptr: *const u8,
     pub piece_inclusion_proof_len: libc::size_t,
+    pub piece_inclusion_proof_ptr: *const u8,
 }
 
-impl Default for GetSealStatusResponse {
-    fn default() -> GetSealStatusResponse {
-        GetSealStatusResponse {
+impl Default for ReadPieceWithProofResponse {
+    fn default() -> ReadPieceWithProofResponse {
+        ReadPieceWithProofResponse {
             status_code: FCPResponseStatus::FCPNoError,
             error_msg: ptr::null(),
-            comm_d: Default::default(),
-            comm_r: Default::default(),
-            comm_r_star: Default::default(),
-            pieces_len: 0,
-            pieces_ptr: ptr::null(),
-            proof_len: 0,
-            proof_ptr: ptr::null(),
-            seal_error_msg: ptr::null(),
-            seal_status_code: FFISealStatus::Failed,
-            sector_access: ptr::null(),
+            data_len: 0,
+            data_ptr: ptr::null(),
             sector_id: 0,
+            comm_d: Default::default(),
+            comm_p: Default::default(),
+            piece_inclusion_proof_len: 0,
+            piece_inclusion_proof_ptr: ptr::null(),
         }
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
-/// FFIStagedSectorMetadata
-///////////////////////////
+/// SealAllStagedSectorsResponse
+////////////////////////////////
 #[repr(C)]
 #[derive(DropStructMacro)]
-pub struct FFIStagedSectorMetadata {
-    pub sector_access: *const libc::c_char,
-    pub sector_id: u64,
-    pub pieces_len: libc::size_t,
-    pub pieces_ptr: *const FFIPieceMetadata,
+pub struct SealAllStagedSectorsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
 
-    // must be one of: Pending, Failed, Sealing
-    pub seal_status_code: FFISealStatus,
+    // the ids of the sectors which were scheduled
+    pub sector_ids_ptr: *const u64,
+    pub sector_ids_len: libc::size_t,
 
-    // if sealing failed - here's the error
-    pub seal_error_msg: *const libc::c_char,
+    // the outcome for every staged sector which hasn't been sealed
+    pub results_ptr: *const FFISealScheduleResult,
+    pub results_len: libc::size_t,
 }
 
-///////////////////////////////////////////////////////////////////////////////
-/// FFISealedSectorMetadata
-///////////////////////////
 #[repr(C)]
 #[derive(DropStructMacro)]
-pub struct FFISealedSectorMetadata {
-    pub comm_d: [u8; 32],
-    pub comm_r: [u8; 32],
-    pub comm_r_star: [u8; 32],
-    pub pieces_len: libc::size_t,
-    pub pieces_ptr: *const FFIPieceMetadata,
-    pub proofs_len: libc::size_t,
-    pub proofs_ptr: *const u8,
-    pub sector_access: *const libc::c_char,
+pub struct FFISealScheduleResult {
     pub sector_id: u64,
-    pub health: FFISealedSectorHealth,
+    pub outcome: FFISealScheduleOutcome,
+    // why the sector was skipped or couldn't be scheduled; null if it was
+    // scheduled
+    pub message: *const libc::c_char,
+}
+
+impl Default for SealAllStagedSectorsResponse {
+    fn default() -> SealAllStagedSectorsResponse {
+        SealAllStagedSectorsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sector_ids_ptr: ptr::null(),
+            sector_ids_len: 0,
+            results_ptr: ptr::null(),
+            results_len: 0,
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
-/// GetSealedSectorsResponse
-////////////////////////////
+/// SetMaxNumStagedSectorsResponse
+//////////////////////////////////
 #[repr(C)]
 #[derive(DropStructMacro)]
-pub struct GetSealedSectorsResponse {
+pub struct SetMaxNumStagedSectorsResponse {
     pub status_code: FCPResponseStatus,
     pub error_msg: *const libc::c_char,
-
-    pub sectors_len: libc::size_t,
-    pub sectors_ptr: *const FFISealedSectorMetadata,
 }
 
-impl Default for GetSealedSectorsResponse {
+impl Default for SetMaxNumStagedSectorsResponse {
+    fn default() -> SetMaxNumStagedSectorsResponse {
+        SetMaxNumStagedSectorsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetSealQueueResponse
+////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetSealQueueResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub entries_len: libc::size_t,
+    pub entries_ptr: *const FFISealQueueEntry,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FFISealQueueEntry {
+    pub sector_id: u64,
+    // seconds since the epoch at which the sector was scheduled for sealing
+    pub enqueued_at: u64,
+    pub priority: i32,
+}
+
+impl Default for GetSealQueueResponse {
+    fn default() -> GetSealQueueResponse {
+        GetSealQueueResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            entries_len: 0,
+            entries_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetSealPriorityResponse
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetSealPriorityResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetSealPriorityResponse {
+    fn default() -> SetSealPriorityResponse {
+        SetSealPriorityResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// PromoteSealResponse
+///////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct PromoteSealResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for PromoteSealResponse {
+    fn default() -> PromoteSealResponse {
+        PromoteSealResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// CancelSealResponse
+//////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct CancelSealResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for CancelSealResponse {
+    fn default() -> CancelSealResponse {
+        CancelSealResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetReplicatorResponse
+/////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetReplicatorResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetReplicatorResponse {
+    fn default() -> SetReplicatorResponse {
+        SetReplicatorResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// ReplicateSealedSectorResponse
+/////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct ReplicateSealedSectorResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for ReplicateSealedSectorResponse {
+    fn default() -> ReplicateSealedSectorResponse {
+        ReplicateSealedSectorResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetSealIsolationResponse
+////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetSealIsolationResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetSealIsolationResponse {
+    fn default() -> SetSealIsolationResponse {
+        SetSealIsolationResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetSealMemoryWatermarkResponse
+//////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetSealMemoryWatermarkResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetSealMemoryWatermarkResponse {
+    fn default() -> SetSealMemoryWatermarkResponse {
+        SetSealMemoryWatermarkResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetStagedWriteConfigResponse
+////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetStagedWriteConfigResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetStagedWriteConfigResponse {
+    fn default() -> SetStagedWriteConfigResponse {
+        SetStagedWriteConfigResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetIngestRateLimitResponse
+//////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetIngestRateLimitResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetIngestRateLimitResponse {
+    fn default() -> SetIngestRateLimitResponse {
+        SetIngestRateLimitResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetTaskTimeoutsResponse
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetTaskTimeoutsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetTaskTimeoutsResponse {
+    fn default() -> SetTaskTimeoutsResponse {
+        SetTaskTimeoutsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// CheckSectorHealthResponse
+/////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct CheckSectorHealthResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub health: FFISealedSectorHealth,
+}
+
+impl Default for CheckSectorHealthResponse {
+    fn default() -> CheckSectorHealthResponse {
+        CheckSectorHealthResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            health: FFISealedSectorHealth::Unknown,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// HydrateSectorIntegrityResponse
+//////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct HydrateSectorIntegrityResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    // whether the sector lacked its checksum or length
+    pub hydrated: bool,
+}
+
+impl Default for HydrateSectorIntegrityResponse {
+    fn default() -> HydrateSectorIntegrityResponse {
+        HydrateSectorIntegrityResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            hydrated: false,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetPieceLayoutResponse
+//////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetPieceLayoutResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceLayout,
+}
+
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIPieceLayout {
+    pub piece_key: *const libc::c_char,
+    pub padded_start_byte: u64,
+    pub padded_len: u64,
+}
+
+impl Default for GetPieceLayoutResponse {
+    fn default() -> GetPieceLayoutResponse {
+        GetPieceLayoutResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            pieces_len: 0,
+            pieces_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetSealStatusResponse
+/////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetSealStatusResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub seal_status_code: FFISealStatus,
+
+    // sealing failed - here's the error
+    pub seal_error_msg: *const libc::c_char,
+
+    // sealing failed - here's why
+    pub seal_failure_code: FFISealFailure,
+
+    // sealed sector metadata
+    pub comm_d: [u8; 32],
+    pub comm_r: [u8; 32],
+    pub comm_r_star: [u8; 32],
+    pub sector_access: *const libc::c_char,
+    pub sector_id: u64,
+    pub proof_len: libc::size_t,
+    pub proof_ptr: *const u8,
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceMetadata,
+
+    // health of a sealed sector, if it was checked
+    pub health: FFISealedSectorHealth,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetSealStatusesResponse
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetSealStatusesResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    // one per requested sector, in the order of the requested ids
+    pub statuses_len: libc::size_t,
+    pub statuses_ptr: *const FFISectorSealStatus,
+}
+
+impl Default for GetSealStatusesResponse {
+    fn default() -> GetSealStatusesResponse {
+        GetSealStatusesResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            statuses_len: 0,
+            statuses_ptr: ptr::null(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFISectorSealStatus {
+    pub sector_id: u64,
+
+    // the status of the sector couldn't be determined (e.g. no sector with
+    // its id exists) - here's why
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub seal_status_code: FFISealStatus,
+
+    // sealing failed - here's the error
+    pub seal_error_msg: *const libc::c_char,
+
+    // sealing failed - here's why
+    pub seal_failure_code: FFISealFailure,
+
+    // sealed sector metadata
+    pub comm_d: [u8; 32],
+    pub comm_r: [u8; 32],
+    pub comm_r_star: [u8; 32],
+    pub sector_access: *const libc::c_char,
+    pub proof_len: libc::size_t,
+    pub proof_ptr: *const u8,
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceMetadata,
+}
+
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIPieceMetadata {
+    pub piece_key: *const libc::c_char,
+    pub num_bytes: u64,
+    pub comm_p: [u8; 32],
+    pub piece_inclusion_proof_ptr: *const u8,
+    pub piece_inclusion_proof_len: libc::size_t,
+    // seconds since the epoch until which the piece is to be stored, 0 if
+    // unknown
+    pub store_until: u64,
+
+    // id of the piece's deal, unset if has_deal_id is false
+    pub has_deal_id: bool,
+    pub deal_id: u64,
+
+    // labels supplied by the client when the piece was added
+    pub labels_len: libc::size_t,
+    pub labels_ptr: *const FFIPieceLabel,
+}
+
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIPieceLabel {
+    pub key: *const libc::c_char,
+    pub value_len: libc::size_t,
+    pub value_ptr: *const u8,
+}
+
+impl Default for GetSealStatusResponse {
+    fn default() -> GetSealStatusResponse {
+        GetSealStatusResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            comm_d: Default::default(),
+            comm_r: Default::default(),
+            comm_r_star: Default::default(),
+            pieces_len: 0,
+            pieces_ptr: ptr::null(),
+            proof_len: 0,
+            proof_ptr: ptr::null(),
+            seal_error_msg: ptr::null(),
+            seal_failure_code: FFISealFailure::None,
+            seal_status_code: FFISealStatus::Failed,
+            sector_access: ptr::null(),
+            sector_id: 0,
+            health: FFISealedSectorHealth::Unknown,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// FFIStagedSectorMetadata
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIStagedSectorMetadata {
+    pub sector_access: *const libc::c_char,
+    pub sector_id: u64,
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceMetadata,
+
+    // must be one of: Pending, Failed, Sealing
+    pub seal_status_code: FFISealStatus,
+
+    // if sealing failed - here's the error
+    pub seal_error_msg: *const libc::c_char,
+
+    // if sealing failed - here's why
+    pub seal_failure_code: FFISealFailure,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// FFISealedSectorMetadata
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFISealedSectorMetadata {
+    pub comm_d: [u8; 32],
+    pub comm_r: [u8; 32],
+    pub comm_r_star: [u8; 32],
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceMetadata,
+    pub proofs_len: libc::size_t,
+    pub proofs_ptr: *const u8,
+    pub sector_access: *const libc::c_char,
+    pub sector_id: u64,
+    pub health: FFISealedSectorHealth,
+
+    // id of the registered store holding the sealed sector-file, or null
+    pub sealed_sector_store: *const libc::c_char,
+
+    // directory holding the sealed sector-file if it has been moved out of
+    // the sealed sector directory (and not into a registered store), or null
+    pub sealed_sector_dir: *const libc::c_char,
+
+    // checksum of the whole sealed sector-file, calculated with checksum_algo
+    pub checksum_len: libc::size_t,
+    pub checksum_ptr: *const u8,
+    pub checksum_algo: FFIChecksumAlgo,
+
+    // checksums of the extents of the sealed sector-file
+    pub extent_checksums_len: libc::size_t,
+    pub extent_checksums_ptr: *const [u8; 32],
+
+    // number of bytes in the sealed sector-file
+    pub len: u64,
+
+    // ticket with which the sector was sealed, unset if has_seal_ticket is
+    // false
+    pub has_seal_ticket: bool,
+    pub seal_ticket: FFISealTicket,
+
+    // when, for how long and where the sector was sealed, unset if
+    // has_seal_timing is false
+    pub has_seal_timing: bool,
+    pub seal_started_at: u64,
+    pub seal_finished_at: u64,
+    pub seal_duration_ms: u64,
+    pub seal_checksum_duration_ms: u64,
+    pub seal_hostname: *const libc::c_char,
+
+    // when the seal proof was verified as the sector was imported, or 0 if
+    // it wasn't
+    pub seal_verified_at: u64,
+
+    // whether the replica has been copied to the replicator's secondary
+    // location; replication_detail holds the location of the copy once
+    // replicated, or the error if copying failed, and is null otherwise
+    pub replication_status: FFIReplicationStatus,
+    pub replicated_at: u64,
+    pub replication_detail: *const libc::c_char,
+
+    // the number of bytes of the replica copied so far, or 0 unless
+    // replication_status is Copying
+    pub replication_bytes_copied: u64,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetSealedSectorsResponse
+////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetSealedSectorsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub sectors_len: libc::size_t,
+    pub sectors_ptr: *const FFISealedSectorMetadata,
+}
+
+impl Default for GetSealedSectorsResponse {
     fn default() -> GetSealedSectorsResponse {
         GetSealedSectorsResponse {
             status_code: FCPResponseStatus::FCPNoError,
@@ -435,6 +1307,347 @@ impl Default for GetSealedSectorsResponse {
     }
 }
 
+///////////////////////////////////////////////////////////////////////////////
+/// GetExpiredSectorsResponse
+/////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetExpiredSectorsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub sectors_len: libc::size_t,
+    pub sectors_ptr: *const FFISealedSectorMetadata,
+}
+
+impl Default for GetExpiredSectorsResponse {
+    fn default() -> GetExpiredSectorsResponse {
+        GetExpiredSectorsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sectors_len: 0,
+            sectors_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetSealStatisticsResponse
+/////////////////////////////
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FFISealDurationStats {
+    pub num_sectors: u64,
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub mean_ms: u64,
+    pub median_ms: u64,
+}
+
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIHostSealStatistics {
+    pub hostname: *const libc::c_char,
+    pub stats: FFISealDurationStats,
+}
+
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetSealStatisticsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub overall: FFISealDurationStats,
+
+    pub per_host_len: libc::size_t,
+    pub per_host_ptr: *const FFIHostSealStatistics,
+}
+
+impl Default for GetSealStatisticsResponse {
+    fn default() -> GetSealStatisticsResponse {
+        GetSealStatisticsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            overall: Default::default(),
+            per_host_len: 0,
+            per_host_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetSupportedSectorSizesResponse
+///////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetSupportedSectorSizesResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub sector_sizes_len: libc::size_t,
+    pub sector_sizes_ptr: *const u64,
+}
+
+impl Default for GetSupportedSectorSizesResponse {
+    fn default() -> GetSupportedSectorSizesResponse {
+        GetSupportedSectorSizesResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sector_sizes_len: 0,
+            sector_sizes_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GeneratePieceCommitmentsResponse
+////////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GeneratePieceCommitmentsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    // one 32-byte comm_p per piece, in the order of the pieces
+    pub flattened_comm_ps_len: libc::size_t,
+    pub flattened_comm_ps_ptr: *const u8,
+}
+
+impl Default for GeneratePieceCommitmentsResponse {
+    fn default() -> GeneratePieceCommitmentsResponse {
+        GeneratePieceCommitmentsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            flattened_comm_ps_len: 0,
+            flattened_comm_ps_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// VerifySealResponse
+//////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct VerifySealResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub is_valid: bool,
+}
+
+impl Default for VerifySealResponse {
+    fn default() -> VerifySealResponse {
+        VerifySealResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            is_valid: false,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GeneratePieceCommitmentResponse
+///////////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GeneratePieceCommitmentResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+    pub comm_p: [u8; 32],
+}
+
+impl Default for GeneratePieceCommitmentResponse {
+    fn default() -> GeneratePieceCommitmentResponse {
+        GeneratePieceCommitmentResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            comm_p: [0; 32],
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// FFIDirectoryUsage
+/////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIDirectoryUsage {
+    pub path: *const libc::c_char,
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetStorageReportResponse
+////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetStorageReportResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub staged_bytes: u64,
+    pub sealed_bytes: u64,
+    pub num_pending_sectors: u64,
+    pub num_sealing_sectors: u64,
+    pub num_failed_sectors: u64,
+    pub num_sealed_sectors: u64,
+
+    pub directories_len: libc::size_t,
+    pub directories_ptr: *const FFIDirectoryUsage,
+}
+
+impl Default for GetStorageReportResponse {
+    fn default() -> GetStorageReportResponse {
+        GetStorageReportResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            staged_bytes: 0,
+            sealed_bytes: 0,
+            num_pending_sectors: 0,
+            num_sealing_sectors: 0,
+            num_failed_sectors: 0,
+            num_sealed_sectors: 0,
+            directories_len: 0,
+            directories_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetKvStoreStatsResponse
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetKvStoreStatsResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub size_on_disk: u64,
+    pub num_keys: u64,
+    // seconds since the epoch at which the store was last flushed, 0 if it
+    // hasn't been
+    pub last_flush: u64,
+}
+
+impl Default for GetKvStoreStatsResponse {
+    fn default() -> GetKvStoreStatsResponse {
+        GetKvStoreStatsResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            size_on_disk: 0,
+            num_keys: 0,
+            last_flush: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// SetJournalKeepLastNResponse
+///////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct SetJournalKeepLastNResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+}
+
+impl Default for SetJournalKeepLastNResponse {
+    fn default() -> SetJournalKeepLastNResponse {
+        SetJournalKeepLastNResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// CompactMetadataResponse
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct CompactMetadataResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    // statistics of the metadata store once it has been compacted
+    pub size_on_disk: u64,
+    pub num_keys: u64,
+    pub last_flush: u64,
+}
+
+impl Default for CompactMetadataResponse {
+    fn default() -> CompactMetadataResponse {
+        CompactMetadataResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            size_on_disk: 0,
+            num_keys: 0,
+            last_flush: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// FFIPieceProvenance
+//////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct FFIPieceProvenance {
+    pub piece_key: *const libc::c_char,
+    pub sector_id: u64,
+    // seconds since the epoch at which the piece was added
+    pub added_at: u64,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetPiecesByOriginResponse
+/////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetPiecesByOriginResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub pieces_len: libc::size_t,
+    pub pieces_ptr: *const FFIPieceProvenance,
+}
+
+impl Default for GetPiecesByOriginResponse {
+    fn default() -> GetPiecesByOriginResponse {
+        GetPiecesByOriginResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            pieces_len: 0,
+            pieces_ptr: ptr::null(),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetStagedSectorResponse
+///////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetStagedSectorResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub sector_ptr: *const FFIStagedSectorMetadata,
+    pub sector_len: libc::size_t,
+}
+
+impl Default for GetStagedSectorResponse {
+    fn default() -> GetStagedSectorResponse {
+        GetStagedSectorResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sector_ptr: ptr::null(),
+            sector_len: 0,
+        }
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// GetStagedSectorsResponse
 ////////////////////////////
@@ -458,3 +1671,82 @@ impl Default for GetStagedSectorsResponse {
         }
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetConfigResponse
+/////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetConfigResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub sector_size: u64,
+    pub porep_proof_partitions: u8,
+    pub prover_id: [u8; 32],
+
+    pub metadata_dir: *const libc::c_char,
+    pub sealed_sector_dir: *const libc::c_char,
+    pub staged_sector_dir: *const libc::c_char,
+    // null if no secondary staged sector directory is configured
+    pub secondary_staged_sector_dir: *const libc::c_char,
+
+    pub max_num_staged_sectors: u64,
+
+    // the storage quota - 0 if a limit isn't enforced
+    pub max_staged_bytes: u64,
+    pub max_sealed_bytes: u64,
+    pub max_staged_sectors: u64,
+}
+
+impl Default for GetConfigResponse {
+    fn default() -> GetConfigResponse {
+        GetConfigResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            sector_size: 0,
+            porep_proof_partitions: 0,
+            prover_id: [0; 32],
+            metadata_dir: ptr::null(),
+            sealed_sector_dir: ptr::null(),
+            staged_sector_dir: ptr::null(),
+            secondary_staged_sector_dir: ptr::null(),
+            max_num_staged_sectors: 0,
+            max_staged_bytes: 0,
+            max_sealed_bytes: 0,
+            max_staged_sectors: 0,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// GetStartupReportResponse
+////////////////////////////
+#[repr(C)]
+#[derive(DropStructMacro)]
+pub struct GetStartupReportResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const libc::c_char,
+
+    pub last_shutdown: FFILastShutdown,
+
+    pub interrupted_seals_len: libc::size_t,
+    pub interrupted_seals_ptr: *const u64,
+
+    pub truncated_sectors_len: libc::size_t,
+    pub truncated_sectors_ptr: *const u64,
+}
+
+impl Default for GetStartupReportResponse {
+    fn default() -> GetStartupReportResponse {
+        GetStartupReportResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            last_shutdown: FFILastShutdown::Fresh,
+            interrupted_seals_len: 0,
+            interrupted_seals_ptr: ptr::null(),
+            truncated_sectors_len: 0,
+            truncated_sectors_ptr: ptr::null(),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+#[macro_use]
+extern crate criterion;
+
+mod common;
+
+use criterion::{BatchSize, Criterion, ParameterizedBenchmark, Throughput};
+use sector_builder::{SecondsSinceEpoch, StagedWriteConfig, StagedWriteStrategy};
+use tempfile::tempdir;
+
+use common::*;
+
+fn add_piece_benchmark(c: &mut Criterion) {
+    let params = vec![
+        1016,             // 1KiB once padded
+        1024 * 1016,      // 1MiB once padded
+        64 * 1024 * 1016, // 64MiB once padded
+    ];
+
+    c.bench(
+        "add_piece",
+        ParameterizedBenchmark::new(
+            "metadata_manager",
+            |b, bytes| {
+                // each piece is added to an empty sector of a new manager, so
+                // that the staged sectors don't grow with the number of
+                // iterations
+                b.iter_batched(
+                    || {
+                        let dir = tempdir().unwrap();
+                        let m = new_metadata_manager(
+                            dir.path(),
+                            TWO_HUNDRED_FIFTY_SIX_MIB_SECTOR_CLASS,
+                            1,
+                        );
+
+                        (dir, m)
+                    },
+                    |(dir, mut m)| {
+                        m.add_piece(
+                            "piece".to_string(),
+                            *bytes,
+                            piece_bytes(*bytes),
+                            SecondsSinceEpoch(0),
+                            None,
+                            Default::default(),
+                        )
+                        .unwrap();
+
+                        (dir, m)
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+            params,
+        )
+        .sample_size(20)
+        .throughput(|bytes| Throughput::Bytes(*bytes)),
+    );
+}
+
+// Compares the buffer sizes and strategies with which a 64MiB piece can be
+// written to its staged sector-file. Direct writes fall back to positional
+// ones where the staged sector directory doesn't support them.
+fn staged_write_benchmark(c: &mut Criterion) {
+    let bytes = 64 * 1024 * 1016;
+
+    let mut params = vec![];
+
+    for strategy in vec![
+        StagedWriteStrategy::Seek,
+        StagedWriteStrategy::Positional,
+        StagedWriteStrategy::Direct,
+    ] {
+        for buffer_size in vec![4 * 1024, 1024 * 1024, 16 * 1024 * 1024] {
+            params.push(StagedWriteConfig {
+                buffer_size,
+                strategy,
+            });
+        }
+    }
+
+    c.bench(
+        "add_piece",
+        ParameterizedBenchmark::new(
+            "staged_write",
+            move |b, config| {
+                b.iter_batched(
+                    || {
+                        let dir = tempdir().unwrap();
+                        let mut m = new_metadata_manager(
+                            dir.path(),
+                            TWO_HUNDRED_FIFTY_SIX_MIB_SECTOR_CLASS,
+                            1,
+                        );
+                        m.set_staged_write_config(*config);
+
+                        (dir, m)
+                    },
+                    |(dir, mut m)| {
+                        m.add_piece(
+                            "piece".to_string(),
+                            bytes,
+                            piece_bytes(bytes),
+                            SecondsSinceEpoch(0),
+                            None,
+                            Default::default(),
+                        )
+                        .unwrap();
+
+                        (dir, m)
+                    },
+                    BatchSize::PerIteration,
+                )
+            },
+            params,
+        )
+        .sample_size(10)
+        .throughput(move |_| Throughput::Bytes(bytes)),
+    );
+}
+
+criterion_group!(benches, add_piece_benchmark, staged_write_benchmark);
+criterion_main!(benches);
@@ -2,7 +2,7 @@
 extern crate criterion;
 
 use criterion::{black_box, Criterion, ParameterizedBenchmark, Throughput};
-use sector_builder::calculate_checksum;
+use sector_builder::{calculate_checksum, calculate_extent_checksums};
 use tempfile::NamedTempFile;
 
 fn checksum_benchmark(c: &mut Criterion) {
@@ -24,6 +24,12 @@ fn checksum_benchmark(c: &mut Criterion) {
             },
             params,
         )
+        .with_function("calculate_extents", |b, bytes| {
+            let mut file = NamedTempFile::new().unwrap();
+            file.as_file_mut().set_len(*bytes).unwrap();
+
+            b.iter(|| black_box(calculate_extent_checksums(&file.path())))
+        })
         .sample_size(20)
         .throughput(|bytes| Throughput::Bytes(*bytes)),
     );
@@ -0,0 +1,136 @@
+// Helpers shared by the benchmarks. Not every benchmark uses all of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use filecoin_proofs::constants::{SECTOR_SIZE_256_MIB, SECTOR_SIZE_ONE_KIB};
+use sector_builder::*;
+use storage_proofs::sector::SectorId;
+
+pub const PROVER_ID: [u8; 31] = [0; 31];
+
+pub type BenchMetadataManager = SectorMetadataManager<SledKvs, ConcreteSectorStore>;
+
+pub const ONE_KIB_SECTOR_CLASS: SectorClass =
+    SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2));
+
+pub const TWO_HUNDRED_FIFTY_SIX_MIB_SECTOR_CLASS: SectorClass =
+    SectorClass(SectorSize(SECTOR_SIZE_256_MIB), PoRepProofPartitions(2));
+
+// Creates a metadata manager whose metadata and sectors are stored in the
+// provided directory, configured like the one SectorBuilder creates.
+pub fn new_metadata_manager(
+    dir: &Path,
+    sector_class: SectorClass,
+    max_num_staged_sectors: u64,
+) -> BenchMetadataManager {
+    fs::create_dir_all(dir.join("sealed")).unwrap();
+    fs::create_dir_all(dir.join("staged")).unwrap();
+
+    let kv_store = SledKvs::initialize(dir.join("metadata")).unwrap();
+    let sector_store = new_sector_store(sector_class, dir.join("sealed"), dir.join("staged"));
+
+    let max_user_bytes_per_staged_sector =
+        sector_store.sector_config().max_unsealed_bytes_per_sector();
+
+    SectorMetadataManager {
+        kv_store,
+        sector_store,
+        state: SectorBuilderState::new(SectorId::from(0)),
+        max_num_staged_sectors,
+        max_user_bytes_per_staged_sector,
+        prover_id: PROVER_ID,
+        sector_size: sector_class.0.into(),
+        sealed_stores: Default::default(),
+        quota: Default::default(),
+        seal_ticket: None,
+        staged_data_retention: Default::default(),
+        replica_transfers: Default::default(),
+        sector_id_provider: Box::new(MonotonicSectorIds),
+        task_timeouts: Default::default(),
+        events: Default::default(),
+        dirty_sectors: Default::default(),
+        snapshot_interval: None,
+        last_snapshot: Instant::now(),
+        checksum_algo: Default::default(),
+        auto_seal_policy: Default::default(),
+        post_duration_per_sector: None,
+        seal_isolation: Default::default(),
+        staged_write_config: Default::default(),
+        ingest_rate_limiter: None,
+        replicator: None,
+        clock: Arc::new(SystemClock),
+        published_state: Default::default(),
+        unpublished_changes: false,
+    }
+}
+
+// Fills the provided number of 1KiB sectors with a piece each.
+pub fn fill_one_kib_sectors(m: &mut BenchMetadataManager, num_sectors: u64) {
+    let piece_bytes_amount = u64::from(m.max_user_bytes_per_staged_sector);
+
+    for n in 0..num_sectors {
+        m.add_piece(
+            format!("piece-{}", n),
+            piece_bytes_amount,
+            piece_bytes(piece_bytes_amount),
+            SecondsSinceEpoch(0),
+            None,
+            Default::default(),
+        )
+        .unwrap();
+    }
+}
+
+// Produces the state of a store holding the provided number of sealed
+// sectors, as large stores do, without sealing them. Each sealed sector is
+// also recorded as a staged sector, as sealing leaves it.
+pub fn sealed_sectors_state(num_sectors: u64) -> SectorBuilderState {
+    let mut state = SectorBuilderState::new(SectorId::from(num_sectors));
+
+    for n in 1..=num_sectors {
+        let sector_id = SectorId::from(n);
+
+        let sealed = SealedSectorMetadata {
+            sector_id,
+            sector_access: SectorAccess::new(format!("sealed-{}", n)).unwrap(),
+            pieces: vec![PieceMetadata {
+                piece_key: format!("piece-{}", n),
+                num_bytes: UnpaddedBytesAmount(1016),
+                comm_p: Some([7; 32]),
+                piece_inclusion_proof: Some(vec![7; 64]),
+                idempotency_key: None,
+                store_until: None,
+                deal_id: Some(n),
+                labels: Default::default(),
+            }],
+            proof: vec![7; 192],
+            blake2b_checksum: vec![7; 32],
+            len: 1024,
+            ..Default::default()
+        };
+
+        state.staged.sectors.insert(
+            sector_id,
+            StagedSectorMetadata {
+                sector_id,
+                sector_access: SectorAccess::new(format!("staged-{}", n)).unwrap(),
+                pieces: sealed.pieces.clone(),
+                seal_status: SealStatus::Sealed(Box::new(sealed.clone())),
+                ..Default::default()
+            },
+        );
+
+        state.sealed.sectors.insert(sector_id, sealed);
+    }
+
+    state
+}
+
+pub fn piece_bytes(len: u64) -> impl Read {
+    io::repeat(7).take(len)
+}
@@ -0,0 +1,52 @@
+#[macro_use]
+extern crate criterion;
+
+mod common;
+
+use criterion::{black_box, Criterion, ParameterizedBenchmark};
+use sector_builder::StagedSectorFilter;
+use storage_proofs::sector::SectorId;
+use tempfile::tempdir;
+
+use common::*;
+
+fn metadata_manager_benchmark(c: &mut Criterion) {
+    let params = vec![1, 16, 128];
+
+    c.bench(
+        "metadata_manager",
+        ParameterizedBenchmark::new(
+            "get_seal_status",
+            |b, num_sectors| {
+                let dir = tempdir().unwrap();
+                let mut m = new_metadata_manager(dir.path(), ONE_KIB_SECTOR_CLASS, *num_sectors);
+                fill_one_kib_sectors(&mut m, *num_sectors);
+
+                let sector_id = SectorId::from(*num_sectors);
+
+                b.iter(|| black_box(m.get_seal_status(sector_id).unwrap()))
+            },
+            params,
+        )
+        .with_function("get_piece_layout", |b, num_sectors| {
+            let dir = tempdir().unwrap();
+            let mut m = new_metadata_manager(dir.path(), ONE_KIB_SECTOR_CLASS, *num_sectors);
+            fill_one_kib_sectors(&mut m, *num_sectors);
+
+            let sector_id = SectorId::from(*num_sectors);
+
+            b.iter(|| black_box(m.get_piece_layout(sector_id).unwrap()))
+        })
+        .with_function("get_staged_sector_filtered", |b, num_sectors| {
+            let dir = tempdir().unwrap();
+            let mut m = new_metadata_manager(dir.path(), ONE_KIB_SECTOR_CLASS, *num_sectors);
+            fill_one_kib_sectors(&mut m, *num_sectors);
+
+            b.iter(|| black_box(m.get_staged_sector_filtered(StagedSectorFilter::All)))
+        })
+        .sample_size(20),
+    );
+}
+
+criterion_group!(benches, metadata_manager_benchmark);
+criterion_main!(benches);
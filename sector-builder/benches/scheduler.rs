@@ -0,0 +1,57 @@
+#[macro_use]
+extern crate criterion;
+
+mod common;
+
+use std::fs::File;
+use std::io::Write;
+
+use criterion::{black_box, Criterion};
+use sector_builder::*;
+use storage_proofs::sector::SectorId;
+use tempfile::{tempdir, NamedTempFile};
+
+use common::*;
+
+// Measures the latency of a request which makes a round-trip through the
+// scheduler thread without involving the proofs backend. SectorBuilder can
+// only be initialized with a hydrated parameter cache, so the benchmark is
+// skipped if parameters for 1KiB sectors are missing.
+fn scheduler_benchmark(c: &mut Criterion) {
+    if let Err(err) = ensure_parameter_cache_hydrated(ONE_KIB_SECTOR_CLASS) {
+        eprintln!("skipping scheduler benchmark: {}", err);
+        return;
+    }
+
+    let dir = tempdir().unwrap();
+
+    let builder = SectorBuilder::<File>::init_from_metadata(
+        ONE_KIB_SECTOR_CLASS,
+        SectorId::from(0),
+        dir.path().join("metadata"),
+        PROVER_ID,
+        dir.path().join("sealed"),
+        dir.path().join("staged"),
+        SectorBuilderOptions::new(2),
+    )
+    .unwrap();
+
+    let mut piece_file = NamedTempFile::new().unwrap();
+    piece_file.write_all(&[7; 127]).unwrap();
+
+    let sector_id = builder
+        .add_piece(
+            "piece".to_string(),
+            piece_file.reopen().unwrap(),
+            127,
+            SecondsSinceEpoch(0),
+        )
+        .unwrap();
+
+    c.bench_function("scheduler get_seal_status", |b| {
+        b.iter(|| black_box(builder.get_seal_status(sector_id).unwrap()))
+    });
+}
+
+criterion_group!(benches, scheduler_benchmark);
+criterion_main!(benches);
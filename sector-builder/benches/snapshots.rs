@@ -0,0 +1,80 @@
+#[macro_use]
+extern crate criterion;
+
+mod common;
+
+use criterion::{black_box, Criterion, ParameterizedBenchmark, Throughput};
+use sector_builder::{load_snapshot, persist_snapshot, KeyValueStore, SledKvs, SnapshotKey};
+use tempfile::tempdir;
+
+use common::*;
+
+fn snapshots_benchmark(c: &mut Criterion) {
+    let params = vec![1, 16, 128];
+
+    c.bench(
+        "snapshots",
+        ParameterizedBenchmark::new(
+            "persist",
+            |b, num_sectors| {
+                let dir = tempdir().unwrap();
+                let mut m = new_metadata_manager(dir.path(), ONE_KIB_SECTOR_CLASS, *num_sectors);
+                fill_one_kib_sectors(&mut m, *num_sectors);
+
+                let key = SnapshotKey::new(m.prover_id, m.sector_size);
+
+                b.iter(|| persist_snapshot(&m.kv_store, &key, &m.state).unwrap())
+            },
+            params,
+        )
+        .with_function("load", |b, num_sectors| {
+            let dir = tempdir().unwrap();
+            let mut m = new_metadata_manager(dir.path(), ONE_KIB_SECTOR_CLASS, *num_sectors);
+            fill_one_kib_sectors(&mut m, *num_sectors);
+
+            let key = SnapshotKey::new(m.prover_id, m.sector_size);
+            persist_snapshot(&m.kv_store, &key, &m.state).unwrap();
+
+            b.iter(|| black_box(load_snapshot(&m.kv_store, &key).unwrap()))
+        })
+        .sample_size(20),
+    );
+}
+
+// Large stores mostly hold sealed sectors, whose metadata is larger than that
+// of the staged sectors above and which are too many to seal for a benchmark.
+fn large_snapshots_benchmark(c: &mut Criterion) {
+    let params = vec![1_000, 10_000];
+
+    c.bench(
+        "large_snapshots",
+        ParameterizedBenchmark::new(
+            "persist",
+            |b, num_sectors| {
+                let dir = tempdir().unwrap();
+                let kv_store = SledKvs::initialize(dir.path()).unwrap();
+                let state = sealed_sectors_state(*num_sectors);
+
+                let key = SnapshotKey::new(PROVER_ID, ONE_KIB_SECTOR_CLASS.0.into());
+
+                b.iter(|| persist_snapshot(&kv_store, &key, &state).unwrap())
+            },
+            params,
+        )
+        .with_function("load", |b, num_sectors| {
+            let dir = tempdir().unwrap();
+            let kv_store = SledKvs::initialize(dir.path()).unwrap();
+            let state = sealed_sectors_state(*num_sectors);
+
+            let key = SnapshotKey::new(PROVER_ID, ONE_KIB_SECTOR_CLASS.0.into());
+            persist_snapshot(&kv_store, &key, &state).unwrap();
+
+            b.iter(|| black_box(load_snapshot(&kv_store, &key).unwrap()))
+        })
+        .sample_size(10)
+        .throughput(|num_sectors| Throughput::Elements(*num_sectors)),
+    );
+}
+
+criterion_group!(benches, snapshots_benchmark, large_snapshots_benchmark);
+criterion_main!(benches);
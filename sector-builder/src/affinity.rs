@@ -0,0 +1,124 @@
+use std::fs;
+
+use crate::error::{err_unrecov, Result};
+
+/// The CPUs to which each worker is pinned, indexed by worker. A worker with
+/// no (or an empty) CPU set runs wherever the OS schedules it. Pinning the
+/// workers of a dual-socket machine to the CPUs of a NUMA node each keeps
+/// their memory traffic local to that node. Seals in child processes (see
+/// SealIsolation) inherit the pinning of their worker.
+///
+/// Pinning is only supported on Linux, and is ignored elsewhere.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkerAffinity(pub Vec<Vec<usize>>);
+
+impl WorkerAffinity {
+    /// Pins each worker to the CPUs of the corresponding NUMA node, as listed
+    /// in /sys/devices/system/node/node<N>/cpulist.
+    pub fn numa_nodes(nodes: &[usize]) -> Result<WorkerAffinity> {
+        nodes
+            .iter()
+            .map(|node| {
+                let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+                let cpu_list = fs::read_to_string(&path).map_err(|err| {
+                    err_unrecov(format!(
+                        "could not read CPUs of NUMA node {}: {}",
+                        node, err
+                    ))
+                })?;
+
+                parse_cpu_list(&cpu_list)
+            })
+            .collect::<Result<_>>()
+            .map(WorkerAffinity)
+    }
+
+    /// Returns the CPUs to which the worker is pinned, if any.
+    pub fn cpus(&self, worker_id: usize) -> Option<&[usize]> {
+        self.0
+            .get(worker_id)
+            .filter(|cpus| !cpus.is_empty())
+            .map(Vec::as_slice)
+    }
+}
+
+/// Parses a list of CPUs in the kernel's format, e.g. "0-3,8,10-11".
+pub fn parse_cpu_list(cpu_list: &str) -> Result<Vec<usize>> {
+    let invalid = || err_unrecov(format!("invalid CPU list: {:?}", cpu_list));
+
+    let mut cpus = Vec::new();
+
+    for range in cpu_list.trim().split(',').filter(|range| !range.is_empty()) {
+        let mut bounds = range.splitn(2, '-');
+
+        let first: usize = bounds
+            .next()
+            .and_then(|cpu| cpu.trim().parse().ok())
+            .ok_or_else(invalid)?;
+
+        let last: usize = match bounds.next() {
+            Some(cpu) => cpu.trim().parse().map_err(|_| invalid())?,
+            None => first,
+        };
+
+        if last < first {
+            return Err(invalid().into());
+        }
+
+        cpus.extend(first..=last);
+    }
+
+    Ok(cpus)
+}
+
+// Pins the calling thread to the provided CPUs.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpus: &[usize]) -> Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+
+        for cpu in cpus {
+            if *cpu >= 8 * std::mem::size_of::<libc::cpu_set_t>() {
+                return Err(err_unrecov(format!("CPU {} is out of range", cpu)).into());
+            }
+
+            libc::CPU_SET(*cpu, &mut set);
+        }
+
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpus: &[usize]) -> Result<()> {
+    Err(err_unrecov("pinning workers to CPUs is only supported on Linux").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpu_lists() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n").unwrap(),
+            vec![0, 1, 2, 3, 8, 10, 11]
+        );
+        assert_eq!(parse_cpu_list("").unwrap(), Vec::<usize>::new());
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+
+    #[test]
+    fn unpinned_workers_have_no_cpus() {
+        let affinity = WorkerAffinity(vec![vec![], vec![2, 3]]);
+
+        assert_eq!(affinity.cpus(0), None);
+        assert_eq!(affinity.cpus(1), Some(&[2, 3][..]));
+        assert_eq!(affinity.cpus(2), None);
+    }
+}
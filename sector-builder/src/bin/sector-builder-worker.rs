@@ -0,0 +1,9 @@
+// Seals a single sector on behalf of a SectorBuilder which isolates its seal
+// tasks in child processes (see SealIsolation::ChildProcess). The task is
+// read from stdin and its result written to stdout.
+fn main() {
+    if let Err(err) = sector_builder::run_isolated_seal() {
+        eprintln!("seal worker failed: {}", err);
+        std::process::exit(1);
+    }
+}
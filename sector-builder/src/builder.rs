@@ -1,44 +1,175 @@
 use std::fs;
 use std::path::Path;
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 use filecoin_proofs::error::ExpectWithBacktrace;
-use filecoin_proofs::types::{PoRepConfig, PoStConfig, SectorClass};
+use filecoin_proofs::types::{
+    PoRepConfig, PoRepProofPartitions, PoStConfig, SectorClass, SectorSize,
+};
+use storage_proofs::rational_post;
 use storage_proofs::sector::SectorId;
 
+use crate::affinity::WorkerAffinity;
+use crate::clock::Clock;
 use crate::constants::*;
-use crate::disk_backed_storage::new_sector_store;
-use crate::error::{Result, SectorBuilderErr};
+use crate::dir_lock::{lock_dirs, DirLock};
+use crate::disk_backed_storage::{new_sector_store_with_spill_over, SectorAccessProto};
+use crate::encryption::{remove_decrypted_staged_sectors, StagedDataKey};
+use crate::error::{
+    err_handoff_mismatch, err_invalid_sector_class, err_scheduler_busy, err_unrecov,
+    err_unsealed_truncated, Result, SectorBuilderErr,
+};
+use crate::events::{EventBus, SectorBuilderEvent};
+use crate::handoff::{HandoffBundle, InitParams};
 use crate::helpers;
 use crate::helpers::SnapshotKey;
-use crate::kv_store::{KeyValueStore, SledKvs};
+use crate::isolation::SealIsolation;
+use crate::kv_store::{KeyValueStore, KvStoreStats, SledKvs};
 use crate::metadata::*;
 use crate::metadata_manager::SectorMetadataManager;
-use crate::scheduler::{PerformHealthCheck, Scheduler, SchedulerTask};
-use crate::state::SectorBuilderState;
+use crate::quota::StorageQuota;
+use crate::replication::SealedSectorReplicator;
+use crate::scheduler::{
+    OverloadPolicy, PerformHealthCheck, Scheduler, SchedulerConfig, SchedulerTask,
+};
+use crate::seal_queue::SealQueueEntry;
+use crate::sector_id_provider::SectorIdProvider;
+use crate::staged_writer::StagedWriteConfig;
+use crate::state::{PublishedState, SectorBuilderState};
+use crate::store::MappedBytes;
+use crate::watchdog::{CancellationToken, TaskTimeouts};
 use crate::worker::*;
 use crate::SectorStore;
 
 const FATAL_NOLOAD: &str = "could not load snapshot";
 
+// A SectorBuilder may be called from many threads at once (it is Send and
+// Sync as long as the piece readers it's given are Send). All of its mutable
+// state is owned by the scheduler thread, which handles one call at a time;
+// long-running work is handed off to the workers.
+//
+// Runtime settings: most of a SectorBuilder's behaviour (e.g. its storage
+// quota, task timeouts or replicator) is configured through setters after
+// it's been initialized. These settings are held by the scheduler and aren't
+// persisted with the metadata, so a SectorBuilder starts out with their
+// defaults whenever it's initialized and callers which change them must
+// apply them again after each (re)initialization.
 pub struct SectorBuilder<T> {
     // Prevents FFI consumers from queueing behind long-running seal operations.
-    worker_tx: mpsc::Sender<WorkerTask<T>>,
-
-    // For additional seal concurrency, add more workers here.
-    workers: Vec<Worker>,
+    // The pool may be shared with the SectorBuilders of other miners (see
+    // MultiMinerSectorBuilder).
+    workers: Arc<WorkerPool<T>>,
 
     // The main worker's queue.
     scheduler_tx: mpsc::SyncSender<SchedulerTask<T>>,
 
+    // What happens to calls which can't be queued for the main worker.
+    overload_policy: OverloadPolicy,
+
     // The main worker. Owns all mutable state for the SectorBuilder.
     scheduler: Scheduler,
+
+    // Delivers events emitted by the scheduler and workers to subscribers.
+    events: EventBus,
+
+    // What was found when the SectorBuilder was initialized.
+    startup_report: StartupReport,
+
+    // The configuration the SectorBuilder was initialized with. Its limits
+    // are owned by the scheduler, which may have changed them since.
+    config: SectorBuilderConfig,
+
+    // What the SectorBuilder was initialized with beyond its config, which
+    // is handed off when it's frozen.
+    params: InitParams,
+
+    // The state as of the scheduler's last change to it, from which queries
+    // which don't touch the disk are served without going through the
+    // scheduler.
+    published_state: PublishedState,
+
+    // Keeps other SectorBuilders out of this one's directories. Released
+    // once the scheduler and workers have shut down.
+    _dir_locks: Vec<DirLock>,
+}
+
+/// How a SectorBuilder is initialized beyond where its metadata and sectors
+/// live (see SectorBuilder::init_from_metadata).
+#[derive(Clone)]
+pub struct SectorBuilderOptions {
+    pub max_num_staged_sectors: u64,
+    pub worker_affinity: WorkerAffinity,
+    pub staged_data_key: Option<StagedDataKey>,
+    pub sector_access_proto: SectorAccessProto,
+    pub scheduler_config: SchedulerConfig,
+    pub staged_spill_over: Option<StagedSpillOver>,
+    pub stale_seal_policy: StaleSealPolicy,
+}
+
+impl SectorBuilderOptions {
+    // Returns the options with which a SectorBuilder accepts pieces into at
+    // most the provided number of staged sectors, and is otherwise
+    // configured with the defaults: unpinned workers, unencrypted staged
+    // sector-files, the default scheduler config, no spill-over and
+    // resumption of interrupted seals.
+    pub fn new(max_num_staged_sectors: u64) -> SectorBuilderOptions {
+        SectorBuilderOptions {
+            max_num_staged_sectors,
+            worker_affinity: Default::default(),
+            staged_data_key: None,
+            sector_access_proto: Default::default(),
+            scheduler_config: Default::default(),
+            staged_spill_over: None,
+            stale_seal_policy: Default::default(),
+        }
+    }
 }
 
 impl<R: 'static + Send + std::io::Read> SectorBuilder<R> {
     // Initialize and return a SectorBuilder from metadata persisted to disk if
     // it exists. Otherwise, initialize and return a fresh SectorBuilder. The
     // metadata key is equal to the prover_id.
+    //
+    // The last committed sector id seeds the sector id counter of a fresh
+    // SectorBuilder. When metadata has been persisted, the counter is derived
+    // from it instead, and an error is produced if the provided id is ahead of
+    // every sector id in the metadata (as subsequent ids would collide with
+    // committed ones).
+    //
+    // The remaining configuration is taken from the provided options.
+    // Workers are pinned to the CPUs given for them by the worker affinity,
+    // if any.
+    //
+    // If a staged data key is provided, staged sector-files are encrypted
+    // with it at rest (see StagedDataKey). Staged sector-files which exist
+    // already must have been written with the same key.
+    //
+    // New sector-files are named according to the sector access protocol,
+    // e.g. s-<miner>-<sector id> for SectorAccessProto::Lotus, as expected by
+    // lotus tooling. Sectors which exist already keep their names.
+    //
+    // Calls are queued for the scheduler according to the scheduler config:
+    // by default, a caller blocks until the scheduler is free. A channel
+    // with capacity queues as many calls, and the overload policy decides
+    // whether calls which don't fit block or fail with a SchedulerBusy error.
+    // Calls the scheduler makes to itself (e.g. once a seal has finished)
+    // always block.
+    //
+    // If a staged spill-over is provided, new staged sectors are provisioned
+    // in its directory while fewer than its threshold of bytes are available
+    // in the staged sector directory. Each staged sector records the
+    // directory in which it was provisioned, and an error is produced if a
+    // sector recorded in the secondary directory is loaded without one.
+    //
+    // Sectors which were being sealed when the previous SectorBuilder went
+    // away are handled according to the stale seal policy: their seals are
+    // resumed, or the sectors are marked as failed or reverted to pending.
+    //
+    // The metadata, sealed sector and staged sector directories (including
+    // the secondary one, if any) are locked for as long as the SectorBuilder
+    // exists. An error is produced if another SectorBuilder, in this or any
+    // other process, holds a lock on any of them.
     pub fn init_from_metadata(
         sector_class: SectorClass,
         last_committed_sector_id: SectorId,
@@ -46,25 +177,96 @@ impl<R: 'static + Send + std::io::Read> SectorBuilder<R> {
         prover_id: [u8; 31],
         sealed_sector_dir: impl AsRef<Path>,
         staged_sector_dir: impl AsRef<Path>,
-        max_num_staged_sectors: u8,
+        options: SectorBuilderOptions,
+    ) -> Result<SectorBuilder<R>> {
+        let workers = WorkerPool::start(NUM_WORKERS, &options.worker_affinity);
+
+        SectorBuilder::init_with_worker_pool(
+            sector_class,
+            last_committed_sector_id,
+            metadata_dir,
+            prover_id,
+            sealed_sector_dir,
+            staged_sector_dir,
+            options,
+            workers,
+        )
+    }
+
+    // Initializes a SectorBuilder as init_from_metadata does, whose tasks are
+    // run by the provided worker pool, e.g. one shared with the
+    // SectorBuilders of other miners. The pool's workers were pinned when it
+    // was started, so the provided worker affinity is only recorded.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn init_with_worker_pool(
+        sector_class: SectorClass,
+        last_committed_sector_id: SectorId,
+        metadata_dir: impl AsRef<Path>,
+        prover_id: [u8; 31],
+        sealed_sector_dir: impl AsRef<Path>,
+        staged_sector_dir: impl AsRef<Path>,
+        options: SectorBuilderOptions,
+        workers: Arc<WorkerPool<R>>,
     ) -> Result<SectorBuilder<R>> {
+        let SectorBuilderOptions {
+            max_num_staged_sectors,
+            worker_affinity,
+            staged_data_key,
+            sector_access_proto,
+            scheduler_config,
+            staged_spill_over,
+            stale_seal_policy,
+        } = options;
+
         ensure_parameter_cache_hydrated(sector_class)?;
 
-        // Configure the scheduler's rendezvous channel.
-        let (scheduler_tx, scheduler_rx) = mpsc::sync_channel(0);
+        let mut locked_dirs = vec![
+            metadata_dir.as_ref(),
+            sealed_sector_dir.as_ref(),
+            staged_sector_dir.as_ref(),
+        ];
 
-        // Configure workers and channels.
-        let (worker_tx, workers) = {
-            let (tx, rx) = mpsc::channel();
-            let rx = Arc::new(Mutex::new(rx));
+        if let Some(spill_over) = &staged_spill_over {
+            locked_dirs.push(spill_over.dir.as_path());
+        }
+
+        let dir_locks = lock_dirs(&locked_dirs)?;
 
-            let workers = (0..NUM_WORKERS)
-                .map(|n| Worker::start(n, rx.clone(), prover_id))
-                .collect();
+        // A process which crashed while sealing may have left the plaintext
+        // of encrypted staged sector-files behind.
+        for staged_dir in &locked_dirs[2..] {
+            remove_decrypted_staged_sectors(staged_dir)?;
+        }
+
+        let config = SectorBuilderConfig {
+            sector_class,
+            prover_id,
+            metadata_dir: metadata_dir.as_ref().to_path_buf(),
+            sealed_sector_dir: sealed_sector_dir.as_ref().to_path_buf(),
+            staged_sector_dir: staged_sector_dir.as_ref().to_path_buf(),
+            secondary_staged_sector_dir: staged_spill_over
+                .as_ref()
+                .map(|spill_over| spill_over.dir.clone()),
+            max_num_staged_sectors,
+            quota: Default::default(),
+        };
 
-            (tx, workers)
+        let params = InitParams {
+            staged_spill_over: staged_spill_over.clone(),
+            worker_affinity: worker_affinity.clone(),
+            sector_access_proto: sector_access_proto.clone(),
+            scheduler_config,
+            encrypted_staged_data: staged_data_key.is_some(),
         };
 
+        // Configure the scheduler's rendezvous channel.
+        let (scheduler_tx, scheduler_rx) = mpsc::sync_channel(scheduler_config.channel_capacity);
+
+        let events = EventBus::default();
+
+        // Configure the channel through which tasks reach the workers.
+        let worker_tx = workers.handle(prover_id, events.clone());
+
         let sector_size = sector_class.0.into();
 
         // Initialize the key/value store in which we store metadata
@@ -74,40 +276,193 @@ impl<R: 'static + Send + std::io::Read> SectorBuilder<R> {
         // Initialize a SectorStore and wrap it in an Arc so we can access it
         // from multiple threads. Our implementation assumes that the
         // SectorStore is safe for concurrent access.
-        let sector_store = new_sector_store(sector_class, sealed_sector_dir, staged_sector_dir);
+        let sector_store = new_sector_store_with_spill_over(
+            sector_class,
+            sealed_sector_dir,
+            staged_sector_dir,
+            staged_data_key,
+            sector_access_proto,
+            staged_spill_over,
+        );
 
         // Build the scheduler's initial state. If available, we
-        // reconstitute this state from persisted metadata. If not, we
-        // create it from scratch.
-        let state = {
-            let loaded =
-                helpers::load_snapshot(&kv_store, &SnapshotKey::new(prover_id, sector_size))
-                    .expects(FATAL_NOLOAD)
-                    .map(Into::into);
-
-            loaded.unwrap_or_else(|| SectorBuilderState::new(last_committed_sector_id))
+        // reconstitute this state from persisted metadata, from which the next
+        // sector id is derived. If not, we create it from scratch.
+        //
+        // If the SectorBuilder which persisted the metadata didn't shut down,
+        // its staged sectors may hold data which the metadata doesn't record,
+        // so they're scanned for it.
+        let (state, startup_report) = {
+            let snapshot_key = SnapshotKey::new(prover_id, sector_size);
+
+            let loaded = helpers::load_snapshot(&kv_store, &snapshot_key).expects(FATAL_NOLOAD);
+
+            match loaded {
+                Some(mut state) => {
+                    state.reconcile_sector_id_nonce(last_committed_sector_id)?;
+                    helpers::check_staged_sector_dirs(&sector_store, &state.staged)?;
+
+                    let report = match helpers::take_shutdown_marker(&kv_store, &snapshot_key)? {
+                        Some(interrupted_seals) => StartupReport {
+                            last_shutdown: LastShutdown::Clean,
+                            interrupted_seals: interrupted_seals
+                                .into_iter()
+                                .map(|seal| seal.sector_id)
+                                .collect(),
+                            truncated_sectors: Vec::new(),
+                        },
+                        None => {
+                            let mut interrupted_seals: Vec<SectorId> = state
+                                .staged
+                                .sectors
+                                .values()
+                                .filter(|meta| meta.seal_status == SealStatus::Sealing)
+                                .map(|meta| meta.sector_id)
+                                .collect();
+
+                            interrupted_seals.sort();
+
+                            StartupReport {
+                                last_shutdown: LastShutdown::Crash,
+                                interrupted_seals,
+                                truncated_sectors: helpers::truncate_unrecorded_pieces(
+                                    &sector_store,
+                                    &state.staged,
+                                )?,
+                            }
+                        }
+                    };
+
+                    (state, report)
+                }
+                None => (
+                    SectorBuilderState::new(last_committed_sector_id),
+                    StartupReport {
+                        last_shutdown: LastShutdown::Fresh,
+                        interrupted_seals: Vec::new(),
+                        truncated_sectors: Vec::new(),
+                    },
+                ),
+            }
         };
 
-        let max_user_bytes_per_staged_sector =
-            sector_store.sector_config().max_unsealed_bytes_per_sector();
+        if startup_report.last_shutdown == LastShutdown::Crash {
+            warn!(
+                "recovered from a crash: {} seals were interrupted, {} staged sectors truncated",
+                startup_report.interrupted_seals.len(),
+                startup_report.truncated_sectors.len()
+            );
+        }
 
-        let m = SectorMetadataManager {
+        let m = SectorMetadataManager::new(
             kv_store,
             sector_store,
             state,
             max_num_staged_sectors,
-            max_user_bytes_per_staged_sector,
             prover_id,
-            sector_size,
-        };
+            events.clone(),
+        )?;
+
+        let published_state = m.published_state.clone();
 
-        let scheduler = Scheduler::start(scheduler_tx.clone(), scheduler_rx, worker_tx.clone(), m)?;
+        let scheduler = Scheduler::start(
+            scheduler_tx.clone(),
+            scheduler_rx,
+            worker_tx,
+            m,
+            stale_seal_policy,
+        )?;
 
         Ok(SectorBuilder {
             scheduler_tx,
+            overload_policy: scheduler_config.overload_policy,
             scheduler,
-            worker_tx,
             workers,
+            events,
+            startup_report,
+            config,
+            params,
+            published_state,
+            _dir_locks: dir_locks,
+        })
+    }
+
+    // Initializes a SectorBuilder from the bundle produced by freezing one,
+    // e.g. in the process which replaces the one it was frozen in during a
+    // rolling upgrade. It's initialized with the frozen SectorBuilder's
+    // config and limits, and restarts the seals it interrupted. The staged
+    // data key isn't part of the bundle, so it must be provided again if
+    // staged sector-files are encrypted. Neither are the runtime settings
+    // (see the note on runtime settings above SectorBuilder), which must be
+    // applied again to the thawed SectorBuilder.
+    //
+    // Fails with a HandoffMismatch error if the persisted metadata isn't the
+    // state the SectorBuilder was frozen with (e.g. as another SectorBuilder
+    // has used its directories since), as sectors or seals would be lost.
+    pub fn thaw(
+        bundle: HandoffBundle,
+        staged_data_key: Option<StagedDataKey>,
+    ) -> Result<SectorBuilder<R>> {
+        if bundle.params.encrypted_staged_data != staged_data_key.is_some() {
+            return Err(err_handoff_mismatch(if staged_data_key.is_some() {
+                "staged sector-files aren't encrypted, but a staged data key was provided"
+            } else {
+                "staged sector-files are encrypted, but no staged data key was provided"
+            })
+            .into());
+        }
+
+        let builder = SectorBuilder::init_from_metadata(
+            bundle.config.sector_class,
+            SectorId::from(bundle.state.staged.sector_id_nonce),
+            &bundle.config.metadata_dir,
+            bundle.config.prover_id,
+            &bundle.config.sealed_sector_dir,
+            &bundle.config.staged_sector_dir,
+            SectorBuilderOptions {
+                max_num_staged_sectors: bundle.config.max_num_staged_sectors,
+                worker_affinity: bundle.params.worker_affinity.clone(),
+                staged_data_key,
+                sector_access_proto: bundle.params.sector_access_proto.clone(),
+                scheduler_config: bundle.params.scheduler_config,
+                staged_spill_over: bundle.params.staged_spill_over.clone(),
+                stale_seal_policy: StaleSealPolicy::Resume,
+            },
+        )?;
+
+        bundle.check_thawed(&builder.startup_report, &builder.published_state.load())?;
+
+        builder.set_storage_quota(bundle.config.quota.clone())?;
+
+        Ok(builder)
+    }
+
+    // Stops this SectorBuilder and produces the bundle from which it's thawed
+    // in another process. Its state is persisted and a clean shutdown is
+    // recorded, as when it's dropped. As it's consumed, no call can be
+    // running or arrive while it's frozen. No more seals are handed to the
+    // workers, and freezing waits for the seals being run to finish (which
+    // may take as long as sealing a sector does), as well as the replica
+    // transfers (e.g. moves) and replication copies being made, so that their
+    // results are part of the handed off state. Seals waiting for a worker
+    // are recorded in the bundle and restarted once it's thawed.
+    pub fn freeze(mut self) -> Result<HandoffBundle> {
+        let config = self.get_config()?;
+
+        let (state, interrupted_seals) = log_unrecov(self.run_blocking(SchedulerTask::Freeze))?;
+
+        // the scheduler has stopped, so it isn't asked to shut down again
+        if let Some(thread) = self.scheduler.thread.take() {
+            let _ = thread
+                .join()
+                .map_err(|err| println!("err joining scheduler thread: {:?}", err));
+        }
+
+        Ok(HandoffBundle {
+            config,
+            params: self.params.clone(),
+            state,
+            interrupted_seals,
         })
     }
 
@@ -119,86 +474,893 @@ impl<R: 'static + Send + std::io::Read> SectorBuilder<R> {
         piece_file: R,
         piece_bytes_amount: u64,
         store_until: SecondsSinceEpoch,
+    ) -> Result<SectorId> {
+        self.add_piece_with_idempotency_key(
+            piece_key,
+            piece_file,
+            piece_bytes_amount,
+            store_until,
+            None,
+        )
+    }
+
+    // Stages user piece-bytes for sealing like add_piece. A piece added with
+    // an idempotency key is staged only once: retrying the call with the
+    // same key returns the id of the sector to which the piece was added
+    // without reading the piece file again. Reusing a key for a different
+    // piece produces an error. Keys are persisted with the piece's metadata.
+    pub fn add_piece_with_idempotency_key(
+        &self,
+        piece_key: String,
+        piece_file: R,
+        piece_bytes_amount: u64,
+        store_until: SecondsSinceEpoch,
+        idempotency_key: Option<String>,
+    ) -> Result<SectorId> {
+        self.add_piece_with_deal_info(
+            piece_key,
+            piece_file,
+            piece_bytes_amount,
+            store_until,
+            idempotency_key,
+            Default::default(),
+        )
+    }
+
+    // Stages user piece-bytes for sealing like add_piece_with_idempotency_key,
+    // recording the id of the piece's deal and the provided labels with the
+    // piece's metadata, so that sectors can be mapped back to deals. Produces
+    // an error if there are too many labels or a label is too long.
+    pub fn add_piece_with_deal_info(
+        &self,
+        piece_key: String,
+        piece_file: R,
+        piece_bytes_amount: u64,
+        store_until: SecondsSinceEpoch,
+        idempotency_key: Option<String>,
+        deal_info: PieceDealInfo,
     ) -> Result<SectorId> {
         log_unrecov(self.run_blocking(|tx| {
-            SchedulerTask::AddPiece(piece_key, piece_bytes_amount, piece_file, store_until, tx)
+            SchedulerTask::AddPiece(
+                piece_key,
+                piece_bytes_amount,
+                piece_file,
+                store_until,
+                idempotency_key,
+                deal_info,
+                tx,
+            )
         }))
     }
 
     // Returns sealing status for the sector with specified id. If no sealed or
-    // staged sector exists with the provided id, produce an error.
+    // staged sector exists with the provided id, produce an error. A sealed
+    // sector which is being resealed or repaired, or whose reseal or repair
+    // failed, reports that status instead of Sealed. Served from the
+    // published state, so it doesn't wait for the scheduler.
     pub fn get_seal_status(&self, sector_id: SectorId) -> Result<SealStatus> {
-        log_unrecov(self.run_blocking(|tx| SchedulerTask::GetSealStatus(sector_id, tx)))
+        let state = self.published_state.load();
+
+        helpers::get_seal_status(&state.staged, &state.sealed, sector_id)
+    }
+
+    // Returns the sealing status of each sector with the provided ids, in the
+    // order of the ids, or an error for each id with which no sealed or
+    // staged sector exists. The statuses are read from the same published
+    // state, so they're consistent with one another.
+    pub fn get_seal_statuses(&self, sector_ids: &[SectorId]) -> Vec<Result<SealStatus>> {
+        let state = self.published_state.load();
+
+        sector_ids
+            .iter()
+            .map(|sector_id| helpers::get_seal_status(&state.staged, &state.sealed, *sector_id))
+            .collect()
+    }
+
+    // Returns the sealing status of the sector with the provided id along
+    // with, if the sector has been sealed and a health check is requested,
+    // the health of its replica, checked in the provided mode. Unlike
+    // get_sealed_sectors with a health check, this hashes no other sector's
+    // replica.
+    pub fn get_seal_status_with_health_check(
+        &self,
+        sector_id: SectorId,
+        check_health: Option<HealthCheckMode>,
+    ) -> Result<(SealStatus, Option<SealedSectorHealth>)> {
+        if check_health.is_none() {
+            return self.get_seal_status(sector_id).map(|status| (status, None));
+        }
+
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::GetSealStatus(sector_id, check_health, tx)),
+        )
+    }
+
+    // Returns the padded start byte and length of each piece in the sector
+    // with the provided id, in the order in which the pieces were added, from
+    // which the ranges of a sector holding a piece can be served without
+    // recomputing its alignment. If no sealed or staged sector exists with
+    // the provided id, produce an error.
+    pub fn get_piece_layout(&self, sector_id: SectorId) -> Result<Vec<PieceLayout>> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::GetPieceLayout(sector_id, tx)))
     }
 
     // Unseals the sector containing the referenced piece and returns its
     // bytes. Produces an error if this sector builder does not have a sealed
     // sector containing the referenced piece.
     pub fn read_piece_from_sealed_sector(&self, piece_key: String) -> Result<Vec<u8>> {
-        log_unrecov(self.run_blocking(|tx| SchedulerTask::RetrievePiece(piece_key, tx)))
+        self.read_piece_from_sealed_sector_mapped(piece_key)
+            .map(|mapped| mapped.to_vec())
+    }
+
+    // Unseals the sector containing the referenced piece and maps the piece's
+    // bytes into memory instead of copying them into a buffer, which keeps
+    // memory pressure down when serving large retrievals.
+    pub fn read_piece_from_sealed_sector_mapped(&self, piece_key: String) -> Result<MappedBytes> {
+        log_unrecov(
+            self.start_read_piece_from_sealed_sector(piece_key, None)
+                .and_then(|rx| rx.recv().expects(FATAL_NORECV_TASK)),
+        )
+    }
+
+    // Starts retrieving the referenced piece like
+    // read_piece_from_sealed_sector_mapped without waiting for it. The result
+    // is sent to the returned receiver, which may be dropped to forget about
+    // the piece. Cancelling the provided token abandons the unseal.
+    pub fn start_read_piece_from_sealed_sector(
+        &self,
+        piece_key: String,
+        cancel: Option<CancellationToken>,
+    ) -> Result<mpsc::Receiver<Result<MappedBytes>>> {
+        let (tx, rx) = mpsc::sync_channel(1);
+
+        submit(
+            &self.scheduler_tx,
+            SchedulerTask::RetrievePiece(piece_key, cancel, tx),
+            self.overload_policy,
+        )?;
+
+        Ok(rx)
+    }
+
+    // Plans the retrieval of the referenced piece without executing it,
+    // producing the replica to unseal, the range of its unsealed bytes which
+    // holds the piece and the path to which it would be unsealed.
+    pub fn create_retrieve_piece_task_proto(
+        &self,
+        piece_key: String,
+    ) -> Result<UnsealTaskPrototype> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::PlanRetrievePiece(piece_key, tx)))
+    }
+
+    // Plans sealing the staged sector with the provided id without executing
+    // it or changing the sector's seal status, producing the paths of its
+    // staged sector-file and replica and the ticket with which it would be
+    // sealed. No sector-file is created. Sectors which are being sealed can't
+    // be planned.
+    pub fn create_seal_task_proto(&self, sector_id: SectorId) -> Result<SealTaskPrototype> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::PlanSeal(sector_id, tx)))
+    }
+
+    // Retrieves the bytes of each of the referenced pieces. Pieces are grouped
+    // by the sealed sector containing them and each sector is unsealed at most
+    // once, no matter how many of its pieces are requested. Results are
+    // returned per piece key, in the order in which the keys were provided.
+    pub fn read_pieces(&self, piece_keys: Vec<String>) -> Vec<(String, Result<Vec<u8>>)> {
+        let retrieved =
+            self.try_run_blocking(|tx| SchedulerTask::RetrievePieces(piece_keys.clone(), tx));
+
+        let (receivers, ranges) = match retrieved {
+            Ok(retrieved) => retrieved,
+            Err(_) => {
+                return piece_keys
+                    .into_iter()
+                    .map(|piece_key| (piece_key, Err(err_scheduler_busy().into())))
+                    .collect()
+            }
+        };
+
+        let unsealed: Vec<_> = receivers
+            .into_iter()
+            .map(|rx| rx.recv().expects(FATAL_NORECV_TASK))
+            .collect();
+
+        piece_keys
+            .into_iter()
+            .zip(ranges)
+            .map(|(piece_key, range)| {
+                let bytes = range.and_then(|range| match &unsealed[range.proto_index] {
+                    Ok(mapped) => unsealed_piece_bytes(&piece_key, mapped, range.offset, range.len),
+                    Err(err) => Err(err_unrecov(format!(
+                        "could not unseal piece {}: {}",
+                        piece_key, err
+                    ))
+                    .into()),
+                });
+
+                (piece_key, log_unrecov(bytes))
+            })
+            .collect()
+    }
+
+    // Unseals the sector containing the referenced piece and returns the
+    // piece's bytes along with its comm_p, its inclusion proof and the comm_d
+    // of the sector, so that a client can verify the piece without a second
+    // request.
+    pub fn read_piece_with_proof(&self, piece_key: String) -> Result<PieceWithProof> {
+        let proof = log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::GetPieceProof(piece_key.clone(), tx)),
+        )?;
+
+        let bytes = self.read_piece_from_sealed_sector(piece_key)?;
+
+        Ok(PieceWithProof { bytes, ..proof })
+    }
+
+    // Limits the storage this SectorBuilder may consume. Subsequent calls to
+    // add_piece fail with a QuotaExceeded error if the piece would exceed any
+    // of the quota's limits. By default, no limits are enforced. This is a
+    // runtime setting (see the note on runtime settings above SectorBuilder).
+    pub fn set_storage_quota(&self, quota: StorageQuota) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetStorageQuota(quota, tx)))
+    }
+
+    // Sets what happens to a staged sector's unsealed data once the sector has
+    // been sealed successfully. By default, the data is kept in the staged
+    // sector directory, which allows the sector to be sealed again (see
+    // reseal_sector). Deleting it reclaims its disk space. This is a runtime
+    // setting (see the note on runtime settings above SectorBuilder).
+    pub fn set_staged_data_retention(&self, retention: StagedDataRetention) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetStagedDataRetention(retention, tx)))
+    }
+
+    // Sets the strategy with which add_piece allocates the ids of the staged
+    // sectors it opens, e.g. to use sector ids assigned by the chain (see
+    // SuppliedSectorIds). An id is only taken from the provider once its
+    // sector has been provisioned; ids which are already in use are rejected.
+    // By default, ids are allocated by incrementing the highest known sector
+    // id.
+    // This is a runtime setting (see the note on runtime settings above
+    // SectorBuilder).
+    pub fn set_sector_id_provider(
+        &self,
+        sector_id_provider: Box<dyn SectorIdProvider>,
+    ) -> Result<()> {
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::SetSectorIdProvider(sector_id_provider, tx)),
+        )
+    }
+
+    // Sets the ticket with which staged sectors are sealed once add_piece
+    // schedules them for sealing. Until a ticket has been set, sectors are
+    // sealed without a recorded ticket.
+    pub fn set_seal_ticket(&self, seal_ticket: SealTicket) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetSealTicket(seal_ticket, tx)))
+    }
+
+    // Limits how long seal, unseal and PoSt tasks may run. A task which runs
+    // for longer fails with a TaskTimedOut error (a sector being sealed is
+    // marked failed), a TaskTimedOut event is emitted and the worker moves on
+    // to its next task. By default, tasks run for as long as they take. This
+    // is a runtime setting (see the note on runtime settings above
+    // SectorBuilder).
+    pub fn set_task_timeouts(&self, task_timeouts: TaskTimeouts) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetTaskTimeouts(task_timeouts, tx)))
+    }
+
+    // Replaces where workers seal sectors. Sealing in child processes keeps a
+    // crash while sealing (e.g. of a GPU driver, an OOM kill or a panic in
+    // the proofs) from taking down the SectorBuilder; crashed children are
+    // restarted. Applies to seal tasks scheduled after it's been replaced. By
+    // default, sectors are sealed in process. This is a runtime setting (see
+    // the note on runtime settings above SectorBuilder).
+    pub fn set_seal_isolation(&self, seal_isolation: SealIsolation) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetSealIsolation(seal_isolation, tx)))
+    }
+
+    // Replaces the replicator which copies the replica of each sector to a
+    // secondary location once it has been sealed and checksummed, e.g. as a
+    // backup. Replicas are copied in the background, a few at a time, and how
+    // far copying has come (down to the bytes copied, for replicators which
+    // report them) is recorded in each sector's metadata (see
+    // ReplicationStatus). By
+    // default, replicas aren't copied; None stops copying them. Copies which
+    // were interrupted are restarted once a replicator is set. This is a
+    // runtime setting (see the note on runtime settings above SectorBuilder).
+    pub fn set_replicator(
+        &self,
+        replicator: Option<Arc<dyn SealedSectorReplicator>>,
+    ) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetReplicator(replicator, tx)))
+    }
+
+    // Copies the replica of the sealed sector with the provided id with the
+    // replicator in the background, e.g. to retry a copy which failed. Fails
+    // if no replicator is set.
+    pub fn replicate_sealed_sector(&self, sector_id: SectorId) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::ReplicateSealedSector(sector_id, tx)))
+    }
+
+    // Replaces how pieces are written to staged sector-files: how many bytes
+    // are read from a piece and written to its staged sector-file at once,
+    // and whether they're written at explicit offsets or bypass the page
+    // cache. Larger buffers keep fast devices busy while pieces are added. By
+    // default, 1MiB is written at once through the page cache. This is a
+    // runtime setting (see the note on runtime settings above SectorBuilder).
+    pub fn set_staged_write_config(&self, staged_write_config: StagedWriteConfig) -> Result<()> {
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::SetStagedWriteConfig(staged_write_config, tx)),
+        )
+    }
+
+    // Replaces the clock against which the SectorBuilder checks the deadlines
+    // of its pieces, the age of its staged sectors (see AutoSealPolicy) and
+    // the deadlines of its proofs-of-spacetime, e.g. with a FakeClock in
+    // tests. By default, the system clock is used. This is a runtime setting
+    // (see the note on runtime settings above SectorBuilder).
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetClock(clock, tx)))
+    }
+
+    // Limits the rate, in bytes per second, at which pieces are read while
+    // they're added, so that staging pieces doesn't saturate disks which a
+    // running seal reads and writes. Up to a second's worth of bytes which
+    // weren't used may be read in a burst. Applies to pieces added after it's
+    // been replaced; None removes the limit. By default, pieces are read as
+    // fast as they can be written. This is a runtime setting (see the note on
+    // runtime settings above SectorBuilder).
+    pub fn set_ingest_rate_limit(&self, bytes_per_sec: Option<u64>) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetIngestRateLimit(bytes_per_sec, tx)))
+    }
+
+    // Batches metadata snapshots such that at most one is persisted per
+    // interval, instead of one after every change, which reduces the load on
+    // the metadata store when pieces are added at a high rate. Changes made
+    // within the interval are flushed once it has elapsed and when the
+    // SectorBuilder is dropped, but are lost if the process crashes before
+    // then. By default, no interval is set. This is a runtime setting (see
+    // the note on runtime settings above SectorBuilder).
+    pub fn set_snapshot_interval(&self, snapshot_interval: Option<Duration>) -> Result<()> {
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::SetSnapshotInterval(snapshot_interval, tx)),
+        )
+    }
+
+    // Sets the algorithm with which the checksums of newly sealed sectors are
+    // calculated. The algorithm is recorded with each sector, so sectors
+    // sealed with a different one still pass health checks. BLAKE3 hashes on
+    // multiple threads and checks replicas considerably faster; the default is
+    // BLAKE2b for compatibility with consumers which verify checksums
+    // themselves. This is a runtime setting (see the note on runtime settings
+    // above SectorBuilder).
+    pub fn set_checksum_algo(&self, checksum_algo: ChecksumAlgo) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetChecksumAlgo(checksum_algo, tx)))
+    }
+
+    // Returns a receiver of the events emitted by this SectorBuilder from
+    // here on.
+    pub fn subscribe_events(&self) -> mpsc::Receiver<SectorBuilderEvent> {
+        self.events.subscribe()
+    }
+
+    // Returns how the previous instance of this SectorBuilder stopped and
+    // which of its sectors were interrupted or repaired as a result.
+    pub fn startup_report(&self) -> StartupReport {
+        self.startup_report.clone()
     }
 
-    // For demo purposes. Schedules sealing of all staged sectors.
-    pub fn seal_all_staged_sectors(&self) -> Result<()> {
-        log_unrecov(self.run_blocking(SchedulerTask::SealAllStagedSectors))
+    // Returns the sector class, prover id, directories and limits this
+    // SectorBuilder is running with, with which a caller can verify that it's
+    // attached to the instance it expects. Limits which have been changed
+    // since initialization are reported as they are now.
+    pub fn get_config(&self) -> Result<SectorBuilderConfig> {
+        let (max_num_staged_sectors, quota) =
+            log_unrecov(self.run_blocking(SchedulerTask::GetLimits))?;
+
+        Ok(SectorBuilderConfig {
+            max_num_staged_sectors,
+            quota,
+            ..self.config.clone()
+        })
+    }
+
+    // Sets the maximum number of staged sectors which accept pieces, e.g. to
+    // temporarily accept more open sectors during a surge of deals. Lowering
+    // the number schedules the surplus sectors for sealing right away. The
+    // number is not persisted; the SectorBuilder starts out with the number
+    // it was initialized with.
+    pub fn set_max_num_staged_sectors(&self, max_num_staged_sectors: u64) -> Result<()> {
+        log_unrecov(
+            self.run_blocking(|tx| {
+                SchedulerTask::SetMaxNumStagedSectors(max_num_staged_sectors, tx)
+            }),
+        )
+    }
+
+    // Sets the policy according to which staged sectors are sealed before
+    // they're full: once their pieces fill a given fraction of the sector,
+    // once they have been staged for a given time, or once the store_until
+    // of one of their pieces is close. A DealDeadlineAtRisk event is emitted
+    // for each sector sealed that close to a deadline. Sectors which satisfy the
+    // policy are scheduled for sealing right away; those which expire later
+    // are scheduled as they expire. By default, only full sectors and those in
+    // excess of the maximum number of staged sectors are sealed. This is a
+    // runtime setting (see the note on runtime settings above SectorBuilder).
+    pub fn set_auto_seal_policy(&self, auto_seal_policy: AutoSealPolicy) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetAutoSealPolicy(auto_seal_policy, tx)))
+    }
+
+    // For demo purposes. Schedules sealing of all staged sectors with the
+    // provided ticket, which also becomes the ticket for subsequently
+    // scheduled sectors. Returns, ordered by sector id, whether each staged
+    // sector which hasn't been sealed was scheduled, skipped (as it wasn't
+    // pending) or couldn't be scheduled. Sectors which can't be scheduled
+    // don't keep the others from being scheduled.
+    pub fn seal_all_staged_sectors(
+        &self,
+        seal_ticket: SealTicket,
+    ) -> Result<Vec<SealScheduleResult>> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SealAllStagedSectors(seal_ticket, tx)))
+    }
+
+    // Returns the sectors which have been scheduled for sealing and are
+    // waiting for a worker, in the order in which they will be handed to the
+    // workers. Sectors which are being sealed aren't included.
+    pub fn get_seal_queue(&self) -> Result<Vec<SealQueueEntry>> {
+        log_unrecov(self.run_blocking(SchedulerTask::GetSealQueue))
+    }
+
+    // Changes the priority of a sector waiting for a worker, moving it ahead
+    // of the waiting sectors with a lower priority. Sectors are scheduled with
+    // priority 0. Produces an error if the sector isn't waiting for a worker.
+    // Priorities are not persisted; sectors which are still waiting when the
+    // SectorBuilder is (re)initialized are scheduled with priority 0 again.
+    pub fn set_seal_priority(&self, sector_id: SectorId, priority: i32) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetSealPriority(sector_id, priority, tx)))
+    }
+
+    // Limits the memory, in bytes, which the sectors being sealed are
+    // estimated to require (from the sector size), so that concurrent seals
+    // don't exhaust the host's memory. Sectors which would take the estimate
+    // above the watermark wait in the seal queue until others have been
+    // sealed. By default, there's no watermark. This is a runtime setting (see
+    // the note on runtime settings above SectorBuilder).
+    pub fn set_seal_memory_watermark(&self, memory_watermark: Option<u64>) -> Result<()> {
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::SetSealMemoryWatermark(memory_watermark, tx)),
+        )
+    }
+
+    // Moves a sector waiting for a worker to the head of the seal queue, e.g.
+    // because it holds a deal with a tight deadline, by giving it a higher
+    // priority than the sector at the head. Produces an error if the sector
+    // isn't waiting for a worker.
+    pub fn promote_seal(&self, sector_id: SectorId) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::PromoteSeal(sector_id, tx)))
+    }
+
+    // Cancels sealing the sector with the provided id. A sector waiting for a
+    // worker is removed from the seal queue right away; a sector which is
+    // being sealed is stopped between the phases of its seal. Either way, its
+    // seal fails as cancelled. Produces an error if the sector isn't being
+    // sealed.
+    pub fn cancel_seal(&self, sector_id: SectorId) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::CancelSeal(sector_id, tx)))
     }
 
-    // Returns all sealed sector metadata.
+    // Schedules the sealed sector with the provided id to be sealed again with
+    // a fresh ticket, e.g. because its previous ticket expired before the
+    // sector was pre-committed. The sector is sealed from its retained staged
+    // sector-file. Its previous replica and metadata remain in place until
+    // sealing succeeds, at which point both are swapped for the new ones; if
+    // sealing fails, the staged sector's status reflects the failure.
+    pub fn reseal_sector(&self, sector_id: SectorId, seal_ticket: SealTicket) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::ResealSector(sector_id, seal_ticket, tx)))
+    }
+
+    // Schedules the replica of the sealed sector with the provided id, which a
+    // health check reported as having an invalid checksum or length, to be
+    // restored from the sector's retained staged sector-file. The sector is
+    // sealed again with its original ticket and the corrupted replica is only
+    // replaced if the comm_r of the new one matches the sector's comm_r; if
+    // not, the staged sector's status reflects the failure. Sectors whose
+    // original ticket isn't recorded can't be repaired. A worker confirms the
+    // replica is corrupted before the repair is scheduled, so other calls
+    // aren't held up while the replica is read.
+    pub fn repair_sector(&self, sector_id: SectorId) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::RepairSector(sector_id, tx)))
+    }
+
+    // Returns all sealed sector metadata. A requested health check rehashes
+    // every sealed sector-file in full. Without one, the metadata is served
+    // from the published state, so it doesn't wait for the scheduler.
     pub fn get_sealed_sectors(&self, check_health: bool) -> Result<Vec<GetSealedSectorResult>> {
+        let mode = if check_health {
+            Some(HealthCheckMode::Full)
+        } else {
+            None
+        };
+
+        self.get_sealed_sectors_with_health_check(mode)
+    }
+
+    // Returns all sealed sector metadata along with each sector's health,
+    // checked in the provided mode. A quick check only hashes a few extents
+    // of each sealed sector-file, and is thus suited to routine checks,
+    // whereas a full check is suited to scheduled deep scrubs.
+    pub fn get_sealed_sectors_with_health_check(
+        &self,
+        mode: Option<HealthCheckMode>,
+    ) -> Result<Vec<GetSealedSectorResult>> {
+        if mode.is_none() {
+            return Ok(self
+                .published_state
+                .load()
+                .sealed
+                .sectors
+                .values()
+                .cloned()
+                .map(GetSealedSectorResult::WithoutHealth)
+                .collect());
+        }
+
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::GetSealedSectors(PerformHealthCheck(mode), tx)),
+        )
+    }
+
+    // Checks the health of a single sealed sector in the provided mode, which,
+    // unlike get_sealed_sectors with a health check, hashes no other sector's
+    // replica. Produces an error if no sealed sector with the provided id
+    // exists.
+    pub fn check_sector_health(
+        &self,
+        sector_id: SectorId,
+        mode: HealthCheckMode,
+    ) -> Result<SealedSectorHealth> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::CheckSectorHealth(sector_id, mode, tx)))
+    }
+
+    // Returns the metadata of the staged sectors which the provided filter
+    // matches, e.g. only of those whose seal failed, for callers which poll
+    // for failures. Served from the published state, so it doesn't wait for
+    // the scheduler.
+    pub fn get_staged_sectors(
+        &self,
+        filter: StagedSectorFilter,
+    ) -> Result<Vec<StagedSectorMetadata>> {
+        Ok(self
+            .published_state
+            .load()
+            .staged
+            .sectors
+            .values()
+            .filter(|sector| filter.matches(&sector.seal_status))
+            .cloned()
+            .collect())
+    }
+
+    // Returns the metadata of the staged sector with the provided id, e.g. to
+    // learn the current contents of an open sector without retrieving every
+    // staged sector. If no staged sector exists with the provided id, produce
+    // an error.
+    pub fn get_staged_sector(&self, sector_id: SectorId) -> Result<StagedSectorMetadata> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::GetStagedSector(sector_id, tx)))
+    }
+
+    // Returns the ids of the staged sectors which are ready to be sealed, i.e.
+    // which would be scheduled for sealing right now, without scheduling them.
+    // If seal_all_staged_sectors is set, returns the ids of the sectors which
+    // seal_all_staged_sectors would schedule instead.
+    pub fn get_sectors_ready_for_sealing(
+        &self,
+        seal_all_staged_sectors: bool,
+    ) -> Result<Vec<SectorId>> {
         log_unrecov(self.run_blocking(|tx| {
-            SchedulerTask::GetSealedSectors(PerformHealthCheck(check_health), tx)
+            SchedulerTask::GetSectorsReadyForSealing(seal_all_staged_sectors, tx)
         }))
     }
 
-    // Returns all staged sector metadata.
-    pub fn get_staged_sectors(&self) -> Result<Vec<StagedSectorMetadata>> {
-        log_unrecov(self.run_blocking(SchedulerTask::GetStagedSectors))
+    // Reports the number of bytes in staged and sealed sector-files, the
+    // number of sectors in each sealing state and the free space in each
+    // directory holding sector-files.
+    pub fn get_storage_report(&self) -> Result<StorageReport> {
+        log_unrecov(self.run_blocking(SchedulerTask::GetStorageReport))
+    }
+
+    // Reports the number of bytes on disk and the number of keys of the store
+    // holding the metadata, and when it last flushed its writes, with which
+    // metadata growth and compaction falling behind can be detected.
+    pub fn kv_store_stats(&self) -> Result<KvStoreStats> {
+        log_unrecov(self.run_blocking(SchedulerTask::GetKvStoreStats))
+    }
+
+    // Replaces the number of the most recent journal entries which are kept
+    // once the snapshot reflecting them has been flushed (by default,
+    // DEFAULT_JOURNAL_KEEP_LAST_N). Older entries are pruned, after which the
+    // pieces they recorded are no longer listed by get_pieces_by_origin. This
+    // is a runtime setting (see the note on runtime settings above
+    // SectorBuilder).
+    pub fn set_journal_keep_last_n(&self, keep_last_n: u64) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::SetJournalKeepLastN(keep_last_n, tx)))
+    }
+
+    // Flushes pending metadata changes and prunes the journal down to its
+    // most recent entries right away, e.g. after lowering the number of
+    // entries to keep, returning the metadata store's statistics afterwards.
+    pub fn compact_metadata(&self) -> Result<KvStoreStats> {
+        log_unrecov(self.run_blocking(SchedulerTask::CompactMetadata))
+    }
+
+    // Lists the pieces which were added with the provided origin (e.g. the
+    // client of a deal), and the sector to which and when each was added,
+    // with which disputes over deals can be resolved. Pieces are listed
+    // even if their sector has since been removed.
+    pub fn get_pieces_by_origin(&self, origin: String) -> Result<Vec<PieceProvenance>> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::GetPiecesByOrigin(origin, tx)))
     }
 
-    // Generates a proof-of-spacetime.
+    // Calculates statistics of how long sealing sectors took, over all sealed
+    // sectors and per machine on which they were sealed, with which sealing
+    // performance can be tracked over time. If a time is provided, only
+    // sectors which finished sealing at or after it are included. Sectors
+    // sealed before seal timings were recorded are left out.
+    pub fn get_seal_statistics(&self, since: Option<SecondsSinceEpoch>) -> Result<SealStatistics> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::GetSealStatistics(since, tx)))
+    }
+
+    // Returns the sealed sectors whose every piece was to be stored until a
+    // time before the provided time, so that the caller can decide whether to
+    // delete or keep them. Sectors holding a piece which was added without
+    // such a time are never returned. Nothing is deleted.
+    pub fn get_expired_sectors(&self, now: SecondsSinceEpoch) -> Result<Vec<SealedSectorMetadata>> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::GetExpiredSectors(now, tx)))
+    }
+
+    // Validates an externally sealed replica (length, checksum and, if
+    // requested, its seal proof) and copies it into the sealed sector
+    // directory. Once imported, the sector is treated like any other sealed
+    // sector.
+    //
+    // The seal proof is verified on the calling thread before the import is
+    // queued, and the replica is validated and copied by a worker, so that
+    // other calls aren't held up while either is done. The time of the
+    // verification is recorded in the sector's metadata (see
+    // SealedSectorMetadata::seal_verified_at).
+    //
+    // If linking is ok, the replica is hard-linked into the sealed sector
+    // directory rather than copied, so that importing a replica which is on
+    // the same filesystem doesn't duplicate it. The replica mustn't be
+    // modified afterwards, as the link shares its contents. Replicas which
+    // can't be linked are copied.
+    pub fn import_sealed_sector(
+        &self,
+        replica_path: impl AsRef<Path>,
+        meta: SealedSectorMetadata,
+        verify_seal: bool,
+        link_ok: bool,
+    ) -> Result<()> {
+        let replica_path = replica_path.as_ref().to_path_buf();
+
+        if verify_seal {
+            helpers::verify_imported_seal(
+                PoRepConfig::from(self.config.sector_class),
+                &self.config.prover_id,
+                &meta,
+            )?;
+        }
+
+        log_unrecov(self.run_blocking(|tx| {
+            SchedulerTask::ImportSealedSector(replica_path, meta, verify_seal, link_ok, tx)
+        }))
+    }
+
+    // Writes a bundle for the sealed sector with the provided id to the
+    // destination directory. The bundle contains a copy of the replica and a
+    // JSON manifest of the sector's metadata, and can be imported into another
+    // SectorBuilder with import_sector_bundle. The replica is copied by a
+    // worker, so other calls aren't held up while it is.
+    pub fn export_sealed_sector(
+        &self,
+        sector_id: SectorId,
+        dest_dir: impl AsRef<Path>,
+    ) -> Result<()> {
+        let dest_dir = dest_dir.as_ref().to_path_buf();
+
+        log_unrecov(
+            self.run_blocking(|tx| SchedulerTask::ExportSealedSector(sector_id, dest_dir, tx)),
+        )
+    }
+
+    // Imports a bundle produced by export_sealed_sector. The bundled replica
+    // is validated against its manifest, and copied or linked, as in
+    // import_sealed_sector.
+    pub fn import_sector_bundle(
+        &self,
+        bundle_dir: impl AsRef<Path>,
+        verify_seal: bool,
+        link_ok: bool,
+    ) -> Result<()> {
+        let (replica_path, meta) = helpers::load_sector_bundle(bundle_dir)?;
+
+        self.import_sealed_sector(replica_path, meta, verify_seal, link_ok)
+    }
+
+    // Registers a directory in which sealed sectors can be stored under the
+    // provided store id. To relocate a store, move its contents and register
+    // the new directory under the same id. Registrations are runtime settings
+    // (see the note on runtime settings above SectorBuilder).
+    pub fn register_sealed_store(&self, store_id: String, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref().to_path_buf();
+
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::RegisterSealedStore(store_id, dir, tx)))
+    }
+
+    // Moves the replica of the sealed sector with the provided id to the
+    // target location, e.g. to archive it on slower, cheaper storage. The
+    // sector's new location is persisted, so subsequent PoSt generation and
+    // health checks resolve the moved replica. The replica is moved by a
+    // worker, so other calls aren't held up while it's copied to another
+    // filesystem. Sectors which are being resealed can't be moved.
+    pub fn move_sealed_sector(
+        &self,
+        sector_id: SectorId,
+        target: SealedSectorLocation,
+    ) -> Result<()> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::MoveSealedSector(sector_id, target, tx)))
+    }
+
+    // Computes the checksum and length of the replica of the sealed sector
+    // with the provided id and persists them, if its metadata lacks them
+    // (e.g. because it was imported from metadata which predates checksums),
+    // so that health checks don't report the sector as corrupted. Metadata
+    // which has them already is left alone. Returns whether they were
+    // missing. The whole replica is read by a worker, so other calls aren't
+    // held up meanwhile; the replica can't be transferred until it's done.
+    pub fn hydrate_sector_integrity(&self, sector_id: SectorId) -> Result<bool> {
+        log_unrecov(self.run_blocking(|tx| SchedulerTask::HydrateSectorIntegrity(sector_id, tx)))
+    }
+
+    // Generates a proof-of-spacetime. If a deadline is provided, proving is
+    // abandoned with a PoStDeadlineMissed error once the deadline has passed,
+    // or before it starts if proving is estimated (from the previous proof)
+    // not to complete in time. The deadline may allow the slowest sectors to
+    // be proven as faulty instead, which the returned faults then include.
+    //
+    // Sectors whose replica is missing or truncated are proven as faulty too,
+    // so the returned faults are the complete set to declare on chain.
+    //
+    // The replicas of sectors with an entry in replica_paths are read from
+    // the provided path instead of their sealed sector-file, so that sectors
+    // on a temporarily mounted archive volume can be proven without moving
+    // them. The sectors' metadata isn't changed.
+    //
+    // If a PoSt configuration is provided, it's used instead of the one
+    // implied by the SectorBuilder's sector class, e.g. after the network's
+    // PoSt parameters have changed.
+    //
+    // Cancelling the provided token abandons proving with a TaskCancelled
+    // error, or keeps it from starting if it hasn't yet.
     pub fn generate_post(
         &self,
         comm_rs: &[[u8; 32]],
         challenge_seed: &[u8; 32],
         faults: Vec<SectorId>,
-    ) -> Result<Vec<u8>> {
+        options: PoStOptions,
+    ) -> Result<GeneratedPoSt> {
+        log_unrecov(
+            self.start_generate_post(comm_rs, challenge_seed, faults, options)
+                .and_then(|rx| rx.recv().expects(FATAL_NORECV_TASK)),
+        )
+    }
+
+    // Starts generating a proof-of-spacetime like generate_post without
+    // waiting for it. The result is sent to the returned receiver, which may
+    // be dropped to forget about the proof, e.g. after cancelling it.
+    pub fn start_generate_post(
+        &self,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        options: PoStOptions,
+    ) -> Result<mpsc::Receiver<Result<GeneratedPoSt>>> {
+        helpers::validate_replica_paths(&options.replica_paths)?;
+
+        let (tx, rx) = mpsc::sync_channel(1);
+
+        submit(
+            &self.scheduler_tx,
+            SchedulerTask::GeneratePoSt(Vec::from(comm_rs), *challenge_seed, faults, options, tx),
+            self.overload_policy,
+        )?;
+
+        Ok(rx)
+    }
+
+    // Derives the challenges of a proof-of-spacetime over the sealed sectors
+    // with the provided comm_rs, without generating the proof. Hosts which
+    // need to know the challenged sectors, e.g. to fetch remote replicas
+    // before proving, can use this like SimpleSectorBuilder's
+    // generate_post_first. The PoSt configuration, if provided, must be the
+    // one the proof is then generated with.
+    pub fn generate_post_first(
+        &self,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        post_config: Option<PoStConfig>,
+    ) -> Result<Vec<rational_post::Challenge>> {
+        log_unrecov(self.run_blocking(|tx| {
+            SchedulerTask::GeneratePoStFirst(
+                Vec::from(comm_rs),
+                *challenge_seed,
+                faults,
+                post_config,
+                tx,
+            )
+        }))
+    }
+
+    // Splits the sealed sectors with the provided comm_rs into partitions of
+    // (at most) partition_size sectors, in order of sector id, and generates a
+    // proof-of-spacetime over each partition. The partitions are proven in
+    // parallel by the workers, alongside any seal tasks, and each is sent to
+    // the returned receiver as soon as it has been proven, so they arrive in
+    // no particular order. The receiver hangs up once every partition has
+    // been sent.
+    pub fn generate_post_partitioned(
+        &self,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        partition_size: usize,
+    ) -> Result<mpsc::Receiver<Result<PoStPartition>>> {
         log_unrecov(self.run_blocking(|tx| {
-            SchedulerTask::GeneratePoSt(Vec::from(comm_rs), *challenge_seed, faults, tx)
+            SchedulerTask::GeneratePoStPartitioned(
+                Vec::from(comm_rs),
+                *challenge_seed,
+                faults,
+                partition_size,
+                tx,
+            )
         }))
     }
 
     // Run a task, blocking on the return channel.
-    fn run_blocking<T, F: FnOnce(mpsc::SyncSender<T>) -> SchedulerTask<R>>(
+    fn run_blocking<T, F: FnOnce(mpsc::SyncSender<Result<T>>) -> SchedulerTask<R>>(
+        &self,
+        with_sender: F,
+    ) -> Result<T> {
+        self.try_run_blocking(with_sender).and_then(|result| result)
+    }
+
+    // Run a task, blocking on the return channel. Fails if the task is
+    // rejected because the scheduler is overloaded.
+    fn try_run_blocking<T, F: FnOnce(mpsc::SyncSender<T>) -> SchedulerTask<R>>(
         &self,
         with_sender: F,
-    ) -> T {
+    ) -> Result<T> {
         let (tx, rx) = mpsc::sync_channel(0);
 
-        self.scheduler_tx
-            .clone()
-            .send(with_sender(tx))
-            .expects(FATAL_NOSEND_TASK);
+        submit(&self.scheduler_tx, with_sender(tx), self.overload_policy)?;
 
-        rx.recv().expects(FATAL_NORECV_TASK)
+        Ok(rx.recv().expects(FATAL_NORECV_TASK))
     }
 }
 
 impl<T> Drop for SectorBuilder<T> {
     fn drop(&mut self) {
-        // Shut down main worker and sealers, too.
-        let _ = self
-            .scheduler_tx
-            .send(SchedulerTask::Shutdown)
-            .map_err(|err| println!("err sending Shutdown to scheduler: {:?}", err));
-
-        for _ in &mut self.workers {
+        // Shut down main worker (unless it stopped as the SectorBuilder was
+        // frozen) and sealers, too.
+        if self.scheduler.thread.is_some() {
             let _ = self
-                .worker_tx
-                .send(WorkerTask::Shutdown)
-                .map_err(|err| println!("err sending Shutdown to sealer: {:?}", err));
+                .scheduler_tx
+                .send(SchedulerTask::Shutdown)
+                .map_err(|err| println!("err sending Shutdown to scheduler: {:?}", err));
         }
 
-        // Wait for worker threads to return.
+        // Wait for the scheduler thread to return. The workers are shut down
+        // once the last SectorBuilder using their pool has been dropped.
         let scheduler_thread = &mut self.scheduler.thread;
 
         if let Some(thread) = scheduler_thread.take() {
@@ -206,43 +1368,126 @@ impl<T> Drop for SectorBuilder<T> {
                 .join()
                 .map_err(|err| println!("err joining scheduler thread: {:?}", err));
         }
-
-        for worker in &mut self.workers {
-            if let Some(thread) = worker.thread.take() {
-                let _ = thread
-                    .join()
-                    .map_err(|err| println!("err joining sealer thread: {:?}", err));
-            }
-        }
     }
 }
 
 /// Checks the parameter cache for the given sector size.
-/// Returns an `Err` if it is not hydrated.
+/// Returns an `Err` listing every missing parameter file and the sector sizes
+/// which are supported if it is not hydrated.
 pub fn ensure_parameter_cache_hydrated(sector_class: SectorClass) -> Result<()> {
-    // PoRep
-    let porep_config: PoRepConfig = sector_class.into();
+    let missing = missing_parameters(sector_class);
 
-    let porep_cache_key = porep_config.get_cache_verifying_key_path();
-    ensure_file(porep_cache_key)
-        .map_err(|err| format_err!("missing verifying key for PoRep: {:?}", err))?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let SectorClass(SectorSize(sector_size), PoRepProofPartitions(partitions)) = sector_class;
+
+    let supported: Vec<String> = supported_sector_sizes(PoRepProofPartitions(partitions))
+        .into_iter()
+        .map(|SectorSize(size)| size.to_string())
+        .collect();
+
+    Err(err_invalid_sector_class(format!(
+        "parameter cache not hydrated for sector size {} ({} PoRep partitions) - {}; supported sector sizes: [{}]",
+        sector_size,
+        partitions,
+        missing.join(", "),
+        supported.join(", ")
+    ))
+    .into())
+}
 
-    let porep_cache_params = porep_config.get_cache_params_path();
-    ensure_file(porep_cache_params)
-        .map_err(|err| format_err!("missing Groth parameters for PoRep: {:?}", err))?;
+/// Enumerates the known sector sizes for which all PoRep and PoSt parameters
+/// exist in the parameter cache.
+pub fn supported_sector_sizes(porep_proof_partitions: PoRepProofPartitions) -> Vec<SectorSize> {
+    KNOWN_SECTOR_SIZES
+        .iter()
+        .map(|&size| SectorSize(size))
+        .filter(|&size| missing_parameters(SectorClass(size, porep_proof_partitions)).is_empty())
+        .collect()
+}
 
-    // PoSt
+// Produces a description of each parameter file required by the sector class
+// which is missing from the parameter cache.
+fn missing_parameters(sector_class: SectorClass) -> Vec<String> {
     let post_config: PoStConfig = sector_class.into();
 
-    let post_cache_key = post_config.get_cache_verifying_key_path();
-    ensure_file(post_cache_key)
-        .map_err(|err| format_err!("missing verifying key for PoSt: {:?}", err))?;
+    let mut missing = missing_porep_parameters(sector_class.into());
 
-    let post_cache_params = post_config.get_cache_params_path();
-    ensure_file(post_cache_params)
-        .map_err(|err| format_err!("missing Groth parameters for PoSt: {:?}", err))?;
+    missing.extend(missing_files(vec![
+        (
+            "verifying key for PoSt",
+            post_config.get_cache_verifying_key_path(),
+        ),
+        (
+            "Groth parameters for PoSt",
+            post_config.get_cache_params_path(),
+        ),
+    ]));
 
-    Ok(())
+    missing
+}
+
+// Produces a description of each parameter file required to seal sectors
+// with the provided PoRep config which is missing from the parameter cache.
+pub(crate) fn missing_porep_parameters(porep_config: PoRepConfig) -> Vec<String> {
+    missing_files(vec![
+        (
+            "verifying key for PoRep",
+            porep_config.get_cache_verifying_key_path(),
+        ),
+        (
+            "Groth parameters for PoRep",
+            porep_config.get_cache_params_path(),
+        ),
+    ])
+}
+
+fn missing_files<P: AsRef<Path>>(required: Vec<(&str, P)>) -> Vec<String> {
+    required
+        .into_iter()
+        .filter_map(|(name, path)| {
+            ensure_file(path)
+                .err()
+                .map(|err| format!("missing {}: {}", name, err))
+        })
+        .collect()
+}
+
+// Sends a task to the scheduler, according to the overload policy if the
+// scheduler can't take it right away.
+fn submit<T>(tx: &mpsc::SyncSender<T>, task: T, overload_policy: OverloadPolicy) -> Result<()> {
+    match overload_policy {
+        OverloadPolicy::Block => {
+            tx.send(task).expects(FATAL_NOSEND_TASK);
+            Ok(())
+        }
+        OverloadPolicy::Reject => match tx.try_send(task) {
+            Ok(()) => Ok(()),
+            Err(mpsc::TrySendError::Full(_)) => Err(err_scheduler_busy().into()),
+            Err(mpsc::TrySendError::Disconnected(_)) => panic!("{}", FATAL_NOSEND_TASK),
+        },
+    }
+}
+
+// Copies the bytes of the piece at the provided offset out of the unsealed
+// bytes of its sector, which may be fewer than its metadata promises if the
+// unseal came up short.
+fn unsealed_piece_bytes(
+    piece_key: &str,
+    unsealed: &[u8],
+    offset: u64,
+    len: u64,
+) -> Result<Vec<u8>> {
+    let num_bytes_unsealed = unsealed.len() as u64;
+    let end = offset.saturating_add(len);
+
+    if end > num_bytes_unsealed {
+        return Err(err_unsealed_truncated(piece_key, end, num_bytes_unsealed).into());
+    }
+
+    Ok(unsealed[offset as usize..end as usize].to_vec())
 }
 
 fn log_unrecov<T>(result: Result<T>) -> Result<T> {
@@ -269,8 +1514,6 @@ fn ensure_file(p: impl AsRef<Path>) -> Result<()> {
 
 #[cfg(test)]
 pub mod tests {
-    use filecoin_proofs::{PoRepProofPartitions, SectorSize};
-
     use super::*;
 
     #[test]
@@ -291,9 +1534,84 @@ pub mod tests {
             [0u8; 31],
             temp_dir.clone(),
             temp_dir,
-            1,
+            SectorBuilderOptions::new(1),
         );
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_missing_parameters_are_reported() {
+        let nonsense_sector_class = SectorClass(SectorSize(32), PoRepProofPartitions(123));
+
+        assert_eq!(4, missing_parameters(nonsense_sector_class).len());
+
+        match ensure_parameter_cache_hydrated(nonsense_sector_class)
+            .unwrap_err()
+            .downcast_ref()
+        {
+            Some(SectorBuilderErr::InvalidSectorClass(msg)) => {
+                assert!(msg.contains("Groth parameters for PoSt"));
+                assert!(msg.contains("supported sector sizes"));
+            }
+            _ => panic!("expected an invalid sector class error"),
+        }
+    }
+
+    #[test]
+    fn test_overloaded_scheduler_rejects_tasks() {
+        let (tx, rx) = mpsc::sync_channel(1);
+
+        // the first task fits into the channel, the second doesn't
+        assert!(submit(&tx, 1, OverloadPolicy::Reject).is_ok());
+
+        match submit(&tx, 2, OverloadPolicy::Reject)
+            .unwrap_err()
+            .downcast_ref()
+        {
+            Some(SectorBuilderErr::SchedulerBusy) => (),
+            _ => panic!("expected a scheduler busy error"),
+        }
+
+        assert_eq!(1, rx.recv().unwrap());
+
+        // once there's room again, the task is accepted
+        assert!(submit(&tx, 3, OverloadPolicy::Reject).is_ok());
+        assert_eq!(3, rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_pieces_beyond_short_unsealed_bytes_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("unsealed");
+
+        fs::write(&path, &[7; 100]).unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        let mapped = MappedBytes::new(unsafe { memmap::Mmap::map(&file).unwrap() });
+
+        assert_eq!(
+            vec![7; 60],
+            unsealed_piece_bytes("a", &mapped, 40, 60).unwrap()
+        );
+
+        match unsealed_piece_bytes("b", &mapped, 40, 61)
+            .unwrap_err()
+            .downcast_ref()
+        {
+            Some(SectorBuilderErr::UnsealedBytesTruncated {
+                piece_key,
+                end,
+                num_bytes_unsealed,
+            }) => {
+                assert_eq!("b", piece_key);
+                assert_eq!(101, *end);
+                assert_eq!(100, *num_bytes_unsealed);
+            }
+            _ => panic!("expected a truncated unsealed bytes error"),
+        }
+
+        // an offset past the end must not wrap around
+        assert!(unsealed_piece_bytes("c", &mapped, u64::max_value(), 1).is_err());
+    }
 }
@@ -0,0 +1,21 @@
+use std::fmt::Debug;
+
+use crate::metadata::SecondsSinceEpoch;
+
+/// Tells the time against which a SectorBuilder checks the deadlines of its
+/// pieces, the age of its staged sectors and the deadlines of its
+/// proofs-of-spacetime. Replacing it allows for these to be tested without
+/// waiting.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> SecondsSinceEpoch;
+}
+
+/// Tells the time by the system clock. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SecondsSinceEpoch {
+        SecondsSinceEpoch::now()
+    }
+}
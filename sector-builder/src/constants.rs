@@ -1,4 +1,28 @@
+use filecoin_proofs::constants::{SECTOR_SIZE_256_MIB, SECTOR_SIZE_ONE_KIB};
+
 pub const NUM_WORKERS: usize = 2;
 
+// Number of replicas of sealed sectors copied to the replicator's secondary
+// location at once (see ReplicationPool).
+pub const NUM_REPLICATION_THREADS: usize = 2;
+
+// Sector sizes probed for parameters when enumerating supported sector sizes.
+pub const KNOWN_SECTOR_SIZES: [u64; 2] = [SECTOR_SIZE_ONE_KIB, SECTOR_SIZE_256_MIB];
+
 pub const FATAL_NOSEND_TASK: &str = "[run_blocking] could not send";
 pub const FATAL_NORECV_TASK: &str = "[run_blocking] could not recv";
+
+// Limits on the labels with which a piece can be added, which are persisted
+// with the piece's metadata in every snapshot.
+pub const MAX_PIECE_LABELS: usize = 16;
+pub const MAX_PIECE_LABEL_KEY_LEN: usize = 64;
+pub const MAX_PIECE_LABEL_VALUE_LEN: usize = 256;
+
+// Limit on the origin with which a piece can be added, which is recorded in
+// the journal.
+pub const MAX_PIECE_ORIGIN_LEN: usize = 256;
+
+// Number of the most recent journal entries which are kept by default once
+// the snapshot reflecting them has been flushed, e.g. to look up the origin
+// of recently added pieces. Older entries are pruned.
+pub const DEFAULT_JOURNAL_KEEP_LAST_N: u64 = 10_000;
@@ -0,0 +1,108 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+
+use crate::error::{err_dir_in_use, Result};
+
+pub(crate) const LOCK_FILE_NAME: &str = ".sector-builder.lock";
+
+/// An advisory lock on a directory used by a SectorBuilder, held through a
+/// lock file in the directory until it's dropped. Two SectorBuilders which
+/// share a metadata, sealed sector or staged sector directory would overwrite
+/// each other's metadata and sector-files.
+///
+/// The lock is advisory: it only keeps out other SectorBuilders. The lock file
+/// holds the id of the process which last held the lock, and is left behind
+/// when the lock is released (by the OS, if the process dies).
+#[derive(Debug)]
+pub struct DirLock {
+    dir: PathBuf,
+    file: File,
+}
+
+impl DirLock {
+    pub fn acquire(dir: impl AsRef<Path>) -> Result<DirLock> {
+        let dir = dir.as_ref().to_path_buf();
+
+        fs::create_dir_all(&dir)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(dir.join(LOCK_FILE_NAME))?;
+
+        if file.try_lock_exclusive().is_err() {
+            return Err(err_dir_in_use(dir).into());
+        }
+
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+
+        Ok(DirLock { dir, file })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+// Locks each of the provided directories. A directory which is provided more
+// than once, e.g. when sealed and staged sectors share a directory, is only
+// locked once.
+pub fn lock_dirs<P: AsRef<Path>>(dirs: &[P]) -> Result<Vec<DirLock>> {
+    let mut locks: Vec<DirLock> = Vec::new();
+
+    for dir in dirs {
+        fs::create_dir_all(dir)?;
+
+        let dir = dir.as_ref().canonicalize()?;
+
+        if locks.iter().all(|lock| lock.dir() != dir.as_path()) {
+            locks.push(DirLock::acquire(dir)?);
+        }
+    }
+
+    Ok(locks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SectorBuilderErr;
+
+    use super::*;
+
+    #[test]
+    fn locks_directories_once() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let lock = DirLock::acquire(dir.path()).unwrap();
+
+        let err = DirLock::acquire(dir.path()).unwrap_err();
+        match err.downcast_ref() {
+            Some(SectorBuilderErr::DirectoryInUse(_)) => (),
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        drop(lock);
+
+        assert!(DirLock::acquire(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn shared_directories_are_locked_once() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let locks = lock_dirs(&[dir.path(), dir.path()]).unwrap();
+
+        assert_eq!(1, locks.len());
+        assert!(lock_dirs(&[dir.path()]).is_err());
+    }
+}
@@ -1,21 +1,27 @@
 use std::fs::{create_dir_all, remove_file, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 use filecoin_proofs::fr32::{
     almost_truncate_to_unpadded_bytes, target_unpadded_bytes, write_padded,
 };
 use filecoin_proofs::types::*;
+use memmap::MmapOptions;
 
+use crate::encryption::{open_encrypted, EncryptedFile, StagedDataKey};
 use crate::error::SectorManagerErr;
-use crate::store::{ProofsConfig, SectorConfig, SectorManager, SimpleSectorManager, SectorStore, SimpleSectorStore};
+use crate::helpers::digest_range;
+use crate::metadata::StagedSpillOver;
+use crate::sector_access::SectorAccess;
+use crate::staged_writer::{StagedWriteConfig, StagedWriter};
+use crate::store::{MappedBytes, ProofsConfig, SectorConfig, SectorManager, SimpleSectorManager, SectorStore, SimpleSectorStore};
 use storage_proofs::sector::SectorId;
 
 // This is a segmented sectorid expression protocol, to support meaningful sector name on disk
 // See: https://github.com/filecoin-project/rust-fil-proofs/issues/620 for the details
-// Currently, only the default one - on (original) and an IP example design are supported,
-// To create a mechanism to support future extension
-#[derive(Debug)]
+// Currently, the default one - on (original), the lotus-style one and an IP example design are
+// supported, To create a mechanism to support future extension
+#[derive(Clone, Debug, PartialEq)]
 #[allow(dead_code)] // IpV4(String) below is dead code, put it there for reference purpose only
 pub enum SectorAccessProto {
     // complicant with the original design, only the lower 32bit is used for sectorId index for a casual miner
@@ -26,10 +32,21 @@ pub enum SectorAccessProto {
     // The sector_access_name is like: ip-192168001010-dddddddddd
     // Here the parameter is IpV4 bytes, e.g. IpV4(192,168,0,10)
     IpV4(u8, u8, u8, u8),
+
+    // compliant with the names lotus gives to sector-files, which external tooling expects
+    // The sector_access_name is like: s-t01000-d (the whole sector id, in decimal)
+    // Here the parameter is the miner address, e.g. Lotus("t01000".to_string())
+    Lotus(String),
     // Leave for future protocol extension, e.g.
     // Uuid(String, u32),     // to indicate a media with UUID
 }
 
+impl Default for SectorAccessProto {
+    fn default() -> Self {
+        SectorAccessProto::Original(0)
+    }
+}
+
 pub struct DiskManager {
     staging_path: PathBuf,
     sealed_path: PathBuf,
@@ -37,20 +54,73 @@ pub struct DiskManager {
     // A sector ID presentation with a defined protocol
     sector_access_proto: SectorAccessProto,
     sector_segment_id: u32,
+
+    // If set, staged sector-files are encrypted with this key
+    staged_data_key: Option<StagedDataKey>,
+}
+
+// A staged sector-file, through which the plaintext of an encrypted one is
+// read and written.
+enum StagedFile {
+    Plain(File),
+    Encrypted(EncryptedFile),
+}
+
+impl StagedFile {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        match self {
+            StagedFile::Plain(file) => file.set_len(len),
+            StagedFile::Encrypted(file) => file.set_len(len),
+        }
+    }
+}
+
+impl Read for StagedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            StagedFile::Plain(file) => file.read(buf),
+            StagedFile::Encrypted(file) => file.read(buf),
+        }
+    }
+}
+
+impl Write for StagedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            StagedFile::Plain(file) => file.write(buf),
+            StagedFile::Encrypted(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            StagedFile::Plain(file) => file.flush(),
+            StagedFile::Encrypted(file) => file.flush(),
+        }
+    }
+}
+
+impl Seek for StagedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            StagedFile::Plain(file) => file.seek(pos),
+            StagedFile::Encrypted(file) => file.seek(pos),
+        }
+    }
 }
 
 pub struct SimpleDiskManager {
     d: DiskManager,
 }
 
-fn sector_path<P: AsRef<Path>>(sector_dir: P, access: &str) -> PathBuf {
+fn sector_path<P: AsRef<Path>>(sector_dir: P, access: &SectorAccess) -> PathBuf {
     let mut file_path = PathBuf::from(sector_dir.as_ref());
     file_path.push(access);
 
     file_path
 }
 
-fn simple_sector_path<P: AsRef<Path>>(sector_dir: P, miner: &str, access: &str) -> PathBuf {
+fn simple_sector_path<P: AsRef<Path>>(sector_dir: P, miner: &str, access: &SectorAccess) -> PathBuf {
     let mut file_path = PathBuf::from(sector_dir.as_ref());
     file_path.push(miner);
     file_path.push(access);
@@ -59,26 +129,40 @@ fn simple_sector_path<P: AsRef<Path>>(sector_dir: P, miner: &str, access: &str)
 }
 
 impl SectorManager for DiskManager {
-    fn sealed_sector_path(&self, access: &str) -> PathBuf {
+    fn sealed_sector_path(&self, access: &SectorAccess) -> PathBuf {
         sector_path(&self.sealed_path, access)
     }
 
-    fn staged_sector_path(&self, access: &str) -> PathBuf {
+    fn staged_sector_path(&self, access: &SectorAccess) -> PathBuf {
         sector_path(&self.staging_path, access)
     }
 
-    fn new_sealed_sector_access(&self, sector_id: SectorId) -> Result<String, SectorManagerErr> {
+    fn sealed_sector_dir(&self) -> PathBuf {
+        self.sealed_path.clone()
+    }
+
+    fn staged_sector_dir(&self) -> PathBuf {
+        self.staging_path.clone()
+    }
+
+    fn staged_data_key(&self) -> Option<StagedDataKey> {
+        self.staged_data_key.clone()
+    }
+
+    fn sealed_sector_access(&self, sector_id: SectorId) -> Result<SectorAccess, SectorManagerErr> {
+        self.new_sector_access_nocreate(sector_id)
+    }
+
+    fn new_sealed_sector_access(&self, sector_id: SectorId) -> Result<SectorAccess, SectorManagerErr> {
         self.new_sector_access(&Path::new(&self.sealed_path), sector_id)
     }
 
-    fn new_staging_sector_access(&self, sector_id: SectorId) -> Result<String, SectorManagerErr> {
+    fn new_staging_sector_access(&self, sector_id: SectorId) -> Result<SectorAccess, SectorManagerErr> {
         self.new_sector_access(&Path::new(&self.staging_path), sector_id)
     }
 
-    fn num_unsealed_bytes(&self, access: &str) -> Result<u64, SectorManagerErr> {
-        OpenOptions::new()
-            .read(true)
-            .open(self.staged_sector_path(access))
+    fn num_unsealed_bytes(&self, access: &SectorAccess) -> Result<u64, SectorManagerErr> {
+        self.open_staged(access)
             .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
             .map(|mut f| {
                 target_unpadded_bytes(&mut f)
@@ -87,11 +171,9 @@ impl SectorManager for DiskManager {
             .and_then(|n| n)
     }
 
-    fn truncate_unsealed(&self, access: &str, size: u64) -> Result<(), SectorManagerErr> {
+    fn truncate_unsealed(&self, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr> {
         // I couldn't wrap my head around all ths result mapping, so here it is all laid out.
-        match OpenOptions::new()
-            .write(true)
-            .open(self.staged_sector_path(access))
+        match self.open_staged(access)
             {
                 Ok(mut file) => match almost_truncate_to_unpadded_bytes(&mut file, size) {
                     Ok(padded_size) => match file.set_len(padded_size as u64) {
@@ -107,29 +189,51 @@ impl SectorManager for DiskManager {
     // TODO: write_and_preprocess should refuse to write more data than will fit. In that case, return 0.
     fn write_and_preprocess(
         &self,
-        access: &str,
+        access: &SectorAccess,
         data: &mut dyn Read,
     ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
-        OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(self.staged_sector_path(access))
-            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
-            .and_then(|mut file| {
-                write_padded(data, &mut file)
-                    .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))
-                    .map(|n| UnpaddedBytesAmount(n as u64))
-            })
+        self.write_and_preprocess_with(access, data, &Default::default())
+    }
+
+    fn write_and_preprocess_with(
+        &self,
+        access: &SectorAccess,
+        data: &mut dyn Read,
+        config: &StagedWriteConfig,
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        let mut data = BufReader::with_capacity(config.buffer_size.max(1), data);
+
+        match self.open_staged(access) {
+            Ok(StagedFile::Plain(file)) => {
+                StagedWriter::new(file, self.staged_sector_path(access), config)
+                    .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
+                    .and_then(|mut writer| write_preprocessed(&mut data, &mut writer))
+            }
+            // an encrypted file collects its writes in chunks of its own
+            Ok(StagedFile::Encrypted(mut file)) => write_preprocessed(&mut data, &mut file),
+            Err(err) => Err(SectorManagerErr::CallerError(format!("{:?}", err))),
+        }
     }
 
-    fn delete_staging_sector_access(&self, access: &str) -> Result<(), SectorManagerErr> {
+    fn delete_staging_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr> {
         remove_file(self.staged_sector_path(access))
             .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
     }
 
+    fn digest_staged_range(
+        &self,
+        access: &SectorAccess,
+        start_offset: u64,
+        num_bytes: u64,
+    ) -> Result<[u8; 32], SectorManagerErr> {
+        self.open_staged(access)
+            .and_then(|mut file| digest_range(&mut file, start_offset, num_bytes))
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
+    }
+
     fn read_raw(
         &self,
-        access: &str,
+        access: &SectorAccess,
         start_offset: u64,
         num_bytes: UnpaddedBytesAmount,
     ) -> Result<Vec<u8>, SectorManagerErr> {
@@ -149,22 +253,63 @@ impl SectorManager for DiskManager {
                 Ok(buf)
             })
     }
+
+    fn map_raw(
+        &self,
+        access: &SectorAccess,
+        start_offset: u64,
+        num_bytes: UnpaddedBytesAmount,
+    ) -> Result<MappedBytes, SectorManagerErr> {
+        let len = u64::from(num_bytes);
+
+        if len == 0 {
+            return Ok(MappedBytes::empty());
+        }
+
+        let file = File::open(self.staged_sector_path(access))
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+        let file_len = file
+            .metadata()
+            .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?
+            .len();
+
+        // accessing a mapping beyond the end of the file is fatal
+        if start_offset + len > file_len {
+            return Err(SectorManagerErr::CallerError(format!(
+                "range {}..{} exceeds file length {}",
+                start_offset,
+                start_offset + len,
+                file_len
+            )));
+        }
+
+        let mmap = unsafe {
+            MmapOptions::new()
+                .offset(start_offset)
+                .len(usize::from(num_bytes))
+                .map(&file)
+        }
+        .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))?;
+
+        Ok(MappedBytes::new(mmap))
+    }
 }
 
 impl SimpleSectorManager for SimpleDiskManager {
-    fn sealed_sector_path(&self, miner: &str, access: &str) -> PathBuf {
+    fn sealed_sector_path(&self, miner: &str, access: &SectorAccess) -> PathBuf {
         simple_sector_path(&self.d.sealed_path, miner, access)
     }
 
-    fn staged_sector_path(&self, miner: &str, access: &str) -> PathBuf {
+    fn staged_sector_path(&self, miner: &str, access: &SectorAccess) -> PathBuf {
         simple_sector_path(&self.d.staging_path, miner, access)
     }
 
-    fn new_sealed_sector_access(&self, miner: &str, sector_id: SectorId) -> Result<String, SectorManagerErr> {
+    fn new_sealed_sector_access(&self, miner: &str, sector_id: SectorId) -> Result<SectorAccess, SectorManagerErr> {
         self.d.new_sector_access(&Path::new(&self.d.sealed_path).join(miner), sector_id)
     }
 
-    fn new_staging_sector_access(&self, miner: &str, sector_id: SectorId, create: bool) -> Result<String, SectorManagerErr> {
+    fn new_staging_sector_access(&self, miner: &str, sector_id: SectorId, create: bool) -> Result<SectorAccess, SectorManagerErr> {
         if create {
             self.d.new_sector_access(&Path::new(&self.d.staging_path).join(miner), sector_id)
         } else {
@@ -172,7 +317,7 @@ impl SimpleSectorManager for SimpleDiskManager {
         }
     }
 
-    fn num_unsealed_bytes(&self, miner: &str, access: &str) -> Result<u64, SectorManagerErr> {
+    fn num_unsealed_bytes(&self, miner: &str, access: &SectorAccess) -> Result<u64, SectorManagerErr> {
         OpenOptions::new()
             .read(true)
             .open(self.staged_sector_path(miner, access))
@@ -184,7 +329,7 @@ impl SimpleSectorManager for SimpleDiskManager {
             .and_then(|n| n)
     }
 
-    fn truncate_unsealed(&self, miner: &str, access: &str, size: u64) -> Result<(), SectorManagerErr> {
+    fn truncate_unsealed(&self, miner: &str, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr> {
         // I couldn't wrap my head around all ths result mapping, so here it is all laid out.
         match OpenOptions::new()
             .write(true)
@@ -205,7 +350,7 @@ impl SimpleSectorManager for SimpleDiskManager {
     fn write_and_preprocess(
         &self,
         miner: &str,
-        access: &str,
+        access: &SectorAccess,
         data: &mut dyn Read,
     ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
         OpenOptions::new()
@@ -220,7 +365,7 @@ impl SimpleSectorManager for SimpleDiskManager {
             })
     }
 
-    fn delete_staging_sector_access(&self, miner: &str, access: &str) -> Result<(), SectorManagerErr> {
+    fn delete_staging_sector_access(&self, miner: &str, access: &SectorAccess) -> Result<(), SectorManagerErr> {
         remove_file(self.staged_sector_path(miner, access))
             .map_err(|err| SectorManagerErr::CallerError(format!("{:?}", err)))
     }
@@ -228,7 +373,7 @@ impl SimpleSectorManager for SimpleDiskManager {
     fn read_raw(
         &self,
         miner: &str,
-        access: &str,
+        access: &SectorAccess,
         start_offset: u64,
         num_bytes: UnpaddedBytesAmount,
     ) -> Result<Vec<u8>, SectorManagerErr> {
@@ -250,13 +395,43 @@ impl SimpleSectorManager for SimpleDiskManager {
     }
 }
 
+// Preprocesses the data into the target, flushing the bytes which the target
+// holds on to (in a buffer, or the last chunk of an encrypted file).
+fn write_preprocessed<W: Read + Write + Seek>(
+    data: &mut dyn Read,
+    target: &mut W,
+) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+    let n = write_padded(data, target)
+        .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+    target.flush()
+        .map_err(|err| SectorManagerErr::ReceiverError(format!("{:?}", err)))?;
+
+    Ok(UnpaddedBytesAmount(n as u64))
+}
+
 impl DiskManager {
+    // Opens the staged sector-file with the provided access, decrypting it
+    // if staged sector-files are encrypted.
+    fn open_staged(&self, access: &SectorAccess) -> io::Result<StagedFile> {
+        let path = self.staged_sector_path(access);
+
+        match &self.staged_data_key {
+            Some(key) => open_encrypted(path, key).map(StagedFile::Encrypted),
+            None => OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .map(StagedFile::Plain),
+        }
+    }
+
     fn new_sector_access(
         &self,
         root: &Path,
         sector_id: SectorId,
-    ) -> Result<String, SectorManagerErr> {
-        let access = self.convert_sector_id_to_access_name(sector_id)?;
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        let access = SectorAccess::new(self.convert_sector_id_to_access_name(sector_id)?)?;
         let file_path = root.join(&access);
 
         create_dir_all(root)
@@ -272,8 +447,8 @@ impl DiskManager {
     fn new_sector_access_nocreate(
         &self,
         sector_id: SectorId,
-    ) -> Result<String, SectorManagerErr> {
-        self.convert_sector_id_to_access_name(sector_id)
+    ) -> Result<SectorAccess, SectorManagerErr> {
+        SectorAccess::new(self.convert_sector_id_to_access_name(sector_id)?)
     }
 
     fn convert_sector_id_to_access_name(
@@ -284,7 +459,10 @@ impl DiskManager {
         let seg_id = (sector_id >> 32) as u32;
         let index = (sector_id & 0x0000_0000_ffff_ffff) as u32;
 
-        if seg_id != self.sector_segment_id {
+        if let SectorAccessProto::Lotus(miner) = &self.sector_access_proto {
+            // the name holds the whole sector id, whatever its segment
+            Ok(format!("s-{}-{}", miner, sector_id))
+        } else if seg_id != self.sector_segment_id {
             // Strictly check if the sector_segment is the same as the initated one.
             Err(SectorManagerErr::CallerError(format!(
                 "seg_id({}) does not match the setting({})",
@@ -297,6 +475,7 @@ impl DiskManager {
                     "ip-{:03}{:03}{:03}{:03}-{:010}",
                     ip1, ip2, ip3, ip4, index
                 )),
+                SectorAccessProto::Lotus(_) => unreachable!(),
             }
         }
     }
@@ -330,7 +509,26 @@ impl SectorAccessProto {
         &self,
         access_name: &'a str,
     ) -> Result<SectorAccessSplit<'a>, SectorManagerErr> {
-        if access_name.len() != 26
+        if access_name.starts_with("s-") {
+            // lotus-style names are of variable length: s-<miner>-<sector id>
+            let mut parts = access_name.splitn(3, '-');
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(proto), Some(seg_str), Some(ind_str))
+                    if !seg_str.is_empty() && !ind_str.is_empty() =>
+                {
+                    Ok(SectorAccessSplit {
+                        proto,
+                        seg_str,
+                        ind_str,
+                    })
+                }
+                _ => Err(SectorManagerErr::CallerError(format!(
+                    "The sector file name '{}' is not supported in this version",
+                    access_name
+                ))),
+            }
+        } else if access_name.len() != 26
             || access_name.chars().nth(2).unwrap() != '-'
             || access_name.chars().nth(15).unwrap() != '-'
         {
@@ -390,6 +588,21 @@ impl SectorAccessProto {
                     Ok(index)
                 }
             }
+            SectorAccessProto::Lotus(miner) => {
+                if sector_access_split.proto != "s" {
+                    Err(SectorManagerErr::CallerError(format!(
+                        "The worker is set to Lotus format sector access only, the file '{}' is not.",
+                        access_name
+                    )))
+                } else if sector_access_split.seg_str != miner {
+                    Err(SectorManagerErr::CallerError(format!(
+                        "The miner should be {}, the file '{}' is not.",
+                        miner, access_name
+                    )))
+                } else {
+                    Ok(index)
+                }
+            }
         }
     }
 
@@ -401,6 +614,20 @@ impl SectorAccessProto {
     ) -> Result<SectorId, SectorManagerErr> {
         let sector_access_split = self.validate_format(access_name)?;
 
+        if sector_access_split.proto == "s" {
+            // A lotus-style name holds the whole sector id
+            return sector_access_split
+                .ind_str
+                .parse::<u64>()
+                .map(SectorId::from)
+                .map_err(|_| {
+                    SectorManagerErr::CallerError(format!(
+                        "sector id {} is invalid",
+                        sector_access_split.ind_str
+                    ))
+                });
+        }
+
         let index = sector_access_split.ind_str.parse::<u32>().map_err(|_| {
             SectorManagerErr::CallerError(format!(
                 "sector index {} is invalid",
@@ -450,6 +677,11 @@ pub struct ConcreteSectorStore {
     proofs_config: Box<dyn ProofsConfig>,
     sector_config: Box<dyn SectorConfig>,
     manager: Box<dyn SectorManager>,
+
+    // If set, manages the secondary staged sector directory, into which new
+    // staged sectors spill over once fewer than the provided number of bytes
+    // are available in the primary one
+    secondary_staged: Option<(Box<dyn SectorManager>, u64)>,
 }
 
 pub struct SimpleConcreteSectorStore {
@@ -470,6 +702,19 @@ impl SectorStore for ConcreteSectorStore {
     fn manager(&self) -> &dyn SectorManager {
         self.manager.as_ref()
     }
+
+    fn secondary_staged_manager(&self) -> Option<&dyn SectorManager> {
+        self.secondary_staged
+            .as_ref()
+            .map(|(manager, _)| manager.as_ref())
+    }
+
+    fn staged_spill_over_min_free_bytes(&self) -> u64 {
+        self.secondary_staged
+            .as_ref()
+            .map(|(_, min_free_bytes)| *min_free_bytes)
+            .unwrap_or(0)
+    }
 }
 
 impl SimpleSectorStore for SimpleConcreteSectorStore {
@@ -490,16 +735,74 @@ pub fn new_sector_store(
     sector_class: SectorClass,
     sealed_sector_dir: impl AsRef<Path>,
     staged_sector_dir: impl AsRef<Path>,
+) -> ConcreteSectorStore {
+    new_sector_store_with_staged_data_key(sector_class, sealed_sector_dir, staged_sector_dir, None)
+}
+
+// Like new_sector_store, but staged sector-files are encrypted with the
+// provided key, if any.
+pub fn new_sector_store_with_staged_data_key(
+    sector_class: SectorClass,
+    sealed_sector_dir: impl AsRef<Path>,
+    staged_sector_dir: impl AsRef<Path>,
+    staged_data_key: Option<StagedDataKey>,
 ) -> ConcreteSectorStore {
     // By default, support on-000000000000-dddddddddd format
-    let default_access_proto = SectorAccessProto::Original(0);
+    new_sector_store_with_access_proto(
+        sector_class,
+        sealed_sector_dir,
+        staged_sector_dir,
+        staged_data_key,
+        SectorAccessProto::default(),
+    )
+}
 
-    let manager = Box::new(DiskManager {
-        staging_path: staged_sector_dir.as_ref().to_owned(),
-        sealed_path: sealed_sector_dir.as_ref().to_owned(),
-        sector_access_proto: default_access_proto,
-        sector_segment_id: 0u32,
-    });
+// Like new_sector_store_with_staged_data_key, but new sector-files are named
+// according to the provided protocol, e.g. s-<miner>-<sector id> for
+// SectorAccessProto::Lotus. Sector-files which exist already keep the names
+// recorded in their metadata.
+pub fn new_sector_store_with_access_proto(
+    sector_class: SectorClass,
+    sealed_sector_dir: impl AsRef<Path>,
+    staged_sector_dir: impl AsRef<Path>,
+    staged_data_key: Option<StagedDataKey>,
+    sector_access_proto: SectorAccessProto,
+) -> ConcreteSectorStore {
+    new_sector_store_with_spill_over(
+        sector_class,
+        sealed_sector_dir,
+        staged_sector_dir,
+        staged_data_key,
+        sector_access_proto,
+        None,
+    )
+}
+
+// Like new_sector_store_with_access_proto, but new staged sectors spill over
+// into the secondary staged sector directory, if one is provided, while the
+// primary one is low on space. Spilled over sector-files are named, and
+// encrypted, like the ones in the primary directory.
+pub fn new_sector_store_with_spill_over(
+    sector_class: SectorClass,
+    sealed_sector_dir: impl AsRef<Path>,
+    staged_sector_dir: impl AsRef<Path>,
+    staged_data_key: Option<StagedDataKey>,
+    sector_access_proto: SectorAccessProto,
+    spill_over: Option<StagedSpillOver>,
+) -> ConcreteSectorStore {
+    let new_manager = |staging_path: &Path| -> Box<dyn SectorManager> {
+        Box::new(DiskManager {
+            staging_path: staging_path.to_owned(),
+            sealed_path: sealed_sector_dir.as_ref().to_owned(),
+            sector_access_proto: sector_access_proto.clone(),
+            sector_segment_id: 0u32,
+            staged_data_key: staged_data_key.clone(),
+        })
+    };
+
+    let manager = new_manager(staged_sector_dir.as_ref());
+    let secondary_staged =
+        spill_over.map(|spill_over| (new_manager(&spill_over.dir), spill_over.min_free_bytes));
 
     let sector_config = Box::new(Config::from(sector_class));
     let proofs_config = Box::new(Config::from(sector_class));
@@ -508,6 +811,7 @@ pub fn new_sector_store(
         proofs_config,
         sector_config,
         manager,
+        secondary_staged,
     }
 }
 
@@ -525,6 +829,7 @@ pub fn new_simple_sector_store(
             sealed_path: sealed_sector_dir.as_ref().to_owned(),
             sector_access_proto: default_access_proto,
             sector_segment_id: 0u32,
+            staged_data_key: None,
         },
     });
 
@@ -718,6 +1023,50 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn encrypted_sector_write_and_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = StagedDataKey::new([3; 32]);
+
+        let storage = new_sector_store_with_staged_data_key(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            dir.path().join("sealed"),
+            dir.path().join("staged"),
+            Some(key.clone()),
+        );
+        let mgr = storage.manager();
+
+        create_dir_all(mgr.staged_sector_dir()).unwrap();
+
+        let access = mgr
+            .new_staging_sector_access(SectorId::from(1))
+            .expect("failed to create staging file");
+
+        let contents = &[2u8; 500];
+
+        let n = mgr
+            .write_and_preprocess(&access, &mut &contents[..])
+            .expect("failed to write");
+        assert_eq!(contents.len(), usize::from(n));
+
+        // the plaintext never reaches the disk
+        let buf = read_all_bytes(mgr.staged_sector_path(&access));
+        assert!(buf.windows(32).all(|w| w != &contents[0..32]));
+
+        assert_eq!(500, mgr.num_unsealed_bytes(&access).unwrap());
+
+        mgr.truncate_unsealed(&access, 32).unwrap();
+        assert_eq!(32, mgr.num_unsealed_bytes(&access).unwrap());
+
+        let decrypted =
+            crate::encryption::decrypt_staged_sector(mgr.staged_sector_path(&access), &key)
+                .unwrap();
+        let buf = read_all_bytes(decrypted.path());
+
+        assert_eq!(33, buf.len());
+        assert_eq!(contents[0..32], buf[0..32]);
+    }
+
     #[test]
     fn deletes_staging_access() {
         let store = create_sector_store(SectorClass(
@@ -745,6 +1094,35 @@ pub mod tests {
             .is_err());
     }
 
+    #[test]
+    fn maps_raw_range() {
+        let store = create_sector_store(SectorClass(
+            SectorSize(SECTOR_SIZE_ONE_KIB),
+            PoRepProofPartitions(2),
+        ));
+        let mgr = store.manager();
+
+        let access = mgr
+            .new_staging_sector_access(SectorId::from(7))
+            .unwrap();
+
+        let contents: Vec<u8> = (0..255).collect();
+        std::fs::write(mgr.staged_sector_path(&access), &contents).unwrap();
+
+        let mapped = mgr
+            .map_raw(&access, 10, UnpaddedBytesAmount(100))
+            .expect("failed to map range");
+
+        assert_eq!(&contents[10..110], &mapped[..]);
+
+        // the mapping outlives the file
+        remove_file(mgr.staged_sector_path(&access)).unwrap();
+        assert_eq!(&contents[10..110], &mapped[..]);
+
+        assert_eq!(0, mgr.map_raw(&access, 0, UnpaddedBytesAmount(0)).unwrap().len());
+        assert!(mgr.map_raw(&access, 10, UnpaddedBytesAmount(100)).is_err());
+    }
+
     #[test]
     fn get_sector_id_from_access_original() {
         // Test original design of sector_access.
@@ -783,6 +1161,41 @@ pub mod tests {
         assert_eq!(sector_id, SectorId::from(0xc0a8010b0000000a_u64));
     }
 
+    #[test]
+    fn lotus_access_names() {
+        let staging_path = tempfile::tempdir().unwrap();
+        let sealed_path = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store_with_access_proto(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_path.path(),
+            staging_path.path(),
+            None,
+            SectorAccessProto::Lotus("t01000".to_string()),
+        );
+
+        let access = store
+            .manager()
+            .new_staging_sector_access(SectorId::from(0x1_0000_0007_u64))
+            .unwrap();
+
+        assert_eq!("s-t01000-4294967303", access.as_str());
+        assert!(staging_path.path().join(&access).exists());
+
+        let sector_access_proto = SectorAccessProto::Lotus("t01000".to_string());
+        let sector_id = sector_access_proto
+            .get_sector_id_from_access_name(&access)
+            .unwrap();
+        assert_eq!(sector_id, SectorId::from(0x1_0000_0007_u64));
+
+        assert!(sector_access_proto
+            .validate_and_return_index("s-t01001-7")
+            .is_err());
+        assert!(sector_access_proto
+            .get_sector_id_from_access_name("s-t01000-")
+            .is_err());
+    }
+
     #[test]
     fn validate_access_proto() {
         let sector_access_proto = &SectorAccessProto::Original(0_u32);
@@ -0,0 +1,687 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::Rng;
+
+use crate::error::Result;
+
+// Number of plaintext bytes encrypted together. Each chunk is stored as its
+// nonce, followed by its ciphertext and authentication tag, so that a chunk
+// can be read and rewritten without touching any other chunk.
+const CHUNK_LEN: u64 = 64 * 1024;
+const NONCE_LEN: u64 = 24;
+const TAG_LEN: u64 = 16;
+const STORED_CHUNK_LEN: u64 = NONCE_LEN + CHUNK_LEN + TAG_LEN;
+
+// The chunks are preceded by the id of the file, which is drawn at random
+// when the first chunk is written.
+const FILE_ID_LEN: usize = 16;
+const HEADER_LEN: u64 = FILE_ID_LEN as u64;
+
+// Extension of the plaintext of a staged sector-file, on platforms where it
+// can't be kept in memory (see DecryptedStagedSector).
+const DECRYPTED_EXTENSION: &str = "decrypted";
+
+/// The key with which staged sector-files are encrypted at rest. Staged
+/// sector-files hold the unsealed data of clients until it's sealed, and
+/// are encrypted with XChaCha20-Poly1305, in chunks of 64KiB, each of which
+/// is authenticated along with the file's id, its position in the file and
+/// whether it's the file's last chunk. Chunks moved between files or within
+/// a file, or chunks cut off the end of a file, fail to decrypt.
+///
+/// A SectorBuilder must be given the same key for as long as staged
+/// sector-files encrypted with it exist, including staged data retained after
+/// sealing. Staged sector-files written without a key can't be read with one,
+/// and vice versa.
+#[derive(Clone)]
+pub struct StagedDataKey([u8; 32]);
+
+impl StagedDataKey {
+    pub fn new(key: [u8; 32]) -> StagedDataKey {
+        StagedDataKey(key)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+// Keeps keys out of logs.
+impl fmt::Debug for StagedDataKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StagedDataKey(..)")
+    }
+}
+
+struct Chunk {
+    index: u64,
+    plaintext: Vec<u8>,
+    dirty: bool,
+}
+
+/// A file encrypted with a StagedDataKey, which reads, writes and seeks like
+/// the plaintext it holds. The chunk at the current position is kept in
+/// memory until a different chunk is accessed or the file is flushed (or
+/// dropped), so small sequential writes only encrypt each chunk once.
+pub struct EncryptedFile {
+    file: File,
+    cipher: XChaCha20Poly1305,
+    // None until the first chunk is written to an empty file
+    file_id: Option<[u8; FILE_ID_LEN]>,
+    pos: u64,
+    len: u64,
+    chunk: Option<Chunk>,
+}
+
+impl EncryptedFile {
+    pub fn new(mut file: File, key: &StagedDataKey) -> io::Result<EncryptedFile> {
+        let stored_len = file.metadata()?.len();
+
+        let file_id = if stored_len == 0 {
+            None
+        } else if stored_len < HEADER_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "encrypted file ends in its header",
+            ));
+        } else {
+            let mut file_id = [0u8; FILE_ID_LEN];
+
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut file_id)?;
+
+            Some(file_id)
+        };
+
+        let stored_len = stored_len.saturating_sub(HEADER_LEN);
+
+        let num_chunks = stored_len / STORED_CHUNK_LEN;
+        let rest = stored_len % STORED_CHUNK_LEN;
+
+        if rest > 0 && rest <= NONCE_LEN + TAG_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "encrypted file ends in a partial chunk",
+            ));
+        }
+
+        let len = num_chunks * CHUNK_LEN + rest.saturating_sub(NONCE_LEN + TAG_LEN);
+
+        Ok(EncryptedFile {
+            file,
+            cipher: key.cipher(),
+            file_id,
+            pos: 0,
+            len,
+            chunk: None,
+        })
+    }
+
+    /// Returns the number of plaintext bytes in the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Truncates or extends (with zeroes) the plaintext to the provided
+    /// length.
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.flush_chunk()?;
+        self.chunk = None;
+
+        if len >= self.len {
+            let pos = self.pos;
+
+            self.seek(SeekFrom::Start(self.len))?;
+            io::copy(&mut io::repeat(0).take(len - self.len), self)?;
+            self.flush_chunk()?;
+
+            self.pos = pos;
+
+            return Ok(());
+        }
+
+        // the chunk which becomes the last one, which is stored again as such
+        let last = if len % CHUNK_LEN > 0 {
+            Some(len / CHUNK_LEN)
+        } else {
+            (len / CHUNK_LEN).checked_sub(1)
+        };
+
+        match last {
+            Some(last) => {
+                let mut plaintext = self.read_chunk(last)?;
+                plaintext.truncate((len - last * CHUNK_LEN) as usize);
+
+                self.file.set_len(HEADER_LEN + last * STORED_CHUNK_LEN)?;
+                self.store_chunk(last, &plaintext, true)?;
+            }
+            None => {
+                self.file.set_len(0)?;
+                self.file_id = None;
+            }
+        }
+
+        self.len = len;
+
+        Ok(())
+    }
+
+    // Returns the number of chunks stored in the file.
+    fn stored_chunks(&self) -> io::Result<u64> {
+        let stored_len = self.file.metadata()?.len().saturating_sub(HEADER_LEN);
+
+        Ok((stored_len + STORED_CHUNK_LEN - 1) / STORED_CHUNK_LEN)
+    }
+
+    // The data authenticated along with the chunk with the provided index.
+    fn aad(&self, index: u64, last: bool) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(FILE_ID_LEN + 9);
+
+        aad.extend_from_slice(&self.file_id.unwrap_or_default());
+        aad.extend_from_slice(&index.to_le_bytes());
+        aad.push(last as u8);
+
+        aad
+    }
+
+    fn read_chunk(&mut self, index: u64) -> io::Result<Vec<u8>> {
+        let stored_chunks = self.stored_chunks()?;
+
+        if index >= stored_chunks {
+            return Ok(Vec::new());
+        }
+
+        let stored_len = self.file.metadata()?.len();
+        let start = HEADER_LEN + index * STORED_CHUNK_LEN;
+
+        let mut stored = vec![0; std::cmp::min(STORED_CHUNK_LEN, stored_len - start) as usize];
+
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut stored)?;
+
+        let (nonce, ciphertext) = stored.split_at(NONCE_LEN as usize);
+
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &self.aad(index, index + 1 == stored_chunks),
+                },
+            )
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("chunk {} of encrypted file could not be decrypted", index),
+                )
+            })
+    }
+
+    // Stores the chunk with the provided index. A chunk stored after the last
+    // one becomes the last, so the chunk which was last is stored again as
+    // one which isn't.
+    fn write_chunk(&mut self, index: u64, plaintext: &[u8]) -> io::Result<()> {
+        let stored_chunks = self.stored_chunks()?;
+
+        if index > 0 && index == stored_chunks {
+            let previous = self.read_chunk(index - 1)?;
+            self.store_chunk(index - 1, &previous, false)?;
+        }
+
+        self.store_chunk(index, plaintext, index + 1 >= stored_chunks)
+    }
+
+    fn store_chunk(&mut self, index: u64, plaintext: &[u8], last: bool) -> io::Result<()> {
+        if self.file_id.is_none() {
+            let mut file_id = [0u8; FILE_ID_LEN];
+            rand::thread_rng().fill_bytes(&mut file_id);
+
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(&file_id)?;
+
+            self.file_id = Some(file_id);
+        }
+
+        let mut nonce = [0u8; NONCE_LEN as usize];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &self.aad(index, last),
+                },
+            )
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "could not encrypt chunk"))?;
+
+        self.file
+            .seek(SeekFrom::Start(HEADER_LEN + index * STORED_CHUNK_LEN))?;
+        self.file.write_all(&nonce)?;
+        self.file.write_all(&ciphertext)?;
+
+        Ok(())
+    }
+
+    // Makes the chunk with the provided index the one kept in memory.
+    fn load_chunk(&mut self, index: u64) -> io::Result<()> {
+        if self.chunk.as_ref().map(|chunk| chunk.index) == Some(index) {
+            return Ok(());
+        }
+
+        self.flush_chunk()?;
+
+        let plaintext = self.read_chunk(index)?;
+
+        self.chunk = Some(Chunk {
+            index,
+            plaintext,
+            dirty: false,
+        });
+
+        Ok(())
+    }
+
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        if let Some(mut chunk) = self.chunk.take() {
+            if chunk.dirty {
+                self.write_chunk(chunk.index, &chunk.plaintext)?;
+                chunk.dirty = false;
+            }
+
+            self.chunk = Some(chunk);
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for EncryptedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.load_chunk(self.pos / CHUNK_LEN)?;
+
+        let chunk = self.chunk.as_ref().expect("chunk has been loaded");
+        let offset = (self.pos % CHUNK_LEN) as usize;
+
+        let n = std::cmp::min(buf.len(), chunk.plaintext.len() - offset);
+        buf[..n].copy_from_slice(&chunk.plaintext[offset..offset + n]);
+
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl Write for EncryptedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // a write past the end fills the gap with zeroes first
+        if self.pos > self.len {
+            let pos = self.pos;
+
+            self.pos = self.len;
+            io::copy(&mut io::repeat(0).take(pos - self.len), self)?;
+        }
+
+        self.load_chunk(self.pos / CHUNK_LEN)?;
+
+        let chunk = self.chunk.as_mut().expect("chunk has been loaded");
+        let offset = (self.pos % CHUNK_LEN) as usize;
+
+        let n = std::cmp::min(buf.len(), CHUNK_LEN as usize - offset);
+
+        if chunk.plaintext.len() < offset + n {
+            chunk.plaintext.resize(offset + n, 0);
+        }
+
+        chunk.plaintext[offset..offset + n].copy_from_slice(&buf[..n]);
+        chunk.dirty = true;
+
+        self.pos += n as u64;
+        self.len = std::cmp::max(self.len, self.pos);
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_chunk()?;
+        self.file.flush()
+    }
+}
+
+impl Seek for EncryptedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => offset_by(self.len, n),
+            SeekFrom::Current(n) => offset_by(self.pos, n),
+        };
+
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        Ok(self.pos)
+    }
+}
+
+impl Drop for EncryptedFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush_chunk() {
+            error!("could not write encrypted chunk: {}", err);
+        }
+    }
+}
+
+fn offset_by(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.wrapping_neg() as u64)
+    }
+}
+
+/// Opens an encrypted staged sector-file for reading and writing.
+pub fn open_encrypted(path: impl AsRef<Path>, key: &StagedDataKey) -> io::Result<EncryptedFile> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    EncryptedFile::new(file, key)
+}
+
+/// The plaintext of an encrypted staged sector-file, for as long as a seal
+/// reads it through its path. On Linux, the plaintext is held in an anonymous
+/// in-memory file (see memfd_create(2)), which never reaches the disk (save
+/// for swap) and vanishes along with the process, and whose path is the
+/// file's descriptor in /proc. Elsewhere, it's written next to the staged
+/// sector-file, readable only by its owner, and removed at startup if a
+/// crash left it behind (see remove_decrypted_staged_sectors). Either way,
+/// the plaintext is gone once this is dropped.
+pub struct DecryptedStagedSector {
+    path: PathBuf,
+    file: File,
+    on_disk: bool,
+}
+
+impl DecryptedStagedSector {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[cfg(target_os = "linux")]
+    fn create(staged_sector_path: &Path) -> io::Result<DecryptedStagedSector> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::io::FromRawFd;
+
+        const MFD_CLOEXEC: libc::c_uint = 1;
+
+        // the name only shows up in /proc, e.g. while debugging
+        let name = staged_sector_path.file_name().unwrap_or_default();
+        let name = CString::new(name.as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let fd = unsafe { libc::syscall(libc::SYS_memfd_create, name.as_ptr(), MFD_CLOEXEC) };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(DecryptedStagedSector {
+            path: PathBuf::from(format!("/proc/{}/fd/{}", std::process::id(), fd)),
+            file: unsafe { File::from_raw_fd(fd as libc::c_int) },
+            on_disk: false,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create(staged_sector_path: &Path) -> io::Result<DecryptedStagedSector> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = staged_sector_path.with_extension(DECRYPTED_EXTENSION);
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)?;
+
+        Ok(DecryptedStagedSector {
+            path,
+            file,
+            on_disk: true,
+        })
+    }
+}
+
+impl Drop for DecryptedStagedSector {
+    fn drop(&mut self) {
+        if self.on_disk {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Decrypts the staged sector-file at the provided path for sealing, which
+/// reads the staged sector-file through its path (see
+/// DecryptedStagedSector).
+pub fn decrypt_staged_sector(
+    path: impl AsRef<Path>,
+    key: &StagedDataKey,
+) -> Result<DecryptedStagedSector> {
+    let mut encrypted = EncryptedFile::new(File::open(&path)?, key)?;
+
+    // dropped, removing whatever was decrypted, if decrypting fails
+    let decrypted = DecryptedStagedSector::create(path.as_ref())?;
+
+    io::copy(&mut encrypted, &mut &decrypted.file)?;
+
+    Ok(decrypted)
+}
+
+/// Removes the plaintext of encrypted staged sector-files left behind in the
+/// provided directory, e.g. by a process which crashed while sealing.
+pub fn remove_decrypted_staged_sectors(dir: impl AsRef<Path>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.extension() == Some(OsStr::new(DECRYPTED_EXTENSION)) {
+            warn!("removing decrypted staged sector-file {:?}", path);
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypted_file(dir: &Path, key: &StagedDataKey) -> EncryptedFile {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(dir.join("staged"))
+            .unwrap();
+
+        EncryptedFile::new(file, key).unwrap()
+    }
+
+    #[test]
+    fn roundtrips_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = StagedDataKey::new([3; 32]);
+
+        let data: Vec<u8> = (0..(2 * CHUNK_LEN + 100)).map(|n| n as u8).collect();
+
+        {
+            let mut file = encrypted_file(dir.path(), &key);
+
+            // written in small pieces, as staging does
+            for piece in data.chunks(127) {
+                file.write_all(piece).unwrap();
+            }
+        }
+
+        let stored = fs::read(dir.path().join("staged")).unwrap();
+        assert_eq!(
+            HEADER_LEN + data.len() as u64 + 3 * (NONCE_LEN + TAG_LEN),
+            stored.len() as u64
+        );
+        assert!(!stored.windows(64).any(|w| w == &data[..64]));
+
+        let mut file = encrypted_file(dir.path(), &key);
+        assert_eq!(data.len() as u64, file.len());
+
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(data, read);
+
+        // overwrite a range spanning two chunks
+        file.seek(SeekFrom::Start(CHUNK_LEN - 2)).unwrap();
+        file.write_all(&[9; 4]).unwrap();
+        file.seek(SeekFrom::Start(CHUNK_LEN - 3)).unwrap();
+
+        let mut buf = [0; 6];
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(
+            [
+                data[CHUNK_LEN as usize - 3],
+                9,
+                9,
+                9,
+                9,
+                data[CHUNK_LEN as usize + 2]
+            ],
+            buf
+        );
+    }
+
+    #[test]
+    fn truncates_and_extends() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = StagedDataKey::new([5; 32]);
+
+        let mut file = encrypted_file(dir.path(), &key);
+        file.write_all(&vec![7; CHUNK_LEN as usize + 10]).unwrap();
+
+        file.set_len(CHUNK_LEN - 1).unwrap();
+        assert_eq!(CHUNK_LEN - 1, file.len());
+
+        file.set_len(CHUNK_LEN + 1).unwrap();
+        drop(file);
+
+        let mut file = encrypted_file(dir.path(), &key);
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+
+        let mut expected = vec![7; CHUNK_LEN as usize - 1];
+        expected.extend_from_slice(&[0, 0]);
+        assert_eq!(expected, read);
+    }
+
+    #[test]
+    fn rejects_the_wrong_key() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let mut file = encrypted_file(dir.path(), &StagedDataKey::new([1; 32]));
+            file.write_all(&[1; 100]).unwrap();
+        }
+
+        let mut file = encrypted_file(dir.path(), &StagedDataKey::new([2; 32]));
+        assert!(file.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn detects_cut_off_and_transplanted_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = StagedDataKey::new([8; 32]);
+
+        let data = vec![8; 2 * CHUNK_LEN as usize + 100];
+        encrypted_file(dir.path(), &key).write_all(&data).unwrap();
+
+        let stored = fs::read(dir.path().join("staged")).unwrap();
+
+        // the last chunk cut off
+        let cut_off = &stored[..(HEADER_LEN + 2 * STORED_CHUNK_LEN) as usize];
+        fs::write(dir.path().join("staged"), cut_off).unwrap();
+        assert!(encrypted_file(dir.path(), &key)
+            .read_to_end(&mut Vec::new())
+            .is_err());
+
+        // the first chunk of another file encrypted with the same key
+        fs::remove_file(dir.path().join("staged")).unwrap();
+        encrypted_file(dir.path(), &key).write_all(&data).unwrap();
+
+        let mut transplanted = fs::read(dir.path().join("staged")).unwrap();
+        let first_chunk = HEADER_LEN as usize..(HEADER_LEN + STORED_CHUNK_LEN) as usize;
+        transplanted[first_chunk.clone()].copy_from_slice(&stored[first_chunk]);
+        fs::write(dir.path().join("staged"), transplanted).unwrap();
+        assert!(encrypted_file(dir.path(), &key)
+            .read_to_end(&mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn decrypts_staged_sectors_for_sealing() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = StagedDataKey::new([4; 32]);
+
+        encrypted_file(dir.path(), &key)
+            .write_all(&[6; 300])
+            .unwrap();
+
+        {
+            let decrypted = decrypt_staged_sector(dir.path().join("staged"), &key).unwrap();
+            assert_eq!(vec![6; 300], fs::read(decrypted.path()).unwrap());
+
+            #[cfg(target_os = "linux")]
+            assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+        }
+
+        assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn removes_leftover_decrypted_staged_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(dir.path().join("staged"), &[1; 10]).unwrap();
+        fs::write(dir.path().join("staged.decrypted"), &[1; 10]).unwrap();
+
+        remove_decrypted_staged_sectors(dir.path()).unwrap();
+        remove_decrypted_staged_sectors(dir.path().join("missing")).unwrap();
+
+        assert!(dir.path().join("staged").exists());
+        assert!(!dir.path().join("staged.decrypted").exists());
+    }
+}
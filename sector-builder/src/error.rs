@@ -4,6 +4,14 @@ pub type Result<T> = ::std::result::Result<T, Error>;
 
 use failure::Backtrace;
 use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Duration;
+use storage_proofs::sector::SectorId;
+
+use crate::metadata::SecondsSinceEpoch;
+use crate::quota::QuotaResource;
+use crate::state::SealState;
+use crate::watchdog::TaskKind;
 
 #[derive(Debug, Fail)]
 pub enum SectorBuilderErr {
@@ -28,6 +36,118 @@ pub enum SectorBuilderErr {
     #[fail(display = "no piece with key {} found", _0)]
     PieceNotFound(String),
 
+    #[fail(display = "sector with id {} already exists", _0)]
+    SectorAlreadyExists(SectorId),
+
+    #[fail(display = "invalid sealed sector replica: {}", _0)]
+    InvalidReplica(String),
+
+    #[fail(display = "no sealed sector store with id {} registered", _0)]
+    UnknownSealedStore(String),
+
+    #[fail(
+        display = "{} quota exceeded ({} required, limit is {})",
+        resource, required, limit
+    )]
+    QuotaExceeded {
+        resource: QuotaResource,
+        required: u64,
+        limit: u64,
+    },
+
+    #[fail(
+        display = "last committed sector id {} is ahead of the highest sector id {} in the persisted metadata",
+        provided, known
+    )]
+    SectorIdConflict { provided: SectorId, known: SectorId },
+
+    #[fail(display = "no miner {} has been added", _0)]
+    UnknownMiner(String),
+
+    #[fail(display = "invalid miner: {}", _0)]
+    InvalidMiner(String),
+
+    #[fail(display = "invalid sector class: {}", _0)]
+    InvalidSectorClass(String),
+
+    #[fail(display = "idempotency key {} was used to add a different piece", _0)]
+    IdempotencyKeyReused(String),
+
+    #[fail(display = "sector {} is already being sealed", _0)]
+    AlreadySealing(SectorId),
+
+    #[fail(
+        display = "sector {} can't move from {:?} to {:?}",
+        sector_id, from, to
+    )]
+    IllegalSealTransition {
+        sector_id: SectorId,
+        from: SealState,
+        to: SealState,
+    },
+
+    #[fail(display = "{} task timed out after {:?}", task, timeout)]
+    TaskTimedOut { task: TaskKind, timeout: Duration },
+
+    #[fail(display = "{} task was cancelled", _0)]
+    TaskCancelled(TaskKind),
+
+    #[fail(
+        display = "worker {} panicked running {} task: {}",
+        worker_id, task, msg
+    )]
+    WorkerPanicked {
+        worker_id: usize,
+        task: TaskKind,
+        msg: String,
+    },
+
+    #[fail(
+        display = "PoSt can't be generated before its deadline ({} seconds since epoch)",
+        _0
+    )]
+    PoStDeadlineMissed(u64),
+
+    #[fail(
+        display = "directory {:?} is already in use by another SectorBuilder",
+        _0
+    )]
+    DirectoryInUse(PathBuf),
+
+    #[fail(
+        display = "staged data of piece {} in sector {} doesn't match its digest",
+        piece_key, sector_id
+    )]
+    StagedDataCorrupted {
+        sector_id: SectorId,
+        piece_key: String,
+    },
+
+    #[fail(display = "invalid piece labels: {}", _0)]
+    InvalidPieceLabels(String),
+
+    #[fail(display = "invalid prover id: {}", _0)]
+    InvalidProverId(String),
+
+    #[fail(display = "the scheduler is too busy to accept the call")]
+    SchedulerBusy,
+
+    #[fail(display = "handoff bundle doesn't match the metadata: {}", _0)]
+    HandoffMismatch(String),
+
+    #[fail(display = "parameter cache not hydrated: {}", _0)]
+    ParamsMissing(String),
+
+    #[fail(
+        display = "piece {} ends at byte {} of its sector's unsealed bytes, but only {} were unsealed",
+        piece_key, end, num_bytes_unsealed
+    )]
+    UnsealedBytesTruncated {
+        piece_key: String,
+        end: u64,
+        num_bytes_unsealed: u64,
+    },
+
     #[fail(display = "unrecoverable error: {}", _0)]
     Unrecoverable(String, Backtrace),
 }
@@ -36,6 +156,132 @@ pub fn err_piecenotfound(piece_key: String) -> SectorBuilderErr {
     SectorBuilderErr::PieceNotFound(piece_key)
 }
 
+pub fn err_sector_exists(sector_id: SectorId) -> SectorBuilderErr {
+    SectorBuilderErr::SectorAlreadyExists(sector_id)
+}
+
+pub fn err_invalid_replica<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidReplica(format!("{}", msg))
+}
+
+pub fn err_unknown_store(store_id: String) -> SectorBuilderErr {
+    SectorBuilderErr::UnknownSealedStore(store_id)
+}
+
+pub fn err_quota_exceeded(resource: QuotaResource, required: u64, limit: u64) -> SectorBuilderErr {
+    SectorBuilderErr::QuotaExceeded {
+        resource,
+        required,
+        limit,
+    }
+}
+
+pub fn err_sector_id_conflict(provided: SectorId, known: SectorId) -> SectorBuilderErr {
+    SectorBuilderErr::SectorIdConflict { provided, known }
+}
+
+pub fn err_unknown_miner<S: Display>(miner: S) -> SectorBuilderErr {
+    SectorBuilderErr::UnknownMiner(format!("{}", miner))
+}
+
+pub fn err_invalid_miner<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidMiner(format!("{}", msg))
+}
+
+pub fn err_invalid_sector_class<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidSectorClass(format!("{}", msg))
+}
+
+pub fn err_idempotency_key_reused<S: Display>(idempotency_key: S) -> SectorBuilderErr {
+    SectorBuilderErr::IdempotencyKeyReused(format!("{}", idempotency_key))
+}
+
+pub fn err_already_sealing(sector_id: SectorId) -> SectorBuilderErr {
+    SectorBuilderErr::AlreadySealing(sector_id)
+}
+
+pub fn err_illegal_seal_transition(
+    sector_id: SectorId,
+    from: SealState,
+    to: SealState,
+) -> SectorBuilderErr {
+    SectorBuilderErr::IllegalSealTransition {
+        sector_id,
+        from,
+        to,
+    }
+}
+
+pub fn err_task_timed_out(task: TaskKind, timeout: Duration) -> SectorBuilderErr {
+    SectorBuilderErr::TaskTimedOut { task, timeout }
+}
+
+pub fn err_task_cancelled(task: TaskKind) -> SectorBuilderErr {
+    SectorBuilderErr::TaskCancelled(task)
+}
+
+pub fn err_worker_panicked<S: Display>(
+    worker_id: usize,
+    task: TaskKind,
+    msg: S,
+) -> SectorBuilderErr {
+    SectorBuilderErr::WorkerPanicked {
+        worker_id,
+        task,
+        msg: format!("{}", msg),
+    }
+}
+
+pub fn err_post_deadline_missed(deadline: &SecondsSinceEpoch) -> SectorBuilderErr {
+    SectorBuilderErr::PoStDeadlineMissed(deadline.0)
+}
+
+pub fn err_dir_in_use(dir: PathBuf) -> SectorBuilderErr {
+    SectorBuilderErr::DirectoryInUse(dir)
+}
+
+pub fn err_staged_data_corrupted<S: Display>(
+    sector_id: SectorId,
+    piece_key: S,
+) -> SectorBuilderErr {
+    SectorBuilderErr::StagedDataCorrupted {
+        sector_id,
+        piece_key: format!("{}", piece_key),
+    }
+}
+
+pub fn err_invalid_piece_labels<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidPieceLabels(format!("{}", msg))
+}
+
+pub fn err_invalid_prover_id<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::InvalidProverId(format!("{}", msg))
+}
+
+pub fn err_scheduler_busy() -> SectorBuilderErr {
+    SectorBuilderErr::SchedulerBusy
+}
+
+pub fn err_handoff_mismatch<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::HandoffMismatch(format!("{}", msg))
+}
+
+pub fn err_params_missing<S: Display>(msg: S) -> SectorBuilderErr {
+    SectorBuilderErr::ParamsMissing(format!("{}", msg))
+}
+
+pub fn err_unsealed_truncated<S: Display>(
+    piece_key: S,
+    end: u64,
+    num_bytes_unsealed: u64,
+) -> SectorBuilderErr {
+    SectorBuilderErr::UnsealedBytesTruncated {
+        piece_key: format!("{}", piece_key),
+        end,
+        num_bytes_unsealed,
+    }
+}
+
 pub fn err_unrecov<S: Display>(msg: S) -> SectorBuilderErr {
     let backtrace = failure::Backtrace::new();
     SectorBuilderErr::Unrecoverable(format!("{}", msg), backtrace)
@@ -0,0 +1,94 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use filecoin_proofs::error::ExpectWithBacktrace;
+use storage_proofs::sector::SectorId;
+
+use crate::metadata::SecondsSinceEpoch;
+use crate::watchdog::TaskKind;
+
+const FATAL_NOLOCK: &str = "error acquiring event subscribers lock";
+
+/// Something which happened inside a SectorBuilder that isn't the outcome of
+/// any particular request, but which its consumer may want to know about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SectorBuilderEvent {
+    /// a task ran for longer than its timeout and was marked failed; the
+    /// thread running it has been abandoned
+    TaskTimedOut {
+        task: TaskKind,
+        sector_id: Option<SectorId>,
+        timeout: Duration,
+    },
+    /// a sector was scheduled for sealing while the deadline of one of its
+    /// pieces was within the auto-seal policy's deadline margin; sealing may
+    /// not complete before the deadline
+    DealDeadlineAtRisk {
+        sector_id: SectorId,
+        deadline: SecondsSinceEpoch,
+    },
+    /// a piece was added to a staged sector; origin is the identifier of the
+    /// client or source supplied by the caller, if any
+    PieceAdded {
+        sector_id: SectorId,
+        piece_key: String,
+        origin: Option<String>,
+    },
+    /// a worker panicked while running a task, which failed; the worker
+    /// carries on with the next task, so the pool doesn't shrink
+    WorkerCrashed {
+        worker_id: usize,
+        task: TaskKind,
+        sector_id: Option<SectorId>,
+        msg: String,
+    },
+}
+
+/// Delivers events to every subscriber. Subscribers which have hung up are
+/// dropped the next time an event is emitted.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<SectorBuilderEvent>>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> mpsc::Receiver<SectorBuilderEvent> {
+        let (tx, rx) = mpsc::channel();
+
+        self.subscribers.lock().expects(FATAL_NOLOCK).push(tx);
+
+        rx
+    }
+
+    pub fn emit(&self, event: SectorBuilderEvent) {
+        self.subscribers
+            .lock()
+            .expects(FATAL_NOLOCK)
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delivers_events_to_live_subscribers() {
+        let bus = EventBus::default();
+
+        let event = SectorBuilderEvent::TaskTimedOut {
+            task: TaskKind::Seal,
+            sector_id: Some(SectorId::from(1)),
+            timeout: Duration::from_secs(1),
+        };
+
+        let rx_a = bus.subscribe();
+        let rx_b = bus.subscribe();
+        drop(rx_b);
+
+        bus.emit(event.clone());
+
+        assert_eq!(event, rx_a.try_recv().unwrap());
+        assert_eq!(1, bus.subscribers.lock().unwrap().len());
+    }
+}
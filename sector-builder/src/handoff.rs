@@ -0,0 +1,355 @@
+use std::path::PathBuf;
+
+use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
+
+use crate::affinity::WorkerAffinity;
+use crate::disk_backed_storage::SectorAccessProto;
+use crate::error::*;
+use crate::metadata::{
+    InterruptedSeal, LastShutdown, SectorBuilderConfig, StagedSpillOver, StartupReport,
+};
+use crate::quota::StorageQuota;
+use crate::scheduler::{OverloadPolicy, SchedulerConfig};
+use crate::state::SectorBuilderState;
+
+/// What a SectorBuilder was initialized with beyond what its config reports,
+/// with which it's initialized again when it's thawed.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InitParams {
+    pub staged_spill_over: Option<StagedSpillOver>,
+    pub worker_affinity: WorkerAffinity,
+    pub sector_access_proto: SectorAccessProto,
+    pub scheduler_config: SchedulerConfig,
+    // the staged data key isn't handed off, only whether one is needed
+    pub encrypted_staged_data: bool,
+}
+
+/// Everything a SectorBuilder which has been frozen hands off to the one
+/// thawed from it in another process: its config, its state as it was
+/// persisted when it was frozen and the seals it interrupted, which are
+/// restarted once it's thawed. Runtime settings aren't part of the bundle
+/// (see SectorBuilder::thaw). Serialized with to_bytes and deserialized with
+/// from_bytes to be passed between processes.
+#[derive(Clone, Debug)]
+pub struct HandoffBundle {
+    pub(crate) config: SectorBuilderConfig,
+    pub(crate) params: InitParams,
+    pub(crate) state: SectorBuilderState,
+    pub(crate) interrupted_seals: Vec<InterruptedSeal>,
+}
+
+impl HandoffBundle {
+    pub fn config(&self) -> &SectorBuilderConfig {
+        &self.config
+    }
+
+    /// the seals which were running or waiting for a worker when the
+    /// SectorBuilder was frozen
+    pub fn interrupted_seals(&self) -> &[InterruptedSeal] {
+        &self.interrupted_seals
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_cbor::to_vec(&HandoffBundleRepr::from(self))?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<HandoffBundle> {
+        let repr: HandoffBundleRepr = serde_cbor::from_slice(bytes)?;
+
+        Ok(repr.into())
+    }
+
+    // Checks that the SectorBuilder thawed from this bundle found the state
+    // it was frozen with, and restarted every seal it interrupted. If the
+    // metadata was changed in the meantime (e.g. by another SectorBuilder),
+    // sectors or seals may have been lost in the handoff.
+    pub(crate) fn check_thawed(
+        &self,
+        report: &StartupReport,
+        state: &SectorBuilderState,
+    ) -> Result<()> {
+        if report.last_shutdown != LastShutdown::Clean {
+            return Err(err_handoff_mismatch(format!(
+                "expected a clean shutdown, found {:?}",
+                report.last_shutdown
+            ))
+            .into());
+        }
+
+        let interrupted_seals: Vec<SectorId> = self
+            .interrupted_seals
+            .iter()
+            .map(|seal| seal.sector_id)
+            .collect();
+
+        if report.interrupted_seals != interrupted_seals {
+            return Err(err_handoff_mismatch(format!(
+                "seals of sectors {:?} were interrupted, but {:?} were restarted",
+                interrupted_seals, report.interrupted_seals
+            ))
+            .into());
+        }
+
+        if state.staged.sector_id_nonce != self.state.staged.sector_id_nonce {
+            return Err(err_handoff_mismatch(format!(
+                "sector id nonce is {}, expected {}",
+                state.staged.sector_id_nonce, self.state.staged.sector_id_nonce
+            ))
+            .into());
+        }
+
+        let mut expected: Vec<&SectorId> = self.state.staged.sectors.keys().collect();
+        let mut found: Vec<&SectorId> = state.staged.sectors.keys().collect();
+        expected.extend(self.state.sealed.sectors.keys());
+        found.extend(state.sealed.sectors.keys());
+        expected.sort();
+        found.sort();
+
+        if found != expected {
+            return Err(err_handoff_mismatch(format!(
+                "found sectors {:?}, expected {:?}",
+                found, expected
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+// The serialized form of a HandoffBundle, as most of the types in its config
+// have no serialized form of their own.
+#[derive(Serialize, Deserialize)]
+struct HandoffBundleRepr {
+    sector_size: u64,
+    porep_proof_partitions: u8,
+    prover_id: [u8; 31],
+    metadata_dir: PathBuf,
+    sealed_sector_dir: PathBuf,
+    staged_sector_dir: PathBuf,
+    staged_spill_over: Option<(PathBuf, u64)>,
+    max_num_staged_sectors: u64,
+    max_staged_bytes: Option<u64>,
+    max_sealed_bytes: Option<u64>,
+    max_staged_sectors: Option<u64>,
+    worker_cpu_lists: Vec<Vec<usize>>,
+    sector_access_proto: SectorAccessProtoRepr,
+    scheduler_channel_capacity: usize,
+    reject_when_overloaded: bool,
+    encrypted_staged_data: bool,
+    state: SectorBuilderState,
+    interrupted_seals: Vec<InterruptedSeal>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SectorAccessProtoRepr {
+    Original(u32),
+    IpV4(u8, u8, u8, u8),
+    Lotus(String),
+}
+
+impl From<&HandoffBundle> for HandoffBundleRepr {
+    fn from(bundle: &HandoffBundle) -> HandoffBundleRepr {
+        let SectorClass(SectorSize(sector_size), PoRepProofPartitions(porep_proof_partitions)) =
+            bundle.config.sector_class;
+
+        HandoffBundleRepr {
+            sector_size,
+            porep_proof_partitions,
+            prover_id: bundle.config.prover_id,
+            metadata_dir: bundle.config.metadata_dir.clone(),
+            sealed_sector_dir: bundle.config.sealed_sector_dir.clone(),
+            staged_sector_dir: bundle.config.staged_sector_dir.clone(),
+            staged_spill_over: bundle
+                .params
+                .staged_spill_over
+                .as_ref()
+                .map(|spill_over| (spill_over.dir.clone(), spill_over.min_free_bytes)),
+            max_num_staged_sectors: bundle.config.max_num_staged_sectors,
+            max_staged_bytes: bundle.config.quota.max_staged_bytes,
+            max_sealed_bytes: bundle.config.quota.max_sealed_bytes,
+            max_staged_sectors: bundle.config.quota.max_staged_sectors,
+            worker_cpu_lists: bundle.params.worker_affinity.0.clone(),
+            sector_access_proto: match &bundle.params.sector_access_proto {
+                SectorAccessProto::Original(index) => SectorAccessProtoRepr::Original(*index),
+                SectorAccessProto::IpV4(a, b, c, d) => SectorAccessProtoRepr::IpV4(*a, *b, *c, *d),
+                SectorAccessProto::Lotus(miner) => SectorAccessProtoRepr::Lotus(miner.clone()),
+            },
+            scheduler_channel_capacity: bundle.params.scheduler_config.channel_capacity,
+            reject_when_overloaded: bundle.params.scheduler_config.overload_policy
+                == OverloadPolicy::Reject,
+            encrypted_staged_data: bundle.params.encrypted_staged_data,
+            state: bundle.state.clone(),
+            interrupted_seals: bundle.interrupted_seals.clone(),
+        }
+    }
+}
+
+impl From<HandoffBundleRepr> for HandoffBundle {
+    fn from(repr: HandoffBundleRepr) -> HandoffBundle {
+        let secondary_staged_sector_dir =
+            repr.staged_spill_over.as_ref().map(|(dir, _)| dir.clone());
+
+        HandoffBundle {
+            config: SectorBuilderConfig {
+                sector_class: SectorClass(
+                    SectorSize(repr.sector_size),
+                    PoRepProofPartitions(repr.porep_proof_partitions),
+                ),
+                prover_id: repr.prover_id,
+                metadata_dir: repr.metadata_dir,
+                sealed_sector_dir: repr.sealed_sector_dir,
+                staged_sector_dir: repr.staged_sector_dir,
+                secondary_staged_sector_dir,
+                max_num_staged_sectors: repr.max_num_staged_sectors,
+                quota: StorageQuota {
+                    max_staged_bytes: repr.max_staged_bytes,
+                    max_sealed_bytes: repr.max_sealed_bytes,
+                    max_staged_sectors: repr.max_staged_sectors,
+                },
+            },
+            params: InitParams {
+                staged_spill_over: repr.staged_spill_over.map(|(dir, min_free_bytes)| {
+                    StagedSpillOver {
+                        dir,
+                        min_free_bytes,
+                    }
+                }),
+                worker_affinity: WorkerAffinity(repr.worker_cpu_lists),
+                sector_access_proto: match repr.sector_access_proto {
+                    SectorAccessProtoRepr::Original(index) => SectorAccessProto::Original(index),
+                    SectorAccessProtoRepr::IpV4(a, b, c, d) => SectorAccessProto::IpV4(a, b, c, d),
+                    SectorAccessProtoRepr::Lotus(miner) => SectorAccessProto::Lotus(miner),
+                },
+                scheduler_config: SchedulerConfig {
+                    channel_capacity: repr.scheduler_channel_capacity,
+                    overload_policy: if repr.reject_when_overloaded {
+                        OverloadPolicy::Reject
+                    } else {
+                        OverloadPolicy::Block
+                    },
+                },
+                encrypted_staged_data: repr.encrypted_staged_data,
+            },
+            state: repr.state,
+            interrupted_seals: repr.interrupted_seals,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+
+    use crate::metadata::{SealStatus, StagedSectorMetadata};
+
+    use super::*;
+
+    fn bundle() -> HandoffBundle {
+        let mut state = SectorBuilderState::new(SectorId::from(2));
+
+        for sector_id in 1..=2 {
+            state.staged.sectors.insert(
+                SectorId::from(sector_id),
+                StagedSectorMetadata {
+                    sector_id: SectorId::from(sector_id),
+                    seal_status: SealStatus::Sealing,
+                    ..Default::default()
+                },
+            );
+        }
+
+        HandoffBundle {
+            config: SectorBuilderConfig {
+                sector_class: SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+                prover_id: [7; 31],
+                metadata_dir: PathBuf::from("/metadata"),
+                sealed_sector_dir: PathBuf::from("/sealed"),
+                staged_sector_dir: PathBuf::from("/staged"),
+                secondary_staged_sector_dir: Some(PathBuf::from("/staged2")),
+                max_num_staged_sectors: 3,
+                quota: StorageQuota {
+                    max_staged_bytes: Some(1024),
+                    ..Default::default()
+                },
+            },
+            params: InitParams {
+                staged_spill_over: Some(StagedSpillOver {
+                    dir: PathBuf::from("/staged2"),
+                    min_free_bytes: 4096,
+                }),
+                worker_affinity: WorkerAffinity(vec![vec![0, 1], vec![2]]),
+                sector_access_proto: SectorAccessProto::Lotus("t01000".to_string()),
+                scheduler_config: SchedulerConfig {
+                    channel_capacity: 8,
+                    overload_policy: OverloadPolicy::Reject,
+                },
+                encrypted_staged_data: true,
+            },
+            state,
+            interrupted_seals: vec![
+                InterruptedSeal {
+                    sector_id: SectorId::from(1),
+                    dispatched: true,
+                },
+                InterruptedSeal {
+                    sector_id: SectorId::from(2),
+                    dispatched: false,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_handoff_bundle_roundtrips() {
+        let bundle = bundle();
+        let thawed = HandoffBundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(bundle.params, thawed.params);
+        assert_eq!(bundle.state, thawed.state);
+        assert_eq!(bundle.interrupted_seals, thawed.interrupted_seals);
+        assert_eq!(
+            format!("{:?}", bundle.config),
+            format!("{:?}", thawed.config)
+        );
+
+        assert!(HandoffBundle::from_bytes(b"not a bundle").is_err());
+    }
+
+    #[test]
+    fn test_thawed_builder_must_find_the_frozen_state() {
+        let bundle = bundle();
+
+        let report = StartupReport {
+            last_shutdown: LastShutdown::Clean,
+            interrupted_seals: vec![SectorId::from(1), SectorId::from(2)],
+            truncated_sectors: vec![],
+        };
+
+        assert!(bundle.check_thawed(&report, &bundle.state).is_ok());
+
+        // the metadata wasn't persisted by the frozen builder
+        let crashed = StartupReport {
+            last_shutdown: LastShutdown::Crash,
+            ..report.clone()
+        };
+
+        assert!(bundle.check_thawed(&crashed, &bundle.state).is_err());
+
+        // a seal wasn't restarted
+        let lost_seal = StartupReport {
+            interrupted_seals: vec![SectorId::from(1)],
+            ..report.clone()
+        };
+
+        assert!(bundle.check_thawed(&lost_seal, &bundle.state).is_err());
+
+        // a sector is missing
+        let mut state = bundle.state.clone();
+        state.staged.sectors.remove(&SectorId::from(2));
+
+        assert!(bundle.check_thawed(&report, &state).is_err());
+    }
+}
@@ -5,39 +5,64 @@ use filecoin_proofs::pieces::{
 };
 use filecoin_proofs::types::UnpaddedBytesAmount;
 
+use crate::constants::{
+    MAX_PIECE_LABELS, MAX_PIECE_LABEL_KEY_LEN, MAX_PIECE_LABEL_VALUE_LEN, MAX_PIECE_ORIGIN_LEN,
+};
 use crate::error::*;
-use crate::metadata::{self, SealStatus, SecondsSinceEpoch, StagedSectorMetadata};
-use crate::state::StagedState;
-use crate::store::{SectorManager, SectorStore, SimpleSectorManager, SimpleSectorStore};
+use crate::helpers::{choose_staged_sector_dir, staged_sector_manager};
+use crate::metadata::{
+    self, PieceDealInfo, SealStatus, SecondsSinceEpoch, StagedSectorDir, StagedSectorMetadata,
+};
+use crate::sector_id_provider::SectorIdProvider;
+use crate::staged_writer::StagedWriteConfig;
+use crate::state::{SealedState, StagedState};
+use crate::store::{SectorStore, SimpleSectorManager, SimpleSectorStore};
 use storage_proofs::sector::SectorId;
 
+/// What add_piece records along with a piece, and how it provisions and
+/// writes the staged sector that receives it.
+pub struct AddPieceOptions<'a> {
+    pub store_until: SecondsSinceEpoch,
+    pub idempotency_key: Option<String>,
+    pub deal_info: PieceDealInfo,
+    pub sector_id_provider: &'a mut dyn SectorIdProvider,
+    pub write_config: &'a StagedWriteConfig,
+    pub now: SecondsSinceEpoch,
+}
+
 pub fn add_piece<S: SectorStore>(
     sector_store: &S,
+    sealed_state: &SealedState,
     mut staged_state: &mut StagedState,
     piece_bytes_amount: u64,
     piece_key: String,
     piece_file: impl std::io::Read,
-    _store_until: SecondsSinceEpoch,
+    options: AddPieceOptions,
 ) -> Result<SectorId> {
-    let sector_mgr = sector_store.manager();
+    let AddPieceOptions {
+        store_until,
+        idempotency_key,
+        deal_info,
+        sector_id_provider,
+        write_config,
+        now,
+    } = options;
+
     let sector_max = sector_store.sector_config().max_unsealed_bytes_per_sector();
 
     let piece_bytes_len = UnpaddedBytesAmount(piece_bytes_amount);
 
-    let opt_dest_sector_id = {
-        let candidates: Vec<StagedSectorMetadata> = staged_state
-            .sectors
-            .iter()
-            .filter(|(_, v)| v.seal_status == SealStatus::Pending)
-            .map(|(_, v)| (*v).clone())
-            .collect();
+    let opt_dest_sector_id = find_destination_sector_id(staged_state, sector_max, piece_bytes_len)?;
 
-        compute_destination_sector_id(&candidates, sector_max, piece_bytes_len)?
-    };
-
-    let dest_sector_id = opt_dest_sector_id
-        .ok_or(())
-        .or_else(|_| provision_new_staged_sector(sector_mgr, &mut staged_state))?;
+    let dest_sector_id = opt_dest_sector_id.ok_or(()).or_else(|_| {
+        provision_new_staged_sector(
+            sector_store,
+            sector_id_provider,
+            sealed_state,
+            &mut staged_state,
+            &now,
+        )
+    })?;
 
     if let Some(s) = staged_state.sectors.get_mut(&dest_sector_id) {
         let piece_lengths: Vec<_> = s.pieces.iter().map(|p| p.num_bytes).collect();
@@ -45,9 +70,8 @@ pub fn add_piece<S: SectorStore>(
         let (expected_num_bytes_written, mut chain) =
             get_aligned_source(piece_file, &piece_lengths, piece_bytes_len);
 
-        sector_store
-            .manager()
-            .write_and_preprocess(&s.sector_access, &mut chain)
+        staged_sector_manager(sector_store, s.staged_dir)
+            .write_and_preprocess_with(&s.sector_access, &mut chain, write_config)
             .map_err(Into::into)
             .and_then(|num_bytes_written| {
                 if num_bytes_written != expected_num_bytes_written {
@@ -65,6 +89,10 @@ pub fn add_piece<S: SectorStore>(
                     num_bytes: piece_bytes_len,
                     comm_p: None,
                     piece_inclusion_proof: None,
+                    idempotency_key,
+                    store_until: Some(store_until),
+                    deal_id: deal_info.deal_id,
+                    labels: deal_info.labels,
                 });
 
                 sector_id
@@ -85,16 +113,7 @@ pub fn add_piece_first<S: SimpleSectorStore>(
 
     let piece_bytes_len = UnpaddedBytesAmount(piece_bytes_amount);
 
-    let opt_dest_sector_id = {
-        let candidates: Vec<StagedSectorMetadata> = staged_state
-            .sectors
-            .iter()
-            .filter(|(_, v)| v.seal_status == SealStatus::Pending)
-            .map(|(_, v)| (*v).clone())
-            .collect();
-
-        compute_destination_sector_id(&candidates, sector_max, piece_bytes_len)?
-    };
+    let opt_dest_sector_id = find_destination_sector_id(staged_state, sector_max, piece_bytes_len)?;
 
     opt_dest_sector_id
         .ok_or(())
@@ -104,13 +123,35 @@ pub fn add_piece_first<S: SimpleSectorStore>(
 pub fn add_piece_second<S: SimpleSectorStore>(
     sector_store: &S,
     miner: &str,
-    mut sector: StagedSectorMetadata,
+    sector: StagedSectorMetadata,
     piece_bytes_amount: u64,
     piece_key: String,
     piece_file: impl std::io::Read,
 ) -> Result<StagedSectorMetadata> {
-    sector_store.manager().new_staging_sector_access(miner, sector.sector_id, true)?;
+    sector_store
+        .manager()
+        .new_staging_sector_access(miner, sector.sector_id, true)?;
+
+    write_piece_second(
+        sector_store,
+        miner,
+        sector,
+        piece_bytes_amount,
+        piece_key,
+        piece_file,
+    )
+}
 
+// Writes a piece into the provided staged sector, whose staged sector-file
+// must exist, and adds it to the sector's pieces.
+fn write_piece_second<S: SimpleSectorStore>(
+    sector_store: &S,
+    miner: &str,
+    mut sector: StagedSectorMetadata,
+    piece_bytes_amount: u64,
+    piece_key: String,
+    piece_file: impl std::io::Read,
+) -> Result<StagedSectorMetadata> {
     let piece_bytes_len = UnpaddedBytesAmount(piece_bytes_amount);
 
     let piece_lengths: Vec<_> = sector.pieces.iter().map(|p| p.num_bytes).collect();
@@ -124,10 +165,7 @@ pub fn add_piece_second<S: SimpleSectorStore>(
         .map_err(Into::into)
         .and_then(|num_bytes_written| {
             if num_bytes_written != expected_num_bytes_written {
-                Err(
-                    err_inc_write(u64::from(num_bytes_written), u64::from(piece_bytes_len))
-                        .into(),
-                )
+                Err(err_inc_write(u64::from(num_bytes_written), u64::from(piece_bytes_len)).into())
             } else {
                 Ok(sector.sector_id)
             }
@@ -138,15 +176,182 @@ pub fn add_piece_second<S: SimpleSectorStore>(
                 num_bytes: piece_bytes_len,
                 comm_p: None,
                 piece_inclusion_proof: None,
+                idempotency_key: None,
+                store_until: None,
+                deal_id: None,
+                labels: Default::default(),
             });
 
             sector
         })
 }
 
+// Writes several pieces into the provided staged sector, one after another,
+// returning the sector's metadata with all of them added. The pieces must
+// all fit into the sector, which is checked before any of them is written.
+// If writing a piece fails, the staged sector-file is truncated to the
+// pieces it held before, so that none of the pieces is half-added.
+pub fn add_pieces_second<S: SimpleSectorStore, R: std::io::Read>(
+    sector_store: &S,
+    miner: &str,
+    sector: StagedSectorMetadata,
+    pieces: Vec<(String, R, u64)>,
+) -> Result<StagedSectorMetadata> {
+    let sector_max = sector_store.sector_config().max_unsealed_bytes_per_sector();
+
+    let mut piece_lengths: Vec<_> = sector.pieces.iter().map(|p| p.num_bytes).collect();
+    let preceding = sum_piece_bytes_with_alignment(&piece_lengths);
+
+    piece_lengths.extend(pieces.iter().map(|(_, _, n)| UnpaddedBytesAmount(*n)));
+    let total = sum_piece_bytes_with_alignment(&piece_lengths);
+
+    if total > sector_max {
+        return Err(err_overflow(
+            u64::from(total) - u64::from(preceding),
+            u64::from(sector_max),
+        )
+        .into());
+    }
+
+    let sector_id = sector.sector_id;
+    let sector_access = sector.sector_access.clone();
+
+    sector_store
+        .manager()
+        .new_staging_sector_access(miner, sector_id, true)?;
+
+    pieces
+        .into_iter()
+        .try_fold(
+            sector,
+            |sector, (piece_key, piece_file, piece_bytes_amount)| {
+                write_piece_second(
+                    sector_store,
+                    miner,
+                    sector,
+                    piece_bytes_amount,
+                    piece_key,
+                    piece_file,
+                )
+            },
+        )
+        .map_err(|err| {
+            if let Err(truncate_err) = sector_store.manager().truncate_unsealed(
+                miner,
+                &sector_access,
+                u64::from(preceding),
+            ) {
+                warn!(
+                    "failed to truncate staged sector {} after failing to add pieces: {:?}",
+                    sector_id, truncate_err
+                );
+            }
+
+            err
+        })
+}
+
+// Checks that the labels with which a piece is to be added are within the
+// limits on their number and length, as they're persisted with the piece's
+// metadata, and that its origin is within the limit on its length.
+pub fn check_piece_labels(deal_info: &PieceDealInfo) -> Result<()> {
+    if let Some(origin) = &deal_info.origin {
+        if origin.len() > MAX_PIECE_ORIGIN_LEN {
+            return Err(err_invalid_piece_labels(format!(
+                "origin exceeds the maximum length of {} bytes",
+                MAX_PIECE_ORIGIN_LEN
+            ))
+            .into());
+        }
+    }
+
+    if deal_info.labels.len() > MAX_PIECE_LABELS {
+        return Err(err_invalid_piece_labels(format!(
+            "{} labels exceed the maximum of {}",
+            deal_info.labels.len(),
+            MAX_PIECE_LABELS
+        ))
+        .into());
+    }
+
+    for (key, value) in &deal_info.labels {
+        if key.len() > MAX_PIECE_LABEL_KEY_LEN {
+            return Err(err_invalid_piece_labels(format!(
+                "key {} exceeds the maximum length of {} bytes",
+                key, MAX_PIECE_LABEL_KEY_LEN
+            ))
+            .into());
+        }
+
+        if value.len() > MAX_PIECE_LABEL_VALUE_LEN {
+            return Err(err_invalid_piece_labels(format!(
+                "value of key {} exceeds the maximum length of {} bytes",
+                key, MAX_PIECE_LABEL_VALUE_LEN
+            ))
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+// Finds the sector to which a piece was added with the provided idempotency
+// key, so that a retried add_piece doesn't stage the piece a second time.
+// Produces an error if the key was used to add a different piece.
+pub fn find_piece_by_idempotency_key(
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    idempotency_key: &str,
+    piece_key: &str,
+    piece_bytes_amount: u64,
+) -> Result<Option<SectorId>> {
+    let staged = staged_state
+        .sectors
+        .values()
+        .map(|s| (s.sector_id, &s.pieces));
+    let sealed = sealed_state
+        .sectors
+        .values()
+        .map(|s| (s.sector_id, &s.pieces));
+
+    for (sector_id, pieces) in staged.chain(sealed) {
+        let found = pieces
+            .iter()
+            .find(|p| p.idempotency_key.as_ref().map(String::as_str) == Some(idempotency_key));
+
+        if let Some(piece) = found {
+            if piece.piece_key != piece_key || u64::from(piece.num_bytes) != piece_bytes_amount {
+                return Err(err_idempotency_key_reused(idempotency_key).into());
+            }
+
+            return Ok(Some(sector_id));
+        }
+    }
+
+    Ok(None)
+}
+
+// Returns the first pending staged sector into which a piece of the provided
+// length will fit, or None if a new sector must be provisioned for it. This
+// is where add_piece places its pieces.
+pub fn find_destination_sector_id(
+    staged_state: &StagedState,
+    max_bytes_per_sector: UnpaddedBytesAmount,
+    num_bytes_in_piece: UnpaddedBytesAmount,
+) -> Result<Option<SectorId>> {
+    let candidates: Vec<StagedSectorMetadata> = staged_state
+        .sectors
+        .values()
+        .filter(|v| v.seal_status == SealStatus::Pending)
+        .cloned()
+        .collect();
+
+    compute_destination_sector_id(&candidates, max_bytes_per_sector, num_bytes_in_piece)
+}
+
 // Given a list of staged sectors which are accepting data, return the
 // first staged sector into which the bytes will fit.
-fn compute_destination_sector_id(
+pub fn compute_destination_sector_id(
     candidate_sectors: &[StagedSectorMetadata],
     max_bytes_per_sector: UnpaddedBytesAmount,
     num_bytes_in_piece: UnpaddedBytesAmount,
@@ -175,29 +380,54 @@ fn compute_destination_sector_id(
 }
 
 // Provisions a new staged sector and returns its sector_id. Not a pure
-// function; creates a sector access (likely a file), increments the sector id
-// nonce, and mutates the StagedState.
-fn provision_new_staged_sector(
-    sector_manager: &dyn SectorManager,
+// function; creates a sector access (likely a file), mutates the StagedState,
+// raises the sector id nonce to the sector's id and only then tells the
+// provider that the id has been allocated. The sector spills over into the
+// secondary staged sector directory while the primary one is low on space.
+fn provision_new_staged_sector<S: SectorStore>(
+    sector_store: &S,
+    sector_id_provider: &mut dyn SectorIdProvider,
+    sealed_state: &SealedState,
     staged_state: &mut StagedState,
+    now: &SecondsSinceEpoch,
 ) -> Result<SectorId> {
-    let sector_id = {
-        let n = &mut staged_state.sector_id_nonce;
-        *n += 1;
-        SectorId::from(*n)
-    };
+    let sector_id = sector_id_provider.next_sector_id(staged_state.sector_id_nonce)?;
+
+    if staged_state.sectors.contains_key(&sector_id)
+        || sealed_state.sectors.contains_key(&sector_id)
+    {
+        return Err(err_sector_exists(sector_id).into());
+    }
 
-    let access = sector_manager.new_staging_sector_access(sector_id)?;
+    let staged_dir = choose_staged_sector_dir(sector_store)?;
+
+    if staged_dir == StagedSectorDir::Secondary {
+        info!(
+            "staged sector directory is low on space, provisioning sector {} in the secondary one",
+            sector_id
+        );
+    }
+
+    let access =
+        staged_sector_manager(sector_store, staged_dir).new_staging_sector_access(sector_id)?;
 
     let meta = StagedSectorMetadata {
         pieces: Default::default(),
         sector_access: access.clone(),
         sector_id,
         seal_status: SealStatus::Pending,
+        seal_ticket: None,
+        staged_data: Default::default(),
+        staged_dir,
+        created_at: Some(now.clone()),
+        repairing: false,
     };
 
+    staged_state.sector_id_nonce = staged_state.sector_id_nonce.max(u64::from(sector_id));
     staged_state.sectors.insert(meta.sector_id, meta.clone());
 
+    sector_id_provider.sector_id_allocated(sector_id);
+
     Ok(sector_id)
 }
 
@@ -219,6 +449,11 @@ fn simple_provision_new_staged_sector(
         sector_access: access.clone(),
         sector_id,
         seal_status: SealStatus::Pending,
+        seal_ticket: None,
+        staged_data: Default::default(),
+        staged_dir: Default::default(),
+        created_at: Some(SecondsSinceEpoch::now()),
+        repairing: false,
     };
 
     staged_state.sectors.insert(meta.sector_id, meta.clone());
@@ -229,7 +464,10 @@ fn simple_provision_new_staged_sector(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::disk_backed_storage::new_simple_sector_store;
     use crate::metadata::PieceMetadata;
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
 
     #[test]
     fn test_alpha() {
@@ -238,15 +476,13 @@ mod tests {
         sealed_sector_a.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(508),
-            comm_p: None,
-            piece_inclusion_proof: None,
+            ..Default::default()
         });
 
         sealed_sector_a.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(254),
-            comm_p: None,
-            piece_inclusion_proof: None,
+            ..Default::default()
         });
 
         let mut sealed_sector_b: StagedSectorMetadata = Default::default();
@@ -254,8 +490,7 @@ mod tests {
         sealed_sector_b.pieces.push(PieceMetadata {
             piece_key: String::from("x"),
             num_bytes: UnpaddedBytesAmount(508),
-            comm_p: None,
-            piece_inclusion_proof: None,
+            ..Default::default()
         });
 
         let staged_sectors = vec![sealed_sector_a.clone(), sealed_sector_b.clone()];
@@ -304,4 +539,115 @@ mod tests {
             _ => panic!("got no destination sector"),
         }
     }
+
+    #[test]
+    fn test_find_piece_by_idempotency_key() {
+        let mut staged_state: StagedState = Default::default();
+        let sealed_state: SealedState = Default::default();
+
+        let mut sector: StagedSectorMetadata = Default::default();
+        sector.sector_id = SectorId::from(3);
+        sector.pieces.push(PieceMetadata {
+            piece_key: String::from("x"),
+            num_bytes: UnpaddedBytesAmount(508),
+            idempotency_key: Some(String::from("deal-1")),
+            ..Default::default()
+        });
+
+        staged_state.sectors.insert(sector.sector_id, sector);
+
+        // a retry finds the sector to which the piece was added
+        let found = find_piece_by_idempotency_key(&staged_state, &sealed_state, "deal-1", "x", 508);
+        assert_eq!(Some(SectorId::from(3)), found.unwrap());
+
+        // an unused key finds nothing
+        let found = find_piece_by_idempotency_key(&staged_state, &sealed_state, "deal-2", "x", 508);
+        assert_eq!(None, found.unwrap());
+
+        // a key can't be reused for a different piece
+        assert!(
+            find_piece_by_idempotency_key(&staged_state, &sealed_state, "deal-1", "y", 508)
+                .is_err()
+        );
+        assert!(
+            find_piece_by_idempotency_key(&staged_state, &sealed_state, "deal-1", "x", 254)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_piece_labels() {
+        let mut deal_info = PieceDealInfo {
+            deal_id: Some(42),
+            labels: Default::default(),
+            origin: Some(String::from("f01234")),
+        };
+        deal_info
+            .labels
+            .insert(String::from("client"), b"f01234".to_vec());
+
+        assert!(check_piece_labels(&deal_info).is_ok());
+
+        deal_info.labels.insert(
+            String::from("payload"),
+            vec![0; MAX_PIECE_LABEL_VALUE_LEN + 1],
+        );
+
+        assert!(check_piece_labels(&deal_info).is_err());
+
+        deal_info.labels.clear();
+        for i in 0..=MAX_PIECE_LABELS {
+            deal_info.labels.insert(format!("{}", i), vec![]);
+        }
+
+        assert!(check_piece_labels(&deal_info).is_err());
+
+        deal_info.labels.clear();
+        deal_info.origin = Some("x".repeat(MAX_PIECE_ORIGIN_LEN + 1));
+
+        assert!(check_piece_labels(&deal_info).is_err());
+    }
+
+    #[test]
+    fn test_add_pieces_second() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+
+        let store = new_simple_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let mut staged_state: StagedState = Default::default();
+        let sector_id = add_piece_first(&store, "m", &mut staged_state, 127).unwrap();
+        let sector = staged_state.sectors[&sector_id].clone();
+
+        // the pieces don't fit into the sector together
+        let too_many = vec![
+            (String::from("a"), &[1u8; 508][..], 508),
+            (String::from("b"), &[2u8; 600][..], 600),
+        ];
+
+        assert!(add_pieces_second(&store, "m", sector.clone(), too_many).is_err());
+
+        let pieces = vec![
+            (String::from("a"), &[1u8; 127][..], 127),
+            (String::from("b"), &[2u8; 254][..], 254),
+        ];
+
+        let sector = add_pieces_second(&store, "m", sector, pieces).unwrap();
+
+        let keys: Vec<&str> = sector.pieces.iter().map(|p| p.piece_key.as_str()).collect();
+        assert_eq!(vec!["a", "b"], keys);
+
+        let piece_lengths = [UnpaddedBytesAmount(127), UnpaddedBytesAmount(254)];
+        assert_eq!(
+            u64::from(sum_piece_bytes_with_alignment(&piece_lengths)),
+            store
+                .manager()
+                .num_unsealed_bytes("m", &sector.sector_access)
+                .unwrap()
+        );
+    }
 }
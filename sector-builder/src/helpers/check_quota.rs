@@ -0,0 +1,232 @@
+use filecoin_proofs::types::{PaddedBytesAmount, UnpaddedBytesAmount};
+
+use crate::error::*;
+use crate::helpers::compute_destination_sector_id;
+use crate::metadata::{SealStatus, StagedSectorMetadata};
+use crate::quota::{QuotaResource, StorageQuota};
+use crate::state::{SealState, SealedState, StagedState};
+
+// Produces an error if adding a piece of the provided size would exceed any
+// of the limits of the provided quota. Staged sectors which have not yet been
+// sealed count against the staged quotas and, as they will eventually be
+// sealed, against the sealed bytes quota.
+//
+// Sectors which are being sealed don't count against the staged sectors
+// quota, so that pieces are packed into a fresh sector while the previous
+// one is sealed instead of waiting for sealing to finish.
+pub fn check_add_piece_quota(
+    quota: &StorageQuota,
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    max_user_bytes_per_staged_sector: UnpaddedBytesAmount,
+    sector_size: PaddedBytesAmount,
+    piece_bytes_amount: u64,
+) -> Result<()> {
+    let unsealed: Vec<&StagedSectorMetadata> = staged_state
+        .sectors
+        .values()
+        .filter(|s| match s.seal_status {
+            SealStatus::Sealed(_) => false,
+            _ => true,
+        })
+        .collect();
+
+    // determine whether or not the piece would be written to a newly
+    // provisioned staged sector
+    let num_new_sectors = {
+        let candidates: Vec<StagedSectorMetadata> = unsealed
+            .iter()
+            .filter(|s| s.seal_status == SealStatus::Pending)
+            .map(|s| (*s).clone())
+            .collect();
+
+        let opt_dest_sector_id = compute_destination_sector_id(
+            &candidates,
+            max_user_bytes_per_staged_sector,
+            UnpaddedBytesAmount(piece_bytes_amount),
+        )?;
+
+        if opt_dest_sector_id.is_some() {
+            0
+        } else {
+            1
+        }
+    };
+
+    let num_staged_sectors = unsealed.len() as u64 + num_new_sectors;
+
+    if let Some(limit) = quota.max_staged_sectors {
+        let num_sealing_sectors = staged_state.num_sectors_in(SealState::Sealing);
+
+        ensure_within(
+            QuotaResource::StagedSectors,
+            num_staged_sectors - num_sealing_sectors,
+            limit,
+        )?;
+    }
+
+    if let Some(limit) = quota.max_staged_bytes {
+        let staged_bytes: u64 = unsealed
+            .iter()
+            .flat_map(|s| s.pieces.iter())
+            .map(|p| u64::from(p.num_bytes))
+            .sum();
+
+        ensure_within(
+            QuotaResource::StagedBytes,
+            staged_bytes + piece_bytes_amount,
+            limit,
+        )?;
+    }
+
+    if let Some(limit) = quota.max_sealed_bytes {
+        let sealed_bytes: u64 = sealed_state.sectors.values().map(|s| s.len).sum();
+
+        ensure_within(
+            QuotaResource::SealedBytes,
+            sealed_bytes + num_staged_sectors * u64::from(sector_size),
+            limit,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn ensure_within(resource: QuotaResource, required: u64, limit: u64) -> Result<()> {
+    if required > limit {
+        Err(err_quota_exceeded(resource, required, limit).into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use storage_proofs::sector::SectorId;
+
+    use crate::metadata::{PieceMetadata, SealedSectorMetadata};
+
+    use super::*;
+
+    const MAX_BYTES: UnpaddedBytesAmount = UnpaddedBytesAmount(1016);
+    const SECTOR_SIZE: PaddedBytesAmount = PaddedBytesAmount(1024);
+
+    fn staged_sector(
+        sector_id: u64,
+        num_bytes: u64,
+        seal_status: SealStatus,
+    ) -> StagedSectorMetadata {
+        StagedSectorMetadata {
+            sector_id: SectorId::from(sector_id),
+            pieces: vec![PieceMetadata {
+                piece_key: format!("piece-{}", sector_id),
+                num_bytes: UnpaddedBytesAmount(num_bytes),
+                comm_p: None,
+                piece_inclusion_proof: None,
+                idempotency_key: None,
+                store_until: None,
+                deal_id: None,
+                labels: Default::default(),
+            }],
+            seal_status,
+            ..Default::default()
+        }
+    }
+
+    fn check(
+        quota: &StorageQuota,
+        staged: &StagedState,
+        sealed: &SealedState,
+        amt: u64,
+    ) -> Result<()> {
+        check_add_piece_quota(quota, staged, sealed, MAX_BYTES, SECTOR_SIZE, amt)
+    }
+
+    fn exceeded(result: Result<()>) -> Option<QuotaResource> {
+        match result {
+            Ok(_) => None,
+            Err(err) => match err.downcast_ref() {
+                Some(SectorBuilderErr::QuotaExceeded { resource, .. }) => Some(*resource),
+                _ => panic!("unexpected error: {}", err),
+            },
+        }
+    }
+
+    #[test]
+    fn test_check_add_piece_quota() {
+        let mut staged: StagedState = Default::default();
+        let mut sealed: SealedState = Default::default();
+
+        staged.sectors.insert(
+            SectorId::from(1),
+            staged_sector(1, 127, SealStatus::Pending),
+        );
+        staged.sectors.insert(
+            SectorId::from(2),
+            staged_sector(2, 1016, SealStatus::Sealing),
+        );
+
+        let sealed_meta = SealedSectorMetadata {
+            sector_id: SectorId::from(3),
+            len: 1024,
+            ..Default::default()
+        };
+
+        staged.sectors.insert(
+            SectorId::from(3),
+            staged_sector(3, 1016, SealStatus::Sealed(Box::new(sealed_meta.clone()))),
+        );
+        sealed.sectors.insert(SectorId::from(3), sealed_meta);
+
+        // no limits
+        assert_eq!(
+            None,
+            exceeded(check(&Default::default(), &staged, &sealed, 508))
+        );
+
+        // the piece fits into the pending sector, so no new sector is needed
+        let quota = StorageQuota {
+            max_staged_sectors: Some(1),
+            ..Default::default()
+        };
+
+        assert_eq!(None, exceeded(check(&quota, &staged, &sealed, 508)));
+        assert_eq!(
+            Some(QuotaResource::StagedSectors),
+            exceeded(check(&quota, &staged, &sealed, 1016))
+        );
+
+        // the sector being sealed doesn't keep a fresh sector from being
+        // provisioned next to the pending one
+        let quota = StorageQuota {
+            max_staged_sectors: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(None, exceeded(check(&quota, &staged, &sealed, 1016)));
+
+        // sealed sectors don't count against the staged bytes quota
+        let quota = StorageQuota {
+            max_staged_bytes: Some(127 + 1016 + 508),
+            ..Default::default()
+        };
+
+        assert_eq!(None, exceeded(check(&quota, &staged, &sealed, 508)));
+        assert_eq!(
+            Some(QuotaResource::StagedBytes),
+            exceeded(check(&quota, &staged, &sealed, 509))
+        );
+
+        // each unsealed staged sector will become a full sealed sector
+        let quota = StorageQuota {
+            max_sealed_bytes: Some(3 * 1024),
+            ..Default::default()
+        };
+
+        assert_eq!(None, exceeded(check(&quota, &staged, &sealed, 508)));
+        assert_eq!(
+            Some(QuotaResource::SealedBytes),
+            exceeded(check(&quota, &staged, &sealed, 1016))
+        );
+    }
+}
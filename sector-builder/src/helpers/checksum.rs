@@ -1,4 +1,13 @@
 use std::convert::AsRef;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use crate::helpers::{extent_size, NUM_CHECKSUM_EXTENTS};
+use crate::metadata::ChecksumAlgo;
+
+// number of bytes read from a file and hashed at once by BLAKE3, large enough
+// for the hashing to be spread over many threads
+const BLAKE3_CHUNK_SIZE: usize = 1 << 24;
 
 /// Calculates the BLAKE2b checksum of a given file.
 pub fn calculate_checksum(
@@ -10,3 +19,182 @@ pub fn calculate_checksum(
     std::io::copy(&mut reader, &mut hasher)?;
     Ok(hasher.finalize())
 }
+
+/// Calculates the BLAKE3 checksum of a given file, hashing each chunk read from
+/// the file on multiple threads.
+pub fn calculate_blake3_checksum(
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    let mut f = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; BLAKE3_CHUNK_SIZE];
+
+    loop {
+        match f.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                hasher.update_with_join::<blake3::join::RayonJoin>(&buf[..n]);
+            }
+            Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Calculates the checksum of a given file with the provided algorithm.
+pub fn calculate_checksum_with(
+    path: impl AsRef<std::path::Path>,
+    algo: ChecksumAlgo,
+) -> std::io::Result<Vec<u8>> {
+    match algo {
+        ChecksumAlgo::Blake2b => calculate_checksum(path).map(|hash| hash.as_bytes().to_vec()),
+        ChecksumAlgo::Blake3 => {
+            calculate_blake3_checksum(path).map(|hash| hash.as_bytes().to_vec())
+        }
+    }
+}
+
+/// Checksums of a sealed sector-file, calculated by the worker which sealed
+/// it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicaChecksums {
+    /// checksum of the whole file
+    pub checksum: Vec<u8>,
+    /// algorithm with which the checksum of the whole file was calculated
+    pub algo: ChecksumAlgo,
+    /// BLAKE3 checksum of each extent of the file (see extent_size)
+    pub extent_checksums: Vec<[u8; 32]>,
+    pub len: u64,
+    /// how long checksumming the file took
+    pub duration: Duration,
+}
+
+enum FileHasher {
+    Blake2b(blake2b_simd::blake2bp::State),
+    Blake3(blake3::Hasher),
+}
+
+/// Calculates the checksum of a sealed sector-file with the provided
+/// algorithm along with the checksums of its extents, reading the file once.
+pub fn calculate_replica_checksums(
+    path: impl AsRef<std::path::Path>,
+    algo: ChecksumAlgo,
+) -> std::io::Result<ReplicaChecksums> {
+    let started = Instant::now();
+
+    let mut f = std::fs::File::open(path)?;
+    let len = f.metadata()?.len();
+    let size = extent_size(len);
+
+    let mut hasher = match algo {
+        ChecksumAlgo::Blake2b => FileHasher::Blake2b(blake2b_simd::blake2bp::State::new()),
+        ChecksumAlgo::Blake3 => FileHasher::Blake3(blake3::Hasher::new()),
+    };
+
+    let mut extent_hasher = blake3::Hasher::new();
+    let mut extent_remaining = size;
+    let mut extent_checksums = Vec::with_capacity(NUM_CHECKSUM_EXTENTS as usize);
+    let mut buf = vec![0u8; BLAKE3_CHUNK_SIZE];
+
+    loop {
+        let n = match f.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        match &mut hasher {
+            FileHasher::Blake2b(state) => {
+                state.update(&buf[..n]);
+            }
+            FileHasher::Blake3(hasher) => {
+                hasher.update_with_join::<blake3::join::RayonJoin>(&buf[..n]);
+            }
+        }
+
+        // the chunk may span the end of one or more extents
+        let mut chunk = &buf[..n];
+
+        while !chunk.is_empty() {
+            let take = std::cmp::min(chunk.len() as u64, extent_remaining) as usize;
+
+            extent_hasher.update(&chunk[..take]);
+            extent_remaining -= take as u64;
+            chunk = &chunk[take..];
+
+            if extent_remaining == 0 {
+                extent_checksums.push(*extent_hasher.finalize().as_bytes());
+                extent_hasher = blake3::Hasher::new();
+                extent_remaining = size;
+            }
+        }
+    }
+
+    // the last extent may be shorter
+    if extent_remaining < size {
+        extent_checksums.push(*extent_hasher.finalize().as_bytes());
+    }
+
+    let checksum = match hasher {
+        FileHasher::Blake2b(state) => state.finalize().as_bytes().to_vec(),
+        FileHasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+    };
+
+    Ok(ReplicaChecksums {
+        checksum,
+        algo,
+        extent_checksums,
+        len,
+        duration: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_calculate_checksum_with() {
+        let data = vec![7u8; 3 * 1024];
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+
+        let blake2b = calculate_checksum_with(file.path(), ChecksumAlgo::Blake2b).unwrap();
+        let blake3 = calculate_checksum_with(file.path(), ChecksumAlgo::Blake3).unwrap();
+
+        assert_eq!(
+            blake2b_simd::blake2bp::blake2bp(&data).as_bytes(),
+            blake2b.as_slice()
+        );
+        assert_eq!(blake3::hash(&data).as_bytes(), blake3.as_slice());
+        assert_ne!(blake2b, blake3);
+    }
+
+    #[test]
+    fn test_calculate_replica_checksums() {
+        let data: Vec<u8> = (0..1000u32).map(|n| n as u8).collect();
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+
+        for &algo in &[ChecksumAlgo::Blake2b, ChecksumAlgo::Blake3] {
+            let checksums = calculate_replica_checksums(file.path(), algo).unwrap();
+
+            assert_eq!(
+                calculate_checksum_with(file.path(), algo).unwrap(),
+                checksums.checksum
+            );
+            assert_eq!(
+                crate::helpers::calculate_extent_checksums(file.path()).unwrap(),
+                checksums.extent_checksums
+            );
+            assert_eq!(1000, checksums.len);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use crate::error::SectorBuilderErr;
+use crate::metadata::SealFailure;
+
+// errno reported by the operating system when it's out of memory
+const ENOMEM: i32 = 12;
+
+// Derives the reason for which sealing into the provided sealed sector-file
+// failed from the type of the error produced while sealing. Errors of types
+// which aren't recognized are attributed to the proofs.
+pub fn classify_seal_failure<P: AsRef<Path>>(
+    err: &failure::Error,
+    sealed_sector_path: P,
+) -> SealFailure {
+    if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+        if io_err.raw_os_error() == Some(ENOMEM) {
+            return SealFailure::OutOfMemory;
+        }
+
+        return SealFailure::Io {
+            path: sealed_sector_path.as_ref().to_path_buf(),
+            errno: io_err.raw_os_error(),
+            msg: format!("{}", io_err),
+        };
+    }
+
+    match err.downcast_ref::<SectorBuilderErr>() {
+        Some(SectorBuilderErr::InvalidReplica(msg)) => {
+            return SealFailure::ReplicaMismatch(msg.clone());
+        }
+        Some(SectorBuilderErr::TaskTimedOut { .. }) => return SealFailure::TimedOut,
+        Some(SectorBuilderErr::TaskCancelled(_)) => return SealFailure::Cancelled,
+        Some(SectorBuilderErr::StagedDataCorrupted { piece_key, .. }) => {
+            return SealFailure::StagedDataCorrupted(piece_key.clone());
+        }
+        Some(SectorBuilderErr::WorkerPanicked { msg, .. }) => {
+            return SealFailure::WorkerCrashed(msg.clone());
+        }
+        Some(SectorBuilderErr::ParamsMissing(msg)) => {
+            return SealFailure::ParamsMissing(msg.clone());
+        }
+        _ => (),
+    }
+
+    SealFailure::ProofGeneration(format!("{}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::PathBuf;
+
+    use std::time::Duration;
+
+    use crate::error::{
+        err_invalid_replica, err_params_missing, err_task_timed_out, err_worker_panicked,
+    };
+    use crate::watchdog::TaskKind;
+
+    use super::*;
+
+    #[test]
+    fn test_classify_seal_failure() {
+        let path = PathBuf::from("/sealed/s-1");
+
+        let err = failure::Error::from(io::Error::from_raw_os_error(28));
+        match classify_seal_failure(&err, &path) {
+            SealFailure::Io { path: p, errno, .. } => {
+                assert_eq!(path, p);
+                assert_eq!(Some(28), errno);
+            }
+            other => panic!("unexpected failure: {:?}", other),
+        }
+
+        let err = failure::Error::from(io::Error::from_raw_os_error(ENOMEM));
+        assert_eq!(SealFailure::OutOfMemory, classify_seal_failure(&err, &path));
+
+        let err = failure::Error::from(err_invalid_replica("comm_r mismatch"));
+        assert_eq!(
+            SealFailure::ReplicaMismatch("comm_r mismatch".to_string()),
+            classify_seal_failure(&err, &path)
+        );
+
+        let err = failure::Error::from(err_task_timed_out(TaskKind::Seal, Duration::from_secs(1)));
+        assert_eq!(SealFailure::TimedOut, classify_seal_failure(&err, &path));
+
+        let err = failure::Error::from(err_worker_panicked(0, TaskKind::Seal, "oops"));
+        assert_eq!(
+            SealFailure::WorkerCrashed("oops".to_string()),
+            classify_seal_failure(&err, &path)
+        );
+
+        let err = failure::Error::from(err_params_missing("missing Groth parameters"));
+        assert_eq!(
+            SealFailure::ParamsMissing("missing Groth parameters".to_string()),
+            classify_seal_failure(&err, &path)
+        );
+
+        // messages aren't inspected, whatever they mention
+        for msg in &[
+            "synthesis error",
+            "No cached parameters found",
+            "out of memory",
+        ] {
+            let err = format_err!("{}", msg);
+            assert_eq!(
+                SealFailure::ProofGeneration(msg.to_string()),
+                classify_seal_failure(&err, &path)
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use storage_proofs::sector::SectorId;
+
+// number of extents into which a sealed sector-file is split, each of which is
+// checksummed on its own so that quick health checks can verify a sample of
+// them instead of the whole file
+pub const NUM_CHECKSUM_EXTENTS: u64 = 64;
+
+// Number of bytes in each extent of a sealed sector-file with the provided
+// length. The last extent may be shorter.
+pub fn extent_size(len: u64) -> u64 {
+    std::cmp::max(1, (len + NUM_CHECKSUM_EXTENTS - 1) / NUM_CHECKSUM_EXTENTS)
+}
+
+// Calculates the BLAKE3 checksum of each extent of the sealed sector-file at
+// the provided path.
+pub fn calculate_extent_checksums<P: AsRef<Path>>(path: P) -> io::Result<Vec<[u8; 32]>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let size = extent_size(len);
+
+    (0..(len + size - 1) / size)
+        .map(|index| calculate_extent_checksum(&mut file, index, size))
+        .collect()
+}
+
+// Calculates the BLAKE3 checksum of the extent with the provided index.
+pub fn calculate_extent_checksum(
+    file: &mut File,
+    index: u64,
+    extent_size: u64,
+) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+
+    file.seek(SeekFrom::Start(index * extent_size))?;
+    io::copy(&mut file.take(extent_size), &mut hasher)?;
+
+    Ok(*hasher.finalize().as_bytes())
+}
+
+// Picks the indices of the extents of the provided sector which a quick
+// health check verifies. The same seed always picks the same extents, while
+// different seeds (e.g. one per check) eventually cover the whole file.
+pub fn sample_extents(
+    sector_id: SectorId,
+    seed: u64,
+    num_extents: usize,
+    num_samples: usize,
+) -> Vec<usize> {
+    if num_extents == 0 {
+        return vec![];
+    }
+
+    (0..num_samples as u64)
+        .map(|n| {
+            let mut input = Vec::with_capacity(24);
+            input.write_u64::<LittleEndian>(seed).unwrap();
+            input
+                .write_u64::<LittleEndian>(u64::from(sector_id))
+                .unwrap();
+            input.write_u64::<LittleEndian>(n).unwrap();
+
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&blake3::hash(&input).as_bytes()[..8]);
+
+            (u64::from_le_bytes(bytes) % num_extents as u64) as usize
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_extent_checksums() {
+        let data: Vec<u8> = (0..1000u32).map(|n| n as u8).collect();
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+
+        let checksums = calculate_extent_checksums(file.path()).unwrap();
+
+        let size = extent_size(1000) as usize;
+        assert_eq!(16, size);
+        assert_eq!(63, checksums.len());
+
+        for (index, checksum) in checksums.iter().enumerate() {
+            let end = std::cmp::min(data.len(), (index + 1) * size);
+            assert_eq!(blake3::hash(&data[index * size..end]).as_bytes(), checksum);
+        }
+    }
+
+    #[test]
+    fn test_sample_extents() {
+        let a = sample_extents(SectorId::from(1), 42, 64, 4);
+        let b = sample_extents(SectorId::from(1), 42, 64, 4);
+        let c = sample_extents(SectorId::from(1), 43, 64, 4);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.iter().all(|&index| index < 64));
+        assert!(sample_extents(SectorId::from(1), 42, 0, 4).is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::Read;
+
+use filecoin_proofs::types::UnpaddedBytesAmount;
+use rayon::prelude::*;
+
+use crate::error::*;
+
+// Computes the piece commitment (comm_p) of each of the provided pieces,
+// using at most max_num_threads threads. Commitments are produced in the
+// order of the pieces. Fails if any of the commitments can't be computed.
+pub fn generate_piece_commitments<R: Read + Send>(
+    pieces: Vec<(R, UnpaddedBytesAmount)>,
+    max_num_threads: usize,
+) -> Result<Vec<[u8; 32]>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(max_num_threads)
+        .build()
+        .map_err(|err| err_unrecov(format!("could not build thread pool: {}", err)))?;
+
+    pool.install(|| {
+        pieces
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, (piece, piece_bytes))| {
+                filecoin_proofs::generate_piece_commitment(piece, piece_bytes).map_err(|err| {
+                    format_err!("could not generate commitment of piece {}: {}", index, err)
+                })
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_generate_piece_commitments() {
+        let pieces: Vec<Vec<u8>> = (0..8).map(|n| vec![n; 127 * (n as usize + 1)]).collect();
+
+        let expected: Vec<[u8; 32]> = pieces
+            .iter()
+            .map(|piece| {
+                filecoin_proofs::generate_piece_commitment(
+                    Cursor::new(piece),
+                    UnpaddedBytesAmount(piece.len() as u64),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        let comm_ps = generate_piece_commitments(
+            pieces
+                .iter()
+                .map(|piece| (Cursor::new(piece), UnpaddedBytesAmount(piece.len() as u64)))
+                .collect(),
+            3,
+        )
+        .expect("failed to generate piece commitments");
+
+        assert_eq!(expected, comm_ps);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::metadata::{SealedSectorMetadata, SecondsSinceEpoch};
+use crate::state::SealedState;
+
+// Returns the sealed sectors, ordered by id, none of whose pieces must be
+// stored any longer, i.e. sectors whose every piece is to be stored until a
+// time before now. Pieces which were added without a time until which they're
+// to be stored are never considered expired, and neither are sectors without
+// pieces.
+pub fn get_expired_sectors(
+    sealed_state: &SealedState,
+    now: &SecondsSinceEpoch,
+) -> Vec<SealedSectorMetadata> {
+    let mut expired: Vec<SealedSectorMetadata> = sealed_state
+        .sectors
+        .values()
+        .filter(|x| {
+            !x.pieces.is_empty()
+                && x.pieces.iter().all(|p| match &p.store_until {
+                    Some(store_until) => store_until.0 < now.0,
+                    None => false,
+                })
+        })
+        .cloned()
+        .collect();
+
+    expired.sort_unstable_by_key(|x| x.sector_id);
+
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_proofs::types::UnpaddedBytesAmount;
+    use storage_proofs::sector::SectorId;
+
+    use crate::metadata::PieceMetadata;
+
+    fn make_meta(sector_id: u64, store_until: &[Option<u64>]) -> SealedSectorMetadata {
+        SealedSectorMetadata {
+            sector_id: SectorId::from(sector_id),
+            pieces: store_until
+                .iter()
+                .map(|store_until| PieceMetadata {
+                    piece_key: format!("{}", sector_id),
+                    num_bytes: UnpaddedBytesAmount(127),
+                    comm_p: None,
+                    piece_inclusion_proof: None,
+                    idempotency_key: None,
+                    store_until: store_until.map(SecondsSinceEpoch),
+                    deal_id: None,
+                    labels: Default::default(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_returns_sectors_whose_pieces_all_expired() {
+        let mut state: SealedState = Default::default();
+
+        for meta in vec![
+            make_meta(1, &[Some(10), Some(20)]),
+            make_meta(2, &[Some(10), Some(200)]),
+            make_meta(3, &[Some(10), None]),
+            make_meta(4, &[]),
+            make_meta(5, &[Some(99)]),
+            make_meta(6, &[Some(100)]),
+        ] {
+            state.sectors.insert(meta.sector_id, meta);
+        }
+
+        let expired: Vec<SectorId> = get_expired_sectors(&state, &SecondsSinceEpoch(100))
+            .into_iter()
+            .map(|x| x.sector_id)
+            .collect();
+
+        assert_eq!(vec![SectorId::from(1), SectorId::from(5)], expired);
+    }
+}
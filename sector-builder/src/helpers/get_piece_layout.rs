@@ -0,0 +1,114 @@
+use filecoin_proofs::pieces::get_piece_start_byte;
+use filecoin_proofs::types::{PaddedBytesAmount, UnpaddedBytesAmount};
+use storage_proofs::sector::SectorId;
+
+use crate::metadata::{PieceLayout, PieceMetadata};
+use crate::state::{SealedState, StagedState};
+use crate::{err_unrecov, error};
+
+// Computes the start byte and length of each of the pieces in the sector with
+// the provided id, in the order in which the pieces were added. Sealed
+// sectors take precedence over the staged sectors from which they were
+// sealed.
+pub fn get_piece_layout(
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+) -> error::Result<Vec<PieceLayout>> {
+    let pieces = sealed_state
+        .sectors
+        .get(&sector_id)
+        .map(|sector| &sector.pieces)
+        .or_else(|| staged_state.sectors.get(&sector_id).map(|s| &s.pieces))
+        .ok_or_else(|| err_unrecov(format!("no sector with id {} found", sector_id)))?;
+
+    Ok(piece_layouts(pieces))
+}
+
+// Computes the start byte and length of each of the provided pieces of a
+// sector, which are in the order in which they were added.
+pub fn piece_layouts(pieces: &[PieceMetadata]) -> Vec<PieceLayout> {
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(index, piece)| layout_of(&pieces[..index], piece))
+        .collect()
+}
+
+pub(crate) fn layout_of(preceding: &[PieceMetadata], piece: &PieceMetadata) -> PieceLayout {
+    let piece_lengths: Vec<_> = preceding.iter().map(|p| p.num_bytes).collect();
+    let start = get_piece_start_byte(&piece_lengths, piece.num_bytes);
+
+    PieceLayout {
+        piece_key: piece.piece_key.clone(),
+        padded_start_byte: u64::from(PaddedBytesAmount::from(UnpaddedBytesAmount(u64::from(
+            start,
+        )))),
+        padded_len: PaddedBytesAmount::from(piece.num_bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::{SealedSectorMetadata, StagedSectorMetadata};
+
+    use super::*;
+
+    #[test]
+    fn test_get_piece_layout() {
+        let mut staged_state: StagedState = Default::default();
+        let mut sealed_state: SealedState = Default::default();
+
+        staged_state.sectors.insert(
+            SectorId::from(1),
+            StagedSectorMetadata {
+                sector_id: SectorId::from(1),
+                pieces: vec![
+                    PieceMetadata::new("a".to_string(), UnpaddedBytesAmount(127)),
+                    PieceMetadata::new("b".to_string(), UnpaddedBytesAmount(254)),
+                ],
+                ..Default::default()
+            },
+        );
+
+        sealed_state.sectors.insert(
+            SectorId::from(2),
+            SealedSectorMetadata {
+                sector_id: SectorId::from(2),
+                pieces: vec![PieceMetadata::new(
+                    "c".to_string(),
+                    UnpaddedBytesAmount(508),
+                )],
+                ..Default::default()
+            },
+        );
+
+        // the second piece is aligned to its own (padded) length
+        assert_eq!(
+            vec![
+                PieceLayout {
+                    piece_key: "a".to_string(),
+                    padded_start_byte: 0,
+                    padded_len: PaddedBytesAmount(128),
+                },
+                PieceLayout {
+                    piece_key: "b".to_string(),
+                    padded_start_byte: 256,
+                    padded_len: PaddedBytesAmount(256),
+                },
+            ],
+            get_piece_layout(&staged_state, &sealed_state, SectorId::from(1)).unwrap()
+        );
+
+        assert_eq!(
+            vec![PieceLayout {
+                piece_key: "c".to_string(),
+                padded_start_byte: 0,
+                padded_len: PaddedBytesAmount(512),
+            }],
+            get_piece_layout(&staged_state, &sealed_state, SectorId::from(2)).unwrap()
+        );
+
+        assert!(get_piece_layout(&staged_state, &sealed_state, SectorId::from(3)).is_err());
+    }
+}
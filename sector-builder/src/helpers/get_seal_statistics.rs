@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::time::Duration;
+
+use crate::metadata::{SealDurationStats, SealStatistics, SecondsSinceEpoch};
+use crate::state::SealedState;
+
+// Calculates statistics of the durations of the seal tasks of the sealed
+// sectors whose seal timing is known and which finished sealing at or after
+// the provided time, if any.
+pub fn get_seal_statistics(
+    sealed_state: &SealedState,
+    since: Option<&SecondsSinceEpoch>,
+) -> SealStatistics {
+    let mut overall: Vec<Duration> = Vec::new();
+    let mut per_host: BTreeMap<String, Vec<Duration>> = BTreeMap::new();
+
+    let timings = sealed_state
+        .sectors
+        .values()
+        .filter_map(|x| x.seal_timing.as_ref())
+        .filter(|t| since.map_or(true, |since| t.finished_at.0 >= since.0));
+
+    for timing in timings {
+        overall.push(timing.duration);
+        per_host
+            .entry(timing.hostname.clone())
+            .or_insert_with(Vec::new)
+            .push(timing.duration);
+    }
+
+    SealStatistics {
+        overall: duration_stats(overall),
+        per_host: per_host
+            .into_iter()
+            .map(|(hostname, durations)| (hostname, duration_stats(durations)))
+            .collect(),
+    }
+}
+
+// Returns the host name of this machine, or an empty string if it can't be
+// determined.
+pub fn local_hostname() -> String {
+    let mut buf = [0u8; 256];
+
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return String::new();
+    }
+
+    // the name is truncated, without a terminating nul, if it doesn't fit
+    let last = buf.len() - 1;
+    buf[last] = 0;
+
+    unsafe { CStr::from_ptr(buf.as_ptr() as *const libc::c_char) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn duration_stats(mut durations: Vec<Duration>) -> SealDurationStats {
+    if durations.is_empty() {
+        return Default::default();
+    }
+
+    durations.sort_unstable();
+
+    let total: Duration = durations.iter().sum();
+
+    SealDurationStats {
+        num_sectors: durations.len() as u64,
+        min: durations[0],
+        max: durations[durations.len() - 1],
+        mean: total / durations.len() as u32,
+        median: durations[(durations.len() - 1) / 2],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use storage_proofs::sector::SectorId;
+
+    use crate::metadata::{SealTiming, SealedSectorMetadata};
+
+    fn insert(state: &mut SealedState, sector_id: u64, hostname: &str, secs: u64) {
+        state.sectors.insert(
+            SectorId::from(sector_id),
+            SealedSectorMetadata {
+                sector_id: SectorId::from(sector_id),
+                seal_timing: Some(SealTiming {
+                    started_at: SecondsSinceEpoch(sector_id * 1000),
+                    finished_at: SecondsSinceEpoch(sector_id * 1000 + secs),
+                    duration: Duration::from_secs(secs),
+                    checksum_duration: Duration::from_secs(1),
+                    hostname: hostname.to_string(),
+                }),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_seal_statistics() {
+        let mut state: SealedState = Default::default();
+
+        insert(&mut state, 1, "a", 10);
+        insert(&mut state, 2, "a", 30);
+        insert(&mut state, 3, "b", 20);
+
+        // sectors sealed before timings were recorded are left out
+        state.sectors.insert(SectorId::from(4), Default::default());
+
+        let stats = get_seal_statistics(&state, None);
+
+        assert_eq!(3, stats.overall.num_sectors);
+        assert_eq!(Duration::from_secs(10), stats.overall.min);
+        assert_eq!(Duration::from_secs(30), stats.overall.max);
+        assert_eq!(Duration::from_secs(20), stats.overall.mean);
+        assert_eq!(Duration::from_secs(20), stats.overall.median);
+
+        assert_eq!(2, stats.per_host["a"].num_sectors);
+        assert_eq!(Duration::from_secs(20), stats.per_host["a"].mean);
+        assert_eq!(1, stats.per_host["b"].num_sectors);
+
+        // only sectors which finished sealing since the provided time count
+        let stats = get_seal_statistics(&state, Some(&SecondsSinceEpoch(2000)));
+
+        assert_eq!(2, stats.overall.num_sectors);
+        assert_eq!(Duration::from_secs(30), stats.overall.max);
+    }
+}
@@ -3,11 +3,24 @@ use crate::state::{SealedState, StagedState};
 use crate::{err_unrecov, error};
 use storage_proofs::sector::SectorId;
 
+// Returns the seal status of the sector with the provided id. A sealed sector
+// which is being resealed or repaired, or whose reseal or repair failed,
+// reports the status of its staged sector rather than being reported sealed,
+// although its previous replica remains in place.
 pub fn get_seal_status(
     staged_state: &StagedState,
     sealed_state: &SealedState,
     sector_id: SectorId,
 ) -> error::Result<SealStatus> {
+    if let Some(staged_sector) = staged_state.sectors.get(&sector_id) {
+        match staged_sector.seal_status {
+            SealStatus::Sealing | SealStatus::Failed(_) => {
+                return Ok(staged_sector.seal_status.clone())
+            }
+            SealStatus::Pending | SealStatus::Sealed(_) => (),
+        }
+    }
+
     sealed_state
         .sectors
         .get(&sector_id)
@@ -23,10 +36,10 @@ pub fn get_seal_status(
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use im::HashMap;
 
-    use crate::metadata::{SealedSectorMetadata, StagedSectorMetadata};
-    use crate::state::{SealedState, SectorBuilderState, StagedState};
+    use crate::metadata::{SealFailure, SealedSectorMetadata, StagedSectorMetadata};
+    use crate::state::{transition_seal_status, SealedState, SectorBuilderState, StagedState};
 
     use super::*;
 
@@ -62,8 +75,8 @@ mod tests {
 
         SectorBuilderState {
             staged: StagedState {
-                sector_id_nonce: 0,
                 sectors: staged_sectors,
+                ..Default::default()
             },
             sealed: SealedState {
                 sectors: sealed_sectors,
@@ -99,4 +112,39 @@ mod tests {
             _ => panic!("should have been SealStatus::Sealed"),
         }
     }
+
+    #[test]
+    fn test_failed_reseal() {
+        let mut state = setup();
+
+        let sector_id = SectorId::from(4);
+        let sealed_sector = state.sealed.sectors[&sector_id].clone();
+
+        state.staged.sectors.insert(
+            sector_id,
+            StagedSectorMetadata {
+                sector_id,
+                seal_status: SealStatus::Sealed(Box::new(sealed_sector)),
+                ..Default::default()
+            },
+        );
+
+        // the reseal starts, while the previous replica stays in place
+        let staged_sector = state.staged.sectors.get_mut(&sector_id).unwrap();
+        transition_seal_status(staged_sector, SealStatus::Sealing).unwrap();
+
+        assert_eq!(
+            SealStatus::Sealing,
+            get_seal_status(&state.staged, &state.sealed, sector_id).unwrap()
+        );
+
+        let staged_sector = state.staged.sectors.get_mut(&sector_id).unwrap();
+        transition_seal_status(staged_sector, SealStatus::Failed(SealFailure::Cancelled)).unwrap();
+
+        assert_eq!(
+            SealStatus::Failed(SealFailure::Cancelled),
+            get_seal_status(&state.staged, &state.sealed, sector_id).unwrap()
+        );
+        assert!(state.sealed.sectors.contains_key(&sector_id));
+    }
 }
@@ -1,10 +1,19 @@
 use crate::helpers;
-use crate::{SealedSectorHealth, SealedSectorMetadata};
+use crate::{HealthCheckMode, SealedSectorHealth, SealedSectorMetadata};
+use std::fs::File;
 use std::path::Path;
 
 pub fn get_sealed_sector_health<T: AsRef<Path>>(
     sealed_sector_path: T,
     meta: &SealedSectorMetadata,
+) -> Result<SealedSectorHealth, failure::Error> {
+    check_sealed_sector_health(sealed_sector_path, meta, HealthCheckMode::Full)
+}
+
+pub fn check_sealed_sector_health<T: AsRef<Path>>(
+    sealed_sector_path: T,
+    meta: &SealedSectorMetadata,
+    mode: HealthCheckMode,
 ) -> Result<SealedSectorHealth, failure::Error> {
     let result = std::fs::metadata(&sealed_sector_path);
 
@@ -18,12 +27,95 @@ pub fn get_sealed_sector_health<T: AsRef<Path>>(
         return Ok(SealedSectorHealth::ErrorInvalidLength);
     }
 
-    // compare checksums
-    if helpers::checksum::calculate_checksum(&sealed_sector_path)?.as_bytes()
-        != meta.blake2b_checksum.as_slice()
-    {
-        return Ok(SealedSectorHealth::ErrorInvalidChecksum);
+    match mode {
+        HealthCheckMode::Quick { seed, num_samples } if !meta.extent_checksums.is_empty() => {
+            // compare checksums of the sampled extents
+            let mut file = File::open(&sealed_sector_path)?;
+            let extent_size = helpers::extent_size(meta.len);
+
+            for index in helpers::sample_extents(
+                meta.sector_id,
+                seed,
+                meta.extent_checksums.len(),
+                num_samples,
+            ) {
+                if helpers::calculate_extent_checksum(&mut file, index as u64, extent_size)?
+                    != meta.extent_checksums[index]
+                {
+                    return Ok(SealedSectorHealth::ErrorInvalidChecksum);
+                }
+            }
+        }
+        _ => {
+            // compare checksums
+            if helpers::checksum::calculate_checksum_with(&sealed_sector_path, meta.checksum_algo)?
+                != meta.blake2b_checksum
+            {
+                return Ok(SealedSectorHealth::ErrorInvalidChecksum);
+            }
+        }
     }
 
     Ok(SealedSectorHealth::Ok)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Seek, SeekFrom, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_quick_health_check() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&[7; 4096]).unwrap();
+
+        let meta = SealedSectorMetadata {
+            blake2b_checksum: helpers::calculate_checksum(file.path())
+                .unwrap()
+                .as_ref()
+                .to_vec(),
+            extent_checksums: helpers::calculate_extent_checksums(file.path()).unwrap(),
+            len: 4096,
+            ..Default::default()
+        };
+
+        // sample every extent, so that a corrupted one is sure to be found
+        let quick = HealthCheckMode::Quick {
+            seed: 0,
+            num_samples: 1024,
+        };
+
+        assert_eq!(
+            SealedSectorHealth::Ok,
+            check_sealed_sector_health(file.path(), &meta, quick).unwrap()
+        );
+
+        file.seek(SeekFrom::Start(2048)).unwrap();
+        file.write_all(&[8]).unwrap();
+
+        assert_eq!(
+            SealedSectorHealth::ErrorInvalidChecksum,
+            check_sealed_sector_health(file.path(), &meta, quick).unwrap()
+        );
+
+        // sectors without extent checksums are checked in full
+        let legacy = SealedSectorMetadata {
+            extent_checksums: vec![],
+            ..meta.clone()
+        };
+
+        assert_eq!(
+            SealedSectorHealth::ErrorInvalidChecksum,
+            check_sealed_sector_health(file.path(), &legacy, quick).unwrap()
+        );
+
+        // lengths are compared before any extent is hashed
+        let truncated = SealedSectorMetadata { len: 1, ..meta };
+
+        assert_eq!(
+            SealedSectorHealth::ErrorInvalidLength,
+            check_sealed_sector_health(file.path(), &truncated, quick).unwrap()
+        );
+    }
+}
@@ -1,17 +1,27 @@
 use std::cmp::Reverse;
+use std::time::Duration;
 
 use filecoin_proofs::pieces::sum_piece_bytes_with_alignment;
 use filecoin_proofs::types::UnpaddedBytesAmount;
 use itertools::chain;
 
-use crate::metadata::{SealStatus, StagedSectorMetadata};
+use crate::metadata::{AutoSealPolicy, SealStatus, SecondsSinceEpoch, StagedSectorMetadata};
 use crate::state::StagedState;
 use storage_proofs::sector::SectorId;
 
+// Determines which pending staged sectors are to be sealed: those which are
+// full or which, according to the policy, are full enough, have been staged
+// for long enough or hold a piece whose deadline is approaching, followed by
+// the oldest of the remaining sectors in excess of the maximum number of
+// staged sectors. Only open (pending) sectors count towards that maximum:
+// sectors which are being sealed don't keep pieces from being packed into a
+// fresh sector.
 pub fn get_sectors_ready_for_sealing(
     staged_state: &StagedState,
     max_user_bytes_per_staged_sector: UnpaddedBytesAmount,
-    max_num_staged_sectors: u8,
+    max_num_staged_sectors: u64,
+    policy: &AutoSealPolicy,
+    now: &SecondsSinceEpoch,
     seal_all_staged_sectors: bool,
 ) -> Vec<SectorId> {
     let (full, mut not_full): (Vec<&StagedSectorMetadata>, Vec<&StagedSectorMetadata>) =
@@ -21,7 +31,22 @@ pub fn get_sectors_ready_for_sealing(
             .filter(|x| x.seal_status == SealStatus::Pending)
             .partition(|x| {
                 let pieces: Vec<_> = x.pieces.iter().map(|p| p.num_bytes).collect();
-                max_user_bytes_per_staged_sector <= sum_piece_bytes_with_alignment(&pieces)
+                let num_bytes = sum_piece_bytes_with_alignment(&pieces);
+
+                if max_user_bytes_per_staged_sector <= num_bytes {
+                    return true;
+                }
+
+                if x.pieces.is_empty() {
+                    return false;
+                }
+
+                let filled = policy.fill_ratio.map_or(false, |ratio| {
+                    u64::from(num_bytes) as f64
+                        >= ratio * u64::from(max_user_bytes_per_staged_sector) as f64
+                });
+
+                filled || is_expired(x, policy, now)
             });
 
     not_full.sort_unstable_by_key(|x| Reverse(x.sector_id));
@@ -37,14 +62,78 @@ pub fn get_sectors_ready_for_sealing(
         .collect::<Vec<SectorId>>()
 }
 
+// Returns how long it takes until the first of the provided staged sectors
+// must be sealed according to the policy regardless of how full it is, or
+// None if no sector is subject to a maximum age or a deadline.
+pub fn time_until_sectors_expire(
+    staged_state: &StagedState,
+    policy: &AutoSealPolicy,
+    now: &SecondsSinceEpoch,
+) -> Option<Duration> {
+    staged_state
+        .sectors
+        .values()
+        .filter(|x| x.seal_status == SealStatus::Pending && !x.pieces.is_empty())
+        .filter_map(|x| expires_at(x, policy))
+        .map(|expires_at| Duration::from_secs(expires_at.saturating_sub(now.0)))
+        .min()
+}
+
+// Returns the earliest deadline of the pieces in the provided sector if it's
+// within the policy's deadline margin, i.e. if the sector must be sealed now
+// for the deadline to be met.
+pub fn deadline_at_risk(
+    sector: &StagedSectorMetadata,
+    policy: &AutoSealPolicy,
+    now: &SecondsSinceEpoch,
+) -> Option<SecondsSinceEpoch> {
+    let margin = policy.deadline_margin?;
+
+    earliest_deadline(sector).filter(|deadline| deadline.0 <= now.0 + margin.as_secs())
+}
+
+fn is_expired(
+    sector: &StagedSectorMetadata,
+    policy: &AutoSealPolicy,
+    now: &SecondsSinceEpoch,
+) -> bool {
+    expires_at(sector, policy).map_or(false, |expires_at| expires_at <= now.0)
+}
+
+// Returns the time, in seconds since the epoch, at which the sector must be
+// sealed regardless of how full it is: once it has been staged for the
+// maximum age, or once the earliest deadline of its pieces is within the
+// deadline margin, whichever comes first.
+fn expires_at(sector: &StagedSectorMetadata, policy: &AutoSealPolicy) -> Option<u64> {
+    let by_age = match (policy.max_age, &sector.created_at) {
+        (Some(max_age), Some(created_at)) => Some(created_at.0 + max_age.as_secs()),
+        _ => None,
+    };
+
+    let by_deadline = match (policy.deadline_margin, earliest_deadline(sector)) {
+        (Some(margin), Some(deadline)) => Some(deadline.0.saturating_sub(margin.as_secs())),
+        _ => None,
+    };
+
+    by_age.into_iter().chain(by_deadline).min()
+}
+
+fn earliest_deadline(sector: &StagedSectorMetadata) -> Option<SecondsSinceEpoch> {
+    sector
+        .pieces
+        .iter()
+        .filter_map(|p| p.store_until.clone())
+        .min_by_key(|store_until| store_until.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use std::collections::HashMap;
+    use im::HashMap;
 
     use crate::metadata::{PieceMetadata, StagedSectorMetadata};
-    use crate::state::StagedState;
+    use crate::state::{SealState, StagedState};
     use storage_proofs::sector::SectorId;
 
     fn make_meta(
@@ -69,6 +158,10 @@ mod tests {
                         num_bytes: UnpaddedBytesAmount(num_bytes),
                         comm_p: None,
                         piece_inclusion_proof: None,
+                        idempotency_key: None,
+                        store_until: None,
+                        deal_id: None,
+                        labels: Default::default(),
                     }]
                 } else {
                     vec![]
@@ -91,10 +184,16 @@ mod tests {
             sectors: m,
         };
 
-        let to_seal: Vec<SectorId> =
-            get_sectors_ready_for_sealing(&state, UnpaddedBytesAmount(127), 10, true)
-                .into_iter()
-                .collect();
+        let to_seal: Vec<SectorId> = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(127),
+            10,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            true,
+        )
+        .into_iter()
+        .collect();
 
         assert_eq!(vec![SectorId::from(201), SectorId::from(200)], to_seal);
     }
@@ -111,10 +210,16 @@ mod tests {
             sectors: m,
         };
 
-        let to_seal: Vec<SectorId> =
-            get_sectors_ready_for_sealing(&state, UnpaddedBytesAmount(127), 10, false)
-                .into_iter()
-                .collect();
+        let to_seal: Vec<SectorId> = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(127),
+            10,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            false,
+        )
+        .into_iter()
+        .collect();
 
         assert_eq!(vec![SectorId::from(200)], to_seal);
     }
@@ -133,10 +238,16 @@ mod tests {
             sectors: m,
         };
 
-        let to_seal: Vec<SectorId> =
-            get_sectors_ready_for_sealing(&state, UnpaddedBytesAmount(127), 2, false)
-                .into_iter()
-                .collect();
+        let to_seal: Vec<SectorId> = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(127),
+            2,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            false,
+        )
+        .into_iter()
+        .collect();
 
         assert_eq!(vec![SectorId::from(201), SectorId::from(200)], to_seal);
     }
@@ -155,10 +266,16 @@ mod tests {
             sectors: m,
         };
 
-        let to_seal: Vec<SectorId> =
-            get_sectors_ready_for_sealing(&state, UnpaddedBytesAmount(127), 4, false)
-                .into_iter()
-                .collect();
+        let to_seal: Vec<SectorId> = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(127),
+            4,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            false,
+        )
+        .into_iter()
+        .collect();
 
         assert_eq!(vec![SectorId::from(0); 0], to_seal);
     }
@@ -177,11 +294,180 @@ mod tests {
             sectors: m,
         };
 
-        let to_seal: Vec<SectorId> =
-            get_sectors_ready_for_sealing(&state, UnpaddedBytesAmount(127), 4, false)
-                .into_iter()
-                .collect();
+        let to_seal: Vec<SectorId> = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(127),
+            4,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            false,
+        )
+        .into_iter()
+        .collect();
 
         assert_eq!(vec![SectorId::from(0); 0], to_seal);
     }
+
+    #[test]
+    fn test_sectors_being_sealed_leave_room_for_an_open_sector() {
+        let mut m: HashMap<SectorId, StagedSectorMetadata> = HashMap::new();
+
+        make_meta(&mut m, SectorId::from(200), 127, false);
+        make_meta(&mut m, SectorId::from(201), 0, true);
+
+        let state = StagedState {
+            sector_id_nonce: 100,
+            sectors: m,
+        };
+
+        let to_seal: Vec<SectorId> = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(127),
+            1,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            false,
+        )
+        .into_iter()
+        .collect();
+
+        assert_eq!(vec![SectorId::from(0); 0], to_seal);
+        assert_eq!(1, state.num_sectors_in(SealState::Pending));
+        assert_eq!(1, state.num_sectors_in(SealState::Sealing));
+    }
+
+    #[test]
+    fn test_seals_filled_beyond_ratio() {
+        let mut m: HashMap<SectorId, StagedSectorMetadata> = HashMap::new();
+
+        make_meta(&mut m, SectorId::from(200), 508, true);
+        make_meta(&mut m, SectorId::from(201), 254, true);
+        make_meta(&mut m, SectorId::from(202), 0, true);
+
+        let state = StagedState {
+            sector_id_nonce: 100,
+            sectors: m,
+        };
+
+        let policy = AutoSealPolicy {
+            fill_ratio: Some(0.5),
+            max_age: None,
+            deadline_margin: None,
+        };
+
+        let to_seal = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(1016),
+            10,
+            &policy,
+            &SecondsSinceEpoch(0),
+            false,
+        );
+
+        assert_eq!(vec![SectorId::from(200)], to_seal);
+    }
+
+    #[test]
+    fn test_seals_expired() {
+        let mut m: HashMap<SectorId, StagedSectorMetadata> = HashMap::new();
+
+        make_meta(&mut m, SectorId::from(200), 127, true);
+        make_meta(&mut m, SectorId::from(201), 127, true);
+        make_meta(&mut m, SectorId::from(202), 127, true);
+
+        m.get_mut(&SectorId::from(200)).unwrap().created_at = Some(SecondsSinceEpoch(1000));
+        m.get_mut(&SectorId::from(201)).unwrap().created_at = Some(SecondsSinceEpoch(2000));
+
+        let state = StagedState {
+            sector_id_nonce: 100,
+            sectors: m,
+        };
+
+        let policy = AutoSealPolicy {
+            fill_ratio: None,
+            max_age: Some(Duration::from_secs(3600)),
+            deadline_margin: None,
+        };
+
+        let now = SecondsSinceEpoch(4600);
+
+        let to_seal = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(1016),
+            10,
+            &policy,
+            &now,
+            false,
+        );
+
+        // sectors of unknown age never expire
+        assert_eq!(vec![SectorId::from(200)], to_seal);
+
+        assert_eq!(
+            Some(Duration::from_secs(0)),
+            time_until_sectors_expire(&state, &policy, &now)
+        );
+
+        assert_eq!(
+            Some(Duration::from_secs(400)),
+            time_until_sectors_expire(&state, &policy, &SecondsSinceEpoch(4200))
+        );
+
+        assert_eq!(
+            None,
+            time_until_sectors_expire(&state, &Default::default(), &now)
+        );
+    }
+
+    #[test]
+    fn test_seals_approaching_deadline() {
+        let mut m: HashMap<SectorId, StagedSectorMetadata> = HashMap::new();
+
+        make_meta(&mut m, SectorId::from(200), 127, true);
+        make_meta(&mut m, SectorId::from(201), 127, true);
+
+        m.get_mut(&SectorId::from(200)).unwrap().pieces[0].store_until =
+            Some(SecondsSinceEpoch(5000));
+        m.get_mut(&SectorId::from(201)).unwrap().pieces[0].store_until =
+            Some(SecondsSinceEpoch(9000));
+
+        let state = StagedState {
+            sector_id_nonce: 100,
+            sectors: m,
+        };
+
+        let policy = AutoSealPolicy {
+            fill_ratio: None,
+            max_age: None,
+            deadline_margin: Some(Duration::from_secs(1000)),
+        };
+
+        let now = SecondsSinceEpoch(4000);
+
+        let to_seal = get_sectors_ready_for_sealing(
+            &state,
+            UnpaddedBytesAmount(1016),
+            10,
+            &policy,
+            &now,
+            false,
+        );
+
+        assert_eq!(vec![SectorId::from(200)], to_seal);
+
+        assert_eq!(
+            Some(SecondsSinceEpoch(5000)),
+            deadline_at_risk(&state.sectors[&SectorId::from(200)], &policy, &now)
+        );
+
+        assert_eq!(
+            None,
+            deadline_at_risk(&state.sectors[&SectorId::from(201)], &policy, &now)
+        );
+
+        assert_eq!(
+            Some(Duration::from_secs(500)),
+            time_until_sectors_expire(&state, &policy, &SecondsSinceEpoch(3500))
+        );
+    }
 }
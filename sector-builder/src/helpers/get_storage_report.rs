@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::*;
+use crate::helpers::{sealed_sector_path, staged_sector_file};
+use crate::metadata::{DirectoryUsage, SealStatus, StorageReport};
+use crate::state::{SealedState, StagedState};
+use crate::store::SectorStore;
+
+// Aggregates the storage consumed by the provided staged and sealed sectors
+// and the capacity of the directories in which their sector-files live.
+// Sector-files which can't be found don't count towards the byte totals.
+pub fn get_storage_report<S: SectorStore>(
+    sector_store: &S,
+    sealed_stores: &HashMap<String, PathBuf>,
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+) -> Result<StorageReport> {
+    let mut report = StorageReport {
+        num_sealed_sectors: sealed_state.sectors.len() as u64,
+        ..Default::default()
+    };
+
+    for meta in staged_state.sectors.values() {
+        match meta.seal_status {
+            SealStatus::Pending => report.num_pending_sectors += 1,
+            SealStatus::Sealing => report.num_sealing_sectors += 1,
+            SealStatus::Failed(_) => report.num_failed_sectors += 1,
+            SealStatus::Sealed(_) => (),
+        }
+
+        if let Some(path) = staged_sector_file(sector_store, meta) {
+            report.staged_bytes += file_len(path);
+        }
+    }
+
+    for meta in sealed_state.sectors.values() {
+        if let Ok(path) = sealed_sector_path(sector_store, sealed_stores, meta) {
+            report.sealed_bytes += file_len(path);
+        }
+    }
+
+    let mut store_ids: Vec<&String> = sealed_stores.keys().collect();
+    store_ids.sort();
+
+    let dirs = vec![
+        sector_store.manager().staged_sector_dir(),
+        sector_store.manager().sealed_sector_dir(),
+    ]
+    .into_iter()
+    .chain(
+        sector_store
+            .secondary_staged_manager()
+            .map(|mgr| mgr.staged_sector_dir()),
+    )
+    .chain(store_ids.into_iter().map(|id| sealed_stores[id].clone()));
+
+    for dir in dirs {
+        report.directories.push(get_directory_usage(dir)?);
+    }
+
+    Ok(report)
+}
+
+fn get_directory_usage(path: PathBuf) -> Result<DirectoryUsage> {
+    let available_bytes = fs2::available_space(&path)?;
+    let total_bytes = fs2::total_space(&path)?;
+
+    Ok(DirectoryUsage {
+        path,
+        available_bytes,
+        total_bytes,
+    })
+}
+
+fn file_len(path: impl AsRef<Path>) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+    use storage_proofs::sector::SectorId;
+
+    use crate::disk_backed_storage::new_sector_store;
+    use crate::metadata::{
+        SealFailure, SealedSectorLocation, SealedSectorMetadata, StagedSectorMetadata,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_get_storage_report() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let mut sealed_stores: HashMap<String, PathBuf> = Default::default();
+        sealed_stores.insert("cold".to_string(), store_dir.path().to_path_buf());
+
+        let mut staged_state: StagedState = Default::default();
+        let mut sealed_state: SealedState = Default::default();
+
+        for (n, seal_status) in vec![
+            SealStatus::Pending,
+            SealStatus::Sealing,
+            SealStatus::Failed(SealFailure::Cancelled),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let sector_id = SectorId::from(n as u64 + 1);
+            let access = store
+                .manager()
+                .new_staging_sector_access(sector_id)
+                .unwrap();
+
+            fs::write(store.manager().staged_sector_path(&access), &[0; 100]).unwrap();
+
+            staged_state.sectors.insert(
+                sector_id,
+                StagedSectorMetadata {
+                    sector_id,
+                    sector_access: access,
+                    seal_status,
+                    ..Default::default()
+                },
+            );
+        }
+
+        // one sealed sector in the sealed sector directory, one in a store and
+        // one whose sector-file is missing
+        for (n, location) in vec![
+            SealedSectorLocation::Default,
+            SealedSectorLocation::Store("cold".to_string()),
+            SealedSectorLocation::Dir(PathBuf::from("/does/not/exist")),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let sector_id = SectorId::from(n as u64 + 10);
+            let access = store.manager().new_sealed_sector_access(sector_id).unwrap();
+
+            let meta = SealedSectorMetadata {
+                sector_id,
+                sector_access: access,
+                location,
+                ..Default::default()
+            };
+
+            if let Ok(path) = sealed_sector_path(&store, &sealed_stores, &meta) {
+                if path.parent().map(Path::exists).unwrap_or(false) {
+                    fs::write(path, &[0; 256]).unwrap();
+                }
+            }
+
+            sealed_state.sectors.insert(sector_id, meta);
+        }
+
+        let report = get_storage_report(&store, &sealed_stores, &staged_state, &sealed_state)
+            .expect("failed to produce storage report");
+
+        assert_eq!(300, report.staged_bytes);
+        assert_eq!(512, report.sealed_bytes);
+        assert_eq!(1, report.num_pending_sectors);
+        assert_eq!(1, report.num_sealing_sectors);
+        assert_eq!(1, report.num_failed_sectors);
+        assert_eq!(3, report.num_sealed_sectors);
+
+        let dirs: Vec<PathBuf> = report.directories.iter().map(|d| d.path.clone()).collect();
+
+        assert_eq!(
+            vec![
+                staged_dir.path().to_path_buf(),
+                sealed_dir.path().to_path_buf(),
+                store_dir.path().to_path_buf(),
+            ],
+            dirs
+        );
+        assert!(report
+            .directories
+            .iter()
+            .all(|d| d.available_bytes <= d.total_bytes));
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+use crate::helpers::checksum::calculate_checksum_with;
+use crate::helpers::sealed_sector_path;
+use crate::state::SealedState;
+use crate::store::SectorStore;
+use crate::worker::HydrateTaskPrototype;
+
+// The integrity fields of a sealed sector's metadata which were computed
+// from its replica, as only the lacking ones are.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SectorIntegrity {
+    pub len: Option<u64>,
+    pub checksum: Option<Vec<u8>>,
+}
+
+// Plans computing the checksum and length of the replica of the sealed
+// sector with the provided id, if its metadata lacks them (e.g. because it
+// was imported through the FFI from metadata which predates checksums).
+// Without them, health checks report the sector as corrupted. Returns None
+// if the metadata lacks neither.
+pub fn plan_sector_integrity_hydration<S: SectorStore>(
+    sector_store: &S,
+    sealed_stores: &HashMap<String, PathBuf>,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+) -> Result<Option<HydrateTaskPrototype>> {
+    let meta = sealed_state
+        .sectors
+        .get(&sector_id)
+        .ok_or_else(|| err_unrecov(format!("no sealed sector with id {} found", sector_id)))?;
+
+    let lacks_checksum = meta.blake2b_checksum.is_empty();
+    let lacks_len = meta.len == 0;
+
+    if !lacks_checksum && !lacks_len {
+        return Ok(None);
+    }
+
+    Ok(Some(HydrateTaskPrototype {
+        sector_id,
+        replica_path: sealed_sector_path(sector_store, sealed_stores, meta)?,
+        checksum_algo: meta.checksum_algo,
+        lacks_len,
+        lacks_checksum,
+    }))
+}
+
+// Computes the integrity fields which the planned hydration found lacking.
+// The checksum is calculated with the sector's checksum algorithm, which
+// reads the whole replica.
+pub fn compute_sector_integrity(proto: &HydrateTaskPrototype) -> Result<SectorIntegrity> {
+    let mut integrity: SectorIntegrity = Default::default();
+
+    if proto.lacks_len {
+        integrity.len = Some(fs::metadata(&proto.replica_path)?.len());
+    }
+
+    if proto.lacks_checksum {
+        integrity.checksum = Some(calculate_checksum_with(
+            &proto.replica_path,
+            proto.checksum_algo,
+        )?);
+    }
+
+    Ok(integrity)
+}
+
+// Records the computed integrity fields in the metadata of the sealed sector
+// with the provided id. Returns whether the metadata was changed. Fields
+// which are present already are left alone, so that corruption of the
+// replica isn't papered over.
+pub fn apply_sector_integrity(
+    sealed_state: &mut SealedState,
+    sector_id: SectorId,
+    integrity: SectorIntegrity,
+) -> Result<bool> {
+    let meta = sealed_state
+        .sectors
+        .get_mut(&sector_id)
+        .ok_or_else(|| err_unrecov(format!("no sealed sector with id {} found", sector_id)))?;
+
+    let mut changed = false;
+
+    if let Some(len) = integrity.len.filter(|_| meta.len == 0) {
+        meta.len = len;
+        changed = true;
+    }
+
+    if let Some(checksum) = integrity
+        .checksum
+        .filter(|_| meta.blake2b_checksum.is_empty())
+    {
+        meta.blake2b_checksum = checksum;
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+
+    use crate::disk_backed_storage::new_sector_store;
+    use crate::helpers::get_sealed_sector_health;
+    use crate::metadata::{SealedSectorHealth, SealedSectorMetadata};
+
+    use super::*;
+
+    #[test]
+    fn test_hydrate_sector_integrity() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let sealed_stores = HashMap::new();
+        let sector_id = SectorId::from(5);
+
+        let sector_access = store.manager().new_sealed_sector_access(sector_id).unwrap();
+        let path = store.manager().sealed_sector_path(&sector_access);
+
+        fs::write(&path, &[9; 128]).unwrap();
+
+        let mut sealed_state: SealedState = Default::default();
+
+        sealed_state.sectors.insert(
+            sector_id,
+            SealedSectorMetadata {
+                sector_id,
+                sector_access,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            SealedSectorHealth::ErrorInvalidLength,
+            get_sealed_sector_health(&path, &sealed_state.sectors[&sector_id]).unwrap()
+        );
+
+        let proto =
+            plan_sector_integrity_hydration(&store, &sealed_stores, &sealed_state, sector_id)
+                .unwrap()
+                .unwrap();
+
+        let integrity = compute_sector_integrity(&proto).unwrap();
+
+        assert!(apply_sector_integrity(&mut sealed_state, sector_id, integrity.clone()).unwrap());
+
+        let meta = &sealed_state.sectors[&sector_id];
+        assert_eq!(128, meta.len);
+        assert_eq!(
+            SealedSectorHealth::Ok,
+            get_sealed_sector_health(&path, meta).unwrap()
+        );
+
+        // a sector with integrity fields is left alone, even if its replica
+        // doesn't match them anymore
+        fs::write(&path, &[1; 128]).unwrap();
+
+        assert!(
+            plan_sector_integrity_hydration(&store, &sealed_stores, &sealed_state, sector_id)
+                .unwrap()
+                .is_none()
+        );
+        assert!(!apply_sector_integrity(&mut sealed_state, sector_id, integrity).unwrap());
+        assert_eq!(
+            SealedSectorHealth::ErrorInvalidChecksum,
+            get_sealed_sector_health(&path, &sealed_state.sectors[&sector_id]).unwrap()
+        );
+
+        assert!(plan_sector_integrity_hydration(
+            &store,
+            &sealed_stores,
+            &sealed_state,
+            SectorId::from(6)
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use filecoin_proofs::types::PoRepConfig;
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+use crate::helpers::get_sealed_sector_health;
+use crate::metadata::{SealedSectorHealth, SealedSectorLocation, SealedSectorMetadata};
+use crate::sector_access::SectorAccess;
+use crate::state::{SealedState, StagedState};
+use crate::store::SectorStore;
+
+// Importing a sealed sector takes three steps. The scheduler provisions the
+// sealed sector access into which the replica is copied, a worker validates
+// the replica and copies it (which reads the whole replica) and the
+// scheduler registers the sector once the copy is done.
+
+// Checks that no sector with the provided id exists and provisions the
+// sealed sector access into which its replica is copied. Not a pure
+// function; creates a sealed sector access (likely a file).
+pub fn provision_sealed_sector_import<S: SectorStore>(
+    sector_store: &S,
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+) -> Result<SectorAccess> {
+    ensure_sector_is_new(staged_state, sealed_state, sector_id)?;
+
+    sector_store.manager().new_sealed_sector_access(sector_id)
+}
+
+// Validates the replica at the provided path against its metadata and copies
+// it to the provisioned sealed sector path. If linking is ok, the replica is
+// hard-linked instead, falling back to a copy if it can't be (e.g. because
+// it's on another filesystem).
+pub fn copy_imported_replica(
+    replica_path: impl AsRef<Path>,
+    sector_path: impl AsRef<Path>,
+    meta: &SealedSectorMetadata,
+    link_ok: bool,
+) -> Result<()> {
+    match get_sealed_sector_health(&replica_path, meta)? {
+        SealedSectorHealth::Ok => (),
+        health => return Err(err_invalid_replica(format!("{:?}", health)).into()),
+    }
+
+    if let Err(err) = link_or_copy(&replica_path, &sector_path, link_ok) {
+        let _ = fs::remove_file(&sector_path);
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+// Registers the sector whose replica has been copied to the provided sealed
+// sector access. Not a pure function; advances the sector id nonce past the
+// imported sector's id (so that newly-provisioned staged sectors don't
+// collide with it) and mutates the SealedState. Fails if a sector with the
+// same id has been created while the replica was copied.
+pub fn register_imported_sector(
+    staged_state: &mut StagedState,
+    sealed_state: &mut SealedState,
+    sector_access: SectorAccess,
+    meta: SealedSectorMetadata,
+) -> Result<()> {
+    let sector_id = meta.sector_id;
+
+    ensure_sector_is_new(staged_state, sealed_state, sector_id)?;
+
+    let n = &mut staged_state.sector_id_nonce;
+    *n = std::cmp::max(*n, u64::from(sector_id));
+
+    sealed_state.sectors.insert(
+        sector_id,
+        SealedSectorMetadata {
+            sector_access,
+            location: SealedSectorLocation::Default,
+            ..meta
+        },
+    );
+
+    Ok(())
+}
+
+fn ensure_sector_is_new(
+    staged_state: &StagedState,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+) -> Result<()> {
+    if staged_state.sectors.contains_key(&sector_id)
+        || sealed_state.sectors.contains_key(&sector_id)
+    {
+        return Err(err_sector_exists(sector_id).into());
+    }
+
+    Ok(())
+}
+
+// Verifies the seal proof of a sealed sector which is about to be imported
+// against its commitments. Verification takes a while, so it's done before
+// the sector is handed to the scheduler rather than on its thread.
+pub fn verify_imported_seal(
+    porep_config: PoRepConfig,
+    prover_id: &[u8; 31],
+    meta: &SealedSectorMetadata,
+) -> Result<()> {
+    let is_valid = filecoin_proofs::verify_seal(
+        porep_config,
+        meta.comm_r,
+        meta.comm_d,
+        meta.comm_r_star,
+        prover_id,
+        meta.sector_id,
+        &meta.proof,
+    )?;
+
+    if !is_valid {
+        return Err(err_invalid_replica("seal proof is not valid").into());
+    }
+
+    Ok(())
+}
+
+// Hard-links the file at from to to, if linking is ok, or copies it. The
+// file which exists at to (the newly-provisioned access) is replaced.
+fn link_or_copy(from: impl AsRef<Path>, to: impl AsRef<Path>, link_ok: bool) -> io::Result<()> {
+    if link_ok {
+        fs::remove_file(&to)?;
+
+        match fs::hard_link(&from, &to) {
+            Ok(()) => return Ok(()),
+            Err(err) => warn!(
+                "could not link {:?} to {:?}, copying it instead: {}",
+                from.as_ref(),
+                to.as_ref(),
+                err
+            ),
+        }
+    }
+
+    fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+    use rand::{thread_rng, Rng};
+    use storage_proofs::sector::SectorId;
+    use tempfile::NamedTempFile;
+
+    use crate::disk_backed_storage::new_sector_store;
+    use crate::helpers::calculate_checksum;
+    use crate::sector_access::SectorAccess;
+
+    use super::*;
+
+    fn import<S: SectorStore>(
+        store: &S,
+        staged_state: &mut StagedState,
+        sealed_state: &mut SealedState,
+        replica_path: impl AsRef<Path>,
+        meta: SealedSectorMetadata,
+        link_ok: bool,
+    ) -> Result<()> {
+        let sector_access =
+            provision_sealed_sector_import(store, staged_state, sealed_state, meta.sector_id)?;
+
+        let sector_path = store.manager().sealed_sector_path(&sector_access);
+
+        copy_imported_replica(replica_path, &sector_path, &meta, link_ok)?;
+
+        register_imported_sector(staged_state, sealed_state, sector_access, meta)
+    }
+
+    fn create_replica() -> (NamedTempFile, SealedSectorMetadata) {
+        let mut rng = thread_rng();
+        let contents: Vec<u8> = (0..1024).map(|_| rng.gen()).collect();
+
+        let mut file = NamedTempFile::new().expect("could not create named temp file");
+        file.write_all(&contents).expect("could not write replica");
+
+        let meta = SealedSectorMetadata {
+            sector_id: SectorId::from(42),
+            sector_access: SectorAccess::new("ignored").unwrap(),
+            blake2b_checksum: calculate_checksum(file.path()).unwrap().as_ref().to_vec(),
+            len: contents.len() as u64,
+            ..Default::default()
+        };
+
+        (file, meta)
+    }
+
+    #[test]
+    fn test_import_sealed_sector() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let mut staged_state: StagedState = Default::default();
+        let mut sealed_state: SealedState = Default::default();
+
+        let (replica, meta) = create_replica();
+
+        // replica whose length doesn't match its metadata is rejected
+        let result = import(
+            &store,
+            &mut staged_state,
+            &mut sealed_state,
+            replica.path(),
+            SealedSectorMetadata {
+                len: 1,
+                ..meta.clone()
+            },
+            false,
+        );
+
+        assert!(result.is_err());
+        assert!(sealed_state.sectors.is_empty());
+
+        // valid replica is copied and registered
+        import(
+            &store,
+            &mut staged_state,
+            &mut sealed_state,
+            replica.path(),
+            meta.clone(),
+            false,
+        )
+        .expect("failed to import sealed sector");
+
+        let imported = sealed_state.sectors.get(&meta.sector_id).unwrap();
+        let imported_path = store.manager().sealed_sector_path(&imported.sector_access);
+
+        assert_ne!(meta.sector_access, imported.sector_access);
+        assert_eq!(meta.len, std::fs::metadata(imported_path).unwrap().len());
+        assert_eq!(42, staged_state.sector_id_nonce);
+
+        // importing the same sector twice is rejected
+        let result = import(
+            &store,
+            &mut staged_state,
+            &mut sealed_state,
+            replica.path(),
+            meta,
+            false,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_import_sealed_sector_by_link() {
+        use std::os::unix::fs::MetadataExt;
+
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let mut staged_state: StagedState = Default::default();
+        let mut sealed_state: SealedState = Default::default();
+
+        // the replica is created in the sealed sector directory, so that it's
+        // on the same filesystem
+        let (replica, meta) = create_replica();
+        let replica_path = sealed_dir.path().join("replica");
+        std::fs::copy(replica.path(), &replica_path).unwrap();
+
+        import(
+            &store,
+            &mut staged_state,
+            &mut sealed_state,
+            &replica_path,
+            meta.clone(),
+            true,
+        )
+        .expect("failed to import sealed sector");
+
+        let imported = sealed_state.sectors.get(&meta.sector_id).unwrap();
+        let imported_path = store.manager().sealed_sector_path(&imported.sector_access);
+
+        let replica_ino = std::fs::metadata(&replica_path).unwrap().ino();
+        assert_eq!(replica_ino, std::fs::metadata(imported_path).unwrap().ino());
+    }
+}
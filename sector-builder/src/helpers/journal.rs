@@ -0,0 +1,519 @@
+use std::collections::HashSet;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_unrecov, Result};
+use crate::helpers::{persist_sectors, persist_sectors_and_journal, persist_snapshot, SnapshotKey};
+use crate::kv_store::{KeyValueStore, WriteBatch};
+use crate::metadata::{SealedSectorMetadata, SecondsSinceEpoch, StagedSectorMetadata};
+use crate::state::SectorBuilderState;
+
+// The journal records every change to a sector's metadata, in the order in
+// which the changes were made, next to the snapshot. Entries are numbered
+// from 1 and stored under keys of their own, and the number of the last entry
+// is stored under the journal key itself. Each entry holds the sector's
+// metadata as it was after the change, so replaying the journal over an
+// empty state reproduces the state which was snapshotted.
+//
+// Entries are written in the same batch as the snapshot which reflects them
+// (see persist_sectors_and_journal), so changes batched by a snapshot
+// interval are journaled once they're flushed rather than as they're made.
+// The index of a snapshot records the last entry reflected by the snapshot.
+// Entries beyond it, appended by SectorBuilders which journaled changes as
+// they were made, are replayed when the snapshot is loaded.
+//
+// Once a snapshot has been flushed, the entries it reflects are pruned, save
+// for the most recent keep_last_n (see DEFAULT_JOURNAL_KEEP_LAST_N), so that
+// the journal doesn't grow without bound. The number of the last pruned entry is stored under a key
+// of its own. A snapshot can't be rebuilt from a journal which has been
+// pruned.
+
+/// A change to the metadata of a sector, as recorded in the journal.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum SectorTransition {
+    /// The sector was found in a snapshot persisted before the journal
+    /// existed.
+    Loaded,
+    PieceAdded {
+        piece_key: String,
+        /// identifier of the client or source which supplied the piece, if
+        /// the caller provided one
+        #[serde(default)]
+        origin: Option<String>,
+    },
+    SealStarted,
+    /// The sector's interrupted seal was abandoned and the sector reverted
+    /// to pending (see StaleSealPolicy::RevertToPending).
+    SealReverted,
+    Sealed,
+    SealFailed,
+    SealedSectorImported,
+    SealedSectorMoved,
+    /// The missing checksum or length of the sealed sector was computed from
+    /// its replica (see SectorBuilder::hydrate_sector_integrity).
+    SealedSectorHydrated,
+    /// Copying the sealed sector's replica to the replicator's secondary
+    /// location started.
+    ReplicationStarted,
+    /// Copying the sealed sector's replica finished, successfully or not.
+    ReplicationFinished,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub recorded_at: SecondsSinceEpoch,
+    pub sector_id: SectorId,
+    pub transition: SectorTransition,
+    pub sector_id_nonce: u64,
+    pub staged: Option<StagedSectorMetadata>,
+    pub sealed: Option<SealedSectorMetadata>,
+}
+
+fn journal_key(key: &SnapshotKey) -> Vec<u8> {
+    let mut journal_key = Vec::from(key);
+    journal_key.extend_from_slice(b"/journal");
+    journal_key
+}
+
+fn journal_entry_key(key: &SnapshotKey, seq: u64) -> Vec<u8> {
+    let mut entry_key = journal_key(key);
+    entry_key.push(b'/');
+    entry_key.write_u64::<LittleEndian>(seq).unwrap();
+    entry_key
+}
+
+fn journal_tail_key(key: &SnapshotKey) -> Vec<u8> {
+    let mut tail_key = journal_key(key);
+    tail_key.extend_from_slice(b"/tail");
+    tail_key
+}
+
+// Returns the number of the last entry appended to the journal, or 0 if the
+// journal is empty.
+pub fn journal_head<T: KeyValueStore>(kv_store: &T, key: &SnapshotKey) -> Result<u64> {
+    match kv_store.get(&journal_key(key))? {
+        Some(val) => Ok(serde_cbor::from_slice(&val[..])?),
+        None => Ok(0),
+    }
+}
+
+// Returns the number of the last entry pruned from the journal, or 0 if the
+// journal hasn't been pruned.
+pub fn journal_tail<T: KeyValueStore>(kv_store: &T, key: &SnapshotKey) -> Result<u64> {
+    match kv_store.get(&journal_tail_key(key))? {
+        Some(val) => Ok(serde_cbor::from_slice(&val[..])?),
+        None => Ok(0),
+    }
+}
+
+// Creates the entry with the provided number, recording the transition of
+// the sector with the provided id, which the state already reflects.
+pub fn new_journal_entry(
+    state: &SectorBuilderState,
+    seq: u64,
+    sector_id: SectorId,
+    transition: SectorTransition,
+) -> JournalEntry {
+    JournalEntry {
+        seq,
+        recorded_at: SecondsSinceEpoch::now(),
+        sector_id,
+        transition,
+        sector_id_nonce: state.staged.sector_id_nonce,
+        staged: state.staged.sectors.get(&sector_id).cloned(),
+        sealed: state.sealed.sectors.get(&sector_id).cloned(),
+    }
+}
+
+// Adds the provided entries, which must follow the journal's head in order,
+// to the batch, along with the number of the last of them as the new head.
+pub fn put_journal_entries(
+    batch: &mut WriteBatch,
+    key: &SnapshotKey,
+    entries: &[JournalEntry],
+) -> Result<()> {
+    for entry in entries {
+        batch.put_with(journal_entry_key(key, entry.seq), |buf| {
+            Ok(serde_cbor::to_writer(buf, entry)?)
+        })?;
+    }
+
+    if let Some(last) = entries.last() {
+        batch.put_with(journal_key(key), |buf| {
+            Ok(serde_cbor::to_writer(buf, &last.seq)?)
+        })?;
+    }
+
+    Ok(())
+}
+
+// Adds the deletes of the entries up to and including the one with the
+// provided number, which haven't been pruned yet, to the batch, along with
+// that number as the journal's new tail.
+pub fn prune_journal_entries<T: KeyValueStore>(
+    kv_store: &T,
+    batch: &mut WriteBatch,
+    key: &SnapshotKey,
+    through: u64,
+) -> Result<()> {
+    let tail = journal_tail(kv_store, key)?;
+
+    if through <= tail {
+        return Ok(());
+    }
+
+    for seq in (tail + 1)..=through {
+        batch.delete(journal_entry_key(key, seq));
+    }
+
+    batch.put_with(journal_tail_key(key), |buf| {
+        Ok(serde_cbor::to_writer(buf, &through)?)
+    })
+}
+
+// Prunes the entries up to and including the one with the provided number,
+// without persisting a snapshot.
+pub fn prune_journal<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    through: u64,
+) -> Result<()> {
+    let mut batch = WriteBatch::default();
+    prune_journal_entries(kv_store, &mut batch, key, through)?;
+
+    kv_store.write_batch(&batch)
+}
+
+// Loads the journal entries appended after the entry with the provided
+// number, oldest first. Loading the entries after 0 loads the whole journal,
+// which fails once it has been pruned; loading those after the journal's
+// tail loads the entries which remain.
+pub fn load_journal<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    after: u64,
+) -> Result<Vec<JournalEntry>> {
+    let tail = journal_tail(kv_store, key)?;
+
+    if after < tail {
+        return Err(err_unrecov(format!("journal entries up to {} have been pruned", tail)).into());
+    }
+
+    let head = journal_head(kv_store, key)?;
+
+    ((after + 1)..=head)
+        .map(|seq| {
+            let val = kv_store
+                .get(&journal_entry_key(key, seq))?
+                .ok_or_else(|| err_unrecov(format!("journal entry {} is missing", seq)))?;
+
+            Ok(serde_cbor::from_slice(&val[..])?)
+        })
+        .collect()
+}
+
+// Applies the journal entries to the state, in order, returning the ids of
+// the sectors they changed.
+pub fn replay_journal(
+    state: &mut SectorBuilderState,
+    entries: &[JournalEntry],
+) -> HashSet<SectorId> {
+    let mut replayed = HashSet::new();
+
+    for entry in entries {
+        state.staged.sector_id_nonce = entry.sector_id_nonce;
+
+        if let Some(meta) = &entry.staged {
+            state.staged.sectors.insert(entry.sector_id, meta.clone());
+        }
+
+        if let Some(meta) = &entry.sealed {
+            state.sealed.sectors.insert(entry.sector_id, meta.clone());
+        }
+
+        replayed.insert(entry.sector_id);
+    }
+
+    replayed
+}
+
+// Rebuilds the snapshot persisted under the provided key from the journal
+// alone, e.g. if the snapshot can no longer be read. Returns the rebuilt
+// state, or None if the journal is empty. A journal which has been pruned no
+// longer holds every sector, so the snapshot can't be rebuilt from it.
+pub fn rebuild_snapshot<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+) -> Result<Option<SectorBuilderState>> {
+    let tail = journal_tail(kv_store, key)?;
+
+    if tail > 0 {
+        return Err(err_unrecov(format!(
+            "can't rebuild snapshot, journal entries up to {} have been pruned",
+            tail
+        ))
+        .into());
+    }
+
+    let entries = load_journal(kv_store, key, 0)?;
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let mut state: SectorBuilderState = Default::default();
+    replay_journal(&mut state, &entries);
+
+    persist_snapshot(kv_store, key, &state)?;
+
+    Ok(Some(state))
+}
+
+// Brings a loaded snapshot, which reflects the journal up to the entry with
+// the provided number, up to date with the journal. A snapshot persisted
+// before the journal existed is recorded in it instead, so that the journal
+// covers every sector.
+pub(crate) fn sync_with_journal<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    state: &mut SectorBuilderState,
+    journal_seq: u64,
+) -> Result<()> {
+    let head = journal_head(kv_store, key)?;
+
+    if head == 0 {
+        let mut sector_ids: Vec<SectorId> = state
+            .staged
+            .sectors
+            .keys()
+            .chain(state.sealed.sectors.keys())
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        sector_ids.sort();
+
+        let entries: Vec<JournalEntry> = sector_ids
+            .into_iter()
+            .enumerate()
+            .map(|(n, sector_id)| {
+                new_journal_entry(state, n as u64 + 1, sector_id, SectorTransition::Loaded)
+            })
+            .collect();
+
+        return persist_sectors_and_journal(kv_store, key, state, &HashSet::new(), &entries, 0);
+    }
+
+    if head > journal_seq {
+        warn!(
+            "replaying {} journal entries not reflected by the snapshot",
+            head - journal_seq
+        );
+
+        let replayed = replay_journal(state, &load_journal(kv_store, key, journal_seq)?);
+
+        persist_sectors(kv_store, key, state, &replayed)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::types::PaddedBytesAmount;
+
+    use crate::helpers::load_snapshot;
+    use crate::kv_store::SledKvs;
+    use crate::metadata::SealStatus;
+
+    use super::*;
+
+    // Appends an entry to the journal without snapshotting the state, like
+    // SectorBuilders which journaled changes as they were made did.
+    fn append_journal_entry<T: KeyValueStore>(
+        kv_store: &T,
+        key: &SnapshotKey,
+        state: &SectorBuilderState,
+        sector_id: SectorId,
+        transition: SectorTransition,
+    ) {
+        let seq = journal_head(kv_store, key).unwrap() + 1;
+        let entry = new_journal_entry(state, seq, sector_id, transition);
+
+        let mut batch = WriteBatch::default();
+        put_journal_entries(&mut batch, key, &[entry]).unwrap();
+
+        kv_store.write_batch(&batch).unwrap();
+    }
+
+    fn staged_sector(n: u64, seal_status: SealStatus) -> StagedSectorMetadata {
+        StagedSectorMetadata {
+            sector_id: SectorId::from(n),
+            seal_status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_replays_entries_missing_from_snapshot() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let mut state: SectorBuilderState = Default::default();
+        state.staged.sector_id_nonce = 1;
+        state
+            .staged
+            .sectors
+            .insert(SectorId::from(1), staged_sector(1, SealStatus::Pending));
+
+        append_journal_entry(
+            &kv_store,
+            &key,
+            &state,
+            SectorId::from(1),
+            SectorTransition::PieceAdded {
+                piece_key: "a".to_string(),
+                origin: Some("f01234".to_string()),
+            },
+        );
+
+        persist_snapshot(&kv_store, &key, &state).unwrap();
+
+        // the second transition is journaled, but never snapshotted
+        state
+            .staged
+            .sectors
+            .insert(SectorId::from(1), staged_sector(1, SealStatus::Sealing));
+
+        append_journal_entry(
+            &kv_store,
+            &key,
+            &state,
+            SectorId::from(1),
+            SectorTransition::SealStarted,
+        );
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+
+        // the replayed transition has been snapshotted
+        assert_eq!(
+            SealStatus::Sealing,
+            crate::helpers::load_staged_sector(&kv_store, &key, SectorId::from(1))
+                .unwrap()
+                .unwrap()
+                .seal_status
+        );
+
+        let transitions: Vec<_> = load_journal(&kv_store, &key, 0)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.transition)
+            .collect();
+
+        assert_eq!(
+            vec![
+                SectorTransition::PieceAdded {
+                    piece_key: "a".to_string(),
+                    origin: Some("f01234".to_string()),
+                },
+                SectorTransition::SealStarted
+            ],
+            transitions
+        );
+    }
+
+    #[test]
+    fn test_rebuilds_unreadable_snapshots() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let mut state: SectorBuilderState = Default::default();
+        state.staged.sector_id_nonce = 2;
+
+        for n in 1..=2 {
+            state
+                .staged
+                .sectors
+                .insert(SectorId::from(n), staged_sector(n, SealStatus::Pending));
+        }
+
+        persist_snapshot(&kv_store, &key, &state).unwrap();
+
+        // loading a snapshot persisted without a journal records its sectors
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+        assert_eq!(2, journal_head(&kv_store, &key).unwrap());
+
+        // the sectors' metadata is lost
+        for n in 1..=2 {
+            let mut sector_key = Vec::from(&key);
+            sector_key.extend_from_slice(b"/staged/");
+            sector_key.write_u64::<LittleEndian>(n).unwrap();
+
+            kv_store.put(&sector_key, b"garbage").unwrap();
+        }
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_prunes_entries_reflected_by_snapshot() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let mut state: SectorBuilderState = Default::default();
+        let mut entries = Vec::new();
+
+        for n in 1..=3 {
+            state
+                .staged
+                .sectors
+                .insert(SectorId::from(n), staged_sector(n, SealStatus::Pending));
+
+            entries.push(new_journal_entry(
+                &state,
+                n,
+                SectorId::from(n),
+                SectorTransition::SealStarted,
+            ));
+        }
+
+        let sector_ids = state.staged.sectors.keys().cloned().collect();
+
+        // the entries are written along with the snapshot, pruning the
+        // first two of them
+        persist_sectors_and_journal(&kv_store, &key, &state, &sector_ids, &entries, 2).unwrap();
+
+        assert_eq!(3, journal_head(&kv_store, &key).unwrap());
+        assert_eq!(2, journal_tail(&kv_store, &key).unwrap());
+        assert_eq!(
+            vec![entries[2].clone()],
+            load_journal(&kv_store, &key, 2).unwrap()
+        );
+
+        assert!(load_journal(&kv_store, &key, 0).is_err());
+        assert!(rebuild_snapshot(&kv_store, &key).is_err());
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+
+        // compacting prunes entries without persisting a snapshot
+        prune_journal(&kv_store, &key, 3).unwrap();
+
+        assert_eq!(3, journal_tail(&kv_store, &key).unwrap());
+        assert!(load_journal(&kv_store, &key, 3).unwrap().is_empty());
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::dir_lock::LOCK_FILE_NAME;
+use crate::error::*;
+use crate::helpers::move_replica;
+
+// Sectors of several miners are kept apart by storing each miner's sectors
+// in a subdirectory, named after the miner, of the sealed and staged sector
+// directories:
+//
+//   <sealed sector dir>/<miner>/<sector access>
+//   <staged sector dir>/<miner>/<sector access>
+//
+// As miners name directories, a miner must be a single, normal path
+// component; anything else could escape the sector directories or collide
+// with another miner's subdirectory.
+pub fn validate_miner(miner: &str) -> Result<()> {
+    let mut components = Path::new(miner).components();
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if name == miner => Ok(()),
+        _ => Err(err_invalid_miner(format!("{:?} is not a valid miner", miner)).into()),
+    }
+}
+
+// Moves the sector-files stored directly in the provided sector directory,
+// i.e. by a SectorBuilder serving a single miner, into the subdirectory of
+// the provided miner. Subdirectories (of other miners) and the directory's
+// lock file are left alone.
+// Returns the number of sector-files which have been moved.
+pub fn migrate_to_miner_layout(sector_dir: impl AsRef<Path>, miner: &str) -> Result<usize> {
+    validate_miner(miner)?;
+
+    let miner_dir = sector_dir.as_ref().join(miner);
+    let mut num_moved = 0;
+
+    for entry in fs::read_dir(&sector_dir)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_file() && entry.file_name() != LOCK_FILE_NAME {
+            move_replica(entry.path(), miner_dir.join(entry.file_name()))?;
+            num_moved += 1;
+        }
+    }
+
+    Ok(num_moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_miner() {
+        assert!(validate_miner("t01000").is_ok());
+
+        for miner in &["", ".", "..", "a/b", "/t01000", "t01000/", "../t01000"] {
+            assert!(validate_miner(miner).is_err(), "{:?} was accepted", miner);
+        }
+    }
+
+    #[test]
+    fn test_migrate_to_miner_layout() {
+        let sector_dir = tempfile::tempdir().unwrap();
+
+        fs::write(
+            sector_dir.path().join("on-000000000000-0000000001"),
+            &[1; 8],
+        )
+        .unwrap();
+        fs::write(
+            sector_dir.path().join("on-000000000000-0000000002"),
+            &[2; 8],
+        )
+        .unwrap();
+        fs::create_dir(sector_dir.path().join("t02000")).unwrap();
+
+        assert_eq!(
+            2,
+            migrate_to_miner_layout(sector_dir.path(), "t01000").unwrap()
+        );
+
+        let miner_dir = sector_dir.path().join("t01000");
+
+        assert_eq!(
+            vec![1; 8],
+            fs::read(miner_dir.join("on-000000000000-0000000001")).unwrap()
+        );
+        assert!(!sector_dir
+            .path()
+            .join("on-000000000000-0000000002")
+            .exists());
+        assert!(sector_dir.path().join("t02000").is_dir());
+
+        // nothing is left to migrate
+        assert_eq!(
+            0,
+            migrate_to_miner_layout(sector_dir.path(), "t01000").unwrap()
+        );
+
+        assert!(migrate_to_miner_layout(sector_dir.path(), "..").is_err());
+    }
+}
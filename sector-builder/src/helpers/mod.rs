@@ -1,13 +1,57 @@
 pub use self::add_piece::*;
+pub use self::check_quota::*;
 pub use self::checksum::*;
+pub use self::classify_seal_failure::*;
+pub use self::extent_checksums::*;
+pub use self::generate_piece_commitments::*;
+pub use self::get_expired_sectors::*;
+pub use self::get_piece_layout::*;
+pub use self::get_seal_statistics::*;
 pub use self::get_seal_status::*;
 pub use self::get_sealed_sector_health::*;
 pub use self::get_sectors_ready_for_sealing::*;
+pub use self::get_storage_report::*;
+pub use self::hydrate_sector_integrity::*;
+pub use self::import_sealed_sector::*;
+pub use self::journal::*;
+pub use self::miner_layout::*;
+pub use self::move_sealed_sector::*;
+pub use self::partition_replicas::*;
+pub use self::piece_manifest::*;
+pub use self::piece_provenance::*;
+pub use self::prove_before_deadline::*;
+pub use self::prover_id::*;
+pub use self::retain_staged_data::*;
+pub use self::sector_bundle::*;
 pub use self::snapshots::*;
+pub use self::staged_sector_dir::*;
+pub use self::truncate_unrecorded_pieces::*;
 
 mod add_piece;
+mod check_quota;
 pub(crate) mod checksum;
+mod classify_seal_failure;
+mod extent_checksums;
+mod generate_piece_commitments;
+mod get_expired_sectors;
+mod get_piece_layout;
+mod get_seal_statistics;
 mod get_seal_status;
 mod get_sealed_sector_health;
 mod get_sectors_ready_for_sealing;
+mod get_storage_report;
+mod hydrate_sector_integrity;
+mod import_sealed_sector;
+mod journal;
+mod miner_layout;
+mod move_sealed_sector;
+mod partition_replicas;
+mod piece_manifest;
+mod piece_provenance;
+mod prove_before_deadline;
+mod prover_id;
+mod retain_staged_data;
+mod sector_bundle;
 mod snapshots;
+mod staged_sector_dir;
+mod truncate_unrecorded_pieces;
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+use crate::metadata::{SealedSectorLocation, SealedSectorMetadata};
+use crate::sector_access::SectorAccess;
+use crate::state::SealedState;
+use crate::store::SectorStore;
+
+// Produces the path to the file with the provided sector access at the
+// provided location. Store ids are resolved using the map of registered
+// sealed sector stores.
+pub fn resolve_sealed_sector_path<S: SectorStore>(
+    sector_store: &S,
+    sealed_stores: &HashMap<String, PathBuf>,
+    location: &SealedSectorLocation,
+    sector_access: &SectorAccess,
+) -> Result<PathBuf> {
+    match location {
+        SealedSectorLocation::Default => {
+            Ok(sector_store.manager().sealed_sector_path(sector_access))
+        }
+        SealedSectorLocation::Store(store_id) => sealed_stores
+            .get(store_id)
+            .map(|dir| dir.join(sector_access))
+            .ok_or_else(|| err_unknown_store(store_id.clone()).into()),
+        SealedSectorLocation::Dir(dir) => Ok(dir.join(sector_access)),
+    }
+}
+
+// Produces the path to the replica of the provided sealed sector.
+pub fn sealed_sector_path<S: SectorStore>(
+    sector_store: &S,
+    sealed_stores: &HashMap<String, PathBuf>,
+    meta: &SealedSectorMetadata,
+) -> Result<PathBuf> {
+    resolve_sealed_sector_path(
+        sector_store,
+        sealed_stores,
+        &meta.location,
+        &meta.sector_access,
+    )
+}
+
+// Produces the paths from and to which the replica of the sealed sector with
+// the provided id is moved to reach the target location. Once the replica
+// has been moved (see move_replica), the sector's metadata is updated to
+// reflect its new location.
+pub fn plan_sealed_sector_move<S: SectorStore>(
+    sector_store: &S,
+    sealed_stores: &HashMap<String, PathBuf>,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+    target: &SealedSectorLocation,
+) -> Result<(PathBuf, PathBuf)> {
+    let meta = sealed_state
+        .sectors
+        .get(&sector_id)
+        .ok_or_else(|| err_unrecov(format!("no sealed sector with id {} found", sector_id)))?;
+
+    let source_path = sealed_sector_path(sector_store, sealed_stores, meta)?;
+    let target_path =
+        resolve_sealed_sector_path(sector_store, sealed_stores, target, &meta.sector_access)?;
+
+    Ok((source_path, target_path))
+}
+
+// Moves the replica at the source path to the target path. Falls back to
+// copying and removing the source if the replica can't be renamed, e.g.
+// because the target is on another filesystem.
+pub fn move_replica(source_path: impl AsRef<Path>, target_path: impl AsRef<Path>) -> Result<()> {
+    if source_path.as_ref() == target_path.as_ref() {
+        return Ok(());
+    }
+
+    if let Some(dir) = target_path.as_ref().parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    if fs::rename(&source_path, &target_path).is_err() {
+        if let Err(err) = fs::copy(&source_path, &target_path) {
+            let _ = fs::remove_file(&target_path);
+            return Err(err.into());
+        }
+
+        fs::remove_file(&source_path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+
+    use crate::disk_backed_storage::new_sector_store;
+
+    use super::*;
+
+    fn move_sealed_sector<S: SectorStore>(
+        sector_store: &S,
+        sealed_stores: &HashMap<String, PathBuf>,
+        sealed_state: &mut SealedState,
+        sector_id: SectorId,
+        target: SealedSectorLocation,
+    ) -> Result<()> {
+        let (source_path, target_path) = plan_sealed_sector_move(
+            sector_store,
+            sealed_stores,
+            sealed_state,
+            sector_id,
+            &target,
+        )?;
+
+        move_replica(&source_path, &target_path)?;
+
+        sealed_state.sectors.get_mut(&sector_id).unwrap().location = target;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_sealed_sector() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+        let archive_dir = tempfile::tempdir().unwrap();
+        let store_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let mut sealed_stores: HashMap<String, PathBuf> = Default::default();
+        sealed_stores.insert("cold".to_string(), store_dir.path().to_path_buf());
+
+        let sector_id = SectorId::from(3);
+
+        let sector_access = store.manager().new_sealed_sector_access(sector_id).unwrap();
+
+        fs::write(store.manager().sealed_sector_path(&sector_access), &[7; 64]).unwrap();
+
+        let mut sealed_state: SealedState = Default::default();
+
+        sealed_state.sectors.insert(
+            sector_id,
+            SealedSectorMetadata {
+                sector_id,
+                sector_access: sector_access.clone(),
+                ..Default::default()
+            },
+        );
+
+        let path_of = |state: &SealedState| {
+            sealed_sector_path(&store, &sealed_stores, &state.sectors[&sector_id]).unwrap()
+        };
+
+        let original_path = path_of(&sealed_state);
+
+        // move to an absolute directory
+        move_sealed_sector(
+            &store,
+            &sealed_stores,
+            &mut sealed_state,
+            sector_id,
+            SealedSectorLocation::Dir(archive_dir.path().to_path_buf()),
+        )
+        .expect("failed to move sealed sector");
+
+        assert_eq!(
+            archive_dir.path().join(&sector_access),
+            path_of(&sealed_state)
+        );
+        assert!(!original_path.exists());
+        assert_eq!(vec![7; 64], fs::read(path_of(&sealed_state)).unwrap());
+
+        // move to a registered store
+        move_sealed_sector(
+            &store,
+            &sealed_stores,
+            &mut sealed_state,
+            sector_id,
+            SealedSectorLocation::Store("cold".to_string()),
+        )
+        .expect("failed to move sealed sector");
+
+        assert_eq!(
+            store_dir.path().join(&sector_access),
+            path_of(&sealed_state)
+        );
+        assert_eq!(vec![7; 64], fs::read(path_of(&sealed_state)).unwrap());
+
+        // unregistered stores can't be resolved
+        assert!(move_sealed_sector(
+            &store,
+            &sealed_stores,
+            &mut sealed_state,
+            sector_id,
+            SealedSectorLocation::Store("hot".to_string()),
+        )
+        .is_err());
+
+        // unknown sectors can't be moved
+        assert!(move_sealed_sector(
+            &store,
+            &sealed_stores,
+            &mut sealed_state,
+            SectorId::from(4),
+            SealedSectorLocation::Default,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_invalid_replica, Result};
+
+// Splits the replicas into partitions of (at most) the provided number of
+// replicas, in order of sector id. Only the last partition may be smaller.
+// The partition size must not be 0.
+pub fn partition_replicas<T>(
+    replicas: BTreeMap<SectorId, T>,
+    partition_size: usize,
+) -> Vec<BTreeMap<SectorId, T>> {
+    let mut partitions = Vec::new();
+    let mut replicas = replicas.into_iter().peekable();
+
+    while replicas.peek().is_some() {
+        partitions.push(replicas.by_ref().take(partition_size).collect());
+    }
+
+    partitions
+}
+
+// Converts the path of the replica of the sector with the provided id into
+// the string the proofs take, which must be valid UTF-8.
+pub fn replica_path_str(sector_id: SectorId, path: &Path) -> Result<String> {
+    path.to_str().map(str::to_string).ok_or_else(|| {
+        err_invalid_replica(format!(
+            "path {:?} of the replica of sector {} isn't valid UTF-8",
+            path, sector_id
+        ))
+        .into()
+    })
+}
+
+// Checks that every replica path override can be passed to the proofs, so
+// that a bad override is rejected before any proving starts.
+pub fn validate_replica_paths(replica_paths: &HashMap<SectorId, PathBuf>) -> Result<()> {
+    for (sector_id, path) in replica_paths {
+        replica_path_str(*sector_id, path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partitions_in_order_of_sector_id() {
+        let replicas: BTreeMap<SectorId, u64> = vec![5, 1, 4, 2, 3]
+            .into_iter()
+            .map(|id| (SectorId::from(id), id))
+            .collect();
+
+        let partitions: Vec<Vec<u64>> = partition_replicas(replicas, 2)
+            .into_iter()
+            .map(|partition| partition.values().cloned().collect())
+            .collect();
+
+        assert_eq!(vec![vec![1, 2], vec![3, 4], vec![5]], partitions);
+
+        assert!(partition_replicas(BTreeMap::<SectorId, u64>::new(), 2).is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn rejects_replica_paths_which_arent_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut replica_paths: HashMap<SectorId, PathBuf> = HashMap::new();
+        replica_paths.insert(SectorId::from(1), PathBuf::from("/mnt/archive/s-t01-1"));
+
+        assert!(validate_replica_paths(&replica_paths).is_ok());
+
+        replica_paths.insert(
+            SectorId::from(2),
+            PathBuf::from(OsStr::from_bytes(b"/mnt/archive/s-t01-\xff")),
+        );
+
+        assert!(validate_replica_paths(&replica_paths).is_err());
+    }
+}
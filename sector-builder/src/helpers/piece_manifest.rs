@@ -0,0 +1,260 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_staged_data_corrupted, Result};
+use crate::helpers::{layout_of, staged_sector_manager, SnapshotKey};
+use crate::kv_store::KeyValueStore;
+use crate::metadata::StagedSectorMetadata;
+use crate::store::SectorStore;
+
+// The manifest of a staged sector records where each of its pieces lies in the
+// staged sector-file and the digest of the piece's (preprocessed) bytes as
+// they were written. It's stored next to the snapshot, under a key of its own
+// per sector, and checked before the sector is sealed so that a staged
+// sector-file which was corrupted on disk fails fast instead of producing a
+// replica of bad data.
+//
+// Pieces staged before manifests existed have no entry and aren't checked.
+
+/// The location and digest of a piece within a staged sector-file.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ManifestEntry {
+    pub piece_key: String,
+    /// offset of the piece's first byte within the staged sector-file
+    pub padded_start_byte: u64,
+    pub padded_len: u64,
+    /// BLAKE3 digest of the piece's bytes in the staged sector-file
+    pub digest: [u8; 32],
+}
+
+fn manifest_key(key: &SnapshotKey, sector_id: SectorId) -> Vec<u8> {
+    let mut manifest_key = Vec::from(key);
+    manifest_key.extend_from_slice(b"/manifest/");
+    manifest_key
+        .write_u64::<LittleEndian>(u64::from(sector_id))
+        .unwrap();
+    manifest_key
+}
+
+// Calculates the BLAKE3 digest of the provided range of a file. A range which
+// extends beyond the end of the file is digested up to the end of the file.
+pub fn digest_range<F: Read + Seek>(file: &mut F, start: u64, len: u64) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+
+    file.seek(SeekFrom::Start(start))?;
+    io::copy(&mut file.take(len), &mut hasher)?;
+
+    Ok(*hasher.finalize().as_bytes())
+}
+
+// Loads the manifest of the staged sector with the provided id, which is empty
+// if no piece has been recorded in it.
+pub fn load_piece_manifest<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    sector_id: SectorId,
+) -> Result<Vec<ManifestEntry>> {
+    match kv_store.get(&manifest_key(key, sector_id))? {
+        Some(val) => Ok(serde_cbor::from_slice(&val[..])?),
+        None => Ok(Vec::new()),
+    }
+}
+
+// Records the piece which was last added to the provided staged sector in the
+// sector's manifest, digesting the piece's bytes in the staged sector-file.
+// Entries of pieces which were truncated from the staged sector-file, and
+// whose space the piece has taken, are dropped.
+pub fn record_last_piece<S: SectorStore, T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    sector_store: &S,
+    staged_sector: &StagedSectorMetadata,
+) -> Result<()> {
+    let index = match staged_sector.pieces.len() {
+        0 => return Ok(()),
+        n => n - 1,
+    };
+
+    let layout = layout_of(&staged_sector.pieces[..index], &staged_sector.pieces[index]);
+
+    let digest = staged_sector_manager(sector_store, staged_sector.staged_dir)
+        .digest_staged_range(
+            &staged_sector.sector_access,
+            layout.padded_start_byte,
+            u64::from(layout.padded_len),
+        )?;
+
+    let mut manifest = load_piece_manifest(kv_store, key, staged_sector.sector_id)?;
+
+    manifest.retain(|entry| entry.padded_start_byte < layout.padded_start_byte);
+    manifest.push(ManifestEntry {
+        piece_key: layout.piece_key,
+        padded_start_byte: layout.padded_start_byte,
+        padded_len: u64::from(layout.padded_len),
+        digest,
+    });
+
+    kv_store.put(
+        &manifest_key(key, staged_sector.sector_id),
+        &serde_cbor::to_vec(&manifest)?,
+    )?;
+
+    Ok(())
+}
+
+// Returns the entries of the manifest which describe the pieces of the
+// provided staged sector, as its metadata records them.
+pub fn manifest_entries_for(
+    staged_sector: &StagedSectorMetadata,
+    manifest: Vec<ManifestEntry>,
+) -> Vec<ManifestEntry> {
+    let layouts: Vec<_> = (0..staged_sector.pieces.len())
+        .map(|index| layout_of(&staged_sector.pieces[..index], &staged_sector.pieces[index]))
+        .collect();
+
+    manifest
+        .into_iter()
+        .filter(|entry| {
+            layouts.iter().any(|layout| {
+                layout.piece_key == entry.piece_key
+                    && layout.padded_start_byte == entry.padded_start_byte
+                    && u64::from(layout.padded_len) == entry.padded_len
+            })
+        })
+        .collect()
+}
+
+// Checks the pieces in the (unencrypted) staged sector-file at the provided
+// path against their manifest entries, producing an error for the first piece
+// whose bytes don't match their digest.
+pub fn verify_staged_sector<P: AsRef<Path>>(
+    path: P,
+    sector_id: SectorId,
+    manifest: &[ManifestEntry],
+) -> Result<()> {
+    if manifest.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = File::open(path)?;
+
+    for entry in manifest {
+        let digest = digest_range(&mut file, entry.padded_start_byte, entry.padded_len)?;
+
+        if digest != entry.digest {
+            return Err(err_staged_data_corrupted(sector_id, &entry.piece_key).into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{
+        PaddedBytesAmount, PoRepProofPartitions, SectorClass, SectorSize, UnpaddedBytesAmount,
+    };
+
+    use crate::disk_backed_storage::new_sector_store;
+    use crate::error::SectorBuilderErr;
+    use crate::kv_store::SledKvs;
+    use crate::metadata::PieceMetadata;
+
+    use super::*;
+
+    #[test]
+    fn test_detects_corrupted_pieces() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(dir.path().join("metadata")).unwrap();
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            dir.path().join("sealed"),
+            dir.path().join("staged"),
+        );
+        std::fs::create_dir_all(store.manager().staged_sector_dir()).unwrap();
+
+        let access = store
+            .manager()
+            .new_staging_sector_access(SectorId::from(1))
+            .unwrap();
+
+        let mut sector = StagedSectorMetadata {
+            sector_id: SectorId::from(1),
+            sector_access: access.clone(),
+            ..Default::default()
+        };
+
+        for (piece_key, byte) in &[("a", 1u8), ("b", 2u8)] {
+            store
+                .manager()
+                .write_and_preprocess(&access, &mut &[*byte; 127][..])
+                .unwrap();
+
+            sector.pieces.push(PieceMetadata::new(
+                piece_key.to_string(),
+                UnpaddedBytesAmount(127),
+            ));
+
+            record_last_piece(&kv_store, &key, &store, &sector).unwrap();
+        }
+
+        let manifest = manifest_entries_for(
+            &sector,
+            load_piece_manifest(&kv_store, &key, SectorId::from(1)).unwrap(),
+        );
+        assert_eq!(2, manifest.len());
+
+        let path = store.manager().staged_sector_path(&access);
+
+        verify_staged_sector(&path, SectorId::from(1), &manifest).unwrap();
+
+        // flip a byte of the second piece
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(130)).unwrap();
+        file.write_all(&[0xff]).unwrap();
+
+        let err = verify_staged_sector(&path, SectorId::from(1), &manifest).unwrap_err();
+        match err.downcast_ref() {
+            Some(SectorBuilderErr::StagedDataCorrupted { piece_key, .. }) => {
+                assert_eq!("b", piece_key)
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_drops_entries_of_truncated_pieces() {
+        let mut sector = StagedSectorMetadata {
+            sector_id: SectorId::from(1),
+            ..Default::default()
+        };
+        sector.pieces.push(PieceMetadata::new(
+            "a".to_string(),
+            UnpaddedBytesAmount(127),
+        ));
+
+        let entry = |piece_key: &str, padded_start_byte| ManifestEntry {
+            piece_key: piece_key.to_string(),
+            padded_start_byte,
+            padded_len: 128,
+            digest: [0; 32],
+        };
+
+        // the second entry's piece was truncated after a crash
+        let manifest = manifest_entries_for(&sector, vec![entry("a", 0), entry("x", 128)]);
+
+        assert_eq!(vec![entry("a", 0)], manifest);
+    }
+}
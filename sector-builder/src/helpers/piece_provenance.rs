@@ -0,0 +1,85 @@
+use crate::helpers::{JournalEntry, SectorTransition};
+use crate::metadata::PieceProvenance;
+
+// Returns who added each piece added with the provided origin and when, in
+// the order in which they were added, according to the provided journal
+// entries. As the journal isn't pruned, pieces whose sectors no longer exist
+// are included.
+pub fn get_pieces_by_origin(entries: &[JournalEntry], origin: &str) -> Vec<PieceProvenance> {
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.transition {
+            SectorTransition::PieceAdded {
+                piece_key,
+                origin: Some(piece_origin),
+            } if piece_origin == origin => Some(PieceProvenance {
+                piece_key: piece_key.clone(),
+                sector_id: entry.sector_id,
+                origin: piece_origin.clone(),
+                added_at: entry.recorded_at.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use storage_proofs::sector::SectorId;
+
+    use crate::metadata::SecondsSinceEpoch;
+
+    use super::*;
+
+    fn entry(seq: u64, sector_id: u64, transition: SectorTransition) -> JournalEntry {
+        JournalEntry {
+            seq,
+            recorded_at: SecondsSinceEpoch(100 + seq),
+            sector_id: SectorId::from(sector_id),
+            transition,
+            sector_id_nonce: sector_id,
+            staged: None,
+            sealed: None,
+        }
+    }
+
+    fn piece_added(piece_key: &str, origin: Option<&str>) -> SectorTransition {
+        SectorTransition::PieceAdded {
+            piece_key: piece_key.to_string(),
+            origin: origin.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_get_pieces_by_origin() {
+        let entries = vec![
+            entry(1, 1, piece_added("a", Some("f01234"))),
+            entry(2, 1, piece_added("b", Some("f05678"))),
+            entry(3, 1, piece_added("c", None)),
+            entry(4, 1, SectorTransition::SealStarted),
+            entry(5, 2, piece_added("d", Some("f01234"))),
+        ];
+
+        let pieces = get_pieces_by_origin(&entries, "f01234");
+
+        assert_eq!(
+            vec![
+                PieceProvenance {
+                    piece_key: "a".to_string(),
+                    sector_id: SectorId::from(1),
+                    origin: "f01234".to_string(),
+                    added_at: SecondsSinceEpoch(101),
+                },
+                PieceProvenance {
+                    piece_key: "d".to_string(),
+                    sector_id: SectorId::from(2),
+                    origin: "f01234".to_string(),
+                    added_at: SecondsSinceEpoch(105),
+                },
+            ],
+            pieces
+        );
+
+        assert!(get_pieces_by_origin(&entries, "f09999").is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_post_deadline_missed, Result, SectorBuilderErr};
+use crate::events::EventBus;
+use crate::metadata::{
+    PoStDeadline, SealedSectorLocation, SealedSectorMetadata, SecondsSinceEpoch,
+};
+use crate::watchdog::{run_cancellable, CancellationToken, TaskKind};
+
+// Determines which of the provided sectors must be proven as faulty for a
+// proof-of-spacetime over them to be ready before the deadline, given how
+// long proving a single sector took last time. Those are the slowest
+// sectors, if the deadline allows marking them faulty, or else an error is
+// produced. Sectors which have been moved out of the default sealed sector
+// directory (e.g. to archive them on slower, cheaper storage) are taken to be
+// slower than those which haven't, and more recently sealed sectors slower
+// than older ones. Without an estimate, no sector is marked faulty up front.
+pub fn plan_post_faults(
+    sectors: &[&SealedSectorMetadata],
+    deadline: &PoStDeadline,
+    duration_per_sector: Option<Duration>,
+    now: &SecondsSinceEpoch,
+) -> Result<Vec<SectorId>> {
+    let budget = time_until(&deadline.deadline, now);
+
+    if budget == Duration::default() {
+        return Err(err_post_deadline_missed(&deadline.deadline).into());
+    }
+
+    let duration_per_sector = match duration_per_sector {
+        Some(duration) if duration > Duration::default() => duration,
+        _ => return Ok(vec![]),
+    };
+
+    let num_in_time = (budget.as_nanos() / duration_per_sector.as_nanos()) as usize;
+
+    if num_in_time >= sectors.len() {
+        return Ok(vec![]);
+    }
+
+    // marking every sector faulty proves nothing
+    if !deadline.mark_slowest_faulty || num_in_time == 0 {
+        return Err(err_post_deadline_missed(&deadline.deadline).into());
+    }
+
+    let mut slowest_first: Vec<&&SealedSectorMetadata> = sectors.iter().collect();
+
+    slowest_first.sort_by_key(|meta| {
+        let is_moved = meta.location != SealedSectorLocation::Default;
+        (
+            std::cmp::Reverse(is_moved),
+            std::cmp::Reverse(meta.sector_id),
+        )
+    });
+
+    Ok(slowest_first
+        .into_iter()
+        .take(sectors.len() - num_in_time)
+        .map(|meta| meta.sector_id)
+        .collect())
+}
+
+/// Generates a proof-of-spacetime, abandoning it once the deadline (counted
+/// from the provided time) or the timeout for PoSt tasks has passed,
+/// whichever comes first. An abandoned proof produces a PoStDeadlineMissed
+/// error if it was the deadline which passed. Proving is also abandoned if
+/// the cancellation token is cancelled.
+pub fn prove_before_deadline<T, F>(
+    deadline: Option<&SecondsSinceEpoch>,
+    now: &SecondsSinceEpoch,
+    timeout: Option<Duration>,
+    cancel: Option<&CancellationToken>,
+    events: &EventBus,
+    f: F,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let budget = deadline.map(|deadline| time_until(deadline, now));
+
+    let limited_by_deadline = match (budget, timeout) {
+        (Some(budget), Some(timeout)) => budget < timeout,
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+
+    let timeout = if limited_by_deadline { budget } else { timeout };
+
+    run_cancellable(TaskKind::PoSt, None, timeout, cancel, events, f).map_err(|err| {
+        match (err.downcast_ref::<SectorBuilderErr>(), deadline) {
+            (Some(SectorBuilderErr::TaskTimedOut { .. }), Some(deadline))
+                if limited_by_deadline =>
+            {
+                err_post_deadline_missed(deadline).into()
+            }
+            _ => err,
+        }
+    })
+}
+
+fn time_until(deadline: &SecondsSinceEpoch, now: &SecondsSinceEpoch) -> Duration {
+    Duration::from_secs(deadline.0.saturating_sub(now.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sector(sector_id: u64, location: SealedSectorLocation) -> SealedSectorMetadata {
+        SealedSectorMetadata {
+            sector_id: SectorId::from(sector_id),
+            location,
+            ..Default::default()
+        }
+    }
+
+    fn deadline(secs: u64, mark_slowest_faulty: bool) -> PoStDeadline {
+        PoStDeadline {
+            deadline: SecondsSinceEpoch(secs),
+            mark_slowest_faulty,
+        }
+    }
+
+    fn is_deadline_missed(result: Result<Vec<SectorId>>) -> bool {
+        match result.unwrap_err().downcast_ref::<SectorBuilderErr>() {
+            Some(SectorBuilderErr::PoStDeadlineMissed(_)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn marks_slowest_sectors_faulty() {
+        let sectors = vec![
+            sector(1, SealedSectorLocation::Default),
+            sector(2, SealedSectorLocation::Store("archive".to_string())),
+            sector(3, SealedSectorLocation::Default),
+            sector(4, SealedSectorLocation::Default),
+        ];
+        let sectors: Vec<&SealedSectorMetadata> = sectors.iter().collect();
+
+        let now = SecondsSinceEpoch(1000);
+        let per_sector = Some(Duration::from_secs(10));
+
+        // everything fits
+        assert_eq!(
+            Vec::<SectorId>::new(),
+            plan_post_faults(&sectors, &deadline(1040, false), per_sector, &now).unwrap()
+        );
+
+        // without an estimate, nothing is marked up front
+        assert_eq!(
+            Vec::<SectorId>::new(),
+            plan_post_faults(&sectors, &deadline(1001, false), None, &now).unwrap()
+        );
+
+        // two sectors fit: the moved one goes first, then the newest one
+        assert_eq!(
+            vec![SectorId::from(2), SectorId::from(4)],
+            plan_post_faults(&sectors, &deadline(1025, true), per_sector, &now).unwrap()
+        );
+
+        assert!(is_deadline_missed(plan_post_faults(
+            &sectors,
+            &deadline(1025, false),
+            per_sector,
+            &now
+        )));
+
+        // no sector fits, or the deadline has passed already
+        assert!(is_deadline_missed(plan_post_faults(
+            &sectors,
+            &deadline(1005, true),
+            per_sector,
+            &now
+        )));
+
+        assert!(is_deadline_missed(plan_post_faults(
+            &sectors,
+            &deadline(1000, true),
+            None,
+            &now
+        )));
+    }
+
+    #[test]
+    fn abandons_proofs_at_the_deadline() {
+        let events = EventBus::default();
+
+        let now = SecondsSinceEpoch::now();
+
+        let result = prove_before_deadline(
+            Some(&SecondsSinceEpoch(now.0 + 1)),
+            &now,
+            Some(Duration::from_secs(60)),
+            None,
+            &events,
+            || {
+                std::thread::sleep(Duration::from_secs(5));
+                Ok(())
+            },
+        );
+
+        match result.unwrap_err().downcast_ref::<SectorBuilderErr>() {
+            Some(SectorBuilderErr::PoStDeadlineMissed(_)) => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use crate::error::*;
+
+// Converts a 32-byte prover id, as taken by the FFI, into the 31 bytes the
+// proofs take. A prover id is a field element whose most significant
+// (i.e. last) byte is always zero, so that byte must be zero: legacy 31-byte
+// prover ids are extended by appending a zero byte (see prover_id_to_bytes).
+pub fn prover_id_from_bytes(bytes: &[u8; 32]) -> Result<[u8; 31]> {
+    if bytes[31] != 0 {
+        return Err(err_invalid_prover_id(format!(
+            "last byte is {}, not 0; 31-byte prover ids must be extended with a zero byte",
+            bytes[31]
+        ))
+        .into());
+    }
+
+    let mut prover_id = [0u8; 31];
+    prover_id.copy_from_slice(&bytes[..31]);
+
+    Ok(prover_id)
+}
+
+// Extends a legacy 31-byte prover id to the 32 bytes taken by the FFI.
+pub fn prover_id_to_bytes(prover_id: &[u8; 31]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[..31].copy_from_slice(prover_id);
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prover_id_round_trip() {
+        let mut prover_id = [0u8; 31];
+        for (i, b) in prover_id.iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+
+        let bytes = prover_id_to_bytes(&prover_id);
+        assert_eq!(0, bytes[31]);
+        assert_eq!(prover_id, prover_id_from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_prover_id_with_nonzero_last_byte_is_rejected() {
+        let mut bytes = [0u8; 32];
+        bytes[31] = 1;
+
+        assert!(prover_id_from_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use crate::error::*;
+use crate::helpers::{move_replica, staged_sector_manager};
+use crate::metadata::{StagedDataLocation, StagedDataRetention, StagedSectorMetadata};
+use crate::store::SectorStore;
+
+// Produces the path to the unsealed data of the provided staged sector, or
+// None if the data has been deleted.
+pub fn staged_sector_file<S: SectorStore>(
+    sector_store: &S,
+    staged_sector: &StagedSectorMetadata,
+) -> Option<PathBuf> {
+    match &staged_sector.staged_data {
+        StagedDataLocation::StagedDir => Some(
+            staged_sector_manager(sector_store, staged_sector.staged_dir)
+                .staged_sector_path(&staged_sector.sector_access),
+        ),
+        StagedDataLocation::Cache(dir) => Some(dir.join(&staged_sector.sector_access)),
+        StagedDataLocation::Deleted => None,
+    }
+}
+
+// Applies the retention policy to the unsealed data of the provided staged
+// sector, which has been sealed, and updates its metadata accordingly.
+pub fn retain_staged_data<S: SectorStore>(
+    sector_store: &S,
+    policy: &StagedDataRetention,
+    staged_sector: &mut StagedSectorMetadata,
+) -> Result<()> {
+    let source_path = match staged_sector_file(sector_store, staged_sector) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    match policy {
+        StagedDataRetention::Keep => (),
+        StagedDataRetention::Delete => {
+            std::fs::remove_file(&source_path)?;
+            staged_sector.staged_data = StagedDataLocation::Deleted;
+        }
+        StagedDataRetention::MoveToCache(dir) => {
+            move_replica(&source_path, dir.join(&staged_sector.sector_access))?;
+            staged_sector.staged_data = StagedDataLocation::Cache(dir.clone());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+    use storage_proofs::sector::SectorId;
+
+    use crate::disk_backed_storage::new_sector_store;
+
+    use super::*;
+
+    #[test]
+    fn test_retain_staged_data() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let sector_id = SectorId::from(1);
+        let access = store
+            .manager()
+            .new_staging_sector_access(sector_id)
+            .unwrap();
+
+        fs::write(store.manager().staged_sector_path(&access), &[9; 64]).unwrap();
+
+        let mut meta = StagedSectorMetadata {
+            sector_id,
+            sector_access: access.clone(),
+            ..Default::default()
+        };
+
+        // keeping the data leaves it in place
+        retain_staged_data(&store, &StagedDataRetention::Keep, &mut meta).unwrap();
+
+        assert_eq!(StagedDataLocation::StagedDir, meta.staged_data);
+        assert_eq!(
+            Some(store.manager().staged_sector_path(&access)),
+            staged_sector_file(&store, &meta)
+        );
+
+        // the data can be moved into the cache
+        let policy = StagedDataRetention::MoveToCache(cache_dir.path().to_path_buf());
+        retain_staged_data(&store, &policy, &mut meta).unwrap();
+
+        assert_eq!(
+            StagedDataLocation::Cache(cache_dir.path().to_path_buf()),
+            meta.staged_data
+        );
+        assert!(!store.manager().staged_sector_path(&access).exists());
+        assert_eq!(
+            vec![9; 64],
+            fs::read(staged_sector_file(&store, &meta).unwrap()).unwrap()
+        );
+
+        // and deleted from wherever it is
+        retain_staged_data(&store, &StagedDataRetention::Delete, &mut meta).unwrap();
+
+        assert_eq!(StagedDataLocation::Deleted, meta.staged_data);
+        assert_eq!(None, staged_sector_file(&store, &meta));
+        assert!(!cache_dir.path().join(&access).exists());
+    }
+}
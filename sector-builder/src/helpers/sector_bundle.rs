@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+use crate::helpers::sealed_sector_path;
+use crate::metadata::SealedSectorMetadata;
+use crate::state::SealedState;
+use crate::store::SectorStore;
+
+/// Name of the replica file within a sector bundle directory.
+pub const BUNDLE_REPLICA_FILE: &str = "replica";
+
+/// Name of the JSON metadata manifest within a sector bundle directory.
+pub const BUNDLE_MANIFEST_FILE: &str = "metadata.json";
+
+// Looks up the metadata of the sealed sector with the provided id and the
+// path to its replica, from which a bundle is written.
+pub fn plan_sector_export<S: SectorStore>(
+    sector_store: &S,
+    sealed_stores: &HashMap<String, PathBuf>,
+    sealed_state: &SealedState,
+    sector_id: SectorId,
+) -> Result<(PathBuf, SealedSectorMetadata)> {
+    let meta = sealed_state
+        .sectors
+        .get(&sector_id)
+        .ok_or_else(|| err_unrecov(format!("no sealed sector with id {} found", sector_id)))?;
+
+    let sector_path = sealed_sector_path(sector_store, sealed_stores, meta)?;
+
+    Ok((sector_path, meta.clone()))
+}
+
+// Writes a bundle for the sealed sector with the provided metadata, whose
+// replica is at the provided path, to the destination directory. A bundle is
+// a directory containing a copy of the replica and a JSON manifest of the
+// sector's metadata.
+pub fn write_sector_bundle(
+    sector_path: impl AsRef<Path>,
+    meta: &SealedSectorMetadata,
+    dest_dir: impl AsRef<Path>,
+) -> Result<()> {
+    fs::create_dir_all(&dest_dir)?;
+    fs::copy(&sector_path, dest_dir.as_ref().join(BUNDLE_REPLICA_FILE))?;
+    fs::write(
+        dest_dir.as_ref().join(BUNDLE_MANIFEST_FILE),
+        serde_json::to_vec_pretty(meta)?,
+    )?;
+
+    Ok(())
+}
+
+// Reads the manifest of the bundle in the provided directory, returning the
+// path to the bundled replica and its metadata.
+pub fn load_sector_bundle(bundle_dir: impl AsRef<Path>) -> Result<(PathBuf, SealedSectorMetadata)> {
+    let manifest = fs::read(bundle_dir.as_ref().join(BUNDLE_MANIFEST_FILE))?;
+    let meta = serde_json::from_slice(&manifest)?;
+
+    Ok((bundle_dir.as_ref().join(BUNDLE_REPLICA_FILE), meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+
+    use crate::disk_backed_storage::new_sector_store;
+    use crate::helpers::calculate_checksum;
+
+    use super::*;
+
+    #[test]
+    fn test_export_and_load_bundle() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+        let bundle_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let sector_id = SectorId::from(7);
+
+        let sector_access = store.manager().new_sealed_sector_access(sector_id).unwrap();
+
+        let sector_path = store.manager().sealed_sector_path(&sector_access);
+
+        fs::File::create(&sector_path)
+            .unwrap()
+            .write_all(&[42; 1024])
+            .unwrap();
+
+        let meta = SealedSectorMetadata {
+            sector_id,
+            sector_access,
+            comm_r: [1; 32],
+            blake2b_checksum: calculate_checksum(&sector_path).unwrap().as_ref().to_vec(),
+            len: 1024,
+            ..Default::default()
+        };
+
+        let mut sealed_state: SealedState = Default::default();
+
+        // unknown sectors can't be exported
+        assert!(plan_sector_export(&store, &Default::default(), &sealed_state, sector_id).is_err());
+
+        sealed_state.sectors.insert(sector_id, meta.clone());
+
+        let (planned_path, planned_meta) =
+            plan_sector_export(&store, &Default::default(), &sealed_state, sector_id)
+                .expect("failed to plan export");
+
+        assert_eq!(sector_path, planned_path);
+
+        write_sector_bundle(&planned_path, &planned_meta, bundle_dir.path())
+            .expect("failed to export sector");
+
+        let (replica_path, loaded) =
+            load_sector_bundle(bundle_dir.path()).expect("failed to load bundle");
+
+        assert_eq!(meta, loaded);
+        assert_eq!(
+            fs::read(sector_path).unwrap(),
+            fs::read(replica_path).unwrap()
+        );
+    }
+}
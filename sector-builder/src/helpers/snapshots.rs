@@ -1,15 +1,76 @@
+use std::collections::HashSet;
+
 use byteorder::{LittleEndian, WriteBytesExt};
 use filecoin_proofs::types::PaddedBytesAmount;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
 
-use crate::error::Result;
-use crate::kv_store::KeyValueStore;
+use crate::error::{err_unrecov, Result};
+use crate::helpers::{
+    journal_head, load_journal, prune_journal_entries, put_journal_entries, rebuild_snapshot,
+    replay_journal, sync_with_journal, JournalEntry,
+};
+use crate::kv_store::{KeyValueStore, WriteBatch};
+use crate::metadata::{InterruptedSeal, SealedSectorMetadata, StagedSectorMetadata};
 use crate::state::*;
 
+// The encoded metadata of a sector takes a few hundred bytes, most of it the
+// proof and checksums of sealed sectors. Batches are preallocated for that
+// much per value so that they're rarely grown.
+const ESTIMATED_SECTOR_VALUE_LEN: usize = 512;
+
+// Prefixes compressed values. No CBOR value starts with a break (0xff), so
+// the header can't be mistaken for the start of an uncompressed value.
+const COMPRESSED_HEADER: [u8; 2] = [0xff, 0x01];
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+// Each staged and sealed sector is stored under a key of its own, derived from
+// the snapshot key, so that a change to a sector only rewrites that sector.
+// The index, also stored under a key of its own, enumerates the sectors and
+// holds what isn't specific to any sector.
+//
+// Snapshots aren't versioned: a sector's key is overwritten in place, the keys
+// of sectors which no longer exist are deleted and a migrated monolithic
+// snapshot is deleted once its sectors have been written. The store holds one
+// value per live sector plus the index, next to the journal, whose history is
+// pruned as snapshots are flushed or when the metadata is compacted (see
+// journal.rs). The space of overwritten and deleted values is reclaimed by
+// the store itself.
+//
+// A SectorBuilder which is shut down stores a shutdown marker next to the
+// index once its snapshot has been flushed, which is removed again when the
+// snapshot is next loaded. A snapshot without a marker was left behind by a
+// SectorBuilder which crashed.
+//
+// Every change to a sector is also appended to a journal (see journal.rs),
+// from which a snapshot which can't be read is rebuilt.
+//
+// Values are compressed with zstd and prefixed with a header which marks
+// them as compressed. Values without the header were persisted before
+// snapshots were compressed; a snapshot holding any of them is rewritten
+// when it's loaded.
 pub struct SnapshotKey {
     prover_id: [u8; 31],
     sector_size: PaddedBytesAmount,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct ShutdownMarker {
+    interrupted_seals: Vec<InterruptedSeal>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct SnapshotIndex {
+    sector_id_nonce: u64,
+    // the last journal entry reflected by the snapshot
+    #[serde(default)]
+    journal_seq: u64,
+    staged: Vec<SectorId>,
+    sealed: Vec<SectorId>,
+}
+
 impl SnapshotKey {
     pub fn new(prover_id: [u8; 31], sector_size: PaddedBytesAmount) -> SnapshotKey {
         SnapshotKey {
@@ -17,23 +78,203 @@ impl SnapshotKey {
             sector_size,
         }
     }
+
+    fn index_key(&self) -> Vec<u8> {
+        let mut key = Vec::from(self);
+        key.extend_from_slice(b"/index");
+        key
+    }
+
+    fn shutdown_key(&self) -> Vec<u8> {
+        let mut key = Vec::from(self);
+        key.extend_from_slice(b"/shutdown");
+        key
+    }
+
+    fn staged_sector_key(&self, sector_id: SectorId) -> Vec<u8> {
+        self.sector_key(b"/staged/", sector_id)
+    }
+
+    fn sealed_sector_key(&self, sector_id: SectorId) -> Vec<u8> {
+        self.sector_key(b"/sealed/", sector_id)
+    }
+
+    fn sector_key(&self, infix: &[u8], sector_id: SectorId) -> Vec<u8> {
+        let mut key = Vec::from(self);
+        key.extend_from_slice(infix);
+        key.write_u64::<LittleEndian>(u64::from(sector_id)).unwrap();
+        key
+    }
 }
 
+// Loads the state persisted under the provided key, along with the changes
+// journaled since it was persisted. A snapshot which can't be read, or whose
+// index has been lost, is rebuilt from the journal. A snapshot persisted
+// before sectors were stored under keys of their own holds the whole state
+// under the snapshot key itself; it's migrated to the per-sector layout when
+// it's loaded.
 pub fn load_snapshot<T: KeyValueStore>(
     kv_store: &T,
     key: &SnapshotKey,
 ) -> Result<Option<SectorBuilderState>> {
-    let result: Option<Vec<u8>> = kv_store.get(&Vec::from(key))?;
+    let loaded = match read_persisted_snapshot(kv_store, key) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            if journal_head(kv_store, key)? == 0 {
+                return Err(err);
+            }
+
+            warn!("rebuilding unreadable snapshot from journal: {}", err);
+
+            return rebuild_snapshot(kv_store, key);
+        }
+    };
+
+    match loaded {
+        Some((mut state, journal_seq, uncompressed)) => {
+            let monolithic = kv_store.get(&Vec::from(key))?.is_some();
+
+            if monolithic {
+                persist_snapshot(kv_store, key, &state)?;
+                kv_store.delete(&Vec::from(key))?;
+            }
+
+            sync_with_journal(kv_store, key, &mut state, journal_seq)?;
+
+            // an uncompressed snapshot is rewritten once it reflects the
+            // journal, as the rewritten index records the journal's head
+            if uncompressed && !monolithic {
+                persist_snapshot(kv_store, key, &state)?;
+            }
+
+            Ok(Some(state))
+        }
+        None => rebuild_snapshot(kv_store, key),
+    }
+}
+
+// Reads the state persisted under the provided key, along with the changes
+// journaled since it was persisted, without writing to the store, e.g. while
+// another process owns it. Neither is a snapshot migrated nor is one which
+// can't be read rebuilt; the state is replayed from the journal in memory
+// instead.
+pub fn read_snapshot<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+) -> Result<Option<SectorBuilderState>> {
+    let head = journal_head(kv_store, key)?;
+
+    let loaded = match read_persisted_snapshot(kv_store, key) {
+        Ok(loaded) => loaded,
+        Err(_) if head > 0 => None,
+        Err(err) => return Err(err),
+    };
+
+    let (mut state, journal_seq) = match loaded {
+        Some((state, journal_seq, _)) => (state, journal_seq),
+        None if head == 0 => return Ok(None),
+        None => (Default::default(), 0),
+    };
+
+    replay_journal(&mut state, &load_journal(kv_store, key, journal_seq)?);
+
+    Ok(Some(state))
+}
+
+// Reads the state persisted under the provided key, the number of the last
+// journal entry it reflects and whether any of its values were persisted
+// uncompressed.
+fn read_persisted_snapshot<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+) -> Result<Option<(SectorBuilderState, u64, bool)>> {
+    if let Some(val) = kv_store.get(&key.index_key())? {
+        let mut uncompressed = !is_compressed(&val);
+        let index: SnapshotIndex = decode_value(&val)?;
+
+        let mut state: SectorBuilderState = Default::default();
+        state.staged.sector_id_nonce = index.sector_id_nonce;
+
+        for sector_id in index.staged {
+            let val = kv_store
+                .get(&key.staged_sector_key(sector_id))?
+                .ok_or_else(|| {
+                    err_unrecov(format!("no metadata for staged sector {}", sector_id))
+                })?;
 
-    if let Some(val) = result {
-        return serde_cbor::from_slice(&val[..])
-            .map_err(failure::Error::from)
-            .map(Option::Some);
+            uncompressed |= !is_compressed(&val);
+            state.staged.sectors.insert(sector_id, decode_value(&val)?);
+        }
+
+        for sector_id in index.sealed {
+            let val = kv_store
+                .get(&key.sealed_sector_key(sector_id))?
+                .ok_or_else(|| {
+                    err_unrecov(format!("no metadata for sealed sector {}", sector_id))
+                })?;
+
+            uncompressed |= !is_compressed(&val);
+            state.sealed.sectors.insert(sector_id, decode_value(&val)?);
+        }
+
+        return Ok(Some((state, index.journal_seq, uncompressed)));
+    }
+
+    if let Some(val) = kv_store.get(&Vec::from(key))? {
+        let state: SectorBuilderState = serde_cbor::from_slice(&val[..])?;
+
+        return Ok(Some((state, 0, true)));
     }
 
     Ok(None)
 }
 
+// Loads the metadata of a single staged sector, without loading the rest of
+// the snapshot.
+pub fn load_staged_sector<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    sector_id: SectorId,
+) -> Result<Option<StagedSectorMetadata>> {
+    load_value(kv_store, &key.staged_sector_key(sector_id))
+}
+
+// Loads the metadata of a single sealed sector, without loading the rest of
+// the snapshot.
+pub fn load_sealed_sector<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    sector_id: SectorId,
+) -> Result<Option<SealedSectorMetadata>> {
+    load_value(kv_store, &key.sealed_sector_key(sector_id))
+}
+
+fn load_value<T: KeyValueStore, V: DeserializeOwned>(
+    kv_store: &T,
+    key: &[u8],
+) -> Result<Option<V>> {
+    match kv_store.get(key)? {
+        Some(val) => Ok(Some(decode_value(&val)?)),
+        None => Ok(None),
+    }
+}
+
+fn is_compressed(val: &[u8]) -> bool {
+    val.starts_with(&COMPRESSED_HEADER)
+}
+
+// Decodes a value persisted by encode_value, or an uncompressed value
+// persisted before snapshots were compressed.
+fn decode_value<V: DeserializeOwned>(val: &[u8]) -> Result<V> {
+    if is_compressed(val) {
+        let decoder = zstd::stream::Decoder::new(&val[COMPRESSED_HEADER.len()..])?;
+
+        Ok(serde_cbor::from_reader(decoder)?)
+    } else {
+        Ok(serde_cbor::from_slice(val)?)
+    }
+}
+
 impl From<&SnapshotKey> for Vec<u8> {
     fn from(n: &SnapshotKey) -> Self {
         // convert the sector size to a byte vector
@@ -49,22 +290,148 @@ impl From<&SnapshotKey> for Vec<u8> {
     }
 }
 
+// Persists every sector of the provided state.
 pub fn persist_snapshot<T: KeyValueStore>(
     kv_store: &T,
     key: &SnapshotKey,
     state: &SectorBuilderState,
 ) -> Result<()> {
-    let serialized = serde_cbor::to_vec(state)?;
-    kv_store.put(&Vec::from(key), &serialized)?;
+    let sector_ids: HashSet<SectorId> = state
+        .staged
+        .sectors
+        .keys()
+        .chain(state.sealed.sectors.keys())
+        .cloned()
+        .collect();
+
+    persist_sectors(kv_store, key, state, &sector_ids)
+}
+
+// Persists the provided sectors of the state, along with the index. Sectors
+// which no longer exist in the state are removed once the index no longer
+// refers to them. The state must reflect every journaled change.
+pub fn persist_sectors<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    state: &SectorBuilderState,
+    sector_ids: &HashSet<SectorId>,
+) -> Result<()> {
+    persist_sectors_and_journal(kv_store, key, state, sector_ids, &[], 0)
+}
+
+// Persists the provided sectors of the state like persist_sectors, along
+// with the journal entries recording the changes which the state reflects
+// and haven't been journaled yet. Entries up to and including the one with
+// the number prune_through are pruned from the journal.
+//
+// The sectors, the entries and the index are encoded into a single
+// preallocated buffer and written as one batch, which the store makes
+// durable at once rather than after every value.
+pub fn persist_sectors_and_journal<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    state: &SectorBuilderState,
+    sector_ids: &HashSet<SectorId>,
+    journal: &[JournalEntry],
+    prune_through: u64,
+) -> Result<()> {
+    let mut batch = WriteBatch::with_capacity(
+        2 * sector_ids.len() + 1,
+        2 * sector_ids.len() * ESTIMATED_SECTOR_VALUE_LEN,
+    );
+
+    for &sector_id in sector_ids {
+        let staged_key = key.staged_sector_key(sector_id);
+
+        match state.staged.sectors.get(&sector_id) {
+            Some(meta) => batch.put_with(staged_key, |buf| encode_value(buf, meta))?,
+            None => batch.delete(staged_key),
+        }
+
+        let sealed_key = key.sealed_sector_key(sector_id);
+
+        match state.sealed.sectors.get(&sector_id) {
+            Some(meta) => batch.put_with(sealed_key, |buf| encode_value(buf, meta))?,
+            None => batch.delete(sealed_key),
+        }
+    }
+
+    put_journal_entries(&mut batch, key, journal)?;
+    prune_journal_entries(kv_store, &mut batch, key, prune_through)?;
+
+    let journal_seq = match journal.last() {
+        Some(entry) => entry.seq,
+        None => journal_head(kv_store, key)?,
+    };
+
+    let mut index = SnapshotIndex {
+        sector_id_nonce: state.staged.sector_id_nonce,
+        journal_seq,
+        staged: state.staged.sectors.keys().cloned().collect(),
+        sealed: state.sealed.sectors.keys().cloned().collect(),
+    };
+
+    index.staged.sort();
+    index.sealed.sort();
+
+    batch.put_with(key.index_key(), |buf| encode_value(buf, &index))?;
+
+    kv_store.write_batch(&batch)
+}
+
+// Appends the compressed CBOR encoding of the provided value, preceded by
+// the compression header, to the buffer, without allocating a buffer of its
+// own as serde_cbor::to_vec does.
+fn encode_value<V: Serialize>(buf: &mut Vec<u8>, value: &V) -> Result<()> {
+    buf.extend_from_slice(&COMPRESSED_HEADER);
+
+    let mut encoder = zstd::stream::Encoder::new(buf, COMPRESSION_LEVEL)?;
+    serde_cbor::to_writer(&mut encoder, value)?;
+    encoder.finish()?;
+
     Ok(())
 }
 
+// Records that the SectorBuilder was shut down after flushing its snapshot,
+// along with the seals it interrupted.
+pub fn persist_shutdown_marker<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+    interrupted_seals: Vec<InterruptedSeal>,
+) -> Result<()> {
+    let marker = ShutdownMarker { interrupted_seals };
+
+    let mut buf = Vec::new();
+    encode_value(&mut buf, &marker)?;
+
+    kv_store.put(&key.shutdown_key(), &buf)
+}
+
+// Removes the shutdown marker, returning the seals it records as interrupted
+// if there was one and None if the SectorBuilder which persisted the
+// snapshot didn't shut down.
+pub fn take_shutdown_marker<T: KeyValueStore>(
+    kv_store: &T,
+    key: &SnapshotKey,
+) -> Result<Option<Vec<InterruptedSeal>>> {
+    let marker: Option<ShutdownMarker> = load_value(kv_store, &key.shutdown_key())?;
+
+    if marker.is_some() {
+        kv_store.delete(&key.shutdown_key())?;
+    }
+
+    Ok(marker.map(|marker| marker.interrupted_seals))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use im::HashMap;
+
+    use serde::Serialize;
 
     use crate::kv_store::SledKvs;
-    use crate::metadata::StagedSectorMetadata;
+    use crate::metadata::{SealFailure, SealStatus, StagedSectorMetadata};
+    use crate::sector_access::SectorAccess;
     use crate::state::StagedState;
     use storage_proofs::sector::SectorId;
 
@@ -133,4 +500,193 @@ mod tests {
         assert_eq!(snapshot_b, loaded_b);
         assert_eq!(true, lookup_miss.is_none());
     }
+
+    #[test]
+    fn test_persist_sectors() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let mut state: SectorBuilderState = Default::default();
+
+        for n in 1..=3 {
+            let sector_id = SectorId::from(n);
+
+            state.staged.sectors.insert(
+                sector_id,
+                StagedSectorMetadata {
+                    sector_id,
+                    ..Default::default()
+                },
+            );
+        }
+
+        persist_snapshot(&kv_store, &key, &state).unwrap();
+
+        // change one sector and remove another, persisting only those
+        state.staged.sector_id_nonce = 3;
+        state
+            .staged
+            .sectors
+            .get_mut(&SectorId::from(2))
+            .unwrap()
+            .sector_access = SectorAccess::new("s-2").unwrap();
+        state.staged.sectors.remove(&SectorId::from(3));
+
+        let dirty = vec![SectorId::from(2), SectorId::from(3)]
+            .into_iter()
+            .collect();
+
+        persist_sectors(&kv_store, &key, &state, &dirty).unwrap();
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+
+        assert_eq!(
+            "s-2",
+            load_staged_sector(&kv_store, &key, SectorId::from(2))
+                .unwrap()
+                .unwrap()
+                .sector_access
+                .as_str()
+        );
+
+        assert!(load_staged_sector(&kv_store, &key, SectorId::from(3))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_migrates_monolithic_snapshots() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let mut state: SectorBuilderState = Default::default();
+        state.staged.sector_id_nonce = 7;
+        state
+            .staged
+            .sectors
+            .insert(SectorId::from(7), Default::default());
+
+        // snapshots used to be stored as a whole under the snapshot key
+        kv_store
+            .put(&Vec::from(&key), &serde_cbor::to_vec(&state).unwrap())
+            .unwrap();
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+
+        assert!(kv_store.get(&Vec::from(&key)).unwrap().is_none());
+        assert!(kv_store.get(&key.index_key()).unwrap().is_some());
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_migrates_uncompressed_snapshots() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        let mut state: SectorBuilderState = Default::default();
+        state.staged.sector_id_nonce = 7;
+        state.staged.sectors.insert(
+            SectorId::from(7),
+            StagedSectorMetadata {
+                sector_id: SectorId::from(7),
+                ..Default::default()
+            },
+        );
+
+        // values used to be persisted without compression
+        let index = SnapshotIndex {
+            sector_id_nonce: 7,
+            journal_seq: 0,
+            staged: vec![SectorId::from(7)],
+            sealed: vec![],
+        };
+
+        kv_store
+            .put(&key.index_key(), &serde_cbor::to_vec(&index).unwrap())
+            .unwrap();
+        kv_store
+            .put(
+                &key.staged_sector_key(SectorId::from(7)),
+                &serde_cbor::to_vec(&state.staged.sectors[&SectorId::from(7)]).unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+
+        for k in &[key.index_key(), key.staged_sector_key(SectorId::from(7))] {
+            assert!(is_compressed(&kv_store.get(k).unwrap().unwrap()));
+        }
+
+        assert_eq!(state, load_snapshot(&kv_store, &key).unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_shutdown_marker_is_taken_once() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let kv_store = SledKvs::initialize(metadata_dir).unwrap();
+
+        let key = SnapshotKey::new([0; 31], PaddedBytesAmount(1024));
+
+        assert_eq!(None, take_shutdown_marker(&kv_store, &key).unwrap());
+
+        let interrupted_seals = vec![InterruptedSeal {
+            sector_id: SectorId::from(4),
+            dispatched: true,
+        }];
+
+        persist_shutdown_marker(&kv_store, &key, interrupted_seals.clone()).unwrap();
+
+        assert_eq!(
+            Some(interrupted_seals),
+            take_shutdown_marker(&kv_store, &key).unwrap()
+        );
+        assert_eq!(None, take_shutdown_marker(&kv_store, &key).unwrap());
+    }
+
+    #[test]
+    fn test_seal_failures_roundtrip() {
+        #[derive(Serialize)]
+        enum LegacySealStatus {
+            Failed(String),
+        }
+
+        // failures recorded before they were classified are still readable
+        let legacy = serde_cbor::to_vec(&LegacySealStatus::Failed("boom".to_string())).unwrap();
+        let status: SealStatus = serde_cbor::from_slice(&legacy).unwrap();
+
+        assert_eq!(
+            SealStatus::Failed(SealFailure::Other("boom".to_string())),
+            status
+        );
+
+        for failure in vec![
+            SealFailure::Cancelled,
+            SealFailure::OutOfMemory,
+            SealFailure::TimedOut,
+            SealFailure::Interrupted,
+            SealFailure::WorkerCrashed("index out of bounds".to_string()),
+            SealFailure::StagedDataCorrupted("piece".to_string()),
+            SealFailure::ParamsMissing("v26-proof".to_string()),
+            SealFailure::Io {
+                path: "/sealed/s-1".into(),
+                errno: Some(28),
+                msg: "no space left on device".to_string(),
+            },
+        ] {
+            let status = SealStatus::Failed(failure);
+            let serialized = serde_cbor::to_vec(&status).unwrap();
+
+            assert_eq!(status, serde_cbor::from_slice(&serialized).unwrap());
+        }
+    }
 }
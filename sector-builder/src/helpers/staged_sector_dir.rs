@@ -0,0 +1,154 @@
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+use crate::metadata::StagedSectorDir;
+use crate::state::StagedState;
+use crate::store::{SectorManager, SectorStore};
+
+// Produces the manager of the sector-files in the provided staged sector
+// directory. A builder won't start with spilled over sectors unless a
+// secondary directory is configured (see check_staged_sector_dirs).
+pub fn staged_sector_manager<S: SectorStore>(
+    sector_store: &S,
+    staged_dir: StagedSectorDir,
+) -> &dyn SectorManager {
+    match staged_dir {
+        StagedSectorDir::Primary => sector_store.manager(),
+        StagedSectorDir::Secondary => sector_store
+            .secondary_staged_manager()
+            .unwrap_or_else(|| sector_store.manager()),
+    }
+}
+
+// Picks the staged sector directory in which a new staged sector is
+// provisioned: the secondary one if one is configured and fewer bytes than
+// its threshold are available in the primary one, else the primary one.
+pub fn choose_staged_sector_dir<S: SectorStore>(sector_store: &S) -> Result<StagedSectorDir> {
+    if sector_store.secondary_staged_manager().is_none() {
+        return Ok(StagedSectorDir::Primary);
+    }
+
+    let available_bytes = fs2::available_space(sector_store.manager().staged_sector_dir())?;
+
+    if available_bytes < sector_store.staged_spill_over_min_free_bytes() {
+        Ok(StagedSectorDir::Secondary)
+    } else {
+        Ok(StagedSectorDir::Primary)
+    }
+}
+
+// Fails if a staged sector spilled over into the secondary staged sector
+// directory while none is configured, as its sector-file can't be found.
+pub fn check_staged_sector_dirs<S: SectorStore>(
+    sector_store: &S,
+    staged_state: &StagedState,
+) -> Result<()> {
+    if sector_store.secondary_staged_manager().is_some() {
+        return Ok(());
+    }
+
+    let spilled_over: Option<SectorId> = staged_state
+        .sectors
+        .values()
+        .filter(|meta| meta.staged_dir == StagedSectorDir::Secondary)
+        .map(|meta| meta.sector_id)
+        .min();
+
+    match spilled_over {
+        Some(sector_id) => Err(err_unrecov(format!(
+            "staged sector {} is in the secondary staged sector directory, which isn't configured",
+            sector_id
+        ))
+        .into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+
+    use crate::disk_backed_storage::{new_sector_store, new_sector_store_with_spill_over};
+    use crate::metadata::{StagedSectorMetadata, StagedSpillOver};
+
+    use super::*;
+
+    const TEST_CLASS: SectorClass =
+        SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2));
+
+    #[test]
+    fn test_spills_over_while_the_primary_dir_is_low_on_space() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+        let secondary_dir = tempfile::tempdir().unwrap();
+
+        let new_store = |min_free_bytes: u64| {
+            new_sector_store_with_spill_over(
+                TEST_CLASS,
+                sealed_dir.path(),
+                staged_dir.path(),
+                None,
+                Default::default(),
+                Some(StagedSpillOver {
+                    dir: secondary_dir.path().to_path_buf(),
+                    min_free_bytes,
+                }),
+            )
+        };
+
+        // no directory has fewer than 0 bytes available
+        assert_eq!(
+            StagedSectorDir::Primary,
+            choose_staged_sector_dir(&new_store(0)).unwrap()
+        );
+
+        // and every directory has fewer than u64::MAX bytes available
+        let store = new_store(std::u64::MAX);
+        assert_eq!(
+            StagedSectorDir::Secondary,
+            choose_staged_sector_dir(&store).unwrap()
+        );
+
+        let mgr = staged_sector_manager(&store, StagedSectorDir::Secondary);
+        let access = mgr.new_staging_sector_access(SectorId::from(1)).unwrap();
+
+        let path = mgr.staged_sector_path(&access);
+        assert!(path.starts_with(secondary_dir.path()));
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_spilled_over_sectors_need_a_secondary_dir() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+        let store = new_sector_store(TEST_CLASS, sealed_dir.path(), staged_dir.path());
+
+        assert_eq!(
+            StagedSectorDir::Primary,
+            choose_staged_sector_dir(&store).unwrap()
+        );
+
+        let mut staged_state = StagedState::default();
+        staged_state.sectors.insert(
+            SectorId::from(1),
+            StagedSectorMetadata {
+                sector_id: SectorId::from(1),
+                ..Default::default()
+            },
+        );
+
+        assert!(check_staged_sector_dirs(&store, &staged_state).is_ok());
+
+        staged_state.sectors.insert(
+            SectorId::from(2),
+            StagedSectorMetadata {
+                sector_id: SectorId::from(2),
+                staged_dir: StagedSectorDir::Secondary,
+                ..Default::default()
+            },
+        );
+
+        assert!(check_staged_sector_dirs(&store, &staged_state).is_err());
+    }
+}
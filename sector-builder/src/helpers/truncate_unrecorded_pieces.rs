@@ -0,0 +1,137 @@
+use filecoin_proofs::pieces::sum_piece_bytes_with_alignment;
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+use crate::helpers::staged_sector_manager;
+use crate::metadata::SealStatus;
+use crate::state::StagedState;
+use crate::store::{SectorManager, SectorStore};
+
+// Truncates the staged sectors which accept pieces to the pieces recorded in
+// their metadata, returning the ids of the sectors which were truncated.
+//
+// A piece is written to its staged sector before the metadata recording it
+// is persisted, so a staged sector left behind by a crash may hold the bytes
+// of a piece which the metadata doesn't know of. Subsequent pieces would be
+// written after those bytes, where the metadata doesn't expect them.
+pub fn truncate_unrecorded_pieces<S: SectorStore>(
+    sector_store: &S,
+    staged_state: &StagedState,
+) -> Result<Vec<SectorId>> {
+    let mut truncated = Vec::new();
+
+    for meta in staged_state.sectors.values() {
+        if meta.seal_status != SealStatus::Pending {
+            continue;
+        }
+
+        let piece_lens: Vec<_> = meta.pieces.iter().map(|p| p.num_bytes).collect();
+        let recorded = u64::from(sum_piece_bytes_with_alignment(&piece_lens));
+
+        let mgr = staged_sector_manager(sector_store, meta.staged_dir);
+        let written = mgr.num_unsealed_bytes(&meta.sector_access)?;
+
+        if written > recorded {
+            warn!(
+                "truncating staged sector {} from {} to {} bytes",
+                meta.sector_id, written, recorded
+            );
+
+            mgr.truncate_unsealed(&meta.sector_access, recorded)?;
+            truncated.push(meta.sector_id);
+        }
+    }
+
+    truncated.sort();
+
+    Ok(truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{
+        PoRepProofPartitions, SectorClass, SectorSize, UnpaddedBytesAmount,
+    };
+
+    use crate::disk_backed_storage::new_sector_store;
+    use crate::metadata::{PieceMetadata, StagedSectorMetadata};
+
+    use super::*;
+
+    #[test]
+    fn test_truncate_unrecorded_pieces() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let staged_dir = tempfile::tempdir().unwrap();
+
+        let store = new_sector_store(
+            SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2)),
+            sealed_dir.path(),
+            staged_dir.path(),
+        );
+
+        let mut staged_state: StagedState = Default::default();
+
+        // both sectors hold a piece, which only the first one's metadata
+        // records
+        for (n, recorded) in vec![(1, true), (2, false)] {
+            let sector_id = SectorId::from(n);
+            let sector_access = store
+                .manager()
+                .new_staging_sector_access(sector_id)
+                .unwrap();
+
+            store
+                .manager()
+                .write_and_preprocess(&sector_access, &mut &[7; 127][..])
+                .unwrap();
+
+            let pieces = if recorded {
+                vec![PieceMetadata {
+                    piece_key: format!("piece-{}", n),
+                    num_bytes: UnpaddedBytesAmount(127),
+                    comm_p: None,
+                    piece_inclusion_proof: None,
+                    idempotency_key: None,
+                    store_until: None,
+                    deal_id: None,
+                    labels: Default::default(),
+                }]
+            } else {
+                vec![]
+            };
+
+            staged_state.sectors.insert(
+                sector_id,
+                StagedSectorMetadata {
+                    sector_id,
+                    sector_access,
+                    pieces,
+                    ..Default::default()
+                },
+            );
+        }
+
+        assert_eq!(
+            vec![SectorId::from(2)],
+            truncate_unrecorded_pieces(&store, &staged_state).unwrap()
+        );
+
+        let num_unsealed_bytes = |n: u64| {
+            let meta = &staged_state.sectors[&SectorId::from(n)];
+
+            store
+                .manager()
+                .num_unsealed_bytes(&meta.sector_access)
+                .unwrap()
+        };
+
+        assert_eq!(127, num_unsealed_bytes(1));
+        assert_eq!(0, num_unsealed_bytes(2));
+
+        // sectors which match their metadata are left alone
+        assert!(truncate_unrecorded_pieces(&store, &staged_state)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use filecoin_proofs::types::{PoRepConfig, PoRepProofPartitions, SectorSize, UnpaddedBytesAmount};
+use filecoin_proofs::SealOutput;
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_task_cancelled, err_unrecov, Result};
+use crate::watchdog::{CancellationToken, TaskKind};
+
+// How often a running child process is checked for having exited, and its
+// seal for having been stopped.
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where workers seal sectors.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SealIsolation {
+    /// seal on the worker's thread, in the builder's process
+    InProcess,
+    /// seal in a child process running the provided worker binary (built from
+    /// src/bin/sector-builder-worker.rs), so that a crash (e.g. of a GPU
+    /// driver, an OOM kill or a panic in the proofs) takes down the child
+    /// only. A child which crashes is restarted up to max_restarts times,
+    /// after which sealing fails.
+    ChildProcess {
+        worker_bin: PathBuf,
+        max_restarts: u32,
+    },
+}
+
+impl Default for SealIsolation {
+    fn default() -> SealIsolation {
+        SealIsolation::InProcess
+    }
+}
+
+// The seal task handed to a child process, on its stdin.
+#[derive(Serialize, Deserialize)]
+struct IsolatedSealTask {
+    sector_size: u64,
+    porep_proof_partitions: u8,
+    staged_sector_path: PathBuf,
+    sealed_sector_path: PathBuf,
+    prover_id: [u8; 31],
+    sector_id: u64,
+    piece_lens: Vec<u64>,
+}
+
+// The output of a seal task, written by a child process to its stdout.
+#[derive(Serialize, Deserialize)]
+struct IsolatedSealOutput {
+    comm_r: [u8; 32],
+    comm_r_star: [u8; 32],
+    comm_d: [u8; 32],
+    proof: Vec<u8>,
+    comm_ps: Vec<[u8; 32]>,
+    piece_inclusion_proofs: Vec<Vec<u8>>,
+}
+
+// Seals a sector in a child process running the provided worker binary,
+// restarting the child if it crashes, including before it has read the whole
+// task. A child which completes the task reports sealing errors as any other
+// result, which aren't retried. The child is killed once the stop token is
+// cancelled, e.g. because the seal timed out, and is always reaped.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_in_child_process(
+    worker_bin: &Path,
+    max_restarts: u32,
+    stop: &CancellationToken,
+    porep_config: PoRepConfig,
+    staged_sector_path: &Path,
+    sealed_sector_path: &Path,
+    prover_id: &[u8; 31],
+    sector_id: SectorId,
+    piece_lens: &[UnpaddedBytesAmount],
+) -> Result<SealOutput> {
+    let PoRepConfig(SectorSize(sector_size), PoRepProofPartitions(porep_proof_partitions)) =
+        porep_config;
+
+    let task = serde_json::to_vec(&IsolatedSealTask {
+        sector_size,
+        porep_proof_partitions,
+        staged_sector_path: staged_sector_path.to_path_buf(),
+        sealed_sector_path: sealed_sector_path.to_path_buf(),
+        prover_id: *prover_id,
+        sector_id: u64::from(sector_id),
+        piece_lens: piece_lens.iter().map(|len| u64::from(*len)).collect(),
+    })?;
+
+    let mut num_restarts = 0;
+
+    loop {
+        let mut child = Command::new(worker_bin)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        // the output is read while the child runs, so that a child writing
+        // more than the pipe holds doesn't block
+        let reader = {
+            let mut stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| err_unrecov("could not open seal worker's stdout"))?;
+
+            thread::spawn(move || -> io::Result<Vec<u8>> {
+                let mut output = Vec::new();
+                stdout.read_to_end(&mut output)?;
+                Ok(output)
+            })
+        };
+
+        // A child which dies before it has read the whole task fails the
+        // write, e.g. with EPIPE, which is handled like any other crash.
+        // Dropping stdin closes it, which ends the task.
+        let written = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(&task),
+            None => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "seal worker's stdin is closed",
+            )),
+        };
+
+        let status = wait_for_child(&mut child, stop);
+
+        let output = reader
+            .join()
+            .map_err(|_| err_unrecov("could not read seal worker's output"))?;
+
+        let status = match status? {
+            Some(status) => status,
+            None => return Err(err_task_cancelled(TaskKind::Seal).into()),
+        };
+
+        let crash = match written {
+            Ok(()) if status.success() => {
+                let result: std::result::Result<IsolatedSealOutput, String> =
+                    serde_json::from_slice(&output?)?;
+
+                return result
+                    .map_err(|msg| err_unrecov(msg).into())
+                    .and_then(into_seal_output);
+            }
+            Ok(()) => format!("{}", status),
+            Err(err) => format!("{}, before it took the task: {}", status, err),
+        };
+
+        if num_restarts >= max_restarts {
+            return Err(err_unrecov(format!(
+                "seal worker for sector {} crashed ({}) after {} restarts",
+                sector_id, crash, num_restarts
+            ))
+            .into());
+        }
+
+        num_restarts += 1;
+
+        warn!(
+            "seal worker for sector {} crashed ({}), restarting it ({} of {})",
+            sector_id, crash, num_restarts, max_restarts
+        );
+    }
+}
+
+// Waits for the child to exit, returning its exit status, or None if it was
+// killed because the stop token was cancelled first. The child is reaped
+// either way.
+fn wait_for_child(child: &mut Child, stop: &CancellationToken) -> io::Result<Option<ExitStatus>> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(Some(status)),
+            Ok(None) if !stop.is_cancelled() => thread::sleep(CHILD_POLL_INTERVAL),
+            Ok(None) => {
+                // the child may have exited in the meantime
+                let _ = child.kill();
+                child.wait()?;
+
+                return Ok(None);
+            }
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+
+                return Err(err);
+            }
+        }
+    }
+}
+
+// Runs the seal task read from stdin, writing its output to stdout. This is
+// what the worker binary of a child process does.
+pub fn run_isolated_seal() -> Result<()> {
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input)?;
+
+    let task: IsolatedSealTask = serde_json::from_slice(&input)?;
+
+    let piece_lens: Vec<UnpaddedBytesAmount> = task
+        .piece_lens
+        .into_iter()
+        .map(UnpaddedBytesAmount)
+        .collect();
+
+    let result = filecoin_proofs::seal(
+        PoRepConfig(
+            SectorSize(task.sector_size),
+            PoRepProofPartitions(task.porep_proof_partitions),
+        ),
+        &task.staged_sector_path,
+        &task.sealed_sector_path,
+        &task.prover_id,
+        SectorId::from(task.sector_id),
+        &piece_lens,
+    )
+    .map(from_seal_output)
+    .map_err(|err| format!("{}", err));
+
+    io::stdout().write_all(&serde_json::to_vec(&result)?)?;
+
+    Ok(())
+}
+
+fn from_seal_output(output: SealOutput) -> IsolatedSealOutput {
+    IsolatedSealOutput {
+        comm_r: output.comm_r,
+        comm_r_star: output.comm_r_star,
+        comm_d: output.comm_d,
+        proof: output.proof,
+        comm_ps: output.comm_ps,
+        piece_inclusion_proofs: output
+            .piece_inclusion_proofs
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    }
+}
+
+fn into_seal_output(output: IsolatedSealOutput) -> Result<SealOutput> {
+    let piece_inclusion_proofs = output
+        .piece_inclusion_proofs
+        .iter()
+        .map(|bytes| {
+            bytes.as_slice().try_into().map_err(|_| {
+                err_unrecov("seal worker produced an invalid piece inclusion proof").into()
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(SealOutput {
+        comm_r: output.comm_r,
+        comm_r_star: output.comm_r_star,
+        comm_d: output.comm_d,
+        proof: output.proof,
+        comm_ps: output.comm_ps,
+        piece_inclusion_proofs,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn gives_up_on_workers_which_keep_crashing() {
+        let dir = tempfile::tempdir().unwrap();
+        let worker_bin = dir.path().join("crashing-worker");
+
+        fs::write(&worker_bin, "#!/bin/sh\ncat > /dev/null\nexit 101\n").unwrap();
+        fs::set_permissions(&worker_bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let err = seal_in_child_process(
+            &worker_bin,
+            2,
+            &Default::default(),
+            PoRepConfig(SectorSize(1024), PoRepProofPartitions(2)),
+            &dir.path().join("staged"),
+            &dir.path().join("sealed"),
+            &[0; 31],
+            SectorId::from(1),
+            &[UnpaddedBytesAmount(127)],
+        )
+        .unwrap_err();
+
+        assert!(format!("{}", err).contains("after 2 restarts"));
+    }
+
+    #[test]
+    fn restarts_workers_which_exit_without_reading_the_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let worker_bin = dir.path().join("exiting-worker");
+
+        // writing the task fails with EPIPE if the child has exited by then
+        fs::write(&worker_bin, "#!/bin/sh\nexit 101\n").unwrap();
+        fs::set_permissions(&worker_bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let err = seal_in_child_process(
+            &worker_bin,
+            1,
+            &Default::default(),
+            PoRepConfig(SectorSize(1024), PoRepProofPartitions(2)),
+            &dir.path().join("staged"),
+            &dir.path().join("sealed"),
+            &[0; 31],
+            SectorId::from(1),
+            &[UnpaddedBytesAmount(127)],
+        )
+        .unwrap_err();
+
+        assert!(format!("{}", err).contains("after 1 restarts"));
+    }
+
+    #[test]
+    fn kills_workers_once_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let worker_bin = dir.path().join("hanging-worker");
+
+        fs::write(&worker_bin, "#!/bin/sh\nexec sleep 60\n").unwrap();
+        fs::set_permissions(&worker_bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let stop = CancellationToken::default();
+        stop.cancel();
+
+        let started = std::time::Instant::now();
+
+        let err = seal_in_child_process(
+            &worker_bin,
+            2,
+            &stop,
+            PoRepConfig(SectorSize(1024), PoRepProofPartitions(2)),
+            &dir.path().join("staged"),
+            &dir.path().join("sealed"),
+            &[0; 31],
+            SectorId::from(1),
+            &[UnpaddedBytesAmount(127)],
+        )
+        .unwrap_err();
+
+        assert!(format!("{}", err).contains("cancelled"));
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -4,16 +4,18 @@ use std::path::{Path, PathBuf};
 
 use blake2b_simd::State as Blake2b;
 
-use crate::error::Result;
-use crate::kv_store::KeyValueStore;
+use crate::error::{err_unrecov, Result};
+use crate::kv_store::{dir_size, KeyValueStore, KvStoreStats};
 
 const FATAL_NOCREATE: &str = "[KeyValueStore#put] could not create path";
+const ERR_READONLY: &str = "[KeyValueStore] store has been opened read-only";
 
 // FileSystemKvs is a file system-backed key/value store, mostly lifted from
 // sile/ekvsb
 #[derive(Debug)]
 pub struct FileSystemKvs {
     root_dir: PathBuf,
+    read_only: bool,
 }
 
 impl FileSystemKvs {
@@ -32,10 +34,30 @@ impl KeyValueStore for FileSystemKvs {
 
         Ok(FileSystemKvs {
             root_dir: root_dir.as_ref().to_path_buf(),
+            read_only: false,
+        })
+    }
+
+    fn open_read_only<P: AsRef<Path>>(root_dir: P) -> Result<Self> {
+        if !root_dir.as_ref().is_dir() {
+            return Err(err_unrecov(format!(
+                "[KeyValueStore] no store at {}",
+                root_dir.as_ref().display()
+            ))
+            .into());
+        }
+
+        Ok(FileSystemKvs {
+            root_dir: root_dir.as_ref().to_path_buf(),
+            read_only: true,
         })
     }
 
     fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if self.read_only {
+            return Err(err_unrecov(ERR_READONLY).into());
+        }
+
         let path = self.key_to_path(key);
 
         fs::create_dir_all(path.parent().expect(FATAL_NOCREATE))?;
@@ -69,6 +91,32 @@ impl KeyValueStore for FileSystemKvs {
             }
         }
     }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        if self.read_only {
+            return Err(err_unrecov(ERR_READONLY).into());
+        }
+
+        match fs::remove_file(self.key_to_path(key)) {
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    Err(e.into())
+                } else {
+                    Ok(())
+                }
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    // Each value is written to a file of its own, which isn't buffered.
+    fn stats(&self) -> Result<KvStoreStats> {
+        Ok(KvStoreStats {
+            size_on_disk: dir_size(&self.root_dir)?,
+            num_keys: fs::read_dir(&self.root_dir)?.count() as u64,
+            last_flush: None,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -90,5 +138,41 @@ mod tests {
 
         let opt = db.get(k_a).unwrap();
         assert_eq!(format!("{:x?}", opt.unwrap()), format!("{:x?}", v_a));
+
+        db.delete(k_a).unwrap();
+        db.delete(k_a).unwrap();
+
+        assert!(db.get(k_a).unwrap().is_none());
+        assert!(db.get(k_b).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_read_only() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+        let db = FileSystemKvs::initialize(metadata_dir.path()).unwrap();
+
+        db.put(b"key-xx", b"value-aa").unwrap();
+
+        let read_only = FileSystemKvs::open_read_only(metadata_dir.path()).unwrap();
+
+        assert!(read_only.get(b"key-xx").unwrap().is_some());
+        assert!(read_only.put(b"key-yy", b"value-bb").is_err());
+        assert!(read_only.delete(b"key-xx").is_err());
+        assert!(db.get(b"key-xx").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_stats() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+        let db = FileSystemKvs::initialize(metadata_dir.path()).unwrap();
+
+        db.put(b"key-xx", b"value-aa").unwrap();
+        db.put(b"key-yy", b"value-bbbb").unwrap();
+
+        let stats = db.stats().unwrap();
+
+        assert_eq!(2, stats.num_keys);
+        assert_eq!(18, stats.size_on_disk);
+        assert_eq!(None, stats.last_flush);
     }
 }
@@ -1,6 +1,8 @@
+use std::ops::Range;
 use std::path::Path;
 
 use crate::error::Result;
+use crate::metadata::SecondsSinceEpoch;
 
 mod fs;
 mod sled;
@@ -10,6 +12,109 @@ pub use self::sled::*;
 
 pub trait KeyValueStore: Sized + Sync + Send {
     fn initialize<P: AsRef<Path>>(root_dir: P) -> Result<Self>;
+    // Opens an existing store without taking a write lock on it. Writing to
+    // a store opened this way produces an error.
+    fn open_read_only<P: AsRef<Path>>(root_dir: P) -> Result<Self>;
     fn put(&self, key: &[u8], value: &[u8]) -> Result<()>;
     fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    fn delete(&self, key: &[u8]) -> Result<()>;
+    fn stats(&self) -> Result<KvStoreStats>;
+
+    // Applies the puts of the provided batch, in order, followed by its
+    // deletes. Stores which make each write durable on its own may make the
+    // batch durable once, after its last write, instead.
+    fn write_batch(&self, batch: &WriteBatch) -> Result<()> {
+        for (key, value) in batch.puts() {
+            self.put(key, value)?;
+        }
+
+        for key in batch.deletes() {
+            self.delete(key)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics of a key/value store, with which the growth of the metadata
+/// can be monitored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KvStoreStats {
+    /// number of bytes in the files of the store
+    pub size_on_disk: u64,
+    pub num_keys: u64,
+    /// when the store last flushed its writes to disk, or None if it hasn't
+    /// since it was opened (or doesn't buffer writes)
+    pub last_flush: Option<SecondsSinceEpoch>,
+}
+
+// Returns the number of bytes in the files in the provided directory and its
+// subdirectories.
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+// A batch of puts and deletes, written by KeyValueStore::write_batch. The
+// values of the puts are encoded back to back into a single buffer, which
+// can be preallocated for the expected size of the batch.
+#[derive(Debug, Default)]
+pub struct WriteBatch {
+    buf: Vec<u8>,
+    puts: Vec<(Vec<u8>, Range<usize>)>,
+    deletes: Vec<Vec<u8>>,
+}
+
+impl WriteBatch {
+    pub fn with_capacity(num_puts: usize, num_value_bytes: usize) -> WriteBatch {
+        WriteBatch {
+            buf: Vec::with_capacity(num_value_bytes),
+            puts: Vec::with_capacity(num_puts),
+            deletes: Default::default(),
+        }
+    }
+
+    // Adds a put of the value which the provided function appends to the
+    // buffer it's given.
+    pub fn put_with<F>(&mut self, key: Vec<u8>, encode: F) -> Result<()>
+    where
+        F: FnOnce(&mut Vec<u8>) -> Result<()>,
+    {
+        let start = self.buf.len();
+
+        if let Err(err) = encode(&mut self.buf) {
+            self.buf.truncate(start);
+            return Err(err);
+        }
+
+        self.puts.push((key, start..self.buf.len()));
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: Vec<u8>) {
+        self.deletes.push(key);
+    }
+
+    pub fn puts(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.puts
+            .iter()
+            .map(move |(key, range)| (&key[..], &self.buf[range.clone()]))
+    }
+
+    pub fn deletes(&self) -> impl Iterator<Item = &[u8]> {
+        self.deletes.iter().map(|key| &key[..])
+    }
 }
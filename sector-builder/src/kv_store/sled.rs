@@ -1,30 +1,97 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use sled::Db;
+use sled::{ConfigBuilder, Db};
 
 use crate::error::Result;
-use crate::kv_store::KeyValueStore;
+use crate::kv_store::{dir_size, KeyValueStore, KvStoreStats, WriteBatch};
+use crate::metadata::SecondsSinceEpoch;
 
 pub struct SledKvs {
     db: Db,
+    path: PathBuf,
+    // seconds since the epoch at which the store was last flushed, 0 if it
+    // hasn't been since it was opened
+    last_flush: AtomicU64,
+}
+
+impl SledKvs {
+    fn new(db: Db, path: &Path) -> SledKvs {
+        SledKvs {
+            db,
+            path: path.to_path_buf(),
+            last_flush: AtomicU64::new(0),
+        }
+    }
+
+    fn flush(&self) -> Result<()> {
+        let _ = self.db.flush()?;
+        self.last_flush
+            .store(SecondsSinceEpoch::now().0, Ordering::SeqCst);
+        Ok(())
+    }
 }
 
 impl KeyValueStore for SledKvs {
     fn initialize<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let db = Db::start_default(path)?;
-        Ok(SledKvs { db })
+        let db = Db::start_default(path.as_ref())?;
+        Ok(SledKvs::new(db, path.as_ref()))
+    }
+
+    // sled takes a shared lock on a store opened read-only, which can't be
+    // taken while another process has the store open for writing.
+    fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config = ConfigBuilder::new()
+            .path(path.as_ref())
+            .read_only(true)
+            .build();
+        let db = Db::start(config)?;
+        Ok(SledKvs::new(db, path.as_ref()))
     }
 
     fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
         self.db.set(key, value)?;
-        let _ = self.db.flush()?;
-        Ok(())
+        self.flush()
     }
 
     fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let value = self.db.get(key)?;
         Ok(value.map(|x| x.to_vec()))
     }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        self.db.del(key)?;
+        self.flush()
+    }
+
+    // Flushing dominates the cost of a put, so a batch is flushed once.
+    fn write_batch(&self, batch: &WriteBatch) -> Result<()> {
+        for (key, value) in batch.puts() {
+            self.db.set(key, value)?;
+        }
+
+        for key in batch.deletes() {
+            self.db.del(key)?;
+        }
+
+        self.flush()
+    }
+
+    // The size on disk includes values which sled has yet to compact away,
+    // so it growing much faster than the number of keys hints at compaction
+    // falling behind.
+    fn stats(&self) -> Result<KvStoreStats> {
+        let last_flush = match self.last_flush.load(Ordering::SeqCst) {
+            0 => None,
+            secs => Some(SecondsSinceEpoch(secs)),
+        };
+
+        Ok(KvStoreStats {
+            size_on_disk: dir_size(&self.path)?,
+            num_keys: self.db.len() as u64,
+            last_flush,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -47,5 +114,48 @@ mod tests {
 
         let opt = db.get(k_a).unwrap();
         assert_eq!(format!("{:x?}", opt.unwrap()), format!("{:x?}", v_a));
+
+        db.delete(k_a).unwrap();
+        db.delete(k_a).unwrap();
+
+        assert!(db.get(k_a).unwrap().is_none());
+        assert!(db.get(k_b).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_write_batch() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let db = SledKvs::initialize(metadata_dir).unwrap();
+
+        db.put(b"key-xx", b"value-aa").unwrap();
+
+        let mut batch = WriteBatch::with_capacity(2, 16);
+
+        batch
+            .put_with(b"key-yy".to_vec(), |buf| {
+                buf.extend_from_slice(b"value-bb");
+                Ok(())
+            })
+            .unwrap();
+        batch
+            .put_with(b"key-zz".to_vec(), |buf| {
+                buf.extend_from_slice(b"value-cc");
+                Ok(())
+            })
+            .unwrap();
+        batch.delete(b"key-xx".to_vec());
+
+        db.write_batch(&batch).unwrap();
+
+        let stats = db.stats().unwrap();
+
+        assert_eq!(2, stats.num_keys);
+        assert!(stats.size_on_disk > 0);
+        assert!(stats.last_flush.is_some());
+
+        assert!(db.get(b"key-xx").unwrap().is_none());
+        assert_eq!(Some(b"value-bb".to_vec()), db.get(b"key-yy").unwrap());
+        assert_eq!(Some(b"value-cc".to_vec()), db.get(b"key-zz").unwrap());
     }
 }
@@ -7,27 +7,87 @@ extern crate log;
 
 pub use filecoin_proofs::types::*;
 
+pub use crate::affinity::{parse_cpu_list, WorkerAffinity};
 pub use crate::builder::*;
+pub use crate::dir_lock::DirLock;
+pub use crate::encryption::{decrypt_staged_sector, DecryptedStagedSector, StagedDataKey};
+pub use crate::clock::{Clock, SystemClock};
 pub use crate::constants::*;
 pub use crate::error::*;
+pub use crate::events::*;
 // Exported for benchmarks
+pub use crate::disk_backed_storage::{
+    new_sector_store, new_sector_store_with_access_proto, new_sector_store_with_spill_over,
+    new_sector_store_with_staged_data_key, ConcreteSectorStore, SectorAccessProto,
+};
+pub use crate::handoff::HandoffBundle;
+pub use crate::helpers::calculate_extent_checksums;
 pub use crate::helpers::checksum::calculate_checksum;
+pub use crate::helpers::generate_piece_commitments;
+pub use crate::helpers::migrate_to_miner_layout;
+pub use crate::helpers::{prover_id_from_bytes, prover_id_to_bytes};
+pub use crate::helpers::{
+    compute_destination_sector_id, find_destination_sector_id, get_sectors_ready_for_sealing,
+    piece_layouts,
+};
+pub use crate::helpers::{
+    load_journal, load_piece_manifest, load_snapshot, persist_snapshot, rebuild_snapshot,
+    JournalEntry, ManifestEntry, SectorTransition, SnapshotKey,
+};
+pub use crate::isolation::{run_isolated_seal, SealIsolation};
+pub use crate::kv_store::{KeyValueStore, KvStoreStats, SledKvs, WriteBatch};
+pub use crate::state::{
+    PublishedState, SealState, SealedState, SectorBuilderState, StagedState,
+};
 pub use crate::metadata::*;
 pub use crate::metadata_manager::*;
+pub use crate::multi_miner_builder::*;
+pub use crate::quota::*;
+pub use crate::rate_limiter::{RateLimiter, ThrottledReader};
+pub use crate::replication::{CommandReplicator, DirReplicator, SealedSectorReplicator};
+pub use crate::read_only_builder::ReadOnlySectorBuilder;
+pub use crate::scheduler::{OverloadPolicy, SchedulerConfig};
+pub use crate::seal_queue::SealQueueEntry;
+pub use crate::sector_access::SectorAccess;
+pub use crate::sector_id_provider::*;
 pub use crate::store::*;
 pub use crate::simple_builder::*;
+pub use crate::staged_writer::{
+    StagedWriteConfig, StagedWriteStrategy, StagedWriter, DEFAULT_STAGED_WRITE_BUFFER_SIZE,
+};
+pub use crate::watchdog::{CancellationToken, TaskKind, TaskTimeouts};
+pub use crate::worker::{SealTaskPrototype, UnsealTaskPrototype};
 
+mod affinity;
 mod builder;
+mod clock;
 mod constants;
+mod dir_lock;
 mod disk_backed_storage;
+mod encryption;
 mod error;
+mod events;
+mod handoff;
 mod helpers;
+mod isolation;
 mod kv_store;
 mod metadata;
 mod metadata_manager;
+mod multi_miner_builder;
+mod quota;
+mod rate_limiter;
+mod read_only_builder;
+mod replication;
 mod scheduler;
+mod seal_queue;
+mod sector_access;
+mod sector_id_provider;
+mod staged_writer;
 mod state;
 mod store;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+mod watchdog;
 mod worker;
 
 mod simple_builder;
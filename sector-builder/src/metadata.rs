@@ -1,28 +1,294 @@
-use filecoin_proofs::types::UnpaddedBytesAmount;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use filecoin_proofs::types::{PaddedBytesAmount, PoStConfig, SectorClass, UnpaddedBytesAmount};
 use serde::{Deserialize, Serialize};
 use storage_proofs::sector::SectorId;
 
+use crate::quota::StorageQuota;
+use crate::sector_access::SectorAccess;
+use crate::watchdog::CancellationToken;
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct StagedSectorMetadata {
     pub sector_id: SectorId,
-    pub sector_access: String,
+    pub sector_access: SectorAccess,
     pub pieces: Vec<PieceMetadata>,
     pub seal_status: SealStatus,
+    /// ticket with which the sector is sealed, set when the sector is
+    /// scheduled for sealing
+    #[serde(default)]
+    pub seal_ticket: Option<SealTicket>,
+    /// where the sector's unsealed data lives once the sector has been sealed
+    #[serde(default)]
+    pub staged_data: StagedDataLocation,
+    /// staged sector directory in which the sector-file was provisioned
+    #[serde(default)]
+    pub staged_dir: StagedSectorDir,
+    /// when the sector was provisioned, unknown for sectors provisioned before
+    /// it was recorded
+    #[serde(default)]
+    pub created_at: Option<SecondsSinceEpoch>,
+    /// whether the sector is being sealed again to repair its replica, in
+    /// which case the new replica must match the comm_r of the one it
+    /// replaces
+    #[serde(default)]
+    pub repairing: bool,
 }
 
 #[derive(Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
 pub struct SealedSectorMetadata {
     pub sector_id: SectorId,
-    pub sector_access: String,
+    pub sector_access: SectorAccess,
     pub pieces: Vec<PieceMetadata>,
     pub comm_r_star: [u8; 32],
     pub comm_r: [u8; 32],
     pub comm_d: [u8; 32],
     pub proof: Vec<u8>,
-    /// checksum on the whole sector
+    /// checksum on the whole sector, calculated with checksum_algo
     pub blake2b_checksum: Vec<u8>,
     /// number of bytes in the sealed sector-file as returned by `std::fs::metadata`
     pub len: u64,
+    /// where the sealed sector-file lives, as it may have been moved out of
+    /// the sealed sector directory (e.g. to cheaper storage)
+    #[serde(default)]
+    pub location: SealedSectorLocation,
+    /// ticket with which the sector was sealed, or None if the sector was
+    /// sealed before tickets were recorded
+    #[serde(default)]
+    pub seal_ticket: Option<SealTicket>,
+    /// algorithm with which the checksum was calculated; sectors sealed
+    /// before the algorithm was recorded were checksummed with BLAKE2b
+    #[serde(default)]
+    pub checksum_algo: ChecksumAlgo,
+    /// BLAKE3 checksums of the extents into which the sealed sector-file is
+    /// split, verified by quick health checks; empty for sectors sealed
+    /// before extents were checksummed
+    #[serde(default)]
+    pub extent_checksums: Vec<[u8; 32]>,
+    /// when and for how long the sector was sealed, or None if the sector was
+    /// sealed before timings were recorded or was imported without them
+    #[serde(default)]
+    pub seal_timing: Option<SealTiming>,
+    /// when the seal proof was verified against the sector's commitments as
+    /// the sector was imported, or None if it wasn't
+    #[serde(default)]
+    pub seal_verified_at: Option<SecondsSinceEpoch>,
+    /// whether the replica has been copied to the secondary location of the
+    /// SectorBuilder's replicator, or None if it hasn't been replicated
+    #[serde(default)]
+    pub replication: Option<ReplicationStatus>,
+}
+
+/// How far copying a sealed sector's replica to a secondary location (see
+/// SealedSectorReplicator) has come.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ReplicationStatus {
+    /// the replica is waiting to be copied, or will be once a replicator is
+    /// set again (e.g. after the SectorBuilder was restarted mid-copy)
+    Pending,
+    /// the replica is being copied, of which the provided number of bytes
+    /// have been copied so far (as reported by the replicator)
+    Copying { bytes_copied: u64 },
+    /// the replica was copied to the provided location
+    Replicated {
+        location: String,
+        replicated_at: SecondsSinceEpoch,
+    },
+    /// copying the replica failed with the provided error
+    Failed(String),
+}
+
+/// When, for how long and on which machine a sector was sealed.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SealTiming {
+    /// when the worker started the seal task
+    pub started_at: SecondsSinceEpoch,
+    /// when the worker finished the seal task
+    pub finished_at: SecondsSinceEpoch,
+    /// how long the seal task took, including the decryption and
+    /// verification of the staged sector-file
+    pub duration: Duration,
+    /// how long checksumming the replica took once it had been sealed
+    pub checksum_duration: Duration,
+    /// host name of the machine on which the sector was sealed
+    pub hostname: String,
+}
+
+/// Algorithm with which the checksum of a sealed sector-file is calculated.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ChecksumAlgo {
+    /// BLAKE2bp, compatible with sectors sealed by earlier versions
+    Blake2b,
+    /// BLAKE3, hashed on multiple threads, which is considerably faster for
+    /// whole replicas
+    Blake3,
+}
+
+/// Location of a staged sector's unsealed data.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum StagedDataLocation {
+    /// the staged sector directory with which the builder was initialized
+    StagedDir,
+    /// an unsealed cache directory
+    Cache(PathBuf),
+    /// the unsealed data has been deleted
+    Deleted,
+}
+
+/// Staged sector directory in which a staged sector-file was provisioned.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum StagedSectorDir {
+    /// the staged sector directory with which the builder was initialized
+    Primary,
+    /// the secondary staged sector directory, into which new staged sectors
+    /// spill over while the primary one is low on space
+    Secondary,
+}
+
+/// A secondary staged sector directory. New staged sectors are provisioned
+/// in it instead of the primary staged sector directory while fewer than
+/// min_free_bytes are available in the latter. Sectors stay in the directory
+/// in which they were provisioned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StagedSpillOver {
+    pub dir: PathBuf,
+    pub min_free_bytes: u64,
+}
+
+/// What happens to a staged sector's unsealed data once the sector has been
+/// sealed successfully. Retained data allows pieces to be retrieved and the
+/// sector to be sealed again without unsealing it first.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StagedDataRetention {
+    /// keep the data in the staged sector directory
+    Keep,
+    /// delete the data, reclaiming its disk space
+    Delete,
+    /// move the data into the provided unsealed cache directory
+    MoveToCache(PathBuf),
+}
+
+/// What happens to the sectors which are being sealed according to the
+/// persisted metadata when a SectorBuilder is initialized. No seal is running
+/// at that point, so their sealing was interrupted as the previous instance
+/// stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StaleSealPolicy {
+    /// seal the sectors again, from their staged data
+    Resume,
+    /// mark the sectors failed with SealFailure::Interrupted, from which the
+    /// operator can reseal them (see SectorBuilder::reseal_sector)
+    MarkFailed,
+    /// revert the sectors to pending, so that they accept pieces and are
+    /// scheduled for sealing again like any other pending sector
+    RevertToPending,
+}
+
+/// When staged sectors which still have room for more pieces are sealed.
+/// Full sectors are always sealed, as are the oldest sectors in excess of the
+/// maximum number of staged sectors. Sectors without pieces are left alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AutoSealPolicy {
+    /// seal a sector once its pieces take up at least this fraction of the
+    /// bytes it can hold
+    pub fill_ratio: Option<f64>,
+    /// seal a sector once it has been staged for this long
+    pub max_age: Option<Duration>,
+    /// seal a sector once the earliest deadline (store_until) of its pieces is
+    /// at most this far away
+    pub deadline_margin: Option<Duration>,
+}
+
+/// How long a SimpleSectorBuilder keeps the staged sector-files into which
+/// pieces of sealed sectors are unsealed for retrieval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnsealedRetention {
+    /// delete the file as soon as the piece has been read from it
+    DeleteAfterRead,
+    /// keep the file, e.g. for the host to read it after unsealing the piece
+    /// itself, until this long after it was provisioned. Pieces of the sector
+    /// read in the meantime are read from the file instead of being unsealed
+    /// again.
+    KeepFor(Duration),
+}
+
+impl Default for UnsealedRetention {
+    fn default() -> UnsealedRetention {
+        UnsealedRetention::DeleteAfterRead
+    }
+}
+
+/// When a proof-of-spacetime must be ready, e.g. the end of the proving window
+/// less the time needed to get the proof on chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoStDeadline {
+    pub deadline: SecondsSinceEpoch,
+    /// if the proof is estimated not to be ready in time, prove the slowest
+    /// sectors as faulty until the others can be proven in time instead of
+    /// failing
+    pub mark_slowest_faulty: bool,
+}
+
+/// How a proof-of-spacetime is generated beyond which sectors it proves. By
+/// default, proving has no deadline, reads each replica from its sealed
+/// sector-file, uses the PoSt configuration implied by the sector class and
+/// can't be cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct PoStOptions {
+    pub deadline: Option<PoStDeadline>,
+    /// paths from which the replicas of the given sectors are read instead of
+    /// their sealed sector-file, e.g. on a temporarily mounted archive volume
+    pub replica_paths: HashMap<SectorId, PathBuf>,
+    /// overrides the PoSt configuration implied by the sector class, e.g.
+    /// after the network's PoSt parameters have changed
+    pub post_config: Option<PoStConfig>,
+    /// abandons proving once cancelled
+    pub cancel: Option<CancellationToken>,
+}
+
+/// A proof-of-spacetime and the sectors it proves as faulty, which include
+/// those marked faulty to meet a deadline and those whose replica was found
+/// to be unavailable.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeneratedPoSt {
+    pub proof: Vec<u8>,
+    pub faults: Vec<SectorId>,
+}
+
+/// A proof-of-spacetime over one partition of a proving set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoStPartition {
+    /// position of the partition within the proving set, whose sectors are
+    /// partitioned in order of sector id
+    pub index: usize,
+    /// the sectors proven by the proof, including the faulty ones
+    pub sector_ids: Vec<SectorId>,
+    pub faults: Vec<SectorId>,
+    pub proof: Vec<u8>,
+}
+
+/// A ticket drawn from the chain with which a sector is sealed. It must match
+/// the ticket in the sector's on-chain pre-commit.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct SealTicket {
+    /// height of the block from which the ticket was drawn
+    pub block_height: u64,
+    pub ticket_bytes: [u8; 32],
+}
+
+/// Location of a sealed sector-file. The sector access is resolved relative to
+/// the directory the location refers to.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum SealedSectorLocation {
+    /// the sealed sector directory with which the builder was initialized
+    Default,
+    /// a directory registered with the builder under the given store id
+    Store(String),
+    /// an absolute directory
+    Dir(PathBuf),
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -31,16 +297,239 @@ pub struct PieceMetadata {
     pub num_bytes: UnpaddedBytesAmount,
     pub comm_p: Option<[u8; 32]>,
     pub piece_inclusion_proof: Option<Vec<u8>>,
+    /// key supplied by the client with which retries of the add_piece call
+    /// which added the piece are recognized
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// time until which the piece is to be stored, by which the sector holding
+    /// it must have been sealed
+    #[serde(default)]
+    pub store_until: Option<SecondsSinceEpoch>,
+    /// id of the on-chain deal for which the piece was stored, if supplied by
+    /// the client
+    #[serde(default)]
+    pub deal_id: Option<u64>,
+    /// labels supplied by the client when the piece was added
+    #[serde(default)]
+    pub labels: BTreeMap<String, Vec<u8>>,
+}
+
+impl PieceMetadata {
+    /// Metadata of a piece with the provided key and length which has neither
+    /// a commitment nor deal information yet.
+    pub fn new(piece_key: String, num_bytes: UnpaddedBytesAmount) -> PieceMetadata {
+        PieceMetadata {
+            piece_key,
+            num_bytes,
+            comm_p: None,
+            piece_inclusion_proof: None,
+            idempotency_key: None,
+            store_until: None,
+            deal_id: None,
+            labels: Default::default(),
+        }
+    }
+}
+
+impl Default for PieceMetadata {
+    fn default() -> PieceMetadata {
+        PieceMetadata::new(String::new(), UnpaddedBytesAmount(0))
+    }
+}
+
+/// Information about a piece's deal, supplied by the client when the piece is
+/// added and persisted with the piece's metadata, with which sectors can be
+/// mapped back to deals.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PieceDealInfo {
+    pub deal_id: Option<u64>,
+    /// at most MAX_PIECE_LABELS labels, whose keys and values are at most
+    /// MAX_PIECE_LABEL_KEY_LEN and MAX_PIECE_LABEL_VALUE_LEN bytes long
+    pub labels: BTreeMap<String, Vec<u8>>,
+    /// identifier of the client or source which supplied the piece, at most
+    /// MAX_PIECE_ORIGIN_LEN bytes long, recorded in the journal rather than
+    /// with the piece's metadata
+    pub origin: Option<String>,
+}
+
+/// Who added a piece and when, as recorded in the journal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceProvenance {
+    pub piece_key: String,
+    /// the sector to which the piece was added
+    pub sector_id: SectorId,
+    pub origin: String,
+    pub added_at: SecondsSinceEpoch,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum SealStatus {
-    Failed(String),
+    Failed(SealFailure),
     Pending,
     Sealed(Box<SealedSectorMetadata>),
     Sealing,
 }
 
+/// What became of a staged sector when all staged sectors were scheduled for
+/// sealing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SealScheduleOutcome {
+    /// the sector has been scheduled for sealing
+    Scheduled,
+    /// the sector wasn't pending (e.g. it's already being sealed), so it
+    /// wasn't scheduled
+    Skipped(String),
+    /// scheduling the sector failed
+    Error(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SealScheduleResult {
+    pub sector_id: SectorId,
+    pub outcome: SealScheduleOutcome,
+}
+
+/// Which staged sectors are returned, by their seal status.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StagedSectorFilter {
+    All,
+    Pending,
+    /// sectors whose seal failed, for any reason
+    Failed,
+    Sealing,
+}
+
+impl Default for StagedSectorFilter {
+    fn default() -> Self {
+        StagedSectorFilter::All
+    }
+}
+
+impl StagedSectorFilter {
+    pub fn matches(self, seal_status: &SealStatus) -> bool {
+        match (self, seal_status) {
+            (StagedSectorFilter::All, _) => true,
+            (StagedSectorFilter::Pending, SealStatus::Pending) => true,
+            (StagedSectorFilter::Failed, SealStatus::Failed(_)) => true,
+            (StagedSectorFilter::Sealing, SealStatus::Sealing) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The reason for which sealing a sector failed.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(from = "SealFailureRepr")]
+pub enum SealFailure {
+    /// the Groth parameters or verifying keys for the sector size could not
+    /// be loaded
+    ParamsMissing(String),
+    /// reading or writing sector data failed
+    Io {
+        /// the sealed sector-file which was being produced
+        path: PathBuf,
+        /// the error number reported by the operating system, if any
+        errno: Option<i32>,
+        msg: String,
+    },
+    /// the proofs failed to replicate the sector or to prove its replication
+    ProofGeneration(String),
+    /// sealing was cancelled before it completed
+    Cancelled,
+    /// the operating system ran out of memory
+    OutOfMemory,
+    /// the new replica of a repaired sector doesn't match the sector's comm_r
+    ReplicaMismatch(String),
+    /// sealing ran for longer than the seal timeout and was abandoned
+    TimedOut,
+    /// the staged data of the piece with the provided key doesn't match the
+    /// digest recorded when it was added
+    StagedDataCorrupted(String),
+    /// sealing was interrupted as the SectorBuilder stopped, and not resumed
+    /// (see StaleSealPolicy::MarkFailed)
+    Interrupted,
+    /// the worker sealing the sector panicked; the worker carried on with
+    /// other tasks
+    WorkerCrashed(String),
+    /// a failure recorded before failures were classified
+    Other(String),
+}
+
+// Snapshots written before failures were classified hold their reason as a
+// plain string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SealFailureRepr {
+    Classified(ClassifiedSealFailure),
+    Legacy(String),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "SealFailure")]
+enum ClassifiedSealFailure {
+    ParamsMissing(String),
+    Io {
+        path: PathBuf,
+        errno: Option<i32>,
+        msg: String,
+    },
+    ProofGeneration(String),
+    Cancelled,
+    OutOfMemory,
+    ReplicaMismatch(String),
+    TimedOut,
+    StagedDataCorrupted(String),
+    Interrupted,
+    WorkerCrashed(String),
+    Other(String),
+}
+
+impl From<SealFailureRepr> for SealFailure {
+    fn from(repr: SealFailureRepr) -> SealFailure {
+        use ClassifiedSealFailure::*;
+
+        match repr {
+            SealFailureRepr::Legacy(msg) => SealFailure::Other(msg),
+            SealFailureRepr::Classified(failure) => match failure {
+                ParamsMissing(msg) => SealFailure::ParamsMissing(msg),
+                Io { path, errno, msg } => SealFailure::Io { path, errno, msg },
+                ProofGeneration(msg) => SealFailure::ProofGeneration(msg),
+                Cancelled => SealFailure::Cancelled,
+                OutOfMemory => SealFailure::OutOfMemory,
+                ReplicaMismatch(msg) => SealFailure::ReplicaMismatch(msg),
+                TimedOut => SealFailure::TimedOut,
+                StagedDataCorrupted(piece_key) => SealFailure::StagedDataCorrupted(piece_key),
+                Interrupted => SealFailure::Interrupted,
+                WorkerCrashed(msg) => SealFailure::WorkerCrashed(msg),
+                Other(msg) => SealFailure::Other(msg),
+            },
+        }
+    }
+}
+
+impl fmt::Display for SealFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SealFailure::ParamsMissing(msg) => write!(f, "missing parameters: {}", msg),
+            SealFailure::Io { path, errno, msg } => match errno {
+                Some(errno) => write!(f, "I/O error (errno {}) on {:?}: {}", errno, path, msg),
+                None => write!(f, "I/O error on {:?}: {}", path, msg),
+            },
+            SealFailure::ProofGeneration(msg) => write!(f, "proof generation failed: {}", msg),
+            SealFailure::Cancelled => write!(f, "sealing was cancelled"),
+            SealFailure::OutOfMemory => write!(f, "out of memory"),
+            SealFailure::ReplicaMismatch(msg) => write!(f, "replica mismatch: {}", msg),
+            SealFailure::TimedOut => write!(f, "sealing timed out"),
+            SealFailure::StagedDataCorrupted(piece_key) => {
+                write!(f, "staged data of piece {} is corrupted", piece_key)
+            }
+            SealFailure::Interrupted => write!(f, "sealing was interrupted"),
+            SealFailure::WorkerCrashed(msg) => write!(f, "the sealing worker crashed: {}", msg),
+            SealFailure::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SealedSectorHealth {
     Ok,
@@ -49,6 +538,19 @@ pub enum SealedSectorHealth {
     ErrorMissing,
 }
 
+/// How thoroughly the health of a sealed sector is checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HealthCheckMode {
+    /// rehash the whole sealed sector-file and compare its checksum, which
+    /// takes minutes for large sectors; meant for scheduled deep scrubs
+    Full,
+    /// compare the length of the sealed sector-file and the checksums of
+    /// num_samples of its extents, picked deterministically from the seed
+    /// and the sector id; sectors without extent checksums are checked in
+    /// full
+    Quick { seed: u64, num_samples: usize },
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum GetSealedSectorResult {
     WithHealth(SealedSectorHealth, SealedSectorMetadata),
@@ -58,6 +560,176 @@ pub enum GetSealedSectorResult {
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct SecondsSinceEpoch(pub u64);
 
+impl SecondsSinceEpoch {
+    pub fn now() -> SecondsSinceEpoch {
+        SecondsSinceEpoch(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// A piece retrieved from a sealed sector along with what a client needs to
+/// verify that the piece is included in the sector.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PieceWithProof {
+    /// the unsealed piece-bytes
+    pub bytes: Vec<u8>,
+    pub sector_id: SectorId,
+    pub comm_d: [u8; 32],
+    pub comm_p: [u8; 32],
+    pub piece_inclusion_proof: Vec<u8>,
+}
+
+/// Where a piece lives within its sector, in terms of the sector's padded
+/// (preprocessed) bytes. Pieces are aligned within the sector, so a piece
+/// doesn't necessarily start where the previous one ends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceLayout {
+    pub piece_key: String,
+    /// offset of the piece's first byte within the sector
+    pub padded_start_byte: u64,
+    pub padded_len: PaddedBytesAmount,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageReport {
+    /// number of bytes in the staged sector-files of all staged sectors
+    pub staged_bytes: u64,
+    /// number of bytes in the sealed sector-files of all sealed sectors
+    pub sealed_bytes: u64,
+    /// capacity of the staged and sealed sector directories and of each
+    /// registered sealed sector store
+    pub directories: Vec<DirectoryUsage>,
+    pub num_pending_sectors: u64,
+    pub num_sealing_sectors: u64,
+    pub num_failed_sectors: u64,
+    pub num_sealed_sectors: u64,
+}
+
+/// Statistics of the durations of the seal tasks of sealed sectors, over all
+/// sectors and per machine on which they were sealed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SealStatistics {
+    pub overall: SealDurationStats,
+    /// statistics keyed by host name
+    pub per_host: BTreeMap<String, SealDurationStats>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SealDurationStats {
+    /// number of sealed sectors whose seal timing is known
+    pub num_sectors: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectoryUsage {
+    pub path: PathBuf,
+    /// number of bytes available to non-privileged users on the directory's
+    /// filesystem
+    pub available_bytes: u64,
+    /// total number of bytes on the directory's filesystem
+    pub total_bytes: u64,
+}
+
+/// How the previous instance of a SectorBuilder stopped, as determined when
+/// the SectorBuilder is initialized from its metadata.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LastShutdown {
+    /// no metadata had been persisted
+    Fresh,
+    /// the previous instance was shut down
+    Clean,
+    /// the previous instance stopped without being shut down (it crashed or
+    /// was killed), so its staged sectors were scanned for data which isn't
+    /// recorded in the metadata
+    Crash,
+}
+
+/// A seal task which was running or waiting for a worker when the previous
+/// instance of a SectorBuilder was shut down. Persisted on shutdown.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct InterruptedSeal {
+    pub sector_id: SectorId,
+    /// whether a worker had started sealing the sector
+    pub dispatched: bool,
+}
+
+/// The configuration a SectorBuilder is running with, from which a caller
+/// can verify that it's attached to the instance it expects.
+#[derive(Clone, Debug)]
+pub struct SectorBuilderConfig {
+    pub sector_class: SectorClass,
+    pub prover_id: [u8; 31],
+    pub metadata_dir: PathBuf,
+    pub sealed_sector_dir: PathBuf,
+    pub staged_sector_dir: PathBuf,
+    /// the directory into which new staged sectors spill over, if one is
+    /// configured
+    pub secondary_staged_sector_dir: Option<PathBuf>,
+    /// the current maximum number of staged sectors which accept pieces,
+    /// which may have been changed since the SectorBuilder was initialized
+    pub max_num_staged_sectors: u64,
+    /// the current storage quota
+    pub quota: StorageQuota,
+}
+
+/// What a SectorBuilder found when it was initialized from its metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartupReport {
+    pub last_shutdown: LastShutdown,
+    /// sectors whose sealing was interrupted, which have been handled
+    /// according to the stale seal policy (by default, sealing is
+    /// restarted). After a crash, these are the sectors which were recorded
+    /// as being sealed.
+    pub interrupted_seals: Vec<SectorId>,
+    /// staged sectors which were truncated to the pieces recorded in their
+    /// metadata after a crash
+    pub truncated_sectors: Vec<SectorId>,
+}
+
+impl Default for ChecksumAlgo {
+    fn default() -> ChecksumAlgo {
+        ChecksumAlgo::Blake2b
+    }
+}
+
+impl Default for StagedDataLocation {
+    fn default() -> StagedDataLocation {
+        StagedDataLocation::StagedDir
+    }
+}
+
+impl Default for StagedSectorDir {
+    fn default() -> StagedSectorDir {
+        StagedSectorDir::Primary
+    }
+}
+
+impl Default for StaleSealPolicy {
+    fn default() -> StaleSealPolicy {
+        StaleSealPolicy::Resume
+    }
+}
+
+impl Default for StagedDataRetention {
+    fn default() -> StagedDataRetention {
+        StagedDataRetention::Keep
+    }
+}
+
+impl Default for SealedSectorLocation {
+    fn default() -> SealedSectorLocation {
+        SealedSectorLocation::Default
+    }
+}
+
 impl Default for StagedSectorMetadata {
     fn default() -> StagedSectorMetadata {
         StagedSectorMetadata {
@@ -65,6 +737,11 @@ impl Default for StagedSectorMetadata {
             sector_access: Default::default(),
             pieces: Default::default(),
             seal_status: SealStatus::Pending,
+            seal_ticket: None,
+            staged_data: Default::default(),
+            staged_dir: Default::default(),
+            created_at: None,
+            repairing: false,
         }
     }
 }
@@ -1,26 +1,62 @@
 use std::collections::btree_map::BTreeMap;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use filecoin_proofs::error::ExpectWithBacktrace;
 use filecoin_proofs::pieces::get_piece_start_byte;
-use filecoin_proofs::{PaddedBytesAmount, PrivateReplicaInfo, SealOutput, UnpaddedBytesAmount};
+use filecoin_proofs::{
+    PaddedBytesAmount, PoStConfig, PrivateReplicaInfo, SealOutput, UnpaddedBytesAmount,
+};
+use storage_proofs::rational_post;
 use storage_proofs::sector::SectorId;
 
+use crate::clock::{Clock, SystemClock};
+use crate::constants::DEFAULT_JOURNAL_KEEP_LAST_N;
 use crate::error::Result;
+use crate::events::{EventBus, SectorBuilderEvent};
 use crate::helpers;
-use crate::kv_store::KeyValueStore;
-use crate::state::SectorBuilderState;
-use crate::worker::{SealTaskPrototype, UnsealTaskPrototype};
+use crate::isolation::SealIsolation;
+use crate::kv_store::{KeyValueStore, KvStoreStats};
+use crate::rate_limiter::{RateLimiter, ThrottledReader};
+use crate::replication::{ReplicationJob, SealedSectorReplicator};
+use crate::staged_writer::StagedWriteConfig;
+use crate::state::{transition_seal_status, PublishedState, SectorBuilderState};
+use crate::watchdog::{CancellationToken, TaskTimeouts};
+use crate::worker::{
+    HydrateTaskPrototype, PoStTaskPrototype, RepairCheckTaskPrototype, ReplicaTaskPrototype,
+    SealTaskPrototype, UnsealTaskPrototype,
+};
 use crate::GetSealedSectorResult::WithHealth;
 use crate::{
-    err_piecenotfound, err_unrecov, GetSealedSectorResult, PieceMetadata, SealStatus,
-    SealedSectorMetadata, SecondsSinceEpoch, SectorStore, StagedSectorMetadata,
+    err_already_sealing, err_invalid_replica, err_piecenotfound, err_sector_exists, err_unrecov,
+    AutoSealPolicy, ChecksumAlgo, GeneratedPoSt, GetSealedSectorResult, HealthCheckMode,
+    InterruptedSeal, MappedBytes, MonotonicSectorIds, PieceDealInfo, PieceLayout, PieceMetadata,
+    PieceProvenance, PieceWithProof, PoStOptions, ReplicationStatus, SealFailure,
+    SealScheduleOutcome, SealScheduleResult, SealStatistics, SealStatus, SealTicket, SealTiming,
+    SealedSectorHealth, SealedSectorLocation, SealedSectorMetadata, SecondsSinceEpoch,
+    SectorAccess, SectorIdProvider, SectorStore, StagedDataRetention, StagedSectorFilter,
+    StagedSectorMetadata, StaleSealPolicy, StorageQuota, StorageReport, UnpaddedByteIndex,
 };
-use helpers::SnapshotKey;
+use helpers::{JournalEntry, ReplicaChecksums, SectorIntegrity, SectorTransition, SnapshotKey};
 
 const FATAL_SNPSHT: &str = "could not snapshot";
 
+static RETRIEVAL_NONCE: AtomicUsize = AtomicUsize::new(0);
+
+// The range of a requested piece within the bytes unsealed for a bulk
+// retrieval.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UnsealedPieceRange {
+    /// index of the task prototype which unseals the piece's sector
+    pub proto_index: usize,
+    /// offset of the piece within the unsealed bytes
+    pub offset: u64,
+    pub len: u64,
+}
+
 // The SectorBuilderStateManager is the owner of all sector-related metadata.
 // It dispatches expensive operations (e.g. unseal and seal) to the sealer
 // worker-threads. Other, inexpensive work (or work which needs to be performed
@@ -29,36 +65,355 @@ pub struct SectorMetadataManager<T: KeyValueStore, S: SectorStore> {
     pub kv_store: T,
     pub sector_store: S,
     pub state: SectorBuilderState,
-    pub max_num_staged_sectors: u8,
+    pub max_num_staged_sectors: u64,
     pub max_user_bytes_per_staged_sector: UnpaddedBytesAmount,
     pub prover_id: [u8; 31],
     pub sector_size: PaddedBytesAmount,
+    pub sealed_stores: HashMap<String, PathBuf>,
+    pub quota: StorageQuota,
+    pub seal_ticket: Option<SealTicket>,
+    pub staged_data_retention: StagedDataRetention,
+    // sectors whose replica a worker is transferring, e.g. while it's
+    // imported
+    pub replica_transfers: HashSet<SectorId>,
+    pub sector_id_provider: Box<dyn SectorIdProvider>,
+    pub task_timeouts: TaskTimeouts,
+    pub events: EventBus,
+    // sectors which have changed since the last snapshot was persisted
+    pub dirty_sectors: HashSet<SectorId>,
+    // the number of the last journal entry, and the entries which are
+    // written along with the next snapshot
+    pub journal_seq: u64,
+    pub unflushed_journal: Vec<JournalEntry>,
+    // how many of the most recent entries are kept when the journal is
+    // pruned
+    pub journal_keep_last_n: u64,
+    // if set, snapshots are persisted at most once per interval
+    pub snapshot_interval: Option<Duration>,
+    pub last_snapshot: Instant,
+    pub checksum_algo: ChecksumAlgo,
+    pub auto_seal_policy: AutoSealPolicy,
+    // how long proving a single sector took during the last proof-of-spacetime,
+    // against which PoSt deadlines are checked
+    pub post_duration_per_sector: Option<Duration>,
+    pub seal_isolation: SealIsolation,
+    // how pieces are written to staged sector-files
+    pub staged_write_config: StagedWriteConfig,
+    // if set, limits the rate at which pieces are read while they're added
+    pub ingest_rate_limiter: Option<RateLimiter>,
+    // tells the time against which deadlines and sector ages are checked
+    pub clock: Arc<dyn Clock>,
+    // if set, copies the replicas of newly sealed sectors to a secondary
+    // location
+    pub replicator: Option<Arc<dyn SealedSectorReplicator>>,
+    // the copy of the state from which queries are served without going
+    // through the scheduler, and whether the state has changed since it was
+    // last published
+    pub published_state: PublishedState,
+    pub unpublished_changes: bool,
 }
 
 impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
+    // Creates the manager of the provided state, which is published as is.
+    // The settings which are changed once the SectorBuilder is running (see
+    // the note on runtime settings above SectorBuilder) start out with their
+    // defaults.
+    pub fn new(
+        kv_store: T,
+        sector_store: S,
+        state: SectorBuilderState,
+        max_num_staged_sectors: u64,
+        prover_id: [u8; 31],
+        events: EventBus,
+    ) -> Result<SectorMetadataManager<T, S>> {
+        let max_user_bytes_per_staged_sector =
+            sector_store.sector_config().max_unsealed_bytes_per_sector();
+        let sector_size = sector_store.sector_config().sector_bytes();
+
+        let journal_seq =
+            helpers::journal_head(&kv_store, &SnapshotKey::new(prover_id, sector_size))?;
+
+        let published_state = PublishedState::new(&state);
+
+        Ok(SectorMetadataManager {
+            kv_store,
+            sector_store,
+            state,
+            max_num_staged_sectors,
+            max_user_bytes_per_staged_sector,
+            prover_id,
+            sector_size,
+            sealed_stores: Default::default(),
+            quota: Default::default(),
+            seal_ticket: None,
+            staged_data_retention: Default::default(),
+            replica_transfers: Default::default(),
+            sector_id_provider: Box::new(MonotonicSectorIds),
+            task_timeouts: Default::default(),
+            events,
+            dirty_sectors: Default::default(),
+            journal_seq,
+            unflushed_journal: Default::default(),
+            journal_keep_last_n: DEFAULT_JOURNAL_KEEP_LAST_N,
+            snapshot_interval: None,
+            last_snapshot: Instant::now(),
+            checksum_algo: Default::default(),
+            auto_seal_policy: Default::default(),
+            post_duration_per_sector: None,
+            seal_isolation: Default::default(),
+            staged_write_config: Default::default(),
+            ingest_rate_limiter: None,
+            replicator: None,
+            clock: Arc::new(SystemClock),
+            published_state,
+            unpublished_changes: false,
+        })
+    }
+
+    // Generates a proof-of-spacetime over the sealed sectors with the provided
+    // comm_rs.
+    //
+    // If a deadline is provided, proving is abandoned once it has passed, and
+    // the slowest sectors are marked faulty up front (if the deadline allows
+    // it) if proving all sectors is estimated to take too long.
+    //
+    // Replicas with a path override are read from that path instead of their
+    // sealed sector-file, e.g. from a temporarily mounted archive volume,
+    // without changing the sectors' metadata.
+    //
+    // Sectors whose replica is missing or has the wrong length are proven as
+    // faulty instead of failing the proof. The returned faults include these
+    // along with the provided faults and those marked faulty to meet the
+    // deadline, so that the faults declared on chain match the proof.
+    //
+    // The proof is generated with the provided PoSt configuration, if any,
+    // instead of the one implied by the sector store's.
+    //
+    // Proving is abandoned if the cancellation token is cancelled.
     pub fn generate_post(
+        &mut self,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        options: PoStOptions,
+    ) -> Result<GeneratedPoSt> {
+        let PoStOptions {
+            deadline,
+            replica_paths,
+            post_config,
+            cancel,
+        } = options;
+
+        helpers::validate_replica_paths(&replica_paths)?;
+
+        let mut fault_set: HashSet<SectorId> = faults.into_iter().collect();
+
+        let comm_rs_set: HashSet<&[u8; 32]> = comm_rs.iter().collect();
+
+        let unavailable = self.unavailable_replicas(&comm_rs_set, &fault_set, &replica_paths);
+
+        if !unavailable.is_empty() {
+            warn!(
+                "marking sectors {:?} faulty as their replicas are unavailable",
+                unavailable
+            );
+        }
+
+        fault_set.extend(unavailable);
+
+        if let Some(deadline) = &deadline {
+            let sectors: Vec<&SealedSectorMetadata> = self
+                .state
+                .sealed
+                .sectors
+                .values()
+                .filter(|sector| comm_rs_set.contains(&sector.comm_r))
+                .filter(|sector| !fault_set.contains(&sector.sector_id))
+                .collect();
+
+            let slowest = helpers::plan_post_faults(
+                &sectors,
+                deadline,
+                self.post_duration_per_sector,
+                &self.clock.now(),
+            )?;
+
+            if !slowest.is_empty() {
+                warn!(
+                    "marking sectors {:?} faulty to meet PoSt deadline {}",
+                    slowest, deadline.deadline.0
+                );
+            }
+
+            fault_set.extend(slowest);
+        }
+
+        let replicas = self.private_replicas(&comm_rs_set, &fault_set, &replica_paths)?;
+
+        let post_config =
+            post_config.unwrap_or_else(|| self.sector_store.proofs_config().post_config());
+        let challenge_seed = *challenge_seed;
+        let num_proven = replicas
+            .keys()
+            .filter(|sector_id| !fault_set.contains(sector_id))
+            .count();
+        let started_at = Instant::now();
+
+        let proof = helpers::prove_before_deadline(
+            deadline.as_ref().map(|deadline| &deadline.deadline),
+            &self.clock.now(),
+            self.task_timeouts.post,
+            cancel.as_ref(),
+            &self.events,
+            move || filecoin_proofs::generate_post(post_config, &challenge_seed, &replicas),
+        )?;
+
+        if num_proven > 0 {
+            self.post_duration_per_sector = Some(started_at.elapsed() / num_proven as u32);
+        }
+
+        let mut faults: Vec<SectorId> = fault_set.into_iter().collect();
+        faults.sort();
+
+        Ok(GeneratedPoSt { proof, faults })
+    }
+
+    // Derives the challenges of a proof-of-spacetime over the sealed sectors
+    // with the provided comm_rs without generating the proof, e.g. so that
+    // the challenged replicas can be fetched before proving. The challenges
+    // are derived with the provided PoSt configuration, if any.
+    pub fn generate_post_first(
         &self,
         comm_rs: &[[u8; 32]],
         challenge_seed: &[u8; 32],
         faults: Vec<SectorId>,
-    ) -> Result<Vec<u8>> {
-        let fault_set: HashSet<SectorId> = faults.into_iter().collect();
+        post_config: Option<PoStConfig>,
+    ) -> Result<Vec<rational_post::Challenge>> {
+        let comm_rs_set: HashSet<&[u8; 32]> = comm_rs.iter().collect();
+
+        let sectors = self
+            .state
+            .sealed
+            .sectors
+            .values()
+            .filter(|sector| comm_rs_set.contains(&sector.comm_r))
+            .map(|sector| sector.sector_id)
+            .collect();
+
+        filecoin_proofs::generate_post_first(
+            post_config.unwrap_or_else(|| self.sector_store.proofs_config().post_config()),
+            challenge_seed,
+            sectors,
+            faults.into_iter().collect(),
+        )
+    }
+
+    // Creates a task prototype for each partition of the sealed sectors with
+    // the provided comm_rs, in order of sector id, so that the proof over each
+    // partition can be generated by a worker of its own.
+    pub fn create_post_partition_task_protos(
+        &self,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        partition_size: usize,
+    ) -> Result<Vec<PoStTaskPrototype>> {
+        if partition_size == 0 {
+            return Err(err_unrecov("PoSt partitions must hold at least one sector").into());
+        }
 
+        let fault_set: HashSet<SectorId> = faults.into_iter().collect();
         let comm_rs_set: HashSet<&[u8; 32]> = comm_rs.iter().collect();
 
+        let replicas = self.private_replicas(&comm_rs_set, &fault_set, &HashMap::new())?;
+
+        Ok(helpers::partition_replicas(replicas, partition_size)
+            .into_iter()
+            .enumerate()
+            .map(|(index, replicas)| PoStTaskPrototype {
+                challenge_seed: *challenge_seed,
+                faults: replicas
+                    .keys()
+                    .filter(|sector_id| fault_set.contains(sector_id))
+                    .cloned()
+                    .collect(),
+                index,
+                post_config: self.sector_store.proofs_config().post_config(),
+                replicas,
+                timeout: self.task_timeouts.post,
+            })
+            .collect())
+    }
+
+    // Returns the ids, in order, of the sealed sectors with the provided
+    // comm_rs and not already faulty whose replica can't be proven over:
+    // those whose location can't be resolved, whose replica is missing or
+    // whose replica doesn't have the length it was sealed with. Replicas
+    // aren't hashed, so this is cheap enough to do before every proof.
+    fn unavailable_replicas(
+        &self,
+        comm_rs: &HashSet<&[u8; 32]>,
+        faults: &HashSet<SectorId>,
+        replica_paths: &HashMap<SectorId, PathBuf>,
+    ) -> Vec<SectorId> {
+        let mut unavailable: Vec<SectorId> = self
+            .state
+            .sealed
+            .sectors
+            .values()
+            .filter(|sector| comm_rs.contains(&sector.comm_r))
+            .filter(|sector| !faults.contains(&sector.sector_id))
+            .filter(|sector| {
+                let path = match replica_paths.get(&sector.sector_id) {
+                    Some(path) => Ok(path.clone()),
+                    None => {
+                        helpers::sealed_sector_path(&self.sector_store, &self.sealed_stores, sector)
+                    }
+                };
+
+                match path.and_then(|path| Ok(std::fs::metadata(path)?)) {
+                    Ok(file) => file.len() != sector.len,
+                    Err(_) => true,
+                }
+            })
+            .map(|sector| sector.sector_id)
+            .collect();
+
+        unavailable.sort();
+
+        unavailable
+    }
+
+    // Resolves the replicas of the sealed sectors with the provided comm_rs,
+    // marking those with the provided ids as faulty. Replicas with a path
+    // override are resolved to that path. Faulty replicas aren't read, so
+    // those whose location can't be resolved are given an empty path.
+    fn private_replicas(
+        &self,
+        comm_rs: &HashSet<&[u8; 32]>,
+        faults: &HashSet<SectorId>,
+        replica_paths: &HashMap<SectorId, PathBuf>,
+    ) -> Result<BTreeMap<SectorId, PrivateReplicaInfo>> {
         let mut replicas: BTreeMap<SectorId, PrivateReplicaInfo> = Default::default();
 
         for sector in self.state.sealed.sectors.values() {
-            if comm_rs_set.contains(&sector.comm_r) {
-                let path_str = self
-                    .sector_store
-                    .manager()
-                    .sealed_sector_path(&sector.sector_access)
-                    .to_str()
-                    .map(str::to_string)
-                    .unwrap();
-
-                let info = if fault_set.contains(&sector.sector_id) {
+            if comm_rs.contains(&sector.comm_r) {
+                let path = match replica_paths.get(&sector.sector_id) {
+                    Some(path) => path.clone(),
+                    None => match helpers::sealed_sector_path(
+                        &self.sector_store,
+                        &self.sealed_stores,
+                        sector,
+                    ) {
+                        Ok(path) => path,
+                        Err(_) if faults.contains(&sector.sector_id) => PathBuf::new(),
+                        Err(err) => return Err(err),
+                    },
+                };
+
+                let path_str = helpers::replica_path_str(sector.sector_id, &path)?;
+
+                let info = if faults.contains(&sector.sector_id) {
                     PrivateReplicaInfo::new_faulty(path_str, sector.comm_r)
                 } else {
                     PrivateReplicaInfo::new(path_str, sector.comm_r)
@@ -68,11 +423,7 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
             }
         }
 
-        filecoin_proofs::generate_post(
-            self.sector_store.proofs_config().post_config(),
-            challenge_seed,
-            &replicas,
-        )
+        Ok(replicas)
     }
 
     // Creates a task prototype for retrieving (unsealing) a piece from a
@@ -104,34 +455,203 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
             .map(|p| p.num_bytes)
             .collect();
 
-        let staged_sector_access = self
-            .sector_store
-            .manager()
-            .new_staging_sector_access(sealed_sector.sector_id)
-            .map_err(failure::Error::from)?;
+        self.create_unseal_task_proto(
+            sealed_sector,
+            get_piece_start_byte(&piece_lengths, piece.num_bytes),
+            piece.num_bytes,
+        )
+    }
+
+    // Creates task prototypes for retrieving many pieces at once. Requested
+    // pieces are grouped by the sealed sector containing them and a single
+    // prototype is created per sector, which unseals the range spanning all
+    // of the sector's requested pieces. Each requested key is mapped to its
+    // range within the bytes unsealed by one of the prototypes, or to an
+    // error if the piece can't be retrieved.
+    pub fn create_retrieve_pieces_task_protos(
+        &self,
+        piece_keys: &[String],
+    ) -> (Vec<UnsealTaskPrototype>, Vec<Result<UnsealedPieceRange>>) {
+        // the start byte and length of each requested piece, grouped by
+        // sector
+        let mut by_sector: BTreeMap<SectorId, Vec<(usize, u64, u64)>> = Default::default();
+        let mut ranges: Vec<Result<UnsealedPieceRange>> = Vec::with_capacity(piece_keys.len());
+
+        for (index, piece_key) in piece_keys.iter().enumerate() {
+            let located = self.state.sealed.sectors.values().find_map(|sector| {
+                let position = sector
+                    .pieces
+                    .iter()
+                    .position(|p| &p.piece_key == piece_key)?;
+
+                let piece_lengths: Vec<_> = sector.pieces[..position]
+                    .iter()
+                    .map(|p| p.num_bytes)
+                    .collect();
+
+                let num_bytes = sector.pieces[position].num_bytes;
+                let start = get_piece_start_byte(&piece_lengths, num_bytes);
+
+                Some((sector.sector_id, u64::from(start), u64::from(num_bytes)))
+            });
+
+            match located {
+                Some((sector_id, start, len)) => {
+                    by_sector
+                        .entry(sector_id)
+                        .or_insert_with(Default::default)
+                        .push((index, start, len));
+                    ranges.push(Ok(Default::default()));
+                }
+                None => ranges.push(Err(err_piecenotfound(piece_key.clone()).into())),
+            }
+        }
+
+        let mut protos = Vec::with_capacity(by_sector.len());
+
+        for (sector_id, pieces) in by_sector {
+            let sealed_sector = &self.state.sealed.sectors[&sector_id];
+
+            let first = pieces.iter().map(|&(_, start, _)| start).min().unwrap_or(0);
+            let last = pieces
+                .iter()
+                .map(|&(_, start, len)| start + len)
+                .max()
+                .unwrap_or(0);
+
+            let proto = self.create_unseal_task_proto(
+                sealed_sector,
+                UnpaddedByteIndex(first),
+                UnpaddedBytesAmount(last - first),
+            );
+
+            match proto {
+                Ok(proto) => {
+                    for (index, start, len) in pieces {
+                        ranges[index] = Ok(UnsealedPieceRange {
+                            proto_index: protos.len(),
+                            offset: start - first,
+                            len,
+                        });
+                    }
+
+                    protos.push(proto);
+                }
+                Err(err) => {
+                    for (index, _, _) in pieces {
+                        ranges[index] = Err(err_unrecov(format!(
+                            "could not unseal sector {}: {}",
+                            sector_id, err
+                        ))
+                        .into());
+                    }
+                }
+            }
+        }
+
+        (protos, ranges)
+    }
+
+    // Creates a task prototype for unsealing the provided range of a sealed
+    // sector.
+    fn create_unseal_task_proto(
+        &self,
+        sealed_sector: &SealedSectorMetadata,
+        piece_start_byte: UnpaddedByteIndex,
+        piece_len: UnpaddedBytesAmount,
+    ) -> Result<UnsealTaskPrototype> {
+        // unseal into a file of its own in the staged sector directory, as
+        // the sector's staged sector-file may have been retained and other
+        // retrievals may be in flight
+        let staged_sector_access = SectorAccess::new(format!(
+            "{}-retrieval-{}",
+            sealed_sector.sector_access,
+            RETRIEVAL_NONCE.fetch_add(1, Ordering::SeqCst)
+        ))?;
 
         Ok(UnsealTaskPrototype {
             porep_config: self.sector_store.proofs_config().porep_config(),
-            source_path: self
-                .sector_store
-                .manager()
-                .sealed_sector_path(&sealed_sector.sector_access),
+            source_path: helpers::sealed_sector_path(
+                &self.sector_store,
+                &self.sealed_stores,
+                sealed_sector,
+            )?,
             destination_path: self
                 .sector_store
                 .manager()
                 .staged_sector_path(&staged_sector_access),
             sector_id: sealed_sector.sector_id,
-            piece_start_byte: get_piece_start_byte(&piece_lengths, piece.num_bytes),
-            piece_len: piece.num_bytes,
+            piece_start_byte,
+            piece_len,
+            timeout: self.task_timeouts.unseal,
         })
     }
 
+    // Returns the commitments and inclusion proof of the piece with the
+    // provided key, which must have been sealed. The piece-bytes are left
+    // empty.
+    pub fn get_piece_proof(&self, piece_key: String) -> Result<PieceWithProof> {
+        self.state
+            .sealed
+            .sectors
+            .values()
+            .find_map(|sector| {
+                sector
+                    .pieces
+                    .iter()
+                    .find(|piece| piece.piece_key == piece_key)
+                    .map(|piece| (sector, piece))
+            })
+            .ok_or_else(|| err_piecenotfound(piece_key.clone()).into())
+            .and_then(
+                |(sector, piece)| match (piece.comm_p, &piece.piece_inclusion_proof) {
+                    (Some(comm_p), Some(proof)) => Ok(PieceWithProof {
+                        bytes: Default::default(),
+                        sector_id: sector.sector_id,
+                        comm_d: sector.comm_d,
+                        comm_p,
+                        piece_inclusion_proof: proof.clone(),
+                    }),
+                    _ => Err(err_unrecov(format!(
+                        "no inclusion proof recorded for piece {}",
+                        piece_key
+                    ))
+                    .into()),
+                },
+            )
+    }
+
     // Returns sealing status for the sector with specified id. If no sealed or
     // staged sector exists with the provided id, produce an error.
     pub fn get_seal_status(&self, sector_id: SectorId) -> Result<SealStatus> {
         helpers::get_seal_status(&self.state.staged, &self.state.sealed, sector_id)
     }
 
+    // Returns the sealing status of the sector with the provided id along
+    // with, if it has been sealed and a health check is requested, the health
+    // of its replica, checked in the provided mode.
+    pub fn get_seal_status_with_health_check(
+        &self,
+        sector_id: SectorId,
+        check_health: Option<HealthCheckMode>,
+    ) -> Result<(SealStatus, Option<SealedSectorHealth>)> {
+        let status = self.get_seal_status(sector_id)?;
+
+        let health = match (&status, check_health) {
+            (SealStatus::Sealed(_), Some(mode)) => Some(self.check_sector_health(sector_id, mode)?),
+            _ => None,
+        };
+
+        Ok((status, health))
+    }
+
+    // Returns the padded start byte and length of each piece in the sector
+    // with the provided id. If no sealed or staged sector exists with the
+    // provided id, produce an error.
+    pub fn get_piece_layout(&self, sector_id: SectorId) -> Result<Vec<PieceLayout>> {
+        helpers::get_piece_layout(&self.state.staged, &self.state.sealed, sector_id)
+    }
+
     // Write the piece to storage, obtaining the sector id with which the
     // piece-bytes are now associated and a vector of SealTaskPrototypes.
     pub fn add_piece(
@@ -140,49 +660,539 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
         piece_bytes_amount: u64,
         piece_file: impl std::io::Read,
         store_until: SecondsSinceEpoch,
+        idempotency_key: Option<String>,
+        deal_info: PieceDealInfo,
     ) -> Result<(SectorId, Vec<SealTaskPrototype>)> {
+        // a retried call finds the piece it added before and stages nothing
+        if let Some(key) = &idempotency_key {
+            let found = helpers::find_piece_by_idempotency_key(
+                &self.state.staged,
+                &self.state.sealed,
+                key,
+                &piece_key,
+                piece_bytes_amount,
+            )?;
+
+            if let Some(sector_id) = found {
+                return Ok((sector_id, vec![]));
+            }
+        }
+
+        helpers::check_piece_labels(&deal_info)?;
+
+        helpers::check_add_piece_quota(
+            &self.quota,
+            &self.state.staged,
+            &self.state.sealed,
+            self.max_user_bytes_per_staged_sector,
+            self.sector_size,
+            piece_bytes_amount,
+        )?;
+
+        let origin = deal_info.origin.clone();
+
+        let transition = SectorTransition::PieceAdded {
+            piece_key: piece_key.clone(),
+            origin: origin.clone(),
+        };
+
+        let added_piece_key = piece_key.clone();
+
+        let piece_file: Box<dyn std::io::Read + '_> = match self.ingest_rate_limiter.as_mut() {
+            Some(limiter) => Box::new(ThrottledReader::new(piece_file, limiter)),
+            None => Box::new(piece_file),
+        };
+
+        let options = helpers::AddPieceOptions {
+            store_until,
+            idempotency_key,
+            deal_info,
+            sector_id_provider: self.sector_id_provider.as_mut(),
+            write_config: &self.staged_write_config,
+            now: self.clock.now(),
+        };
+
         let destination_sector_id = helpers::add_piece(
             &self.sector_store,
+            &self.state.sealed,
             &mut self.state.staged,
             piece_bytes_amount,
             piece_key,
             piece_file,
-            store_until,
+            options,
         )?;
 
+        // a piece without a digest is sealed unchecked, which isn't worth
+        // failing the piece over
+        if let Some(staged_sector) = self.state.staged.sectors.get(&destination_sector_id) {
+            if let Err(err) = helpers::record_last_piece(
+                &self.kv_store,
+                &SnapshotKey::new(self.prover_id, self.sector_size),
+                &self.sector_store,
+                staged_sector,
+            ) {
+                warn!(
+                    "could not record the digest of a piece in sector {}: {}",
+                    destination_sector_id, err
+                );
+            }
+        }
+
+        self.record(destination_sector_id, transition);
+
+        self.events.emit(SectorBuilderEvent::PieceAdded {
+            sector_id: destination_sector_id,
+            piece_key: added_piece_key,
+            origin,
+        });
+
         let to_seal = self.check_and_schedule(false)?;
         self.checkpoint().expects(FATAL_SNPSHT);
 
         Ok((destination_sector_id, to_seal))
     }
 
-    // For demo purposes. Schedules sealing of all staged sectors.
-    pub fn seal_all_staged_sectors(&mut self) -> Result<Vec<SealTaskPrototype>> {
-        let to_seal = self.check_and_schedule(true)?;
+    // Replaces the storage quota enforced when adding pieces. Sectors which
+    // already exceed a lowered quota are left in place.
+    pub fn set_storage_quota(&mut self, quota: StorageQuota) {
+        self.quota = quota;
+    }
+
+    // Replaces the policy applied to the unsealed data of sectors which have
+    // been sealed successfully.
+    pub fn set_staged_data_retention(&mut self, retention: StagedDataRetention) -> Result<()> {
+        if let StagedDataRetention::MoveToCache(dir) = &retention {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        self.staged_data_retention = retention;
+
+        Ok(())
+    }
+
+    // Replaces the strategy with which the ids of new staged sectors are
+    // allocated.
+    pub fn set_sector_id_provider(&mut self, sector_id_provider: Box<dyn SectorIdProvider>) {
+        self.sector_id_provider = sector_id_provider;
+    }
+
+    // Replaces the limits on how long seal, unseal and PoSt tasks may run.
+    // Limits apply to tasks started after they've been replaced.
+    pub fn set_task_timeouts(&mut self, task_timeouts: TaskTimeouts) {
+        self.task_timeouts = task_timeouts;
+    }
+
+    // Replaces how pieces are written to staged sector-files. Applies to
+    // pieces added after it's been replaced.
+    pub fn set_staged_write_config(&mut self, staged_write_config: StagedWriteConfig) {
+        self.staged_write_config = staged_write_config;
+    }
+
+    // Replaces the limit, in bytes per second, on the rate at which pieces are
+    // read while they're added. Applies to pieces added after it's been
+    // replaced.
+    pub fn set_ingest_rate_limit(&mut self, bytes_per_sec: Option<u64>) {
+        self.ingest_rate_limiter = bytes_per_sec.map(RateLimiter::new);
+    }
+
+    // Replaces the clock against which deadlines and sector ages are checked.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    // Replaces where workers seal sectors. Applies to seal tasks scheduled
+    // after it's been replaced.
+    pub fn set_seal_isolation(&mut self, seal_isolation: SealIsolation) {
+        self.seal_isolation = seal_isolation;
+    }
+
+    // Replaces the algorithm with which the checksums of newly sealed sectors
+    // are calculated. Sectors which have already been sealed keep theirs.
+    pub fn set_checksum_algo(&mut self, checksum_algo: ChecksumAlgo) {
+        self.checksum_algo = checksum_algo;
+    }
+
+    // Replaces the ticket with which sectors are sealed once they're
+    // scheduled for sealing.
+    pub fn set_seal_ticket(&mut self, seal_ticket: SealTicket) {
+        self.seal_ticket = Some(seal_ticket);
+    }
+
+    // Returns the maximum number of staged sectors which accept pieces and
+    // the storage quota, as they may have been changed since initialization.
+    pub fn get_limits(&self) -> (u64, StorageQuota) {
+        (self.max_num_staged_sectors, self.quota.clone())
+    }
+
+    // Replaces the maximum number of staged sectors which accept pieces. When
+    // the number is lowered, the surplus sectors are scheduled for sealing.
+    pub fn set_max_num_staged_sectors(
+        &mut self,
+        max_num_staged_sectors: u64,
+    ) -> Result<Vec<SealTaskPrototype>> {
+        self.max_num_staged_sectors = max_num_staged_sectors;
+        self.schedule_sectors_ready_for_sealing()
+    }
+
+    // Replaces the policy according to which staged sectors which aren't full
+    // yet are sealed. Sectors which satisfy the new policy are scheduled for
+    // sealing right away.
+    pub fn set_auto_seal_policy(
+        &mut self,
+        auto_seal_policy: AutoSealPolicy,
+    ) -> Result<Vec<SealTaskPrototype>> {
+        self.auto_seal_policy = auto_seal_policy;
+        self.schedule_sectors_ready_for_sealing()
+    }
+
+    // Schedules sealing of the staged sectors which are ready to be sealed,
+    // e.g. because they have been staged for longer than the auto-seal policy
+    // allows.
+    pub fn schedule_sectors_ready_for_sealing(&mut self) -> Result<Vec<SealTaskPrototype>> {
+        let to_seal = self.check_and_schedule(false)?;
         self.checkpoint().expects(FATAL_SNPSHT);
 
         Ok(to_seal)
     }
 
+    // Returns how long it takes until a staged sector has been staged for
+    // longer than the auto-seal policy allows, or None if no sector is
+    // subject to a maximum age.
+    pub fn time_until_auto_seal(&self) -> Option<Duration> {
+        helpers::time_until_sectors_expire(
+            &self.state.staged,
+            &self.auto_seal_policy,
+            &self.clock.now(),
+        )
+    }
+
+    // For demo purposes. Schedules sealing of all staged sectors with the
+    // provided ticket. A sector which can't be scheduled doesn't keep the
+    // others from being scheduled; the outcome for every staged sector which
+    // hasn't been sealed is returned with the seal task prototypes.
+    pub fn seal_all_staged_sectors(
+        &mut self,
+        seal_ticket: SealTicket,
+    ) -> (Vec<SealTaskPrototype>, Vec<SealScheduleResult>) {
+        self.set_seal_ticket(seal_ticket);
+
+        let now = self.clock.now();
+
+        let to_be_sealed = helpers::get_sectors_ready_for_sealing(
+            &self.state.staged,
+            self.max_user_bytes_per_staged_sector,
+            self.max_num_staged_sectors,
+            &self.auto_seal_policy,
+            &now,
+            true,
+        );
+
+        let mut results: Vec<SealScheduleResult> = self
+            .state
+            .staged
+            .sectors
+            .values()
+            .filter_map(|sector| {
+                let reason = match sector.seal_status {
+                    SealStatus::Pending | SealStatus::Sealed(_) => return None,
+                    SealStatus::Sealing => format!("{}", err_already_sealing(sector.sector_id)),
+                    SealStatus::Failed(ref failure) => format!("sealing failed: {}", failure),
+                };
+
+                Some(SealScheduleResult {
+                    sector_id: sector.sector_id,
+                    outcome: SealScheduleOutcome::Skipped(reason),
+                })
+            })
+            .collect();
+
+        let mut to_seal: Vec<SealTaskPrototype> = Default::default();
+        for sector_id in to_be_sealed {
+            let outcome = match self.schedule_seal(sector_id, &now) {
+                Ok(proto) => {
+                    to_seal.push(proto);
+                    SealScheduleOutcome::Scheduled
+                }
+                Err(err) => {
+                    warn!(
+                        "could not schedule sector {} for sealing: {}",
+                        sector_id, err
+                    );
+                    SealScheduleOutcome::Error(format!("{}", err))
+                }
+            };
+
+            results.push(SealScheduleResult { sector_id, outcome });
+        }
+
+        results.sort_by_key(|result| result.sector_id);
+
+        self.checkpoint().expects(FATAL_SNPSHT);
+
+        (to_seal, results)
+    }
+
+    // Plans the import of an externally sealed replica, provisioning the
+    // sealed sector access into which a worker validates and copies (or
+    // hard-links, if linking is ok) the replica. The sector is registered
+    // once the worker is done, after which it is treated like any other
+    // sealed sector. The caller verifies the seal proof, if requested, so
+    // that verification doesn't hold up the scheduler; if it did, the time is
+    // recorded in the sector's metadata.
+    pub fn create_import_task_proto(
+        &mut self,
+        replica_path: PathBuf,
+        meta: SealedSectorMetadata,
+        seal_verified: bool,
+        link_ok: bool,
+    ) -> Result<ReplicaTaskPrototype> {
+        let sector_id = meta.sector_id;
+
+        if self.replica_transfers.contains(&sector_id) {
+            return Err(err_sector_exists(sector_id).into());
+        }
+
+        let sector_access = helpers::provision_sealed_sector_import(
+            &self.sector_store,
+            &self.state.staged,
+            &self.state.sealed,
+            sector_id,
+        )?;
+
+        let sector_path = self
+            .sector_store
+            .manager()
+            .sealed_sector_path(&sector_access);
+
+        self.replica_transfers.insert(sector_id);
+
+        Ok(ReplicaTaskPrototype::Import {
+            replica_path,
+            sector_access,
+            sector_path,
+            meta: SealedSectorMetadata {
+                seal_verified_at: if seal_verified {
+                    Some(self.clock.now())
+                } else {
+                    None
+                },
+                ..meta
+            },
+            link_ok,
+        })
+    }
+
+    // Updates the metadata of the sector whose replica a worker has
+    // transferred, or cleans up after a failed transfer.
+    pub fn handle_replica_task_result(
+        &mut self,
+        proto: ReplicaTaskPrototype,
+        result: Result<()>,
+    ) -> Result<()> {
+        let sector_id = proto.sector_id();
+
+        self.replica_transfers.remove(&sector_id);
+
+        match proto {
+            ReplicaTaskPrototype::Import {
+                sector_access,
+                sector_path,
+                meta,
+                ..
+            } => {
+                let result = result.and_then(|_| {
+                    helpers::register_imported_sector(
+                        &mut self.state.staged,
+                        &mut self.state.sealed,
+                        sector_access,
+                        meta,
+                    )
+                });
+
+                if result.is_err() {
+                    let _ = std::fs::remove_file(&sector_path);
+                    return result;
+                }
+
+                self.record(sector_id, SectorTransition::SealedSectorImported);
+                self.checkpoint().expects(FATAL_SNPSHT);
+
+                Ok(())
+            }
+            ReplicaTaskPrototype::Export { .. } => result,
+            ReplicaTaskPrototype::Move { target, .. } => {
+                result?;
+
+                let meta = self
+                    .state
+                    .sealed
+                    .sectors
+                    .get_mut(&sector_id)
+                    .ok_or_else(|| {
+                        err_unrecov(format!("no sealed sector with id {} found", sector_id))
+                    })?;
+
+                meta.location = target;
+
+                self.record(sector_id, SectorTransition::SealedSectorMoved);
+                self.checkpoint().expects(FATAL_SNPSHT);
+
+                Ok(())
+            }
+        }
+    }
+
+    // Plans writing a bundle (replica and JSON metadata manifest) for the
+    // sealed sector with the provided id to the destination directory, which
+    // a worker does. The sector's replica can't be moved until it's done.
+    pub fn create_export_task_proto(
+        &mut self,
+        sector_id: SectorId,
+        dest_dir: PathBuf,
+    ) -> Result<ReplicaTaskPrototype> {
+        self.ensure_not_transferring(sector_id)?;
+
+        let (sector_path, meta) = helpers::plan_sector_export(
+            &self.sector_store,
+            &self.sealed_stores,
+            &self.state.sealed,
+            sector_id,
+        )?;
+
+        self.replica_transfers.insert(sector_id);
+
+        Ok(ReplicaTaskPrototype::Export {
+            sector_path,
+            meta,
+            dest_dir,
+        })
+    }
+
+    // Fails if a worker is transferring the replica of the sector with the
+    // provided id, as one transfer could pull the replica from under another.
+    fn ensure_not_transferring(&self, sector_id: SectorId) -> Result<()> {
+        if self.replica_transfers.contains(&sector_id) {
+            return Err(err_unrecov(format!(
+                "the replica of sector {} is being transferred",
+                sector_id
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    // Registers a directory in which sealed sectors can be stored under the
+    // provided store id. Sectors which have been moved to a store are located
+    // relative to its directory, so a store can be relocated by registering
+    // its new directory under the same id.
+    pub fn register_sealed_store(&mut self, store_id: String, dir: PathBuf) -> Result<()> {
+        std::fs::create_dir_all(&dir)?;
+        self.sealed_stores.insert(store_id, dir);
+
+        Ok(())
+    }
+
+    // Plans moving the replica of the sealed sector with the provided id to
+    // the target location, e.g. to archive it on cheaper storage, which a
+    // worker does. The sector's location is updated once it's done.
+    pub fn create_move_task_proto(
+        &mut self,
+        sector_id: SectorId,
+        target: SealedSectorLocation,
+    ) -> Result<ReplicaTaskPrototype> {
+        self.ensure_not_transferring(sector_id)?;
+
+        let (source_path, target_path) = helpers::plan_sealed_sector_move(
+            &self.sector_store,
+            &self.sealed_stores,
+            &self.state.sealed,
+            sector_id,
+            &target,
+        )?;
+
+        self.replica_transfers.insert(sector_id);
+
+        Ok(ReplicaTaskPrototype::Move {
+            sector_id,
+            source_path,
+            target_path,
+            target,
+        })
+    }
+
+    // Plans computing the checksum and length of the sealed sector with the
+    // provided id, if its metadata lacks them, which a worker does. Returns
+    // None if the metadata lacks neither.
+    pub fn create_hydrate_task_proto(
+        &mut self,
+        sector_id: SectorId,
+    ) -> Result<Option<HydrateTaskPrototype>> {
+        self.ensure_not_transferring(sector_id)?;
+
+        let proto = helpers::plan_sector_integrity_hydration(
+            &self.sector_store,
+            &self.sealed_stores,
+            &self.state.sealed,
+            sector_id,
+        )?;
+
+        // the replica mustn't be moved from under the worker reading it
+        if proto.is_some() {
+            self.replica_transfers.insert(sector_id);
+        }
+
+        Ok(proto)
+    }
+
+    // Persists the checksum and length a worker computed for a sealed
+    // sector. Returns whether they were missing.
+    pub fn handle_hydrate_task_result(
+        &mut self,
+        proto: HydrateTaskPrototype,
+        result: Result<SectorIntegrity>,
+    ) -> Result<bool> {
+        let sector_id = proto.sector_id;
+
+        self.replica_transfers.remove(&sector_id);
+
+        let hydrated = helpers::apply_sector_integrity(&mut self.state.sealed, sector_id, result?)?;
+
+        if hydrated {
+            self.record(sector_id, SectorTransition::SealedSectorHydrated);
+            self.checkpoint().expects(FATAL_SNPSHT);
+        }
+
+        Ok(hydrated)
+    }
+
     // Produces a vector containing metadata for all sealed sectors that this
-    // SectorBuilder knows about. Includes sector health-information on request.
-    pub fn get_sealed_sectors(&self, check_health: bool) -> Result<Vec<GetSealedSectorResult>> {
+    // SectorBuilder knows about. Includes sector health-information, checked
+    // in the provided mode, on request.
+    pub fn get_sealed_sectors(
+        &self,
+        check_health: Option<HealthCheckMode>,
+    ) -> Result<Vec<GetSealedSectorResult>> {
         use rayon::prelude::*;
 
         let sectors_iter = self.state.sealed.sectors.values().cloned();
 
-        if !check_health {
-            return Ok(sectors_iter
-                .map(GetSealedSectorResult::WithoutHealth)
-                .collect());
-        }
+        let mode = match check_health {
+            Some(mode) => mode,
+            None => {
+                return Ok(sectors_iter
+                    .map(GetSealedSectorResult::WithoutHealth)
+                    .collect());
+            }
+        };
 
-        let with_path: Vec<(PathBuf, SealedSectorMetadata)> = sectors_iter
+        // a sector whose location can't be resolved (e.g. because it lives in
+        // a store which hasn't been registered) is reported as missing
+        let with_path: Vec<(Option<PathBuf>, SealedSectorMetadata)> = sectors_iter
             .map(|meta| {
-                let pbuf = self
-                    .sector_store
-                    .manager()
-                    .sealed_sector_path(&meta.sector_access);
+                let pbuf =
+                    helpers::sealed_sector_path(&self.sector_store, &self.sealed_stores, &meta)
+                        .ok();
 
                 (pbuf, meta)
             })
@@ -193,63 +1203,173 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
         with_path
             .into_par_iter()
             .map(|(pbuf, meta)| {
-                let health = helpers::get_sealed_sector_health(&pbuf, &meta)?;
+                let health = match pbuf {
+                    Some(pbuf) => helpers::check_sealed_sector_health(&pbuf, &meta, mode)?,
+                    None => SealedSectorHealth::ErrorMissing,
+                };
+
                 Ok(WithHealth(health, meta))
             })
             .collect()
     }
 
+    // Checks the health of the sealed sector with the provided id only,
+    // hashing (part of) its replica but no other. A sector whose location
+    // can't be resolved is reported as missing.
+    pub fn check_sector_health(
+        &self,
+        sector_id: SectorId,
+        mode: HealthCheckMode,
+    ) -> Result<SealedSectorHealth> {
+        let meta =
+            self.state.sealed.sectors.get(&sector_id).ok_or_else(|| {
+                err_unrecov(format!("no sealed sector with id {} found", sector_id))
+            })?;
+
+        match helpers::sealed_sector_path(&self.sector_store, &self.sealed_stores, meta) {
+            Ok(path) => helpers::check_sealed_sector_health(&path, meta, mode),
+            Err(_) => Ok(SealedSectorHealth::ErrorMissing),
+        }
+    }
+
+    // Reports the size and number of keys of the store holding the metadata
+    // and when it was last flushed.
+    pub fn kv_store_stats(&self) -> Result<KvStoreStats> {
+        self.kv_store.stats()
+    }
+
+    // Lists the pieces which were added with the provided origin, according
+    // to the journal. Pieces whose entries have been pruned from the journal
+    // aren't listed.
+    pub fn get_pieces_by_origin(&self, origin: &str) -> Result<Vec<PieceProvenance>> {
+        let key = SnapshotKey::new(self.prover_id, self.sector_size);
+
+        let mut entries = helpers::load_journal(
+            &self.kv_store,
+            &key,
+            helpers::journal_tail(&self.kv_store, &key)?,
+        )?;
+        entries.extend(self.unflushed_journal.iter().cloned());
+
+        Ok(helpers::get_pieces_by_origin(&entries, origin))
+    }
+
+    // Reports the storage consumed by this SectorBuilder's sectors and the
+    // capacity of the directories in which they are stored.
+    pub fn get_storage_report(&self) -> Result<StorageReport> {
+        helpers::get_storage_report(
+            &self.sector_store,
+            &self.sealed_stores,
+            &self.state.staged,
+            &self.state.sealed,
+        )
+    }
+
+    // Calculates statistics of how long sealing sectors took, of the sectors
+    // which finished sealing at or after the provided time, if any.
+    pub fn get_seal_statistics(&self, since: Option<&SecondsSinceEpoch>) -> SealStatistics {
+        helpers::get_seal_statistics(&self.state.sealed, since)
+    }
+
+    // Returns the sealed sectors whose every piece was to be stored until a
+    // time before the provided time.
+    pub fn get_expired_sectors(&self, now: &SecondsSinceEpoch) -> Vec<SealedSectorMetadata> {
+        helpers::get_expired_sectors(&self.state.sealed, now)
+    }
+
+    // Returns the ids of the staged sectors which would be scheduled for
+    // sealing if the sectors ready for sealing were scheduled now, without
+    // scheduling them.
+    pub fn get_sectors_ready_for_sealing(&self, seal_all_staged_sectors: bool) -> Vec<SectorId> {
+        helpers::get_sectors_ready_for_sealing(
+            &self.state.staged,
+            self.max_user_bytes_per_staged_sector,
+            self.max_num_staged_sectors,
+            &self.auto_seal_policy,
+            &self.clock.now(),
+            seal_all_staged_sectors,
+        )
+    }
+
+    // Returns the metadata of the staged sector with the provided id. If no
+    // staged sector exists with the provided id, produce an error.
+    pub fn get_staged_sector(&self, sector_id: SectorId) -> Result<StagedSectorMetadata> {
+        self.state
+            .staged
+            .sectors
+            .get(&sector_id)
+            .cloned()
+            .ok_or_else(|| {
+                err_unrecov(format!("no staged sector with id {} found", sector_id)).into()
+            })
+    }
+
     // Produces a vector containing metadata for all staged sectors that this
-    // SectorBuilder knows about. If a sealing status is provided, return only
-    // the staged sector metadata with matching status.
+    // SectorBuilder knows about whose sealing status matches the provided
+    // filter.
     pub fn get_staged_sector_filtered(
         &self,
-        target_status: Option<SealStatus>,
+        filter: StagedSectorFilter,
     ) -> Vec<StagedSectorMetadata> {
         self.state
             .staged
             .sectors
             .values()
-            .filter(|meta| {
-                if let Some(ref s) = target_status {
-                    s == &meta.seal_status
-                } else {
-                    true
-                }
-            })
+            .filter(|meta| filter.matches(&meta.seal_status))
             .cloned()
             .collect()
     }
 
-    // Read the raw (without bit-padding) bytes from the provided path into a
-    // buffer and return the buffer.
+    // Map the raw (without bit-padding) bytes at the provided path into
+    // memory. The file only exists to hold the unsealed bytes, so it's
+    // removed once they've been mapped.
     pub fn read_unsealed_bytes_from(
         &mut self,
         result: Result<(UnpaddedBytesAmount, PathBuf)>,
-    ) -> Result<Vec<u8>> {
+    ) -> Result<MappedBytes> {
         result.and_then(|(n, pbuf)| {
-            let buffer = self.sector_store.manager().read_raw(
-                pbuf.to_str()
+            // unsealed bytes are written to a file in the staged sector
+            // directory, named by its access
+            let access = SectorAccess::new(
+                pbuf.file_name()
+                    .and_then(|name| name.to_str())
                     .ok_or_else(|| format_err!("conversion failed"))?,
-                0,
-                n,
             )?;
 
-            Ok(buffer)
+            let mapped = self.sector_store.manager().map_raw(&access, 0, n)?;
+
+            // the mapping remains valid after the file has been removed,
+            // except on platforms which refuse to remove mapped files
+            let _ = std::fs::remove_file(&pbuf);
+
+            Ok(mapped)
         })
     }
 
-    // Update metadata to reflect the sealing results.
+    // Update metadata to reflect the sealing results. If the sector was
+    // sealed and a replicator is set, returns the job which copies the new
+    // replica to the replicator's secondary location.
     pub fn handle_seal_result(
         &mut self,
         sector_id: SectorId,
-        sector_access: String,
+        sector_access: SectorAccess,
         sector_path: PathBuf,
-        result: Result<SealOutput>,
-    ) {
+        result: Result<(SealOutput, ReplicaChecksums)>,
+        timing: SealTiming,
+    ) -> Option<ReplicationJob> {
+        // a sector which is sealed again is sealed next to its previous
+        // replica, which is only replaced once sealing has succeeded
+        let final_path = self
+            .sector_store
+            .manager()
+            .sealed_sector_path(&sector_access);
+
         // scope exists to end the mutable borrow of self so that we can
         // checkpoint
-        {
+        let sealed = {
+            let sector_store = &self.sector_store;
+            let sealed_stores = &self.sealed_stores;
+            let retention = &self.staged_data_retention;
             let staged_state = &mut self.state.staged;
             let sealed_state = &mut self.state.sealed;
 
@@ -258,8 +1378,10 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
                 .get_mut(&sector_id)
                 .expect("missing staged sector");
 
-            let _ = result
-                .and_then(|output| {
+            let is_repair = std::mem::replace(&mut staged_sector.repairing, false);
+
+            result
+                .and_then(|(output, checksums)| {
                     let SealOutput {
                         comm_r,
                         comm_r_star,
@@ -269,12 +1391,32 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
                         piece_inclusion_proofs,
                     } = output;
 
-                    // generate checksum
-                    let blake2b_checksum =
-                        helpers::calculate_checksum(&sector_path)?.as_ref().to_vec();
+                    // a repaired replica must be identical to the one it
+                    // replaces, which is what the sector was committed to
+                    if is_repair {
+                        if let Some(previous) = sealed_state.sectors.get(&sector_id) {
+                            if previous.comm_r != comm_r {
+                                return Err(err_invalid_replica(format!(
+                                    "comm_r of repaired sector {} doesn't match its original",
+                                    sector_id
+                                ))
+                                .into());
+                            }
+                        }
+                    }
+
+                    // the worker checksummed the replica once it was sealed
+                    let ReplicaChecksums {
+                        checksum: blake2b_checksum,
+                        algo: checksum_algo,
+                        extent_checksums,
+                        len,
+                        ..
+                    } = checksums;
 
-                    // get number of bytes in sealed sector-file
-                    let len = std::fs::metadata(&sector_path)?.len();
+                    if sector_path != final_path {
+                        std::fs::rename(&sector_path, &final_path)?;
+                    }
 
                     // combine the piece commitment, piece inclusion proof, and other piece
                     // metadata into a single struct (to be persisted to metadata store)
@@ -289,6 +1431,10 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
                             num_bytes: piece.num_bytes,
                             comm_p: Some(comm_p),
                             piece_inclusion_proof: Some(piece_inclusion_proof.into()),
+                            idempotency_key: piece.idempotency_key,
+                            store_until: piece.store_until,
+                            deal_id: piece.deal_id,
+                            labels: piece.labels,
                         })
                         .collect();
 
@@ -302,19 +1448,205 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
                         proof,
                         blake2b_checksum,
                         len,
+                        location: SealedSectorLocation::Default,
+                        seal_ticket: staged_sector.seal_ticket,
+                        checksum_algo,
+                        extent_checksums,
+                        seal_timing: Some(timing),
+                        seal_verified_at: None,
+                        replication: None,
                     };
 
                     Ok(meta)
                 })
                 .map_err(|err| {
-                    staged_sector.seal_status = SealStatus::Failed(format!("{}", err_unrecov(err)));
+                    if sector_path != final_path {
+                        let _ = std::fs::remove_file(&sector_path);
+                    }
+
+                    let status =
+                        SealStatus::Failed(helpers::classify_seal_failure(&err, &sector_path));
+
+                    if let Err(err) = transition_seal_status(staged_sector, status) {
+                        error!("could not record seal failure: {}", err);
+                    }
                 })
                 .map(|meta| {
+                    // a previous replica which has been moved out of the
+                    // sealed sector directory is superseded by the new one
+                    if let Some(previous) = sealed_state.sectors.get(&sector_id) {
+                        if let Ok(previous_path) =
+                            helpers::sealed_sector_path(sector_store, sealed_stores, previous)
+                        {
+                            if previous_path != final_path {
+                                let _ = std::fs::remove_file(previous_path);
+                            }
+                        }
+                    }
+
                     sealed_state.sectors.insert(sector_id, meta.clone());
-                    staged_sector.seal_status = SealStatus::Sealed(Box::new(meta));
-                });
+
+                    let status = SealStatus::Sealed(Box::new(meta));
+
+                    if let Err(err) = transition_seal_status(staged_sector, status) {
+                        error!("could not record sealed sector: {}", err);
+                    }
+
+                    // failing to apply the retention policy leaves the
+                    // unsealed data in place, which doesn't affect the
+                    // sealed sector
+                    if let Err(err) =
+                        helpers::retain_staged_data(sector_store, retention, staged_sector)
+                    {
+                        warn!("could not apply staged data retention policy: {}", err);
+                    }
+                })
+                .is_ok()
+        };
+
+        let transition = if sealed {
+            SectorTransition::Sealed
+        } else {
+            SectorTransition::SealFailed
+        };
+
+        self.record(sector_id, transition);
+        self.checkpoint().expects(FATAL_SNPSHT);
+
+        if !sealed || self.replicator.is_none() {
+            return None;
+        }
+
+        self.replicate_sealed_sector(sector_id)
+            .map_err(|err| warn!("could not replicate sealed sector {}: {}", sector_id, err))
+            .ok()
+    }
+
+    // Marks the staged sector as failed after its seal was cancelled before
+    // a worker took it. Seals cancelled once a worker has taken them fail
+    // through handle_seal_result instead.
+    pub fn handle_cancelled_seal(&mut self, sector_id: SectorId) -> Result<()> {
+        let staged_sector = self
+            .state
+            .staged
+            .sectors
+            .get_mut(&sector_id)
+            .ok_or_else(|| err_unrecov(format!("missing staged sector {}", sector_id)))?;
+
+        transition_seal_status(staged_sector, SealStatus::Failed(SealFailure::Cancelled))?;
+
+        self.record(sector_id, SectorTransition::SealFailed);
+        self.checkpoint()
+    }
+
+    // Replaces the replicator which copies the replicas of newly sealed
+    // sectors to a secondary location, or stops replicating them if None is
+    // provided. Returns the jobs which restart the replication of sectors
+    // whose replication was pending, e.g. because the SectorBuilder stopped
+    // while their replicas were being copied.
+    pub fn set_replicator(
+        &mut self,
+        replicator: Option<Arc<dyn SealedSectorReplicator>>,
+    ) -> Vec<ReplicationJob> {
+        self.replicator = replicator;
+
+        if self.replicator.is_none() {
+            return vec![];
+        }
+
+        let pending: Vec<SectorId> = self
+            .state
+            .sealed
+            .sectors
+            .values()
+            .filter(|meta| match meta.replication {
+                Some(ReplicationStatus::Pending) | Some(ReplicationStatus::Copying { .. }) => true,
+                _ => false,
+            })
+            .map(|meta| meta.sector_id)
+            .collect();
+
+        pending
+            .into_iter()
+            .filter_map(|sector_id| {
+                self.replicate_sealed_sector(sector_id)
+                    .map_err(|err| {
+                        warn!("could not replicate sealed sector {}: {}", sector_id, err)
+                    })
+                    .ok()
+            })
+            .collect()
+    }
+
+    // Marks the replication of the sealed sector with the provided id as
+    // pending and returns the job which copies its replica with the
+    // replicator, whatever the outcome of previous attempts.
+    pub fn replicate_sealed_sector(&mut self, sector_id: SectorId) -> Result<ReplicationJob> {
+        let replicator = self
+            .replicator
+            .clone()
+            .ok_or_else(|| err_unrecov("no replicator is set"))?;
+
+        let meta = self
+            .state
+            .sealed
+            .sectors
+            .get_mut(&sector_id)
+            .ok_or_else(|| err_unrecov(format!("no sealed sector with id {} found", sector_id)))?;
+
+        let replica_path =
+            helpers::sealed_sector_path(&self.sector_store, &self.sealed_stores, meta)?;
+
+        meta.replication = Some(ReplicationStatus::Pending);
+
+        self.record(sector_id, SectorTransition::ReplicationStarted);
+        self.checkpoint()?;
+
+        Ok(ReplicationJob {
+            replicator,
+            sector_id,
+            replica_path,
+        })
+    }
+
+    // Records how many bytes of the replica of the sealed sector with the
+    // provided id have been copied so far. Progress is published to queries,
+    // but neither journaled nor flushed on its own, as a copy which is
+    // interrupted starts over anyway.
+    pub fn handle_replication_progress(&mut self, sector_id: SectorId, bytes_copied: u64) {
+        let meta = match self.state.sealed.sectors.get_mut(&sector_id) {
+            Some(meta) => meta,
+            None => return,
+        };
+
+        match meta.replication {
+            Some(ReplicationStatus::Pending) | Some(ReplicationStatus::Copying { .. }) => {
+                meta.replication = Some(ReplicationStatus::Copying { bytes_copied });
+            }
+            _ => return,
+        }
+
+        self.published_state.publish(&self.state);
+    }
+
+    // Records the outcome of copying the replica of the sealed sector with
+    // the provided id. Outcomes for sectors which are no longer sealed are
+    // dropped.
+    pub fn handle_replication_result(&mut self, sector_id: SectorId, result: Result<String>) {
+        let status = match result {
+            Ok(location) => ReplicationStatus::Replicated {
+                location,
+                replicated_at: self.clock.now(),
+            },
+            Err(err) => ReplicationStatus::Failed(format!("{}", err)),
+        };
+
+        match self.state.sealed.sectors.get_mut(&sector_id) {
+            Some(meta) => meta.replication = Some(status),
+            None => return,
         }
 
+        self.record(sector_id, SectorTransition::ReplicationFinished);
         self.checkpoint().expects(FATAL_SNPSHT);
     }
 
@@ -326,47 +1658,341 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
     ) -> Result<Vec<SealTaskPrototype>> {
         let staged_state = &mut self.state.staged;
 
+        let now = self.clock.now();
+
         let to_be_sealed = helpers::get_sectors_ready_for_sealing(
             staged_state,
             self.max_user_bytes_per_staged_sector,
             self.max_num_staged_sectors,
+            &self.auto_seal_policy,
+            &now,
             seal_all_staged_sectors,
         );
 
         let mut to_seal: Vec<SealTaskPrototype> = Default::default();
         for sector_id in to_be_sealed {
-            to_seal.push(self.create_seal_task_proto(sector_id)?);
+            to_seal.push(self.schedule_seal(sector_id, &now)?);
         }
 
         Ok(to_seal)
     }
 
+    // Schedules the staged sector with the provided id for sealing with the
+    // current seal ticket.
+    fn schedule_seal(
+        &mut self,
+        sector_id: SectorId,
+        now: &SecondsSinceEpoch,
+    ) -> Result<SealTaskPrototype> {
+        // sectors are sealed with the ticket which is current at the time
+        // they're scheduled; the ticket is persisted with the sector so
+        // that sealing resumed after a restart uses the same ticket
+        if let Some(sector) = self.state.staged.sectors.get_mut(&sector_id) {
+            sector.seal_ticket = self.seal_ticket;
+
+            if let Some(deadline) = helpers::deadline_at_risk(sector, &self.auto_seal_policy, now) {
+                warn!(
+                    "sealing sector {} close to the deadline of one of its pieces ({})",
+                    sector_id, deadline.0
+                );
+
+                self.events.emit(SectorBuilderEvent::DealDeadlineAtRisk {
+                    sector_id,
+                    deadline,
+                });
+            }
+        }
+
+        self.create_seal_task_proto(sector_id)
+    }
+
+    // Schedules the sealed sector with the provided id to be sealed again,
+    // using the provided ticket, from its retained staged sector-file. The
+    // previous replica and metadata remain in place until sealing succeeds.
+    pub fn create_reseal_task_proto(
+        &mut self,
+        sector_id: SectorId,
+        seal_ticket: SealTicket,
+    ) -> Result<SealTaskPrototype> {
+        self.create_resealing_task_proto(sector_id, seal_ticket, false)
+    }
+
+    // Schedules the sealed sector with the provided id to be sealed again,
+    // with the provided ticket, from its retained staged sector-file. If the
+    // sector is repaired, the new replica must match the comm_r of the one it
+    // replaces, which is persisted with the sector's metadata so that it's
+    // still checked if sealing is resumed after a restart.
+    fn create_resealing_task_proto(
+        &mut self,
+        sector_id: SectorId,
+        seal_ticket: SealTicket,
+        repairing: bool,
+    ) -> Result<SealTaskPrototype> {
+        // the resealed replica replaces the one at the sector's location,
+        // which mustn't change in the meantime
+        self.ensure_not_transferring(sector_id)?;
+
+        if !self.state.sealed.sectors.contains_key(&sector_id) {
+            return Err(
+                err_unrecov(format!("no sealed sector with id {} found", sector_id)).into(),
+            );
+        }
+
+        let staged_sector = self
+            .state
+            .staged
+            .sectors
+            .get_mut(&sector_id)
+            .ok_or_else(|| {
+                err_unrecov(format!("no staged data retained for sector {}", sector_id))
+            })?;
+
+        if staged_sector.seal_status == SealStatus::Sealing {
+            return Err(err_already_sealing(sector_id).into());
+        }
+
+        let exists = helpers::staged_sector_file(&self.sector_store, staged_sector)
+            .map(|path| path.exists())
+            .unwrap_or(false);
+
+        if !exists {
+            return Err(err_unrecov(format!(
+                "unsealed data of sector {} has not been retained",
+                sector_id
+            ))
+            .into());
+        }
+
+        staged_sector.seal_ticket = Some(seal_ticket);
+        staged_sector.repairing = repairing;
+
+        let proto = self.create_seal_task_proto(sector_id)?;
+        self.checkpoint().expects(FATAL_SNPSHT);
+
+        Ok(proto)
+    }
+
+    // Plans checking that the replica of the sealed sector with the provided
+    // id is corrupted, which a worker does before the sector is repaired.
+    pub fn create_repair_check_task_proto(
+        &mut self,
+        sector_id: SectorId,
+    ) -> Result<RepairCheckTaskPrototype> {
+        self.ensure_not_transferring(sector_id)?;
+
+        // a sector which can't be repaired anyway isn't worth checking
+        self.original_seal_ticket(sector_id)?;
+
+        let meta = &self.state.sealed.sectors[&sector_id];
+        let replica_path =
+            helpers::sealed_sector_path(&self.sector_store, &self.sealed_stores, meta)?;
+
+        // the replica mustn't be moved from under the worker reading it
+        self.replica_transfers.insert(sector_id);
+
+        Ok(RepairCheckTaskPrototype {
+            replica_path,
+            meta: meta.clone(),
+        })
+    }
+
+    // Schedules the corrupted replica of the sealed sector which a worker
+    // checked to be restored by sealing its retained staged sector-file again
+    // with the sector's original ticket. The resulting replica only replaces
+    // the corrupted one if its comm_r matches the one recorded for the
+    // sector.
+    pub fn handle_repair_check_result(
+        &mut self,
+        proto: RepairCheckTaskPrototype,
+        result: Result<SealedSectorHealth>,
+    ) -> Result<SealTaskPrototype> {
+        let sector_id = proto.meta.sector_id;
+
+        self.replica_transfers.remove(&sector_id);
+
+        match result? {
+            SealedSectorHealth::ErrorInvalidChecksum | SealedSectorHealth::ErrorInvalidLength => (),
+            health => {
+                return Err(err_unrecov(format!(
+                    "sector {} can't be repaired (health: {:?})",
+                    sector_id, health
+                ))
+                .into());
+            }
+        }
+
+        let seal_ticket = self.original_seal_ticket(sector_id)?;
+
+        self.create_resealing_task_proto(sector_id, seal_ticket, true)
+    }
+
+    // Returns the ticket with which the sealed sector with the provided id was
+    // sealed, which a repair must seal it with again.
+    fn original_seal_ticket(&self, sector_id: SectorId) -> Result<SealTicket> {
+        let meta =
+            self.state.sealed.sectors.get(&sector_id).ok_or_else(|| {
+                err_unrecov(format!("no sealed sector with id {} found", sector_id))
+            })?;
+
+        let seal_ticket = meta
+            .seal_ticket
+            .or_else(|| {
+                self.state
+                    .staged
+                    .sectors
+                    .get(&sector_id)
+                    .and_then(|staged_sector| staged_sector.seal_ticket)
+            })
+            .ok_or_else(|| {
+                err_unrecov(format!(
+                    "sector {} can't be repaired, as the ticket it was sealed with isn't recorded",
+                    sector_id
+                ))
+            })?;
+
+        Ok(seal_ticket)
+    }
+
+    // Applies the stale seal policy to the sectors which are being sealed
+    // according to the metadata when the SectorBuilder starts, none of whose
+    // seals is running. Returns the seal task prototypes of the sectors whose
+    // sealing is resumed; the others are marked failed or reverted to
+    // pending.
+    pub fn handle_stale_seals(
+        &mut self,
+        policy: StaleSealPolicy,
+    ) -> Result<Vec<SealTaskPrototype>> {
+        let mut stale: Vec<SectorId> = self
+            .get_staged_sector_filtered(StagedSectorFilter::Sealing)
+            .into_iter()
+            .map(|meta| meta.sector_id)
+            .collect();
+
+        stale.sort();
+
+        let (status, transition) = match policy {
+            StaleSealPolicy::Resume => {
+                return stale
+                    .into_iter()
+                    .map(|sector_id| self.create_seal_task_proto(sector_id))
+                    .collect();
+            }
+            StaleSealPolicy::MarkFailed => (
+                SealStatus::Failed(SealFailure::Interrupted),
+                SectorTransition::SealFailed,
+            ),
+            StaleSealPolicy::RevertToPending => {
+                (SealStatus::Pending, SectorTransition::SealReverted)
+            }
+        };
+
+        for sector_id in stale {
+            if let Some(staged_sector) = self.state.staged.sectors.get_mut(&sector_id) {
+                transition_seal_status(staged_sector, status.clone())?;
+                staged_sector.repairing = false;
+            }
+
+            warn!(
+                "sealing of sector {} was interrupted, applied {:?} policy",
+                sector_id, policy
+            );
+
+            self.record(sector_id, transition.clone());
+        }
+
+        self.checkpoint()?;
+
+        Ok(vec![])
+    }
+
     // creates a seal task prototype for the provided sector id and modifies
     // metadata to reflect the fact that it's about to be sealed
     pub fn create_seal_task_proto(&mut self, sector_id: SectorId) -> Result<SealTaskPrototype> {
-        let staged_state = &mut self.state.staged;
+        let proto = self.seal_task_proto(sector_id)?;
+
+        // provision the sealed sector-file of a sector which has never been
+        // sealed; a resealed sector is sealed into a temporary file instead
+        if !self.state.sealed.sectors.contains_key(&sector_id) {
+            self.sector_store
+                .manager()
+                .new_sealed_sector_access(sector_id)
+                .map_err(failure::Error::from)?;
+        }
+
+        // mutate staged sector state such that we don't try to write any
+        // more pieces to it
+        if let Some(staged_sector) = self.state.staged.sectors.get_mut(&sector_id) {
+            transition_seal_status(staged_sector, SealStatus::Sealing)?;
+        }
 
-        let mut staged_sector = staged_state
+        self.record(sector_id, SectorTransition::SealStarted);
+
+        Ok(proto)
+    }
+
+    // Creates a seal task prototype for the provided sector id without
+    // modifying any metadata or touching any sector-file. A sector which
+    // hasn't been scheduled for sealing yet is planned with the current seal
+    // ticket. Sectors which are being sealed can't be planned.
+    pub fn plan_seal_task_proto(&self, sector_id: SectorId) -> Result<SealTaskPrototype> {
+        let is_sealing = self
+            .state
+            .staged
             .sectors
-            .get_mut(&sector_id)
+            .get(&sector_id)
+            .map(|sector| sector.seal_status == SealStatus::Sealing)
+            .unwrap_or(false);
+
+        if is_sealing {
+            return Err(err_already_sealing(sector_id).into());
+        }
+
+        self.seal_task_proto(sector_id)
+    }
+
+    // Derives the seal task prototype of the provided sector id from its
+    // metadata. The sealed sector access of a sector which has never been
+    // sealed is derived from its id, but isn't provisioned.
+    fn seal_task_proto(&self, sector_id: SectorId) -> Result<SealTaskPrototype> {
+        let staged_sector = self
+            .state
+            .staged
+            .sectors
+            .get(&sector_id)
             .ok_or_else(|| err_unrecov(format!("missing sector id={:?}", sector_id)))?;
 
-        // Provision a new sealed sector access through the manager.
-        let sealed_sector_access = self
-            .sector_store
-            .manager()
-            .new_sealed_sector_access(staged_sector.sector_id)
-            .map_err(failure::Error::from)?;
+        let (sealed_sector_access, sealed_sector_path) =
+            match self.state.sealed.sectors.get(&sector_id) {
+                // The sector is sealed again. Seal into a temporary file next to
+                // the sector's replica so that the replica stays intact until
+                // sealing has succeeded.
+                Some(sealed_sector) => {
+                    let mut path = self
+                        .sector_store
+                        .manager()
+                        .sealed_sector_path(&sealed_sector.sector_access)
+                        .into_os_string();
+                    path.push(".reseal");
 
-        let sealed_sector_path = self
-            .sector_store
-            .manager()
-            .sealed_sector_path(&sealed_sector_access);
+                    (sealed_sector.sector_access.clone(), PathBuf::from(path))
+                }
+                None => {
+                    let access = self
+                        .sector_store
+                        .manager()
+                        .sealed_sector_access(staged_sector.sector_id)
+                        .map_err(failure::Error::from)?;
 
-        let staged_sector_path = self
-            .sector_store
-            .manager()
-            .staged_sector_path(&staged_sector.sector_access);
+                    let path = self.sector_store.manager().sealed_sector_path(&access);
+
+                    (access, path)
+                }
+            };
+
+        let staged_sector_path = helpers::staged_sector_file(&self.sector_store, staged_sector)
+            .ok_or_else(|| {
+                err_unrecov(format!("unsealed data of sector {} was deleted", sector_id))
+            })?;
 
         let piece_lens = staged_sector
             .pieces
@@ -374,28 +2000,184 @@ impl<T: KeyValueStore, S: SectorStore> SectorMetadataManager<T, S> {
             .map(|p| p.num_bytes)
             .collect::<Vec<UnpaddedBytesAmount>>();
 
-        // mutate staged sector state such that we don't try to write any
-        // more pieces to it
-        staged_sector.seal_status = SealStatus::Sealing;
+        let seal_ticket = match staged_sector.seal_status {
+            SealStatus::Pending => self.seal_ticket,
+            _ => staged_sector.seal_ticket,
+        };
+
+        let piece_manifest = helpers::manifest_entries_for(
+            staged_sector,
+            helpers::load_piece_manifest(
+                &self.kv_store,
+                &SnapshotKey::new(self.prover_id, self.sector_size),
+                sector_id,
+            )?,
+        );
 
         Ok(SealTaskPrototype {
             piece_lens,
             porep_config: self.sector_store.proofs_config().porep_config(),
             sealed_sector_access,
             sealed_sector_path,
+            seal_ticket,
             sector_id,
             staged_sector_path,
+            timeout: self.task_timeouts.seal,
+            isolation: self.seal_isolation.clone(),
+            staged_data_key: self.sector_store.manager().staged_data_key(),
+            piece_manifest,
+            checksum_algo: self.checksum_algo,
+        })
+    }
+
+    // Replaces the minimum interval between metadata snapshots. Without an
+    // interval, a snapshot is persisted after every mutation.
+    pub fn set_snapshot_interval(&mut self, snapshot_interval: Option<Duration>) -> Result<()> {
+        self.snapshot_interval = snapshot_interval;
+        self.checkpoint()
+    }
+
+    // Replaces the number of the most recent journal entries which are kept
+    // when the journal is pruned. Entries beyond them are pruned along with
+    // the next snapshot, or right away by compact_metadata.
+    pub fn set_journal_keep_last_n(&mut self, keep_last_n: u64) {
+        self.journal_keep_last_n = keep_last_n;
+    }
+
+    // Flushes the changes which haven't been persisted yet and prunes the
+    // journal down to its most recent entries right away, instead of along
+    // with the next snapshot, returning the metadata store's statistics
+    // afterwards. The space of pruned entries is reclaimed by the store
+    // itself.
+    pub fn compact_metadata(&mut self) -> Result<KvStoreStats> {
+        self.flush_snapshot()?;
+
+        helpers::prune_journal(
+            &self.kv_store,
+            &SnapshotKey::new(self.prover_id, self.sector_size),
+            self.journal_seq.saturating_sub(self.journal_keep_last_n),
+        )?;
+
+        self.kv_store.stats()
+    }
+
+    // Returns how long changes which haven't been persisted yet may wait
+    // before they must be flushed, or None if there are no such changes.
+    pub fn time_until_snapshot(&self) -> Option<Duration> {
+        if self.dirty_sectors.is_empty() {
+            return None;
+        }
+
+        self.snapshot_interval.map(|interval| {
+            interval
+                .checked_sub(self.last_snapshot.elapsed())
+                .unwrap_or_default()
         })
     }
 
-    // Create and persist metadata snapshot.
-    fn checkpoint(&self) -> Result<()> {
-        helpers::persist_snapshot(
+    // Create and persist metadata snapshot if any sector has changed since
+    // the last one.
+    //
+    // Only dirty sectors are persisted, so a sector which was changed without
+    // being recorded would silently go missing from the snapshot. Debug
+    // builds assert that every other sector matches its persisted metadata.
+    pub fn flush_snapshot(&mut self) -> Result<()> {
+        if self.dirty_sectors.is_empty() {
+            return Ok(());
+        }
+
+        if cfg!(debug_assertions) {
+            let unrecorded = self.unrecorded_changes()?;
+
+            debug_assert!(
+                unrecorded.is_empty(),
+                "sectors {:?} were changed without being recorded",
+                unrecorded
+            );
+        }
+
+        helpers::persist_sectors_and_journal(
             &self.kv_store,
             &SnapshotKey::new(self.prover_id, self.sector_size),
             &self.state,
+            &self.dirty_sectors,
+            &self.unflushed_journal,
+            self.journal_seq.saturating_sub(self.journal_keep_last_n),
         )?;
 
+        self.dirty_sectors.clear();
+        self.unflushed_journal.clear();
+        self.last_snapshot = Instant::now();
+
         Ok(())
     }
+
+    // Returns the ids of the sectors which aren't dirty, but whose metadata
+    // differs from their persisted metadata.
+    fn unrecorded_changes(&self) -> Result<HashSet<SectorId>> {
+        let key = SnapshotKey::new(self.prover_id, self.sector_size);
+        let mut unrecorded = HashSet::new();
+
+        for (sector_id, meta) in &self.state.staged.sectors {
+            if !self.dirty_sectors.contains(sector_id)
+                && helpers::load_staged_sector(&self.kv_store, &key, *sector_id)?.as_ref()
+                    != Some(meta)
+            {
+                unrecorded.insert(*sector_id);
+            }
+        }
+
+        for (sector_id, meta) in &self.state.sealed.sectors {
+            if !self.dirty_sectors.contains(sector_id)
+                && helpers::load_sealed_sector(&self.kv_store, &key, *sector_id)?.as_ref()
+                    != Some(meta)
+            {
+                unrecorded.insert(*sector_id);
+            }
+        }
+
+        Ok(unrecorded)
+    }
+
+    // Records that the SectorBuilder has been shut down, interrupting the
+    // provided seals. Changes must have been flushed beforehand.
+    pub fn persist_shutdown_marker(&self, interrupted_seals: Vec<InterruptedSeal>) -> Result<()> {
+        helpers::persist_shutdown_marker(
+            &self.kv_store,
+            &SnapshotKey::new(self.prover_id, self.sector_size),
+            interrupted_seals,
+        )
+    }
+
+    // Records that the sector with the provided id has changed. The sector is
+    // included in the next snapshot, along with a journal entry recording the
+    // transition.
+    fn record(&mut self, sector_id: SectorId, transition: SectorTransition) {
+        self.journal_seq += 1;
+        self.unflushed_journal.push(helpers::new_journal_entry(
+            &self.state,
+            self.journal_seq,
+            sector_id,
+            transition,
+        ));
+
+        self.dirty_sectors.insert(sector_id);
+        self.unpublished_changes = true;
+    }
+
+    // Flushes changes to the metadata store, unless a snapshot interval has
+    // been set and hasn't elapsed since the last snapshot, in which case the
+    // changes are batched with those which follow. Changes are published to
+    // the readers of the published state either way.
+    fn checkpoint(&mut self) -> Result<()> {
+        if self.unpublished_changes {
+            self.published_state.publish(&self.state);
+            self.unpublished_changes = false;
+        }
+
+        match self.snapshot_interval {
+            Some(interval) if self.last_snapshot.elapsed() < interval => Ok(()),
+            _ => self.flush_snapshot(),
+        }
+    }
 }
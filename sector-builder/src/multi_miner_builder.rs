@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+use filecoin_proofs::types::{PoStConfig, SectorClass};
+use serde::{Deserialize, Serialize};
+use storage_proofs::rational_post;
+use storage_proofs::sector::SectorId;
+
+use crate::affinity::WorkerAffinity;
+use crate::builder::{SectorBuilder, SectorBuilderOptions};
+use crate::constants::NUM_WORKERS;
+use crate::dir_lock::DirLock;
+use crate::encryption::StagedDataKey;
+use crate::error::{err_invalid_miner, err_unknown_miner, Result};
+use crate::helpers;
+use crate::metadata::{
+    GeneratedPoSt, PoStOptions, PoStPartition, SealScheduleResult, SealStatus, SealTicket,
+    SecondsSinceEpoch,
+};
+use crate::worker::WorkerPool;
+
+// The file in the metadata directory which records the miners which have
+// been added, so that they're added again when the MultiMinerSectorBuilder
+// is recreated.
+const MINER_REGISTRY_FILE_NAME: &str = ".miners";
+
+// A miner recorded in the registry.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+struct RegisteredMiner {
+    miner: String,
+    prover_id: [u8; 31],
+}
+
+/// Manages the sectors of several miner actors from one process. Each miner
+/// gets its own SectorBuilder, whose metadata, sealed sectors and staged
+/// sectors live in a subdirectory (named after the miner) of the respective
+/// directory the MultiMinerSectorBuilder was created with. Sectors of a
+/// SectorBuilder which served a single miner can be moved into this layout
+/// using migrate_to_miner_layout.
+///
+/// Every miner's SectorBuilder hands its tasks to one pool of workers, which
+/// are pinned according to the worker affinity, and their staged
+/// sector-files are encrypted with the same staged data key, if any. The
+/// miners which have been added are recorded in the metadata directory, which
+/// is locked for as long as the MultiMinerSectorBuilder exists.
+pub struct MultiMinerSectorBuilder<R> {
+    sector_class: SectorClass,
+    metadata_dir: PathBuf,
+    sealed_sector_dir: PathBuf,
+    staged_sector_dir: PathBuf,
+    max_num_staged_sectors: u64,
+    worker_affinity: WorkerAffinity,
+    staged_data_key: Option<StagedDataKey>,
+    builders: HashMap<String, SectorBuilder<R>>,
+    registry: Vec<RegisteredMiner>,
+    workers: Arc<WorkerPool<R>>,
+    _dir_lock: DirLock,
+}
+
+impl<R: 'static + Send + std::io::Read> MultiMinerSectorBuilder<R> {
+    // Creates a MultiMinerSectorBuilder and adds the miners recorded in its
+    // metadata directory.
+    pub fn new(
+        sector_class: SectorClass,
+        metadata_dir: impl AsRef<Path>,
+        sealed_sector_dir: impl AsRef<Path>,
+        staged_sector_dir: impl AsRef<Path>,
+        max_num_staged_sectors: u64,
+        worker_affinity: WorkerAffinity,
+        staged_data_key: Option<StagedDataKey>,
+    ) -> Result<MultiMinerSectorBuilder<R>> {
+        fs::create_dir_all(&metadata_dir)?;
+
+        let dir_lock = DirLock::acquire(&metadata_dir)?;
+        let registry = load_miner_registry(metadata_dir.as_ref())?;
+        let workers = WorkerPool::start(NUM_WORKERS, &worker_affinity);
+
+        let mut builder = MultiMinerSectorBuilder {
+            sector_class,
+            metadata_dir: metadata_dir.as_ref().to_path_buf(),
+            sealed_sector_dir: sealed_sector_dir.as_ref().to_path_buf(),
+            staged_sector_dir: staged_sector_dir.as_ref().to_path_buf(),
+            max_num_staged_sectors,
+            worker_affinity,
+            staged_data_key,
+            builders: Default::default(),
+            registry: registry.clone(),
+            workers,
+            _dir_lock: dir_lock,
+        };
+
+        // the sector id nonce of a registered miner is restored from its
+        // metadata
+        for registered in registry {
+            builder.add_miner(&registered.miner, registered.prover_id, SectorId::from(0))?;
+        }
+
+        Ok(builder)
+    }
+
+    // Initializes a SectorBuilder for the provided miner from its persisted
+    // metadata, if any (see SectorBuilder::init_from_metadata), and records
+    // the miner so that it's added again when the MultiMinerSectorBuilder is
+    // recreated.
+    pub fn add_miner(
+        &mut self,
+        miner: &str,
+        prover_id: [u8; 31],
+        last_committed_sector_id: SectorId,
+    ) -> Result<()> {
+        helpers::validate_miner(miner)?;
+
+        // the registry (and its partial copy) live next to the miners'
+        // metadata directories
+        if miner.starts_with(MINER_REGISTRY_FILE_NAME) {
+            return Err(err_invalid_miner(format!("{:?} is reserved", miner)).into());
+        }
+
+        if self.builders.contains_key(miner) {
+            return Err(
+                err_invalid_miner(format!("miner {} has already been added", miner)).into(),
+            );
+        }
+
+        let builder = SectorBuilder::init_with_worker_pool(
+            self.sector_class,
+            last_committed_sector_id,
+            self.metadata_dir.join(miner),
+            prover_id,
+            self.sealed_sector_dir.join(miner),
+            self.staged_sector_dir.join(miner),
+            SectorBuilderOptions {
+                worker_affinity: self.worker_affinity.clone(),
+                staged_data_key: self.staged_data_key.clone(),
+                ..SectorBuilderOptions::new(self.max_num_staged_sectors)
+            },
+            self.workers.clone(),
+        )?;
+
+        if !self
+            .registry
+            .iter()
+            .any(|registered| registered.miner == miner)
+        {
+            let mut registry = self.registry.clone();
+
+            registry.push(RegisteredMiner {
+                miner: miner.to_string(),
+                prover_id,
+            });
+
+            persist_miner_registry(&self.metadata_dir, &registry)?;
+
+            self.registry = registry;
+        }
+
+        self.builders.insert(miner.to_string(), builder);
+
+        Ok(())
+    }
+
+    // Shuts down the SectorBuilder of the provided miner and removes it from
+    // the recorded miners. Its metadata and sectors remain on disk.
+    pub fn remove_miner(&mut self, miner: &str) -> Result<()> {
+        if !self.builders.contains_key(miner) {
+            return Err(err_unknown_miner(miner).into());
+        }
+
+        let registry: Vec<RegisteredMiner> = self
+            .registry
+            .iter()
+            .filter(|registered| registered.miner != miner)
+            .cloned()
+            .collect();
+
+        persist_miner_registry(&self.metadata_dir, &registry)?;
+
+        self.registry = registry;
+        self.builders.remove(miner);
+
+        Ok(())
+    }
+
+    // Returns the miners which have been added.
+    pub fn miners(&self) -> Vec<String> {
+        self.builders.keys().cloned().collect()
+    }
+
+    // Returns the SectorBuilder of the provided miner, through which all of
+    // the miner's sectors are managed.
+    pub fn miner(&self, miner: &str) -> Result<&SectorBuilder<R>> {
+        self.builders
+            .get(miner)
+            .ok_or_else(|| err_unknown_miner(miner).into())
+    }
+
+    // Stages user piece-bytes for sealing by the provided miner.
+    pub fn add_piece(
+        &self,
+        miner: &str,
+        piece_key: String,
+        piece_file: R,
+        piece_bytes_amount: u64,
+        store_until: SecondsSinceEpoch,
+    ) -> Result<SectorId> {
+        self.miner(miner)?
+            .add_piece(piece_key, piece_file, piece_bytes_amount, store_until)
+    }
+
+    // Returns sealing status for the provided miner's sector.
+    pub fn get_seal_status(&self, miner: &str, sector_id: SectorId) -> Result<SealStatus> {
+        self.miner(miner)?.get_seal_status(sector_id)
+    }
+
+    // Schedules sealing of all of the provided miner's staged sectors,
+    // returning the outcome for each of them (see
+    // SectorBuilder::seal_all_staged_sectors).
+    pub fn seal_all_staged_sectors(
+        &self,
+        miner: &str,
+        seal_ticket: SealTicket,
+    ) -> Result<Vec<SealScheduleResult>> {
+        self.miner(miner)?.seal_all_staged_sectors(seal_ticket)
+    }
+
+    // Generates a proof-of-spacetime over the provided miner's sectors.
+    pub fn generate_post(
+        &self,
+        miner: &str,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        options: PoStOptions,
+    ) -> Result<GeneratedPoSt> {
+        self.miner(miner)?
+            .generate_post(comm_rs, challenge_seed, faults, options)
+    }
+
+    // Derives the challenges of a proof-of-spacetime over the provided miner's
+    // sectors without generating the proof.
+    pub fn generate_post_first(
+        &self,
+        miner: &str,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        post_config: Option<PoStConfig>,
+    ) -> Result<Vec<rational_post::Challenge>> {
+        self.miner(miner)?
+            .generate_post_first(comm_rs, challenge_seed, faults, post_config)
+    }
+
+    // Generates a proof-of-spacetime over each partition of the provided
+    // miner's sectors, streaming the partitions as they are proven.
+    pub fn generate_post_partitioned(
+        &self,
+        miner: &str,
+        comm_rs: &[[u8; 32]],
+        challenge_seed: &[u8; 32],
+        faults: Vec<SectorId>,
+        partition_size: usize,
+    ) -> Result<mpsc::Receiver<Result<PoStPartition>>> {
+        self.miner(miner)?.generate_post_partitioned(
+            comm_rs,
+            challenge_seed,
+            faults,
+            partition_size,
+        )
+    }
+}
+
+// Loads the miners recorded in the provided metadata directory, if any.
+fn load_miner_registry(metadata_dir: &Path) -> Result<Vec<RegisteredMiner>> {
+    match fs::read(metadata_dir.join(MINER_REGISTRY_FILE_NAME)) {
+        Ok(bytes) => Ok(serde_cbor::from_slice(&bytes)?),
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Records the provided miners in the metadata directory. The registry is
+// written under a temporary name and renamed over the previous one, so that
+// a crash never leaves a partial registry behind.
+fn persist_miner_registry(metadata_dir: &Path, registry: &[RegisteredMiner]) -> Result<()> {
+    let registry_path = metadata_dir.join(MINER_REGISTRY_FILE_NAME);
+    let partial_path = metadata_dir.join(format!("{}.part", MINER_REGISTRY_FILE_NAME));
+
+    let mut file = File::create(&partial_path)?;
+    file.write_all(&serde_cbor::to_vec(&registry)?)?;
+    file.sync_all()?;
+
+    fs::rename(&partial_path, &registry_path)?;
+    File::open(metadata_dir)?.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_miner() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let builder: MultiMinerSectorBuilder<std::fs::File> = MultiMinerSectorBuilder::new(
+            SectorClass(
+                filecoin_proofs::types::SectorSize(1024),
+                filecoin_proofs::types::PoRepProofPartitions(2),
+            ),
+            metadata_dir.path(),
+            "sealed",
+            "staged",
+            2,
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        assert!(builder.miner("t01000").is_err());
+        assert!(builder.miners().is_empty());
+    }
+
+    #[test]
+    fn test_miner_registry_round_trip() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        assert!(load_miner_registry(metadata_dir.path()).unwrap().is_empty());
+
+        let registry = vec![
+            RegisteredMiner {
+                miner: "t01000".to_string(),
+                prover_id: [1; 31],
+            },
+            RegisteredMiner {
+                miner: "t01001".to_string(),
+                prover_id: [2; 31],
+            },
+        ];
+
+        persist_miner_registry(metadata_dir.path(), &registry).unwrap();
+        assert_eq!(registry, load_miner_registry(metadata_dir.path()).unwrap());
+
+        persist_miner_registry(metadata_dir.path(), &registry[1..]).unwrap();
+        assert_eq!(
+            registry[1..].to_vec(),
+            load_miner_registry(metadata_dir.path()).unwrap()
+        );
+    }
+}
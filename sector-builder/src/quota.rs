@@ -0,0 +1,36 @@
+use std::fmt;
+
+/// Limits on the storage a SectorBuilder may consume. A limit which is `None`
+/// is not enforced.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageQuota {
+    /// maximum number of user bytes held in staged sectors which have not yet
+    /// been sealed
+    pub max_staged_bytes: Option<u64>,
+    /// maximum number of bytes in sealed sector-files, counting each staged
+    /// sector which has not yet been sealed as a full sealed sector
+    pub max_sealed_bytes: Option<u64>,
+    /// maximum number of staged sectors which have not yet been sealed,
+    /// independent of the number of sectors kept open for new pieces. Sectors
+    /// which are being sealed don't count, so that a fresh sector accepts
+    /// pieces while the previous one is sealed.
+    pub max_staged_sectors: Option<u64>,
+}
+
+/// The resource whose quota has been exceeded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuotaResource {
+    StagedBytes,
+    SealedBytes,
+    StagedSectors,
+}
+
+impl fmt::Display for QuotaResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuotaResource::StagedBytes => write!(f, "staged bytes"),
+            QuotaResource::SealedBytes => write!(f, "sealed bytes"),
+            QuotaResource::StagedSectors => write!(f, "staged sectors"),
+        }
+    }
+}
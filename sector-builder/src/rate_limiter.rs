@@ -0,0 +1,104 @@
+use std::cmp;
+use std::io::{self, Read};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Limits the rate, in bytes per second, at which pieces are read while
+/// they're added, so that staging pieces doesn't saturate disks which running
+/// seals depend on. Up to a second's worth of bytes which weren't used can be
+/// read in a burst.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    // bytes which may be read without waiting; negative once more bytes have
+    // been read than the rate allows, which is paid off by waiting
+    available: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> RateLimiter {
+        let bytes_per_sec = cmp::max(bytes_per_sec, 1);
+
+        RateLimiter {
+            bytes_per_sec,
+            available: bytes_per_sec as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    // Takes the provided number of bytes, waiting until they may be read.
+    pub fn acquire(&mut self, num_bytes: u64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill);
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+
+        self.last_refill = now;
+        self.available = (self.available + elapsed * self.bytes_per_sec as f64)
+            .min(self.bytes_per_sec as f64)
+            - num_bytes as f64;
+
+        if self.available < 0.0 {
+            let nanos = -self.available * 1e9 / self.bytes_per_sec as f64;
+
+            thread::sleep(Duration::from_nanos(nanos as u64));
+        }
+    }
+}
+
+/// Reads from the inner reader no faster than the rate limiter allows.
+pub struct ThrottledReader<'a, R> {
+    inner: R,
+    limiter: &'a mut RateLimiter,
+}
+
+impl<'a, R: Read> ThrottledReader<'a, R> {
+    pub fn new(inner: R, limiter: &'a mut RateLimiter) -> ThrottledReader<'a, R> {
+        ThrottledReader { inner, limiter }
+    }
+}
+
+impl<'a, R: Read> Read for ThrottledReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // no more than a second's worth of bytes are read at once, so that
+        // large buffers are filled at the limited rate, too
+        let max = cmp::min(buf.len() as u64, self.limiter.bytes_per_sec()) as usize;
+
+        let n = self.inner.read(&mut buf[..max])?;
+        self.limiter.acquire(n as u64);
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_reads_after_a_burst() {
+        let mut limiter = RateLimiter::new(1000);
+
+        let started = Instant::now();
+
+        // a second's worth of bytes is read without waiting
+        let mut burst = vec![0; 1000];
+        ThrottledReader::new(io::repeat(7).take(1000), &mut limiter)
+            .read_exact(&mut burst)
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_millis(250));
+
+        // the rest is read at the limited rate
+        let mut rest = vec![0; 500];
+        ThrottledReader::new(io::repeat(7).take(500), &mut limiter)
+            .read_exact(&mut rest)
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(450));
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use filecoin_proofs::types::SectorClass;
+use storage_proofs::sector::SectorId;
+
+use crate::builder::SectorBuilder;
+use crate::error::Result;
+use crate::helpers;
+use crate::helpers::SnapshotKey;
+use crate::kv_store::{KeyValueStore, SledKvs};
+use crate::metadata::{SealStatus, SealedSectorMetadata, StagedSectorMetadata};
+use crate::state::SectorBuilderState;
+
+/// Serves the metadata of a SectorBuilder's sectors without starting any
+/// workers or writing to its metadata store, e.g. for monitoring tools. The
+/// metadata is read again for every call, including changes which have only
+/// been journaled so far, so calls reflect the latest state persisted by the
+/// SectorBuilder which owns the store.
+///
+/// Nothing is migrated, rebuilt or otherwise repaired; that's left to the
+/// SectorBuilder which owns the store.
+pub struct ReadOnlySectorBuilder<T: KeyValueStore = SledKvs> {
+    kv_store: T,
+    snapshot_key: SnapshotKey,
+}
+
+// Defined for a SectorBuilder which reads no pieces, so that it can be called
+// without naming a piece reader type.
+impl SectorBuilder<()> {
+    /// Opens the metadata persisted to the provided directory by a
+    /// SectorBuilder of the provided sector class and prover id, read-only.
+    pub fn open_read_only(
+        sector_class: SectorClass,
+        metadata_dir: impl AsRef<Path>,
+        prover_id: [u8; 31],
+    ) -> Result<ReadOnlySectorBuilder> {
+        ReadOnlySectorBuilder::open(sector_class, metadata_dir, prover_id)
+    }
+}
+
+impl<T: KeyValueStore> ReadOnlySectorBuilder<T> {
+    pub fn open(
+        sector_class: SectorClass,
+        metadata_dir: impl AsRef<Path>,
+        prover_id: [u8; 31],
+    ) -> Result<ReadOnlySectorBuilder<T>> {
+        let kv_store = T::open_read_only(metadata_dir)?;
+        let snapshot_key = SnapshotKey::new(prover_id, sector_class.0.into());
+
+        Ok(ReadOnlySectorBuilder {
+            kv_store,
+            snapshot_key,
+        })
+    }
+
+    // Returns all staged sector metadata.
+    pub fn get_staged_sectors(&self) -> Result<Vec<StagedSectorMetadata>> {
+        Ok(self.state()?.staged.sectors.values().cloned().collect())
+    }
+
+    // Returns all sealed sector metadata. Replicas aren't health-checked.
+    pub fn get_sealed_sectors(&self) -> Result<Vec<SealedSectorMetadata>> {
+        Ok(self.state()?.sealed.sectors.values().cloned().collect())
+    }
+
+    // Returns sealing status for the sector with specified id. If no sealed or
+    // staged sector exists with the provided id, produce an error.
+    pub fn get_seal_status(&self, sector_id: SectorId) -> Result<SealStatus> {
+        let state = self.state()?;
+
+        helpers::get_seal_status(&state.staged, &state.sealed, sector_id)
+    }
+
+    // Returns the id of the sector which holds the piece with the provided
+    // key, or None if no sector holds it. A piece which has been sealed is
+    // found in its sealed sector, even if the staged sector it was sealed
+    // from has been retained.
+    pub fn find_piece(&self, piece_key: &str) -> Result<Option<SectorId>> {
+        let state = self.state()?;
+
+        let sealed = state
+            .sealed
+            .sectors
+            .values()
+            .find(|sector| sector.pieces.iter().any(|p| p.piece_key == piece_key))
+            .map(|sector| sector.sector_id);
+
+        let staged = || {
+            state
+                .staged
+                .sectors
+                .values()
+                .find(|sector| sector.pieces.iter().any(|p| p.piece_key == piece_key))
+                .map(|sector| sector.sector_id)
+        };
+
+        Ok(sealed.or_else(staged))
+    }
+
+    fn state(&self) -> Result<SectorBuilderState> {
+        helpers::read_snapshot(&self.kv_store, &self.snapshot_key).map(Option::unwrap_or_default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use filecoin_proofs::constants::SECTOR_SIZE_ONE_KIB;
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorSize, UnpaddedBytesAmount};
+
+    use crate::helpers::persist_snapshot;
+    use crate::kv_store::FileSystemKvs;
+    use crate::metadata::PieceMetadata;
+
+    use super::*;
+
+    #[test]
+    fn serves_persisted_metadata() {
+        let metadata_dir = tempfile::tempdir().unwrap();
+
+        let sector_class = SectorClass(SectorSize(SECTOR_SIZE_ONE_KIB), PoRepProofPartitions(2));
+        let prover_id = [7; 31];
+
+        let mut state: SectorBuilderState = Default::default();
+
+        state.staged.sectors.insert(
+            SectorId::from(1),
+            StagedSectorMetadata {
+                sector_id: SectorId::from(1),
+                pieces: vec![PieceMetadata {
+                    piece_key: "a".to_string(),
+                    num_bytes: UnpaddedBytesAmount(127),
+                    comm_p: None,
+                    piece_inclusion_proof: None,
+                    idempotency_key: None,
+                    store_until: None,
+                    deal_id: None,
+                    labels: Default::default(),
+                }],
+                ..Default::default()
+            },
+        );
+
+        {
+            let kv_store = FileSystemKvs::initialize(metadata_dir.path()).unwrap();
+            let key = SnapshotKey::new(prover_id, sector_class.0.into());
+
+            persist_snapshot(&kv_store, &key, &state).unwrap();
+        }
+
+        let builder: ReadOnlySectorBuilder<FileSystemKvs> =
+            ReadOnlySectorBuilder::open(sector_class, metadata_dir.path(), prover_id).unwrap();
+
+        assert_eq!(1, builder.get_staged_sectors().unwrap().len());
+        assert!(builder.get_sealed_sectors().unwrap().is_empty());
+        assert_eq!(
+            SealStatus::Pending,
+            builder.get_seal_status(SectorId::from(1)).unwrap()
+        );
+        assert!(builder.get_seal_status(SectorId::from(2)).is_err());
+        assert_eq!(Some(SectorId::from(1)), builder.find_piece("a").unwrap());
+        assert_eq!(None, builder.find_piece("b").unwrap());
+    }
+}
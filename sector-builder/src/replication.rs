@@ -0,0 +1,346 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use filecoin_proofs::error::ExpectWithBacktrace;
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_unrecov, Result};
+use crate::scheduler::SchedulerTask;
+
+const FATAL_NOLOCK: &str = "error acquiring replication job lock";
+
+// How many bytes DirReplicator copies at once.
+const COPY_BUFFER_LEN: usize = 1 << 20;
+
+// How often the progress of a copy is handed to the scheduler, at most.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Copies the replicas of sealed sectors to a secondary location, e.g. a
+/// backup volume, an rsync target or an object store, once they've been
+/// sealed and checksummed. Replicas are copied on threads of their own, so
+/// implementations may block for as long as copying takes.
+pub trait SealedSectorReplicator: Send + Sync {
+    /// Copies the replica at the provided path, returning where the copy can
+    /// be found (e.g. a path or URL), which is recorded in the sector's
+    /// metadata. Implementations which can tell may report the number of
+    /// bytes copied so far through progress as they go, which is recorded as
+    /// well (see ReplicationStatus::Copying).
+    fn replicate(
+        &self,
+        sector_id: SectorId,
+        replica_path: &Path,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<String>;
+}
+
+/// Copies replicas into a directory, e.g. on a mounted backup volume. Each
+/// copy is written under a temporary name, synced to disk and renamed once
+/// complete, after which the directory is synced, so that the directory never
+/// holds a partial copy under a replica's name, even after a crash.
+#[derive(Clone, Debug)]
+pub struct DirReplicator {
+    pub dir: PathBuf,
+}
+
+impl SealedSectorReplicator for DirReplicator {
+    fn replicate(
+        &self,
+        _sector_id: SectorId,
+        replica_path: &Path,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<String> {
+        let file_name = replica_path
+            .file_name()
+            .ok_or_else(|| err_unrecov(format!("{:?} doesn't name a replica", replica_path)))?;
+
+        let mut partial_name = OsString::from(file_name);
+        partial_name.push(".part");
+
+        let target_path = self.dir.join(file_name);
+        let partial_path = self.dir.join(partial_name);
+
+        fs::create_dir_all(&self.dir)?;
+
+        if let Err(err) = copy_synced(replica_path, &partial_path, progress) {
+            let _ = fs::remove_file(&partial_path);
+            return Err(err.into());
+        }
+
+        fs::rename(&partial_path, &target_path)?;
+        File::open(&self.dir)?.sync_all()?;
+
+        Ok(target_path.to_string_lossy().into_owned())
+    }
+}
+
+// Copies the file at the provided path, reporting the number of bytes copied
+// so far as it goes, and syncs the copy to disk.
+fn copy_synced(from: &Path, to: &Path, progress: &mut dyn FnMut(u64)) -> io::Result<()> {
+    let mut reader = File::open(from)?;
+    let mut writer = File::create(to)?;
+
+    let mut buf = vec![0; COPY_BUFFER_LEN];
+    let mut bytes_copied = 0;
+
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        writer.write_all(&buf[..n])?;
+
+        bytes_copied += n as u64;
+        progress(bytes_copied);
+    }
+
+    writer.sync_all()
+}
+
+/// Copies replicas by running a program, e.g. a script wrapping rsync or an
+/// object store's CLI, with the provided arguments followed by the sector id
+/// and the path of the replica. The program must exit successfully once the
+/// replica has been copied. Whatever it prints to stdout (trimmed) is
+/// recorded as the location of the copy.
+#[derive(Clone, Debug)]
+pub struct CommandReplicator {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl SealedSectorReplicator for CommandReplicator {
+    fn replicate(
+        &self,
+        sector_id: SectorId,
+        replica_path: &Path,
+        _progress: &mut dyn FnMut(u64),
+    ) -> Result<String> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg(u64::from(sector_id).to_string())
+            .arg(replica_path)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(err_unrecov(format!(
+                "{:?} exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+            .into());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+// Everything needed to copy the replica of a sealed sector, which the
+// metadata manager plans before the copy is made off the scheduler thread.
+pub struct ReplicationJob {
+    pub replicator: Arc<dyn SealedSectorReplicator>,
+    pub sector_id: SectorId,
+    pub replica_path: PathBuf,
+}
+
+// Copies the replicas of sealed sectors on a fixed number of threads, so that
+// a burst of seals doesn't start as many copies at once. Jobs wait for a
+// thread in the order in which they were submitted. The threads hand the
+// progress and outcome of each copy back to the scheduler, and exit once the
+// pool has been dropped (after finishing the copy they're making).
+//
+// Outcomes are dropped if the scheduler has shut down in the meantime, in
+// which case the sector's replication remains pending and is restarted once
+// a replicator is set again. The pool counts the jobs whose outcome the
+// scheduler has yet to handle, for which freezing waits.
+pub struct ReplicationPool {
+    job_tx: mpsc::Sender<ReplicationJob>,
+    in_flight: usize,
+}
+
+impl ReplicationPool {
+    pub fn new<T: 'static + Send>(
+        num_threads: usize,
+        done_tx: mpsc::SyncSender<SchedulerTask<T>>,
+    ) -> ReplicationPool {
+        let (job_tx, job_rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        for _ in 0..num_threads {
+            let job_rx = job_rx.clone();
+            let done_tx = done_tx.clone();
+
+            thread::spawn(move || loop {
+                // the lock is released before the job is run
+                let job = job_rx.lock().expects(FATAL_NOLOCK).recv();
+
+                match job {
+                    Ok(job) => replicate(job, &done_tx),
+                    Err(_) => break,
+                }
+            });
+        }
+
+        ReplicationPool {
+            job_tx,
+            in_flight: 0,
+        }
+    }
+
+    pub fn submit(&mut self, job: ReplicationJob) {
+        // the threads only stop receiving once the pool has been dropped
+        let _ = self.job_tx.send(job);
+
+        self.in_flight += 1;
+    }
+
+    // Records that the outcome of a submitted job has been handled.
+    pub fn complete(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    // Returns whether any submitted job's outcome is yet to be handled.
+    pub fn is_copying(&self) -> bool {
+        self.in_flight > 0
+    }
+}
+
+fn replicate<T>(job: ReplicationJob, done_tx: &mpsc::SyncSender<SchedulerTask<T>>) {
+    let ReplicationJob {
+        replicator,
+        sector_id,
+        replica_path,
+    } = job;
+
+    let mut last_progress = Instant::now();
+
+    let result = replicator.replicate(sector_id, &replica_path, &mut |bytes_copied| {
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+
+            let _ = done_tx.send(SchedulerTask::HandleReplicationProgress(
+                sector_id,
+                bytes_copied,
+            ));
+        }
+    });
+
+    if let Err(err) = &result {
+        warn!("could not replicate sealed sector {}: {}", sector_id, err);
+    }
+
+    let _ = done_tx.send(SchedulerTask::HandleReplicationResult(sector_id, result));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_replicator_copies_replicas() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let backup_dir = tempfile::tempdir().unwrap();
+
+        let replica_path = sealed_dir.path().join("s-t01-5");
+        fs::write(&replica_path, &[5; 256]).unwrap();
+
+        let replicator = DirReplicator {
+            dir: backup_dir.path().join("sealed"),
+        };
+
+        let mut reported = Vec::new();
+
+        let location = replicator
+            .replicate(SectorId::from(5), &replica_path, &mut |bytes_copied| {
+                reported.push(bytes_copied)
+            })
+            .unwrap();
+
+        assert_eq!(vec![256], reported);
+
+        assert_eq!(
+            backup_dir.path().join("sealed").join("s-t01-5"),
+            PathBuf::from(location.clone())
+        );
+        assert_eq!(vec![5; 256], fs::read(&location).unwrap());
+        assert_eq!(
+            1,
+            fs::read_dir(backup_dir.path().join("sealed"))
+                .unwrap()
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_command_replicator_reports_failures() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let replica_path = sealed_dir.path().join("s-t01-6");
+        fs::write(&replica_path, &[6; 64]).unwrap();
+
+        let echo = CommandReplicator {
+            program: PathBuf::from("echo"),
+            args: vec!["s3://bucket".to_string()],
+        };
+
+        assert_eq!(
+            format!("s3://bucket 6 {}", replica_path.display()),
+            echo.replicate(SectorId::from(6), &replica_path, &mut |_| {})
+                .unwrap()
+        );
+
+        let failing = CommandReplicator {
+            program: PathBuf::from("false"),
+            args: vec![],
+        };
+
+        assert!(failing
+            .replicate(SectorId::from(6), &replica_path, &mut |_| {})
+            .is_err());
+    }
+
+    #[test]
+    fn test_replication_pool_reports_outcomes() {
+        let sealed_dir = tempfile::tempdir().unwrap();
+        let backup_dir = tempfile::tempdir().unwrap();
+
+        let (done_tx, done_rx) = mpsc::sync_channel::<SchedulerTask<std::fs::File>>(0);
+        let pool = ReplicationPool::new(2, done_tx);
+
+        let replicator: Arc<dyn SealedSectorReplicator> = Arc::new(DirReplicator {
+            dir: backup_dir.path().to_path_buf(),
+        });
+
+        for n in 0..4 {
+            let replica_path = sealed_dir.path().join(format!("s-t01-{}", n));
+            fs::write(&replica_path, &[n as u8; 64]).unwrap();
+
+            pool.submit(ReplicationJob {
+                replicator: replicator.clone(),
+                sector_id: SectorId::from(n),
+                replica_path,
+            });
+        }
+
+        let mut replicated: Vec<u64> = (0..4)
+            .map(|_| match done_rx.recv().unwrap() {
+                SchedulerTask::HandleReplicationResult(sector_id, result) => {
+                    assert!(result.is_ok());
+                    u64::from(sector_id)
+                }
+                _ => panic!("expected the outcome of a copy"),
+            })
+            .collect();
+
+        replicated.sort();
+        assert_eq!(vec![0, 1, 2, 3], replicated);
+        assert_eq!(4, fs::read_dir(backup_dir.path()).unwrap().count());
+    }
+}
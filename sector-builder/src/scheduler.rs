@@ -1,27 +1,55 @@
 use std::path::PathBuf;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
 use std::thread;
+use std::time::Duration;
 
 use filecoin_proofs::error::ExpectWithBacktrace;
-use filecoin_proofs::SealOutput;
+use filecoin_proofs::{PoStConfig, SealOutput};
+use storage_proofs::rational_post;
 use storage_proofs::sector::SectorId;
 
+use crate::clock::Clock;
+use crate::constants::NUM_REPLICATION_THREADS;
 use crate::error::Result;
-use crate::kv_store::KeyValueStore;
-use crate::metadata::{SealStatus, StagedSectorMetadata};
-use crate::store::SectorStore;
-use crate::worker::{SealTaskPrototype, WorkerTask};
-use crate::{GetSealedSectorResult, SecondsSinceEpoch, SectorMetadataManager, UnpaddedBytesAmount};
+use crate::helpers::{ReplicaChecksums, SectorIntegrity};
+use crate::isolation::SealIsolation;
+use crate::kv_store::{KeyValueStore, KvStoreStats};
+use crate::metadata::{
+    AutoSealPolicy, ChecksumAlgo, GeneratedPoSt, InterruptedSeal, PieceDealInfo, PieceLayout,
+    PieceProvenance, PieceWithProof, PoStOptions, PoStPartition, SealScheduleResult,
+    SealStatistics, SealStatus, SealTicket, SealTiming, SealedSectorHealth, SealedSectorLocation,
+    SealedSectorMetadata, StagedDataRetention, StagedSectorMetadata, StaleSealPolicy,
+    StorageReport,
+};
+use crate::metadata_manager::UnsealedPieceRange;
+use crate::quota::StorageQuota;
+use crate::replication::{ReplicationPool, SealedSectorReplicator};
+use crate::seal_queue::{SealQueue, SealQueueEntry};
+use crate::sector_access::SectorAccess;
+use crate::sector_id_provider::SectorIdProvider;
+use crate::staged_writer::StagedWriteConfig;
+use crate::state::SectorBuilderState;
+use crate::store::{MappedBytes, SectorStore};
+use crate::watchdog::{CancellationToken, TaskTimeouts};
+use crate::worker::{
+    HydrateTaskPrototype, RepairCheckTaskPrototype, ReplicaTaskPrototype, SealTaskPrototype,
+    UnsealTaskPrototype, WorkerHandle, WorkerTask,
+};
+use crate::{
+    GetSealedSectorResult, HealthCheckMode, SecondsSinceEpoch, SectorMetadataManager,
+    UnpaddedBytesAmount,
+};
 
 const FATAL_NORECV: &str = "could not receive task";
 const FATAL_NOSEND: &str = "could not send";
+const FATAL_SNPSHT: &str = "could not snapshot";
 
 pub struct Scheduler {
     pub thread: Option<thread::JoinHandle<()>>,
 }
 
 #[derive(Debug)]
-pub struct PerformHealthCheck(pub bool);
+pub struct PerformHealthCheck(pub Option<HealthCheckMode>);
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
@@ -31,30 +59,183 @@ pub enum SchedulerTask<T> {
         u64,
         T,
         SecondsSinceEpoch,
+        Option<String>, // idempotency key
+        PieceDealInfo,
         mpsc::SyncSender<Result<SectorId>>,
     ),
     GetSealedSectors(
         PerformHealthCheck,
         mpsc::SyncSender<Result<Vec<GetSealedSectorResult>>>,
     ),
-    GetStagedSectors(mpsc::SyncSender<Result<Vec<StagedSectorMetadata>>>),
-    GetSealStatus(SectorId, mpsc::SyncSender<Result<SealStatus>>),
+    GetStagedSector(SectorId, mpsc::SyncSender<Result<StagedSectorMetadata>>),
+    GetSectorsReadyForSealing(bool, mpsc::SyncSender<Result<Vec<SectorId>>>),
+    CheckSectorHealth(
+        SectorId,
+        HealthCheckMode,
+        mpsc::SyncSender<Result<SealedSectorHealth>>,
+    ),
+    GetStorageReport(mpsc::SyncSender<Result<StorageReport>>),
+    GetKvStoreStats(mpsc::SyncSender<Result<KvStoreStats>>),
+    SetJournalKeepLastN(u64, mpsc::SyncSender<Result<()>>),
+    CompactMetadata(mpsc::SyncSender<Result<KvStoreStats>>),
+    GetPiecesByOrigin(String, mpsc::SyncSender<Result<Vec<PieceProvenance>>>),
+    GetSealStatistics(
+        Option<SecondsSinceEpoch>,
+        mpsc::SyncSender<Result<SealStatistics>>,
+    ),
+    GetExpiredSectors(
+        SecondsSinceEpoch,
+        mpsc::SyncSender<Result<Vec<SealedSectorMetadata>>>,
+    ),
+    ExportSealedSector(SectorId, PathBuf, mpsc::SyncSender<Result<()>>),
+    MoveSealedSector(SectorId, SealedSectorLocation, mpsc::SyncSender<Result<()>>),
+    HydrateSectorIntegrity(SectorId, mpsc::SyncSender<Result<bool>>),
+    RegisterSealedStore(String, PathBuf, mpsc::SyncSender<Result<()>>),
+    SetStorageQuota(StorageQuota, mpsc::SyncSender<Result<()>>),
+    ImportSealedSector(
+        PathBuf,
+        SealedSectorMetadata,
+        bool, // seal verified
+        bool, // link ok
+        mpsc::SyncSender<Result<()>>,
+    ),
+    GetSealStatus(
+        SectorId,
+        Option<HealthCheckMode>,
+        mpsc::SyncSender<Result<(SealStatus, Option<SealedSectorHealth>)>>,
+    ),
+    GetPieceLayout(SectorId, mpsc::SyncSender<Result<Vec<PieceLayout>>>),
+    GetPieceProof(String, mpsc::SyncSender<Result<PieceWithProof>>),
     GeneratePoSt(
         Vec<[u8; 32]>,
         [u8; 32],      // seed
         Vec<SectorId>, // faults
-        mpsc::SyncSender<Result<Vec<u8>>>,
+        PoStOptions,
+        mpsc::SyncSender<Result<GeneratedPoSt>>,
+    ),
+    GeneratePoStFirst(
+        Vec<[u8; 32]>,
+        [u8; 32],      // seed
+        Vec<SectorId>, // faults
+        Option<PoStConfig>,
+        mpsc::SyncSender<Result<Vec<rational_post::Challenge>>>,
+    ),
+    GeneratePoStPartitioned(
+        Vec<[u8; 32]>,
+        [u8; 32],      // seed
+        Vec<SectorId>, // faults
+        usize,         // partition size
+        mpsc::SyncSender<Result<mpsc::Receiver<Result<PoStPartition>>>>,
+    ),
+    RetrievePiece(
+        String,
+        Option<CancellationToken>,
+        mpsc::SyncSender<Result<MappedBytes>>,
     ),
-    RetrievePiece(String, mpsc::SyncSender<Result<Vec<u8>>>),
-    SealAllStagedSectors(mpsc::SyncSender<Result<()>>),
-    HandleSealResult(SectorId, String, PathBuf, Result<SealOutput>),
+    PlanRetrievePiece(String, mpsc::SyncSender<Result<UnsealTaskPrototype>>),
+    PlanSeal(SectorId, mpsc::SyncSender<Result<SealTaskPrototype>>),
+    RetrievePieces(
+        Vec<String>,
+        mpsc::SyncSender<(
+            Vec<mpsc::Receiver<Result<MappedBytes>>>,
+            Vec<Result<UnsealedPieceRange>>,
+        )>,
+    ),
+    SealAllStagedSectors(
+        SealTicket,
+        mpsc::SyncSender<Result<Vec<SealScheduleResult>>>,
+    ),
+    SetMaxNumStagedSectors(u64, mpsc::SyncSender<Result<()>>),
+    GetLimits(mpsc::SyncSender<Result<(u64, StorageQuota)>>),
+    SetAutoSealPolicy(AutoSealPolicy, mpsc::SyncSender<Result<()>>),
+    SetSealTicket(SealTicket, mpsc::SyncSender<Result<()>>),
+    SetSectorIdProvider(Box<dyn SectorIdProvider>, mpsc::SyncSender<Result<()>>),
+    SetStagedDataRetention(StagedDataRetention, mpsc::SyncSender<Result<()>>),
+    SetTaskTimeouts(TaskTimeouts, mpsc::SyncSender<Result<()>>),
+    SetChecksumAlgo(ChecksumAlgo, mpsc::SyncSender<Result<()>>),
+    SetSealIsolation(SealIsolation, mpsc::SyncSender<Result<()>>),
+    SetStagedWriteConfig(StagedWriteConfig, mpsc::SyncSender<Result<()>>),
+    SetIngestRateLimit(Option<u64>, mpsc::SyncSender<Result<()>>),
+    SetClock(Arc<dyn Clock>, mpsc::SyncSender<Result<()>>),
+    SetReplicator(
+        Option<Arc<dyn SealedSectorReplicator>>,
+        mpsc::SyncSender<Result<()>>,
+    ),
+    ReplicateSealedSector(SectorId, mpsc::SyncSender<Result<()>>),
+    HandleReplicationProgress(SectorId, u64),
+    HandleReplicationResult(SectorId, Result<String>),
+    SetSnapshotInterval(Option<Duration>, mpsc::SyncSender<Result<()>>),
+    ResealSector(SectorId, SealTicket, mpsc::SyncSender<Result<()>>),
+    RepairSector(SectorId, mpsc::SyncSender<Result<()>>),
+    // The bool is set if the worker abandoned the seal, which may still be
+    // writing the replica (see HandleAbandonedSealExit).
+    HandleSealResult(
+        SectorId,
+        SectorAccess,
+        PathBuf,
+        Result<(SealOutput, ReplicaChecksums)>,
+        SealTiming,
+        bool,
+    ),
+    HandleAbandonedSealExit(SectorId),
+    GetSealQueue(mpsc::SyncSender<Result<Vec<SealQueueEntry>>>),
+    SetSealPriority(SectorId, i32, mpsc::SyncSender<Result<()>>),
+    PromoteSeal(SectorId, mpsc::SyncSender<Result<()>>),
+    CancelSeal(SectorId, mpsc::SyncSender<Result<()>>),
+    SetSealMemoryWatermark(Option<u64>, mpsc::SyncSender<Result<()>>),
     HandleRetrievePieceResult(
         Result<(UnpaddedBytesAmount, PathBuf)>,
-        mpsc::SyncSender<Result<Vec<u8>>>,
+        mpsc::SyncSender<Result<MappedBytes>>,
     ),
+    HandleReplicaTaskResult(
+        ReplicaTaskPrototype,
+        Result<()>,
+        mpsc::SyncSender<Result<()>>,
+    ),
+    HandleHydrateTaskResult(
+        HydrateTaskPrototype,
+        Result<SectorIntegrity>,
+        mpsc::SyncSender<Result<bool>>,
+    ),
+    HandleRepairCheckResult(
+        RepairCheckTaskPrototype,
+        Result<SealedSectorHealth>,
+        mpsc::SyncSender<Result<()>>,
+    ),
+    Freeze(mpsc::SyncSender<Result<(SectorBuilderState, Vec<InterruptedSeal>)>>),
     Shutdown,
 }
 
+// How a SectorBuilder's callers hand their calls to the scheduler, which
+// handles one call at a time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SchedulerConfig {
+    // The number of calls which may wait for the scheduler. With a capacity
+    // of 0, a call is handed over only once the scheduler is free.
+    pub channel_capacity: usize,
+    pub overload_policy: OverloadPolicy,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            channel_capacity: 0,
+            overload_policy: OverloadPolicy::Block,
+        }
+    }
+}
+
+// What happens to a call which can't be handed to the scheduler right away,
+// as the scheduler is busy and as many calls as the channel holds are waiting
+// for it already.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverloadPolicy {
+    // the caller blocks until the call can be handed over
+    Block,
+    // the call fails with a SchedulerBusy error
+    Reject,
+}
+
 impl Scheduler {
     #[allow(clippy::too_many_arguments)]
     pub fn start<
@@ -64,41 +245,88 @@ impl Scheduler {
     >(
         scheduler_tx: mpsc::SyncSender<SchedulerTask<U>>,
         scheduler_rx: mpsc::Receiver<SchedulerTask<U>>,
-        worker_tx: mpsc::Sender<WorkerTask<U>>,
+        worker_tx: WorkerHandle<U>,
         mut m: SectorMetadataManager<T, S>,
+        stale_seal_policy: StaleSealPolicy,
     ) -> Result<Scheduler> {
         // If a previous instance of the SectorBuilder was shut down mid-seal,
         // its metadata store will contain staged sectors who are still
         // "Sealing." If we do have any of those when we start the Scheduler,
-        // we should immediately restart sealing.
+        // we restart sealing, mark them failed or revert them to pending,
+        // according to the stale seal policy.
         //
         // For more information, see rust-fil-sector-builder/17.
-        let protos: Result<Vec<SealTaskPrototype>> = m
-            .get_staged_sector_filtered(Some(SealStatus::Sealing))
-            .into_iter()
-            .map(|meta| m.create_seal_task_proto(meta.sector_id))
-            .collect();
-
-        for p in protos? {
-            worker_tx
-                .send(WorkerTask::from_seal_proto(p, scheduler_tx.clone()))
-                .expects(FATAL_NOSEND);
-        }
+        let protos = m.handle_stale_seals(stale_seal_policy)?;
+
+        let mut seal_queue = SealQueue::default();
+        seal_queue.extend(protos);
+        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+        let mut replication_pool =
+            ReplicationPool::new(NUM_REPLICATION_THREADS, scheduler_tx.clone());
 
         let thread = thread::spawn(move || {
+            // the caller of freeze, which waits for the seals being run by the
+            // workers to finish
+            let mut freezing: Option<mpsc::SyncSender<_>> = None;
+
+            // the number of replica transfers (exports, moves and imports)
+            // and checks handed to the workers whose results are yet to be
+            // handled
+            let mut replica_tasks_in_flight: usize = 0;
+
             loop {
-                let task = scheduler_rx.recv().expects(FATAL_NORECV);
+                // changes batched by a snapshot interval are flushed once
+                // the interval has elapsed, and sectors which have been
+                // staged for too long are sealed once they expire, even if no
+                // task arrives
+                let timeout = m
+                    .time_until_snapshot()
+                    .into_iter()
+                    .chain(m.time_until_auto_seal())
+                    .min();
+
+                let task = match timeout {
+                    Some(timeout) => match scheduler_rx.recv_timeout(timeout) {
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            if m.time_until_snapshot() == Some(Duration::default()) {
+                                m.flush_snapshot().expects(FATAL_SNPSHT);
+                            }
+
+                            if m.time_until_auto_seal() == Some(Duration::default()) {
+                                match m.schedule_sectors_ready_for_sealing() {
+                                    Ok(protos) => {
+                                        seal_queue.extend(protos);
+                                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+                                    }
+                                    Err(err) => {
+                                        error!("could not seal expired sectors: {:?}", err);
+                                    }
+                                }
+                            }
+
+                            continue;
+                        }
+                        received => received.expects(FATAL_NORECV),
+                    },
+                    None => scheduler_rx.recv().expects(FATAL_NORECV),
+                };
 
                 // Dispatch to the appropriate task-handler.
                 match task {
-                    SchedulerTask::AddPiece(key, amt, file, store_until, tx) => {
-                        match m.add_piece(key, amt, file, store_until) {
+                    SchedulerTask::AddPiece(
+                        key,
+                        amt,
+                        file,
+                        store_until,
+                        idempotency_key,
+                        deal_info,
+                        tx,
+                    ) => {
+                        match m.add_piece(key, amt, file, store_until, idempotency_key, deal_info) {
                             Ok((sector_id, protos)) => {
-                                for p in protos {
-                                    worker_tx
-                                        .send(WorkerTask::from_seal_proto(p, scheduler_tx.clone()))
-                                        .expects(FATAL_NOSEND);
-                                }
+                                seal_queue.extend(protos);
+                                seal_queue.dispatch(&worker_tx, &scheduler_tx);
 
                                 tx.send(Ok(sector_id)).expects(FATAL_NOSEND);
                             }
@@ -107,59 +335,518 @@ impl Scheduler {
                             }
                         }
                     }
-                    SchedulerTask::GetSealStatus(sector_id, tx) => {
-                        tx.send(m.get_seal_status(sector_id)).expects(FATAL_NOSEND);
+                    SchedulerTask::GetPieceLayout(sector_id, tx) => {
+                        tx.send(m.get_piece_layout(sector_id)).expects(FATAL_NOSEND);
                     }
-                    SchedulerTask::RetrievePiece(piece_key, tx) => {
+                    SchedulerTask::GetSealStatus(sector_id, check_health, tx) => {
+                        tx.send(m.get_seal_status_with_health_check(sector_id, check_health))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetPieceProof(piece_key, tx) => {
+                        tx.send(m.get_piece_proof(piece_key)).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::PlanRetrievePiece(piece_key, tx) => {
+                        tx.send(m.create_retrieve_piece_task_proto(piece_key))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::PlanSeal(sector_id, tx) => {
+                        tx.send(m.plan_seal_task_proto(sector_id))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::RetrievePiece(piece_key, cancel, tx) => {
                         match m.create_retrieve_piece_task_proto(piece_key) {
                             Ok(proto) => {
                                 worker_tx
                                     .send(WorkerTask::from_unseal_proto(
                                         proto,
+                                        cancel,
                                         tx.clone(),
                                         scheduler_tx.clone(),
                                     ))
                                     .expects(FATAL_NOSEND);
                             }
                             Err(err) => {
-                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                                // the caller of start_read_piece_from_sealed_sector
+                                // may have stopped waiting for the piece
+                                let _ = tx.send(Err(err));
                             }
                         }
                     }
+                    SchedulerTask::RetrievePieces(piece_keys, tx) => {
+                        let (protos, ranges) = m.create_retrieve_pieces_task_protos(&piece_keys);
+
+                        // each unseal reports to a channel of its own, which
+                        // buffers the result so that the scheduler isn't
+                        // blocked while the caller waits for other sectors
+                        let receivers = protos
+                            .into_iter()
+                            .map(|proto| {
+                                let (caller_done_tx, caller_done_rx) = mpsc::sync_channel(1);
+
+                                worker_tx
+                                    .send(WorkerTask::from_unseal_proto(
+                                        proto,
+                                        None,
+                                        caller_done_tx,
+                                        scheduler_tx.clone(),
+                                    ))
+                                    .expects(FATAL_NOSEND);
+
+                                caller_done_rx
+                            })
+                            .collect();
+
+                        tx.send((receivers, ranges)).expects(FATAL_NOSEND);
+                    }
                     SchedulerTask::GetSealedSectors(check_health, tx) => {
                         tx.send(m.get_sealed_sectors(check_health.0))
                             .expects(FATAL_NOSEND);
                     }
-                    SchedulerTask::GetStagedSectors(tx) => {
-                        tx.send(Ok(m.get_staged_sector_filtered(None)))
-                            .expect(FATAL_NOSEND);
+                    SchedulerTask::CheckSectorHealth(sector_id, mode, tx) => {
+                        tx.send(m.check_sector_health(sector_id, mode))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetStagedSector(sector_id, tx) => {
+                        tx.send(m.get_staged_sector(sector_id))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetSectorsReadyForSealing(seal_all_staged_sectors, tx) => {
+                        tx.send(Ok(m.get_sectors_ready_for_sealing(seal_all_staged_sectors)))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetStorageReport(tx) => {
+                        tx.send(m.get_storage_report()).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetKvStoreStats(tx) => {
+                        tx.send(m.kv_store_stats()).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetJournalKeepLastN(keep_last_n, tx) => {
+                        m.set_journal_keep_last_n(keep_last_n);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::CompactMetadata(tx) => {
+                        tx.send(m.compact_metadata()).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetPiecesByOrigin(origin, tx) => {
+                        tx.send(m.get_pieces_by_origin(&origin))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetSealStatistics(since, tx) => {
+                        tx.send(Ok(m.get_seal_statistics(since.as_ref())))
+                            .expects(FATAL_NOSEND);
                     }
-                    SchedulerTask::SealAllStagedSectors(tx) => match m.seal_all_staged_sectors() {
-                        Ok(protos) => {
-                            for p in protos {
+                    SchedulerTask::GetExpiredSectors(now, tx) => {
+                        tx.send(Ok(m.get_expired_sectors(&now)))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::ExportSealedSector(sector_id, dest_dir, tx) => {
+                        match m.create_export_task_proto(sector_id, dest_dir) {
+                            Ok(proto) => {
                                 worker_tx
-                                    .send(WorkerTask::from_seal_proto(p, scheduler_tx.clone()))
+                                    .send(WorkerTask::TransferReplica {
+                                        proto,
+                                        caller_done_tx: tx,
+                                        done_tx: scheduler_tx.clone(),
+                                    })
                                     .expects(FATAL_NOSEND);
+
+                                replica_tasks_in_flight += 1;
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
                             }
+                        }
+                    }
+                    SchedulerTask::MoveSealedSector(sector_id, target, tx) => {
+                        // a replica which is being resealed can't be moved,
+                        // as the new one would replace it at its old location
+                        let result = seal_queue
+                            .ensure_not_sealing(sector_id)
+                            .and_then(|_| m.create_move_task_proto(sector_id, target));
+
+                        match result {
+                            Ok(proto) => {
+                                worker_tx
+                                    .send(WorkerTask::TransferReplica {
+                                        proto,
+                                        caller_done_tx: tx,
+                                        done_tx: scheduler_tx.clone(),
+                                    })
+                                    .expects(FATAL_NOSEND);
 
-                            tx.send(Ok(())).expects(FATAL_NOSEND);
+                                replica_tasks_in_flight += 1;
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
                         }
-                        Err(err) => {
-                            tx.send(Err(err)).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::HydrateSectorIntegrity(sector_id, tx) => {
+                        match m.create_hydrate_task_proto(sector_id) {
+                            Ok(Some(proto)) => {
+                                worker_tx
+                                    .send(WorkerTask::HydrateIntegrity {
+                                        proto,
+                                        caller_done_tx: tx,
+                                        done_tx: scheduler_tx.clone(),
+                                    })
+                                    .expects(FATAL_NOSEND);
+
+                                replica_tasks_in_flight += 1;
+                            }
+                            Ok(None) => {
+                                tx.send(Ok(false)).expects(FATAL_NOSEND);
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
                         }
-                    },
-                    SchedulerTask::HandleSealResult(sector_id, access, path, result) => {
-                        m.handle_seal_result(sector_id, access, path, result);
                     }
-                    SchedulerTask::HandleRetrievePieceResult(result, tx) => {
-                        tx.send(m.read_unsealed_bytes_from(result))
+                    SchedulerTask::HandleHydrateTaskResult(proto, result, tx) => {
+                        replica_tasks_in_flight -= 1;
+
+                        tx.send(m.handle_hydrate_task_result(proto, result))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::RegisterSealedStore(store_id, dir, tx) => {
+                        tx.send(m.register_sealed_store(store_id, dir))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetStorageQuota(quota, tx) => {
+                        m.set_storage_quota(quota);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::ImportSealedSector(path, meta, seal_verified, link_ok, tx) => {
+                        match m.create_import_task_proto(path, meta, seal_verified, link_ok) {
+                            Ok(proto) => {
+                                worker_tx
+                                    .send(WorkerTask::TransferReplica {
+                                        proto,
+                                        caller_done_tx: tx,
+                                        done_tx: scheduler_tx.clone(),
+                                    })
+                                    .expects(FATAL_NOSEND);
+
+                                replica_tasks_in_flight += 1;
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
+                        }
+                    }
+                    SchedulerTask::HandleReplicaTaskResult(proto, result, tx) => {
+                        replica_tasks_in_flight -= 1;
+
+                        tx.send(m.handle_replica_task_result(proto, result))
                             .expects(FATAL_NOSEND);
                     }
-                    SchedulerTask::GeneratePoSt(comm_rs, chg_seed, faults, tx) => {
-                        tx.send(m.generate_post(&comm_rs, &chg_seed, faults))
+                    SchedulerTask::SealAllStagedSectors(seal_ticket, tx) => {
+                        let (protos, results) = m.seal_all_staged_sectors(seal_ticket);
+
+                        seal_queue.extend(protos);
+                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+                        tx.send(Ok(results)).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GetLimits(tx) => {
+                        tx.send(Ok(m.get_limits())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetMaxNumStagedSectors(max_num_staged_sectors, tx) => {
+                        match m.set_max_num_staged_sectors(max_num_staged_sectors) {
+                            Ok(protos) => {
+                                seal_queue.extend(protos);
+                                seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+                                tx.send(Ok(())).expects(FATAL_NOSEND);
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
+                        }
+                    }
+                    SchedulerTask::SetAutoSealPolicy(auto_seal_policy, tx) => {
+                        match m.set_auto_seal_policy(auto_seal_policy) {
+                            Ok(protos) => {
+                                seal_queue.extend(protos);
+                                seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+                                tx.send(Ok(())).expects(FATAL_NOSEND);
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
+                        }
+                    }
+                    SchedulerTask::ResealSector(sector_id, seal_ticket, tx) => {
+                        // check the queue before touching any metadata, so that
+                        // a rejected task leaves the sector as it was
+                        let result = seal_queue
+                            .ensure_not_sealing(sector_id)
+                            .and_then(|_| m.create_reseal_task_proto(sector_id, seal_ticket))
+                            .and_then(|proto| seal_queue.push(proto));
+
+                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+                        tx.send(result).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::RepairSector(sector_id, tx) => {
+                        let result = seal_queue
+                            .ensure_not_sealing(sector_id)
+                            .and_then(|_| m.create_repair_check_task_proto(sector_id));
+
+                        match result {
+                            Ok(proto) => {
+                                worker_tx
+                                    .send(WorkerTask::CheckRepairable {
+                                        proto,
+                                        caller_done_tx: tx,
+                                        done_tx: scheduler_tx.clone(),
+                                    })
+                                    .expects(FATAL_NOSEND);
+
+                                replica_tasks_in_flight += 1;
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
+                        }
+                    }
+                    SchedulerTask::HandleRepairCheckResult(proto, result, tx) => {
+                        replica_tasks_in_flight -= 1;
+
+                        // the sector may have been resealed while its replica
+                        // was being checked, which fails the repair but must
+                        // still release the replica
+                        let result = seal_queue
+                            .ensure_not_sealing(proto.meta.sector_id)
+                            .and(result);
+
+                        let result = m
+                            .handle_repair_check_result(proto, result)
+                            .and_then(|proto| seal_queue.push(proto));
+
+                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+                        tx.send(result).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetStagedDataRetention(retention, tx) => {
+                        tx.send(m.set_staged_data_retention(retention))
                             .expects(FATAL_NOSEND);
                     }
-                    SchedulerTask::Shutdown => break,
+                    SchedulerTask::SetSectorIdProvider(sector_id_provider, tx) => {
+                        m.set_sector_id_provider(sector_id_provider);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetSealTicket(seal_ticket, tx) => {
+                        m.set_seal_ticket(seal_ticket);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetTaskTimeouts(task_timeouts, tx) => {
+                        m.set_task_timeouts(task_timeouts);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetChecksumAlgo(checksum_algo, tx) => {
+                        m.set_checksum_algo(checksum_algo);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetSealIsolation(seal_isolation, tx) => {
+                        m.set_seal_isolation(seal_isolation);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetStagedWriteConfig(staged_write_config, tx) => {
+                        m.set_staged_write_config(staged_write_config);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetIngestRateLimit(bytes_per_sec, tx) => {
+                        m.set_ingest_rate_limit(bytes_per_sec);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetReplicator(replicator, tx) => {
+                        for job in m.set_replicator(replicator) {
+                            replication_pool.submit(job);
+                        }
+
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::ReplicateSealedSector(sector_id, tx) => {
+                        let result = m
+                            .replicate_sealed_sector(sector_id)
+                            .map(|job| replication_pool.submit(job));
+
+                        tx.send(result).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::HandleReplicationProgress(sector_id, bytes_copied) => {
+                        m.handle_replication_progress(sector_id, bytes_copied);
+                    }
+                    SchedulerTask::HandleReplicationResult(sector_id, result) => {
+                        replication_pool.complete();
+
+                        m.handle_replication_result(sector_id, result);
+                    }
+                    SchedulerTask::SetClock(clock, tx) => {
+                        m.set_clock(clock);
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetSnapshotInterval(snapshot_interval, tx) => {
+                        tx.send(m.set_snapshot_interval(snapshot_interval))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::HandleSealResult(
+                        sector_id,
+                        access,
+                        path,
+                        result,
+                        timing,
+                        abandoned,
+                    ) => {
+                        if let Some(job) =
+                            m.handle_seal_result(sector_id, access, path, result, timing)
+                        {
+                            replication_pool.submit(job);
+                        }
+
+                        if abandoned {
+                            seal_queue.abandon(sector_id);
+                        } else {
+                            seal_queue.complete(sector_id);
+                        }
+
+                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+                    }
+                    SchedulerTask::HandleAbandonedSealExit(sector_id) => {
+                        info!("abandoned seal of sector {} has exited", sector_id);
+
+                        seal_queue.abandoned_seal_exited(sector_id);
+                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+                    }
+                    SchedulerTask::GetSealQueue(tx) => {
+                        tx.send(Ok(seal_queue.entries())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetSealPriority(sector_id, priority, tx) => {
+                        tx.send(seal_queue.set_priority(sector_id, priority))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::SetSealMemoryWatermark(memory_watermark, tx) => {
+                        seal_queue.set_memory_watermark(memory_watermark);
+                        seal_queue.dispatch(&worker_tx, &scheduler_tx);
+
+                        tx.send(Ok(())).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::PromoteSeal(sector_id, tx) => {
+                        tx.send(seal_queue.promote(sector_id)).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::CancelSeal(sector_id, tx) => {
+                        // seals which a worker has taken fail once the worker
+                        // stops, which is handled like any other seal result
+                        let result = seal_queue
+                            .cancel(sector_id)
+                            .and_then(|queued| match queued {
+                                Some(_) => m.handle_cancelled_seal(sector_id),
+                                None => Ok(()),
+                            });
+
+                        tx.send(result).expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::HandleRetrievePieceResult(result, tx) => {
+                        // the caller may have stopped waiting for the piece,
+                        // e.g. after cancelling its retrieval
+                        let _ = tx.send(m.read_unsealed_bytes_from(result));
+                    }
+                    SchedulerTask::GeneratePoSt(comm_rs, chg_seed, faults, options, tx) => {
+                        // the caller of start_generate_post may have
+                        // stopped waiting for the proof
+                        let _ = tx.send(m.generate_post(&comm_rs, &chg_seed, faults, options));
+                    }
+                    SchedulerTask::GeneratePoStFirst(
+                        comm_rs,
+                        chg_seed,
+                        faults,
+                        post_config,
+                        tx,
+                    ) => {
+                        tx.send(m.generate_post_first(&comm_rs, &chg_seed, faults, post_config))
+                            .expects(FATAL_NOSEND);
+                    }
+                    SchedulerTask::GeneratePoStPartitioned(
+                        comm_rs,
+                        chg_seed,
+                        faults,
+                        partition_size,
+                        tx,
+                    ) => {
+                        match m.create_post_partition_task_protos(
+                            &comm_rs,
+                            &chg_seed,
+                            faults,
+                            partition_size,
+                        ) {
+                            Ok(protos) => {
+                                // the workers report each partition as soon as
+                                // it's proven, without waiting for the caller
+                                let (caller_done_tx, caller_done_rx) = mpsc::channel();
+
+                                for proto in protos {
+                                    worker_tx
+                                        .send(WorkerTask::GeneratePoSt {
+                                            proto,
+                                            caller_done_tx: caller_done_tx.clone(),
+                                        })
+                                        .expects(FATAL_NOSEND);
+                                }
+
+                                tx.send(Ok(caller_done_rx)).expects(FATAL_NOSEND);
+                            }
+                            Err(err) => {
+                                tx.send(Err(err)).expects(FATAL_NOSEND);
+                            }
+                        }
+                    }
+                    SchedulerTask::Freeze(tx) => {
+                        // seals which are waiting for a worker are handed off,
+                        // those being run, replica transfers and copies made
+                        // by the replication pool are waited for (see below)
+                        seal_queue.pause();
+                        freezing = Some(tx);
+                    }
+                    SchedulerTask::Shutdown => {
+                        m.flush_snapshot().expects(FATAL_SNPSHT);
+                        m.persist_shutdown_marker(seal_queue.interrupted())
+                            .expects(FATAL_SNPSHT);
+                        break;
+                    }
+                }
+
+                // Once the results of the seals, replica transfers and checks,
+                // and replication copies which were in flight when the
+                // SectorBuilder was frozen have been applied, the state is
+                // persisted and handed off. The scheduler stops right away, as
+                // anything it did afterwards would be lost in the handoff, as
+                // would results which arrived once it has stopped (e.g. the
+                // new location of a replica whose move has already removed it
+                // from the old one).
+                if freezing.is_some()
+                    && !seal_queue.is_sealing()
+                    && replica_tasks_in_flight == 0
+                    && !replication_pool.is_copying()
+                {
+                    let interrupted_seals = seal_queue.interrupted();
+
+                    let result = m
+                        .flush_snapshot()
+                        .and_then(|_| m.persist_shutdown_marker(interrupted_seals.clone()))
+                        .map(|_| (m.state.clone(), interrupted_seals));
+
+                    let frozen = result.is_ok();
+
+                    if let Some(tx) = freezing.take() {
+                        tx.send(result).expects(FATAL_NOSEND);
+                    }
+
+                    // a SectorBuilder which couldn't be frozen is dropped,
+                    // which shuts the scheduler down
+                    if frozen {
+                        break;
+                    }
                 }
             }
         });
@@ -169,3 +856,136 @@ impl Scheduler {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use filecoin_proofs::types::{PoRepProofPartitions, SectorClass, SectorSize};
+
+    use super::*;
+    use crate::disk_backed_storage::new_sector_store_with_spill_over;
+    use crate::events::EventBus;
+    use crate::helpers::move_replica;
+    use crate::kv_store::SledKvs;
+
+    #[test]
+    fn freezing_waits_for_an_in_flight_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let sealed_dir = dir.path().join("sealed");
+        let archive_dir = dir.path().join("archive");
+
+        let sector_store = new_sector_store_with_spill_over(
+            SectorClass(SectorSize(1024), PoRepProofPartitions(2)),
+            &sealed_dir,
+            dir.path().join("staged"),
+            None,
+            Default::default(),
+            None,
+        );
+
+        let sector_id = SectorId::from(7);
+        let sector_access = SectorAccess::new("s-t01-7").unwrap();
+
+        fs::create_dir_all(&sealed_dir).unwrap();
+        fs::write(
+            sector_store.manager().sealed_sector_path(&sector_access),
+            &[7; 128],
+        )
+        .unwrap();
+
+        let mut state = SectorBuilderState::new(sector_id);
+        state.sealed.sectors.insert(
+            sector_id,
+            SealedSectorMetadata {
+                sector_id,
+                sector_access: sector_access.clone(),
+                len: 128,
+                ..Default::default()
+            },
+        );
+
+        let m = SectorMetadataManager::new(
+            SledKvs::initialize(dir.path().join("metadata")).unwrap(),
+            sector_store,
+            state,
+            1,
+            [0; 31],
+            EventBus::default(),
+        )
+        .unwrap();
+
+        // the test stands in for the workers
+        let (worker_tx, worker_rx) = mpsc::channel();
+        let (scheduler_tx, scheduler_rx) = mpsc::sync_channel::<SchedulerTask<File>>(0);
+
+        let mut scheduler = Scheduler::start(
+            scheduler_tx.clone(),
+            scheduler_rx,
+            WorkerHandle::new(worker_tx, Default::default()),
+            m,
+            StaleSealPolicy::Resume,
+        )
+        .unwrap();
+
+        let target = SealedSectorLocation::Dir(archive_dir.clone());
+
+        let (move_tx, move_rx) = mpsc::sync_channel(1);
+        scheduler_tx
+            .send(SchedulerTask::MoveSealedSector(
+                sector_id,
+                target.clone(),
+                move_tx,
+            ))
+            .unwrap();
+
+        let (proto, caller_done_tx) = match worker_rx.recv().unwrap() {
+            (
+                _,
+                WorkerTask::TransferReplica {
+                    proto,
+                    caller_done_tx,
+                    ..
+                },
+            ) => (proto, caller_done_tx),
+            _ => panic!("expected the move to be handed to a worker"),
+        };
+
+        let (freeze_tx, freeze_rx) = mpsc::sync_channel(1);
+        scheduler_tx.send(SchedulerTask::Freeze(freeze_tx)).unwrap();
+
+        // the replica has left its old location, so the state mustn't be
+        // handed off until the move has been recorded
+        if let ReplicaTaskPrototype::Move {
+            source_path,
+            target_path,
+            ..
+        } = &proto
+        {
+            move_replica(source_path, target_path).unwrap();
+        }
+
+        assert!(freeze_rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        scheduler_tx
+            .send(SchedulerTask::HandleReplicaTaskResult(
+                proto,
+                Ok(()),
+                caller_done_tx,
+            ))
+            .unwrap();
+
+        assert!(move_rx.recv().unwrap().is_ok());
+
+        let (state, interrupted_seals) = freeze_rx.recv().unwrap().unwrap();
+
+        assert_eq!(
+            target,
+            state.sealed.sectors.get(&sector_id).unwrap().location
+        );
+        assert!(interrupted_seals.is_empty());
+        assert!(archive_dir.join("s-t01-7").exists());
+
+        scheduler.thread.take().unwrap().join().unwrap();
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+
+use filecoin_proofs::error::ExpectWithBacktrace;
+use filecoin_proofs::types::PaddedBytesAmount;
+use storage_proofs::sector::SectorId;
+
+use crate::constants::NUM_WORKERS;
+use crate::error::{err_already_sealing, err_unrecov, Result};
+use crate::metadata::{InterruptedSeal, SecondsSinceEpoch};
+use crate::scheduler::SchedulerTask;
+use crate::watchdog::CancellationToken;
+use crate::worker::{SealTaskPrototype, WorkerHandle, WorkerTask};
+
+const FATAL_NOSEND: &str = "could not send";
+
+// Rough peak memory required by each phase of sealing a sector, as multiples
+// of the sector size. Replication holds the sector's data along with the
+// merkle trees of the layers it encodes, and proving holds the trees along
+// with the circuit into which the vanilla proofs are synthesized.
+const REPLICATION_MEMORY_FACTOR: u64 = 3;
+const PROVING_MEMORY_FACTOR: u64 = 2;
+
+// Estimates the peak memory required to seal a sector of the provided size,
+// which is that of its most demanding phase.
+pub fn estimate_seal_memory(sector_size: u64) -> u64 {
+    std::cmp::max(
+        sector_size.saturating_mul(REPLICATION_MEMORY_FACTOR),
+        sector_size.saturating_mul(PROVING_MEMORY_FACTOR),
+    )
+}
+
+/// A sector which has been scheduled for sealing and is waiting for a worker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SealQueueEntry {
+    pub sector_id: SectorId,
+    /// when the sector was scheduled for sealing
+    pub enqueued_at: SecondsSinceEpoch,
+    /// sectors with a higher priority are handed to a worker first; sectors
+    /// are scheduled with priority 0
+    pub priority: i32,
+}
+
+// Holds the seal tasks which have been scheduled, handing them to the workers
+// in order of priority (and then in the order in which they were scheduled)
+// as workers become available. Tasks which have been handed to the workers
+// are no longer in the queue and can't be reordered.
+//
+// A sector is sealed by a single task, so the queue holds at most one task
+// per sector until the worker sealing it has finished. Two workers writing
+// the same replica would clobber each other's output.
+//
+// With a memory watermark, a task is only handed to a worker if the memory
+// estimated to be required by the tasks being sealed, including it, stays
+// below the watermark. Otherwise it waits until enough tasks have finished.
+// A task is always handed to a worker if no other task is being sealed, as
+// it would never be otherwise.
+//
+// A seal which was abandoned (e.g. as it timed out) may still be writing its
+// replica, so its sector can't be scheduled for sealing again until the
+// abandoned seal has exited. It no longer takes up a worker, but its memory
+// still counts towards the watermark.
+pub struct SealQueue {
+    entries: Vec<(SealQueueEntry, SealTaskPrototype)>,
+    // estimated memory required by each of the tasks being sealed
+    in_flight: HashMap<SectorId, u64>,
+    // tokens through which the tasks being sealed are cancelled
+    cancel_tokens: HashMap<SectorId, CancellationToken>,
+    // estimated memory required by each of the abandoned seals which are
+    // still running
+    abandoned: HashMap<SectorId, u64>,
+    // abandoned seals which exited before the seal's result was handled
+    exited: HashSet<SectorId>,
+    max_in_flight: usize,
+    memory_watermark: Option<u64>,
+    // set while the SectorBuilder is being frozen, which hands off the tasks
+    // waiting for a worker instead of sealing them
+    paused: bool,
+}
+
+impl Default for SealQueue {
+    fn default() -> SealQueue {
+        SealQueue {
+            entries: Default::default(),
+            in_flight: Default::default(),
+            cancel_tokens: Default::default(),
+            abandoned: Default::default(),
+            exited: Default::default(),
+            max_in_flight: NUM_WORKERS,
+            memory_watermark: None,
+            paused: false,
+        }
+    }
+}
+
+impl SealQueue {
+    // Produces an error if the sector with the provided id is waiting for a
+    // worker or being sealed by one.
+    pub fn ensure_not_sealing(&self, sector_id: SectorId) -> Result<()> {
+        let is_queued = self
+            .entries
+            .iter()
+            .any(|(entry, _)| entry.sector_id == sector_id);
+
+        if is_queued
+            || self.in_flight.contains_key(&sector_id)
+            || self.abandoned.contains_key(&sector_id)
+        {
+            return Err(err_already_sealing(sector_id).into());
+        }
+
+        Ok(())
+    }
+
+    pub fn push(&mut self, proto: SealTaskPrototype) -> Result<()> {
+        self.ensure_not_sealing(proto.sector_id)?;
+
+        let entry = SealQueueEntry {
+            sector_id: proto.sector_id,
+            enqueued_at: SecondsSinceEpoch::now(),
+            priority: 0,
+        };
+
+        self.insert(entry, proto);
+
+        Ok(())
+    }
+
+    // Queues the provided tasks, dropping those for sectors which are already
+    // being sealed.
+    pub fn extend(&mut self, protos: Vec<SealTaskPrototype>) {
+        for proto in protos {
+            if let Err(err) = self.push(proto) {
+                warn!("dropping duplicate seal task: {}", err);
+            }
+        }
+    }
+
+    // Returns the sectors waiting for a worker, in the order in which they
+    // will be handed to the workers.
+    pub fn entries(&self) -> Vec<SealQueueEntry> {
+        self.entries
+            .iter()
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+
+    // Changes the priority of the sector with the provided id, which moves it
+    // ahead of the sectors with a lower priority and behind those with the
+    // same or a higher one.
+    pub fn set_priority(&mut self, sector_id: SectorId, priority: i32) -> Result<()> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(entry, _)| entry.sector_id == sector_id)
+            .ok_or_else(|| {
+                err_unrecov(format!("sector {} isn't waiting for a worker", sector_id))
+            })?;
+
+        let (mut entry, proto) = self.entries.remove(index);
+        entry.priority = priority;
+
+        self.insert(entry, proto);
+
+        Ok(())
+    }
+
+    // Moves the sector with the provided id to the head of the queue by giving
+    // it a higher priority than that of the sector at the head.
+    pub fn promote(&mut self, sector_id: SectorId) -> Result<()> {
+        let priority = match self.entries.first() {
+            Some((head, _)) if head.sector_id == sector_id => return Ok(()),
+            Some((head, _)) => head.priority.saturating_add(1),
+            None => 0,
+        };
+
+        self.set_priority(sector_id, priority)
+    }
+
+    // Replaces the memory watermark, in bytes, above which no more tasks are
+    // handed to the workers. Without a watermark, tasks are handed to the
+    // workers regardless of the memory they require.
+    pub fn set_memory_watermark(&mut self, memory_watermark: Option<u64>) {
+        self.memory_watermark = memory_watermark;
+    }
+
+    // Stops handing tasks to the workers for good. Tasks which are waiting for
+    // a worker keep waiting, and those being sealed are sealed as usual.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    // Returns whether any of the tasks handed to the workers is still being
+    // sealed. Abandoned seals don't count, as their results are handled
+    // already.
+    pub fn is_sealing(&self) -> bool {
+        !self.in_flight.is_empty()
+    }
+
+    // Hands queued tasks to the workers until every worker has a seal task or
+    // the next task would take the memory required by the tasks being sealed
+    // above the watermark. Nothing is handed over once the queue is paused.
+    pub fn dispatch<T>(
+        &mut self,
+        worker_tx: &WorkerHandle<T>,
+        done_tx: &mpsc::SyncSender<SchedulerTask<T>>,
+    ) {
+        while !self.paused && self.in_flight.len() < self.max_in_flight && !self.entries.is_empty()
+        {
+            let required = estimate_seal_memory(u64::from(PaddedBytesAmount::from(
+                self.entries[0].1.porep_config,
+            )));
+
+            if !self.admits(required) {
+                break;
+            }
+
+            let (entry, proto) = self.entries.remove(0);
+            let cancel = CancellationToken::default();
+
+            worker_tx
+                .send(WorkerTask::from_seal_proto(
+                    proto,
+                    cancel.clone(),
+                    done_tx.clone(),
+                ))
+                .expects(FATAL_NOSEND);
+
+            self.in_flight.insert(entry.sector_id, required);
+            self.cancel_tokens.insert(entry.sector_id, cancel);
+        }
+    }
+
+    // Cancels sealing the sector with the provided id. A task waiting for a
+    // worker is taken out of the queue and returned, so that the caller can
+    // record that it won't be sealed. A task being sealed is abandoned by its
+    // worker, which reports it like any other abandoned seal.
+    pub fn cancel(&mut self, sector_id: SectorId) -> Result<Option<SealTaskPrototype>> {
+        if let Some(index) = self
+            .entries
+            .iter()
+            .position(|(entry, _)| entry.sector_id == sector_id)
+        {
+            let (_, proto) = self.entries.remove(index);
+            return Ok(Some(proto));
+        }
+
+        match self.cancel_tokens.get(&sector_id) {
+            Some(cancel) => {
+                cancel.cancel();
+                Ok(None)
+            }
+            None => Err(err_unrecov(format!("sector {} isn't being sealed", sector_id)).into()),
+        }
+    }
+
+    // Records that a worker has finished sealing the sector with the provided
+    // id, which may then be scheduled for sealing again.
+    pub fn complete(&mut self, sector_id: SectorId) {
+        self.in_flight.remove(&sector_id);
+        self.cancel_tokens.remove(&sector_id);
+    }
+
+    // Records that the worker sealing the sector with the provided id has
+    // abandoned the seal, which keeps the sector from being scheduled again
+    // until abandoned_seal_exited is called for it.
+    pub fn abandon(&mut self, sector_id: SectorId) {
+        self.cancel_tokens.remove(&sector_id);
+
+        if let Some(required) = self.in_flight.remove(&sector_id) {
+            if !self.exited.remove(&sector_id) {
+                self.abandoned.insert(sector_id, required);
+            }
+        }
+    }
+
+    // Records that the abandoned seal of the sector with the provided id has
+    // exited, which may be reported before the seal's result is handled.
+    pub fn abandoned_seal_exited(&mut self, sector_id: SectorId) {
+        if self.abandoned.remove(&sector_id).is_none() {
+            self.exited.insert(sector_id);
+        }
+    }
+
+    // Returns the tasks being sealed, followed by those waiting for a worker,
+    // which are interrupted if the SectorBuilder shuts down.
+    pub fn interrupted(&self) -> Vec<InterruptedSeal> {
+        let mut dispatched: Vec<SectorId> = self.in_flight.keys().cloned().collect();
+        dispatched.sort();
+
+        let dispatched = dispatched.into_iter().map(|sector_id| InterruptedSeal {
+            sector_id,
+            dispatched: true,
+        });
+
+        let queued = self.entries.iter().map(|(entry, _)| InterruptedSeal {
+            sector_id: entry.sector_id,
+            dispatched: false,
+        });
+
+        dispatched.chain(queued).collect()
+    }
+
+    fn admits(&self, required: u64) -> bool {
+        match self.memory_watermark {
+            Some(_) if self.in_flight.is_empty() => true,
+            Some(watermark) => {
+                let in_use: u64 = self.in_flight.values().chain(self.abandoned.values()).sum();
+                in_use.saturating_add(required) <= watermark
+            }
+            None => true,
+        }
+    }
+
+    fn insert(&mut self, entry: SealQueueEntry, proto: SealTaskPrototype) {
+        let index = self
+            .entries
+            .iter()
+            .position(|(queued, _)| queued.priority < entry.priority)
+            .unwrap_or_else(|| self.entries.len());
+
+        self.entries.insert(index, (entry, proto));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use filecoin_proofs::{PoRepConfig, PoRepProofPartitions, SectorSize};
+
+    use crate::sector_access::SectorAccess;
+
+    use super::*;
+
+    fn proto(sector_id: u64) -> SealTaskPrototype {
+        SealTaskPrototype {
+            piece_lens: vec![],
+            porep_config: PoRepConfig(SectorSize(1024), PoRepProofPartitions(2)),
+            sealed_sector_access: SectorAccess::new(format!("sealed-{}", sector_id)).unwrap(),
+            sealed_sector_path: PathBuf::from(format!("sealed-{}", sector_id)),
+            seal_ticket: None,
+            sector_id: SectorId::from(sector_id),
+            staged_sector_path: PathBuf::from(format!("staged-{}", sector_id)),
+            timeout: None,
+            isolation: Default::default(),
+            staged_data_key: None,
+            piece_manifest: Vec::new(),
+            checksum_algo: Default::default(),
+        }
+    }
+
+    fn queued_sector_ids(queue: &SealQueue) -> Vec<SectorId> {
+        queue
+            .entries()
+            .iter()
+            .map(|entry| entry.sector_id)
+            .collect()
+    }
+
+    #[test]
+    fn orders_by_priority_then_schedule() {
+        let mut queue = SealQueue::default();
+
+        queue.push(proto(1)).unwrap();
+        queue.push(proto(2)).unwrap();
+        queue.push(proto(3)).unwrap();
+
+        assert_eq!(
+            vec![SectorId::from(1), SectorId::from(2), SectorId::from(3)],
+            queued_sector_ids(&queue)
+        );
+
+        queue.set_priority(SectorId::from(3), 1).unwrap();
+        queue.set_priority(SectorId::from(2), 1).unwrap();
+
+        assert_eq!(
+            vec![SectorId::from(3), SectorId::from(2), SectorId::from(1)],
+            queued_sector_ids(&queue)
+        );
+
+        queue.set_priority(SectorId::from(1), -1).unwrap();
+        queue.push(proto(4)).unwrap();
+
+        assert_eq!(
+            vec![
+                SectorId::from(3),
+                SectorId::from(2),
+                SectorId::from(4),
+                SectorId::from(1)
+            ],
+            queued_sector_ids(&queue)
+        );
+
+        assert!(queue.set_priority(SectorId::from(5), 1).is_err());
+    }
+
+    #[test]
+    fn dispatches_to_available_workers() {
+        let (tx, worker_rx) = mpsc::channel::<(_, WorkerTask<std::fs::File>)>();
+        let worker_tx = WorkerHandle::new(tx, Default::default());
+        let (done_tx, _done_rx) = mpsc::sync_channel(0);
+
+        let mut queue = SealQueue::default();
+
+        for sector_id in 0..NUM_WORKERS as u64 + 1 {
+            queue.push(proto(sector_id)).unwrap();
+        }
+
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert_eq!(NUM_WORKERS, worker_rx.try_iter().count());
+        assert_eq!(1, queue.entries().len());
+
+        queue.complete(SectorId::from(0));
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert_eq!(1, worker_rx.try_iter().count());
+        assert!(queue.entries().is_empty());
+    }
+
+    #[test]
+    fn stops_dispatching_once_paused() {
+        let (tx, worker_rx) = mpsc::channel::<(_, WorkerTask<std::fs::File>)>();
+        let worker_tx = WorkerHandle::new(tx, Default::default());
+        let (done_tx, _done_rx) = mpsc::sync_channel(0);
+
+        let mut queue = SealQueue::default();
+
+        queue.push(proto(1)).unwrap();
+        queue.dispatch(&worker_tx, &done_tx);
+        assert!(queue.is_sealing());
+
+        queue.pause();
+        queue.push(proto(2)).unwrap();
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert_eq!(1, worker_rx.try_iter().count());
+        assert_eq!(vec![SectorId::from(2)], queued_sector_ids(&queue));
+
+        // the task being sealed finishes as usual
+        queue.complete(SectorId::from(1));
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert!(!queue.is_sealing());
+        assert_eq!(0, worker_rx.try_iter().count());
+        assert_eq!(
+            vec![InterruptedSeal {
+                sector_id: SectorId::from(2),
+                dispatched: false,
+            }],
+            queue.interrupted()
+        );
+    }
+
+    #[test]
+    fn promotes_to_head() {
+        let mut queue = SealQueue::default();
+
+        queue.push(proto(1)).unwrap();
+        queue.push(proto(2)).unwrap();
+        queue.push(proto(3)).unwrap();
+        queue.set_priority(SectorId::from(1), 5).unwrap();
+
+        queue.promote(SectorId::from(3)).unwrap();
+
+        assert_eq!(
+            vec![SectorId::from(3), SectorId::from(1), SectorId::from(2)],
+            queued_sector_ids(&queue)
+        );
+        assert_eq!(6, queue.entries()[0].priority);
+
+        // promoting the head leaves the queue as it is
+        queue.promote(SectorId::from(3)).unwrap();
+        assert_eq!(6, queue.entries()[0].priority);
+
+        assert!(queue.promote(SectorId::from(4)).is_err());
+    }
+
+    #[test]
+    fn rejects_sectors_already_being_sealed() {
+        let (tx, _worker_rx) = mpsc::channel::<(_, WorkerTask<std::fs::File>)>();
+        let worker_tx = WorkerHandle::new(tx, Default::default());
+        let (done_tx, _done_rx) = mpsc::sync_channel(0);
+
+        let mut queue = SealQueue::default();
+
+        queue.push(proto(1)).unwrap();
+        assert!(queue.push(proto(1)).is_err());
+
+        // duplicates are dropped from batches
+        queue.extend(vec![proto(1), proto(2)]);
+        assert_eq!(
+            vec![SectorId::from(1), SectorId::from(2)],
+            queued_sector_ids(&queue)
+        );
+
+        // sectors handed to a worker can't be scheduled again until the
+        // worker has finished
+        queue.dispatch(&worker_tx, &done_tx);
+        assert!(queue.entries().is_empty());
+        assert!(queue.push(proto(1)).is_err());
+
+        queue.complete(SectorId::from(1));
+        assert!(queue.push(proto(1)).is_ok());
+    }
+
+    #[test]
+    fn holds_sectors_back_until_abandoned_seals_exit() {
+        let (tx, _worker_rx) = mpsc::channel::<(_, WorkerTask<std::fs::File>)>();
+        let worker_tx = WorkerHandle::new(tx, Default::default());
+        let (done_tx, _done_rx) = mpsc::sync_channel(0);
+
+        let mut queue = SealQueue::default();
+
+        queue.push(proto(1)).unwrap();
+        queue.push(proto(2)).unwrap();
+        queue.dispatch(&worker_tx, &done_tx);
+
+        queue.abandon(SectorId::from(1));
+        assert!(queue.push(proto(1)).is_err());
+
+        queue.abandoned_seal_exited(SectorId::from(1));
+        assert!(queue.push(proto(1)).is_ok());
+
+        // the exit may be reported before the abandoned seal's result
+        queue.abandoned_seal_exited(SectorId::from(2));
+        queue.abandon(SectorId::from(2));
+        assert!(queue.push(proto(2)).is_ok());
+    }
+
+    #[test]
+    fn cancels_queued_and_dispatched_seals() {
+        let (tx, worker_rx) = mpsc::channel::<(_, WorkerTask<std::fs::File>)>();
+        let worker_tx = WorkerHandle::new(tx, Default::default());
+        let (done_tx, _done_rx) = mpsc::sync_channel(0);
+
+        let mut queue = SealQueue::default();
+
+        // room for one 1KiB sector at a time
+        queue.set_memory_watermark(Some(estimate_seal_memory(1024) + 1));
+
+        queue.push(proto(1)).unwrap();
+        queue.push(proto(2)).unwrap();
+        queue.dispatch(&worker_tx, &done_tx);
+
+        // a queued task is handed back
+        let cancelled = queue.cancel(SectorId::from(2)).unwrap();
+        assert_eq!(
+            Some(SectorId::from(2)),
+            cancelled.map(|proto| proto.sector_id)
+        );
+        assert!(queue.entries().is_empty());
+
+        // the worker sealing a dispatched task is told to abandon it
+        assert!(queue.cancel(SectorId::from(1)).unwrap().is_none());
+
+        match worker_rx.try_recv().unwrap().1 {
+            WorkerTask::Seal { cancel, .. } => assert!(cancel.is_cancelled()),
+            _ => panic!("expected a seal task"),
+        }
+
+        queue.abandon(SectorId::from(1));
+        assert!(queue.cancel(SectorId::from(1)).is_err());
+    }
+
+    #[test]
+    fn holds_tasks_back_above_the_memory_watermark() {
+        let (tx, worker_rx) = mpsc::channel::<(_, WorkerTask<std::fs::File>)>();
+        let worker_tx = WorkerHandle::new(tx, Default::default());
+        let (done_tx, _done_rx) = mpsc::sync_channel(0);
+
+        let mut queue = SealQueue::default();
+
+        // room for one 1KiB sector at a time
+        queue.set_memory_watermark(Some(estimate_seal_memory(1024) + 1));
+
+        queue.push(proto(1)).unwrap();
+        queue.push(proto(2)).unwrap();
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert_eq!(1, worker_rx.try_iter().count());
+        assert_eq!(vec![SectorId::from(2)], queued_sector_ids(&queue));
+
+        queue.complete(SectorId::from(1));
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert_eq!(1, worker_rx.try_iter().count());
+        assert!(queue.entries().is_empty());
+
+        // a task which exceeds the watermark on its own is still sealed once
+        // nothing else is
+        queue.complete(SectorId::from(2));
+        queue.set_memory_watermark(Some(1));
+        queue.push(proto(3)).unwrap();
+        queue.dispatch(&worker_tx, &done_tx);
+
+        assert_eq!(1, worker_rx.try_iter().count());
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::ops::Deref;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SectorManagerErr;
+
+/// The name of a sector-file within the directory which holds it, as
+/// provisioned by a SectorManager.
+///
+/// A sector access is joined onto a sector directory to produce the path of
+/// the sector-file, so it must name a file in that directory: it can't be
+/// empty, contain a path separator or NUL byte, or be "." or "..". Accesses
+/// provided by the host (e.g. through the FFI) must be validated with
+/// SectorAccess::new before they're used. Persisted accesses aren't validated
+/// again when they're loaded, as they were validated when they were
+/// provisioned.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SectorAccess(String);
+
+impl SectorAccess {
+    pub fn new<S: Into<String>>(access: S) -> Result<SectorAccess, SectorManagerErr> {
+        let access = access.into();
+
+        let reason = if access.is_empty() {
+            Some("it is empty")
+        } else if access == "." || access == ".." {
+            Some("it names a directory")
+        } else if access.contains(|c| c == '/' || c == '\\') {
+            Some("it contains a path separator")
+        } else if access.contains('\0') {
+            Some("it contains a NUL byte")
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Err(SectorManagerErr::CallerError(format!(
+                "invalid sector access {:?}: {}",
+                access, reason
+            ))),
+            None => Ok(SectorAccess(access)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for SectorAccess {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for SectorAccess {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl fmt::Display for SectorAccess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<SectorAccess> for String {
+    fn from(access: SectorAccess) -> String {
+        access.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sector_access_accepts_provisioned_names() {
+        for name in &[
+            "on-000000000000-0000000001",
+            "ip-192168001010-0000000001",
+            "s-t01000-1",
+        ] {
+            let access = SectorAccess::new(*name).unwrap();
+
+            assert_eq!(*name, access.as_str());
+            assert_eq!(
+                Path::new("/sealed").join(name),
+                Path::new("/sealed").join(&access)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sector_access_rejects_names_which_escape_the_sector_dir() {
+        for name in &[
+            "",
+            ".",
+            "..",
+            "../s-t01000-1",
+            "/etc/passwd",
+            "a/b",
+            "a\\b",
+            "a\0b",
+        ] {
+            assert!(SectorAccess::new(*name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn test_sector_access_serializes_as_a_string() {
+        let access = SectorAccess::new("s-t01000-1").unwrap();
+        let json = serde_json::to_string(&access).unwrap();
+
+        assert_eq!("\"s-t01000-1\"", json);
+        assert_eq!(access, serde_json::from_str::<SectorAccess>(&json).unwrap());
+    }
+}
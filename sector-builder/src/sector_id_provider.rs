@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::*;
+
+/// Allocates the ids of the staged sectors which are opened by add_piece.
+pub trait SectorIdProvider: Send {
+    /// Proposes the id of a newly opened staged sector. The provided nonce is
+    /// the highest sector id known to the SectorBuilder. The id isn't taken
+    /// until sector_id_allocated is called with it, so an id whose sector
+    /// couldn't be provisioned is proposed again.
+    fn next_sector_id(&mut self, sector_id_nonce: u64) -> Result<SectorId>;
+
+    /// Called once the staged sector with the proposed id has been
+    /// provisioned.
+    fn sector_id_allocated(&mut self, _sector_id: SectorId) {}
+}
+
+/// Allocates sector ids by incrementing the highest sector id known to the
+/// SectorBuilder. This is the default.
+#[derive(Clone, Debug, Default)]
+pub struct MonotonicSectorIds;
+
+impl SectorIdProvider for MonotonicSectorIds {
+    fn next_sector_id(&mut self, sector_id_nonce: u64) -> Result<SectorId> {
+        Ok(SectorId::from(sector_id_nonce + 1))
+    }
+}
+
+/// Hands out sector ids which have been allocated elsewhere, e.g. by the
+/// chain, in the order in which they were supplied. Clones share their ids,
+/// so a clone kept by the caller can supply ids to the one handed to the
+/// SectorBuilder.
+#[derive(Clone, Debug, Default)]
+pub struct SuppliedSectorIds(Arc<Mutex<VecDeque<SectorId>>>);
+
+impl SuppliedSectorIds {
+    pub fn new() -> SuppliedSectorIds {
+        Default::default()
+    }
+
+    /// Makes the provided ids available for allocation.
+    pub fn supply(&self, sector_ids: impl IntoIterator<Item = SectorId>) {
+        self.0
+            .lock()
+            .expect("sector id queue poisoned")
+            .extend(sector_ids);
+    }
+
+    /// The number of supplied ids which have not yet been allocated.
+    pub fn remaining(&self) -> usize {
+        self.0.lock().expect("sector id queue poisoned").len()
+    }
+}
+
+impl SectorIdProvider for SuppliedSectorIds {
+    fn next_sector_id(&mut self, _sector_id_nonce: u64) -> Result<SectorId> {
+        self.0
+            .lock()
+            .expect("sector id queue poisoned")
+            .front()
+            .cloned()
+            .ok_or_else(|| err_unrecov("no sector ids have been supplied").into())
+    }
+
+    fn sector_id_allocated(&mut self, sector_id: SectorId) {
+        let mut sector_ids = self.0.lock().expect("sector id queue poisoned");
+
+        if sector_ids.front() == Some(&sector_id) {
+            sector_ids.pop_front();
+        }
+    }
+}
+
+/// Allocates sector ids using the provided function.
+impl<F: FnMut(u64) -> Result<SectorId> + Send> SectorIdProvider for F {
+    fn next_sector_id(&mut self, sector_id_nonce: u64) -> Result<SectorId> {
+        self(sector_id_nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic_sector_ids() {
+        let mut provider = MonotonicSectorIds;
+
+        assert_eq!(SectorId::from(1), provider.next_sector_id(0).unwrap());
+        assert_eq!(SectorId::from(43), provider.next_sector_id(42).unwrap());
+    }
+
+    #[test]
+    fn test_supplied_sector_ids() {
+        let supplier = SuppliedSectorIds::new();
+        let mut provider = supplier.clone();
+
+        assert!(provider.next_sector_id(0).is_err());
+
+        supplier.supply(vec![SectorId::from(7), SectorId::from(3)]);
+
+        assert_eq!(2, supplier.remaining());
+
+        // an id isn't taken until its sector has been provisioned
+        assert_eq!(SectorId::from(7), provider.next_sector_id(0).unwrap());
+        assert_eq!(SectorId::from(7), provider.next_sector_id(0).unwrap());
+
+        provider.sector_id_allocated(SectorId::from(7));
+
+        assert_eq!(SectorId::from(3), provider.next_sector_id(7).unwrap());
+
+        provider.sector_id_allocated(SectorId::from(3));
+
+        assert!(provider.next_sector_id(7).is_err());
+    }
+}
@@ -1,22 +1,52 @@
 use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet, BTreeMap};
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
 
-use filecoin_proofs::{SectorClass, UnpaddedBytesAmount, SealOutput, PrivateReplicaInfo};
-use filecoin_proofs::pieces::get_piece_start_byte;
+use filecoin_proofs::error::ExpectWithBacktrace;
+
+use filecoin_proofs::{SectorClass, UnpaddedBytesAmount, UnpaddedByteIndex, SealOutput, PrivateReplicaInfo, PoStConfig};
+use filecoin_proofs::pieces::{get_piece_start_byte, sum_piece_bytes_with_alignment};
 use storage_proofs::sector::SectorId;
 use storage_proofs::rational_post;
 
 use crate::builder::*;
-use crate::error::{Result, err_unrecov, err_piecenotfound};
-use crate::{StagedSectorMetadata, SimpleSectorStore, SealedSectorMetadata, SealStatus, PieceMetadata};
+use crate::error::{Result, err_unrecov, err_piecenotfound, err_unknown_store};
+use crate::{StagedSectorMetadata, SimpleSectorStore, SealedSectorMetadata, SealedSectorLocation, SealStatus, PieceMetadata, AutoSealPolicy, PoStOptions, SecondsSinceEpoch, SealTicket, SealTiming, UnsealedRetention};
+use crate::events::EventBus;
 use crate::helpers;
-use crate::state::StagedState;
+use crate::sector_access::SectorAccess;
+use crate::state::{transition_seal_status, StagedState};
 use crate::worker::{UnsealTaskPrototype, SealTaskPrototype};
 use crate::disk_backed_storage::{new_simple_sector_store, SimpleConcreteSectorStore};
 
+const FATAL_NOLOCK: &str = "error acquiring sealed stores lock";
+const FATAL_NOUNSL: &str = "error acquiring unsealed files lock";
+
+// Stores the sectors of each miner in a subdirectory, named after the miner,
+// of the sealed and staged sector directories. Miners are validated before
+// they're used to build paths (see helpers::validate_miner).
+//
+// A SimpleSectorBuilder may be called from many threads at once. Sector
+// metadata is owned by the caller, so the only state shared between calls is
+// the set of registered sealed stores, the retention of unsealed pieces and
+// the files into which pieces have been unsealed, each of which is guarded
+// by a lock. Calls touching the same sector must still be serialized by the
+// caller.
 pub struct SimpleSectorBuilder {
     pub sector_store: SimpleConcreteSectorStore,
-    pub max_num_staged_sectors: u8,
+    pub max_num_staged_sectors: u64,
+    pub sealed_stores: RwLock<HashMap<String, PathBuf>>,
+    pub unsealed_retention: RwLock<UnsealedRetention>,
+    unsealed_files: Mutex<HashMap<(String, SectorId), UnsealedFile>>,
+}
+
+// A file into which (a range of) a miner's sector is unsealed.
+struct UnsealedFile {
+    provisioned_at: Instant,
+    // the start and length of the sector's unsealed bytes which the file
+    // holds, unknown until the SimpleSectorBuilder has unsealed them itself
+    range: Option<(u64, u64)>,
 }
 
 impl SimpleSectorBuilder {
@@ -24,7 +54,7 @@ impl SimpleSectorBuilder {
         sector_class: SectorClass,
         sealed_sector_dir: impl AsRef<Path>,
         staged_sector_dir: impl AsRef<Path>,
-        max_num_staged_sectors: u8,
+        max_num_staged_sectors: u64,
     ) -> Result<SimpleSectorBuilder> {
         ensure_parameter_cache_hydrated(sector_class)?;
 
@@ -33,6 +63,9 @@ impl SimpleSectorBuilder {
         Ok(SimpleSectorBuilder {
             sector_store,
             max_num_staged_sectors,
+            sealed_stores: Default::default(),
+            unsealed_retention: Default::default(),
+            unsealed_files: Default::default(),
         })
     }
 
@@ -43,9 +76,11 @@ impl SimpleSectorBuilder {
         piece_bytes_amount: u64,
         new_sector_id: SectorId,
     ) -> Result<SectorId> {
+        helpers::validate_miner(&miner)?;
+
         let mut staged = StagedState {
             sector_id_nonce: u64::from(new_sector_id) - 1, // it will be added 1 later
-            sectors: staged_sectors,
+            sectors: staged_sectors.into_iter().collect(),
         };
 
         helpers::add_piece_first(
@@ -64,6 +99,8 @@ impl SimpleSectorBuilder {
         piece_file: impl std::io::Read,
         piece_bytes_amount: u64,
     ) -> Result<StagedSectorMetadata> {
+        helpers::validate_miner(&miner)?;
+
         helpers::add_piece_second(
             &self.sector_store,
             &miner,
@@ -74,6 +111,25 @@ impl SimpleSectorBuilder {
         )
     }
 
+    // Writes several pieces into the provided staged sector in one call,
+    // returning its metadata once, with all of the pieces added. The pieces
+    // must all fit into the sector; if any can't be written, none are added.
+    pub fn add_pieces_second(
+        &self,
+        miner: String,
+        staged_sector: StagedSectorMetadata,
+        pieces: Vec<(String, impl std::io::Read, u64)>,
+    ) -> Result<StagedSectorMetadata> {
+        helpers::validate_miner(&miner)?;
+
+        helpers::add_pieces_second(&self.sector_store, &miner, staged_sector, pieces)
+    }
+
+    // Reads a piece from the provided sealed sector. If an unsealed copy of
+    // the sector which holds the piece has been kept, the piece is read from
+    // the copy instead of being unsealed again. While unsealed files are
+    // kept, all of the sector's pieces are unsealed at once, so that the
+    // sector's other pieces can be read from the copy as well.
     pub fn read_piece_from_sealed_sector(
         &self,
         miner: String,
@@ -81,29 +137,207 @@ impl SimpleSectorBuilder {
         piece_key: String,
         prover_id: [u8; 31],
     ) -> Result<Vec<u8>> {
-        let proto = self.create_retrieve_piece_task_proto(&miner, sealed_sector, piece_key)?;
-        let result = filecoin_proofs::get_unsealed_range(
+        helpers::validate_miner(&miner)?;
+
+        let (start, len) = piece_range(sealed_sector, &piece_key)?;
+
+        self.purge_expired_unsealed()?;
+
+        let retention = *self.unsealed_retention.read().expects(FATAL_NOUNSL);
+
+        let bytes = match self.read_unsealed_copy(&miner, sealed_sector.sector_id, start, len) {
+            Some(bytes) => Ok(bytes),
+            None => {
+                let (first, num_bytes) = match retention {
+                    UnsealedRetention::DeleteAfterRead => (start, len),
+                    UnsealedRetention::KeepFor(_) => {
+                        let piece_lengths: Vec<_> =
+                            sealed_sector.pieces.iter().map(|p| p.num_bytes).collect();
+
+                        (0, u64::from(sum_piece_bytes_with_alignment(&piece_lengths)))
+                    }
+                };
+
+                self.unseal_and_read(
+                    &miner,
+                    sealed_sector,
+                    piece_key,
+                    (first, num_bytes),
+                    start - first,
+                    len,
+                    prover_id,
+                )
+            }
+        };
+
+        if retention == UnsealedRetention::DeleteAfterRead {
+            if let Err(err) = self.purge_unsealed(&miner, sealed_sector.sector_id) {
+                warn!("failed to delete unsealed sector {}: {:?}", sealed_sector.sector_id, err);
+            }
+        }
+
+        bytes
+    }
+
+    // Sets how long the files into which pieces are unsealed are kept. Files
+    // which are to be kept for a while are deleted by purge_expired_unsealed,
+    // which is also done whenever a piece is to be unsealed.
+    pub fn set_unsealed_retention(&self, retention: UnsealedRetention) {
+        *self.unsealed_retention.write().expects(FATAL_NOUNSL) = retention;
+    }
+
+    // Reads the provided range of a sector's unsealed bytes from the file
+    // into which they were unsealed, if it holds them.
+    fn read_unsealed_copy(
+        &self,
+        miner: &str,
+        sector_id: SectorId,
+        start: u64,
+        len: u64,
+    ) -> Option<Vec<u8>> {
+        let (first, num_bytes) = self
+            .unsealed_files
+            .lock()
+            .expects(FATAL_NOUNSL)
+            .get(&(miner.to_string(), sector_id))?
+            .range?;
+
+        if start < first || start + len > first + num_bytes {
+            return None;
+        }
+
+        let access = self.unsealed_access(miner, sector_id).ok()?;
+
+        self.sector_store
+            .manager()
+            .read_raw(miner, &access, start - first, UnpaddedBytesAmount(len))
+            .ok()
+    }
+
+    // Unseals the provided range (start and length) of the sealed sector,
+    // which includes the referenced piece, and reads len bytes at the
+    // provided offset within the range from the unsealed bytes.
+    #[allow(clippy::too_many_arguments)]
+    fn unseal_and_read(
+        &self,
+        miner: &str,
+        sealed_sector: &SealedSectorMetadata,
+        piece_key: String,
+        range: (u64, u64),
+        offset: u64,
+        len: u64,
+        prover_id: [u8; 31],
+    ) -> Result<Vec<u8>> {
+        let proto = self.create_retrieve_piece_task_proto(miner, sealed_sector, piece_key)?;
+
+        filecoin_proofs::get_unsealed_range(
             proto.porep_config,
             &proto.source_path,
             &proto.destination_path,
             &prover_id,
             proto.sector_id,
-            proto.piece_start_byte,
-            proto.piece_len,
-        )
-            .map(|num_bytes_unsealed| (num_bytes_unsealed, proto.destination_path));
+            UnpaddedByteIndex(range.0),
+            UnpaddedBytesAmount(range.1),
+        )?;
+
+        if let Some(file) = self
+            .unsealed_files
+            .lock()
+            .expects(FATAL_NOUNSL)
+            .get_mut(&(miner.to_string(), sealed_sector.sector_id))
+        {
+            file.range = Some(range);
+        }
+
+        let access = self.unsealed_access(miner, sealed_sector.sector_id)?;
+
+        let buffer = self
+            .sector_store
+            .manager()
+            .read_raw(miner, &access, offset, UnpaddedBytesAmount(len))?;
+
+        Ok(buffer)
+    }
+
+    // Produces the access of the file into which the provided miner's sector
+    // is unsealed.
+    fn unsealed_access(&self, miner: &str, sector_id: SectorId) -> Result<SectorAccess> {
+        let access = self
+            .sector_store
+            .manager()
+            .new_staging_sector_access(miner, sector_id, false)?;
+
+        Ok(access)
+    }
+
+    // Deletes the file into which the provided miner's sector was unsealed,
+    // if there is one. Hosts which unseal pieces themselves, using
+    // create_retrieve_piece_task_proto, call this once they've read them.
+    pub fn purge_unsealed(&self, miner: &str, sector_id: SectorId) -> Result<()> {
+        helpers::validate_miner(miner)?;
+
+        self.unsealed_files
+            .lock()
+            .expects(FATAL_NOUNSL)
+            .remove(&(miner.to_string(), sector_id));
+
+        let mgr = self.sector_store.manager();
+        let access = self.unsealed_access(miner, sector_id)?;
+
+        if mgr.staged_sector_path(miner, &access).exists() {
+            mgr.delete_staging_sector_access(miner, &access)?;
+        }
+
+        Ok(())
+    }
 
-        self.read_unsealed_bytes_from(&miner, result)
+    // Deletes the files into which pieces were unsealed which have been kept
+    // for longer than the retention allows. Files provisioned before the
+    // SimpleSectorBuilder was (re)initialized aren't known to it and must be
+    // deleted with purge_unsealed.
+    pub fn purge_expired_unsealed(&self) -> Result<()> {
+        let keep_for = match *self.unsealed_retention.read().expects(FATAL_NOUNSL) {
+            UnsealedRetention::KeepFor(keep_for) => keep_for,
+            UnsealedRetention::DeleteAfterRead => return Ok(()),
+        };
+
+        let expired: Vec<(String, SectorId)> = self
+            .unsealed_files
+            .lock()
+            .expects(FATAL_NOUNSL)
+            .iter()
+            .filter(|(_, file)| file.provisioned_at.elapsed() > keep_for)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for (miner, sector_id) in expired {
+            self.purge_unsealed(&miner, sector_id)?;
+        }
+
+        Ok(())
     }
 
     pub fn seal_staged_sector(
         &self,
         miner: String,
         staged_sector: &mut StagedSectorMetadata,
+        seal_ticket: SealTicket,
         prover_id: [u8; 31],
     ) -> Result<SealedSectorMetadata> {
+        helpers::validate_miner(&miner)?;
+
         let proto = self.create_seal_task_proto(&miner, staged_sector)?;
 
+        // the ticket is carried into the sealed sector's metadata
+        staged_sector.seal_ticket = Some(seal_ticket);
+
+        // mutate staged sector state such that we don't try to write any
+        // more pieces to it
+        transition_seal_status(staged_sector, SealStatus::Sealing)?;
+
+        let started_at = SecondsSinceEpoch::now();
+        let started = Instant::now();
+
         let result = filecoin_proofs::seal(
             proto.porep_config,
             &proto.staged_sector_path,
@@ -124,12 +358,28 @@ impl SimpleSectorBuilder {
                     piece_inclusion_proofs,
                 } = output;
 
-                // generate checksum
-                let blake2b_checksum =
-                    helpers::calculate_checksum(&proto.sealed_sector_path)?.as_ref().to_vec();
+                let finished_at = SecondsSinceEpoch::now();
+                let duration = started.elapsed();
 
-                // get number of bytes in sealed sector-file
-                let len = std::fs::metadata(&proto.sealed_sector_path)?.len();
+                // checksum the sealed sector-file and its extents in one pass
+                let helpers::ReplicaChecksums {
+                    checksum: blake2b_checksum,
+                    algo: checksum_algo,
+                    extent_checksums,
+                    len,
+                    duration: checksum_duration,
+                } = helpers::calculate_replica_checksums(
+                    &proto.sealed_sector_path,
+                    proto.checksum_algo,
+                )?;
+
+                let seal_timing = SealTiming {
+                    started_at,
+                    finished_at,
+                    duration,
+                    checksum_duration,
+                    hostname: helpers::local_hostname(),
+                };
 
                 // combine the piece commitment, piece inclusion proof, and other piece
                 // metadata into a single struct (to be persisted to metadata store)
@@ -144,6 +394,10 @@ impl SimpleSectorBuilder {
                         num_bytes: piece.num_bytes,
                         comm_p: Some(comm_p),
                         piece_inclusion_proof: Some(piece_inclusion_proof.into()),
+                        idempotency_key: piece.idempotency_key,
+                        store_until: piece.store_until,
+                        deal_id: piece.deal_id,
+                        labels: piece.labels,
                     })
                     .collect();
 
@@ -157,6 +411,13 @@ impl SimpleSectorBuilder {
                     proof,
                     blake2b_checksum,
                     len,
+                    location: SealedSectorLocation::Default,
+                    seal_ticket: staged_sector.seal_ticket,
+                    checksum_algo,
+                    extent_checksums,
+                    seal_timing: Some(seal_timing),
+                    seal_verified_at: None,
+                    replication: None,
                 };
 
                 Ok(meta)
@@ -166,42 +427,68 @@ impl SimpleSectorBuilder {
             })
     }
 
+    // Derives the challenges of a proof-of-spacetime over the provided sealed
+    // sectors. If a PoSt configuration is provided, it's used instead of the
+    // one implied by the sector class, and must be passed to
+    // generate_post_second as well.
     pub fn generate_post_first(
         &self,
         challenge_seed: &[u8; 32],
         faults: Vec<SectorId>,
         sealed_sectors: &HashMap<SectorId, SealedSectorMetadata>, // sealed sectors that have been committed
+        post_config: Option<PoStConfig>,
     ) -> Result<Vec<rational_post::Challenge>> {
         let sectors = sealed_sectors.iter().map(|(sector_id, _)| *sector_id).collect();
         let faults = faults.iter().map(|sector_id| *sector_id).collect();
 
         filecoin_proofs::generate_post_first(
-            self.sector_store.proofs_config().post_config(),
+            post_config.unwrap_or_else(|| self.sector_store.proofs_config().post_config()),
             challenge_seed,
             sectors,
             faults,
         )
     }
 
+    // Proves the challenged sectors. If a deadline is provided, proving is
+    // abandoned with a PoStDeadlineMissed error once it has passed. Marking
+    // slow sectors faulty instead would change the challenges, which are
+    // derived from the faults passed to generate_post_first, so a deadline
+    // which asks for it is rejected.
+    //
+    // The replicas of sectors with an entry in replica_paths are read from
+    // the provided path instead of their sealed sector-file, e.g. from a
+    // temporarily mounted archive volume.
+    //
+    // The PoSt configuration must be the one passed to generate_post_first.
+    // Proving is abandoned if the cancellation token is cancelled.
     pub fn generate_post_second(
         &self,
         miner: String,
         challenges: &Vec<rational_post::Challenge>,
         faults: Vec<SectorId>,
         sealed_sectors: &HashMap<SectorId, SealedSectorMetadata>, // sealed sectors that have been committed
+        options: PoStOptions,
     ) -> Result<Vec<u8>> {
+        let PoStOptions { deadline, replica_paths, post_config, cancel } = options;
+
+        helpers::validate_miner(&miner)?;
+        helpers::validate_replica_paths(&replica_paths)?;
+
+        if deadline.as_ref().map_or(false, |deadline| deadline.mark_slowest_faulty) {
+            return Err(err_unrecov("the challenges are fixed, so slow sectors can't be marked faulty").into());
+        }
+
         let fault_set: HashSet<SectorId> = faults.clone().into_iter().collect();
 
         let mut replicas: BTreeMap<SectorId, PrivateReplicaInfo> = Default::default();
 
         for sector in sealed_sectors.values() {
-            let path_str = self
-                .sector_store
-                .manager()
-                .sealed_sector_path(&miner, &sector.sector_access)
-                .to_str()
-                .map(str::to_string)
-                .unwrap();
+            let path = match replica_paths.get(&sector.sector_id) {
+                Some(path) => path.clone(),
+                None => self.sealed_sector_path(&miner, sector)?,
+            };
+
+            let path_str = helpers::replica_path_str(sector.sector_id, &path)?;
 
             let info = if fault_set.contains(&sector.sector_id) {
                 PrivateReplicaInfo::new_faulty(path_str, sector.comm_r)
@@ -212,22 +499,67 @@ impl SimpleSectorBuilder {
             replicas.insert(sector.sector_id, info);
         }
 
-        filecoin_proofs::generate_post_second(
-            self.sector_store.proofs_config().post_config(),
-            challenges,
-            &replicas,
-            faults,
+        let post_config =
+            post_config.unwrap_or_else(|| self.sector_store.proofs_config().post_config());
+        let challenges = challenges.clone();
+
+        helpers::prove_before_deadline(
+            deadline.as_ref().map(|deadline| &deadline.deadline),
+            &SecondsSinceEpoch::now(),
+            None,
+            cancel.as_ref(),
+            &EventBus::default(),
+            move || filecoin_proofs::generate_post_second(post_config, &challenges, &replicas, faults),
         )
     }
 
+    // Registers a directory in which sealed sectors can be stored under the
+    // provided store id. Each miner's sectors live in their own subdirectory
+    // of the store.
+    pub fn register_sealed_store(
+        &self,
+        store_id: String,
+        dir: impl AsRef<Path>,
+    ) -> Result<()> {
+        std::fs::create_dir_all(&dir)?;
+        self.sealed_stores
+            .write()
+            .expects(FATAL_NOLOCK)
+            .insert(store_id, dir.as_ref().to_path_buf());
+
+        Ok(())
+    }
+
+    pub fn move_sealed_sector(
+        &self,
+        miner: String,
+        sealed_sector: &SealedSectorMetadata,
+        target: SealedSectorLocation,
+    ) -> Result<SealedSectorMetadata> {
+        helpers::validate_miner(&miner)?;
+
+        let source_path = self.sealed_sector_path(&miner, sealed_sector)?;
+        let target_path = self.resolve_sealed_sector_path(&miner, &target, &sealed_sector.sector_access)?;
+
+        helpers::move_replica(&source_path, &target_path)?;
+
+        Ok(SealedSectorMetadata {
+            location: target,
+            ..sealed_sector.clone()
+        })
+    }
+
+    // Determines which of the provided staged sectors are to be sealed. Sectors
+    // which aren't full yet are sealed according to the provided policy.
     pub fn get_sectors_ready_for_sealing(
         &self,
         staged_sectors: HashMap<SectorId, StagedSectorMetadata>,
+        auto_seal_policy: &AutoSealPolicy,
         seal_all_staged_sectors: bool,
     ) -> Vec<SectorId> {
         let staged = StagedState {
             sector_id_nonce: 0, // unused
-            sectors: staged_sectors,
+            sectors: staged_sectors.into_iter().collect(),
         };
 
         let max_user_bytes_per_staged_sector =
@@ -237,16 +569,24 @@ impl SimpleSectorBuilder {
             &staged,
             max_user_bytes_per_staged_sector,
             self.max_num_staged_sectors,
+            auto_seal_policy,
+            &SecondsSinceEpoch::now(),
             seal_all_staged_sectors,
         )
     }
 
-    fn create_retrieve_piece_task_proto(
+    // Plans the retrieval of the referenced piece from the provided sealed
+    // sector without executing it. The staged sector access into which the
+    // piece would be unsealed is provisioned, and kept according to the
+    // unsealed retention.
+    pub fn create_retrieve_piece_task_proto(
         &self,
         miner: &str,
         sealed_sector: &SealedSectorMetadata,
         piece_key: String,
     ) -> Result<UnsealTaskPrototype> {
+        helpers::validate_miner(miner)?;
+
         let piece = sealed_sector
             .pieces
             .iter()
@@ -260,18 +600,25 @@ impl SimpleSectorBuilder {
             .map(|p| p.num_bytes)
             .collect();
 
+        self.purge_expired_unsealed()?;
+
         let staged_sector_access = self
             .sector_store
             .manager()
             .new_staging_sector_access(miner, sealed_sector.sector_id, true)
             .map_err(failure::Error::from)?;
 
+        self.unsealed_files.lock().expects(FATAL_NOUNSL).insert(
+            (miner.to_string(), sealed_sector.sector_id),
+            UnsealedFile {
+                provisioned_at: Instant::now(),
+                range: None,
+            },
+        );
+
         Ok(UnsealTaskPrototype {
             porep_config: self.sector_store.proofs_config().porep_config(),
-            source_path: self
-                .sector_store
-                .manager()
-                .sealed_sector_path(miner, &sealed_sector.sector_access),
+            source_path: self.sealed_sector_path(miner, sealed_sector)?,
             destination_path: self
                 .sector_store
                 .manager()
@@ -279,32 +626,53 @@ impl SimpleSectorBuilder {
             sector_id: sealed_sector.sector_id,
             piece_start_byte: get_piece_start_byte(&piece_lengths, piece.num_bytes),
             piece_len: piece.num_bytes,
+            timeout: None,
         })
     }
 
-    fn read_unsealed_bytes_from(
+    // Produces the path to the replica of the provided sealed sector, taking
+    // into account that the replica may have been moved out of the miner's
+    // sealed sector directory.
+    fn sealed_sector_path(
         &self,
         miner: &str,
-        result: Result<(UnpaddedBytesAmount, PathBuf)>,
-    ) -> Result<Vec<u8>> {
-        result.and_then(|(n, pbuf)| {
-            let buffer = self.sector_store.manager().read_raw(
-                miner,
-                pbuf.to_str()
-                    .ok_or_else(|| format_err!("conversion failed"))?,
-                0,
-                n,
-            )?;
-
-            Ok(buffer)
-        })
+        sealed_sector: &SealedSectorMetadata,
+    ) -> Result<PathBuf> {
+        self.resolve_sealed_sector_path(miner, &sealed_sector.location, &sealed_sector.sector_access)
     }
 
-    fn create_seal_task_proto(
+    fn resolve_sealed_sector_path(
         &self,
         miner: &str,
-        staged_sector: &mut StagedSectorMetadata,
+        location: &SealedSectorLocation,
+        sector_access: &SectorAccess,
+    ) -> Result<PathBuf> {
+        match location {
+            SealedSectorLocation::Default => Ok(self
+                .sector_store
+                .manager()
+                .sealed_sector_path(miner, sector_access)),
+            SealedSectorLocation::Store(store_id) => self
+                .sealed_stores
+                .read()
+                .expects(FATAL_NOLOCK)
+                .get(store_id)
+                .map(|dir| dir.join(miner).join(sector_access))
+                .ok_or_else(|| err_unknown_store(store_id.clone()).into()),
+            SealedSectorLocation::Dir(dir) => Ok(dir.join(sector_access)),
+        }
+    }
+
+    // Plans sealing the provided staged sector without executing it. The
+    // sealed sector access into which the sector would be sealed is
+    // provisioned.
+    pub fn create_seal_task_proto(
+        &self,
+        miner: &str,
+        staged_sector: &StagedSectorMetadata,
     ) -> Result<SealTaskPrototype> {
+        helpers::validate_miner(miner)?;
+
         let sealed_sector_access = self
             .sector_store
             .manager()
@@ -327,17 +695,39 @@ impl SimpleSectorBuilder {
             .map(|p| p.num_bytes)
             .collect::<Vec<UnpaddedBytesAmount>>();
 
-        // mutate staged sector state such that we don't try to write any
-        // more pieces to it
-        staged_sector.seal_status = SealStatus::Sealing;
-
         Ok(SealTaskPrototype {
             piece_lens,
             porep_config: self.sector_store.proofs_config().porep_config(),
             sealed_sector_access,
             sealed_sector_path,
+            seal_ticket: staged_sector.seal_ticket,
             sector_id: staged_sector.sector_id,
             staged_sector_path,
+            timeout: None,
+            isolation: Default::default(),
+            staged_data_key: None,
+            piece_manifest: Vec::new(),
+            checksum_algo: Default::default(),
         })
     }
 }
+
+// Returns the start and length of the referenced piece within the provided
+// sealed sector's unsealed bytes.
+fn piece_range(sealed_sector: &SealedSectorMetadata, piece_key: &str) -> Result<(u64, u64)> {
+    let position = sealed_sector
+        .pieces
+        .iter()
+        .position(|p| p.piece_key == piece_key)
+        .ok_or_else(|| err_piecenotfound(piece_key.to_string()))?;
+
+    let piece_lengths: Vec<_> = sealed_sector.pieces[..position]
+        .iter()
+        .map(|p| p.num_bytes)
+        .collect();
+
+    let num_bytes = sealed_sector.pieces[position].num_bytes;
+    let start = get_piece_start_byte(&piece_lengths, num_bytes);
+
+    Ok((u64::from(start), u64::from(num_bytes)))
+}
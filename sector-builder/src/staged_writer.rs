@@ -0,0 +1,433 @@
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+// Default number of preprocessed bytes collected before they're written.
+pub const DEFAULT_STAGED_WRITE_BUFFER_SIZE: usize = 1 << 20;
+
+// Alignment of the offsets, lengths and memory of direct writes.
+const DIRECT_ALIGN: usize = 4096;
+
+/// How the preprocessed bytes of pieces are written to staged sector-files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StagedWriteStrategy {
+    /// seek to each buffer's offset, then write it through the page cache
+    Seek,
+    /// write each buffer at its offset (pwrite), through the page cache
+    Positional,
+    /// write each buffer at its offset, bypassing the page cache (O_DIRECT),
+    /// which keeps pieces from evicting the pages of running seals. Direct
+    /// writes are only supported on Linux and by some filesystems; positional
+    /// writes are used where they aren't.
+    Direct,
+}
+
+/// How pieces are written to staged sector-files. Preprocessing a piece
+/// produces many small writes, which are collected in a buffer and written
+/// out at once, so that fast (e.g. NVMe) devices are kept busy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StagedWriteConfig {
+    /// number of bytes read from a piece, and of preprocessed bytes written
+    /// to the staged sector-file, at once; rounded up to a multiple of 4KiB
+    /// for direct writes
+    pub buffer_size: usize,
+    pub strategy: StagedWriteStrategy,
+}
+
+impl Default for StagedWriteConfig {
+    fn default() -> Self {
+        StagedWriteConfig {
+            buffer_size: DEFAULT_STAGED_WRITE_BUFFER_SIZE,
+            strategy: StagedWriteStrategy::Seek,
+        }
+    }
+}
+
+// A buffer whose first byte is aligned for direct writes.
+struct AlignedBuf {
+    storage: Vec<u8>,
+    start: usize,
+    capacity: usize,
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn new(capacity: usize) -> AlignedBuf {
+        let storage = vec![0; capacity + DIRECT_ALIGN];
+        let start = storage.as_ptr().align_offset(DIRECT_ALIGN);
+
+        AlignedBuf {
+            storage,
+            start,
+            capacity,
+            len: 0,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.storage[self.start..self.start + self.len]
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.storage[self.start..self.start + self.capacity]
+    }
+}
+
+/// Collects the writes made to a (plain) staged sector-file while a piece is
+/// preprocessed and writes them out, as configured, once the buffer is full.
+/// Preprocessing seeks back to complete the last byte it wrote, so bytes are
+/// read back from the buffer, and may be overwritten in it, as long as it
+/// holds them.
+///
+/// Buffered bytes are written out when the writer is flushed or dropped, but
+/// only a flush reports errors.
+pub struct StagedWriter {
+    file: File,
+    // the file, opened for direct writes, if they're used
+    direct: Option<File>,
+    strategy: StagedWriteStrategy,
+    // the buffered bytes, which belong at `offset` in the file; the offset is
+    // aligned for direct writes
+    buf: AlignedBuf,
+    offset: u64,
+    pos: u64,
+    len: u64,
+}
+
+impl StagedWriter {
+    pub fn new(
+        file: File,
+        path: impl AsRef<Path>,
+        config: &StagedWriteConfig,
+    ) -> io::Result<StagedWriter> {
+        let len = file.metadata()?.len();
+
+        let direct = match config.strategy {
+            StagedWriteStrategy::Direct => match open_direct(path.as_ref()) {
+                Ok(direct) => Some(direct),
+                Err(err) => {
+                    warn!(
+                        "can't write {:?} directly, writing it positionally instead: {}",
+                        path.as_ref(),
+                        err
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        let strategy = match (config.strategy, &direct) {
+            (StagedWriteStrategy::Direct, None) => StagedWriteStrategy::Positional,
+            (strategy, _) => strategy,
+        };
+
+        let capacity = match strategy {
+            StagedWriteStrategy::Direct => {
+                let blocks = (cmp::max(config.buffer_size, 1) + DIRECT_ALIGN - 1) / DIRECT_ALIGN;
+                blocks * DIRECT_ALIGN
+            }
+            _ => cmp::max(config.buffer_size, 1),
+        };
+
+        Ok(StagedWriter {
+            file,
+            direct,
+            strategy,
+            buf: AlignedBuf::new(capacity),
+            offset: 0,
+            pos: 0,
+            len,
+        })
+    }
+
+    // Starts buffering at the current position. A direct write must start at
+    // an aligned offset, so the bytes between the offset and the position are
+    // read into the buffer and written again along with it.
+    fn start_buffer(&mut self) -> io::Result<()> {
+        self.buf.len = 0;
+        self.offset = self.pos;
+
+        if self.direct.is_some() {
+            self.offset = self.pos - self.pos % DIRECT_ALIGN as u64;
+
+            let head = (self.pos - self.offset) as usize;
+            let n = read_at_most(&self.file, &mut self.buf.bytes_mut()[..head], self.offset)?;
+
+            for byte in &mut self.buf.bytes_mut()[n..head] {
+                *byte = 0;
+            }
+
+            self.buf.len = head;
+        }
+
+        Ok(())
+    }
+
+    // Writes the buffered bytes to the file. Unless all of them are written,
+    // the unaligned tail of a direct write stays in the buffer until it's
+    // been completed.
+    fn write_out(&mut self, all: bool) -> io::Result<()> {
+        let len = self.buf.len;
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        let mut written = 0;
+
+        if let Some(direct) = &self.direct {
+            let aligned = len - len % DIRECT_ALIGN;
+
+            if aligned > 0 {
+                match direct.write_all_at(&self.buf.bytes()[..aligned], self.offset) {
+                    Ok(()) => written = aligned,
+                    Err(ref err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                        warn!(
+                            "direct writes aren't supported, writing positionally instead: {}",
+                            err
+                        );
+                        self.direct = None;
+                        self.strategy = StagedWriteStrategy::Positional;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        if self.direct.is_some() && !all {
+            self.buf.bytes_mut().copy_within(written..len, 0);
+            self.buf.len = len - written;
+            self.offset += written as u64;
+
+            return Ok(());
+        }
+
+        let at = self.offset + written as u64;
+        let rest = &self.buf.bytes()[written..];
+
+        match self.strategy {
+            StagedWriteStrategy::Seek => {
+                self.file.seek(SeekFrom::Start(at))?;
+                self.file.write_all(rest)?;
+            }
+            _ => self.file.write_all_at(rest, at)?,
+        }
+
+        self.buf.len = 0;
+        self.offset += len as u64;
+
+        Ok(())
+    }
+
+    // Whether a write at the current position extends or overwrites the
+    // buffered bytes.
+    fn buffers_pos(&self) -> bool {
+        self.buf.len > 0 && self.pos >= self.offset && self.pos <= self.offset + self.buf.len as u64
+    }
+}
+
+impl Write for StagedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        if !self.buffers_pos() {
+            self.write_out(true)?;
+            self.start_buffer()?;
+        }
+
+        if (self.pos - self.offset) as usize == self.buf.capacity {
+            self.write_out(false)?;
+
+            if self.buf.len == 0 {
+                self.start_buffer()?;
+            }
+        }
+
+        let at = (self.pos - self.offset) as usize;
+        let n = cmp::min(data.len(), self.buf.capacity - at);
+
+        self.buf.bytes_mut()[at..at + n].copy_from_slice(&data[..n]);
+        self.buf.len = cmp::max(self.buf.len, at + n);
+
+        self.pos += n as u64;
+        self.len = cmp::max(self.len, self.pos);
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out(true)?;
+        self.file.flush()
+    }
+}
+
+impl Read for StagedWriter {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let buf_end = self.offset + self.buf.len as u64;
+
+        if self.buf.len > 0 && self.pos >= self.offset && self.pos < buf_end {
+            let at = (self.pos - self.offset) as usize;
+            let n = cmp::min(out.len(), self.buf.len - at);
+
+            out[..n].copy_from_slice(&self.buf.bytes()[at..at + n]);
+            self.pos += n as u64;
+
+            return Ok(n);
+        }
+
+        self.write_out(true)?;
+
+        let n = self.file.read_at(out, self.pos)?;
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl Seek for StagedWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => offset_by(self.len, n),
+            SeekFrom::Current(n) => offset_by(self.pos, n),
+        };
+
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl Drop for StagedWriter {
+    fn drop(&mut self) {
+        let _ = self.write_out(true);
+    }
+}
+
+fn offset_by(base: u64, delta: i64) -> Option<u64> {
+    if delta >= 0 {
+        base.checked_add(delta as u64)
+    } else {
+        base.checked_sub(delta.wrapping_neg() as u64)
+    }
+}
+
+// Reads into the buffer from the provided offset, until the buffer is full or
+// the end of the file, returning the number of bytes read.
+fn read_at_most(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut n = 0;
+
+    while n < buf.len() {
+        match file.read_at(&mut buf[n..], offset + n as u64) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(n)
+}
+
+#[cfg(target_os = "linux")]
+fn open_direct(path: &Path) -> io::Result<File> {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_direct(_path: &Path) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "direct writes are only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+
+    use filecoin_proofs::fr32::write_padded;
+
+    use super::*;
+
+    fn write_with(dir: &Path, prefix: &[u8], data: &[u8], config: StagedWriteConfig) -> Vec<u8> {
+        let path = dir.join(format!("{:?}-{}", config.strategy, config.buffer_size));
+
+        fs::write(&path, prefix).unwrap();
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+
+        let mut writer = StagedWriter::new(file, &path, &config).unwrap();
+
+        write_padded(&mut &data[..], &mut writer).unwrap();
+        writer.flush().unwrap();
+
+        fs::read(&path).unwrap()
+    }
+
+    #[test]
+    fn test_writes_like_the_file_itself() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let prefix: Vec<u8> = (0..5000u32).map(|n| (n % 251) as u8).collect();
+        let data: Vec<u8> = (0..70000u32).map(|n| (n % 241) as u8).collect();
+
+        // preprocessing straight into the file
+        let expected = {
+            let path = dir.path().join("expected");
+            fs::write(&path, &prefix).unwrap();
+
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .unwrap();
+
+            write_padded(&mut &data[..], &mut file).unwrap();
+
+            fs::read(&path).unwrap()
+        };
+
+        for strategy in &[
+            StagedWriteStrategy::Seek,
+            StagedWriteStrategy::Positional,
+            StagedWriteStrategy::Direct,
+        ] {
+            for buffer_size in &[1, 1000, 4096, 1 << 20] {
+                let config = StagedWriteConfig {
+                    buffer_size: *buffer_size,
+                    strategy: *strategy,
+                };
+
+                assert_eq!(
+                    expected,
+                    write_with(dir.path(), &prefix, &data, config),
+                    "{:?}",
+                    config
+                );
+            }
+        }
+    }
+}
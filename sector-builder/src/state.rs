@@ -1,22 +1,99 @@
-use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
 
+use im::HashMap;
 use serde::{Deserialize, Serialize};
 use storage_proofs::sector::SectorId;
 
-use crate::metadata::{SealedSectorMetadata, StagedSectorMetadata};
+use crate::error::{err_illegal_seal_transition, err_sector_id_conflict, Result};
+use crate::metadata::{SealStatus, SealedSectorMetadata, StagedSectorMetadata};
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+/// The states a staged sector moves through as it's sealed, without the data
+/// each SealStatus carries.
+///
+/// A pending sector accepts pieces until it's scheduled for sealing. Sealing
+/// ends in a sealed or failed sector, either of which can be sealed again
+/// (see SectorBuilder::reseal_sector and repair_sector). A sector which was
+/// being sealed when its SectorBuilder stopped resumes sealing on restart,
+/// unless the stale seal policy marks it failed or reverts it to pending (see
+/// StaleSealPolicy).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SealState {
+    Pending,
+    Sealing,
+    Sealed,
+    Failed,
+}
+
+impl From<&SealStatus> for SealState {
+    fn from(status: &SealStatus) -> SealState {
+        match status {
+            SealStatus::Pending => SealState::Pending,
+            SealStatus::Sealing => SealState::Sealing,
+            SealStatus::Sealed(_) => SealState::Sealed,
+            SealStatus::Failed(_) => SealState::Failed,
+        }
+    }
+}
+
+impl SealState {
+    // Returns whether a sector in this state may move to the provided one.
+    pub fn can_transition_to(self, to: SealState) -> bool {
+        use SealState::*;
+
+        match (self, to) {
+            (Pending, Sealing) => true,
+            (Sealing, Sealing) => true,
+            (Sealing, Pending) => true,
+            (Sealing, Sealed) => true,
+            (Sealing, Failed) => true,
+            (Sealed, Sealing) => true,
+            (Failed, Sealing) => true,
+            _ => false,
+        }
+    }
+}
+
+// Moves the staged sector to the provided seal status, producing an error
+// (and leaving the sector as it is) if its current status doesn't allow it.
+pub fn transition_seal_status(sector: &mut StagedSectorMetadata, to: SealStatus) -> Result<()> {
+    let from = SealState::from(&sector.seal_status);
+    let to_state = SealState::from(&to);
+
+    if !from.can_transition_to(to_state) {
+        return Err(err_illegal_seal_transition(sector.sector_id, from, to_state).into());
+    }
+
+    sector.seal_status = to;
+
+    Ok(())
+}
+
+// The sectors of a state are kept in persistent maps, which are cloned in
+// constant time and share their entries with their clones, so that publishing
+// the state (see PublishedState) doesn't copy the metadata of every sector.
+#[derive(Clone, Default, Serialize, Deserialize, Debug, PartialEq)]
 pub struct StagedState {
     pub sector_id_nonce: u64,
     pub sectors: HashMap<SectorId, StagedSectorMetadata>,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+impl StagedState {
+    // Returns the number of staged sectors in the provided state, e.g. the
+    // open (pending) sectors which accept pieces or those being sealed.
+    pub fn num_sectors_in(&self, state: SealState) -> u64 {
+        self.sectors
+            .values()
+            .filter(|meta| SealState::from(&meta.seal_status) == state)
+            .count() as u64
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug, PartialEq)]
 pub struct SealedState {
     pub sectors: HashMap<SectorId, SealedSectorMetadata>,
 }
 
-#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Clone, Default, Serialize, Deserialize, Debug, PartialEq)]
 pub struct SectorBuilderState {
     pub staged: StagedState,
     pub sealed: SealedState,
@@ -32,4 +109,152 @@ impl SectorBuilderState {
             sealed: Default::default(),
         }
     }
+
+    // Derives the sector id nonce from the sectors this state knows about and
+    // ensures that it isn't behind the last sector id committed on chain, as
+    // the next sector id would collide with a committed one otherwise.
+    pub fn reconcile_sector_id_nonce(&mut self, last_committed_sector_id: SectorId) -> Result<()> {
+        let highest_known = self
+            .staged
+            .sectors
+            .keys()
+            .chain(self.sealed.sectors.keys())
+            .map(|sector_id| u64::from(*sector_id))
+            .fold(self.staged.sector_id_nonce, u64::max);
+
+        if u64::from(last_committed_sector_id) > highest_known {
+            return Err(err_sector_id_conflict(
+                last_committed_sector_id,
+                SectorId::from(highest_known),
+            )
+            .into());
+        }
+
+        self.staged.sector_id_nonce = highest_known;
+
+        Ok(())
+    }
+}
+
+// An immutable copy of a SectorBuilder's state, which the scheduler publishes
+// whenever it has changed the state, so that queries can be served from it
+// without waiting for the scheduler. Readers hold on to the copy they loaded
+// for as long as they like; publishing replaces it for subsequent readers.
+// Copies share the sectors which haven't changed since, so publishing costs
+// next to nothing, however many sectors there are.
+#[derive(Clone, Default)]
+pub struct PublishedState(Arc<RwLock<Arc<SectorBuilderState>>>);
+
+impl PublishedState {
+    pub fn new(state: &SectorBuilderState) -> PublishedState {
+        PublishedState(Arc::new(RwLock::new(Arc::new(state.clone()))))
+    }
+
+    pub fn load(&self) -> Arc<SectorBuilderState> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn publish(&self, state: &SectorBuilderState) {
+        let state = Arc::new(state.clone());
+
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_published_state() {
+        let mut state = SectorBuilderState::new(SectorId::from(10));
+        let published = PublishedState::new(&state);
+
+        let loaded = published.load();
+
+        state.staged.sector_id_nonce = 11;
+        published.publish(&state);
+
+        // a loaded copy isn't affected by subsequent publications
+        assert_eq!(10, loaded.staged.sector_id_nonce);
+        assert_eq!(11, published.load().staged.sector_id_nonce);
+
+        // publishing doesn't copy the sectors
+        assert!(published
+            .load()
+            .sealed
+            .sectors
+            .ptr_eq(&state.sealed.sectors));
+    }
+
+    #[test]
+    fn test_reconcile_sector_id_nonce() {
+        let mut state = SectorBuilderState::new(SectorId::from(10));
+
+        state
+            .sealed
+            .sectors
+            .insert(SectorId::from(14), Default::default());
+
+        // the nonce catches up with the sectors the state knows about
+        state
+            .reconcile_sector_id_nonce(SectorId::from(0))
+            .expect("failed to reconcile nonce");
+
+        assert_eq!(14, state.staged.sector_id_nonce);
+
+        // sectors committed on chain which the state doesn't know about would
+        // be allocated again
+        assert!(state.reconcile_sector_id_nonce(SectorId::from(15)).is_err());
+
+        assert_eq!(14, state.staged.sector_id_nonce);
+    }
+
+    #[test]
+    fn test_seal_state_transitions() {
+        use SealState::*;
+
+        let states = [Pending, Sealing, Sealed, Failed];
+
+        let legal = [
+            (Pending, Sealing),
+            (Sealing, Sealing),
+            (Sealing, Pending),
+            (Sealing, Sealed),
+            (Sealing, Failed),
+            (Sealed, Sealing),
+            (Failed, Sealing),
+        ];
+
+        for &from in &states {
+            for &to in &states {
+                assert_eq!(
+                    legal.contains(&(from, to)),
+                    from.can_transition_to(to),
+                    "{:?} -> {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_illegal_transitions_leave_sectors_as_they_are() {
+        let mut sector = StagedSectorMetadata::default();
+
+        assert!(
+            transition_seal_status(&mut sector, SealStatus::Sealed(Default::default())).is_err()
+        );
+        assert_eq!(SealStatus::Pending, sector.seal_status);
+
+        transition_seal_status(&mut sector, SealStatus::Sealing).unwrap();
+        assert_eq!(SealStatus::Sealing, sector.seal_status);
+
+        assert!(transition_seal_status(&mut sector, SealStatus::Pending).is_err());
+        assert_eq!(SealStatus::Sealing, sector.seal_status);
+    }
 }
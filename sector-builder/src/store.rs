@@ -1,10 +1,16 @@
 use std::io::Read;
+use std::ops::Deref;
 use std::path::PathBuf;
 
+use memmap::Mmap;
+
 use filecoin_proofs::types::*;
 use storage_proofs::sector::SectorId;
 
+use crate::encryption::StagedDataKey;
 use crate::error::SectorManagerErr;
+use crate::sector_access::SectorAccess;
+use crate::staged_writer::StagedWriteConfig;
 
 pub trait SectorConfig: Sync + Send {
     /// returns the number of user-provided bytes that will fit into a sector managed by this store
@@ -24,73 +30,147 @@ pub trait ProofsConfig: Sync + Send {
 
 pub trait SectorManager: Sync + Send {
     /// produce the path to the file associated with sealed sector access-token
-    fn sealed_sector_path(&self, access: &str) -> PathBuf;
+    fn sealed_sector_path(&self, access: &SectorAccess) -> PathBuf;
 
     /// produce the path to the file associated with staged sector access-token
-    fn staged_sector_path(&self, access: &str) -> PathBuf;
+    fn staged_sector_path(&self, access: &SectorAccess) -> PathBuf;
+
+    /// produce the path to the directory in which sealed sector-files are stored
+    fn sealed_sector_dir(&self) -> PathBuf;
+
+    /// produce the path to the directory in which staged sector-files are stored
+    fn staged_sector_dir(&self) -> PathBuf;
+
+    /// produce the key with which staged sector-files are encrypted, if any
+    fn staged_data_key(&self) -> Option<StagedDataKey> {
+        None
+    }
+
+    /// produce the access-token of the sealed sector with the sector_id, without provisioning it
+    fn sealed_sector_access(&self, sector_id: SectorId) -> Result<SectorAccess, SectorManagerErr>;
 
     /// provisions a new sealed sector with the sector_id and reports the corresponding access
-    fn new_sealed_sector_access(&self, sector_id: SectorId) -> Result<String, SectorManagerErr>;
+    fn new_sealed_sector_access(
+        &self,
+        sector_id: SectorId,
+    ) -> Result<SectorAccess, SectorManagerErr>;
 
     /// provisions a new staging sector and reports the corresponding access
-    fn new_staging_sector_access(&self, sector_id: SectorId) -> Result<String, SectorManagerErr>;
+    fn new_staging_sector_access(
+        &self,
+        sector_id: SectorId,
+    ) -> Result<SectorAccess, SectorManagerErr>;
 
     /// reports the number of bytes written to an unsealed sector
-    fn num_unsealed_bytes(&self, access: &str) -> Result<u64, SectorManagerErr>;
+    fn num_unsealed_bytes(&self, access: &SectorAccess) -> Result<u64, SectorManagerErr>;
 
     /// sets the number of bytes in an unsealed sector identified by `access`
-    fn truncate_unsealed(&self, access: &str, size: u64) -> Result<(), SectorManagerErr>;
+    fn truncate_unsealed(&self, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr>;
 
     /// writes `data` to the staging sector identified by `access`, incrementally preprocessing `access`
     fn write_and_preprocess(
         &self,
-        access: &str,
+        access: &SectorAccess,
         data: &mut dyn Read,
     ) -> Result<UnpaddedBytesAmount, SectorManagerErr>;
 
-    fn delete_staging_sector_access(&self, access: &str) -> Result<(), SectorManagerErr>;
+    /// like write_and_preprocess, but writes the preprocessed bytes as configured
+    fn write_and_preprocess_with(
+        &self,
+        access: &SectorAccess,
+        data: &mut dyn Read,
+        _config: &StagedWriteConfig,
+    ) -> Result<UnpaddedBytesAmount, SectorManagerErr> {
+        self.write_and_preprocess(access, data)
+    }
+
+    fn delete_staging_sector_access(&self, access: &SectorAccess) -> Result<(), SectorManagerErr>;
+
+    /// produces the BLAKE3 digest of `num_bytes` of the (preprocessed) staging sector identified
+    /// by `access`, starting at `start_offset`
+    fn digest_staged_range(
+        &self,
+        access: &SectorAccess,
+        start_offset: u64,
+        num_bytes: u64,
+    ) -> Result<[u8; 32], SectorManagerErr>;
 
     fn read_raw(
         &self,
-        access: &str,
+        access: &SectorAccess,
         start_offset: u64,
         num_bytes: UnpaddedBytesAmount,
     ) -> Result<Vec<u8>, SectorManagerErr>;
+
+    /// maps `num_bytes` of the sector-file identified by `access`, starting at `start_offset`,
+    /// into memory instead of copying them into a buffer
+    fn map_raw(
+        &self,
+        access: &SectorAccess,
+        start_offset: u64,
+        num_bytes: UnpaddedBytesAmount,
+    ) -> Result<MappedBytes, SectorManagerErr>;
+}
+
+/// A read-only range of a sector-file which has been mapped into memory. The
+/// mapping remains valid after the file has been removed.
+pub struct MappedBytes(Option<Mmap>);
+
+impl MappedBytes {
+    pub fn new(mmap: Mmap) -> MappedBytes {
+        MappedBytes(Some(mmap))
+    }
+
+    /// an empty range, which can't be mapped
+    pub fn empty() -> MappedBytes {
+        MappedBytes(None)
+    }
+}
+
+impl Deref for MappedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            Some(mmap) => mmap,
+            None => &[],
+        }
+    }
 }
 
 pub trait SimpleSectorManager: Sync + Send {
     /// produce the path to the file associated with sealed sector access-token
-    fn sealed_sector_path(&self, miner: &str, access: &str) -> PathBuf;
+    fn sealed_sector_path(&self, miner: &str, access: &SectorAccess) -> PathBuf;
 
     /// produce the path to the file associated with staged sector access-token
-    fn staged_sector_path(&self, miner: &str, access: &str) -> PathBuf;
+    fn staged_sector_path(&self, miner: &str, access: &SectorAccess) -> PathBuf;
 
     /// provisions a new sealed sector with the sector_id and reports the corresponding access
-    fn new_sealed_sector_access(&self, miner: &str, sector_id: SectorId) -> Result<String, SectorManagerErr>;
+    fn new_sealed_sector_access(&self, miner: &str, sector_id: SectorId) -> Result<SectorAccess, SectorManagerErr>;
 
     /// provisions a new staging sector and reports the corresponding access
-    fn new_staging_sector_access(&self, miner: &str, sector_id: SectorId, create: bool) -> Result<String, SectorManagerErr>;
+    fn new_staging_sector_access(&self, miner: &str, sector_id: SectorId, create: bool) -> Result<SectorAccess, SectorManagerErr>;
 
     /// reports the number of bytes written to an unsealed sector
-    fn num_unsealed_bytes(&self, miner: &str, access: &str) -> Result<u64, SectorManagerErr>;
+    fn num_unsealed_bytes(&self, miner: &str, access: &SectorAccess) -> Result<u64, SectorManagerErr>;
 
     /// sets the number of bytes in an unsealed sector identified by `access`
-    fn truncate_unsealed(&self, miner: &str, access: &str, size: u64) -> Result<(), SectorManagerErr>;
+    fn truncate_unsealed(&self, miner: &str, access: &SectorAccess, size: u64) -> Result<(), SectorManagerErr>;
 
     /// writes `data` to the staging sector identified by `access`, incrementally preprocessing `access`
     fn write_and_preprocess(
         &self,
         miner: &str,
-        access: &str,
+        access: &SectorAccess,
         data: &mut dyn Read,
     ) -> Result<UnpaddedBytesAmount, SectorManagerErr>;
 
-    fn delete_staging_sector_access(&self, miner: &str, access: &str) -> Result<(), SectorManagerErr>;
+    fn delete_staging_sector_access(&self, miner: &str, access: &SectorAccess) -> Result<(), SectorManagerErr>;
 
     fn read_raw(
         &self,
         miner: &str,
-        access: &str,
+        access: &SectorAccess,
         start_offset: u64,
         num_bytes: UnpaddedBytesAmount,
     ) -> Result<Vec<u8>, SectorManagerErr>;
@@ -100,6 +180,19 @@ pub trait SectorStore: Sync + Send + Sized {
     fn sector_config(&self) -> &dyn SectorConfig;
     fn proofs_config(&self) -> &dyn ProofsConfig;
     fn manager(&self) -> &dyn SectorManager;
+
+    /// manages the sector-files of staged sectors which spilled over into the
+    /// secondary staged sector directory, if one is configured
+    fn secondary_staged_manager(&self) -> Option<&dyn SectorManager> {
+        None
+    }
+
+    /// the number of bytes which must be available in the staged sector
+    /// directory, below which new staged sectors spill over into the
+    /// secondary staged sector directory
+    fn staged_spill_over_min_free_bytes(&self) -> u64 {
+        0
+    }
 }
 
 pub trait SimpleSectorStore: Sync + Send + Sized {
@@ -129,10 +222,10 @@ mod tests {
     struct Harness<S: SectorStore> {
         prover_id: FrSafe,
         seal_output: SealOutput,
-        sealed_access: String,
+        sealed_access: SectorAccess,
         sector_id: SectorId,
         store: S,
-        unseal_access: String,
+        unseal_access: SectorAccess,
         written_contents: Vec<Vec<u8>>,
     }
 
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::metadata::SecondsSinceEpoch;
+
+/// A clock which only moves when it's told to, with which time-dependent
+/// behavior (auto-sealing, piece and PoSt deadlines) can be tested without
+/// sleeping. Clones share their time, so a clone kept by a test can move the
+/// time of the one handed to the SectorBuilder.
+#[derive(Clone, Debug, Default)]
+pub struct FakeClock(Arc<AtomicU64>);
+
+impl FakeClock {
+    pub fn new(now: SecondsSinceEpoch) -> FakeClock {
+        FakeClock(Arc::new(AtomicU64::new(now.0)))
+    }
+
+    pub fn set(&self, now: SecondsSinceEpoch) {
+        self.0.store(now.0, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.0.fetch_add(duration.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SecondsSinceEpoch {
+        SecondsSinceEpoch(self.0.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_their_time() {
+        let clock = FakeClock::new(SecondsSinceEpoch(100));
+        let handed_out = clock.clone();
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(SecondsSinceEpoch(160), handed_out.now());
+
+        clock.set(SecondsSinceEpoch(10));
+        assert_eq!(SecondsSinceEpoch(10), handed_out.now());
+    }
+}
@@ -0,0 +1,459 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use storage_proofs::sector::SectorId;
+
+use crate::error::{err_task_cancelled, err_task_timed_out, err_unrecov, Result};
+use crate::events::{EventBus, SectorBuilderEvent};
+
+/// The kinds of long-running task which can be given a timeout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TaskKind {
+    Seal,
+    Unseal,
+    PoSt,
+    /// copying or moving the replica of a sealed sector, which runs without
+    /// a timeout
+    Transfer,
+    /// reading the replica of a sealed sector to check or checksum it, which
+    /// runs without a timeout
+    Check,
+}
+
+impl fmt::Display for TaskKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskKind::Seal => write!(f, "seal"),
+            TaskKind::Unseal => write!(f, "unseal"),
+            TaskKind::PoSt => write!(f, "PoSt"),
+            TaskKind::Transfer => write!(f, "replica transfer"),
+            TaskKind::Check => write!(f, "replica check"),
+        }
+    }
+}
+
+/// Limits on how long each kind of task may run. Tasks of a kind without a
+/// limit run for as long as they take, which is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TaskTimeouts {
+    pub seal: Option<Duration>,
+    pub unseal: Option<Duration>,
+    pub post: Option<Duration>,
+}
+
+impl TaskTimeouts {
+    pub fn get(&self, task: TaskKind) -> Option<Duration> {
+        match task {
+            TaskKind::Seal => self.seal,
+            TaskKind::Unseal => self.unseal,
+            TaskKind::PoSt => self.post,
+            // transfers and checks are limited by the disks rather than the
+            // proofs, so they run for as long as they take
+            TaskKind::Transfer | TaskKind::Check => None,
+        }
+    }
+}
+
+// How often a thread watching a cancellable task checks whether it has been
+// cancelled.
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Lets the caller of a long-running task give up on it. Clones share their
+/// state, so the caller keeps a clone of the token it hands to the task.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// Once this many abandoned tasks are still running, across every
+// SectorBuilder in the process, tasks which would run on a thread of their own
+// are refused instead of started, so that hung tasks can't pile up until the
+// process runs out of memory.
+pub const MAX_ABANDONED_TASKS: usize = 4;
+
+// The number of abandoned tasks whose threads are still running.
+static ABANDONED_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// How the caller of a task deals with the task being abandoned.
+#[derive(Default)]
+pub struct OnAbandon {
+    /// cancelled once the task is abandoned, so that a task which checks it
+    /// (e.g. between phases, or while waiting on a child process) stops early
+    pub stop: CancellationToken,
+    /// called on the task's thread once the abandoned task has finished after
+    /// all, e.g. to release what it was still writing to
+    pub on_exit: Option<Box<dyn FnOnce() + Send>>,
+}
+
+// Whether a task's thread has been abandoned by the thread watching it, which
+// both threads decide under the same lock, so that an abandoned task's
+// on_exit is called exactly once.
+#[derive(Default)]
+struct TaskThread {
+    abandoned: bool,
+    on_exit: Option<Box<dyn FnOnce() + Send>>,
+}
+
+// Dropped once a task's thread is done with the task, even if it panicked.
+struct TaskThreadExit(Arc<Mutex<TaskThread>>);
+
+impl Drop for TaskThreadExit {
+    fn drop(&mut self) {
+        let on_exit = {
+            let mut thread = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if !thread.abandoned {
+                return;
+            }
+
+            thread.on_exit.take()
+        };
+
+        ABANDONED_TASKS.fetch_sub(1, Ordering::SeqCst);
+
+        if let Some(on_exit) = on_exit {
+            on_exit();
+        }
+    }
+}
+
+// Runs the task, failing with a TaskTimedOut error if it doesn't complete
+// within the provided timeout. A task with a timeout runs on a thread of its
+// own which is watched by the calling thread. Threads can't be interrupted,
+// so a task which times out (e.g. because a GPU driver hung) is abandoned:
+// it keeps its thread and whatever resources it holds and its result is
+// discarded, but the calling thread is free to move on to other tasks.
+pub fn run_with_timeout<T, F>(
+    task: TaskKind,
+    sector_id: Option<SectorId>,
+    timeout: Option<Duration>,
+    events: &EventBus,
+    f: F,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    run_cancellable(task, sector_id, timeout, None, events, f)
+}
+
+// Runs the task like run_with_timeout, failing with a TaskCancelled error
+// instead if the provided token is cancelled before the task completes. A
+// task which is cancelled is abandoned just like one which times out, or
+// isn't started at all if it's cancelled beforehand.
+pub fn run_cancellable<T, F>(
+    task: TaskKind,
+    sector_id: Option<SectorId>,
+    timeout: Option<Duration>,
+    cancel: Option<&CancellationToken>,
+    events: &EventBus,
+    f: F,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let (result, _) = run_watched(
+        task,
+        sector_id,
+        timeout,
+        cancel,
+        events,
+        Default::default(),
+        f,
+    );
+
+    result
+}
+
+// Runs the task like run_cancellable, returning whether it was abandoned
+// along with its result. Abandoning a task cancels its stop token, and its
+// on_exit is called once the abandoned task has finished after all, e.g. so
+// that a seal's sector isn't sealed again while the abandoned seal is still
+// writing the replica.
+pub fn run_watched<T, F>(
+    task: TaskKind,
+    sector_id: Option<SectorId>,
+    timeout: Option<Duration>,
+    cancel: Option<&CancellationToken>,
+    events: &EventBus,
+    on_abandon: OnAbandon,
+    f: F,
+) -> (Result<T>, bool)
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let is_cancelled = || cancel.map_or(false, CancellationToken::is_cancelled);
+
+    if is_cancelled() {
+        return (Err(err_task_cancelled(task).into()), false);
+    }
+
+    if timeout.is_none() && cancel.is_none() {
+        return (f(), false);
+    }
+
+    let num_abandoned = ABANDONED_TASKS.load(Ordering::SeqCst);
+
+    if num_abandoned >= MAX_ABANDONED_TASKS {
+        return (
+            Err(err_unrecov(format!(
+                "{} abandoned tasks are still running, not starting a {} task",
+                num_abandoned, task
+            ))
+            .into()),
+            false,
+        );
+    }
+
+    let task_thread: Arc<Mutex<TaskThread>> = Default::default();
+
+    let (tx, rx) = mpsc::sync_channel(1);
+
+    {
+        let exit = TaskThreadExit(task_thread.clone());
+
+        thread::spawn(move || {
+            let _exit = exit;
+
+            // the watching thread hangs up once the task has been abandoned
+            let _ = tx.send(f());
+        });
+    }
+
+    // Abandons the task unless it has completed in the meantime, in which
+    // case its result is returned instead.
+    let abandon = |err: Result<T>| {
+        let mut thread = task_thread.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Ok(result) = rx.try_recv() {
+            return (result, false);
+        }
+
+        thread.abandoned = true;
+        thread.on_exit = on_abandon.on_exit;
+
+        ABANDONED_TASKS.fetch_add(1, Ordering::SeqCst);
+        on_abandon.stop.cancel();
+
+        (err, true)
+    };
+
+    let started_at = Instant::now();
+
+    loop {
+        let remaining = timeout.map(|timeout| {
+            timeout
+                .checked_sub(started_at.elapsed())
+                .unwrap_or_default()
+        });
+
+        let wait = match (remaining, cancel) {
+            (Some(remaining), Some(_)) => remaining.min(CANCELLATION_POLL_INTERVAL),
+            (Some(remaining), None) => remaining,
+            (None, _) => CANCELLATION_POLL_INTERVAL,
+        };
+
+        match rx.recv_timeout(wait) {
+            Ok(result) => return (result, false),
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return (
+                    Err(err_unrecov(format!("{} task panicked", task)).into()),
+                    false,
+                );
+            }
+        }
+
+        if let Some(timeout) = timeout {
+            if started_at.elapsed() >= timeout {
+                warn!(
+                    "{} task for sector {:?} timed out after {:?}, abandoning it",
+                    task, sector_id, timeout
+                );
+
+                events.emit(SectorBuilderEvent::TaskTimedOut {
+                    task,
+                    sector_id,
+                    timeout,
+                });
+
+                return abandon(Err(err_task_timed_out(task, timeout).into()));
+            }
+        }
+
+        if is_cancelled() {
+            warn!(
+                "{} task for sector {:?} was cancelled, abandoning it",
+                task, sector_id
+            );
+
+            return abandon(Err(err_task_cancelled(task).into()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SectorBuilderErr;
+
+    use super::*;
+
+    #[test]
+    fn abandons_tasks_which_time_out() {
+        let events = EventBus::default();
+        let rx = events.subscribe();
+
+        let result = run_with_timeout(
+            TaskKind::Unseal,
+            Some(SectorId::from(7)),
+            Some(Duration::from_millis(10)),
+            &events,
+            || {
+                thread::sleep(Duration::from_secs(5));
+                Ok(())
+            },
+        );
+
+        match result.unwrap_err().downcast_ref::<SectorBuilderErr>() {
+            Some(SectorBuilderErr::TaskTimedOut { task, .. }) => {
+                assert_eq!(TaskKind::Unseal, *task)
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        assert_eq!(
+            SectorBuilderEvent::TaskTimedOut {
+                task: TaskKind::Unseal,
+                sector_id: Some(SectorId::from(7)),
+                timeout: Duration::from_millis(10),
+            },
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn abandons_tasks_which_are_cancelled() {
+        let events = EventBus::default();
+        let cancel = CancellationToken::default();
+
+        let is_cancelled = |result: Result<()>| match result.unwrap_err().downcast_ref() {
+            Some(SectorBuilderErr::TaskCancelled(TaskKind::PoSt)) => true,
+            _ => false,
+        };
+
+        let running = {
+            let cancel = cancel.clone();
+
+            thread::spawn(move || {
+                run_cancellable(TaskKind::PoSt, None, None, Some(&cancel), &events, || {
+                    thread::sleep(Duration::from_secs(5));
+                    Ok(())
+                })
+            })
+        };
+
+        cancel.cancel();
+
+        assert!(is_cancelled(running.join().unwrap()));
+
+        // tasks which are cancelled before they start don't run at all
+        let not_started = run_cancellable(
+            TaskKind::PoSt,
+            None,
+            None,
+            Some(&cancel),
+            &EventBus::default(),
+            || panic!("cancelled task was started"),
+        );
+
+        assert!(is_cancelled(not_started));
+    }
+
+    #[test]
+    fn stops_abandoned_tasks_and_reports_their_exit() {
+        let stop = CancellationToken::default();
+        let (exit_tx, exit_rx) = mpsc::channel();
+
+        let on_abandon = OnAbandon {
+            stop: stop.clone(),
+            on_exit: Some(Box::new(move || exit_tx.send(()).unwrap())),
+        };
+
+        let (result, abandoned) = run_watched(
+            TaskKind::Seal,
+            Some(SectorId::from(3)),
+            Some(Duration::from_millis(10)),
+            None,
+            &EventBus::default(),
+            on_abandon,
+            move || {
+                while !stop.is_cancelled() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+
+                Ok(())
+            },
+        );
+
+        assert!(result.is_err());
+        assert!(abandoned);
+
+        // the task stops once it sees that it was abandoned
+        exit_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // tasks which complete aren't abandoned, so their on_exit isn't called
+        let (exit_tx, exit_rx) = mpsc::channel();
+
+        let on_abandon = OnAbandon {
+            on_exit: Some(Box::new(move || exit_tx.send(()).unwrap())),
+            ..Default::default()
+        };
+
+        let (result, abandoned) = run_watched(
+            TaskKind::Seal,
+            None,
+            Some(Duration::from_secs(60)),
+            None,
+            &EventBus::default(),
+            on_abandon,
+            || Ok(5),
+        );
+
+        assert_eq!(5, result.unwrap());
+        assert!(!abandoned);
+        assert!(exit_rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn returns_results_of_tasks_which_complete() {
+        let events = EventBus::default();
+        let rx = events.subscribe();
+
+        let with_timeout = run_with_timeout(
+            TaskKind::Seal,
+            None,
+            Some(Duration::from_secs(60)),
+            &events,
+            || Ok(42),
+        );
+
+        let without_timeout = run_with_timeout(TaskKind::PoSt, None, None, &events, || Ok(7));
+
+        assert_eq!(42, with_timeout.unwrap());
+        assert_eq!(7, without_timeout.unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+}
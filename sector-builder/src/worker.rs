@@ -1,49 +1,302 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use filecoin_proofs::error::ExpectWithBacktrace;
+use filecoin_proofs::{PoStConfig, PrivateReplicaInfo};
 
-use crate::error::Result;
+use crate::affinity::{pin_current_thread, WorkerAffinity};
+use crate::builder::missing_porep_parameters;
+use crate::encryption::{decrypt_staged_sector, StagedDataKey};
+use crate::error::{err_params_missing, err_task_cancelled, err_worker_panicked, Result};
+use crate::events::{EventBus, SectorBuilderEvent};
+use crate::helpers::{
+    calculate_replica_checksums, compute_sector_integrity, copy_imported_replica,
+    get_sealed_sector_health, local_hostname, move_replica, verify_staged_sector,
+    write_sector_bundle, ManifestEntry,
+};
+use crate::isolation::{seal_in_child_process, SealIsolation};
 use crate::scheduler::SchedulerTask;
-use crate::{PoRepConfig, UnpaddedByteIndex, UnpaddedBytesAmount};
+use crate::sector_access::SectorAccess;
+use crate::store::MappedBytes;
+use crate::watchdog::{
+    run_cancellable, run_watched, run_with_timeout, CancellationToken, OnAbandon, TaskKind,
+};
+use crate::{
+    ChecksumAlgo, PoRepConfig, PoStPartition, SealTicket, SealTiming, SealedSectorLocation,
+    SealedSectorMetadata, SecondsSinceEpoch, UnpaddedByteIndex, UnpaddedBytesAmount,
+};
 use std::path::PathBuf;
 use storage_proofs::sector::SectorId;
 
 const FATAL_NOLOCK: &str = "error acquiring task lock";
 const FATAL_RCVTSK: &str = "error receiving seal task";
-const FATAL_SNDRLT: &str = "error sending result";
 
 pub struct Worker {
     pub id: usize,
     pub thread: Option<thread::JoinHandle<()>>,
 }
 
+// What a task runs with that depends on the SectorBuilder which sent it, so
+// that the SectorBuilders of several miners can share one worker pool.
+#[derive(Clone, Default)]
+pub struct WorkerContext {
+    pub prover_id: [u8; 31],
+    pub events: EventBus,
+}
+
+// A SectorBuilder's end of a worker pool's task channel, which sends each task
+// with the SectorBuilder's context.
+pub struct WorkerHandle<T> {
+    tx: mpsc::Sender<(WorkerContext, WorkerTask<T>)>,
+    context: WorkerContext,
+}
+
+impl<T> Clone for WorkerHandle<T> {
+    fn clone(&self) -> Self {
+        WorkerHandle {
+            tx: self.tx.clone(),
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<T> WorkerHandle<T> {
+    pub fn new(
+        tx: mpsc::Sender<(WorkerContext, WorkerTask<T>)>,
+        context: WorkerContext,
+    ) -> WorkerHandle<T> {
+        WorkerHandle { tx, context }
+    }
+
+    pub fn send(
+        &self,
+        task: WorkerTask<T>,
+    ) -> std::result::Result<(), mpsc::SendError<WorkerTask<T>>> {
+        self.tx
+            .send((self.context.clone(), task))
+            .map_err(|mpsc::SendError((_, task))| mpsc::SendError(task))
+    }
+}
+
+// The workers which run the long-running tasks of one or more SectorBuilders.
+// The workers are shut down once the pool is dropped, i.e. once every
+// SectorBuilder using it has been dropped.
+pub struct WorkerPool<T> {
+    // A Sender can't be shared between threads, so it's kept behind a lock.
+    tx: Mutex<mpsc::Sender<(WorkerContext, WorkerTask<T>)>>,
+    workers: Vec<Worker>,
+}
+
+impl<T: 'static + Send> WorkerPool<T> {
+    pub fn start(num_workers: usize, affinity: &WorkerAffinity) -> Arc<WorkerPool<T>> {
+        let (tx, rx) = mpsc::channel();
+        let rx = Arc::new(Mutex::new(rx));
+
+        let workers = (0..num_workers)
+            .map(|n| Worker::start(n, rx.clone(), affinity))
+            .collect();
+
+        Arc::new(WorkerPool {
+            tx: Mutex::new(tx),
+            workers,
+        })
+    }
+}
+
+impl<T> WorkerPool<T> {
+    // Returns the handle through which the SectorBuilder with the provided
+    // prover id sends tasks to the workers.
+    pub fn handle(&self, prover_id: [u8; 31], events: EventBus) -> WorkerHandle<T> {
+        let tx = match self.tx.lock() {
+            Ok(tx) => tx.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+
+        WorkerHandle::new(tx, WorkerContext { prover_id, events })
+    }
+}
+
+impl<T> Drop for WorkerPool<T> {
+    fn drop(&mut self) {
+        let tx = match self.tx.get_mut() {
+            Ok(tx) => tx,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        for _ in &self.workers {
+            let _ = tx
+                .send((Default::default(), WorkerTask::Shutdown))
+                .map_err(|err| println!("err sending Shutdown to sealer: {:?}", err));
+        }
+
+        // Wait for worker threads to return.
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread
+                    .join()
+                    .map_err(|err| println!("err joining sealer thread: {:?}", err));
+            }
+        }
+    }
+}
+
+/// Everything needed to unseal a range of a sealed sector, which a builder
+/// plans before handing the task to a worker. Orchestration layers can plan
+/// a retrieval without executing it, e.g. to run it elsewhere.
+#[derive(Clone, Debug)]
 pub struct UnsealTaskPrototype {
-    pub(crate) destination_path: PathBuf,
-    pub(crate) piece_len: UnpaddedBytesAmount,
-    pub(crate) piece_start_byte: UnpaddedByteIndex,
-    pub(crate) porep_config: PoRepConfig,
-    pub(crate) sector_id: SectorId,
-    pub(crate) source_path: PathBuf,
+    /// where the unsealed bytes are written
+    pub destination_path: PathBuf,
+    pub piece_len: UnpaddedBytesAmount,
+    /// offset of the range within the sector's unsealed bytes
+    pub piece_start_byte: UnpaddedByteIndex,
+    pub porep_config: PoRepConfig,
+    pub sector_id: SectorId,
+    /// the replica which is unsealed
+    pub source_path: PathBuf,
+    pub timeout: Option<Duration>,
 }
 
+/// Everything needed to seal a staged sector, which a builder plans before
+/// handing the task to a worker. Orchestration layers can plan sealing
+/// without executing it, e.g. to run it elsewhere.
+#[derive(Clone, Debug)]
 pub struct SealTaskPrototype {
-    pub(crate) piece_lens: Vec<UnpaddedBytesAmount>,
-    pub(crate) porep_config: PoRepConfig,
-    pub(crate) sealed_sector_access: String,
-    pub(crate) sealed_sector_path: PathBuf,
-    pub(crate) sector_id: SectorId,
-    pub(crate) staged_sector_path: PathBuf,
+    pub piece_lens: Vec<UnpaddedBytesAmount>,
+    pub porep_config: PoRepConfig,
+    pub sealed_sector_access: SectorAccess,
+    /// where the replica is written
+    pub sealed_sector_path: PathBuf,
+    /// ticket with which the sector must be sealed, if one is required
+    pub seal_ticket: Option<SealTicket>,
+    pub sector_id: SectorId,
+    pub staged_sector_path: PathBuf,
+    pub timeout: Option<Duration>,
+    /// where the worker seals the sector
+    pub isolation: SealIsolation,
+    /// key with which the staged sector-file is encrypted, if it is
+    pub staged_data_key: Option<StagedDataKey>,
+    /// digests of the pieces in the staged sector-file, which are checked
+    /// before sealing begins
+    pub piece_manifest: Vec<ManifestEntry>,
+    /// algorithm with which the replica is checksummed once it's been sealed
+    pub checksum_algo: ChecksumAlgo,
+}
+
+// Everything needed to prove one partition of a proving set.
+pub struct PoStTaskPrototype {
+    pub challenge_seed: [u8; 32],
+    pub faults: Vec<SectorId>,
+    pub index: usize,
+    pub post_config: PoStConfig,
+    pub replicas: BTreeMap<SectorId, PrivateReplicaInfo>,
+    pub timeout: Option<Duration>,
+}
+
+// Everything needed to transfer the replica of a sealed sector, which reads
+// the whole replica and so is done by a worker rather than the scheduler.
+// The scheduler updates the sector's metadata once the transfer is done.
+#[derive(Clone, Debug)]
+pub enum ReplicaTaskPrototype {
+    // Copies an externally sealed replica to the provisioned sealed sector
+    // access, after validating it against its metadata.
+    Import {
+        replica_path: PathBuf,
+        sector_access: SectorAccess,
+        sector_path: PathBuf,
+        meta: SealedSectorMetadata,
+        link_ok: bool,
+    },
+    // Writes a bundle of the sealed sector's replica and metadata to the
+    // destination directory.
+    Export {
+        sector_path: PathBuf,
+        meta: SealedSectorMetadata,
+        dest_dir: PathBuf,
+    },
+    // Moves the sealed sector's replica to the target location, e.g. to
+    // archive it on cheaper storage.
+    Move {
+        sector_id: SectorId,
+        source_path: PathBuf,
+        target_path: PathBuf,
+        target: SealedSectorLocation,
+    },
+}
+
+impl ReplicaTaskPrototype {
+    pub fn sector_id(&self) -> SectorId {
+        match self {
+            ReplicaTaskPrototype::Import { meta, .. } => meta.sector_id,
+            ReplicaTaskPrototype::Export { meta, .. } => meta.sector_id,
+            ReplicaTaskPrototype::Move { sector_id, .. } => *sector_id,
+        }
+    }
+
+    fn run(&self) -> Result<()> {
+        match self {
+            ReplicaTaskPrototype::Import {
+                replica_path,
+                sector_path,
+                meta,
+                link_ok,
+                ..
+            } => copy_imported_replica(replica_path, sector_path, meta, *link_ok),
+            ReplicaTaskPrototype::Export {
+                sector_path,
+                meta,
+                dest_dir,
+            } => write_sector_bundle(sector_path, meta, dest_dir),
+            ReplicaTaskPrototype::Move {
+                source_path,
+                target_path,
+                ..
+            } => move_replica(source_path, target_path),
+        }
+    }
+}
+
+// Everything needed to compute the integrity fields which the metadata of a
+// sealed sector lacks, which reads the whole replica and so is done by a
+// worker rather than the scheduler. The scheduler records the fields once
+// they've been computed.
+#[derive(Clone, Debug)]
+pub struct HydrateTaskPrototype {
+    pub sector_id: SectorId,
+    pub replica_path: PathBuf,
+    pub checksum_algo: ChecksumAlgo,
+    pub lacks_len: bool,
+    pub lacks_checksum: bool,
+}
+
+// Everything needed to check that the replica of a sealed sector is
+// corrupted before it's repaired, which reads the whole replica and so is
+// done by a worker rather than the scheduler. The scheduler plans the repair
+// once the replica's health is known.
+#[derive(Clone, Debug)]
+pub struct RepairCheckTaskPrototype {
+    pub replica_path: PathBuf,
+    pub meta: SealedSectorMetadata,
 }
 
 pub enum WorkerTask<T> {
     Seal {
         piece_lens: Vec<UnpaddedBytesAmount>,
         porep_config: PoRepConfig,
-        sealed_sector_access: String,
+        sealed_sector_access: SectorAccess,
         sealed_sector_path: PathBuf,
         sector_id: SectorId,
         staged_sector_path: PathBuf,
+        timeout: Option<Duration>,
+        isolation: SealIsolation,
+        staged_data_key: Option<StagedDataKey>,
+        piece_manifest: Vec<ManifestEntry>,
+        checksum_algo: ChecksumAlgo,
+        cancel: CancellationToken,
         done_tx: mpsc::SyncSender<SchedulerTask<T>>,
     },
     Unseal {
@@ -53,7 +306,28 @@ pub enum WorkerTask<T> {
         sector_id: SectorId,
         piece_start_byte: UnpaddedByteIndex,
         piece_len: UnpaddedBytesAmount,
-        caller_done_tx: mpsc::SyncSender<Result<Vec<u8>>>,
+        timeout: Option<Duration>,
+        cancel: Option<CancellationToken>,
+        caller_done_tx: mpsc::SyncSender<Result<MappedBytes>>,
+        done_tx: mpsc::SyncSender<SchedulerTask<T>>,
+    },
+    GeneratePoSt {
+        proto: PoStTaskPrototype,
+        caller_done_tx: mpsc::Sender<Result<PoStPartition>>,
+    },
+    TransferReplica {
+        proto: ReplicaTaskPrototype,
+        caller_done_tx: mpsc::SyncSender<Result<()>>,
+        done_tx: mpsc::SyncSender<SchedulerTask<T>>,
+    },
+    HydrateIntegrity {
+        proto: HydrateTaskPrototype,
+        caller_done_tx: mpsc::SyncSender<Result<bool>>,
+        done_tx: mpsc::SyncSender<SchedulerTask<T>>,
+    },
+    CheckRepairable {
+        proto: RepairCheckTaskPrototype,
+        caller_done_tx: mpsc::SyncSender<Result<()>>,
         done_tx: mpsc::SyncSender<SchedulerTask<T>>,
     },
     Shutdown,
@@ -62,6 +336,7 @@ pub enum WorkerTask<T> {
 impl<T> WorkerTask<T> {
     pub fn from_seal_proto(
         proto: SealTaskPrototype,
+        cancel: CancellationToken,
         done_tx: mpsc::SyncSender<SchedulerTask<T>>,
     ) -> WorkerTask<T> {
         let SealTaskPrototype {
@@ -71,6 +346,12 @@ impl<T> WorkerTask<T> {
             sealed_sector_path,
             sector_id,
             staged_sector_path,
+            timeout,
+            isolation,
+            staged_data_key,
+            piece_manifest,
+            checksum_algo,
+            ..
         } = proto;
 
         WorkerTask::Seal {
@@ -80,13 +361,20 @@ impl<T> WorkerTask<T> {
             sealed_sector_path,
             sector_id,
             staged_sector_path,
+            timeout,
+            isolation,
+            staged_data_key,
+            piece_manifest,
+            checksum_algo,
+            cancel,
             done_tx,
         }
     }
 
     pub fn from_unseal_proto(
         proto: UnsealTaskPrototype,
-        caller_done_tx: mpsc::SyncSender<Result<Vec<u8>>>,
+        cancel: Option<CancellationToken>,
+        caller_done_tx: mpsc::SyncSender<Result<MappedBytes>>,
         done_tx: mpsc::SyncSender<SchedulerTask<T>>,
     ) -> WorkerTask<T> {
         let UnsealTaskPrototype {
@@ -96,6 +384,7 @@ impl<T> WorkerTask<T> {
             sector_id,
             piece_start_byte,
             piece_len,
+            timeout,
         } = proto;
 
         WorkerTask::Unseal {
@@ -105,6 +394,8 @@ impl<T> WorkerTask<T> {
             sector_id,
             piece_start_byte,
             piece_len,
+            timeout,
+            cancel,
             caller_done_tx,
             done_tx,
         }
@@ -114,76 +405,363 @@ impl<T> WorkerTask<T> {
 impl Worker {
     pub fn start<T: 'static + Send>(
         id: usize,
-        seal_task_rx: Arc<Mutex<mpsc::Receiver<WorkerTask<T>>>>,
-        prover_id: [u8; 31],
+        seal_task_rx: Arc<Mutex<mpsc::Receiver<(WorkerContext, WorkerTask<T>)>>>,
+        affinity: &WorkerAffinity,
     ) -> Worker {
-        let thread = thread::spawn(move || loop {
-            // Acquire a lock on the rx end of the channel, get a task,
-            // relinquish the lock and return the task. The receiver is mutexed
-            // for coordinating reads across multiple worker-threads.
-            let task = {
-                let rx = seal_task_rx.lock().expects(FATAL_NOLOCK);
-                rx.recv().expects(FATAL_RCVTSK)
-            };
-
-            // Dispatch to the appropriate task-handler.
-            match task {
-                WorkerTask::Seal {
-                    porep_config,
-                    sector_id,
-                    sealed_sector_access,
-                    sealed_sector_path,
-                    staged_sector_path,
-                    piece_lens,
-                    done_tx,
-                } => {
-                    let result = filecoin_proofs::seal(
+        let cpus = affinity.cpus(id).map(<[usize]>::to_vec);
+
+        let thread = thread::spawn(move || {
+            if let Some(cpus) = cpus {
+                if let Err(err) = pin_current_thread(&cpus) {
+                    warn!("could not pin worker {} to CPUs {:?}: {}", id, cpus, err);
+                }
+            }
+
+            loop {
+                // Acquire a lock on the rx end of the channel, get a task,
+                // relinquish the lock and return the task. The receiver is mutexed
+                // for coordinating reads across multiple worker-threads.
+                let (WorkerContext { prover_id, events }, task) = {
+                    let rx = seal_task_rx.lock().expects(FATAL_NOLOCK);
+                    rx.recv().expects(FATAL_RCVTSK)
+                };
+
+                // Dispatch to the appropriate task-handler.
+                match task {
+                    WorkerTask::Seal {
                         porep_config,
-                        &staged_sector_path,
-                        &sealed_sector_path,
-                        &prover_id,
                         sector_id,
-                        &piece_lens,
-                    );
+                        sealed_sector_access,
+                        sealed_sector_path,
+                        staged_sector_path,
+                        piece_lens,
+                        timeout,
+                        isolation,
+                        staged_data_key,
+                        piece_manifest,
+                        checksum_algo,
+                        cancel,
+                        done_tx,
+                    } => {
+                        let started_at = SecondsSinceEpoch::now();
+                        let started = Instant::now();
+
+                        // A seal which is abandoned, as it timed out or was
+                        // cancelled, is stopped: its child process, if any,
+                        // is killed, and an in-process seal stops at the end
+                        // of its current phase. The sector isn't sealed again
+                        // until the seal has stopped writing its replica.
+                        let stop = CancellationToken::default();
+
+                        let on_abandon = {
+                            let done_tx = done_tx.clone();
+
+                            OnAbandon {
+                                stop: stop.clone(),
+                                on_exit: Some(Box::new(move || {
+                                    let _ = done_tx
+                                        .send(SchedulerTask::HandleAbandonedSealExit(sector_id));
+                                })),
+                            }
+                        };
+
+                        let (result, abandoned) = {
+                            let sealed_sector_path = sealed_sector_path.clone();
+
+                            run_watched(
+                                TaskKind::Seal,
+                                Some(sector_id),
+                                timeout,
+                                Some(&cancel),
+                                &events,
+                                on_abandon,
+                                catch_panics(
+                                    id,
+                                    TaskKind::Seal,
+                                    Some(sector_id),
+                                    &events,
+                                    move || {
+                                        let check_stop = || -> Result<()> {
+                                            if stop.is_cancelled() {
+                                                Err(err_task_cancelled(TaskKind::Seal).into())
+                                            } else {
+                                                Ok(())
+                                            }
+                                        };
+
+                                        // fail with a ParamsMissing error rather
+                                        // than whatever the proofs produce
+                                        let missing = missing_porep_parameters(porep_config);
+
+                                        if !missing.is_empty() {
+                                            return Err(
+                                                err_params_missing(missing.join(", ")).into()
+                                            );
+                                        }
+
+                                        // Sealing reads the staged sector-file
+                                        // through its path, so an encrypted one
+                                        // is sealed from its plaintext, which is
+                                        // kept off the disk where possible and
+                                        // dropped on every way out of this task.
+                                        let decrypted = match &staged_data_key {
+                                            Some(key) => Some(decrypt_staged_sector(
+                                                &staged_sector_path,
+                                                key,
+                                            )?),
+                                            None => None,
+                                        };
+
+                                        let staged_sector_path = decrypted
+                                            .as_ref()
+                                            .map(|d| d.path().to_path_buf())
+                                            .unwrap_or(staged_sector_path);
+
+                                        check_stop()?;
+
+                                        // don't spend hours sealing corrupted data
+                                        verify_staged_sector(
+                                            &staged_sector_path,
+                                            sector_id,
+                                            &piece_manifest,
+                                        )?;
 
-                    done_tx
-                        .send(SchedulerTask::HandleSealResult(
+                                        check_stop()?;
+
+                                        let output = match isolation {
+                                            SealIsolation::InProcess => filecoin_proofs::seal(
+                                                porep_config,
+                                                &staged_sector_path,
+                                                &sealed_sector_path,
+                                                &prover_id,
+                                                sector_id,
+                                                &piece_lens,
+                                            ),
+                                            SealIsolation::ChildProcess {
+                                                worker_bin,
+                                                max_restarts,
+                                            } => seal_in_child_process(
+                                                &worker_bin,
+                                                max_restarts,
+                                                &stop,
+                                                porep_config,
+                                                &staged_sector_path,
+                                                &sealed_sector_path,
+                                                &prover_id,
+                                                sector_id,
+                                                &piece_lens,
+                                            ),
+                                        }?;
+
+                                        check_stop()?;
+
+                                        // checksum the replica while it's
+                                        // likely still in the page cache
+                                        let checksums = calculate_replica_checksums(
+                                            &sealed_sector_path,
+                                            checksum_algo,
+                                        )?;
+
+                                        Ok((output, checksums))
+                                    },
+                                ),
+                            )
+                        };
+
+                        let checksum_duration = result
+                            .as_ref()
+                            .map(|(_, checksums)| checksums.duration)
+                            .unwrap_or_default();
+
+                        let timing = SealTiming {
+                            started_at,
+                            finished_at: SecondsSinceEpoch::now(),
+                            duration: started
+                                .elapsed()
+                                .checked_sub(checksum_duration)
+                                .unwrap_or_default(),
+                            checksum_duration,
+                            hostname: local_hostname(),
+                        };
+
+                        // The scheduler may have stopped in the meantime, as
+                        // its SectorBuilder was dropped, in which case the
+                        // sector is treated as a stale seal on restart. A
+                        // frozen SectorBuilder waits for its seals' results.
+                        let sent = done_tx.send(SchedulerTask::HandleSealResult(
                             sector_id,
                             sealed_sector_access,
                             sealed_sector_path,
                             result,
-                        ))
-                        .expects(FATAL_SNDRLT);
-                }
-                WorkerTask::Unseal {
-                    porep_config,
-                    source_path,
-                    destination_path,
-                    sector_id,
-                    piece_start_byte,
-                    piece_len,
-                    caller_done_tx,
-                    done_tx,
-                } => {
-                    let result = filecoin_proofs::get_unsealed_range(
+                            timing,
+                            abandoned,
+                        ));
+
+                        if sent.is_err() {
+                            warn!(
+                                "dropping result of sealing sector {}, as the scheduler has stopped",
+                                sector_id
+                            );
+                        }
+                    }
+                    WorkerTask::Unseal {
                         porep_config,
-                        &source_path,
-                        &destination_path,
-                        &prover_id,
+                        source_path,
+                        destination_path,
                         sector_id,
                         piece_start_byte,
                         piece_len,
-                    )
-                    .map(|num_bytes_unsealed| (num_bytes_unsealed, destination_path));
+                        timeout,
+                        cancel,
+                        caller_done_tx,
+                        done_tx,
+                    } => {
+                        let result = {
+                            let destination_path = destination_path.clone();
+
+                            run_cancellable(
+                                TaskKind::Unseal,
+                                Some(sector_id),
+                                timeout,
+                                cancel.as_ref(),
+                                &events,
+                                catch_panics(
+                                    id,
+                                    TaskKind::Unseal,
+                                    Some(sector_id),
+                                    &events,
+                                    move || {
+                                        filecoin_proofs::get_unsealed_range(
+                                            porep_config,
+                                            &source_path,
+                                            &destination_path,
+                                            &prover_id,
+                                            sector_id,
+                                            piece_start_byte,
+                                            piece_len,
+                                        )
+                                    },
+                                ),
+                            )
+                        }
+                        .map(|num_bytes_unsealed| (num_bytes_unsealed, destination_path));
 
-                    done_tx
-                        .send(SchedulerTask::HandleRetrievePieceResult(
+                        // the caller's receiver is dropped along with the
+                        // result if the scheduler has stopped
+                        let _ = done_tx.send(SchedulerTask::HandleRetrievePieceResult(
                             result,
                             caller_done_tx,
-                        ))
-                        .expects(FATAL_SNDRLT);
+                        ));
+                    }
+                    WorkerTask::GeneratePoSt {
+                        proto,
+                        caller_done_tx,
+                    } => {
+                        let PoStTaskPrototype {
+                            challenge_seed,
+                            faults,
+                            index,
+                            post_config,
+                            replicas,
+                            timeout,
+                        } = proto;
+
+                        let sector_ids = replicas.keys().cloned().collect();
+
+                        let result = run_with_timeout(
+                            TaskKind::PoSt,
+                            None,
+                            timeout,
+                            &events,
+                            catch_panics(id, TaskKind::PoSt, None, &events, move || {
+                                filecoin_proofs::generate_post(
+                                    post_config,
+                                    &challenge_seed,
+                                    &replicas,
+                                )
+                            }),
+                        )
+                        .map(|proof| PoStPartition {
+                            index,
+                            sector_ids,
+                            faults,
+                            proof,
+                        });
+
+                        // the caller may have stopped waiting for partitions
+                        let _ = caller_done_tx.send(result);
+                    }
+                    WorkerTask::TransferReplica {
+                        proto,
+                        caller_done_tx,
+                        done_tx,
+                    } => {
+                        let result = {
+                            let proto = proto.clone();
+
+                            catch_panics(
+                                id,
+                                TaskKind::Transfer,
+                                Some(proto.sector_id()),
+                                &events,
+                                move || proto.run(),
+                            )()
+                        };
+
+                        let _ = done_tx.send(SchedulerTask::HandleReplicaTaskResult(
+                            proto,
+                            result,
+                            caller_done_tx,
+                        ));
+                    }
+                    WorkerTask::HydrateIntegrity {
+                        proto,
+                        caller_done_tx,
+                        done_tx,
+                    } => {
+                        let result = {
+                            let proto = proto.clone();
+
+                            catch_panics(
+                                id,
+                                TaskKind::Check,
+                                Some(proto.sector_id),
+                                &events,
+                                move || compute_sector_integrity(&proto),
+                            )()
+                        };
+
+                        let _ = done_tx.send(SchedulerTask::HandleHydrateTaskResult(
+                            proto,
+                            result,
+                            caller_done_tx,
+                        ));
+                    }
+                    WorkerTask::CheckRepairable {
+                        proto,
+                        caller_done_tx,
+                        done_tx,
+                    } => {
+                        let result = {
+                            let proto = proto.clone();
+
+                            catch_panics(
+                                id,
+                                TaskKind::Check,
+                                Some(proto.meta.sector_id),
+                                &events,
+                                move || get_sealed_sector_health(&proto.replica_path, &proto.meta),
+                            )()
+                        };
+
+                        let _ = done_tx.send(SchedulerTask::HandleRepairCheckResult(
+                            proto,
+                            result,
+                            caller_done_tx,
+                        ));
+                    }
+                    WorkerTask::Shutdown => break,
                 }
-                WorkerTask::Shutdown => break,
             }
         });
 
@@ -193,3 +771,87 @@ impl Worker {
         }
     }
 }
+
+// Wraps the task so that a panic while running it fails the task with a
+// WorkerPanicked error instead of unwinding the worker's thread, which would
+// silently shrink the pool and lose the task's result. The worker carries
+// on with the next task, as a restarted one would. Each panic is reported
+// with a WorkerCrashed event.
+fn catch_panics<T, F>(
+    worker_id: usize,
+    task: TaskKind,
+    sector_id: Option<SectorId>,
+    events: &EventBus,
+    f: F,
+) -> impl FnOnce() -> Result<T> + Send + 'static
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let events = events.clone();
+
+    move || match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let msg = panic_message(payload.as_ref());
+
+            warn!(
+                "worker {} panicked running {} task for sector {:?}: {}",
+                worker_id, task, sector_id, msg
+            );
+
+            events.emit(SectorBuilderEvent::WorkerCrashed {
+                worker_id,
+                task,
+                sector_id,
+                msg: msg.clone(),
+            });
+
+            Err(err_worker_panicked(worker_id, task, msg).into())
+        }
+    }
+}
+
+// Extracts the message from the payload of a panic, which is a &str or a
+// String unless the panic was raised with a value of another type.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "panicked with a non-string payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panicking_tasks_fail_and_are_reported() {
+        let events = EventBus::default();
+        let rx = events.subscribe();
+
+        let ok: Result<u64> = catch_panics(3, TaskKind::Seal, None, &events, || Ok(7))();
+        assert_eq!(7, ok.unwrap());
+        assert!(rx.try_recv().is_err());
+
+        let failed: Result<u64> =
+            catch_panics(3, TaskKind::Seal, Some(SectorId::from(9)), &events, || {
+                panic!("sector {} exploded", 9)
+            })();
+
+        let msg = format!("{}", failed.unwrap_err());
+        assert!(msg.contains("sector 9 exploded"), "{}", msg);
+
+        assert_eq!(
+            SectorBuilderEvent::WorkerCrashed {
+                worker_id: 3,
+                task: TaskKind::Seal,
+                sector_id: Some(SectorId::from(9)),
+                msg: "sector 9 exploded".to_string(),
+            },
+            rx.try_recv().unwrap()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+// Invariants of how pieces are packed into staged sectors and of which
+// sectors are picked for sealing, checked against generated piece sizes.
+
+use std::collections::HashSet;
+
+use filecoin_proofs::pieces::sum_piece_bytes_with_alignment;
+use proptest::prelude::*;
+use sector_builder::*;
+use storage_proofs::sector::SectorId;
+
+// the number of user bytes in 1KiB, 2KiB and 4KiB sectors
+fn max_bytes_per_sector() -> impl Strategy<Value = u64> {
+    prop_oneof![Just(1016u64), Just(2032u64), Just(4064u64)]
+}
+
+fn sector_and_piece_sizes() -> impl Strategy<Value = (u64, Vec<u64>)> {
+    max_bytes_per_sector()
+        .prop_flat_map(|max| (Just(max), prop::collection::vec(1..max + 1, 1..40)))
+}
+
+// Adds pieces of the provided sizes to the sectors add_piece places them in,
+// provisioning a new sector whenever a piece fits into none. Returns the
+// sectors ordered by id.
+fn pack(max: u64, piece_sizes: &[u64]) -> Vec<StagedSectorMetadata> {
+    let mut state: StagedState = Default::default();
+
+    for (index, &num_bytes) in piece_sizes.iter().enumerate() {
+        let destination = find_destination_sector_id(
+            &state,
+            UnpaddedBytesAmount(max),
+            UnpaddedBytesAmount(num_bytes),
+        )
+        .unwrap();
+
+        let sector_id = destination.unwrap_or_else(|| {
+            state.sector_id_nonce += 1;
+
+            let sector_id = SectorId::from(state.sector_id_nonce);
+            state.sectors.insert(
+                sector_id,
+                StagedSectorMetadata {
+                    sector_id,
+                    seal_status: SealStatus::Pending,
+                    ..Default::default()
+                },
+            );
+
+            sector_id
+        });
+
+        state
+            .sectors
+            .get_mut(&sector_id)
+            .unwrap()
+            .pieces
+            .push(PieceMetadata::new(
+                format!("{}", index),
+                UnpaddedBytesAmount(num_bytes),
+            ));
+    }
+
+    let mut sectors: Vec<StagedSectorMetadata> = state.sectors.values().cloned().collect();
+    sectors.sort_by_key(|s| s.sector_id);
+    sectors
+}
+
+fn staged_state(sectors: Vec<StagedSectorMetadata>) -> StagedState {
+    StagedState {
+        sector_id_nonce: sectors.len() as u64,
+        sectors: sectors.into_iter().map(|s| (s.sector_id, s)).collect(),
+    }
+}
+
+fn is_full(sector: &StagedSectorMetadata, max: u64) -> bool {
+    let piece_lengths: Vec<_> = sector.pieces.iter().map(|p| p.num_bytes).collect();
+
+    sum_piece_bytes_with_alignment(&piece_lengths) >= UnpaddedBytesAmount(max)
+}
+
+proptest! {
+    #[test]
+    fn sectors_never_overflow((max, piece_sizes) in sector_and_piece_sizes()) {
+        for sector in pack(max, &piece_sizes) {
+            let piece_lengths: Vec<_> = sector.pieces.iter().map(|p| p.num_bytes).collect();
+
+            prop_assert!(sum_piece_bytes_with_alignment(&piece_lengths) <= UnpaddedBytesAmount(max));
+        }
+    }
+
+    #[test]
+    fn every_piece_is_placed_once((max, piece_sizes) in sector_and_piece_sizes()) {
+        let mut keys: Vec<String> = pack(max, &piece_sizes)
+            .into_iter()
+            .flat_map(|s| s.pieces.into_iter().map(|p| p.piece_key))
+            .collect();
+        keys.sort_by_key(|key| key.parse::<usize>().unwrap());
+
+        let expected: Vec<String> = (0..piece_sizes.len()).map(|i| format!("{}", i)).collect();
+
+        prop_assert_eq!(expected, keys);
+    }
+
+    #[test]
+    fn piece_layouts_are_ordered_and_disjoint((max, piece_sizes) in sector_and_piece_sizes()) {
+        let sector_bytes = u64::from(PaddedBytesAmount::from(UnpaddedBytesAmount(max)));
+
+        for sector in pack(max, &piece_sizes) {
+            let layouts = piece_layouts(&sector.pieces);
+
+            // pieces keep the order in which they were added
+            let indices: Vec<usize> =
+                layouts.iter().map(|l| l.piece_key.parse().unwrap()).collect();
+            prop_assert!(indices.windows(2).all(|w| w[0] < w[1]));
+
+            for pair in layouts.windows(2) {
+                prop_assert!(
+                    pair[0].padded_start_byte + u64::from(pair[0].padded_len)
+                        <= pair[1].padded_start_byte
+                );
+            }
+
+            let last = layouts.last().unwrap();
+            prop_assert!(last.padded_start_byte + u64::from(last.padded_len) <= sector_bytes);
+        }
+    }
+
+    #[test]
+    fn oversized_pieces_are_rejected(max in max_bytes_per_sector(), excess in 1u64..4096) {
+        let sectors = pack(max, &[1]);
+
+        prop_assert!(compute_destination_sector_id(
+            &sectors,
+            UnpaddedBytesAmount(max),
+            UnpaddedBytesAmount(max + excess),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn full_sectors_are_always_sealed(
+        (max, piece_sizes) in sector_and_piece_sizes(),
+        max_num_staged_sectors in 0u64..8,
+    ) {
+        let sectors = pack(max, &piece_sizes);
+        let full: HashSet<SectorId> =
+            sectors.iter().filter(|s| is_full(s, max)).map(|s| s.sector_id).collect();
+        let num_not_full = (sectors.len() - full.len()) as u64;
+
+        let ready = get_sectors_ready_for_sealing(
+            &staged_state(sectors),
+            UnpaddedBytesAmount(max),
+            max_num_staged_sectors,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            false,
+        );
+
+        let unique: HashSet<SectorId> = ready.iter().cloned().collect();
+        prop_assert_eq!(unique.len(), ready.len());
+        prop_assert!(full.is_subset(&unique));
+
+        // at most the allowed number of sectors which aren't full stays staged
+        let num_left_staged = num_not_full - (ready.len() - full.len()) as u64;
+        prop_assert_eq!(num_not_full.min(max_num_staged_sectors), num_left_staged);
+    }
+
+    #[test]
+    fn only_pending_sectors_are_sealed(
+        (max, piece_sizes) in sector_and_piece_sizes(),
+        sealing in prop::collection::vec(any::<bool>(), 40),
+    ) {
+        let mut sectors = pack(max, &piece_sizes);
+        for (sector, &is_sealing) in sectors.iter_mut().zip(sealing.iter()) {
+            if is_sealing {
+                sector.seal_status = SealStatus::Sealing;
+            }
+        }
+
+        let pending: HashSet<SectorId> = sectors
+            .iter()
+            .filter(|s| s.seal_status == SealStatus::Pending)
+            .map(|s| s.sector_id)
+            .collect();
+
+        let ready: HashSet<SectorId> = get_sectors_ready_for_sealing(
+            &staged_state(sectors),
+            UnpaddedBytesAmount(max),
+            0,
+            &Default::default(),
+            &SecondsSinceEpoch(0),
+            true,
+        )
+        .into_iter()
+        .collect();
+
+        prop_assert_eq!(pending, ready);
+    }
+}